@@ -0,0 +1,302 @@
+use std::path::Path;
+
+use arrow::record_batch::RecordBatch;
+use arrow_flight::flight_service_client::FlightServiceClient;
+use arrow_flight::{FlightClient, Ticket};
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use reqwest::{Certificate, Identity, Response};
+use serde::{Deserialize, Serialize};
+use tonic::transport::{Channel, ClientTlsConfig};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::error::{ClientError, Result};
+use crate::options::{parse_env, PollOptions};
+
+/// The batches of every result of a query, one result after the other.
+pub type RecordBatchStream = BoxStream<'static, Result<RecordBatch>>;
+
+/// Configuration of a [Client].
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// rest_server of the relay queries are submitted to, e.g. https://localhost:8443
+    pub rest_endpoint: String,
+    /// PEM encoded x509 certificate with which the client authenticates with relays.
+    pub client_cert: Vec<u8>,
+    /// PEM encoded private key of the client certificate.
+    pub client_key: Vec<u8>,
+    /// PEM encoded CA certificate bundle with which relays are verified.
+    pub ca_cert: Vec<u8>,
+    pub poll: PollOptions,
+    /// If true, a query some of whose tasks failed or which did not complete in time returns the
+    /// results which did complete, rather than an error.
+    pub allow_partial: bool,
+}
+
+impl ClientConfig {
+    pub fn new(
+        rest_endpoint: impl Into<String>,
+        client_cert: Vec<u8>,
+        client_key: Vec<u8>,
+        ca_cert: Vec<u8>,
+    ) -> Self {
+        Self {
+            rest_endpoint: rest_endpoint.into(),
+            client_cert,
+            client_key,
+            ca_cert,
+            poll: PollOptions::default(),
+            allow_partial: false,
+        }
+    }
+
+    /// Reads the client certificate, its key and the CA certificate bundle from PEM files.
+    pub fn from_pem_files(
+        rest_endpoint: impl Into<String>,
+        client_cert: impl AsRef<Path>,
+        client_key: impl AsRef<Path>,
+        ca_cert: impl AsRef<Path>,
+    ) -> Result<Self> {
+        let read = |path: &Path| {
+            std::fs::read(path)
+                .map_err(|e| ClientError::Config(format!("Unable to read {}! {e}", path.display())))
+        };
+        Ok(Self::new(
+            rest_endpoint,
+            read(client_cert.as_ref())?,
+            read(client_key.as_ref())?,
+            read(ca_cert.as_ref())?,
+        ))
+    }
+
+    /// Reads the configuration from DATAWEB_REST_ENDPOINT and the PEM files at
+    /// DATAWEB_CLIENT_CERT, DATAWEB_CLIENT_KEY and DATAWEB_CACERT, which are required, along with
+    /// DATAWEB_ALLOW_PARTIAL (default false) and the [PollOptions].
+    pub fn from_env() -> Result<Self> {
+        let required = |var: &str| {
+            std::env::var(var).map_err(|_| ClientError::Config(format!("{var} is not set!")))
+        };
+        let config = Self::from_pem_files(
+            required("DATAWEB_REST_ENDPOINT")?,
+            required("DATAWEB_CLIENT_CERT")?,
+            required("DATAWEB_CLIENT_KEY")?,
+            required("DATAWEB_CACERT")?,
+        )?;
+        Ok(Self {
+            poll: PollOptions::from_env()?,
+            allow_partial: parse_env("DATAWEB_ALLOW_PARTIAL", false)?,
+            ..config
+        })
+    }
+}
+
+/// Status of a submitted query, as reported by the relay it was submitted to.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueryStatus {
+    pub request_id: Uuid,
+    pub message: String,
+    pub complete: usize,
+    pub failed: usize,
+    pub in_progress: usize,
+}
+
+#[derive(Serialize, Debug)]
+struct QueryRequest<'a> {
+    sql: &'a str,
+}
+
+#[derive(Deserialize, Debug)]
+struct SubmitQueryResponse {
+    id: Uuid,
+    #[serde(default)]
+    warnings: Vec<String>,
+}
+
+/// A stored result of a query, as listed by the rest_server.
+#[derive(Deserialize, Debug)]
+struct StoredResultEndpoint {
+    location: String,
+    /// Opaque to the client, and passed back to the relay as is.
+    ticket: serde_json::Value,
+}
+
+impl StoredResultEndpoint {
+    fn ticket(&self) -> Result<Ticket> {
+        let ticket = serde_json::to_vec(&self.ticket)
+            .map_err(|e| ClientError::Relay(format!("Invalid ticket {e}")))?;
+        Ok(Ticket::new(ticket))
+    }
+}
+
+async fn check_response(response: Response) -> Result<Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    Err(ClientError::Relay(format!("{status}: {body}")))
+}
+
+/// Queries the web via a relay. A query is submitted to the rest_server of the relay, its
+/// status is polled until every task completed, and the stored results are then retrieved as
+/// Arrow via Flight from the relay.
+#[derive(Debug, Clone)]
+pub struct Client {
+    config: ClientConfig,
+    rest: reqwest::Client,
+}
+
+impl Client {
+    pub fn new(config: ClientConfig) -> Result<Self> {
+        let identity_pem =
+            [config.client_key.as_slice(), config.client_cert.as_slice()].join(&b'\n');
+        let rest = reqwest::Client::builder()
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(Certificate::from_pem(&config.ca_cert)?)
+            .identity(Identity::from_pem(&identity_pem)?)
+            .build()?;
+        Ok(Self { config, rest })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.config.rest_endpoint.trim_end_matches('/'))
+    }
+
+    /// Executes sql and returns the batches of its results once every task completed.
+    pub async fn query(&self, sql: &str) -> Result<RecordBatchStream> {
+        let request_id = self.submit(sql).await?;
+        self.wait(request_id).await?;
+        self.fetch(request_id).await
+    }
+
+    /// Submits sql without waiting for it to complete, returning the id of the query.
+    pub async fn submit(&self, sql: &str) -> Result<Uuid> {
+        let submitted: SubmitQueryResponse = check_response(
+            self.rest
+                .post(self.url("/query"))
+                .json(&QueryRequest { sql })
+                .send()
+                .await?,
+        )
+        .await?
+        .json()
+        .await?;
+        for warning in &submitted.warnings {
+            tracing::warn!("Query {}: {warning}", submitted.id);
+        }
+        debug!("Submitted query {}", submitted.id);
+        Ok(submitted.id)
+    }
+
+    /// Returns the current status of a query.
+    pub async fn status(&self, request_id: Uuid) -> Result<QueryStatus> {
+        Ok(check_response(
+            self.rest
+                .get(self.url(&format!("/query/{request_id}")))
+                .query(&[("status_only", "true")])
+                .send()
+                .await?,
+        )
+        .await?
+        .json()
+        .await?)
+    }
+
+    /// Polls the status of a query with backoff until none of its tasks are in progress. Fails
+    /// if any task failed or the query did not complete within the timeout, unless partial
+    /// results are allowed, in which case the last status is returned.
+    pub async fn wait(&self, request_id: Uuid) -> Result<QueryStatus> {
+        let poll = &self.config.poll;
+        let mut last_status = None;
+        let wait = async {
+            let mut interval = poll.initial_interval;
+            loop {
+                tokio::time::sleep(interval).await;
+                let status = self.status(request_id).await?;
+                if status.failed > 0 && !self.config.allow_partial {
+                    return Err(ClientError::QueryFailed(status));
+                }
+                if status.in_progress == 0 {
+                    return Ok(status);
+                }
+                debug!(
+                    "Waiting on {} tasks of query {request_id}",
+                    status.in_progress
+                );
+                last_status = Some(status);
+                interval = poll.next_interval(interval);
+            }
+        };
+        let waited = tokio::time::timeout(poll.timeout, wait).await;
+        match (waited, last_status) {
+            (Ok(status), _) => status,
+            (Err(_), Some(status)) if self.config.allow_partial => Ok(status),
+            (Err(_), _) => Err(ClientError::Timeout(request_id, poll.timeout)),
+        }
+    }
+
+    /// Retrieves the results of a query which already completed. If tasks are still in progress
+    /// or failed, only the results which completed are returned.
+    pub async fn fetch(&self, request_id: Uuid) -> Result<RecordBatchStream> {
+        let endpoints: Vec<StoredResultEndpoint> = check_response(
+            self.rest
+                .get(self.url(&format!("/query/{request_id}/tickets")))
+                .send()
+                .await?,
+        )
+        .await?
+        .json()
+        .await?;
+        debug!(
+            "Retrieving {} results of query {request_id}",
+            endpoints.len()
+        );
+
+        let client = self.clone();
+        let batches = futures::stream::iter(endpoints)
+            .then(move |endpoint| {
+                let client = client.clone();
+                async move { client.do_get(endpoint).await }
+            })
+            .try_flatten();
+        Ok(batches.boxed())
+    }
+
+    async fn do_get(&self, endpoint: StoredResultEndpoint) -> Result<RecordBatchStream> {
+        let channel = Channel::from_shared(endpoint.location.clone())
+            .map_err(|e| ClientError::Relay(format!("Invalid location: {e}")))?
+            .tls_config(
+                ClientTlsConfig::new()
+                    .identity(tonic::transport::Identity::from_pem(
+                        &self.config.client_cert,
+                        &self.config.client_key,
+                    ))
+                    .ca_certificate(tonic::transport::Certificate::from_pem(
+                        &self.config.ca_cert,
+                    )),
+            )?
+            .connect()
+            .await?;
+        let mut client = FlightClient::new_from_inner(FlightServiceClient::new(channel));
+        let batches = client.do_get(endpoint.ticket()?).await?;
+        Ok(batches.map_err(ClientError::from).boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StoredResultEndpoint;
+
+    #[test]
+    fn test_stored_result_ticket() {
+        let endpoint: StoredResultEndpoint = serde_json::from_str(
+            r#"{"location": "https://relay:50055", "ticket": {"request_id": "a", "result_id": "b"},
+            "statement": 0, "metadata": {}}"#,
+        )
+        .unwrap();
+        let ticket: serde_json::Value =
+            serde_json::from_slice(&endpoint.ticket().unwrap().ticket).unwrap();
+        assert_eq!(ticket["result_id"], "b");
+    }
+}
@@ -0,0 +1,63 @@
+use std::time::Duration;
+use std::{error::Error, fmt, result};
+
+use uuid::Uuid;
+
+use crate::QueryStatus;
+
+pub type Result<T, E = ClientError> = result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The client is misconfigured, e.g. a certificate could not be read.
+    Config(String),
+    /// A request to a relay could not be sent or its response could not be read.
+    Request(String),
+    /// A relay rejected a request, e.g. because the SQL is invalid.
+    Relay(String),
+    /// Some tasks of the query failed, and partial results were not allowed.
+    QueryFailed(QueryStatus),
+    /// The query did not complete in time, and partial results were not allowed.
+    Timeout(Uuid, Duration),
+    /// Retrieving a result via Arrow Flight failed.
+    Flight(String),
+}
+
+impl Error for ClientError {}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::Config(s) => write!(f, "Invalid client configuration: {s}"),
+            ClientError::Request(s) => write!(f, "Request to relay failed: {s}"),
+            ClientError::Relay(s) => write!(f, "Relay returned an error: {s}"),
+            ClientError::QueryFailed(status) => write!(
+                f,
+                "{} tasks of query {} failed: {}",
+                status.failed, status.request_id, status.message
+            ),
+            ClientError::Timeout(request_id, timeout) => {
+                write!(f, "Query {request_id} did not complete within {timeout:?}")
+            }
+            ClientError::Flight(s) => write!(f, "Retrieving results failed: {s}"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::Request(e.to_string())
+    }
+}
+
+impl From<arrow_flight::error::FlightError> for ClientError {
+    fn from(e: arrow_flight::error::FlightError) -> Self {
+        ClientError::Flight(e.to_string())
+    }
+}
+
+impl From<tonic::transport::Error> for ClientError {
+    fn from(e: tonic::transport::Error) -> Self {
+        ClientError::Flight(e.to_string())
+    }
+}
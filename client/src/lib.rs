@@ -0,0 +1,31 @@
+//! Client library with which applications query the web via a relay, without implementing the
+//! protocol between clients and relays themselves.
+//!
+//! ```no_run
+//! # async fn example() -> dataweb_client::Result<()> {
+//! use dataweb_client::{Client, ClientConfig};
+//! use futures::TryStreamExt;
+//!
+//! let config = ClientConfig::from_pem_files(
+//!     "https://localhost:8443",
+//!     "client_cert.pem",
+//!     "client_key.pem",
+//!     "cacert.pem",
+//! )?;
+//! let client = Client::new(config)?;
+//! let batches = client
+//!     .query("select linenumber from lineitem limit 10")
+//!     .await?
+//!     .try_collect::<Vec<_>>()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+mod error;
+mod options;
+
+pub use client::{Client, ClientConfig, QueryStatus, RecordBatchStream};
+pub use error::{ClientError, Result};
+pub use options::PollOptions;
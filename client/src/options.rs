@@ -0,0 +1,106 @@
+use std::env;
+use std::time::Duration;
+
+use crate::error::{ClientError, Result};
+
+/// How the status of a submitted query is polled until it completes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PollOptions {
+    /// Delay before the status is checked the first time.
+    pub initial_interval: Duration,
+    /// Longest delay between two checks.
+    pub max_interval: Duration,
+    /// Factor by which the delay grows after every check, up to max_interval.
+    pub backoff: f64,
+    /// How long to wait for a submitted query to complete.
+    pub timeout: Duration,
+}
+
+impl Default for PollOptions {
+    fn default() -> Self {
+        Self {
+            initial_interval: Duration::from_millis(250),
+            max_interval: Duration::from_secs(5),
+            backoff: 2.0,
+            timeout: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl PollOptions {
+    /// Reads the options from DATAWEB_POLL_INTERVAL_MS (default 250),
+    /// DATAWEB_MAX_POLL_INTERVAL_MS (default 5000), DATAWEB_POLL_BACKOFF (default 2.0) and
+    /// DATAWEB_QUERY_TIMEOUT_SECS (default 3600).
+    pub fn from_env() -> Result<Self> {
+        let defaults = Self::default();
+        Ok(Self {
+            initial_interval: Duration::from_millis(parse_env(
+                "DATAWEB_POLL_INTERVAL_MS",
+                defaults.initial_interval.as_millis() as u64,
+            )?),
+            max_interval: Duration::from_millis(parse_env(
+                "DATAWEB_MAX_POLL_INTERVAL_MS",
+                defaults.max_interval.as_millis() as u64,
+            )?),
+            backoff: parse_env("DATAWEB_POLL_BACKOFF", defaults.backoff)?,
+            timeout: Duration::from_secs(parse_env(
+                "DATAWEB_QUERY_TIMEOUT_SECS",
+                defaults.timeout.as_secs(),
+            )?),
+        })
+    }
+
+    /// Returns the delay before the check of the status which follows a check after interval.
+    /// A backoff below 1 would shrink the delay, so the status is polled at a constant rate.
+    pub fn next_interval(&self, interval: Duration) -> Duration {
+        interval
+            .mul_f64(self.backoff.max(1.0))
+            .min(self.max_interval)
+    }
+}
+
+pub(crate) fn parse_env<T>(var: &str, default: T) -> Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env::var(var) {
+        Ok(val) => val
+            .parse::<T>()
+            .map_err(|e| ClientError::Config(format!("Unable to parse {var}! {e}"))),
+        Err(_) => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::PollOptions;
+
+    #[test]
+    fn test_poll_intervals() {
+        let options = PollOptions {
+            initial_interval: Duration::from_millis(100),
+            max_interval: Duration::from_millis(500),
+            backoff: 2.0,
+            ..Default::default()
+        };
+        let intervals = std::iter::successors(Some(options.initial_interval), |i| {
+            Some(options.next_interval(*i))
+        });
+        assert_eq!(
+            intervals.take(5).collect::<Vec<_>>(),
+            [100, 200, 400, 500, 500].map(Duration::from_millis)
+        );
+
+        let options = PollOptions {
+            backoff: 0.5,
+            ..options
+        };
+        assert_eq!(
+            options.next_interval(Duration::from_millis(100)),
+            Duration::from_millis(100)
+        );
+    }
+}
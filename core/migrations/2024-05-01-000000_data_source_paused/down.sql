@@ -0,0 +1 @@
+ALTER TABLE data_source DROP COLUMN paused;
@@ -0,0 +1 @@
+ALTER TABLE data_source ADD COLUMN paused BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,2 @@
+ALTER TABLE data_connection DROP COLUMN execution_windows;
+ALTER TABLE query_task DROP COLUMN not_before;
@@ -0,0 +1,2 @@
+ALTER TABLE data_connection ADD COLUMN execution_windows jsonb NOT NULL DEFAULT '[]';
+ALTER TABLE query_task ADD COLUMN not_before TIMESTAMPTZ;
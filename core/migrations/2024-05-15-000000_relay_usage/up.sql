@@ -0,0 +1,10 @@
+CREATE TABLE relay_usage (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    relay_id uuid NOT NULL REFERENCES relays(id),
+    query_task_id uuid NOT NULL REFERENCES query_task(id),
+    rows BIGINT NOT NULL,
+    bytes BIGINT NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX relay_usage_relay_time ON relay_usage (relay_id, recorded_at);
@@ -0,0 +1,2 @@
+DROP TABLE entity_validation;
+ALTER TABLE entities DROP COLUMN validation_query;
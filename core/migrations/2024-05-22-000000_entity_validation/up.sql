@@ -0,0 +1,13 @@
+ALTER TABLE entities ADD COLUMN validation_query VARCHAR;
+
+CREATE TABLE entity_validation (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    entity_id uuid NOT NULL REFERENCES entities(id),
+    data_source_id uuid NOT NULL REFERENCES data_source(id),
+    success BOOLEAN NOT NULL,
+    row_count BIGINT,
+    error VARCHAR,
+    checked_at TIMESTAMPTZ NOT NULL,
+    last_success_at TIMESTAMPTZ,
+    UNIQUE (entity_id, data_source_id)
+);
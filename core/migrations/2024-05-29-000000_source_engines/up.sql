@@ -0,0 +1,2 @@
+ALTER TABLE data_source ADD COLUMN engines jsonb NOT NULL DEFAULT '[]';
+ALTER TABLE query_task ADD COLUMN engine VARCHAR;
@@ -0,0 +1 @@
+ALTER TABLE query_task DROP COLUMN scan_metrics;
@@ -0,0 +1 @@
+ALTER TABLE query_task ADD COLUMN scan_metrics jsonb;
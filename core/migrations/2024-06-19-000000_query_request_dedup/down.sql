@@ -0,0 +1,7 @@
+DROP INDEX query_request_live_originator_request_id;
+ALTER TABLE query_request ADD CONSTRAINT query_request_originator_request_id_key
+    UNIQUE (originator_request_id);
+
+ALTER TABLE query_request DROP COLUMN reexecution_of;
+ALTER TABLE query_request DROP COLUMN retired;
+ALTER TABLE query_request DROP COLUMN received_at;
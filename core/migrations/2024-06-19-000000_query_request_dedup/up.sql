@@ -0,0 +1,9 @@
+ALTER TABLE query_request ADD COLUMN received_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE query_request ADD COLUMN retired BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE query_request ADD COLUMN reexecution_of uuid;
+
+-- Only requests which have not been retired count as duplicates, so an originator_request_id
+-- may be executed again once its previous request falls out of the dedup retention window.
+ALTER TABLE query_request DROP CONSTRAINT query_request_originator_request_id_key;
+CREATE UNIQUE INDEX query_request_live_originator_request_id
+    ON query_request (originator_request_id) WHERE NOT retired;
@@ -0,0 +1,7 @@
+-- Messages about newly created tasks are written to the outbox in the same transaction as the
+-- tasks themselves and deleted once they were published to the message broker.
+CREATE TABLE task_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    message JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
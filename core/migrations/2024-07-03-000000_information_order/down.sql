@@ -0,0 +1,2 @@
+ALTER TABLE information DROP COLUMN nullable;
+ALTER TABLE information DROP COLUMN position;
@@ -0,0 +1,3 @@
+-- Canonical column order and nullability of an entity, used for the schema of combined results.
+ALTER TABLE information ADD COLUMN position INTEGER;
+ALTER TABLE information ADD COLUMN nullable BOOLEAN NOT NULL DEFAULT true;
@@ -0,0 +1,9 @@
+-- Invites issued to peer relays. The secret signs the invite token and never leaves this relay.
+CREATE TABLE relay_invites (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    peer_name VARCHAR NOT NULL,
+    secret BYTEA NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    redeemed_at TIMESTAMPTZ,
+    redeemed_by uuid REFERENCES relays(id) ON DELETE SET NULL
+);
@@ -0,0 +1,2 @@
+DROP TABLE information_usage;
+DROP TABLE entity_usage;
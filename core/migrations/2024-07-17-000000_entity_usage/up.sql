@@ -0,0 +1,13 @@
+-- Counts the queries which accessed each entity and piece of information, so that unused
+-- information and frequently queried entities can be identified.
+CREATE TABLE entity_usage (
+    entity_id uuid PRIMARY KEY REFERENCES entities(id) ON DELETE CASCADE,
+    query_count BIGINT NOT NULL DEFAULT 0,
+    last_accessed_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE information_usage (
+    information_id uuid PRIMARY KEY REFERENCES information(id) ON DELETE CASCADE,
+    query_count BIGINT NOT NULL DEFAULT 0,
+    last_accessed_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,7 @@
+-- Named query policies enforced in addition to the built in SQL validation. The "default" set
+-- applies to every user which is not assigned another set via its attributes.
+CREATE TABLE validation_rule_sets (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR NOT NULL UNIQUE,
+    rules JSONB NOT NULL
+);
@@ -0,0 +1,3 @@
+DROP INDEX query_task_source_status;
+ALTER TABLE query_task DROP COLUMN completed_at;
+ALTER TABLE query_task DROP COLUMN started_at;
@@ -0,0 +1,4 @@
+-- Recorded by the query_runner, so that queue wait and execution latency can be estimated.
+ALTER TABLE query_task ADD COLUMN started_at TIMESTAMPTZ;
+ALTER TABLE query_task ADD COLUMN completed_at TIMESTAMPTZ;
+CREATE INDEX query_task_source_status ON query_task (data_source_id, status);
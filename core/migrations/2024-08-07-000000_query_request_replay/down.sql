@@ -0,0 +1 @@
+ALTER TABLE query_request DROP COLUMN replay_of;
@@ -0,0 +1,3 @@
+-- Set on requests created by replaying an earlier request, whose results are kept apart from
+-- the results delivered to users.
+ALTER TABLE query_request ADD COLUMN replay_of uuid REFERENCES query_request(id) ON DELETE CASCADE;
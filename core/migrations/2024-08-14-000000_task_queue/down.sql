@@ -0,0 +1,3 @@
+DROP TRIGGER task_queue_notify ON task_queue;
+DROP FUNCTION notify_task_queue();
+DROP TABLE task_queue;
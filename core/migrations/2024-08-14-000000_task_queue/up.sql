@@ -0,0 +1,20 @@
+-- Used as the message broker when MSG_BROKER_OPTS is of type Database. Consumers lease a message
+-- by setting locked_until and delete it once acked, so an unacked message is redelivered once
+-- its lease expires.
+CREATE TABLE task_queue (
+    id BIGSERIAL PRIMARY KEY,
+    message JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    locked_until TIMESTAMPTZ
+);
+
+-- Wakes up consumers which LISTEN on the task_queue channel as soon as a message is added.
+CREATE FUNCTION notify_task_queue() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('task_queue', '');
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER task_queue_notify AFTER INSERT ON task_queue
+    FOR EACH STATEMENT EXECUTE PROCEDURE notify_task_queue();
@@ -0,0 +1,2 @@
+DROP INDEX incoming_flight_streams_status_updated_at;
+ALTER TABLE incoming_flight_streams DROP COLUMN updated_at;
@@ -0,0 +1,4 @@
+-- Refreshed while a do_put is writing, so that streams left Started by a crashed flight_server
+-- can be detected and marked Failed.
+ALTER TABLE incoming_flight_streams ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+CREATE INDEX incoming_flight_streams_status_updated_at ON incoming_flight_streams (status, updated_at);
@@ -0,0 +1,13 @@
+-- Query task messages which the query_runner failed to process. A message about a task is
+-- retried until it failed the configured number of times and is then kept here as dead, so
+-- operators can inspect and replay it. A message which could not be parsed has no task_id.
+CREATE TABLE dead_letter (
+    id BIGSERIAL PRIMARY KEY,
+    task_id UUID UNIQUE,
+    message JSONB,
+    error VARCHAR NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 1,
+    dead BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
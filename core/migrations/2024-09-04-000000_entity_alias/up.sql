@@ -0,0 +1,6 @@
+-- Alternative names of an entity, e.g. its name before a rename, which queries may still use.
+CREATE TABLE entity_alias (
+    alias VARCHAR PRIMARY KEY,
+    entity_id UUID NOT NULL REFERENCES entities(id) ON DELETE CASCADE
+);
+CREATE INDEX entity_alias_entity_id ON entity_alias (entity_id);
@@ -0,0 +1,3 @@
+DROP INDEX query_task_status_heartbeat_at;
+ALTER TABLE query_task DROP COLUMN attempts;
+ALTER TABLE query_task DROP COLUMN heartbeat_at;
@@ -0,0 +1,8 @@
+-- Refreshed while a query_runner executes the task, so that tasks left InProgress by a crashed
+-- query_runner can be detected and requeued or failed.
+ALTER TABLE query_task ADD COLUMN heartbeat_at TIMESTAMPTZ;
+-- Number of times a query_runner began executing the task.
+ALTER TABLE query_task ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+UPDATE query_task SET heartbeat_at = COALESCE(started_at, now()), attempts = 1
+    WHERE status = 'in_progress';
+CREATE INDEX query_task_status_heartbeat_at ON query_task (status, heartbeat_at);
@@ -0,0 +1,2 @@
+ALTER TABLE query_task_remote DROP COLUMN error;
+ALTER TABLE query_task_remote DROP COLUMN attempts;
@@ -0,0 +1,3 @@
+-- Number of attempts to submit the task to its relay, and the error of the last failed attempt.
+ALTER TABLE query_task_remote ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE query_task_remote ADD COLUMN error VARCHAR;
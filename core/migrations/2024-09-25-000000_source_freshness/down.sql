@@ -0,0 +1,3 @@
+ALTER TABLE incoming_flight_streams DROP COLUMN freshness;
+ALTER TABLE query_task DROP COLUMN freshness;
+ALTER TABLE data_source DROP COLUMN freshness_query;
@@ -0,0 +1,5 @@
+-- Optional query executed against a source which returns when its data was last updated.
+ALTER TABLE data_source ADD COLUMN freshness_query VARCHAR;
+-- When the data of the source was last updated, as reported when each result was produced.
+ALTER TABLE query_task ADD COLUMN freshness TIMESTAMPTZ;
+ALTER TABLE incoming_flight_streams ADD COLUMN freshness TIMESTAMPTZ;
@@ -0,0 +1,3 @@
+ALTER TABLE query_task_remote DROP COLUMN statement_index;
+ALTER TABLE query_task DROP COLUMN statement_index;
+ALTER TABLE query_request DROP COLUMN statements;
@@ -0,0 +1,5 @@
+-- The ordered statements of a batch request, each executed as its own group of tasks. Empty
+-- for requests with a single statement, which is stored in sql.
+ALTER TABLE query_request ADD COLUMN statements TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE query_task ADD COLUMN statement_index INT NOT NULL DEFAULT 0;
+ALTER TABLE query_task_remote ADD COLUMN statement_index INT NOT NULL DEFAULT 0;
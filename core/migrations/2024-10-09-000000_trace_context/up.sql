@@ -0,0 +1,3 @@
+-- The W3C trace context of the call which created the request, so that tasks executed for the
+-- request later on continue the same trace.
+ALTER TABLE query_request ADD COLUMN trace_context JSONB;
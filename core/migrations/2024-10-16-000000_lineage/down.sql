@@ -0,0 +1 @@
+ALTER TABLE query_request DROP COLUMN lineage_emitted_at;
@@ -0,0 +1,2 @@
+-- Set once an OpenLineage event was emitted for the request, or is being emitted.
+ALTER TABLE query_request ADD COLUMN lineage_emitted_at TIMESTAMPTZ;
@@ -0,0 +1 @@
+DROP INDEX query_request_origin_user_received_at;
@@ -0,0 +1,3 @@
+-- Service accounts are rate limited by counting their recent requests
+CREATE INDEX query_request_origin_user_received_at
+    ON query_request ((origin_info -> 'origin_user' ->> 'x509_sha256'), received_at);
@@ -0,0 +1,17 @@
+-- Who ran which query against which sources with which permissions, and who changed the
+-- configuration of the relay via /admin/apply. Entries are never updated.
+CREATE TYPE audit_action AS ENUM ('query', 'apply');
+
+CREATE TABLE audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    action audit_action NOT NULL,
+    actor_sha256 VARCHAR NOT NULL,
+    actor_subject VARCHAR NOT NULL,
+    query_request_id UUID,
+    sql VARCHAR,
+    detail JSONB NOT NULL
+);
+
+CREATE INDEX audit_log_actor ON audit_log (actor_sha256, id);
+CREATE INDEX audit_log_recorded_at ON audit_log (recorded_at);
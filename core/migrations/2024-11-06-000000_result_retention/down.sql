@@ -0,0 +1,3 @@
+ALTER TABLE incoming_flight_streams DROP COLUMN result_expired_at;
+ALTER TABLE query_task DROP COLUMN result_expired_at;
+ALTER TABLE query_request DROP COLUMN result_ttl_secs;
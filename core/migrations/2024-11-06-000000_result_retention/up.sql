@@ -0,0 +1,6 @@
+-- How long the results of the request are retained after they were produced, NULL to retain
+-- them forever.
+ALTER TABLE query_request ADD COLUMN result_ttl_secs BIGINT;
+-- When the stored result was deleted because its retention elapsed.
+ALTER TABLE query_task ADD COLUMN result_expired_at TIMESTAMPTZ;
+ALTER TABLE incoming_flight_streams ADD COLUMN result_expired_at TIMESTAMPTZ;
@@ -0,0 +1,2 @@
+ALTER TABLE query_request DROP COLUMN result_format;
+DROP TYPE result_format;
@@ -0,0 +1,4 @@
+-- Format the results of the request are stored in, resolved from its hints or the relay default
+-- when it is received.
+CREATE TYPE result_format AS ENUM ('parquet', 'arrow_ipc');
+ALTER TABLE query_request ADD COLUMN result_format result_format NOT NULL DEFAULT 'parquet';
@@ -0,0 +1,3 @@
+DROP INDEX query_request_relay_received_at;
+DROP TABLE quota;
+DROP TYPE quota_subject;
@@ -0,0 +1,14 @@
+-- Limits on the query requests of a user or peer relay, identified by certificate fingerprint.
+-- Usage is counted from query_request, so limits hold across every service of the relay.
+CREATE TYPE quota_subject AS ENUM ('user', 'relay');
+
+CREATE TABLE quota (
+    subject quota_subject NOT NULL,
+    x509_sha256 VARCHAR NOT NULL,
+    max_concurrent_requests INTEGER,
+    max_requests_per_minute INTEGER,
+    PRIMARY KEY (subject, x509_sha256)
+);
+
+-- Requests of peer relays are counted by the relay they were received from
+CREATE INDEX query_request_relay_received_at ON query_request (relay_id, received_at);
@@ -0,0 +1,4 @@
+DROP TABLE relay_role;
+DROP TABLE user_role;
+DROP TABLE role_source_permission;
+DROP TABLE roles;
@@ -0,0 +1,25 @@
+-- Named groups of source permissions which are granted to every member user and relay
+CREATE TABLE roles (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR NOT NULL UNIQUE
+);
+
+CREATE TABLE role_source_permission (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    data_source_id uuid NOT NULL REFERENCES data_source(id),
+    role_id uuid NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+    source_permission jsonb NOT NULL,
+    UNIQUE(data_source_id, role_id)
+);
+
+CREATE TABLE user_role (
+    role_id uuid NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+    user_id uuid NOT NULL REFERENCES users(id),
+    PRIMARY KEY (role_id, user_id)
+);
+
+CREATE TABLE relay_role (
+    role_id uuid NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+    relay_id uuid NOT NULL REFERENCES relays(id),
+    PRIMARY KEY (role_id, relay_id)
+);
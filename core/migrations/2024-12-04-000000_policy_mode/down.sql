@@ -0,0 +1,2 @@
+ALTER TABLE data_source DROP COLUMN policy_mode;
+DROP TYPE policy_mode;
@@ -0,0 +1,5 @@
+-- Permissive sources grant the union of the default and explicit permissions, restrictive
+-- sources only their intersection, denying access without an explicit grant.
+CREATE TYPE policy_mode AS ENUM ('permissive', 'restrictive');
+
+ALTER TABLE data_source ADD COLUMN policy_mode policy_mode NOT NULL DEFAULT 'permissive';
@@ -0,0 +1,55 @@
+-- Values can not be removed from an enum, so audit_action keeps 'delete'.
+ALTER TABLE data_source DROP CONSTRAINT data_source_data_connection_id_fkey,
+    ADD CONSTRAINT data_source_data_connection_id_fkey FOREIGN KEY (data_connection_id) REFERENCES data_connection(id);
+ALTER TABLE data_field DROP CONSTRAINT data_field_data_source_id_fkey,
+    ADD CONSTRAINT data_field_data_source_id_fkey FOREIGN KEY (data_source_id) REFERENCES data_source(id);
+ALTER TABLE field_mappings DROP CONSTRAINT field_mappings_data_field_id_fkey,
+    ADD CONSTRAINT field_mappings_data_field_id_fkey FOREIGN KEY (data_field_id) REFERENCES data_field(id);
+ALTER TABLE field_mappings DROP CONSTRAINT field_mappings_information_id_fkey,
+    ADD CONSTRAINT field_mappings_information_id_fkey FOREIGN KEY (information_id) REFERENCES information(id);
+ALTER TABLE information DROP CONSTRAINT information_entity_id_fkey,
+    ADD CONSTRAINT information_entity_id_fkey FOREIGN KEY (entity_id) REFERENCES entities(id);
+ALTER TABLE default_source_permission DROP CONSTRAINT default_source_permission_data_source_id_fkey,
+    ADD CONSTRAINT default_source_permission_data_source_id_fkey FOREIGN KEY (data_source_id) REFERENCES data_source(id);
+ALTER TABLE relay_source_permission DROP CONSTRAINT relay_source_permission_data_source_id_fkey,
+    ADD CONSTRAINT relay_source_permission_data_source_id_fkey FOREIGN KEY (data_source_id) REFERENCES data_source(id);
+ALTER TABLE relay_source_permission DROP CONSTRAINT relay_source_permission_relay_id_fkey,
+    ADD CONSTRAINT relay_source_permission_relay_id_fkey FOREIGN KEY (relay_id) REFERENCES relays(id);
+ALTER TABLE user_source_permission DROP CONSTRAINT user_source_permission_data_source_id_fkey,
+    ADD CONSTRAINT user_source_permission_data_source_id_fkey FOREIGN KEY (data_source_id) REFERENCES data_source(id);
+ALTER TABLE user_source_permission DROP CONSTRAINT user_source_permission_user_id_fkey,
+    ADD CONSTRAINT user_source_permission_user_id_fkey FOREIGN KEY (user_id) REFERENCES users(id);
+ALTER TABLE role_source_permission DROP CONSTRAINT role_source_permission_data_source_id_fkey,
+    ADD CONSTRAINT role_source_permission_data_source_id_fkey FOREIGN KEY (data_source_id) REFERENCES data_source(id);
+ALTER TABLE user_role DROP CONSTRAINT user_role_user_id_fkey,
+    ADD CONSTRAINT user_role_user_id_fkey FOREIGN KEY (user_id) REFERENCES users(id);
+ALTER TABLE relay_role DROP CONSTRAINT relay_role_relay_id_fkey,
+    ADD CONSTRAINT relay_role_relay_id_fkey FOREIGN KEY (relay_id) REFERENCES relays(id);
+ALTER TABLE remote_entity_mapping DROP CONSTRAINT remote_entity_mapping_relay_id_fkey,
+    ADD CONSTRAINT remote_entity_mapping_relay_id_fkey FOREIGN KEY (relay_id) REFERENCES relays(id);
+ALTER TABLE remote_entity_mapping DROP CONSTRAINT remote_entity_mapping_entity_id_fkey,
+    ADD CONSTRAINT remote_entity_mapping_entity_id_fkey FOREIGN KEY (entity_id) REFERENCES entities(id);
+ALTER TABLE remote_info_mapping DROP CONSTRAINT remote_info_mapping_remote_entity_mapping_id_fkey,
+    ADD CONSTRAINT remote_info_mapping_remote_entity_mapping_id_fkey FOREIGN KEY (remote_entity_mapping_id) REFERENCES remote_entity_mapping(id);
+ALTER TABLE remote_info_mapping DROP CONSTRAINT remote_info_mapping_information_id_fkey,
+    ADD CONSTRAINT remote_info_mapping_information_id_fkey FOREIGN KEY (information_id) REFERENCES information(id);
+ALTER TABLE entity_validation DROP CONSTRAINT entity_validation_entity_id_fkey,
+    ADD CONSTRAINT entity_validation_entity_id_fkey FOREIGN KEY (entity_id) REFERENCES entities(id);
+ALTER TABLE entity_validation DROP CONSTRAINT entity_validation_data_source_id_fkey,
+    ADD CONSTRAINT entity_validation_data_source_id_fkey FOREIGN KEY (data_source_id) REFERENCES data_source(id);
+ALTER TABLE query_request DROP CONSTRAINT query_request_relay_id_fkey,
+    ADD CONSTRAINT query_request_relay_id_fkey FOREIGN KEY (relay_id) REFERENCES relays(id);
+ALTER TABLE query_task DROP CONSTRAINT query_task_query_request_id_fkey,
+    ADD CONSTRAINT query_task_query_request_id_fkey FOREIGN KEY (query_request_id) REFERENCES query_request(id);
+ALTER TABLE query_task DROP CONSTRAINT query_task_data_source_id_fkey,
+    ADD CONSTRAINT query_task_data_source_id_fkey FOREIGN KEY (data_source_id) REFERENCES data_source(id);
+ALTER TABLE query_task_remote DROP CONSTRAINT query_task_remote_query_request_id_fkey,
+    ADD CONSTRAINT query_task_remote_query_request_id_fkey FOREIGN KEY (query_request_id) REFERENCES query_request(id);
+ALTER TABLE query_task_remote DROP CONSTRAINT query_task_remote_relay_id_fkey,
+    ADD CONSTRAINT query_task_remote_relay_id_fkey FOREIGN KEY (relay_id) REFERENCES relays(id);
+ALTER TABLE incoming_flight_streams DROP CONSTRAINT incoming_flight_streams_query_task_remote_id_fkey,
+    ADD CONSTRAINT incoming_flight_streams_query_task_remote_id_fkey FOREIGN KEY (query_task_remote_id) REFERENCES query_task_remote(id);
+ALTER TABLE relay_usage DROP CONSTRAINT relay_usage_relay_id_fkey,
+    ADD CONSTRAINT relay_usage_relay_id_fkey FOREIGN KEY (relay_id) REFERENCES relays(id);
+ALTER TABLE relay_usage DROP CONSTRAINT relay_usage_query_task_id_fkey,
+    ADD CONSTRAINT relay_usage_query_task_id_fkey FOREIGN KEY (query_task_id) REFERENCES query_task(id);
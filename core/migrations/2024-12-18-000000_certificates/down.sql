@@ -0,0 +1,2 @@
+ALTER TABLE users DROP COLUMN x509_cert;
+ALTER TABLE relays DROP COLUMN x509_cert;
@@ -0,0 +1,3 @@
+-- PEM encoded certificates of declared users and relays, so that the configuration can be exported.
+ALTER TABLE users ADD COLUMN x509_cert BYTEA;
+ALTER TABLE relays ADD COLUMN x509_cert BYTEA;
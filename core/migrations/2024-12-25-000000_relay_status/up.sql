@@ -0,0 +1,12 @@
+-- Reachability of each peer relay as last probed by a query_runner. The last_seen_at columns
+-- keep the time each endpoint was last reachable while it fails.
+CREATE TABLE relay_status (
+    relay_id uuid PRIMARY KEY REFERENCES relays(id) ON DELETE CASCADE,
+    checked_at TIMESTAMPTZ NOT NULL,
+    rest_latency_ms BIGINT,
+    rest_error VARCHAR,
+    rest_last_seen_at TIMESTAMPTZ,
+    flight_latency_ms BIGINT,
+    flight_error VARCHAR,
+    flight_last_seen_at TIMESTAMPTZ
+);
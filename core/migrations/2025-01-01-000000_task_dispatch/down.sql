@@ -0,0 +1,4 @@
+ALTER TABLE query_task_remote DROP COLUMN dispatched_at;
+ALTER TABLE query_task DROP COLUMN dispatched_at;
+DROP INDEX task_outbox_task_id_idx;
+ALTER TABLE task_outbox DROP COLUMN task_id;
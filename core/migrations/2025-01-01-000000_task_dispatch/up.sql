@@ -0,0 +1,10 @@
+-- The task each outbox message is about, so that tasks without a pending message are found
+-- with an anti-join, and when the message of each task was last published.
+ALTER TABLE task_outbox ADD COLUMN task_id uuid;
+UPDATE task_outbox SET task_id = COALESCE(
+    message->'LocalQueryTask'->>'id',
+    message->'RemoteQueryTask'->>'id'
+)::uuid;
+CREATE INDEX task_outbox_task_id_idx ON task_outbox (task_id);
+ALTER TABLE query_task ADD COLUMN dispatched_at TIMESTAMPTZ;
+ALTER TABLE query_task_remote ADD COLUMN dispatched_at TIMESTAMPTZ;
@@ -0,0 +1 @@
+DROP TABLE scratch_dataset;
@@ -0,0 +1,9 @@
+-- Datasets uploaded by users, which are deleted along with their connection and entity once
+-- they expire. user_id is not a foreign key since the uploaded file outlives a deleted user.
+CREATE TABLE scratch_dataset (
+    data_connection_id uuid PRIMARY KEY REFERENCES data_connection(id) ON DELETE CASCADE,
+    user_id uuid NOT NULL,
+    dataset_name varchar NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX scratch_dataset_expires_at_idx ON scratch_dataset (expires_at);
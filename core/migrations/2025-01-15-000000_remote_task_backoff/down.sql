@@ -0,0 +1 @@
+ALTER TABLE query_task_remote DROP COLUMN not_before;
@@ -0,0 +1,2 @@
+-- When a remote task whose submission failed is retried, see release_deferred_tasks.
+ALTER TABLE query_task_remote ADD COLUMN not_before TIMESTAMPTZ;
@@ -1,8 +1,12 @@
 use crate::{
-    error::Result, messaging::MessageBrokerOptions,
-    model::data_stores::options::SupportedObjectStore,
+    error::Result,
+    messaging::MessageBrokerOptions,
+    model::data_stores::options::{
+        file_directory::{ObjectStoreClientConfig, TenantResultStore},
+        SupportedObjectStore,
+    },
 };
-use std::{env, io::Read};
+use std::{collections::HashMap, env, io::Read};
 
 /// Initializes and Holds envrionment variable settings which
 /// control system behavior. Panics if any required setting
@@ -12,11 +16,21 @@ pub struct EnvConfigSettings {
     pub relay_name: String,
     pub rest_url: String,
     pub rest_port: String,
+    /// If set, the /admin routes are only served on this port, bound at admin_rest_url, and not
+    /// on rest_port.
+    pub admin_rest_port: Option<String>,
+    pub admin_rest_url: String,
+    /// If true, the admin listener rejects TLS handshakes from clients without a trusted
+    /// certificate, rather than only rejecting their requests. Requires direct_tls.
+    pub admin_require_client_cert: bool,
     pub flight_addr: String,
     pub ca_cert_file: String,
     pub direct_tls: bool,
     pub server_cert_file: String,
     pub server_key_file: String,
+    /// Comma separated names of the headers in which a TLS terminating proxy passes the client's
+    /// certificate, in order of precedence. An x-forwarded-client-cert header is parsed in Envoy's
+    /// format, other headers must contain a urlencoded PEM.
     pub client_cert_header: Option<String>,
     pub client_cert_file: String,
     pub client_key_file: String,
@@ -26,6 +40,10 @@ pub struct EnvConfigSettings {
     pub result_bucket: Option<String>,
     pub result_region: Option<String>,
     pub result_prefix: Option<String>,
+    pub result_client_config: ObjectStoreClientConfig,
+    /// Result stores used instead of the default one for results received from the peer
+    /// relays with these names.
+    pub result_tenant_stores: HashMap<String, TenantResultStore>,
 }
 
 impl EnvConfigSettings {
@@ -33,6 +51,12 @@ impl EnvConfigSettings {
         let relay_name = env::var("RELAY_NAME").expect("RELAY_NAME must be set");
         let rest_url = env::var("REST_SERVICE_URL").expect("REST_SERVICE_URL must be set");
         let rest_port = env::var("REST_SERVICE_PORT").expect("REST_SERVICE_PORT must be set");
+        let admin_rest_port = env::var("ADMIN_REST_SERVICE_PORT").ok();
+        let admin_rest_url = env::var("ADMIN_REST_SERVICE_URL").unwrap_or(rest_url.clone());
+        let admin_require_client_cert = env::var("ADMIN_REQUIRE_CLIENT_CERT")
+            .unwrap_or("false".to_string())
+            .parse::<bool>()
+            .expect("Unable to parse ADMIN_REQUIRE_CLIENT_CERT configuration as boolean!");
         let result_prefix = env::var("RESULT_SOURCE_PFX").ok();
         let result_bucket = env::var("RESULT_SOURCE_BUCKET").ok();
         let result_region = env::var("RESULT_SOURCE_REGION").ok();
@@ -40,6 +64,13 @@ impl EnvConfigSettings {
             .expect("RESULT_SOURCE_OBJECT_STORE must be set")
             .try_into()
             .expect("RESULT_SOURCE_OBJECT_STORE is invalid!");
+        let result_client_config = ObjectStoreClientConfig::from_env("RESULT_OBJECT_STORE")
+            .expect("RESULT_OBJECT_STORE client configuration is invalid!");
+        let result_tenant_stores = match env::var("RESULT_TENANT_STORES") {
+            Ok(stores) => serde_json::from_str(&stores)
+                .expect("RESULT_TENANT_STORES could not be parsed as json"),
+            Err(_) => HashMap::new(),
+        };
         let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
         let flight_addr =
             env::var("FLIGHT_SERVICE_ENDPOINT").expect("FLIGHT_SERVICE_ENDPOINT must be set");
@@ -52,6 +83,11 @@ impl EnvConfigSettings {
             .parse::<bool>()
             .expect("Unable to parse DIRECT_TLS configuration as boolean!");
 
+        assert!(
+            direct_tls || !admin_require_client_cert,
+            "ADMIN_REQUIRE_CLIENT_CERT can only be enforced when DIRECT_TLS is true!"
+        );
+
         let (server_key_file, server_cert_file, client_cert_header) = if direct_tls {
             (
                 env::var("SERVER_KEY_FILE")
@@ -82,6 +118,9 @@ impl EnvConfigSettings {
             relay_name,
             rest_url,
             rest_port,
+            admin_rest_port,
+            admin_rest_url,
+            admin_require_client_cert,
             flight_addr,
             ca_cert_file,
             direct_tls,
@@ -96,6 +135,8 @@ impl EnvConfigSettings {
             result_bucket,
             result_region,
             result_prefix,
+            result_client_config,
+            result_tenant_stores,
         }
     }
 
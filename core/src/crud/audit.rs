@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use crate::error::Result;
+use crate::model::audit::{AuditAction, AuditEntry, AuditFilter, AuditPage, NewAuditEntry};
+use crate::model::query::{QueryRequest, QueryTask};
+use crate::model::user::User;
+
+use crate::schema;
+use diesel::{insert_into, prelude::*};
+use diesel_async::RunQueryDsl;
+use serde_json::json;
+use uuid::Uuid;
+
+use super::PgDb;
+
+impl<'a> PgDb<'a> {
+    pub async fn record_audit_entry(&mut self, val: &NewAuditEntry) -> Result<()> {
+        use schema::audit_log::dsl::*;
+        insert_into(audit_log)
+            .values(val)
+            .execute(&mut self.con)
+            .await?;
+        Ok(())
+    }
+
+    /// Records that user ran request, querying the sources of its local tasks with the
+    /// permissions which were resolved for each of them.
+    pub async fn record_query_audit(
+        &mut self,
+        request: &QueryRequest,
+        user: &User,
+        tasks: &[QueryTask],
+    ) -> Result<()> {
+        use schema::data_connection::dsl as con;
+        use schema::data_source::dsl as source;
+        let source_ids: Vec<Uuid> = tasks.iter().map(|t| t.data_source_id).collect();
+        let names: HashMap<Uuid, (String, String)> = source::data_source
+            .inner_join(con::data_connection)
+            .filter(source::id.eq_any(source_ids))
+            .select((source::id, con::name, source::name))
+            .load::<(Uuid, String, String)>(&mut self.con)
+            .await?
+            .into_iter()
+            .map(|(id, con_name, source_name)| (id, (con_name, source_name)))
+            .collect();
+        let sources = tasks
+            .iter()
+            .map(|t| {
+                let (con_name, source_name) =
+                    names.get(&t.data_source_id).cloned().unwrap_or_default();
+                json!({
+                    "data_connection": con_name,
+                    "data_source": source_name,
+                    "statement_index": t.statement_index,
+                    "permission": t.permission,
+                })
+            })
+            .collect::<Vec<_>>();
+        let origin_relay = request
+            .origin_info
+            .origin_relay
+            .as_ref()
+            .map(|r| &r.x509_sha256);
+        self.record_audit_entry(&NewAuditEntry {
+            action: AuditAction::Query,
+            actor_sha256: user.x509_sha256.clone(),
+            actor_subject: user.x509_subject.clone(),
+            query_request_id: Some(request.id),
+            sql: Some(request.sql.clone()),
+            detail: json!({ "sources": sources, "origin_relay": origin_relay }),
+        })
+        .await
+    }
+
+    /// Returns up to limit [AuditEntry]s matching filter, newest first.
+    pub async fn get_audit_page(&mut self, filter: &AuditFilter, limit: i64) -> Result<AuditPage> {
+        use schema::audit_log::dsl::*;
+        let mut query = audit_log.select(AuditEntry::as_select()).into_boxed();
+        if let Some(before_id) = filter.before_id {
+            query = query.filter(id.lt(before_id));
+        }
+        if let Some(actor) = &filter.actor {
+            query = query.filter(actor_sha256.eq(actor));
+        }
+        if let Some(action_val) = filter.action {
+            query = query.filter(action.eq(action_val));
+        }
+        if let Some(since) = filter.since {
+            query = query.filter(recorded_at.ge(since));
+        }
+        if let Some(until) = filter.until {
+            query = query.filter(recorded_at.lt(until));
+        }
+        // One more than a page tells whether there is a next page
+        let mut entries: Vec<AuditEntry> = query
+            .order(id.desc())
+            .limit(limit + 1)
+            .load(&mut self.con)
+            .await?;
+        let next_before_id = if entries.len() as i64 > limit {
+            entries.truncate(limit as usize);
+            entries.last().map(|e| e.id)
+        } else {
+            None
+        };
+        Ok(AuditPage {
+            entries,
+            next_before_id,
+        })
+    }
+}
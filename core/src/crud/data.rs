@@ -1,15 +1,19 @@
-use crate::error::Result;
+use crate::error::{MeshError, Result};
 use crate::model::access_control::{DefaultSourcePermission, SourcePermission};
 use crate::model::data_stores::{
-    options::ConnectionOptions, DataConnection, DataField, DataSource, NewDataField, NewDataSource,
+    options::ConnectionOptions, schedule::ExecutionWindows, DataConnection, DataField, DataSource,
+    NewDataField, NewDataSource, ScratchDataset,
 };
 
 use crate::schema::{self};
-use diesel::{insert_into, prelude::*};
-use diesel_async::RunQueryDsl;
+use chrono::{DateTime, Utc};
+use diesel::{delete, dsl::exists, insert_into, prelude::*, select, update, upsert::excluded};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
 use uuid::Uuid;
 
-use super::PgDb;
+use super::utils::dedup_last_by_key;
+use super::{PgDb, UPSERT_BATCH_SIZE};
 
 impl<'a> PgDb<'a> {
     pub async fn create_connection(
@@ -35,20 +39,44 @@ impl<'a> PgDb<'a> {
             .await?)
     }
 
+    /// Returns every [DataConnection] ordered by name.
+    pub async fn get_connections(&mut self) -> Result<Vec<DataConnection>> {
+        use schema::data_connection::dsl::*;
+        Ok(data_connection
+            .order(name.asc())
+            .get_results(&mut self.con)
+            .await?)
+    }
+
+    /// Returns every [DataSource] ordered by name.
+    pub async fn get_sources(&mut self) -> Result<Vec<DataSource>> {
+        use schema::data_source::dsl::*;
+        Ok(data_source
+            .order(name.asc())
+            .select(DataSource::as_select())
+            .get_results(&mut self.con)
+            .await?)
+    }
+
     pub async fn upsert_connection(
         &mut self,
         name_val: &str,
         connection_options_val: ConnectionOptions,
+        execution_windows_val: ExecutionWindows,
     ) -> Result<DataConnection> {
         use schema::data_connection::dsl::*;
         insert_into(data_connection)
             .values((
                 name.eq(name_val),
                 connection_options.eq(&connection_options_val),
+                execution_windows.eq(&execution_windows_val),
             ))
             .on_conflict(name)
             .do_update()
-            .set(connection_options.eq(&connection_options_val))
+            .set((
+                connection_options.eq(&connection_options_val),
+                execution_windows.eq(&execution_windows_val),
+            ))
             .execute(&mut self.con)
             .await?;
         self.get_connection(name_val).await
@@ -77,6 +105,21 @@ impl<'a> PgDb<'a> {
             .await?)
     }
 
+    /// Gets a [DataSource] by id along with the [DataConnection] it belongs to.
+    pub async fn get_source_by_id(
+        &mut self,
+        id_val: &Uuid,
+    ) -> Result<(DataConnection, DataSource)> {
+        use schema::data_connection::dsl as con;
+        use schema::data_source::dsl::*;
+        Ok(data_source
+            .inner_join(con::data_connection)
+            .select((DataConnection::as_select(), DataSource::as_select()))
+            .filter(id.eq(id_val))
+            .get_result(&mut self.con)
+            .await?)
+    }
+
     pub async fn upsert_source(&mut self, val: &NewDataSource) -> Result<DataSource> {
         use schema::data_source::dsl::*;
         insert_into(data_source)
@@ -89,6 +132,23 @@ impl<'a> PgDb<'a> {
         self.get_source(&val.name, &val.data_connection_id).await
     }
 
+    /// Sets the paused flag on the [DataSource] identified by its name and the name of its
+    /// [DataConnection], returning the updated [DataSource].
+    pub async fn set_source_paused(
+        &mut self,
+        connection_name_val: &str,
+        source_name_val: &str,
+        paused_val: bool,
+    ) -> Result<DataSource> {
+        use schema::data_source::dsl::*;
+        let con = self.get_connection(connection_name_val).await?;
+        Ok(update(data_source)
+            .filter(name.eq(source_name_val).and(data_connection_id.eq(con.id)))
+            .set(paused.eq(paused_val))
+            .get_result(&mut self.con)
+            .await?)
+    }
+
     pub async fn create_field(&mut self, vals: &Vec<NewDataField>) -> Result<Vec<DataField>> {
         use schema::data_field::dsl::*;
         Ok(insert_into(data_field)
@@ -121,6 +181,42 @@ impl<'a> PgDb<'a> {
         Ok(())
     }
 
+    /// Upserts many [DataField]s with batched statements, returning the upserted fields.
+    pub async fn upsert_fields(&mut self, vals: &[NewDataField]) -> Result<Vec<DataField>> {
+        use schema::data_field::dsl::*;
+        let vals = dedup_last_by_key(vals, |f| (f.data_source_id, f.name.clone()));
+        let mut out = Vec::with_capacity(vals.len());
+        for chunk in vals.chunks(UPSERT_BATCH_SIZE) {
+            let fields: Vec<DataField> = insert_into(data_field)
+                .values(chunk.to_vec())
+                .on_conflict((data_source_id, name))
+                .do_update()
+                .set(path.eq(excluded(path)))
+                .get_results(&mut self.con)
+                .await?;
+            out.extend(fields);
+        }
+        Ok(out)
+    }
+
+    /// Get all [DataField]s of a [DataSource] based on its id
+    pub async fn get_fields_for_source(&mut self, source_id_val: &Uuid) -> Result<Vec<DataField>> {
+        use schema::data_field::dsl::*;
+        Ok(data_field
+            .filter(data_source_id.eq(source_id_val))
+            .get_results(&mut self.con)
+            .await?)
+    }
+
+    /// Get the [DataField]s of every [DataSource]
+    pub async fn get_all_fields(&mut self) -> Result<Vec<DataField>> {
+        use schema::data_field::dsl::*;
+        Ok(data_field
+            .order(name.asc())
+            .get_results(&mut self.con)
+            .await?)
+    }
+
     pub async fn upsert_default_source_permission(
         &mut self,
         data_source_id_val: &Uuid,
@@ -141,6 +237,11 @@ impl<'a> PgDb<'a> {
         Ok(())
     }
 
+    pub async fn get_default_source_permissions(&mut self) -> Result<Vec<DefaultSourcePermission>> {
+        use schema::default_source_permission::dsl::*;
+        Ok(default_source_permission.get_results(&mut self.con).await?)
+    }
+
     pub async fn get_default_source_permission(
         &mut self,
         source_id_val: &Uuid,
@@ -151,4 +252,111 @@ impl<'a> PgDb<'a> {
             .get_result(&mut self.con)
             .await?)
     }
+
+    /// Deletes the [DataSource] named name_val of the [DataConnection] with id
+    /// data_connection_id_val, along with its fields, mappings, permissions and query tasks.
+    /// Returns the number of deleted sources.
+    pub async fn delete_source(
+        &mut self,
+        data_connection_id_val: &Uuid,
+        name_val: &str,
+    ) -> Result<usize> {
+        use schema::data_source::dsl::*;
+        Ok(delete(
+            data_source.filter(
+                data_connection_id
+                    .eq(data_connection_id_val)
+                    .and(name.eq(name_val)),
+            ),
+        )
+        .execute(&mut self.con)
+        .await?)
+    }
+
+    /// Deletes the [DataConnection] with id id_val if it has no [DataSource]s left, returning the
+    /// number of deleted connections.
+    pub async fn delete_connection_if_empty(&mut self, id_val: &Uuid) -> Result<usize> {
+        use schema::data_connection::dsl::*;
+        use schema::data_source::dsl as source;
+        let has_sources: bool = select(exists(
+            source::data_source.filter(source::data_connection_id.eq(id_val)),
+        ))
+        .get_result(&mut self.con)
+        .await?;
+        if has_sources {
+            return Ok(0);
+        }
+        Ok(delete(data_connection.filter(id.eq(id_val)))
+            .execute(&mut self.con)
+            .await?)
+    }
+    /// Records the [ScratchDataset] of an upload, replacing the expiry of a previous upload of
+    /// the same dataset.
+    pub async fn upsert_scratch_dataset(&mut self, dataset: &ScratchDataset) -> Result<()> {
+        use schema::scratch_dataset::dsl::*;
+        insert_into(scratch_dataset)
+            .values(dataset)
+            .on_conflict(data_connection_id)
+            .do_update()
+            .set(expires_at.eq(excluded(expires_at)))
+            .execute(&mut self.con)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns up to limit [ScratchDataset]s which expired before now.
+    pub async fn get_expired_scratch_datasets(
+        &mut self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ScratchDataset>> {
+        use schema::scratch_dataset::dsl::*;
+        Ok(scratch_dataset
+            .filter(expires_at.lt(now))
+            .order(expires_at)
+            .limit(limit)
+            .select(ScratchDataset::as_select())
+            .load(&mut self.con)
+            .await?)
+    }
+
+    /// Deletes a [ScratchDataset] along with its [DataConnection] and [DataSource], and the
+    /// scratch [Entity][crate::model::entity::Entity] named entity_name which it is mapped to.
+    /// Returns the number of deleted datasets, which is 0 if it was uploaded again since it was
+    /// looked up and so no longer expired.
+    pub async fn delete_scratch_dataset(
+        &mut self,
+        dataset: &ScratchDataset,
+        entity_name: &str,
+    ) -> Result<usize> {
+        use schema::data_connection::dsl as con;
+        use schema::entities::dsl as entity;
+        use schema::scratch_dataset::dsl::*;
+        let data_connection_id_val = dataset.data_connection_id;
+        let expires_at_val = dataset.expires_at;
+        (*self.con)
+            .transaction::<_, MeshError, _>(|c| {
+                async move {
+                    let deleted = delete(
+                        scratch_dataset
+                            .filter(data_connection_id.eq(data_connection_id_val))
+                            .filter(expires_at.eq(expires_at_val)),
+                    )
+                    .execute(c)
+                    .await?;
+                    if deleted == 0 {
+                        return Ok(0);
+                    }
+                    delete(con::data_connection.filter(con::id.eq(data_connection_id_val)))
+                        .execute(c)
+                        .await?;
+                    delete(entity::entities.filter(entity::name.eq(entity_name)))
+                        .execute(c)
+                        .await?;
+                    Ok(deleted)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
 }
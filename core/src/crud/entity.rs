@@ -1,14 +1,17 @@
 use std::collections::HashMap;
 
-use crate::model::entity::{Information, NewInformation};
+use crate::error::MeshError;
+use crate::model::entity::{EntityValidation, Information, NewEntityValidation, NewInformation};
 use crate::{error::Result, model::entity::Entity};
 
 use crate::schema;
-use diesel::{insert_into, prelude::*};
-use diesel_async::RunQueryDsl;
+use diesel::{delete, insert_into, prelude::*, update, upsert::excluded};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
 use uuid::Uuid;
 
-use super::PgDb;
+use super::utils::dedup_last_by_key;
+use super::{PgDb, UPSERT_BATCH_SIZE};
 
 impl<'a> PgDb<'a> {
     pub async fn create_entity(&mut self, name_val: &str) -> Result<Entity> {
@@ -41,6 +44,159 @@ impl<'a> PgDb<'a> {
             .await?)
     }
 
+    /// Returns every [Entity] ordered by name.
+    pub async fn get_entities(&mut self) -> Result<Vec<Entity>> {
+        use schema::entities::dsl::*;
+        Ok(entities
+            .order(name.asc())
+            .select(Entity::as_select())
+            .get_results(&mut self.con)
+            .await?)
+    }
+
+    /// Returns the aliases of every [Entity], keyed by the id of the [Entity].
+    pub async fn get_all_entity_aliases(&mut self) -> Result<HashMap<Uuid, Vec<String>>> {
+        use schema::entity_alias::dsl::*;
+        let rows: Vec<(String, Uuid)> = entity_alias
+            .order(alias.asc())
+            .select((alias, entity_id))
+            .get_results(&mut self.con)
+            .await?;
+        let mut out: HashMap<Uuid, Vec<String>> = HashMap::new();
+        for (a, e) in rows {
+            out.entry(e).or_default().push(a);
+        }
+        Ok(out)
+    }
+
+    /// Returns the [Entity] which declares alias_val as one of its aliases, if any.
+    pub async fn get_entity_by_alias(&mut self, alias_val: &str) -> Result<Option<Entity>> {
+        use schema::entities::dsl as entity;
+        use schema::entity_alias::dsl as alias;
+        Ok(alias::entity_alias
+            .inner_join(entity::entities)
+            .filter(alias::alias.eq(alias_val))
+            .select(Entity::as_select())
+            .first(&mut self.con)
+            .await
+            .optional()?)
+    }
+
+    /// Replaces the aliases of the [Entity] with id entity_id_val. Fails if an alias is the name
+    /// of an [Entity] or an alias of another [Entity], since it would be ambiguous.
+    pub async fn set_entity_aliases(
+        &mut self,
+        entity_id_val: &Uuid,
+        aliases: &[String],
+    ) -> Result<()> {
+        use schema::entities::dsl as entity;
+        use schema::entity_alias::dsl as alias;
+        let entity_id_val = *entity_id_val;
+        let mut aliases = aliases.to_vec();
+        aliases.sort();
+        aliases.dedup();
+        (*self.con)
+            .transaction::<_, MeshError, _>(|con| {
+                async move {
+                    let names: Vec<String> = entity::entities
+                        .filter(entity::name.eq_any(&aliases))
+                        .select(entity::name)
+                        .load(con)
+                        .await?;
+                    if !names.is_empty() {
+                        return Err(MeshError::InvalidQuery(format!(
+                            "Aliases {names:?} are already names of entities!"
+                        )));
+                    }
+                    delete(alias::entity_alias.filter(alias::entity_id.eq(entity_id_val)))
+                        .execute(con)
+                        .await?;
+                    let vals = aliases
+                        .iter()
+                        .map(|a| (alias::alias.eq(a), alias::entity_id.eq(entity_id_val)))
+                        .collect::<Vec<_>>();
+                    let inserted = insert_into(alias::entity_alias)
+                        .values(&vals)
+                        .on_conflict_do_nothing()
+                        .execute(con)
+                        .await?;
+                    if inserted != vals.len() {
+                        return Err(MeshError::InvalidQuery(format!(
+                            "Aliases {aliases:?} include an alias of another entity!"
+                        )));
+                    }
+                    Ok(())
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    pub async fn set_entity_validation_query(
+        &mut self,
+        id_val: &Uuid,
+        validation_query_val: Option<&str>,
+    ) -> Result<()> {
+        use schema::entities::dsl::*;
+        update(entities)
+            .filter(id.eq(id_val))
+            .set(validation_query.eq(validation_query_val))
+            .execute(&mut self.con)
+            .await?;
+        Ok(())
+    }
+
+    /// Gets all [Entity]s which declare a validation_query
+    pub async fn get_entities_with_validation_query(&mut self) -> Result<Vec<Entity>> {
+        use schema::entities::dsl::*;
+        Ok(entities
+            .filter(validation_query.is_not_null())
+            .get_results(&mut self.con)
+            .await?)
+    }
+
+    /// Records the result of a validation check, updating last_success_at only if it succeeded.
+    /// Returns the previously recorded result for the same [Entity] and source, if any.
+    pub async fn record_entity_validation(
+        &mut self,
+        val: &NewEntityValidation,
+    ) -> Result<Option<EntityValidation>> {
+        use schema::entity_validation::dsl::*;
+        let previous = entity_validation
+            .filter(
+                entity_id
+                    .eq(val.entity_id)
+                    .and(data_source_id.eq(val.data_source_id)),
+            )
+            .get_result::<EntityValidation>(&mut self.con)
+            .await
+            .optional()?;
+        let last_success = if val.success {
+            Some(val.checked_at)
+        } else {
+            previous.as_ref().and_then(|p| p.last_success_at)
+        };
+        insert_into(entity_validation)
+            .values((val, last_success_at.eq(last_success)))
+            .on_conflict((entity_id, data_source_id))
+            .do_update()
+            .set((val, last_success_at.eq(last_success)))
+            .execute(&mut self.con)
+            .await?;
+        Ok(previous)
+    }
+
+    pub async fn get_entity_validations(
+        &mut self,
+        entity_id_val: &Uuid,
+    ) -> Result<Vec<EntityValidation>> {
+        use schema::entity_validation::dsl::*;
+        Ok(entity_validation
+            .filter(entity_id.eq(entity_id_val))
+            .get_results(&mut self.con)
+            .await?)
+    }
+
     pub async fn create_information(
         &mut self,
         vals: &Vec<NewInformation>,
@@ -64,7 +220,27 @@ impl<'a> PgDb<'a> {
         Ok(())
     }
 
-    /// Get relevant information for a given [Entity] based on its id
+    /// Upserts many [Information] with batched statements.
+    pub async fn upsert_information_batch(&mut self, vals: &[NewInformation]) -> Result<()> {
+        use schema::information::dsl::*;
+        let vals = dedup_last_by_key(vals, |i| (i.entity_id, i.name.clone()));
+        for chunk in vals.chunks(UPSERT_BATCH_SIZE) {
+            insert_into(information)
+                .values(chunk.to_vec())
+                .on_conflict((entity_id, name))
+                .do_update()
+                .set((
+                    arrow_dtype.eq(excluded(arrow_dtype)),
+                    position.eq(excluded(position)),
+                    nullable.eq(excluded(nullable)),
+                ))
+                .execute(&mut self.con)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Get relevant information for a given [Entity] based on its id, in canonical column order
     pub async fn get_information_for_entity(
         &mut self,
         entity_id_val: Uuid,
@@ -72,6 +248,7 @@ impl<'a> PgDb<'a> {
         use schema::information::dsl::*;
         Ok(information
             .filter(entity_id.eq(entity_id_val))
+            .order((position.asc().nulls_last(), name.asc()))
             .select(Information::as_select())
             .get_results(&mut self.con)
             .await?)
@@ -95,6 +272,10 @@ impl<'a> PgDb<'a> {
 
         let rows: Vec<(Entity, Information)> = information::information
             .inner_join(entity::entities)
+            .order((
+                information::position.asc().nulls_last(),
+                information::name.asc(),
+            ))
             .select((Entity::as_select(), Information::as_select()))
             .get_results(&mut self.con)
             .await?;
@@ -111,4 +292,13 @@ impl<'a> PgDb<'a> {
 
         Ok(out)
     }
+
+    /// Deletes the [Entity] named name_val along with its information, aliases and mappings,
+    /// returning the number of deleted entities.
+    pub async fn delete_entity(&mut self, name_val: &str) -> Result<usize> {
+        use schema::entities::dsl::*;
+        Ok(delete(entities.filter(name.eq(name_val)))
+            .execute(&mut self.con)
+            .await?)
+    }
 }
@@ -0,0 +1,78 @@
+use diesel::dsl::sql;
+use diesel::sql_types::{BigInt, Bool};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+
+use crate::error::Result;
+
+/// A Postgres session level advisory lock, which elects the one replica running a background task
+/// that must not run on every replica at once. The lock is held on a connection of its own rather
+/// than one borrowed from a pool, and is released when that connection is lost or this is dropped.
+pub struct LeaderLock {
+    db_url: String,
+    key: i64,
+    con: Option<AsyncPgConnection>,
+}
+
+impl LeaderLock {
+    pub fn new(db_url: &str, key: i64) -> Self {
+        Self {
+            db_url: db_url.to_string(),
+            key,
+            con: None,
+        }
+    }
+
+    /// Returns true if this process holds the lock, first trying to acquire it if it does not. A
+    /// lock whose connection was lost may since have been acquired by another replica, so it is
+    /// acquired again on a new connection.
+    pub async fn acquire(&mut self) -> Result<bool> {
+        if let Some(con) = self.con.as_mut() {
+            if diesel::select(sql::<Bool>("true"))
+                .get_result::<bool>(con)
+                .await
+                .is_ok()
+            {
+                return Ok(true);
+            }
+            self.con = None;
+        }
+
+        let mut con = AsyncPgConnection::establish(&self.db_url).await?;
+        let locked = diesel::select(
+            sql::<Bool>("pg_try_advisory_lock(")
+                .bind::<BigInt, _>(self.key)
+                .sql(")"),
+        )
+        .get_result::<bool>(&mut con)
+        .await?;
+        if locked {
+            self.con = Some(con);
+        }
+        Ok(locked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LeaderLock;
+    use crate::crud::test_utils::test_db;
+
+    #[tokio::test]
+    async fn test_only_one_leader_holds_the_lock() {
+        let Some(test_db) = test_db().await else {
+            return;
+        };
+        let mut leader = LeaderLock::new(&test_db.url, 42);
+        let mut follower = LeaderLock::new(&test_db.url, 42);
+        let mut other_task = LeaderLock::new(&test_db.url, 43);
+
+        assert!(leader.acquire().await.unwrap());
+        assert!(!follower.acquire().await.unwrap());
+        assert!(leader.acquire().await.unwrap());
+        assert!(other_task.acquire().await.unwrap());
+
+        // The lock is released with the leader's connection
+        drop(leader);
+        assert!(follower.acquire().await.unwrap());
+    }
+}
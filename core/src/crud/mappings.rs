@@ -12,12 +12,13 @@ use crate::{
 use crate::{error::Result, model::entity::Entity};
 
 use crate::schema;
-use diesel::{insert_into, prelude::*};
+use diesel::{delete, insert_into, prelude::*, upsert::excluded};
 use diesel_async::RunQueryDsl;
 
 use uuid::Uuid;
 
-use super::PgDb;
+use super::utils::dedup_last_by_key;
+use super::{PgDb, UPSERT_BATCH_SIZE};
 
 impl<'a> PgDb<'a> {
     pub async fn upsert_local_mapping(&mut self, val: &Mapping) -> Result<()> {
@@ -32,6 +33,22 @@ impl<'a> PgDb<'a> {
         Ok(())
     }
 
+    /// Upserts many local [Mapping]s with batched statements.
+    pub async fn upsert_local_mappings(&mut self, vals: &[Mapping]) -> Result<()> {
+        use schema::field_mappings::dsl::*;
+        let vals = dedup_last_by_key(vals, |m| (m.information_id, m.data_field_id));
+        for chunk in vals.chunks(UPSERT_BATCH_SIZE) {
+            insert_into(field_mappings)
+                .values(chunk.to_vec())
+                .on_conflict((information_id, data_field_id))
+                .do_update()
+                .set(transformation.eq(excluded(transformation)))
+                .execute(&mut self.con)
+                .await?;
+        }
+        Ok(())
+    }
+
     pub async fn get_remote_entity_mapping(
         &mut self,
         relay_id_val: &Uuid,
@@ -203,4 +220,45 @@ impl<'a> PgDb<'a> {
 
         Ok(joined_rows_to_map(rows))
     }
+
+    /// Deletes the [Mapping] of the [Information] with id information_id_val to the
+    /// [DataField] with id data_field_id_val, returning the number of deleted mappings.
+    pub async fn delete_local_mapping(
+        &mut self,
+        information_id_val: &Uuid,
+        data_field_id_val: &Uuid,
+    ) -> Result<usize> {
+        use schema::field_mappings::dsl::*;
+        Ok(delete(
+            field_mappings.filter(
+                information_id
+                    .eq(information_id_val)
+                    .and(data_field_id.eq(data_field_id_val)),
+            ),
+        )
+        .execute(&mut self.con)
+        .await?)
+    }
+
+    /// Deletes a [RemoteEntityMapping] along with its [RemoteInfoMapping]s, returning the number
+    /// of deleted entity mappings.
+    pub async fn delete_remote_entity_mapping(
+        &mut self,
+        relay_id_val: &Uuid,
+        entity_id_val: &Uuid,
+        remote_entity_name_val: &str,
+    ) -> Result<usize> {
+        use schema::remote_entity_mapping::dsl::*;
+        Ok(delete(
+            remote_entity_mapping.filter(
+                relay_id.eq(relay_id_val).and(
+                    entity_id
+                        .eq(entity_id_val)
+                        .and(remote_entity_name.eq(remote_entity_name_val)),
+                ),
+            ),
+        )
+        .execute(&mut self.con)
+        .await?)
+    }
 }
@@ -7,21 +7,34 @@ use diesel::Connection;
 use diesel::PgConnection;
 use diesel_async::{
     pooled_connection::bb8::{Pool, PooledConnection},
-    AsyncPgConnection,
+    AnsiTransactionManager, AsyncPgConnection, TransactionManager,
 };
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use tracing::error;
 
+mod audit;
 mod data;
 mod entity;
+mod lock;
 mod mappings;
 mod query;
+mod quota;
 mod relay;
+mod role;
+#[cfg(test)]
+pub(crate) mod test_utils;
+mod usage;
 mod user;
 mod utils;
 
+pub use lock::LeaderLock;
+
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
 
+/// Maximum number of rows written by a single batched upsert statement, which keeps the number
+/// of bind parameters well below the Postgres limit.
+const UPSERT_BATCH_SIZE: usize = 1000;
+
 /// Bootstraps database with diesel migrations uses embedded code. Continues to retry on error, logging
 /// the error cause, and sleeping for a time. Intermittent connectivity errors on startup are expected while
 /// Postgres is initializing. Panics if the migrations fail for any reason other inability to connect.
@@ -58,4 +71,21 @@ impl<'a> PgDb<'a> {
         })?;
         Ok(Self { con })
     }
+
+    /// Begins a transaction which spans every following operation until [PgDb::commit] or
+    /// [PgDb::rollback]. Operations which use a transaction themselves run in a savepoint. A
+    /// connection returned to the [Pool] with the transaction still open is discarded.
+    pub async fn begin(&mut self) -> Result<()> {
+        Ok(AnsiTransactionManager::begin_transaction(&mut *self.con).await?)
+    }
+
+    /// Commits the transaction begun with [PgDb::begin].
+    pub async fn commit(&mut self) -> Result<()> {
+        Ok(AnsiTransactionManager::commit_transaction(&mut *self.con).await?)
+    }
+
+    /// Rolls back the transaction begun with [PgDb::begin].
+    pub async fn rollback(&mut self) -> Result<()> {
+        Ok(AnsiTransactionManager::rollback_transaction(&mut *self.con).await?)
+    }
 }
@@ -1,30 +1,74 @@
 use crate::error::{MeshError, Result};
+use crate::messaging::{GenericMessage, QueryTaskMessage};
 use crate::model::{
     data_stores::{DataConnection, DataSource},
     query::{
-        FlightStream, NewFlightStream, NewQueryTask, QueryOriginationInfo, QueryRequest, QueryTask,
-        QueryTaskRemote, QueryTaskRemoteStatus, QueryTaskStatus,
+        DeadLetter, FlightStream, FlightStreamStatus, NewDeadLetter, NewFlightStream,
+        NewOutboxMessage, NewQueryTask, OutboxMessage, QueryOriginationInfo, QueryRequest,
+        QueryTask, QueryTaskRemote, QueryTaskRemoteStatus, QueryTaskStatus, ResultFormat,
+        ScanMetrics, SourceQueueStats, StoredResult, TraceContext,
     },
     relay::Relay,
 };
 
 use crate::schema;
+use chrono::{DateTime, Utc};
 use diesel::result::DatabaseErrorKind;
-use diesel::{insert_into, prelude::*, update};
-use diesel_async::RunQueryDsl;
+use diesel::{delete, insert_into, prelude::*, update};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
 
 use uuid::Uuid;
 
 use super::PgDb;
 
+/// Number of recently completed tasks of a [DataSource] which its average latency is based on.
+const LATENCY_SAMPLE_SIZE: i64 = 100;
+
+/// Writes [GenericMessage]s to the task outbox using the connection of an open transaction.
+async fn insert_outbox_messages(
+    con: &mut AsyncPgConnection,
+    messages: Vec<GenericMessage>,
+) -> Result<()> {
+    use schema::task_outbox::dsl::*;
+    if messages.is_empty() {
+        return Ok(());
+    }
+    let vals = messages
+        .iter()
+        .map(|m| {
+            Ok(NewOutboxMessage {
+                message: serde_json::to_value(m)?,
+                task_id: m.task_id(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    insert_into(task_outbox).values(&vals).execute(con).await?;
+    Ok(())
+}
+
+fn local_task_messages(tasks: &[QueryTask]) -> Vec<GenericMessage> {
+    tasks
+        .iter()
+        .filter(|t| t.not_before.is_none())
+        .map(|t| GenericMessage::LocalQueryTask(QueryTaskMessage { id: t.id }))
+        .collect()
+}
+
 impl<'a> PgDb<'a> {
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_query_request(
         &mut self,
         local_id_val: &Uuid,
         relay_id_val: &Uuid,
         originator_request_id_val: &Uuid,
         sql_val: &str,
+        statements_val: &[String],
         origin_info_val: &QueryOriginationInfo,
+        reexecution_of_val: Option<&Uuid>,
+        trace_context_val: Option<&TraceContext>,
+        result_ttl_secs_val: Option<i64>,
+        result_format_val: ResultFormat,
     ) -> Result<QueryRequest> {
         use schema::query_request::dsl::*;
         let r: Result<QueryRequest, diesel::result::Error> = insert_into(query_request)
@@ -32,8 +76,13 @@ impl<'a> PgDb<'a> {
                 id.eq(local_id_val),
                 relay_id.eq(relay_id_val),
                 sql.eq(sql_val),
+                statements.eq(statements_val),
                 originator_request_id.eq(originator_request_id_val),
                 origin_info.eq(origin_info_val),
+                reexecution_of.eq(reexecution_of_val),
+                trace_context.eq(trace_context_val),
+                result_ttl_secs.eq(result_ttl_secs_val),
+                result_format.eq(result_format_val),
             ))
             .get_result(&mut self.con)
             .await;
@@ -48,6 +97,7 @@ impl<'a> PgDb<'a> {
                 {
                     let already_recieved_request: QueryRequest = query_request
                         .filter(originator_request_id.eq(originator_request_id_val))
+                        .filter(retired.eq(false))
                         .get_result(&mut self.con)
                         .await?;
                     return Err(MeshError::DuplicateQueryRequest(Box::new(
@@ -74,17 +124,245 @@ impl<'a> PgDb<'a> {
             .await?)
     }
 
+    /// Returns the [QueryRequest] along with its results which are stored by the
+    /// [ResultManager][crate::execute::result_manager::ResultManager], i.e. its complete local
+    /// [QueryTask]s and complete [FlightStream]s whose results have not expired.
+    pub async fn get_stored_results(
+        &mut self,
+        id_val: Uuid,
+    ) -> Result<Option<(QueryRequest, Vec<StoredResult>)>> {
+        let (request, tasks, remote_tasks) = match self.get_query_request(id_val).await? {
+            Some((request, _, _)) if request.replay_of.is_some() => return Ok(None),
+            Some(r) => r,
+            None => return Ok(None),
+        };
+        let flights = self.get_all_flight_streams(&remote_tasks).await?;
+        let results = tasks
+            .iter()
+            .filter(|t| matches!(t.status, QueryTaskStatus::Complete))
+            .filter(|t| t.result_expired_at.is_none())
+            .map(|t| StoredResult {
+                id: t.id,
+                relay_id: None,
+                freshness: t.freshness,
+                statement_index: t.statement_index,
+            })
+            .chain(
+                flights
+                    .iter()
+                    .filter(|(_, f)| matches!(f.status, FlightStreamStatus::Complete))
+                    .filter(|(_, f)| f.result_expired_at.is_none())
+                    .map(|(remote, f)| StoredResult {
+                        id: f.flight_id,
+                        relay_id: Some(remote.relay_id),
+                        freshness: f.freshness,
+                        statement_index: remote.statement_index,
+                    }),
+            )
+            .collect();
+        Ok(Some((request, results)))
+    }
+
+    /// Claims up to limit [QueryRequest]s received before received_before which have no pending
+    /// work left, i.e. no queued or in progress [QueryTask]s, no remote tasks waiting to be
+    /// submitted and no [FlightStream]s still being received, and for which no lineage was
+    /// emitted yet. Claiming marks them as emitted, so concurrent query_runners never claim the
+    /// same request. Replays are never claimed.
+    pub async fn claim_settled_requests(
+        &mut self,
+        received_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<QueryRequest>> {
+        use schema::incoming_flight_streams::dsl as flight;
+        use schema::query_request::dsl::*;
+        use schema::query_task::dsl as task;
+        use schema::query_task_remote::dsl as remote;
+        let pending_tasks = task::query_task
+            .select(task::query_request_id)
+            .filter(task::status.eq_any([QueryTaskStatus::Queued, QueryTaskStatus::InProgress]));
+        let pending_remote_tasks = remote::query_task_remote
+            .select(remote::query_request_id)
+            .filter(remote::status.eq(QueryTaskRemoteStatus::Queued));
+        let pending_flights = flight::incoming_flight_streams
+            .inner_join(remote::query_task_remote)
+            .select(remote::query_request_id)
+            .filter(flight::status.eq(FlightStreamStatus::Started));
+        let settled: Vec<Uuid> = query_request
+            .select(id)
+            .filter(lineage_emitted_at.is_null())
+            .filter(retired.eq(false))
+            .filter(replay_of.is_null())
+            .filter(received_at.lt(received_before))
+            .filter(id.ne_all(pending_tasks))
+            .filter(id.ne_all(pending_remote_tasks))
+            .filter(id.ne_all(pending_flights))
+            .order(received_at)
+            .limit(limit)
+            .load(&mut self.con)
+            .await?;
+        Ok(update(query_request)
+            .filter(id.eq_any(settled))
+            .filter(lineage_emitted_at.is_null())
+            .set(lineage_emitted_at.eq(Utc::now()))
+            .get_results(&mut self.con)
+            .await?)
+    }
+
+    /// Releases a request claimed by [PgDb::claim_settled_requests], e.g. because emitting its
+    /// lineage failed, so that it is claimed again.
+    pub async fn release_lineage_claim(&mut self, id_val: Uuid) -> Result<()> {
+        use schema::query_request::dsl::*;
+        update(query_request.filter(id.eq(id_val)))
+            .set(lineage_emitted_at.eq(None::<DateTime<Utc>>))
+            .execute(&mut self.con)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns up to limit complete [QueryTask]s whose result was produced longer ago than the
+    /// result_ttl_secs of their [QueryRequest] and was not deleted yet, as the ids of the task
+    /// and of its request along with the replay_of and result_format of the request.
+    pub async fn get_expired_task_results(
+        &mut self,
+        limit: i64,
+    ) -> Result<Vec<(Uuid, Uuid, Option<Uuid>, ResultFormat)>> {
+        use diesel::dsl::sql;
+        use diesel::sql_types::Bool;
+        use schema::query_request::dsl as req;
+        use schema::query_task::dsl::*;
+        Ok(query_task
+            .inner_join(req::query_request)
+            .filter(status.eq(QueryTaskStatus::Complete))
+            .filter(result_expired_at.is_null())
+            .filter(req::result_ttl_secs.is_not_null())
+            .filter(sql::<Bool>(
+                "query_task.completed_at + query_request.result_ttl_secs * interval '1 second' < now()",
+            ))
+            .select((id, req::id, req::replay_of, req::result_format))
+            .limit(limit)
+            .load(&mut self.con)
+            .await?)
+    }
+
+    /// Returns up to limit complete [FlightStream]s whose result was received longer ago than the
+    /// result_ttl_secs of their [QueryRequest] and was not deleted yet, as the ids of the
+    /// stream, of its flight and of the [Relay] which sent it along with the result_format of
+    /// the request.
+    pub async fn get_expired_flight_results(
+        &mut self,
+        limit: i64,
+    ) -> Result<Vec<(Uuid, Uuid, Uuid, ResultFormat)>> {
+        use diesel::dsl::sql;
+        use diesel::sql_types::Bool;
+        use schema::incoming_flight_streams::dsl::*;
+        use schema::query_request::dsl as req;
+        use schema::query_task_remote::dsl as remote;
+        Ok(incoming_flight_streams
+            .inner_join(remote::query_task_remote.inner_join(req::query_request))
+            .filter(status.eq(FlightStreamStatus::Complete))
+            .filter(result_expired_at.is_null())
+            .filter(req::result_ttl_secs.is_not_null())
+            .filter(sql::<Bool>(
+                "incoming_flight_streams.updated_at + query_request.result_ttl_secs * interval '1 second' < now()",
+            ))
+            .select((id, flight_id, remote::relay_id, req::result_format))
+            .limit(limit)
+            .load(&mut self.con)
+            .await?)
+    }
+
+    /// Records that the results of the [QueryTask]s with ids_val were deleted.
+    pub async fn mark_task_results_expired(&mut self, ids_val: &[Uuid]) -> Result<()> {
+        use schema::query_task::dsl::*;
+        update(query_task.filter(id.eq_any(ids_val)))
+            .set(result_expired_at.eq(diesel::dsl::now))
+            .execute(&mut self.con)
+            .await?;
+        Ok(())
+    }
+
+    /// Records that the results of the [FlightStream]s with ids_val were deleted.
+    pub async fn mark_flight_results_expired(&mut self, ids_val: &[Uuid]) -> Result<()> {
+        use schema::incoming_flight_streams::dsl::*;
+        update(incoming_flight_streams.filter(id.eq_any(ids_val)))
+            .set(result_expired_at.eq(diesel::dsl::now))
+            .execute(&mut self.con)
+            .await?;
+        Ok(())
+    }
+
+    /// Creates a [QueryRequest] replaying original with the same SQL and originating [User]. The
+    /// replay has no origin relay or task, so its results are never sent to another relay.
+    pub async fn create_replay_request(&mut self, original: &QueryRequest) -> Result<QueryRequest> {
+        use schema::query_request::dsl::*;
+        let local_id = Uuid::new_v4();
+        let origin_info_val = QueryOriginationInfo {
+            origin_user: original.origin_info.origin_user.clone(),
+            origin_relay: None,
+            origin_task_id: None,
+        };
+        Ok(insert_into(query_request)
+            .values((
+                id.eq(local_id),
+                relay_id.eq(original.relay_id),
+                sql.eq(&original.sql),
+                statements.eq(&original.statements),
+                originator_request_id.eq(local_id),
+                origin_info.eq(&origin_info_val),
+                replay_of.eq(original.id),
+                result_ttl_secs.eq(original.result_ttl_secs),
+                result_format.eq(original.result_format),
+            ))
+            .get_result(&mut self.con)
+            .await?)
+    }
+
+    /// Returns the [QueryRequest] which was already received with this originator_request_id (or
+    /// local id), if it has not been retired. If a retention is passed, a request received longer
+    /// ago than the retention is retired first, so that the id is executed again.
     pub async fn check_if_request_already_received(
         &mut self,
         originator_request_id_val: &Uuid,
+        retention: Option<chrono::Duration>,
     ) -> Result<QueryRequest> {
         use schema::query_request::dsl::*;
+        if let Some(retention) = retention {
+            update(query_request)
+                .filter(originator_request_id.eq(originator_request_id_val))
+                .filter(retired.eq(false))
+                .filter(received_at.lt(Utc::now() - retention))
+                .set(retired.eq(true))
+                .execute(&mut self.con)
+                .await?;
+        }
         Ok(query_request
             .filter(
                 originator_request_id
                     .eq(originator_request_id_val)
                     .or(id.eq(originator_request_id_val)),
             )
+            .filter(retired.eq(false))
+            .get_result(&mut self.con)
+            .await?)
+    }
+
+    /// Counts the [QueryRequest]s originated by the user with x509_sha256_val which were
+    /// received since since, e.g. to enforce the request rate of a service account.
+    pub async fn count_user_requests_since(
+        &mut self,
+        x509_sha256_val: &str,
+        since: DateTime<Utc>,
+    ) -> Result<i64> {
+        use diesel::dsl::sql;
+        use diesel::sql_types::{Bool, Text};
+        use schema::query_request::dsl::*;
+        Ok(query_request
+            .filter(received_at.ge(since))
+            .filter(
+                sql::<Bool>("origin_info -> 'origin_user' ->> 'x509_sha256' = ")
+                    .bind::<Text, _>(x509_sha256_val),
+            )
+            .count()
             .get_result(&mut self.con)
             .await?)
     }
@@ -143,6 +421,28 @@ impl<'a> PgDb<'a> {
             .await?)
     }
 
+    /// Creates [QueryTask]s and, in the same transaction, writes a message for each task which
+    /// is not deferred to the task outbox.
+    pub async fn create_query_tasks_with_outbox(
+        &mut self,
+        vals: &Vec<NewQueryTask>,
+    ) -> Result<Vec<QueryTask>> {
+        use schema::query_task::dsl::*;
+        (*self.con)
+            .transaction::<_, MeshError, _>(|con| {
+                async move {
+                    let tasks: Vec<QueryTask> = insert_into(query_task)
+                        .values(vals)
+                        .get_results(con)
+                        .await?;
+                    insert_outbox_messages(con, local_task_messages(&tasks)).await?;
+                    Ok(tasks)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
     pub async fn create_remote_query_tasks(
         &mut self,
         vals: &Vec<QueryTaskRemote>,
@@ -154,18 +454,412 @@ impl<'a> PgDb<'a> {
             .await?)
     }
 
+    /// Creates [QueryTaskRemote]s and, in the same transaction, writes a message for each task
+    /// to the task outbox.
+    pub async fn create_remote_query_tasks_with_outbox(
+        &mut self,
+        vals: &Vec<QueryTaskRemote>,
+    ) -> Result<Vec<QueryTaskRemote>> {
+        use schema::query_task_remote::dsl::*;
+        (*self.con)
+            .transaction::<_, MeshError, _>(|con| {
+                async move {
+                    let tasks: Vec<QueryTaskRemote> = insert_into(query_task_remote)
+                        .values(vals)
+                        .get_results(con)
+                        .await?;
+                    let messages = tasks
+                        .iter()
+                        .map(|t| GenericMessage::RemoteQueryTask(QueryTaskMessage { id: t.id }))
+                        .collect();
+                    insert_outbox_messages(con, messages).await?;
+                    Ok(tasks)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    /// Returns up to limit outbox messages which were written before created_before, oldest first.
+    /// The messages are locked until the end of the current transaction, and messages locked by
+    /// another transaction are skipped, so concurrent publishers never publish the same message.
+    pub async fn get_outbox_messages(
+        &mut self,
+        created_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<OutboxMessage>> {
+        use schema::task_outbox::dsl::*;
+        Ok(task_outbox
+            .filter(created_at.le(created_before))
+            .order(id.asc())
+            .limit(limit)
+            .select(OutboxMessage::as_select())
+            .for_update()
+            .skip_locked()
+            .load(&mut self.con)
+            .await?)
+    }
+
+    /// Writes outbox messages for Queued tasks which are not deferred and have no message in the
+    /// outbox or dead letter, e.g. because the message broker lost their messages. Only tasks whose
+    /// message was published before orphaned_before are requeued, or if it was never published,
+    /// tasks of requests received before then. Returns the number of requeued tasks.
+    pub async fn requeue_orphaned_tasks(
+        &mut self,
+        orphaned_before: DateTime<Utc>,
+    ) -> Result<usize> {
+        use diesel::dsl::{exists, not};
+        use schema::dead_letter::dsl as dead_letter;
+        use schema::query_request::dsl as request;
+        use schema::query_task::dsl as local;
+        use schema::query_task_remote::dsl as remote;
+        use schema::task_outbox::dsl as outbox;
+        (*self.con)
+            .transaction::<_, MeshError, _>(|con| {
+                async move {
+                    let local_ids: Vec<Uuid> = local::query_task
+                        .inner_join(request::query_request)
+                        .filter(
+                            local::status
+                                .eq(QueryTaskStatus::Queued)
+                                .and(local::not_before.is_null())
+                                .and(
+                                    local::dispatched_at.lt(orphaned_before).or(
+                                        local::dispatched_at
+                                            .is_null()
+                                            .and(request::received_at.lt(orphaned_before)),
+                                    ),
+                                ),
+                        )
+                        .filter(not(exists(
+                            outbox::task_outbox.filter(outbox::task_id.eq(local::id.nullable())),
+                        )))
+                        .filter(not(exists(
+                            dead_letter::dead_letter
+                                .filter(dead_letter::task_id.eq(local::id.nullable())),
+                        )))
+                        .select(local::id)
+                        .load(con)
+                        .await?;
+                    let remote_ids: Vec<Uuid> = remote::query_task_remote
+                        .inner_join(request::query_request)
+                        .filter(
+                            remote::status
+                                .eq(QueryTaskRemoteStatus::Queued)
+                                .and(remote::not_before.is_null())
+                                .and(
+                                    remote::dispatched_at.lt(orphaned_before).or(
+                                        remote::dispatched_at
+                                            .is_null()
+                                            .and(request::received_at.lt(orphaned_before)),
+                                    ),
+                                ),
+                        )
+                        .filter(not(exists(
+                            outbox::task_outbox.filter(outbox::task_id.eq(remote::id.nullable())),
+                        )))
+                        .filter(not(exists(
+                            dead_letter::dead_letter
+                                .filter(dead_letter::task_id.eq(remote::id.nullable())),
+                        )))
+                        .select(remote::id)
+                        .load(con)
+                        .await?;
+                    let messages = local_ids
+                        .into_iter()
+                        .map(|id| GenericMessage::LocalQueryTask(QueryTaskMessage { id }))
+                        .chain(
+                            remote_ids
+                                .into_iter()
+                                .map(|id| GenericMessage::RemoteQueryTask(QueryTaskMessage { id })),
+                        )
+                        .collect::<Vec<_>>();
+                    let requeued = messages.len();
+                    insert_outbox_messages(con, messages).await?;
+                    Ok(requeued)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    /// Removes a message from the outbox once it was published, recording when the task it is
+    /// about was dispatched.
+    pub async fn delete_outbox_message(&mut self, id_val: i64) -> Result<()> {
+        use schema::query_task::dsl as local;
+        use schema::query_task_remote::dsl as remote;
+        use schema::task_outbox::dsl::*;
+        let deleted: Option<Option<Uuid>> = delete(task_outbox.filter(id.eq(id_val)))
+            .returning(task_id)
+            .get_result(&mut self.con)
+            .await
+            .optional()?;
+        if let Some(task_id_val) = deleted.flatten() {
+            update(local::query_task.filter(local::id.eq(task_id_val)))
+                .set(local::dispatched_at.eq(diesel::dsl::now))
+                .execute(&mut self.con)
+                .await?;
+            update(remote::query_task_remote.filter(remote::id.eq(task_id_val)))
+                .set(remote::dispatched_at.eq(diesel::dsl::now))
+                .execute(&mut self.con)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Records that processing msg failed with error_val. A message about a task is written to the
+    /// task outbox again, until it failed max_attempts times. It is then dead, and its task is
+    /// marked Failed so that the status of its request resolves. A message which could not be
+    /// parsed, passed as None, is dead at once.
+    pub async fn record_failed_message(
+        &mut self,
+        msg: Option<&GenericMessage>,
+        error_val: &str,
+        max_attempts: i32,
+    ) -> Result<DeadLetter> {
+        use schema::dead_letter::dsl::*;
+        use schema::query_task::dsl as local;
+        use schema::query_task_remote::dsl as remote;
+        let new_letter = NewDeadLetter {
+            task_id: msg.map(|m| m.task_id()),
+            message: msg.map(serde_json::to_value).transpose()?,
+            error: error_val.to_string(),
+            dead: msg.is_none() || max_attempts <= 1,
+        };
+        let msg = msg.cloned();
+        (*self.con)
+            .transaction::<_, MeshError, _>(|con| {
+                async move {
+                    let letter = match msg {
+                        None => {
+                            insert_into(dead_letter)
+                                .values(&new_letter)
+                                .returning(DeadLetter::as_returning())
+                                .get_result(con)
+                                .await?
+                        }
+                        Some(_) => {
+                            insert_into(dead_letter)
+                                .values(&new_letter)
+                                .on_conflict(task_id)
+                                .do_update()
+                                .set((
+                                    attempts.eq(attempts + 1),
+                                    dead.eq((attempts + 1).ge(max_attempts)),
+                                    error.eq(&new_letter.error),
+                                    updated_at.eq(diesel::dsl::now),
+                                ))
+                                .returning(DeadLetter::as_returning())
+                                .get_result(con)
+                                .await?
+                        }
+                    };
+                    match msg {
+                        Some(msg) if !letter.dead => insert_outbox_messages(con, vec![msg]).await?,
+                        Some(GenericMessage::LocalQueryTask(m)) => {
+                            update(local::query_task.filter(local::id.eq(m.id)))
+                                .set((
+                                    local::status.eq(QueryTaskStatus::Failed),
+                                    local::completed_at.eq(diesel::dsl::now),
+                                ))
+                                .execute(con)
+                                .await?;
+                        }
+                        Some(GenericMessage::RemoteQueryTask(m)) => {
+                            update(remote::query_task_remote.filter(remote::id.eq(m.id)))
+                                .set(remote::status.eq(QueryTaskRemoteStatus::Failed))
+                                .execute(con)
+                                .await?;
+                        }
+                        None => (),
+                    }
+                    Ok(letter)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    /// Returns the dead messages, and with retrying set also the messages which are still being
+    /// retried, most recently failed first.
+    pub async fn get_dead_letters(&mut self, retrying: bool) -> Result<Vec<DeadLetter>> {
+        use schema::dead_letter::dsl::*;
+        let mut query = dead_letter.into_boxed();
+        if !retrying {
+            query = query.filter(dead.eq(true));
+        }
+        Ok(query
+            .order(updated_at.desc())
+            .select(DeadLetter::as_select())
+            .load(&mut self.con)
+            .await?)
+    }
+
+    /// Removes a [DeadLetter] and writes its message to the task outbox again, after requeueing
+    /// its task. Returns the removed [DeadLetter], or None if none exists with id_val.
+    pub async fn replay_dead_letter(&mut self, id_val: i64) -> Result<Option<DeadLetter>> {
+        use schema::dead_letter::dsl::*;
+        use schema::query_task::dsl as local;
+        use schema::query_task_remote::dsl as remote;
+        (*self.con)
+            .transaction::<_, MeshError, _>(|con| {
+                async move {
+                    let letter = match delete(dead_letter.filter(id.eq(id_val)))
+                        .returning(DeadLetter::as_returning())
+                        .get_result(con)
+                        .await
+                        .optional()?
+                    {
+                        Some(letter) => letter,
+                        None => return Ok(None),
+                    };
+                    let msg: GenericMessage = match &letter.message {
+                        Some(msg) => serde_json::from_value(msg.clone())?,
+                        None => {
+                            return Err(MeshError::InvalidQuery(format!(
+                                "Dead letter {id_val} could not be parsed and cannot be replayed"
+                            )))
+                        }
+                    };
+                    match &msg {
+                        GenericMessage::LocalQueryTask(m) => {
+                            update(local::query_task.filter(local::id.eq(m.id)))
+                                .set((
+                                    local::status.eq(QueryTaskStatus::Queued),
+                                    local::completed_at.eq(None::<DateTime<Utc>>),
+                                ))
+                                .execute(con)
+                                .await?;
+                        }
+                        GenericMessage::RemoteQueryTask(m) => {
+                            update(remote::query_task_remote.filter(remote::id.eq(m.id)))
+                                .set(remote::status.eq(QueryTaskRemoteStatus::Queued))
+                                .execute(con)
+                                .await?;
+                        }
+                    }
+                    insert_outbox_messages(con, vec![msg]).await?;
+                    Ok(Some(letter))
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    /// Returns the [FlightStream] received for a flight_id, if any.
+    pub async fn get_flight_stream(
+        &mut self,
+        flight_id_val: &Uuid,
+    ) -> Result<Option<FlightStream>> {
+        use schema::incoming_flight_streams::dsl::*;
+        Ok(incoming_flight_streams
+            .filter(flight_id.eq(flight_id_val))
+            .select(FlightStream::as_select())
+            .first(&mut self.con)
+            .await
+            .optional()?)
+    }
+
     pub async fn upsert_flight_stream(&mut self, flight: &NewFlightStream) -> Result<()> {
         use schema::incoming_flight_streams::dsl::*;
         insert_into(incoming_flight_streams)
             .values(flight)
             .on_conflict(flight_id)
             .do_update()
-            .set(flight)
+            .set((flight, updated_at.eq(diesel::dsl::now)))
             .execute(&mut self.con)
             .await?;
         Ok(())
     }
 
+    /// Starts receiving a flight by upserting it as Started, unless a [FlightStream] with its
+    /// flight_id was already received from another relay, or is neither Failed nor Started
+    /// without an update since stale_before. A stream which is still being received by another
+    /// do_put, or which is Complete, is thus never overwritten. Returns the previously received
+    /// FlightStream, if any, and whether the flight was started.
+    pub async fn start_flight_stream(
+        &mut self,
+        flight: &NewFlightStream,
+        stale_before: DateTime<Utc>,
+    ) -> Result<(Option<FlightStream>, bool)> {
+        use schema::incoming_flight_streams::dsl::*;
+        (*self.con)
+            .transaction::<_, MeshError, _>(|con| {
+                async move {
+                    let existing = incoming_flight_streams
+                        .filter(flight_id.eq(flight.flight_id))
+                        .select(FlightStream::as_select())
+                        .for_update()
+                        .first(con)
+                        .await
+                        .optional()?;
+                    let Some(existing) = existing else {
+                        // A concurrent do_put of the same flight may insert it first
+                        let inserted = insert_into(incoming_flight_streams)
+                            .values(flight)
+                            .on_conflict_do_nothing()
+                            .execute(con)
+                            .await?;
+                        if inserted > 0 {
+                            return Ok((None, true));
+                        }
+                        let existing = incoming_flight_streams
+                            .filter(flight_id.eq(flight.flight_id))
+                            .select(FlightStream::as_select())
+                            .first(con)
+                            .await?;
+                        return Ok((Some(existing), false));
+                    };
+                    let replaceable = existing.remote_fingerprint == flight.remote_fingerprint
+                        && match existing.status {
+                            FlightStreamStatus::Failed => true,
+                            FlightStreamStatus::Started => existing.updated_at < stale_before,
+                            FlightStreamStatus::Complete | FlightStreamStatus::Invalid => false,
+                        };
+                    if replaceable {
+                        update(incoming_flight_streams.filter(flight_id.eq(flight.flight_id)))
+                            .set((flight, updated_at.eq(diesel::dsl::now)))
+                            .execute(con)
+                            .await?;
+                    }
+                    Ok((Some(existing), replaceable))
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    /// Records that the [FlightStream] is still being written, so it is not considered abandoned.
+    pub async fn touch_flight_stream(&mut self, flight_id_val: &Uuid) -> Result<()> {
+        use schema::incoming_flight_streams::dsl::*;
+        update(incoming_flight_streams.filter(flight_id.eq(flight_id_val)))
+            .set(updated_at.eq(diesel::dsl::now))
+            .execute(&mut self.con)
+            .await?;
+        Ok(())
+    }
+
+    /// Marks every Started [FlightStream] which was last updated before updated_before as Failed,
+    /// so that the status of its [QueryRequest] resolves. Returns the failed streams.
+    pub async fn fail_stale_flight_streams(
+        &mut self,
+        updated_before: DateTime<Utc>,
+    ) -> Result<Vec<FlightStream>> {
+        use schema::incoming_flight_streams::dsl::*;
+        Ok(update(
+            incoming_flight_streams
+                .filter(status.eq(FlightStreamStatus::Started))
+                .filter(updated_at.lt(updated_before)),
+        )
+        .set((
+            status.eq(FlightStreamStatus::Failed),
+            updated_at.eq(diesel::dsl::now),
+        ))
+        .returning(FlightStream::as_returning())
+        .get_results(&mut self.con)
+        .await?)
+    }
+
     pub async fn get_query_task(
         &mut self,
         id_val: Uuid,
@@ -190,6 +884,29 @@ impl<'a> PgDb<'a> {
             .await?)
     }
 
+    /// Returns the trace context stored with a [QueryRequest], if it was traced.
+    pub async fn get_request_trace_context(
+        &mut self,
+        request_id: Uuid,
+    ) -> Result<Option<TraceContext>> {
+        use schema::query_request::dsl::*;
+        Ok(query_request
+            .select(trace_context)
+            .filter(id.eq(request_id))
+            .get_result(&mut self.con)
+            .await?)
+    }
+
+    /// Returns the format the results of a [QueryRequest] are stored in.
+    pub async fn get_request_result_format(&mut self, request_id: Uuid) -> Result<ResultFormat> {
+        use schema::query_request::dsl::*;
+        Ok(query_request
+            .select(result_format)
+            .filter(id.eq(request_id))
+            .get_result(&mut self.con)
+            .await?)
+    }
+
     pub async fn get_remote_query_task(
         &mut self,
         id_val: Uuid,
@@ -204,20 +921,316 @@ impl<'a> PgDb<'a> {
             .await?)
     }
 
+    /// Moves a Queued [QueryTask] InProgress, so that only one query_runner executes it.
+    /// Returns false if the task was not Queued, e.g. because a duplicate of its message was
+    /// already received by another query_runner, in which case it must not be executed again.
+    pub async fn claim_query_task(&mut self, id_val: Uuid) -> Result<bool> {
+        use schema::query_task::dsl::*;
+        let now = Utc::now();
+        let claimed = update(
+            query_task
+                .filter(id.eq(id_val))
+                .filter(status.eq(QueryTaskStatus::Queued)),
+        )
+        .set((
+            status.eq(QueryTaskStatus::InProgress),
+            started_at.eq(now),
+            heartbeat_at.eq(now),
+            attempts.eq(attempts + 1),
+        ))
+        .execute(&mut self.con)
+        .await?;
+        Ok(claimed > 0)
+    }
+
     pub async fn update_task_status(
         &mut self,
         id_val: Uuid,
         status_val: QueryTaskStatus,
+    ) -> Result<()> {
+        use schema::query_task::dsl::*;
+        let now = Utc::now();
+        let target = update(query_task).filter(id.eq(id_val));
+        match status_val {
+            QueryTaskStatus::InProgress => {
+                target
+                    .set((
+                        status.eq(status_val),
+                        started_at.eq(now),
+                        heartbeat_at.eq(now),
+                        attempts.eq(attempts + 1),
+                    ))
+                    .execute(&mut self.con)
+                    .await?
+            }
+            QueryTaskStatus::Complete | QueryTaskStatus::Failed => {
+                target
+                    .set((status.eq(status_val), completed_at.eq(now)))
+                    .execute(&mut self.con)
+                    .await?
+            }
+            QueryTaskStatus::Queued => {
+                target
+                    .set(status.eq(status_val))
+                    .execute(&mut self.con)
+                    .await?
+            }
+        };
+        Ok(())
+    }
+
+    /// Cancels every [QueryTask] and [QueryTaskRemote] of a [QueryRequest] which did not yet
+    /// complete or fail by marking it Failed, in a single transaction. A query_runner executing
+    /// a cancelled task stops once its next heartbeat finds the task is no longer InProgress.
+    /// Returns the number of cancelled local and remote tasks.
+    pub async fn cancel_query_request(&mut self, request_id: Uuid) -> Result<(usize, usize)> {
+        use schema::query_task::dsl as local;
+        use schema::query_task_remote::dsl as remote;
+        (*self.con)
+            .transaction::<_, MeshError, _>(|con| {
+                async move {
+                    let tasks = update(local::query_task)
+                        .filter(local::query_request_id.eq(request_id))
+                        .filter(
+                            local::status
+                                .eq(QueryTaskStatus::Queued)
+                                .or(local::status.eq(QueryTaskStatus::InProgress)),
+                        )
+                        .set((
+                            local::status.eq(QueryTaskStatus::Failed),
+                            local::completed_at.eq(diesel::dsl::now),
+                        ))
+                        .execute(con)
+                        .await?;
+                    let remote_tasks = update(remote::query_task_remote)
+                        .filter(remote::query_request_id.eq(request_id))
+                        .filter(
+                            remote::status
+                                .eq(QueryTaskRemoteStatus::Queued)
+                                .or(remote::status.eq(QueryTaskRemoteStatus::Submitted)),
+                        )
+                        .set((
+                            remote::status.eq(QueryTaskRemoteStatus::Failed),
+                            remote::error.eq("Cancelled"),
+                        ))
+                        .execute(con)
+                        .await?;
+                    Ok((tasks, remote_tasks))
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    /// Records when the data a [QueryTask] read was last updated.
+    pub async fn set_task_freshness(
+        &mut self,
+        id_val: Uuid,
+        freshness_val: DateTime<Utc>,
+    ) -> Result<()> {
+        use schema::query_task::dsl::*;
+        update(query_task.filter(id.eq(id_val)))
+            .set(freshness.eq(freshness_val))
+            .execute(&mut self.con)
+            .await?;
+        Ok(())
+    }
+
+    /// Records that the [QueryTask] is still being executed, so it is not considered abandoned.
+    /// Returns false if the task is no longer InProgress, e.g. because it was reclaimed or
+    /// failed elsewhere, in which case its execution should be cancelled.
+    pub async fn touch_query_task(&mut self, id_val: Uuid) -> Result<bool> {
+        use schema::query_task::dsl::*;
+        let touched = update(
+            query_task
+                .filter(id.eq(id_val))
+                .filter(status.eq(QueryTaskStatus::InProgress)),
+        )
+        .set(heartbeat_at.eq(diesel::dsl::now))
+        .execute(&mut self.con)
+        .await?;
+        Ok(touched > 0)
+    }
+
+    /// Reclaims every InProgress [QueryTask] without a heartbeat since heartbeat_before, whose
+    /// query_runner presumably died. Tasks which were attempted fewer than max_attempts times are
+    /// Queued again and written to the task outbox, the rest are marked Failed so that the status
+    /// of their request resolves. Returns the ids of the requeued and of the failed tasks.
+    pub async fn reclaim_stale_tasks(
+        &mut self,
+        heartbeat_before: DateTime<Utc>,
+        max_attempts: i32,
+    ) -> Result<(Vec<Uuid>, Vec<Uuid>)> {
+        use schema::query_task::dsl::*;
+        (*self.con)
+            .transaction::<_, MeshError, _>(|con| {
+                async move {
+                    // Each update only matches tasks which are still InProgress once locked, so
+                    // concurrent reclaimers never reclaim the same task twice.
+                    let stale = || {
+                        status
+                            .eq(QueryTaskStatus::InProgress)
+                            .and(heartbeat_at.lt(heartbeat_before))
+                    };
+                    let failed: Vec<Uuid> =
+                        update(query_task.filter(stale().and(attempts.ge(max_attempts))))
+                            .set((
+                                status.eq(QueryTaskStatus::Failed),
+                                completed_at.eq(diesel::dsl::now),
+                            ))
+                            .returning(id)
+                            .get_results(con)
+                            .await?;
+                    let requeued: Vec<Uuid> = update(query_task.filter(stale()))
+                        .set(status.eq(QueryTaskStatus::Queued))
+                        .returning(id)
+                        .get_results(con)
+                        .await?;
+                    let messages = requeued
+                        .iter()
+                        .map(|task_id| {
+                            GenericMessage::LocalQueryTask(QueryTaskMessage { id: *task_id })
+                        })
+                        .collect();
+                    insert_outbox_messages(con, messages).await?;
+                    Ok((requeued, failed))
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    /// Reports the number of queued and in progress [QueryTask]s of each [DataSource] in
+    /// source_ids, or of every DataSource if None, along with their recent average latency.
+    pub async fn get_source_queue_stats(
+        &mut self,
+        source_ids: Option<&[Uuid]>,
+    ) -> Result<Vec<SourceQueueStats>> {
+        use schema::data_source::dsl as ds;
+        use schema::query_task::dsl::*;
+        let mut sources_query = ds::data_source.select((ds::id, ds::name)).into_boxed();
+        if let Some(ids) = source_ids {
+            sources_query = sources_query.filter(ds::id.eq_any(ids));
+        }
+        let sources: Vec<(Uuid, String)> = sources_query.load(&mut self.con).await?;
+
+        let ids: Vec<Uuid> = sources.iter().map(|(source_id, _)| *source_id).collect();
+        let counts: Vec<(Uuid, QueryTaskStatus, i64)> = query_task
+            .filter(data_source_id.eq_any(&ids))
+            .filter(
+                status
+                    .eq(QueryTaskStatus::InProgress)
+                    .or(status.eq(QueryTaskStatus::Queued).and(not_before.is_null())),
+            )
+            .group_by((data_source_id, status))
+            .select((data_source_id, status, diesel::dsl::count_star()))
+            .load(&mut self.con)
+            .await?;
+
+        let mut stats = Vec::with_capacity(sources.len());
+        for (source_id, source_name) in sources {
+            let count = |status_val: QueryTaskStatus| {
+                counts
+                    .iter()
+                    .filter(|(s, st, _)| *s == source_id && *st == status_val)
+                    .map(|(_, _, n)| *n)
+                    .sum()
+            };
+            let durations: Vec<(DateTime<Utc>, DateTime<Utc>)> = query_task
+                .filter(data_source_id.eq(source_id))
+                .filter(status.eq(QueryTaskStatus::Complete))
+                .filter(started_at.is_not_null().and(completed_at.is_not_null()))
+                .order(completed_at.desc())
+                .limit(LATENCY_SAMPLE_SIZE)
+                .select((started_at.assume_not_null(), completed_at.assume_not_null()))
+                .load(&mut self.con)
+                .await?;
+            let latencies: Vec<i64> = durations
+                .into_iter()
+                .map(|(start, end)| (end - start).num_milliseconds())
+                .collect();
+            let avg_task_latency_ms = match latencies.len() {
+                0 => None,
+                n => Some(latencies.iter().sum::<i64>() / n as i64),
+            };
+            stats.push(SourceQueueStats {
+                data_source_id: source_id,
+                data_source_name: source_name,
+                queued: count(QueryTaskStatus::Queued),
+                in_progress: count(QueryTaskStatus::InProgress),
+                avg_task_latency_ms,
+            });
+        }
+        Ok(stats)
+    }
+
+    pub async fn set_task_scan_metrics(
+        &mut self,
+        id_val: Uuid,
+        scan_metrics_val: &ScanMetrics,
     ) -> Result<()> {
         use schema::query_task::dsl::*;
         update(query_task)
             .filter(id.eq(id_val))
-            .set(status.eq(status_val))
+            .set(scan_metrics.eq(scan_metrics_val))
+            .execute(&mut self.con)
+            .await?;
+        Ok(())
+    }
+
+    /// Defers a [QueryTask] so that it is not dispatched for execution before not_before_val.
+    pub async fn defer_task(&mut self, id_val: Uuid, not_before_val: DateTime<Utc>) -> Result<()> {
+        use schema::query_task::dsl::*;
+        update(query_task)
+            .filter(id.eq(id_val))
+            .set(not_before.eq(Some(not_before_val)))
             .execute(&mut self.con)
             .await?;
         Ok(())
     }
 
+    /// Clears the deferral of every queued [QueryTask] and [QueryTaskRemote] whose not_before
+    /// has elapsed and, in the same transaction, writes a message for each released task to the
+    /// task outbox. Returns the ids of the released tasks.
+    pub async fn release_deferred_tasks(&mut self, now_val: DateTime<Utc>) -> Result<Vec<Uuid>> {
+        use schema::query_task::dsl as local;
+        use schema::query_task_remote::dsl as remote;
+        (*self.con)
+            .transaction::<_, MeshError, _>(|con| {
+                async move {
+                    let tasks: Vec<QueryTask> = update(local::query_task)
+                        .filter(
+                            local::status
+                                .eq(QueryTaskStatus::Queued)
+                                .and(local::not_before.le(now_val)),
+                        )
+                        .set(local::not_before.eq(None::<DateTime<Utc>>))
+                        .get_results(con)
+                        .await?;
+                    let remote_ids: Vec<Uuid> = update(remote::query_task_remote)
+                        .filter(
+                            remote::status
+                                .eq(QueryTaskRemoteStatus::Queued)
+                                .and(remote::not_before.le(now_val)),
+                        )
+                        .set(remote::not_before.eq(None::<DateTime<Utc>>))
+                        .returning(remote::id)
+                        .get_results(con)
+                        .await?;
+                    let messages = local_task_messages(&tasks)
+                        .into_iter()
+                        .chain(remote_ids.iter().map(|id| {
+                            GenericMessage::RemoteQueryTask(QueryTaskMessage { id: *id })
+                        }))
+                        .collect();
+                    insert_outbox_messages(con, messages).await?;
+                    Ok(tasks.iter().map(|t| t.id).chain(remote_ids).collect())
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
     pub async fn update_remote_task_status(
         &mut self,
         id_val: Uuid,
@@ -231,4 +1244,339 @@ impl<'a> PgDb<'a> {
             .await?;
         Ok(())
     }
+
+    /// Records an attempt to submit a [QueryTaskRemote] to its relay, which failed with
+    /// error_val if set, and moves the task to status_val. A task which stays Queued is retried
+    /// once retry_at has passed, see [PgDb::release_deferred_tasks].
+    pub async fn record_remote_task_attempt(
+        &mut self,
+        id_val: Uuid,
+        error_val: Option<&str>,
+        status_val: QueryTaskRemoteStatus,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        use schema::query_task_remote::dsl::*;
+        update(query_task_remote)
+            .filter(id.eq(id_val))
+            .set((
+                status.eq(status_val),
+                attempts.eq(attempts + 1),
+                error.eq(error_val),
+                not_before.eq(retry_at),
+            ))
+            .execute(&mut self.con)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crud::test_utils::test_db;
+
+    fn local_messages(ids: &[Uuid]) -> Vec<GenericMessage> {
+        ids.iter()
+            .map(|id| GenericMessage::LocalQueryTask(QueryTaskMessage { id: *id }))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_task_message_is_claimed_once() {
+        let Some(test_db) = test_db().await else {
+            return;
+        };
+        let mut db = PgDb::try_from_pool(&test_db.pool).await.unwrap();
+        let task_id = db
+            .create_test_task(Utc::now(), QueryTaskStatus::Queued)
+            .await;
+
+        // The same message is delivered twice, only its first delivery executes the task
+        assert!(db.claim_query_task(task_id).await.unwrap());
+        assert!(!db.claim_query_task(task_id).await.unwrap());
+        assert_eq!(
+            db.test_task_state(task_id).await,
+            (QueryTaskStatus::InProgress, 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_requeue_orphaned_tasks() {
+        let Some(test_db) = test_db().await else {
+            return;
+        };
+        let mut db = PgDb::try_from_pool(&test_db.pool).await.unwrap();
+        let received_at = Utc::now() - chrono::Duration::hours(1);
+        let never_published = db
+            .create_test_task(received_at, QueryTaskStatus::Queued)
+            .await;
+        let pending = db
+            .create_test_task(received_at, QueryTaskStatus::Queued)
+            .await;
+        let just_published = db
+            .create_test_task(received_at, QueryTaskStatus::Queued)
+            .await;
+        let remote = db
+            .create_test_remote_task(received_at, QueryTaskRemoteStatus::Queued)
+            .await;
+        db.create_test_task(received_at, QueryTaskStatus::Complete)
+            .await;
+        insert_outbox_messages(&mut db.con, local_messages(&[pending, just_published]))
+            .await
+            .unwrap();
+        let published = db.get_outbox_messages(Utc::now(), 10).await.unwrap();
+        db.delete_outbox_message(published[1].id).await.unwrap();
+
+        // A task published recently is not orphaned, even though its request is old
+        let orphaned_before = Utc::now() - chrono::Duration::minutes(5);
+        assert_eq!(db.requeue_orphaned_tasks(orphaned_before).await.unwrap(), 2);
+        assert_eq!(
+            db.test_outbox_task_ids().await,
+            vec![pending, never_published, remote]
+        );
+
+        // Once it was published long enough ago, it is requeued, but no task is requeued twice
+        assert_eq!(db.requeue_orphaned_tasks(Utc::now()).await.unwrap(), 1);
+        assert_eq!(
+            db.test_outbox_task_ids().await,
+            vec![pending, never_published, remote, just_published]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_remote_submission_is_released_once_its_backoff_elapsed() {
+        let Some(test_db) = test_db().await else {
+            return;
+        };
+        let mut db = PgDb::try_from_pool(&test_db.pool).await.unwrap();
+        let received_at = Utc::now() - chrono::Duration::hours(1);
+        let remote = db
+            .create_test_remote_task(received_at, QueryTaskRemoteStatus::Queued)
+            .await;
+        let retry_at = Utc::now() + chrono::Duration::minutes(1);
+        db.record_remote_task_attempt(
+            remote,
+            Some("unreachable"),
+            QueryTaskRemoteStatus::Queued,
+            Some(retry_at),
+        )
+        .await
+        .unwrap();
+
+        // A task waiting for its retry is neither orphaned nor released early
+        assert_eq!(db.requeue_orphaned_tasks(Utc::now()).await.unwrap(), 0);
+        assert!(db
+            .release_deferred_tasks(Utc::now())
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(db.test_outbox_task_ids().await.is_empty());
+
+        assert_eq!(
+            db.release_deferred_tasks(retry_at).await.unwrap(),
+            vec![remote]
+        );
+        assert_eq!(db.test_outbox_task_ids().await, vec![remote]);
+        let (task, _) = db.get_remote_query_task(remote).await.unwrap();
+        assert_eq!((task.attempts, task.not_before), (1, None));
+    }
+
+    async fn start(
+        db: &mut PgDb<'_>,
+        flight: &NewFlightStream,
+        stale_before: DateTime<Utc>,
+    ) -> (Option<FlightStreamStatus>, bool) {
+        let (existing, started) = db.start_flight_stream(flight, stale_before).await.unwrap();
+        (existing.map(|f| f.status), started)
+    }
+
+    #[tokio::test]
+    async fn test_flight_stream_is_only_replaced_once_failed_or_stale() {
+        let Some(test_db) = test_db().await else {
+            return;
+        };
+        let mut db = PgDb::try_from_pool(&test_db.pool).await.unwrap();
+        let remote_task_id = db
+            .create_test_remote_task(Utc::now(), QueryTaskRemoteStatus::Submitted)
+            .await;
+        let mut flight = NewFlightStream {
+            query_task_remote_id: remote_task_id,
+            remote_fingerprint: "sender".to_string(),
+            flight_id: Uuid::new_v4(),
+            status: FlightStreamStatus::Started,
+            freshness: None,
+        };
+        let stale_before = Utc::now() - chrono::Duration::minutes(10);
+        assert_eq!(start(&mut db, &flight, stale_before).await, (None, true));
+        // The same flight is sent again while the first do_put is still receiving it
+        assert_eq!(
+            start(&mut db, &flight, stale_before).await,
+            (Some(FlightStreamStatus::Started), false)
+        );
+        // Once the first do_put stopped sending heartbeats, its flight is replaced
+        assert_eq!(
+            start(&mut db, &flight, Utc::now() + chrono::Duration::minutes(1)).await,
+            (Some(FlightStreamStatus::Started), true)
+        );
+
+        flight.status = FlightStreamStatus::Failed;
+        db.upsert_flight_stream(&flight).await.unwrap();
+        flight.status = FlightStreamStatus::Started;
+        assert_eq!(
+            start(&mut db, &flight, stale_before).await,
+            (Some(FlightStreamStatus::Failed), true)
+        );
+
+        flight.status = FlightStreamStatus::Complete;
+        db.upsert_flight_stream(&flight).await.unwrap();
+        flight.status = FlightStreamStatus::Started;
+        assert_eq!(
+            start(&mut db, &flight, Utc::now()).await,
+            (Some(FlightStreamStatus::Complete), false)
+        );
+
+        // A flight is never replaced by another relay
+        flight.status = FlightStreamStatus::Failed;
+        db.upsert_flight_stream(&flight).await.unwrap();
+        flight.status = FlightStreamStatus::Started;
+        flight.remote_fingerprint = "impostor".to_string();
+        assert_eq!(
+            start(&mut db, &flight, Utc::now()).await,
+            (Some(FlightStreamStatus::Failed), false)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_message_is_retried_until_dead_and_replayed() {
+        let Some(test_db) = test_db().await else {
+            return;
+        };
+        let mut db = PgDb::try_from_pool(&test_db.pool).await.unwrap();
+        let task_id = db
+            .create_test_task(Utc::now(), QueryTaskStatus::Queued)
+            .await;
+        let msg = GenericMessage::LocalQueryTask(QueryTaskMessage { id: task_id });
+
+        // Below max_attempts the message is retried via the outbox
+        let letter = db
+            .record_failed_message(Some(&msg), "first", 2)
+            .await
+            .unwrap();
+        assert!(!letter.dead);
+        assert_eq!(db.test_outbox_task_ids().await, vec![task_id]);
+        assert!(db.get_dead_letters(false).await.unwrap().is_empty());
+        assert_eq!(db.get_dead_letters(true).await.unwrap().len(), 1);
+
+        // At max_attempts it is dead and its task failed
+        let letter = db
+            .record_failed_message(Some(&msg), "second", 2)
+            .await
+            .unwrap();
+        assert!(letter.dead);
+        assert_eq!(letter.error, "second");
+        assert_eq!(db.test_outbox_task_ids().await, vec![task_id]);
+        assert_eq!(db.test_task_state(task_id).await.0, QueryTaskStatus::Failed);
+        let dead = db.get_dead_letters(false).await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].task_id, Some(task_id));
+
+        // Replaying requeues the task and removes the dead letter
+        let replayed = db.replay_dead_letter(letter.id).await.unwrap().unwrap();
+        assert_eq!(replayed.id, letter.id);
+        assert_eq!(db.test_task_state(task_id).await.0, QueryTaskStatus::Queued);
+        assert_eq!(db.test_outbox_task_ids().await, vec![task_id, task_id]);
+        assert!(db.get_dead_letters(true).await.unwrap().is_empty());
+        assert!(db.replay_dead_letter(letter.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unparsable_message_is_dead_at_once() {
+        let Some(test_db) = test_db().await else {
+            return;
+        };
+        let mut db = PgDb::try_from_pool(&test_db.pool).await.unwrap();
+
+        let letter = db.record_failed_message(None, "garbage", 3).await.unwrap();
+        assert!(letter.dead);
+        assert_eq!(letter.task_id, None);
+        assert!(db.test_outbox_task_ids().await.is_empty());
+        assert!(matches!(
+            db.replay_dead_letter(letter.id).await,
+            Err(MeshError::InvalidQuery(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reclaim_stale_tasks() {
+        let Some(test_db) = test_db().await else {
+            return;
+        };
+        let mut db = PgDb::try_from_pool(&test_db.pool).await.unwrap();
+        let max_attempts = 2;
+        let exhausted = db
+            .create_test_task(Utc::now(), QueryTaskStatus::Queued)
+            .await;
+        assert!(db.claim_query_task(exhausted).await.unwrap());
+        let (requeued, failed) = db
+            .reclaim_stale_tasks(Utc::now(), max_attempts)
+            .await
+            .unwrap();
+        assert_eq!((requeued, failed), (vec![exhausted], vec![]));
+        assert!(db.claim_query_task(exhausted).await.unwrap());
+        let abandoned = db
+            .create_test_task(Utc::now(), QueryTaskStatus::Queued)
+            .await;
+        assert!(db.claim_query_task(abandoned).await.unwrap());
+
+        // Tasks with a recent heartbeat are left alone
+        let (requeued, failed) = db
+            .reclaim_stale_tasks(Utc::now() - chrono::Duration::minutes(1), max_attempts)
+            .await
+            .unwrap();
+        assert!(requeued.is_empty() && failed.is_empty());
+
+        let (requeued, failed) = db
+            .reclaim_stale_tasks(Utc::now(), max_attempts)
+            .await
+            .unwrap();
+        assert_eq!((requeued, failed), (vec![abandoned], vec![exhausted]));
+        assert_eq!(
+            db.test_task_state(abandoned).await,
+            (QueryTaskStatus::Queued, 1)
+        );
+        assert_eq!(
+            db.test_task_state(exhausted).await,
+            (QueryTaskStatus::Failed, 2)
+        );
+        assert_eq!(db.test_outbox_task_ids().await, vec![exhausted, abandoned]);
+    }
+
+    #[tokio::test]
+    async fn test_outbox_messages_are_locked_by_their_publisher() {
+        let Some(test_db) = test_db().await else {
+            return;
+        };
+        let mut db = PgDb::try_from_pool(&test_db.pool).await.unwrap();
+        let mut other_db = PgDb::try_from_pool(&test_db.pool).await.unwrap();
+        let task_id = db
+            .create_test_task(Utc::now(), QueryTaskStatus::Queued)
+            .await;
+        insert_outbox_messages(&mut db.con, local_messages(&[task_id]))
+            .await
+            .unwrap();
+
+        db.begin().await.unwrap();
+        assert_eq!(
+            db.get_outbox_messages(Utc::now(), 10).await.unwrap().len(),
+            1
+        );
+        other_db.begin().await.unwrap();
+        assert!(other_db
+            .get_outbox_messages(Utc::now(), 10)
+            .await
+            .unwrap()
+            .is_empty());
+        other_db.rollback().await.unwrap();
+        db.rollback().await.unwrap();
+    }
 }
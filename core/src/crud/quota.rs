@@ -0,0 +1,106 @@
+use crate::error::Result;
+use crate::model::quota::{Quota, QuotaSubject};
+
+use crate::schema;
+use chrono::{DateTime, Utc};
+use diesel::{delete, insert_into, prelude::*};
+use diesel_async::RunQueryDsl;
+
+use super::PgDb;
+
+/// Matches [QueryRequest][crate::model::query::QueryRequest]s with local tasks which are queued
+/// or executing, or remote tasks whose results were not yet received.
+const RUNNING_REQUEST_SQL: &str = "(EXISTS (SELECT 1 FROM query_task t \
+    WHERE t.query_request_id = query_request.id AND t.status IN ('queued', 'in_progress')) \
+    OR EXISTS (SELECT 1 FROM query_task_remote r WHERE r.query_request_id = query_request.id \
+    AND (r.status = 'queued' OR (r.status = 'submitted' AND NOT EXISTS (SELECT 1 FROM \
+    incoming_flight_streams f WHERE f.query_task_remote_id = r.id \
+    AND f.status IN ('complete', 'failed'))))))";
+
+impl<'a> PgDb<'a> {
+    pub async fn upsert_quota(&mut self, val: &Quota) -> Result<()> {
+        use schema::quota::dsl::*;
+        insert_into(quota)
+            .values(val)
+            .on_conflict((subject, x509_sha256))
+            .do_update()
+            .set(val)
+            .execute(&mut self.con)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns every declared [Quota].
+    pub async fn get_quotas(&mut self) -> Result<Vec<Quota>> {
+        use schema::quota::dsl::*;
+        Ok(quota
+            .select(Quota::as_select())
+            .get_results(&mut self.con)
+            .await?)
+    }
+
+    pub async fn delete_quota(
+        &mut self,
+        subject_val: QuotaSubject,
+        x509_sha256_val: &str,
+    ) -> Result<usize> {
+        use schema::quota::dsl::*;
+        Ok(delete(
+            quota
+                .filter(subject.eq(subject_val))
+                .filter(x509_sha256.eq(x509_sha256_val)),
+        )
+        .execute(&mut self.con)
+        .await?)
+    }
+
+    /// Returns the declared [Quota] of the user or relay with x509_sha256_val, if any.
+    pub async fn get_quota(
+        &mut self,
+        subject_val: QuotaSubject,
+        x509_sha256_val: &str,
+    ) -> Result<Option<Quota>> {
+        use schema::quota::dsl::*;
+        Ok(quota
+            .filter(subject.eq(subject_val))
+            .filter(x509_sha256.eq(x509_sha256_val))
+            .select(Quota::as_select())
+            .first(&mut self.con)
+            .await
+            .optional()?)
+    }
+
+    /// Counts the [QueryRequest][crate::model::query::QueryRequest]s received since since,
+    /// which a user submitted directly or a peer relay forwarded, depending on subject_val. If
+    /// running is set, only requests which are still running are counted.
+    pub async fn count_subject_requests_since(
+        &mut self,
+        subject_val: QuotaSubject,
+        x509_sha256_val: &str,
+        since: DateTime<Utc>,
+        running: bool,
+    ) -> Result<i64> {
+        use diesel::dsl::sql;
+        use diesel::sql_types::{Bool, Text};
+        use schema::query_request::dsl::*;
+        use schema::relays::dsl as relay;
+        let mut query = query_request.filter(received_at.ge(since)).into_boxed();
+        query = match subject_val {
+            QuotaSubject::User => query.filter(
+                sql::<Bool>("origin_info -> 'origin_user' ->> 'x509_sha256' = ")
+                    .bind::<Text, _>(x509_sha256_val.to_string()),
+            ),
+            QuotaSubject::Relay => query.filter(
+                relay_id.eq_any(
+                    relay::relays
+                        .filter(relay::x509_sha256.eq(x509_sha256_val.to_string()))
+                        .select(relay::id),
+                ),
+            ),
+        };
+        if running {
+            query = query.filter(sql::<Bool>(RUNNING_REQUEST_SQL));
+        }
+        Ok(query.count().get_result(&mut self.con).await?)
+    }
+}
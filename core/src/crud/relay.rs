@@ -1,10 +1,13 @@
+use crate::error::MeshError;
 use crate::model::access_control::{RelaySourcePermission, SourcePermission};
-use crate::model::relay::Relay;
+use crate::model::relay::{NewRelayInvite, NewRelayStatus, Relay, RelayInvite, RelayStatus};
 use crate::{error::Result, model::relay::NewRelay};
 
 use crate::schema;
-use diesel::{insert_into, prelude::*};
-use diesel_async::RunQueryDsl;
+use chrono::Utc;
+use diesel::{delete, insert_into, prelude::*};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
 use uuid::Uuid;
 
 use super::PgDb;
@@ -32,18 +35,106 @@ impl<'a> PgDb<'a> {
             .await?)
     }
 
+    /// Returns every peer [Relay] ordered by name.
+    pub async fn get_relays(&mut self) -> Result<Vec<Relay>> {
+        use schema::relays::dsl::*;
+        Ok(relays
+            .order(name.asc())
+            .select(Relay::as_select())
+            .get_results(&mut self.con)
+            .await?)
+    }
+
+    /// Returns the name and PEM encoded certificate of every peer [Relay] whose certificate is
+    /// stored.
+    pub async fn get_relay_certificates(&mut self) -> Result<Vec<(String, Vec<u8>)>> {
+        use schema::relays::dsl::*;
+        Ok(relays
+            .filter(x509_cert.is_not_null())
+            .select((name, x509_cert.assume_not_null()))
+            .load(&mut self.con)
+            .await?)
+    }
+
     pub async fn get_relay_by_name(&mut self, name_val: &str) -> Result<Relay> {
         use schema::relays::dsl::*;
         Ok(relays
             .filter(name.eq(name_val))
+            .select(Relay::as_select())
             .get_result(&mut self.con)
             .await?)
     }
 
+    pub async fn create_relay_invite(&mut self, val: &NewRelayInvite) -> Result<RelayInvite> {
+        use schema::relay_invites::dsl::*;
+        Ok(insert_into(relay_invites)
+            .values(val)
+            .returning(RelayInvite::as_returning())
+            .get_result(&mut self.con)
+            .await?)
+    }
+
+    pub async fn get_relay_invite(&mut self, invite_id: &Uuid) -> Result<RelayInvite> {
+        use schema::relay_invites::dsl::*;
+        Ok(relay_invites
+            .filter(id.eq(invite_id))
+            .select(RelayInvite::as_select())
+            .get_result(&mut self.con)
+            .await?)
+    }
+
+    /// Marks a [RelayInvite] as redeemed and registers the redeeming [Relay] under the
+    /// peer_name of the invite, in a single transaction. Fails if the invite expired or was
+    /// already redeemed.
+    pub async fn redeem_relay_invite(
+        &mut self,
+        invite_id: &Uuid,
+        peer: &NewRelay,
+    ) -> Result<Relay> {
+        let invite_id = *invite_id;
+        (*self.con)
+            .transaction::<_, MeshError, _>(|con| {
+                async move {
+                    use schema::relay_invites::dsl as inv;
+                    use schema::relays::dsl as rel;
+                    let now = Utc::now();
+                    let claimed = diesel::update(inv::relay_invites)
+                        .filter(inv::id.eq(invite_id))
+                        .filter(inv::redeemed_at.is_null())
+                        .filter(inv::expires_at.gt(now))
+                        .set(inv::redeemed_at.eq(now))
+                        .execute(con)
+                        .await?;
+                    if claimed == 0 {
+                        return Err(MeshError::InvalidQuery(format!(
+                            "Invite {invite_id} does not exist, expired or was already redeemed!"
+                        )));
+                    }
+                    let relay = insert_into(rel::relays)
+                        .values(peer)
+                        .on_conflict(rel::name)
+                        .do_update()
+                        .set(peer)
+                        .returning(Relay::as_returning())
+                        .get_result(con)
+                        .await?;
+                    diesel::update(inv::relay_invites)
+                        .filter(inv::id.eq(invite_id))
+                        .set(inv::redeemed_by.eq(relay.id))
+                        .execute(con)
+                        .await?;
+                    Ok(relay)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
     pub async fn get_relay_by_id(&mut self, id_val: &Uuid) -> Result<Relay> {
         use schema::relays::dsl::*;
         Ok(relays
             .filter(id.eq(id_val))
+            .select(Relay::as_select())
             .get_result(&mut self.con)
             .await?)
     }
@@ -86,4 +177,65 @@ impl<'a> PgDb<'a> {
             .await
             .optional()?)
     }
+
+    /// Returns the [RelaySourcePermission]s of every [Relay].
+    pub async fn get_relay_source_permissions(&mut self) -> Result<Vec<RelaySourcePermission>> {
+        use schema::relay_source_permission::dsl::*;
+        Ok(relay_source_permission.get_results(&mut self.con).await?)
+    }
+
+    /// Deletes the peer [Relay] named name_val along with its mappings, permissions and the
+    /// query requests it forwarded, returning the number of deleted relays.
+    pub async fn delete_relay(&mut self, name_val: &str) -> Result<usize> {
+        use schema::relays::dsl::*;
+        Ok(delete(relays.filter(name.eq(name_val)))
+            .execute(&mut self.con)
+            .await?)
+    }
+
+    /// Records the result of probing a peer [Relay], updating the last_seen_at of each endpoint
+    /// only if it was reached. Returns the previously recorded status, if any.
+    pub async fn record_relay_status(
+        &mut self,
+        val: &NewRelayStatus,
+    ) -> Result<Option<RelayStatus>> {
+        use schema::relay_status::dsl::*;
+        let previous = relay_status
+            .filter(relay_id.eq(val.relay_id))
+            .get_result::<RelayStatus>(&mut self.con)
+            .await
+            .optional()?;
+        let rest_seen = match val.rest_latency_ms {
+            Some(_) => Some(val.checked_at),
+            None => previous.as_ref().and_then(|p| p.rest_last_seen_at),
+        };
+        let flight_seen = match val.flight_latency_ms {
+            Some(_) => Some(val.checked_at),
+            None => previous.as_ref().and_then(|p| p.flight_last_seen_at),
+        };
+        let last_seen = (
+            rest_last_seen_at.eq(rest_seen),
+            flight_last_seen_at.eq(flight_seen),
+        );
+        insert_into(relay_status)
+            .values((val, last_seen))
+            .on_conflict(relay_id)
+            .do_update()
+            .set((val, last_seen))
+            .execute(&mut self.con)
+            .await?;
+        Ok(previous)
+    }
+
+    /// Returns every peer [Relay] ordered by name, along with its [RelayStatus] if it was
+    /// probed.
+    pub async fn get_relay_statuses(&mut self) -> Result<Vec<(Relay, Option<RelayStatus>)>> {
+        use schema::{relay_status, relays};
+        Ok(relays::table
+            .left_join(relay_status::table)
+            .order(relays::name.asc())
+            .select((Relay::as_select(), Option::<RelayStatus>::as_select()))
+            .load(&mut self.con)
+            .await?)
+    }
 }
@@ -0,0 +1,192 @@
+use crate::error::{MeshError, Result};
+use crate::model::access_control::{RoleSourcePermission, SourcePermission};
+use crate::model::role::Role;
+
+use crate::schema;
+use diesel::{delete, insert_into, prelude::*, upsert::excluded};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use uuid::Uuid;
+
+use super::PgDb;
+
+impl<'a> PgDb<'a> {
+    pub async fn upsert_role(&mut self, name_val: &str) -> Result<Role> {
+        use schema::roles::dsl::*;
+        Ok(insert_into(roles)
+            .values(name.eq(name_val))
+            .on_conflict(name)
+            .do_update()
+            .set(name.eq(excluded(name)))
+            .returning(Role::as_returning())
+            .get_result(&mut self.con)
+            .await?)
+    }
+
+    /// Returns every [Role] ordered by name.
+    pub async fn get_roles(&mut self) -> Result<Vec<Role>> {
+        use schema::roles::dsl::*;
+        Ok(roles
+            .order(name.asc())
+            .select(Role::as_select())
+            .get_results(&mut self.con)
+            .await?)
+    }
+
+    /// Deletes the [Role] named name_val along with its members and permissions, returning the
+    /// number of deleted roles.
+    pub async fn delete_role(&mut self, name_val: &str) -> Result<usize> {
+        use schema::roles::dsl::*;
+        Ok(delete(roles.filter(name.eq(name_val)))
+            .execute(&mut self.con)
+            .await?)
+    }
+
+    /// Replaces the member users and relays of the [Role] with id role_id_val.
+    pub async fn set_role_members(
+        &mut self,
+        role_id_val: &Uuid,
+        user_ids: &[Uuid],
+        relay_ids: &[Uuid],
+    ) -> Result<()> {
+        use schema::relay_role::dsl as relay_role;
+        use schema::user_role::dsl as user_role;
+        let role_id_val = *role_id_val;
+        (*self.con)
+            .transaction::<_, MeshError, _>(|con| {
+                async move {
+                    delete(user_role::user_role.filter(user_role::role_id.eq(role_id_val)))
+                        .execute(con)
+                        .await?;
+                    delete(relay_role::relay_role.filter(relay_role::role_id.eq(role_id_val)))
+                        .execute(con)
+                        .await?;
+                    let users = user_ids
+                        .iter()
+                        .map(|u| (user_role::role_id.eq(role_id_val), user_role::user_id.eq(u)))
+                        .collect::<Vec<_>>();
+                    insert_into(user_role::user_role)
+                        .values(&users)
+                        .on_conflict_do_nothing()
+                        .execute(con)
+                        .await?;
+                    let relays = relay_ids
+                        .iter()
+                        .map(|r| {
+                            (
+                                relay_role::role_id.eq(role_id_val),
+                                relay_role::relay_id.eq(r),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    insert_into(relay_role::relay_role)
+                        .values(&relays)
+                        .on_conflict_do_nothing()
+                        .execute(con)
+                        .await?;
+                    Ok(())
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    pub async fn upsert_role_source_permission(
+        &mut self,
+        role_id_val: &Uuid,
+        data_source_id_val: &Uuid,
+        source_permission_val: &SourcePermission,
+    ) -> Result<()> {
+        use schema::role_source_permission::dsl::*;
+        let record = (
+            role_id.eq(role_id_val),
+            data_source_id.eq(data_source_id_val),
+            source_permission.eq(source_permission_val),
+        );
+        insert_into(role_source_permission)
+            .values(&record)
+            .on_conflict((data_source_id, role_id))
+            .do_update()
+            .set(record)
+            .execute(&mut self.con)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the [SourcePermission]s for the source with id source_id_val of every role the
+    /// user with user_fingerprint is a member of.
+    pub async fn get_user_role_source_permissions(
+        &mut self,
+        user_fingerprint: &str,
+        source_id_val: &Uuid,
+    ) -> Result<Vec<SourcePermission>> {
+        use schema::role_source_permission::dsl::*;
+        use schema::user_role::dsl as user_role;
+        use schema::users::dsl as users;
+        Ok(role_source_permission
+            .inner_join(user_role::user_role.on(user_role::role_id.eq(role_id)))
+            .inner_join(users::users.on(users::id.eq(user_role::user_id)))
+            .filter(
+                users::x509_sha256
+                    .eq(user_fingerprint)
+                    .and(data_source_id.eq(source_id_val)),
+            )
+            .select(source_permission)
+            .load(&mut self.con)
+            .await?)
+    }
+
+    /// Returns the [SourcePermission]s for the source with id source_id_val of every role the
+    /// relay with relay_id_val is a member of.
+    pub async fn get_relay_role_source_permissions(
+        &mut self,
+        relay_id_val: &Uuid,
+        source_id_val: &Uuid,
+    ) -> Result<Vec<SourcePermission>> {
+        use schema::relay_role::dsl as relay_role;
+        use schema::role_source_permission::dsl::*;
+        Ok(role_source_permission
+            .inner_join(relay_role::relay_role.on(relay_role::role_id.eq(role_id)))
+            .filter(
+                relay_role::relay_id
+                    .eq(relay_id_val)
+                    .and(data_source_id.eq(source_id_val)),
+            )
+            .select(source_permission)
+            .load(&mut self.con)
+            .await?)
+    }
+
+    /// Returns the [SourcePermission]s of every role.
+    pub async fn get_role_source_permissions(&mut self) -> Result<Vec<RoleSourcePermission>> {
+        use schema::role_source_permission::dsl::*;
+        Ok(role_source_permission
+            .select(RoleSourcePermission::as_select())
+            .get_results(&mut self.con)
+            .await?)
+    }
+
+    /// Returns the role id and certificate fingerprint of every user who is a member of a role.
+    pub async fn get_role_users(&mut self) -> Result<Vec<(Uuid, String)>> {
+        use schema::user_role::dsl::*;
+        use schema::users::dsl as users;
+        Ok(user_role
+            .inner_join(users::users)
+            .order(users::x509_sha256.asc())
+            .select((role_id, users::x509_sha256))
+            .load(&mut self.con)
+            .await?)
+    }
+
+    /// Returns the role id and relay name of every relay which is a member of a role.
+    pub async fn get_role_relays(&mut self) -> Result<Vec<(Uuid, String)>> {
+        use schema::relay_role::dsl::*;
+        use schema::relays::dsl as relays;
+        Ok(relay_role
+            .inner_join(relays::relays)
+            .order(relays::name.asc())
+            .select((role_id, relays::name))
+            .load(&mut self.con)
+            .await?)
+    }
+}
@@ -0,0 +1,186 @@
+//! Harness for tests which need a Postgres database. They are skipped unless TEST_DATABASE_URL
+//! points at a database which they may wipe, and run one at a time since they share it.
+use std::env;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use diesel::{insert_into, prelude::*, sql_query, Connection, PgConnection};
+use diesel_async::pooled_connection::{bb8::Pool, AsyncDieselConnectionManager};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use diesel_migrations::MigrationHarness;
+use tokio::sync::{Mutex, MutexGuard};
+use uuid::Uuid;
+
+use crate::model::query::{QueryTaskRemoteStatus, QueryTaskStatus};
+use crate::schema;
+
+use super::{PgDb, MIGRATIONS};
+
+static DB_LOCK: Mutex<()> = Mutex::const_new(());
+static MIGRATED: OnceLock<()> = OnceLock::new();
+
+/// Empties every table except the migration history.
+const TRUNCATE_ALL: &str = "DO $$ DECLARE t text; BEGIN
+    FOR t IN SELECT tablename FROM pg_tables
+        WHERE schemaname = 'public' AND tablename <> '__diesel_schema_migrations' LOOP
+        EXECUTE format('TRUNCATE TABLE %I CASCADE', t);
+    END LOOP;
+END $$";
+
+/// An empty, migrated test database, which no other test uses while this is held.
+pub(crate) struct TestDb {
+    pub url: String,
+    pub pool: Pool<AsyncPgConnection>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+/// Returns the test database with every table emptied, or None if TEST_DATABASE_URL is unset.
+pub(crate) async fn test_db() -> Option<TestDb> {
+    let Ok(url) = env::var("TEST_DATABASE_URL") else {
+        eprintln!("TEST_DATABASE_URL is not set, skipping database test");
+        return None;
+    };
+    let lock = DB_LOCK.lock().await;
+    MIGRATED.get_or_init(|| {
+        PgConnection::establish(&url)
+            .expect("failed to connect to TEST_DATABASE_URL")
+            .run_pending_migrations(MIGRATIONS)
+            .expect("failed to migrate the test database");
+    });
+    let pool = Pool::builder()
+        .max_size(4)
+        .build(AsyncDieselConnectionManager::<AsyncPgConnection>::new(&url))
+        .await
+        .expect("failed to create test database pool");
+    sql_query(TRUNCATE_ALL)
+        .execute(&mut pool.get().await.expect("failed to connect"))
+        .await
+        .expect("failed to empty the test database");
+    Some(TestDb {
+        url,
+        pool,
+        _lock: lock,
+    })
+}
+
+impl<'a> PgDb<'a> {
+    /// Creates a local task with the given status of a request received at received_at, along
+    /// with the relay, connection and source it refers to.
+    pub(crate) async fn create_test_task(
+        &mut self,
+        received_at: DateTime<Utc>,
+        status: QueryTaskStatus,
+    ) -> Uuid {
+        let (request_id, _) = self.create_test_request(received_at).await;
+        let connection_id: Uuid = insert_into(schema::data_connection::table)
+            .values((
+                schema::data_connection::name.eq(Uuid::new_v4().to_string()),
+                schema::data_connection::connection_options.eq(serde_json::json!({})),
+            ))
+            .returning(schema::data_connection::id)
+            .get_result(&mut self.con)
+            .await
+            .unwrap();
+        let source_id: Uuid = insert_into(schema::data_source::table)
+            .values((
+                schema::data_source::name.eq(Uuid::new_v4().to_string()),
+                schema::data_source::source_sql.eq(""),
+                schema::data_source::data_connection_id.eq(connection_id),
+                schema::data_source::source_options.eq(serde_json::json!({})),
+            ))
+            .returning(schema::data_source::id)
+            .get_result(&mut self.con)
+            .await
+            .unwrap();
+        insert_into(schema::query_task::table)
+            .values((
+                schema::query_task::query_request_id.eq(request_id),
+                schema::query_task::data_source_id.eq(source_id),
+                schema::query_task::task.eq(serde_json::json!({})),
+                schema::query_task::status.eq(status),
+            ))
+            .returning(schema::query_task::id)
+            .get_result(&mut self.con)
+            .await
+            .unwrap()
+    }
+
+    /// Creates a remote task with the given status of a request received at received_at.
+    pub(crate) async fn create_test_remote_task(
+        &mut self,
+        received_at: DateTime<Utc>,
+        status: QueryTaskRemoteStatus,
+    ) -> Uuid {
+        let (request_id, relay_id) = self.create_test_request(received_at).await;
+        insert_into(schema::query_task_remote::table)
+            .values((
+                schema::query_task_remote::query_request_id.eq(request_id),
+                schema::query_task_remote::relay_id.eq(relay_id),
+                schema::query_task_remote::task.eq(serde_json::json!({})),
+                schema::query_task_remote::status.eq(status),
+            ))
+            .returning(schema::query_task_remote::id)
+            .get_result(&mut self.con)
+            .await
+            .unwrap()
+    }
+
+    /// Returns the status and number of attempts of a local task.
+    pub(crate) async fn test_task_state(&mut self, id_val: Uuid) -> (QueryTaskStatus, i32) {
+        use schema::query_task::dsl::*;
+        query_task
+            .filter(id.eq(id_val))
+            .select((status, attempts))
+            .get_result(&mut self.con)
+            .await
+            .unwrap()
+    }
+
+    /// Returns the ids of the tasks which have a message in the outbox.
+    pub(crate) async fn test_outbox_task_ids(&mut self) -> Vec<Uuid> {
+        use schema::task_outbox::dsl::*;
+        task_outbox
+            .order(id.asc())
+            .select(message)
+            .load::<serde_json::Value>(&mut self.con)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| {
+                serde_json::from_value::<crate::messaging::GenericMessage>(m)
+                    .unwrap()
+                    .task_id()
+            })
+            .collect()
+    }
+
+    async fn create_test_request(&mut self, received_at: DateTime<Utc>) -> (Uuid, Uuid) {
+        let name = Uuid::new_v4().to_string();
+        let relay_id: Uuid = insert_into(schema::relays::table)
+            .values((
+                schema::relays::name.eq(&name),
+                schema::relays::rest_endpoint.eq(""),
+                schema::relays::flight_endpoint.eq(""),
+                schema::relays::x509_sha256.eq(&name),
+                schema::relays::x509_subject.eq(""),
+                schema::relays::x509_issuer.eq(""),
+            ))
+            .returning(schema::relays::id)
+            .get_result(&mut self.con)
+            .await
+            .unwrap();
+        let request_id: Uuid = insert_into(schema::query_request::table)
+            .values((
+                schema::query_request::originator_request_id.eq(Uuid::new_v4()),
+                schema::query_request::sql.eq(""),
+                schema::query_request::relay_id.eq(relay_id),
+                schema::query_request::origin_info.eq(serde_json::json!({})),
+                schema::query_request::received_at.eq(received_at),
+            ))
+            .returning(schema::query_request::id)
+            .get_result(&mut self.con)
+            .await
+            .unwrap();
+        (request_id, relay_id)
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::error::Result;
+use crate::model::entity::{Entity, Information};
+use crate::model::relay::Relay;
+use crate::model::usage::{
+    EntityUsage, EntityUsageReport, InformationUsage, InformationUsageReport, NewRelayUsage,
+    RelayUsage, RelayUsageReport,
+};
+
+use crate::schema;
+use chrono::{DateTime, Utc};
+use diesel::{insert_into, prelude::*};
+use diesel_async::RunQueryDsl;
+
+use super::PgDb;
+
+impl<'a> PgDb<'a> {
+    pub async fn record_relay_usage(&mut self, val: &NewRelayUsage) -> Result<()> {
+        use schema::relay_usage::dsl::*;
+        insert_into(relay_usage)
+            .values(val)
+            .execute(&mut self.con)
+            .await?;
+        Ok(())
+    }
+
+    /// Aggregates all [RelayUsage] recorded within the passed time range by peer [Relay].
+    pub async fn get_relay_usage_report(
+        &mut self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<RelayUsageReport>> {
+        use schema::relay_usage::dsl::*;
+        use schema::relays::dsl as relay;
+
+        let mut query = relay_usage
+            .inner_join(relay::relays)
+            .select((RelayUsage::as_select(), Relay::as_select()))
+            .into_boxed();
+        if let Some(since) = since {
+            query = query.filter(recorded_at.ge(since));
+        }
+        if let Some(until) = until {
+            query = query.filter(recorded_at.lt(until));
+        }
+        let rows_val: Vec<(RelayUsage, Relay)> = query.load(&mut self.con).await?;
+
+        let mut reports: HashMap<_, RelayUsageReport> = HashMap::new();
+        for (usage, r) in rows_val {
+            let report = reports.entry(r.id).or_insert_with(|| RelayUsageReport {
+                relay_id: r.id,
+                relay_name: r.name,
+                tasks: 0,
+                rows: 0,
+                bytes: 0,
+            });
+            report.tasks += 1;
+            report.rows += usage.rows;
+            report.bytes += usage.bytes;
+        }
+        let mut reports = reports.into_values().collect::<Vec<_>>();
+        reports.sort_by(|a, b| a.relay_name.cmp(&b.relay_name));
+        Ok(reports)
+    }
+
+    /// Records that a query accessed an [Entity] and referenced the named [Information] about it.
+    pub async fn record_entity_access(
+        &mut self,
+        entity_name: &str,
+        info_names: &HashSet<String>,
+    ) -> Result<()> {
+        let now = Utc::now();
+        let entity = self.get_entity(entity_name).await?;
+        {
+            use schema::entity_usage::dsl::*;
+            insert_into(entity_usage)
+                .values(&EntityUsage {
+                    entity_id: entity.id,
+                    query_count: 1,
+                    last_accessed_at: now,
+                })
+                .on_conflict(entity_id)
+                .do_update()
+                .set((query_count.eq(query_count + 1), last_accessed_at.eq(now)))
+                .execute(&mut self.con)
+                .await?;
+        }
+
+        let info_usages = self
+            .get_information_for_entity(entity.id)
+            .await?
+            .into_iter()
+            .filter(|info| info_names.contains(&info.name))
+            .map(|info| InformationUsage {
+                information_id: info.id,
+                query_count: 1,
+                last_accessed_at: now,
+            })
+            .collect::<Vec<_>>();
+        if !info_usages.is_empty() {
+            use schema::information_usage::dsl::*;
+            insert_into(information_usage)
+                .values(&info_usages)
+                .on_conflict(information_id)
+                .do_update()
+                .set((query_count.eq(query_count + 1), last_accessed_at.eq(now)))
+                .execute(&mut self.con)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Reports the query counts of every [Entity] and its [Information], or only of the named
+    /// entity if one is passed. Entities and information which were never queried are included.
+    pub async fn get_entity_usage_report(
+        &mut self,
+        entity_name: Option<&str>,
+    ) -> Result<Vec<EntityUsageReport>> {
+        use schema::entities::dsl as entity;
+        use schema::entity_usage::dsl as entity_usage;
+        use schema::information::dsl as info;
+        use schema::information_usage::dsl as info_usage;
+
+        let mut entity_query = entity::entities
+            .left_join(entity_usage::entity_usage)
+            .select((
+                Entity::as_select(),
+                entity_usage::query_count.nullable(),
+                entity_usage::last_accessed_at.nullable(),
+            ))
+            .order(entity::name.asc())
+            .into_boxed();
+        let mut info_query = info::information
+            .inner_join(entity::entities)
+            .left_join(info_usage::information_usage)
+            .select((
+                Information::as_select(),
+                info_usage::query_count.nullable(),
+                info_usage::last_accessed_at.nullable(),
+            ))
+            .order((info::position.asc().nulls_last(), info::name.asc()))
+            .into_boxed();
+        if let Some(name_val) = entity_name {
+            entity_query = entity_query.filter(entity::name.eq(name_val));
+            info_query = info_query.filter(entity::name.eq(name_val));
+        }
+        let entity_rows: Vec<(Entity, Option<i64>, Option<DateTime<Utc>>)> =
+            entity_query.load(&mut self.con).await?;
+        let info_rows: Vec<(Information, Option<i64>, Option<DateTime<Utc>>)> =
+            info_query.load(&mut self.con).await?;
+
+        let mut info_reports: HashMap<_, Vec<InformationUsageReport>> = HashMap::new();
+        for (i, count, accessed) in info_rows {
+            info_reports
+                .entry(i.entity_id)
+                .or_default()
+                .push(InformationUsageReport {
+                    name: i.name,
+                    query_count: count.unwrap_or_default(),
+                    last_accessed_at: accessed,
+                });
+        }
+        Ok(entity_rows
+            .into_iter()
+            .map(|(e, count, accessed)| EntityUsageReport {
+                information: info_reports.remove(&e.id).unwrap_or_default(),
+                entity_name: e.name,
+                query_count: count.unwrap_or_default(),
+                last_accessed_at: accessed,
+            })
+            .collect())
+    }
+}
@@ -1,9 +1,10 @@
 use crate::model::access_control::{SourcePermission, UserSourcePermission};
 use crate::model::user::User;
+use crate::model::validation::{NewValidationRuleSet, ValidationRuleSet};
 use crate::{error::Result, model::user::NewUser};
 
 use crate::schema;
-use diesel::{insert_into, prelude::*};
+use diesel::{delete, insert_into, prelude::*};
 use diesel_async::RunQueryDsl;
 use uuid::Uuid;
 
@@ -27,6 +28,72 @@ impl<'a> PgDb<'a> {
             .await?)
     }
 
+    pub async fn upsert_validation_rule_set(
+        &mut self,
+        val: &NewValidationRuleSet,
+    ) -> Result<ValidationRuleSet> {
+        use schema::validation_rule_sets::dsl::*;
+        Ok(insert_into(validation_rule_sets)
+            .values(val)
+            .on_conflict(name)
+            .do_update()
+            .set(val)
+            .returning(ValidationRuleSet::as_returning())
+            .get_result(&mut self.con)
+            .await?)
+    }
+
+    /// Returns every [ValidationRuleSet] ordered by name.
+    pub async fn get_validation_rule_sets(&mut self) -> Result<Vec<ValidationRuleSet>> {
+        use schema::validation_rule_sets::dsl::*;
+        Ok(validation_rule_sets
+            .order(name.asc())
+            .select(ValidationRuleSet::as_select())
+            .get_results(&mut self.con)
+            .await?)
+    }
+
+    pub async fn delete_validation_rule_set(&mut self, name_val: &str) -> Result<usize> {
+        use schema::validation_rule_sets::dsl::*;
+        Ok(delete(validation_rule_sets.filter(name.eq(name_val)))
+            .execute(&mut self.con)
+            .await?)
+    }
+
+    pub async fn get_validation_rule_set(
+        &mut self,
+        name_val: &str,
+    ) -> Result<Option<ValidationRuleSet>> {
+        use schema::validation_rule_sets::dsl::*;
+        Ok(validation_rule_sets
+            .filter(name.eq(name_val))
+            .select(ValidationRuleSet::as_select())
+            .first(&mut self.con)
+            .await
+            .optional()?)
+    }
+
+    /// Returns every [User] ordered by certificate subject.
+    pub async fn get_users(&mut self) -> Result<Vec<User>> {
+        use schema::users::dsl::*;
+        Ok(users
+            .order(x509_subject.asc())
+            .select(User::as_select())
+            .get_results(&mut self.con)
+            .await?)
+    }
+
+    /// Returns the fingerprint and PEM encoded certificate of every user whose certificate is
+    /// stored, i.e. which was declared rather than registered on its first query.
+    pub async fn get_user_certificates(&mut self) -> Result<Vec<(String, Vec<u8>)>> {
+        use schema::users::dsl::*;
+        Ok(users
+            .filter(x509_cert.is_not_null())
+            .select((x509_sha256, x509_cert.assume_not_null()))
+            .load(&mut self.con)
+            .await?)
+    }
+
     pub async fn get_user_by_x509_fingerprint(&mut self, x509_sha256_val: &str) -> Result<User> {
         use schema::users::dsl::*;
         Ok(users
@@ -77,4 +144,22 @@ impl<'a> PgDb<'a> {
             .await
             .optional()?)
     }
+
+    /// Returns the [UserSourcePermission]s of every [User].
+    pub async fn get_user_source_permissions(&mut self) -> Result<Vec<UserSourcePermission>> {
+        use schema::user_source_permission::dsl::*;
+        Ok(user_source_permission
+            .select(UserSourcePermission::as_select())
+            .get_results(&mut self.con)
+            .await?)
+    }
+
+    /// Deletes the [User] with x509_sha256_val along with its permissions, returning the number
+    /// of deleted users.
+    pub async fn delete_user(&mut self, x509_sha256_val: &str) -> Result<usize> {
+        use schema::users::dsl::*;
+        Ok(delete(users.filter(x509_sha256.eq(x509_sha256_val)))
+            .execute(&mut self.con)
+            .await?)
+    }
 }
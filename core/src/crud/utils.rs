@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
 
 use crate::model::entity::Entity;
 use crate::model::{
@@ -62,3 +63,28 @@ pub(crate) fn joined_rows_to_map_remote(
     }
     outer_map
 }
+
+/// Removes values with a duplicate key, keeping the last occurrence. Postgres rejects a batched
+/// upsert which would update the same row twice.
+pub(crate) fn dedup_last_by_key<T, K: Eq + Hash>(vals: &[T], key: impl Fn(&T) -> K) -> Vec<&T> {
+    let mut seen = HashSet::new();
+    let mut out = vals
+        .iter()
+        .rev()
+        .filter(|v| seen.insert(key(v)))
+        .collect::<Vec<_>>();
+    out.reverse();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dedup_last_by_key;
+
+    #[test]
+    fn test_dedup_last_by_key() {
+        let vals = vec![("a", 1), ("b", 2), ("a", 3), ("c", 4)];
+        let deduped = dedup_last_by_key(&vals, |v| v.0);
+        assert_eq!(deduped, vec![&("b", 2), &("a", 3), &("c", 4)]);
+    }
+}
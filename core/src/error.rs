@@ -21,6 +21,8 @@ pub enum MeshError {
     InvalidQuery(String),
     InvalidTransform(Value),
     RemoteError(String),
+    /// The requester exceeded a request rate, and may retry later.
+    RateLimited(String),
     DuplicateQueryRequest(Box<QueryRequest>),
     EmptyQuery,
 }
@@ -42,6 +44,7 @@ impl fmt::Display for MeshError {
             MeshError::InvalidTransform(_) => write!(f, "Invalid Transformation"),
             MeshError::InvalidQuery(s) => write!(f, "invalid query: {}", s),
             MeshError::RemoteError(s) => write!(f, "Issue related to a remote relay: {}", s),
+            MeshError::RateLimited(s) => write!(f, "rate limited: {}", s),
             MeshError::DuplicateQueryRequest(q) => write!(
                 f,
                 "Query {} has already been processed!",
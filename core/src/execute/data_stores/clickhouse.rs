@@ -0,0 +1,292 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+
+use arrow::ipc::reader::StreamReader;
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use async_trait::async_trait;
+use bytes::Bytes;
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use reqwest::{Certificate, Client, Response};
+use tokio::sync::mpsc::{self, Receiver};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+use url::Url;
+
+use crate::error::{MeshError, Result};
+use crate::model::data_stores::options::clickhouse::{ClickHouseConnection, ClickHouseSource};
+use crate::model::query::Query;
+
+use super::{CancellableStream, QueryRunner};
+
+/// Number of chunks of the response body, and of decoded [RecordBatch]es, buffered between
+/// the HTTP client and the IPC decoder.
+const CHANNEL_CAPACITY: usize = 8;
+
+/// Provides [QueryRunner] impl leveraging an external ClickHouse server as the execution
+/// engine. Results are requested in the ArrowStream format, so they are decoded directly into
+/// [RecordBatch]es without converting individual rows.
+pub struct ClickHouseRunner {
+    client: Client,
+    url: Url,
+    user: Option<String>,
+    password: Option<String>,
+}
+
+impl TryFrom<(ClickHouseConnection, ClickHouseSource)> for ClickHouseRunner {
+    type Error = MeshError;
+
+    fn try_from(value: (ClickHouseConnection, ClickHouseSource)) -> Result<Self> {
+        let (con, _source) = value;
+        let password = match &con.password {
+            Some(password) => Some(env::var(password).map_err(|_e| {
+                MeshError::Internal(format!(
+                    "Expected clickhouse password to be set in {password} \
+                env variable, but it is unset!"
+                ))
+            })?),
+            None => None,
+        };
+        let mut builder = Client::builder();
+        if let Some(bundle) = &con.ca_cert_bundle {
+            let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(bundle)?))?;
+            for cert in certs {
+                let cert = Certificate::from_der(&cert).map_err(|e| {
+                    MeshError::Internal(format!("Invalid CA cert in {bundle}: {e}"))
+                })?;
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        let client = builder
+            .build()
+            .map_err(|e| MeshError::Internal(format!("Unable to build ClickHouse client: {e}")))?;
+        Ok(Self {
+            client,
+            url: query_url(&con)?,
+            user: con.user,
+            password,
+        })
+    }
+}
+
+/// Returns the URL which queries are posted to, with the settings which make ClickHouse return
+/// results as an Arrow IPC stream.
+fn query_url(con: &ClickHouseConnection) -> Result<Url> {
+    let mut url = Url::parse(&con.url)?;
+    {
+        let mut query = url.query_pairs_mut();
+        if let Some(database) = &con.database {
+            query.append_pair("database", database);
+        }
+        query.append_pair("default_format", "ArrowStream");
+        // Otherwise String columns are returned as Binary
+        query.append_pair("output_format_arrow_string_as_string", "1");
+    }
+    Ok(url)
+}
+
+/// Casts each column of a [RecordBatch] to the type of the corresponding field of schema.
+fn cast_batch(
+    batch: RecordBatch,
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, DataFusionError> {
+    if batch.num_columns() != schema.fields().len() {
+        return Err(DataFusionError::Execution(format!(
+            "ClickHouse returned {} columns, but {} were expected!",
+            batch.num_columns(),
+            schema.fields().len()
+        )));
+    }
+    let columns = batch
+        .columns()
+        .iter()
+        .zip(schema.fields())
+        .map(|(array, field)| arrow::compute::cast(array, field.data_type()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+/// Blocking [Read] over the chunks of a response body, so it can be decoded by an IPC
+/// [StreamReader] as it arrives.
+struct ChannelReader {
+    rx: Receiver<io::Result<Bytes>>,
+    current: Bytes,
+}
+
+impl ChannelReader {
+    fn new(rx: Receiver<io::Result<Bytes>>) -> Self {
+        Self {
+            rx,
+            current: Bytes::new(),
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.rx.blocking_recv() {
+                Some(Ok(chunk)) => self.current = chunk,
+                Some(Err(e)) => return Err(e),
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current[..n]);
+        self.current = self.current.slice(n..);
+        Ok(n)
+    }
+}
+
+/// Forwards the chunks of a response body to a channel until it is consumed or the receiver
+/// is dropped.
+fn forward_body(mut response: Response) -> Receiver<io::Result<Bytes>> {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        loop {
+            let chunk = match response.chunk().await {
+                Ok(Some(chunk)) => Ok(chunk),
+                Ok(None) => break,
+                Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+            };
+            let failed = chunk.is_err();
+            if tx.send(chunk).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+#[async_trait]
+impl QueryRunner for ClickHouseRunner {
+    async fn execute_stream(
+        &mut self,
+        query: Query,
+        cancel: CancellationToken,
+    ) -> Result<SendableRecordBatchStream> {
+        debug!("Executing {query:?} on ClickHouseRunner");
+        let mut request = self.client.post(self.url.clone()).body(query.sql);
+        if let Some(user) = &self.user {
+            request = request.header("X-ClickHouse-User", user);
+        }
+        if let Some(password) = &self.password {
+            request = request.header("X-ClickHouse-Key", password);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| MeshError::RemoteError(format!("Failed to query clickhouse: {e}")))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(MeshError::RemoteError(format!(
+                "ClickHouse returned {status}: {body}"
+            )));
+        }
+
+        // The IPC reader is blocking, so it runs off the async runtime and hands decoded
+        // batches back over a channel.
+        let body = ChannelReader::new(forward_body(response));
+        let reader = tokio::task::spawn_blocking(move || StreamReader::try_new(body, None))
+            .await
+            .map_err(|e| MeshError::Internal(format!("ClickHouse reader panicked: {e}")))??;
+        let schema = match query.return_schema {
+            Some(schema) => SchemaRef::new(schema),
+            None => reader.schema(),
+        };
+        debug!("ClickHouse runner streaming data with arrow schema {schema}");
+
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let schema_clone = schema.clone();
+        tokio::task::spawn_blocking(move || {
+            for batch in reader {
+                let batch = batch
+                    .map_err(DataFusionError::from)
+                    .and_then(|b| cast_batch(b, schema_clone.clone()));
+                if tx.blocking_send(batch).is_err() {
+                    break;
+                }
+            }
+        });
+        let stream = Box::pin(RecordBatchStreamAdapter::new(
+            schema,
+            ReceiverStream::new(rx),
+        ));
+        // Dropping the stream on cancellation stops the decoder, which closes the connection
+        Ok(Box::pin(CancellableStream::new(stream, cancel)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::ipc::reader::StreamReader;
+    use arrow::ipc::writer::StreamWriter;
+    use arrow_array::{Int32Array, RecordBatch, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use bytes::Bytes;
+    use tokio::sync::mpsc;
+
+    use crate::error::Result;
+    use crate::model::data_stores::options::clickhouse::ClickHouseConnection;
+
+    use super::{cast_batch, query_url, ChannelReader};
+
+    #[test]
+    fn test_decode_chunked_arrow_stream() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec![Some("a"), None, Some("c")])),
+            ],
+        )?;
+        let mut writer = StreamWriter::try_new(vec![], &schema)?;
+        writer.write(&batch)?;
+        writer.write(&batch)?;
+        let ipc = Bytes::from(writer.into_inner()?);
+
+        // Split the stream at arbitrary points, as an HTTP body would be
+        let (tx, rx) = mpsc::channel(ipc.len());
+        for chunk in ipc.chunks(7) {
+            tx.try_send(Ok(Bytes::copy_from_slice(chunk))).unwrap();
+        }
+        drop(tx);
+
+        let reader = StreamReader::try_new(ChannelReader::new(rx), None)?;
+        let return_schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let batches = reader
+            .map(|b| cast_batch(b?, return_schema.clone()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].schema(), return_schema);
+        assert_eq!(batches[1].num_rows(), 3);
+
+        let url = query_url(&ClickHouseConnection {
+            url: "http://clickhouse:8123".to_string(),
+            database: Some("events".to_string()),
+            user: None,
+            password: None,
+            ca_cert_bundle: None,
+        })?;
+        assert_eq!(
+            url.as_str(),
+            "http://clickhouse:8123/?database=events&default_format=ArrowStream\
+            &output_format_arrow_string_as_string=1"
+        );
+        Ok(())
+    }
+}
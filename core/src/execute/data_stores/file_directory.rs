@@ -6,29 +6,39 @@ use datafusion::{
     common::{FileType, GetExt},
     datasource::{
         file_format::{csv::CsvFormat, json::JsonFormat, parquet::ParquetFormat},
-        listing::ListingOptions,
+        listing::{ListingOptions, ListingTableUrl},
+        physical_plan::ParquetExec,
     },
     error::DataFusionError,
-    physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
-    prelude::SessionContext,
+    physical_plan::{
+        execute_stream, stream::RecordBatchStreamAdapter, ExecutionPlan, SendableRecordBatchStream,
+    },
+    prelude::{SessionConfig, SessionContext},
 };
 
-use futures::StreamExt;
-use object_store::ObjectStore;
+use arrow_schema::Schema;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt};
+use object_store::{ObjectMeta, ObjectStore};
+use tokio_util::sync::CancellationToken;
 use tracing::debug;
 use url::Url;
 
 use crate::{
     error::MeshError,
     model::data_stores::options::{
-        file_directory::{FileDirectoryConnection, FileDirectorySource},
+        file_directory::{
+            DeclaredSchema, FileDirectoryConnection, FileDirectorySource, PartitionColumn,
+        },
         SourceFileType,
     },
+    model::query::ScanMetrics,
 };
 
 use crate::error::Result;
 
-use super::{initialize_object_store, Query, QueryRunner};
+use super::{initialize_object_store, CancellableStream, Query, QueryRunner};
+use crate::execute::udf::udf_registry;
 
 /// This runner is unqiue in that it uses in process query engine DataFusion to directly query
 /// raw files in an [ObjectStore].
@@ -37,6 +47,10 @@ pub struct FileDirectoryRunner {
     url: Url,
     file_type: SourceFileType,
     table_name: String,
+    declared_schema: Option<DeclaredSchema>,
+    partition_columns: Vec<PartitionColumn>,
+    /// The physical plan of the last executed query, retained to report [ScanMetrics].
+    plan: Option<Arc<dyn ExecutionPlan>>,
 }
 
 impl TryFrom<(FileDirectoryConnection, FileDirectorySource, String)> for FileDirectoryRunner {
@@ -44,78 +58,139 @@ impl TryFrom<(FileDirectoryConnection, FileDirectorySource, String)> for FileDir
 
     fn try_from(value: (FileDirectoryConnection, FileDirectorySource, String)) -> Result<Self> {
         let (con, source, table_name) = value;
-        let object_store = initialize_object_store(con.object_store_type, &source)?;
+        let object_store =
+            initialize_object_store(con.object_store_type, &source, &con.client_config)?;
         Ok(Self {
             object_store,
             url: Url::parse(&con.url)?,
             file_type: source.file_type,
             table_name,
+            declared_schema: source.declared_schema,
+            partition_columns: source.partition_columns,
+            plan: None,
         })
     }
 }
 
+impl FileDirectoryRunner {
+    fn listing_options(&self) -> ListingOptions {
+        match self.file_type {
+            SourceFileType::CSV => ListingOptions::new(Arc::new(CsvFormat::default()))
+                .with_file_extension(FileType::CSV.get_ext()),
+            SourceFileType::JSON => ListingOptions::new(Arc::new(JsonFormat::default()))
+                .with_file_extension(FileType::JSON.get_ext()),
+            SourceFileType::Parquet => ListingOptions::new(Arc::new(ParquetFormat::default()))
+                .with_file_extension(FileType::PARQUET.get_ext()),
+        }
+        .with_table_partition_cols(
+            self.partition_columns
+                .iter()
+                .map(|col| (col.name.clone(), col.data_type.clone()))
+                .collect(),
+        )
+    }
+
+    /// Infers the schema of each file individually and fails on the first file which is missing
+    /// a declared field or stores it with a different type.
+    async fn check_file_schemas(
+        &self,
+        ctx: &SessionContext,
+        listing_options: &ListingOptions,
+        declared: &Schema,
+    ) -> Result<()> {
+        let state = ctx.state();
+        let table_url = ListingTableUrl::parse(self.url.as_str())?;
+        let files: Vec<ObjectMeta> = table_url
+            .list_all_files(
+                &state,
+                self.object_store.as_ref(),
+                &listing_options.file_extension,
+            )
+            .await?
+            .try_collect()
+            .await?;
+        for file in files {
+            let file_schema = listing_options
+                .format
+                .infer_schema(&state, &self.object_store, std::slice::from_ref(&file))
+                .await?;
+            for field in declared.fields() {
+                match file_schema.field_with_name(field.name()) {
+                    Ok(f) if f.data_type() == field.data_type() => (),
+                    Ok(f) => {
+                        return Err(MeshError::InvalidQuery(format!(
+                            "File {} of source {} stores field {} as {} but {} is declared",
+                            file.location,
+                            self.table_name,
+                            field.name(),
+                            f.data_type(),
+                            field.data_type()
+                        )))
+                    }
+                    Err(_) => {
+                        return Err(MeshError::InvalidQuery(format!(
+                            "File {} of source {} is missing declared field {}",
+                            file.location,
+                            self.table_name,
+                            field.name()
+                        )))
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl QueryRunner for FileDirectoryRunner {
-    async fn execute_stream(&mut self, query: Query) -> Result<SendableRecordBatchStream> {
-        let ctx = SessionContext::new();
+    async fn execute_stream(
+        &mut self,
+        query: Query,
+        cancel: CancellationToken,
+    ) -> Result<SendableRecordBatchStream> {
+        let ctx = SessionContext::new_with_config(session_config());
         ctx.runtime_env()
             .register_object_store(&self.url, self.object_store.clone());
+        if let Ok(registry) = udf_registry().read() {
+            registry.register_all(&ctx);
+        }
 
-        match self.file_type {
-            SourceFileType::CSV => {
-                let file_format = CsvFormat::default();
-                let listing_options = ListingOptions::new(Arc::new(file_format))
-                    .with_file_extension(FileType::CSV.get_ext());
-                ctx.register_listing_table(
-                    &self.table_name,
-                    format!("{}", self.url),
-                    listing_options,
-                    None,
-                    None,
-                )
-                .await?;
-            }
-            SourceFileType::JSON => {
-                let file_format = JsonFormat::default();
-                let listing_options = ListingOptions::new(Arc::new(file_format))
-                    .with_file_extension(FileType::JSON.get_ext());
-                ctx.register_listing_table(
-                    &self.table_name,
-                    format!("{}", self.url),
-                    listing_options,
-                    None,
-                    None,
-                )
-                .await?;
-            }
-            SourceFileType::Parquet => {
-                let file_format = ParquetFormat::default();
-                let listing_options = ListingOptions::new(Arc::new(file_format))
-                    .with_file_extension(FileType::PARQUET.get_ext());
-                ctx.register_listing_table(
-                    &self.table_name,
-                    format!("{}", self.url),
-                    listing_options,
-                    None,
-                    None,
-                )
-                .await?;
+        let listing_options = self.listing_options();
+        let provided_schema = match &self.declared_schema {
+            Some(declared) => {
+                let schema = Arc::new(declared.schema.clone());
+                if declared.strict {
+                    self.check_file_schemas(&ctx, &listing_options, &schema)
+                        .await?;
+                }
+                Some(schema)
             }
+            None => None,
         };
+        ctx.register_listing_table(
+            &self.table_name,
+            format!("{}", self.url),
+            listing_options,
+            provided_schema,
+            None,
+        )
+        .await?;
 
         debug!("datafusion executing SQL: {}", query.sql);
         let df = ctx.sql(&query.sql).await?;
+        let plan = df.create_physical_plan().await?;
+        self.plan = Some(plan.clone());
+        let rb_stream = execute_stream(plan, ctx.task_ctx())?;
         // Check if a specific return_schema was specified, and if so
         // attempt to cast the output to the return_schema, otherwise,
         // just return the stream as-is.
-        match query.return_schema {
+        let rb_stream = match query.return_schema {
             Some(schema) => {
                 let schema: Arc<arrow_schema::Schema> = Arc::new(schema);
                 let schema_clone1 = schema.clone();
                 let schema_clone2 = schema.clone();
-                let stream = df
-                    .execute_stream()
-                    .await?
+                let stream = rb_stream
                     .map(move |maybe_batch| match maybe_batch {
                         Ok(rb) => rb
                             .columns()
@@ -133,9 +208,160 @@ impl QueryRunner for FileDirectoryRunner {
                             .map_err(DataFusionError::from),
                         Err(e) => Err(e),
                     });
-                Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+                Box::pin(RecordBatchStreamAdapter::new(schema, stream))
+            }
+            None => rb_stream,
+        };
+        // Dropping the stream on cancellation aborts the tasks executing the plan
+        Ok(Box::pin(CancellableStream::new(rb_stream, cancel)))
+    }
+
+    fn scan_metrics(&self) -> Option<ScanMetrics> {
+        let mut metrics = ScanMetrics::default();
+        let mut found_scan = false;
+        let mut nodes = vec![self.plan.clone()?];
+        while let Some(node) = nodes.pop() {
+            if let Some(parquet) = node.as_any().downcast_ref::<ParquetExec>() {
+                found_scan = true;
+                metrics.files_scanned += parquet
+                    .base_config()
+                    .file_groups
+                    .iter()
+                    .map(|g| g.len())
+                    .sum::<usize>();
+                if let Some(set) = parquet.metrics() {
+                    let count = |name: &str| set.sum_by_name(name).map_or(0, |m| m.as_usize());
+                    metrics.row_groups_pruned += count("row_groups_pruned_statistics")
+                        + count("row_groups_pruned_bloom_filter");
+                    metrics.page_index_rows_filtered += count("page_index_rows_filtered");
+                    metrics.pushdown_rows_filtered += count("pushdown_rows_filtered");
+                    metrics.bytes_scanned += count("bytes_scanned");
+                    metrics.output_rows += set.output_rows().unwrap_or(0);
+                }
             }
-            None => Ok(df.execute_stream().await?),
+            nodes.extend(node.children());
+        }
+        found_scan.then_some(metrics)
+    }
+
+    /// Reports the modification time of the most recently modified file of the source.
+    async fn freshness(&mut self) -> Result<Option<DateTime<Utc>>> {
+        let ctx = SessionContext::new_with_config(session_config());
+        let table_url = ListingTableUrl::parse(self.url.as_str())?;
+        let latest = table_url
+            .list_all_files(
+                &ctx.state(),
+                self.object_store.as_ref(),
+                &self.listing_options().file_extension,
+            )
+            .await?
+            .try_fold(None, |latest: Option<DateTime<Utc>>, file| async move {
+                Ok(latest.max(Some(file.last_modified)))
+            })
+            .await?;
+        Ok(latest)
+    }
+
+    /// Lists the files of the source rather than querying them, which checks access to the
+    /// ObjectStore without reading any data.
+    async fn probe(&mut self) -> Result<()> {
+        self.freshness().await?;
+        Ok(())
+    }
+}
+
+/// Configures DataFusion to prune Parquet row groups and pages using statistics and the page
+/// index, and to evaluate filters while decoding so that only matching rows are materialized.
+fn session_config() -> SessionConfig {
+    let mut config = SessionConfig::new();
+    let parquet = &mut config.options_mut().execution.parquet;
+    parquet.pruning = true;
+    parquet.enable_page_index = true;
+    parquet.pushdown_filters = true;
+    parquet.reorder_filters = true;
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::parquet::arrow::ArrowWriter;
+    use futures::TryStreamExt;
+    use tokio_util::sync::CancellationToken;
+    use uuid::Uuid;
+
+    use crate::error::Result;
+    use crate::execute::data_stores::QueryRunner;
+    use crate::model::data_stores::options::file_directory::{
+        FileDirectoryConnection, FileDirectorySource, PartitionColumn,
+    };
+    use crate::model::data_stores::options::{SourceFileType, SupportedObjectStore};
+    use crate::model::query::Query;
+
+    use super::FileDirectoryRunner;
+
+    #[tokio::test]
+    async fn test_hive_partition_pruning() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dataweb-{}", Uuid::new_v4()));
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        for (year, ids) in [(2023, vec![1, 2]), (2024, vec![3, 4, 5])] {
+            let partition = dir.join(format!("year={year}"));
+            fs::create_dir_all(&partition)?;
+            let batch =
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(ids))])?;
+            let mut writer = ArrowWriter::try_new(
+                File::create(partition.join("part-0.parquet"))?,
+                schema.clone(),
+                None,
+            )
+            .unwrap();
+            writer.write(&batch).unwrap();
+            writer.close().unwrap();
         }
+
+        let con = FileDirectoryConnection {
+            object_store_type: SupportedObjectStore::LocalFileSystem,
+            url: format!("file://{}/", dir.display()),
+            client_config: Default::default(),
+        };
+        let source = FileDirectorySource {
+            bucket: None,
+            region: None,
+            prefix: None,
+            file_type: SourceFileType::Parquet,
+            s3: None,
+            hdfs: None,
+            include: vec![],
+            exclude: vec![],
+            path_regex: None,
+            modified_after: None,
+            declared_schema: None,
+            partition_columns: vec![PartitionColumn {
+                name: "year".to_string(),
+                data_type: DataType::Int32,
+            }],
+        };
+        let mut runner = FileDirectoryRunner::try_from((con, source, "events".to_string()))?;
+        let batches: Vec<RecordBatch> = runner
+            .execute_stream(
+                Query {
+                    sql: "select id, year from events where year = 2024".to_string(),
+                    return_schema: None,
+                },
+                CancellationToken::new(),
+            )
+            .await?
+            .try_collect()
+            .await?;
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 3);
+        assert_eq!(batches[0].schema().field(1).data_type(), &DataType::Int32);
+        assert_eq!(runner.scan_metrics().unwrap().files_scanned, 1);
+        Ok(())
     }
 }
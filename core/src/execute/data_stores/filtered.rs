@@ -0,0 +1,234 @@
+use std::fmt::Display;
+use std::ops::Range;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::stream::{BoxStream, StreamExt};
+use glob::{MatchOptions, Pattern};
+use object_store::path::Path;
+use object_store::{
+    GetOptions, GetResult, ListResult, MultipartId, ObjectMeta, ObjectStore, PutOptions, PutResult,
+};
+use regex::Regex;
+use tokio::io::AsyncWrite;
+
+use crate::error::{MeshError, Result};
+use crate::model::data_stores::options::file_directory::FileDirectorySource;
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Decides which files listed in an [ObjectStore] belong to a
+/// [FileDirectorySource], based on its include and exclude globs, path regex and
+/// modified_after filters.
+#[derive(Debug, Clone)]
+pub struct FileFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    path_regex: Option<Regex>,
+    modified_after: Option<DateTime<Utc>>,
+}
+
+impl FileFilter {
+    /// Returns None if the source does not declare any filters.
+    pub fn try_from_source(source: &FileDirectorySource) -> Result<Option<Self>> {
+        if source.include.is_empty()
+            && source.exclude.is_empty()
+            && source.path_regex.is_none()
+            && source.modified_after.is_none()
+        {
+            return Ok(None);
+        }
+        let compile = |patterns: &Vec<String>| {
+            patterns
+                .iter()
+                .map(|p| {
+                    Pattern::new(p)
+                        .map_err(|e| MeshError::SerDe(format!("Invalid glob pattern {p}: {e}")))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Some(Self {
+            include: compile(&source.include)?,
+            exclude: compile(&source.exclude)?,
+            path_regex: source.path_regex.as_deref().map(Regex::new).transpose()?,
+            modified_after: source.modified_after,
+        }))
+    }
+
+    pub fn matches(&self, meta: &ObjectMeta) -> bool {
+        let path = meta.location.as_ref();
+        (self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|p| p.matches_with(path, MATCH_OPTIONS)))
+            && !self
+                .exclude
+                .iter()
+                .any(|p| p.matches_with(path, MATCH_OPTIONS))
+            && self.path_regex.as_ref().map_or(true, |r| r.is_match(path))
+            && self
+                .modified_after
+                .map_or(true, |after| meta.last_modified > after)
+    }
+}
+
+/// Wraps an [ObjectStore] so that listing only returns the files accepted by a [FileFilter].
+/// All other operations are passed through unchanged.
+#[derive(Debug)]
+pub struct FilteredStore {
+    inner: Arc<dyn ObjectStore>,
+    filter: FileFilter,
+}
+
+impl FilteredStore {
+    pub fn new(inner: Arc<dyn ObjectStore>, filter: FileFilter) -> Self {
+        Self { inner, filter }
+    }
+}
+
+impl Display for FilteredStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FilteredStore({})", self.inner)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for FilteredStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        bytes: Bytes,
+        opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        self.inner.put_opts(location, bytes, opts).await
+    }
+
+    async fn put_multipart(
+        &self,
+        location: &Path,
+    ) -> object_store::Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        self.inner.put_multipart(location).await
+    }
+
+    async fn abort_multipart(
+        &self,
+        location: &Path,
+        multipart_id: &MultipartId,
+    ) -> object_store::Result<()> {
+        self.inner.abort_multipart(location, multipart_id).await
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        self.inner.get_opts(location, options).await
+    }
+
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> object_store::Result<Bytes> {
+        self.inner.get_range(location, range).await
+    }
+
+    async fn head(&self, location: &Path) -> object_store::Result<ObjectMeta> {
+        self.inner.head(location).await
+    }
+
+    async fn delete(&self, location: &Path) -> object_store::Result<()> {
+        self.inner.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        self.inner
+            .list(prefix)
+            .filter(|result| {
+                let keep = match result {
+                    Ok(meta) => self.filter.matches(meta),
+                    Err(_) => true,
+                };
+                futures::future::ready(keep)
+            })
+            .boxed()
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        let mut result = self.inner.list_with_delimiter(prefix).await?;
+        result.objects.retain(|meta| self.filter.matches(meta));
+        Ok(result)
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use object_store::path::Path;
+    use object_store::ObjectMeta;
+
+    use crate::error::Result;
+    use crate::model::data_stores::options::file_directory::FileDirectorySource;
+    use crate::model::data_stores::options::SourceFileType;
+
+    use super::FileFilter;
+
+    fn meta(path: &str, day: u32) -> ObjectMeta {
+        ObjectMeta {
+            location: Path::from(path),
+            last_modified: Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(),
+            size: 0,
+            e_tag: None,
+            version: None,
+        }
+    }
+
+    #[test]
+    fn test_file_filter() -> Result<()> {
+        let mut source = FileDirectorySource {
+            bucket: None,
+            region: None,
+            prefix: None,
+            file_type: SourceFileType::Parquet,
+            s3: None,
+            hdfs: None,
+            include: vec![],
+            exclude: vec![],
+            path_regex: None,
+            modified_after: None,
+            declared_schema: None,
+            partition_columns: vec![],
+        };
+        assert!(FileFilter::try_from_source(&source)?.is_none());
+
+        source.include = vec!["events/2024/*/*.parquet".to_string()];
+        source.exclude = vec!["**/_tmp*".to_string()];
+        source.modified_after = Some(Utc.with_ymd_and_hms(2024, 1, 10, 0, 0, 0).unwrap());
+        let filter = FileFilter::try_from_source(&source)?.unwrap();
+
+        assert!(filter.matches(&meta("events/2024/01/part-0.parquet", 15)));
+        assert!(!filter.matches(&meta("events/2024/01/part-0.parquet", 5)));
+        assert!(!filter.matches(&meta("events/2024/01/02/part-0.parquet", 15)));
+        assert!(!filter.matches(&meta("events/2024/01/_tmp.parquet", 15)));
+        assert!(!filter.matches(&meta("other/2024/01/part-0.parquet", 15)));
+
+        source.include = vec![];
+        source.path_regex = Some(r"part-\d+\.parquet$".to_string());
+        let filter = FileFilter::try_from_source(&source)?.unwrap();
+        assert!(filter.matches(&meta("a/part-12.parquet", 15)));
+        assert!(!filter.matches(&meta("a/summary.parquet", 15)));
+        Ok(())
+    }
+}
@@ -13,6 +13,7 @@ use datafusion::error::DataFusionError;
 use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use datafusion::physical_plan::SendableRecordBatchStream;
 use futures::{StreamExt, TryStreamExt};
+use tokio_util::sync::CancellationToken;
 use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity};
 use tracing::debug;
 
@@ -22,7 +23,7 @@ use crate::model::data_stores::options::flight_sql::{
 };
 use crate::model::query::Query;
 
-use super::QueryRunner;
+use super::{CancellableStream, QueryRunner};
 
 /// [BasicFlightSQLAuth][crate::model::data_stores::options::flight_sql::BasicFlightSQLAuth],
 /// but with the password resolved by looking up the referenced env variable.
@@ -90,7 +91,11 @@ impl TryFrom<(FlightSqlConnection, FlightSQLSource)> for FlightSQLRunner {
 
 #[async_trait]
 impl QueryRunner for FlightSQLRunner {
-    async fn execute_stream(&mut self, query: Query) -> Result<SendableRecordBatchStream> {
+    async fn execute_stream(
+        &mut self,
+        query: Query,
+        cancel: CancellationToken,
+    ) -> Result<SendableRecordBatchStream> {
         debug!("Executing {query:?} on FlightSQLRunner");
         debug!("Connecting to FlightSQL endpoint {:?}", self.endpoint);
         let channel = self.endpoint.connect().await?;
@@ -118,7 +123,7 @@ impl QueryRunner for FlightSQLRunner {
 
         let record_batch_stream = futures::stream::select_all(flight_data_streams);
 
-        match query.return_schema {
+        let stream: SendableRecordBatchStream = match query.return_schema {
             Some(schema) => {
                 let schema: Arc<arrow_schema::Schema> = Arc::new(schema);
                 let schema_clone1 = schema.clone();
@@ -141,7 +146,7 @@ impl QueryRunner for FlightSQLRunner {
                             .map_err(DataFusionError::from),
                         Err(e) => Err(e),
                     });
-                Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+                Box::pin(RecordBatchStreamAdapter::new(schema, stream))
             }
             None => {
                 let mut peek = record_batch_stream.peekable();
@@ -156,8 +161,10 @@ impl QueryRunner for FlightSQLRunner {
                 };
 
                 let stream = peek.map_err(|e| DataFusionError::External(Box::new(e)));
-                Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+                Box::pin(RecordBatchStreamAdapter::new(schema, stream))
             }
-        }
+        };
+        // Dropping the stream on cancellation closes the DoGet streams of the server
+        Ok(Box::pin(CancellableStream::new(stream, cancel)))
     }
 }
@@ -1,17 +1,39 @@
+#[cfg(feature = "clickhouse")]
+pub mod clickhouse;
 #[cfg(feature = "datafusion")]
 pub mod file_directory;
+pub mod filtered;
 pub mod flight_sql;
+#[cfg(feature = "mysql")]
+pub mod mysql;
+#[cfg(feature = "postgres")]
+pub mod postgres;
 #[cfg(feature = "trino")]
 pub mod trino;
+#[cfg(feature = "os-hdfs")]
+pub mod webhdfs;
 
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
 use async_trait::async_trait;
-use datafusion::physical_plan::SendableRecordBatchStream;
+use chrono::{DateTime, Utc};
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::common::collect;
+use datafusion::physical_plan::{RecordBatchStream, SendableRecordBatchStream};
+use futures::{Future, Stream};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
 
 use crate::error::{MeshError, Result};
 
-use crate::model::data_stores::options::file_directory::FileDirectorySource;
+use crate::model::data_stores::options::file_directory::{
+    FileDirectorySource, ObjectStoreClientConfig,
+};
 use crate::model::data_stores::options::{SourceOptions, SupportedObjectStore};
 use crate::model::data_stores::{options::ConnectionOptions, DataConnection, DataSource};
 
@@ -24,19 +46,58 @@ use object_store::azure::MicrosoftAzureBuilder;
 #[cfg(feature = "os-gcp")]
 use object_store::gcp::GoogleCloudStorageBuilder;
 
-use object_store::{local::LocalFileSystem, ObjectStore};
+use object_store::{limit::LimitStore, local::LocalFileSystem, ObjectStore};
+#[cfg(any(feature = "os-aws", feature = "os-azure", feature = "os-gcp"))]
+use object_store::{ClientOptions, RetryConfig};
 
-use crate::model::query::Query;
+use crate::model::query::{Query, ScanMetrics};
 
+#[cfg(feature = "clickhouse")]
+use self::clickhouse::ClickHouseRunner;
 #[cfg(feature = "datafusion")]
 use self::file_directory::FileDirectoryRunner;
+use self::filtered::{FileFilter, FilteredStore};
 use self::flight_sql::FlightSQLRunner;
+#[cfg(feature = "mysql")]
+use self::mysql::MySqlRunner;
+#[cfg(feature = "postgres")]
+use self::postgres::PostgresRunner;
 #[cfg(feature = "trino")]
 use self::trino::TrinoRunner;
+#[cfg(feature = "os-hdfs")]
+use self::webhdfs::WebHdfsStore;
+
+#[cfg(any(feature = "os-aws", feature = "os-azure", feature = "os-gcp"))]
+fn retry_config(config: &ObjectStoreClientConfig) -> RetryConfig {
+    let mut retry = RetryConfig::default();
+    if let Some(max_retries) = config.max_retries {
+        retry.max_retries = max_retries;
+    }
+    if let Some(secs) = config.retry_timeout_secs {
+        retry.retry_timeout = Duration::from_secs(secs);
+    }
+    retry
+}
+
+#[cfg(any(feature = "os-aws", feature = "os-azure", feature = "os-gcp"))]
+fn client_options(config: &ObjectStoreClientConfig) -> ClientOptions {
+    let mut options = ClientOptions::new();
+    if let Some(secs) = config.request_timeout_secs {
+        options = options.with_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = config.connect_timeout_secs {
+        options = options.with_connect_timeout(Duration::from_secs(secs));
+    }
+    if let Some(max) = config.pool_max_idle_per_host {
+        options = options.with_pool_max_idle_per_host(max);
+    }
+    options
+}
 
 pub fn initialize_object_store(
     store_type: SupportedObjectStore,
     source: &FileDirectorySource,
+    config: &ObjectStoreClientConfig,
 ) -> Result<Arc<dyn ObjectStore>> {
     let object_store: Arc<dyn ObjectStore> = match store_type {
         SupportedObjectStore::LocalFileSystem => {
@@ -48,18 +109,41 @@ pub fn initialize_object_store(
         }
         #[cfg(feature = "os-aws")]
         SupportedObjectStore::S3 => {
-            let mut s3 = AmazonS3Builder::from_env();
+            let mut s3 = AmazonS3Builder::from_env()
+                .with_retry(retry_config(config))
+                .with_client_options(client_options(config));
             if let Some(bucket) = &source.bucket {
                 s3 = s3.with_bucket_name(bucket);
             }
             if let Some(region) = &source.region {
                 s3 = s3.with_region(region);
             }
+            if let Some(opts) = &source.s3 {
+                if let Some(endpoint) = &opts.endpoint {
+                    s3 = s3
+                        .with_allow_http(endpoint.starts_with("http://"))
+                        .with_endpoint(endpoint);
+                }
+                if let Some(path_style) = opts.path_style {
+                    s3 = s3.with_virtual_hosted_style_request(!path_style);
+                }
+                if let Some(key_id) = &opts.access_key_id {
+                    s3 = s3.with_access_key_id(key_id);
+                }
+                if let Some(secret) = &opts.secret_access_key {
+                    s3 = s3.with_secret_access_key(secret);
+                }
+                if let Some(token) = &opts.session_token {
+                    s3 = s3.with_token(token);
+                }
+            }
             Arc::new(s3.build()?)
         }
         #[cfg(feature = "os-azure")]
         SupportedObjectStore::Azure => {
-            let mut az = MicrosoftAzureBuilder::from_env();
+            let mut az = MicrosoftAzureBuilder::from_env()
+                .with_retry(retry_config(config))
+                .with_client_options(client_options(config));
             if let Some(bucket) = &source.bucket {
                 az = az.with_container_name(bucket);
             }
@@ -67,14 +151,30 @@ pub fn initialize_object_store(
         }
         #[cfg(feature = "os-gcp")]
         SupportedObjectStore::GCP => {
-            let mut gcp = GoogleCloudStorageBuilder::from_env();
+            let mut gcp = GoogleCloudStorageBuilder::from_env()
+                .with_retry(retry_config(config))
+                .with_client_options(client_options(config));
             if let Some(bucket) = &source.bucket {
                 gcp = gcp.with_bucket_name(bucket);
             }
             Arc::new(gcp.build()?)
         }
+        #[cfg(feature = "os-hdfs")]
+        SupportedObjectStore::HDFS => {
+            let hdfs = source.hdfs.as_ref().ok_or(MeshError::InvalidQuery(
+                "HDFS sources must declare the hdfs namenode_url".to_string(),
+            ))?;
+            Arc::new(WebHdfsStore::try_new(hdfs, source.prefix.as_deref())?)
+        }
+    };
+    let object_store: Arc<dyn ObjectStore> = match config.max_concurrent_requests {
+        Some(max) => Arc::new(LimitStore::new(object_store, max)),
+        None => object_store,
     };
-    Ok(object_store)
+    Ok(match FileFilter::try_from_source(source)? {
+        Some(filter) => Arc::new(FilteredStore::new(object_store, filter)),
+        None => object_store,
+    })
 }
 
 /// Attempts to establish a connection to a specific [DataConnection] which can query a specific
@@ -83,13 +183,23 @@ pub async fn try_connect(
     con: DataConnection,
     source: DataSource,
 ) -> Result<Box<dyn QueryRunner + Send>> {
-    match (con.connection_options, source.source_options) {
+    try_connect_options(con.connection_options, source.source_options, source.name).await
+}
+
+/// Attempts to establish a connection with the options of a [DataConnection] and one of its
+/// [DataSource]s, which need not be declared yet. source_name is the name of the [DataSource].
+pub async fn try_connect_options(
+    connection_options: ConnectionOptions,
+    source_options: SourceOptions,
+    source_name: String,
+) -> Result<Box<dyn QueryRunner + Send>> {
+    match (connection_options, source_options) {
         #[cfg(feature = "datafusion")]
         (ConnectionOptions::FileDirectory(con_opts), SourceOptions::FileDirectory(source_opts)) => {
             Ok(Box::new(FileDirectoryRunner::try_from((
                 con_opts,
                 source_opts,
-                source.name,
+                source_name,
             ))?))
         }
         #[cfg(feature = "trino")]
@@ -99,16 +209,222 @@ pub async fn try_connect(
         (ConnectionOptions::FlightSQL(con_opts), SourceOptions::FlightSQL(source_opts)) => Ok(
             Box::new(FlightSQLRunner::try_from((con_opts, source_opts))?),
         ),
+        #[cfg(feature = "postgres")]
+        (ConnectionOptions::Postgres(con_opts), SourceOptions::Postgres(source_opts)) => {
+            Ok(Box::new(PostgresRunner::try_from((con_opts, source_opts))?))
+        }
+        #[cfg(feature = "clickhouse")]
+        (ConnectionOptions::ClickHouse(con_opts), SourceOptions::ClickHouse(source_opts)) => Ok(
+            Box::new(ClickHouseRunner::try_from((con_opts, source_opts))?),
+        ),
+        #[cfg(feature = "mysql")]
+        (ConnectionOptions::MySql(con_opts), SourceOptions::MySql(source_opts)) => {
+            Ok(Box::new(MySqlRunner::try_from((con_opts, source_opts))?))
+        }
         _ => Err(MeshError::InvalidQuery(format!(
             "Invalid or unsupported combination of \
-                        DataConnection options and DataSource options: {}, {}",
-            con.id, source.id
+                        DataConnection options and DataSource options for DataSource {source_name}"
         ))),
     }
 }
 
+/// Outcome of [test_connection].
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ConnectionTest {
+    /// Time taken to connect and run the probe, or until it failed.
+    pub latency_ms: u64,
+    /// Set if the connection or probe failed, e.g. because the credentials are invalid.
+    pub error: Option<String>,
+}
+
+/// Connects with the options of a [DataConnection] and [DataSource] and runs the
+/// [probe][QueryRunner::probe] of the runner, failing if it does not complete within timeout.
+pub async fn test_connection(
+    connection_options: ConnectionOptions,
+    source_options: SourceOptions,
+    timeout: Duration,
+) -> ConnectionTest {
+    let start = Instant::now();
+    let probe = async {
+        let mut runner =
+            try_connect_options(connection_options, source_options, "probe".to_string()).await?;
+        runner.probe().await
+    };
+    let error = match tokio::time::timeout(timeout, probe).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some(format!(
+            "Probe did not complete within {} seconds",
+            timeout.as_secs()
+        )),
+    };
+    ConnectionTest {
+        latency_ms: start.elapsed().as_millis() as u64,
+        error,
+    }
+}
+
+/// Wraps a [SendableRecordBatchStream] so that it ends with an error as soon as cancel is
+/// cancelled. The wrapped stream is dropped at that point rather than when the wrapper is, which
+/// stops a DataFusion plan and closes the connection of runners which stream from a remote engine.
+pub struct CancellableStream {
+    schema: SchemaRef,
+    inner: Option<SendableRecordBatchStream>,
+    cancelled: Pin<Box<WaitForCancellationFutureOwned>>,
+}
+
+impl CancellableStream {
+    pub fn new(inner: SendableRecordBatchStream, cancel: CancellationToken) -> Self {
+        Self {
+            schema: inner.schema(),
+            inner: Some(inner),
+            cancelled: Box::pin(cancel.cancelled_owned()),
+        }
+    }
+}
+
+impl Stream for CancellableStream {
+    type Item = datafusion::error::Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.inner.is_none() {
+            return Poll::Ready(None);
+        }
+        if self.cancelled.as_mut().poll(cx).is_ready() {
+            self.inner = None;
+            return Poll::Ready(Some(Err(DataFusionError::Execution(
+                "Query was cancelled".to_string(),
+            ))));
+        }
+        let polled = match self.inner.as_mut() {
+            Some(inner) => inner.as_mut().poll_next(cx),
+            None => Poll::Ready(None),
+        };
+        if let Poll::Ready(None) = polled {
+            // Cancelling a stream which already ended is not an error
+            self.inner = None;
+        }
+        polled
+    }
+}
+
+impl RecordBatchStream for CancellableStream {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
 #[async_trait]
 pub trait QueryRunner {
-    /// Execute query, returning a stream of [RecordBatches][arrow_array::RecordBatch]
-    async fn execute_stream(&mut self, query: Query) -> Result<SendableRecordBatchStream>;
+    /// Execute query, returning a stream of [RecordBatches][arrow_array::RecordBatch]. Once
+    /// cancel is cancelled the stream ends with an error, and the runner stops executing the
+    /// query rather than letting it run to completion in the background.
+    async fn execute_stream(
+        &mut self,
+        query: Query,
+        cancel: CancellationToken,
+    ) -> Result<SendableRecordBatchStream>;
+
+    /// Returns statistics about the data scanned by the last executed query, once its stream
+    /// has been consumed. Runners which delegate execution to a remote engine return None.
+    fn scan_metrics(&self) -> Option<ScanMetrics> {
+        None
+    }
+
+    /// Returns when the data was last updated, if the runner can tell without a declared
+    /// freshness_query, see [source_freshness][crate::execute::freshness::source_freshness].
+    async fn freshness(&mut self) -> Result<Option<DateTime<Utc>>> {
+        Ok(None)
+    }
+
+    /// Checks that the runner can reach its data, e.g. that the credentials of its connection
+    /// are valid, by executing a trivial query.
+    async fn probe(&mut self) -> Result<()>
+    where
+        Self: Send,
+    {
+        let stream = self
+            .execute_stream(
+                Query {
+                    sql: "select 1".to_string(),
+                    return_schema: None,
+                },
+                CancellationToken::new(),
+            )
+            .await?;
+        collect(stream).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::physical_plan::memory::MemoryStream;
+    use futures::StreamExt;
+    use tokio_util::sync::CancellationToken;
+
+    use crate::model::data_stores::options::file_directory::{
+        FileDirectoryConnection, FileDirectorySource,
+    };
+    use crate::model::data_stores::options::trino::TrinoSource;
+    use crate::model::data_stores::options::{
+        ConnectionOptions, SourceFileType, SourceOptions, SupportedObjectStore,
+    };
+
+    use super::{test_connection, CancellableStream};
+
+    #[tokio::test]
+    async fn test_cancellable_stream() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(vec![1, 2]))])
+                .unwrap();
+        let batches = vec![batch.clone(), batch];
+        let cancel = CancellationToken::new();
+        let inner = MemoryStream::try_new(batches, schema, None).unwrap();
+        let mut stream = CancellableStream::new(Box::pin(inner), cancel.clone());
+
+        assert!(stream.next().await.unwrap().is_ok());
+        cancel.cancel();
+        // The stream ends with an error once cancelled, even though batches remain
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_test_connection() {
+        let dir = std::env::temp_dir().join(format!("dataweb-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let con = ConnectionOptions::FileDirectory(FileDirectoryConnection {
+            object_store_type: SupportedObjectStore::LocalFileSystem,
+            url: format!("file://{}/", dir.display()),
+            client_config: Default::default(),
+        });
+        let source = SourceOptions::FileDirectory(FileDirectorySource {
+            bucket: None,
+            region: None,
+            prefix: None,
+            file_type: SourceFileType::Parquet,
+            s3: None,
+            hdfs: None,
+            include: vec![],
+            exclude: vec![],
+            path_regex: None,
+            modified_after: None,
+            declared_schema: None,
+            partition_columns: vec![],
+        });
+        let timeout = std::time::Duration::from_secs(10);
+
+        let result = test_connection(con.clone(), source, timeout).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result.error, None);
+
+        let result = test_connection(con, SourceOptions::Trino(TrinoSource {}), timeout).await;
+        assert!(result.error.unwrap().contains("unsupported combination"));
+    }
 }
@@ -0,0 +1,495 @@
+use std::env;
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef, BinaryArray, Date32Array, Float32Array, Float64Array, Int16Array, Int32Array,
+    Int64Array, Int8Array, NullArray, RecordBatch, StringArray, Time64MicrosecondArray,
+    TimestampMicrosecondArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime};
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use mysql_async::consts::{ColumnFlags, ColumnType};
+use mysql_async::prelude::{FromValue, Queryable};
+use mysql_async::{from_value_opt, Column, Conn, Opts, OptsBuilder, Value};
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use crate::error::{MeshError, Result};
+use crate::model::data_stores::options::mysql::{MySqlConnection, MySqlSource};
+use crate::model::query::Query;
+
+use super::{CancellableStream, QueryRunner};
+
+/// Number of rows collected into each [RecordBatch] streamed from the database.
+const BATCH_ROWS: usize = 8192;
+
+/// Number of [RecordBatch]es buffered between the connection and the consumer of the stream.
+const CHANNEL_CAPACITY: usize = 2;
+
+/// Character set which MySQL reports for binary strings, as opposed to text.
+const BINARY_CHARSET: u16 = 63;
+
+/// Provides [QueryRunner] impl leveraging an external MySQL database
+/// as the execution engine.
+pub struct MySqlRunner {
+    pub opts: Opts,
+}
+
+impl TryFrom<(MySqlConnection, MySqlSource)> for MySqlRunner {
+    type Error = MeshError;
+
+    fn try_from(value: (MySqlConnection, MySqlSource)) -> Result<Self> {
+        let (con, _source) = value;
+        let pass = match &con.password {
+            Some(password) => Some(env::var(password).map_err(|_e| {
+                MeshError::Internal(format!(
+                    "Expected mysql password to be set in {password} \
+                env variable, but it is unset!"
+                ))
+            })?),
+            None => None,
+        };
+        let opts = OptsBuilder::default()
+            .ip_or_hostname(con.host)
+            .tcp_port(con.port)
+            .db_name(Some(con.database))
+            .user(Some(con.user))
+            .pass(pass);
+        Ok(Self { opts: opts.into() })
+    }
+}
+
+/// Returns the Arrow [DataType] which values of a MySQL [Column] are read as.
+fn mysql_type_to_arrow(column: &Column) -> Result<DataType> {
+    let unsigned = column.flags().contains(ColumnFlags::UNSIGNED_FLAG);
+    let binary = column.character_set() == BINARY_CHARSET;
+    Ok(match column.column_type() {
+        ColumnType::MYSQL_TYPE_TINY if unsigned => DataType::UInt8,
+        ColumnType::MYSQL_TYPE_TINY => DataType::Int8,
+        ColumnType::MYSQL_TYPE_SHORT if unsigned => DataType::UInt16,
+        ColumnType::MYSQL_TYPE_SHORT | ColumnType::MYSQL_TYPE_YEAR => DataType::Int16,
+        ColumnType::MYSQL_TYPE_INT24 | ColumnType::MYSQL_TYPE_LONG if unsigned => DataType::UInt32,
+        ColumnType::MYSQL_TYPE_INT24 | ColumnType::MYSQL_TYPE_LONG => DataType::Int32,
+        ColumnType::MYSQL_TYPE_LONGLONG if unsigned => DataType::UInt64,
+        ColumnType::MYSQL_TYPE_LONGLONG => DataType::Int64,
+        ColumnType::MYSQL_TYPE_FLOAT => DataType::Float32,
+        ColumnType::MYSQL_TYPE_DOUBLE => DataType::Float64,
+        // Decimals are returned as text, which the return_schema may cast to a Decimal128
+        ColumnType::MYSQL_TYPE_DECIMAL
+        | ColumnType::MYSQL_TYPE_NEWDECIMAL
+        | ColumnType::MYSQL_TYPE_ENUM
+        | ColumnType::MYSQL_TYPE_SET
+        | ColumnType::MYSQL_TYPE_JSON => DataType::Utf8,
+        ColumnType::MYSQL_TYPE_VARCHAR
+        | ColumnType::MYSQL_TYPE_VAR_STRING
+        | ColumnType::MYSQL_TYPE_STRING
+        | ColumnType::MYSQL_TYPE_TINY_BLOB
+        | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+        | ColumnType::MYSQL_TYPE_LONG_BLOB
+        | ColumnType::MYSQL_TYPE_BLOB => {
+            if binary {
+                DataType::Binary
+            } else {
+                DataType::Utf8
+            }
+        }
+        ColumnType::MYSQL_TYPE_DATE | ColumnType::MYSQL_TYPE_NEWDATE => DataType::Date32,
+        ColumnType::MYSQL_TYPE_TIME | ColumnType::MYSQL_TYPE_TIME2 => {
+            DataType::Time64(TimeUnit::Microsecond)
+        }
+        // MySQL converts TIMESTAMPs to the session time zone, so neither type carries one
+        ColumnType::MYSQL_TYPE_DATETIME
+        | ColumnType::MYSQL_TYPE_DATETIME2
+        | ColumnType::MYSQL_TYPE_TIMESTAMP
+        | ColumnType::MYSQL_TYPE_TIMESTAMP2 => DataType::Timestamp(TimeUnit::Microsecond, None),
+        ColumnType::MYSQL_TYPE_NULL => DataType::Null,
+        other => {
+            return Err(MeshError::NotImplemented(format!(
+                "MySQL type {other:?} of column {} is not supported. Cast it to a supported \
+                type in the source_sql of the DataSource.",
+                column.name_str()
+            )))
+        }
+    })
+}
+
+fn value_err(value: Value) -> DataFusionError {
+    DataFusionError::Execution(format!("Unable to convert MySQL value {value:?}"))
+}
+
+/// Converts a value returned by the binary protocol into a nullable T.
+fn from_value<T: FromValue>(value: Value) -> std::result::Result<Option<T>, DataFusionError> {
+    from_value_opt::<Option<T>>(value).map_err(|e| value_err(e.0))
+}
+
+/// Converts a DATE, DATETIME or TIMESTAMP value. Zero dates, which MySQL may store in place of
+/// NULL, are read as null.
+fn to_datetime(value: Value) -> std::result::Result<Option<NaiveDateTime>, DataFusionError> {
+    match value {
+        Value::NULL | Value::Date(0, 0, 0, 0, 0, 0, 0) => Ok(None),
+        Value::Date(year, month, day, hour, min, sec, micros) => {
+            NaiveDate::from_ymd_opt(year.into(), month.into(), day.into())
+                .and_then(|d| d.and_hms_micro_opt(hour.into(), min.into(), sec.into(), micros))
+                .map(Some)
+                .ok_or_else(|| value_err(value))
+        }
+        _ => Err(value_err(value)),
+    }
+}
+
+/// Converts a TIME value into microseconds since midnight. MySQL TIMEs are intervals, so
+/// negative values, and values of a day or more, are rejected.
+fn to_time_micros(value: Value) -> std::result::Result<Option<i64>, DataFusionError> {
+    match value {
+        Value::NULL => Ok(None),
+        Value::Time(false, 0, hour, min, sec, micros) => Ok(Some(
+            (i64::from(hour) * 3600 + i64::from(min) * 60 + i64::from(sec)) * 1_000_000
+                + i64::from(micros),
+        )),
+        _ => Err(value_err(value)),
+    }
+}
+
+/// Takes column i of every row into an array of the Arrow type returned by
+/// [mysql_type_to_arrow].
+fn column_to_array(
+    rows: &mut [Vec<Value>],
+    i: usize,
+    dtype: &DataType,
+) -> std::result::Result<ArrayRef, DataFusionError> {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default();
+    let len = rows.len();
+    let values = rows
+        .iter_mut()
+        .map(|r| std::mem::replace(&mut r[i], Value::NULL));
+    Ok(match dtype {
+        DataType::Int8 => Arc::new(
+            values
+                .map(from_value::<i8>)
+                .collect::<std::result::Result<Int8Array, _>>()?,
+        ),
+        DataType::Int16 => Arc::new(
+            values
+                .map(from_value::<i16>)
+                .collect::<std::result::Result<Int16Array, _>>()?,
+        ),
+        DataType::Int32 => Arc::new(
+            values
+                .map(from_value::<i32>)
+                .collect::<std::result::Result<Int32Array, _>>()?,
+        ),
+        DataType::Int64 => Arc::new(
+            values
+                .map(from_value::<i64>)
+                .collect::<std::result::Result<Int64Array, _>>()?,
+        ),
+        DataType::UInt8 => Arc::new(
+            values
+                .map(from_value::<u8>)
+                .collect::<std::result::Result<UInt8Array, _>>()?,
+        ),
+        DataType::UInt16 => Arc::new(
+            values
+                .map(from_value::<u16>)
+                .collect::<std::result::Result<UInt16Array, _>>()?,
+        ),
+        DataType::UInt32 => Arc::new(
+            values
+                .map(from_value::<u32>)
+                .collect::<std::result::Result<UInt32Array, _>>()?,
+        ),
+        DataType::UInt64 => Arc::new(
+            values
+                .map(from_value::<u64>)
+                .collect::<std::result::Result<UInt64Array, _>>()?,
+        ),
+        DataType::Float32 => Arc::new(
+            values
+                .map(from_value::<f32>)
+                .collect::<std::result::Result<Float32Array, _>>()?,
+        ),
+        DataType::Float64 => Arc::new(
+            values
+                .map(from_value::<f64>)
+                .collect::<std::result::Result<Float64Array, _>>()?,
+        ),
+        DataType::Binary => Arc::new(
+            values
+                .map(from_value::<Vec<u8>>)
+                .collect::<std::result::Result<BinaryArray, _>>()?,
+        ),
+        DataType::Date32 => Arc::new(
+            values
+                .map(|v| to_datetime(v).map(|v| v.map(|t| (t.date() - epoch).num_days() as i32)))
+                .collect::<std::result::Result<Date32Array, _>>()?,
+        ),
+        DataType::Time64(_) => Arc::new(
+            values
+                .map(to_time_micros)
+                .collect::<std::result::Result<Time64MicrosecondArray, _>>()?,
+        ),
+        DataType::Timestamp(_, _) => Arc::new(
+            values
+                .map(|v| to_datetime(v).map(|v| v.map(|t| t.and_utc().timestamp_micros())))
+                .collect::<std::result::Result<TimestampMicrosecondArray, _>>()?,
+        ),
+        DataType::Null => Arc::new(NullArray::new(len)),
+        // Every other type is read as Utf8 by mysql_type_to_arrow
+        _ => Arc::new(
+            values
+                .map(from_value::<String>)
+                .collect::<std::result::Result<StringArray, _>>()?,
+        ),
+    })
+}
+
+/// Converts a chunk of rows into a [RecordBatch], casting each column to the type of the
+/// corresponding field of the output schema.
+fn rows_to_batch(
+    mut rows: Vec<Vec<Value>>,
+    arrow_types: &[DataType],
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, DataFusionError> {
+    let columns = arrow_types
+        .iter()
+        .zip(schema.fields())
+        .enumerate()
+        .map(|(i, (dtype, field))| {
+            let array = column_to_array(&mut rows, i, dtype)?;
+            arrow::compute::cast(&array, field.data_type()).map_err(DataFusionError::from)
+        })
+        .collect::<std::result::Result<Vec<_>, DataFusionError>>()?;
+    RecordBatch::try_new(schema, columns).map_err(DataFusionError::from)
+}
+
+/// Executes query on a new connection, sending the schema of its result once it is known and
+/// then the rows in batches of [BATCH_ROWS], until every row is sent or the receiver of the
+/// batches is dropped.
+async fn stream_rows(
+    opts: Opts,
+    query: Query,
+    schema_tx: oneshot::Sender<Result<SchemaRef>>,
+    tx: mpsc::Sender<std::result::Result<RecordBatch, DataFusionError>>,
+) {
+    let mut conn = match Conn::new(opts).await {
+        Ok(conn) => conn,
+        Err(e) => {
+            let _ = schema_tx.send(Err(MeshError::RemoteError(format!(
+                "Failed to connect to mysql: {e}"
+            ))));
+            return;
+        }
+    };
+    // The binary protocol of prepared statements returns typed values rather than text
+    let mut result = match conn.exec_iter(query.sql.as_str(), ()).await {
+        Ok(result) => result,
+        Err(e) => {
+            let _ = schema_tx.send(Err(MeshError::RemoteError(format!("{e}"))));
+            return;
+        }
+    };
+
+    let columns = result.columns_ref().to_vec();
+    let arrow_types = match columns
+        .iter()
+        .map(mysql_type_to_arrow)
+        .collect::<Result<Vec<_>>>()
+    {
+        Ok(arrow_types) => arrow_types,
+        Err(e) => {
+            let _ = schema_tx.send(Err(e));
+            return;
+        }
+    };
+    let schema = match query.return_schema {
+        Some(schema) => Arc::new(schema),
+        None => {
+            let fields = columns
+                .iter()
+                .zip(&arrow_types)
+                .map(|(c, dtype)| Field::new(c.name_str(), dtype.clone(), true))
+                .collect::<Vec<_>>();
+            Arc::new(Schema::new(fields))
+        }
+    };
+    if schema_tx.send(Ok(schema.clone())).is_err() {
+        return;
+    }
+
+    let mut rows = Vec::with_capacity(BATCH_ROWS);
+    loop {
+        let (batch, done) = match result.next().await {
+            Ok(Some(row)) => {
+                rows.push(row.unwrap());
+                if rows.len() < BATCH_ROWS {
+                    continue;
+                }
+                (std::mem::take(&mut rows), false)
+            }
+            Ok(None) if rows.is_empty() => break,
+            Ok(None) => (std::mem::take(&mut rows), true),
+            Err(e) => {
+                let _ = tx.send(Err(DataFusionError::External(Box::new(e)))).await;
+                break;
+            }
+        };
+        if tx
+            .send(rows_to_batch(batch, &arrow_types, schema.clone()))
+            .await
+            .is_err()
+            || done
+        {
+            break;
+        }
+    }
+}
+
+#[async_trait]
+impl QueryRunner for MySqlRunner {
+    async fn execute_stream(
+        &mut self,
+        query: Query,
+        cancel: CancellationToken,
+    ) -> Result<SendableRecordBatchStream> {
+        debug!("Executing {query:?} on MySqlRunner");
+        // The rows of a result borrow its connection, so both are owned by a task which
+        // hands back batches over a channel.
+        let (schema_tx, schema_rx) = oneshot::channel();
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        tokio::spawn(stream_rows(self.opts.clone(), query, schema_tx, tx));
+        let schema = schema_rx
+            .await
+            .map_err(|_e| MeshError::Internal("MySQL query task ended unexpectedly".into()))??;
+        debug!("MySQL runner streaming data with arrow schema {schema}");
+
+        let stream = Box::pin(RecordBatchStreamAdapter::new(
+            schema,
+            ReceiverStream::new(rx),
+        ));
+        // Dropping the stream on cancellation ends the task, which closes the connection
+        Ok(Box::pin(CancellableStream::new(stream, cancel)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Array, Date32Array, Decimal128Array, TimestampMicrosecondArray};
+    use arrow_schema::{DataType, Field, Schema, TimeUnit};
+    use mysql_async::consts::{ColumnFlags, ColumnType};
+    use mysql_async::{Column, Value};
+
+    use crate::error::Result;
+    use crate::model::data_stores::options::mysql::{MySqlConnection, MySqlSource};
+
+    use super::{mysql_type_to_arrow, rows_to_batch, MySqlRunner, BINARY_CHARSET};
+
+    #[test]
+    fn test_mysql_type_to_arrow() -> Result<()> {
+        let column = |column_type| Column::new(column_type).with_character_set(255);
+        assert_eq!(
+            mysql_type_to_arrow(&column(ColumnType::MYSQL_TYPE_LONGLONG))?,
+            DataType::Int64
+        );
+        assert_eq!(
+            mysql_type_to_arrow(
+                &column(ColumnType::MYSQL_TYPE_LONG).with_flags(ColumnFlags::UNSIGNED_FLAG)
+            )?,
+            DataType::UInt32
+        );
+        assert_eq!(
+            mysql_type_to_arrow(&column(ColumnType::MYSQL_TYPE_VAR_STRING))?,
+            DataType::Utf8
+        );
+        assert_eq!(
+            mysql_type_to_arrow(
+                &column(ColumnType::MYSQL_TYPE_BLOB).with_character_set(BINARY_CHARSET)
+            )?,
+            DataType::Binary
+        );
+        assert_eq!(
+            mysql_type_to_arrow(&column(ColumnType::MYSQL_TYPE_DATETIME))?,
+            DataType::Timestamp(TimeUnit::Microsecond, None)
+        );
+        assert!(mysql_type_to_arrow(&column(ColumnType::MYSQL_TYPE_GEOMETRY)).is_err());
+
+        let runner = MySqlRunner::try_from((
+            MySqlConnection {
+                host: "localhost".to_string(),
+                port: 3306,
+                database: "warehouse".to_string(),
+                user: "reader".to_string(),
+                password: None,
+            },
+            MySqlSource {},
+        ))?;
+        assert_eq!(runner.opts.db_name(), Some("warehouse"));
+        assert_eq!(runner.opts.tcp_port(), 3306);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rows_to_batch_casts_to_return_schema() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("price", DataType::Decimal128(10, 2), true),
+            Field::new("day", DataType::Date32, true),
+            Field::new(
+                "updated_at",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+        ]));
+        let rows = vec![
+            vec![
+                Value::Bytes(b"12.50".to_vec()),
+                Value::Date(1970, 1, 2, 0, 0, 0, 0),
+                Value::Date(1970, 1, 1, 0, 0, 1, 5),
+            ],
+            vec![Value::NULL, Value::Date(0, 0, 0, 0, 0, 0, 0), Value::NULL],
+        ];
+        let arrow_types = [
+            DataType::Utf8,
+            DataType::Date32,
+            DataType::Timestamp(TimeUnit::Microsecond, None),
+        ];
+        let batch = rows_to_batch(rows, &arrow_types, schema.clone())?;
+        assert_eq!(batch.schema(), schema);
+
+        let price = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Decimal128Array>()
+            .unwrap();
+        assert_eq!(price.value(0), 1250);
+        assert!(price.is_null(1));
+        let day = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Date32Array>()
+            .unwrap();
+        assert_eq!(day.value(0), 1);
+        assert!(day.is_null(1));
+        let updated_at = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        assert_eq!(updated_at.value(0), 1_000_005);
+        assert!(updated_at.is_null(1));
+
+        assert!(rows_to_batch(
+            vec![vec![Value::Bytes(b"x".to_vec())]],
+            &[DataType::Int64],
+            Arc::new(Schema::new(vec![Field::new("n", DataType::Int64, true)]))
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,477 @@
+use std::env;
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, BufReader};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use arrow_array::{
+    ArrayRef, BinaryArray, BooleanArray, Date32Array, Float32Array, Float64Array, Int16Array,
+    Int32Array, Int64Array, RecordBatch, StringArray, Time64MicrosecondArray,
+    TimestampMicrosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::StreamExt;
+use rustls::{Certificate, ClientConfig, RootCertStore, ServerName};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_postgres::tls::{ChannelBinding, MakeTlsConnect, TlsConnect};
+use tokio_postgres::types::Type;
+use tokio_postgres::{Client, Config, NoTls, Row};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error};
+
+use crate::error::{MeshError, Result};
+use crate::model::data_stores::options::postgres::{
+    PostgresConnection, PostgresSource, PostgresTls,
+};
+use crate::model::query::Query;
+
+use super::{CancellableStream, QueryRunner};
+
+/// Number of rows collected into each [RecordBatch] streamed from the database.
+const BATCH_ROWS: usize = 8192;
+
+/// Provides [QueryRunner] impl leveraging an external PostgreSQL database
+/// as the execution engine.
+pub struct PostgresRunner {
+    pub config: Config,
+    /// Set if the connection to the database must be encrypted.
+    pub tls: Option<Arc<ClientConfig>>,
+}
+
+impl TryFrom<(PostgresConnection, PostgresSource)> for PostgresRunner {
+    type Error = MeshError;
+
+    fn try_from(value: (PostgresConnection, PostgresSource)) -> Result<Self> {
+        let (con, _source) = value;
+        let mut config = Config::new();
+        config
+            .host(&con.host)
+            .port(con.port)
+            .dbname(&con.database)
+            .user(&con.user);
+        if let Some(password) = &con.password {
+            let pass = env::var(password).map_err(|_e| {
+                MeshError::Internal(format!(
+                    "Expected postgres password to be set in {password} \
+                env variable, but it is unset!"
+                ))
+            })?;
+            config.password(pass);
+        }
+        let tls = match &con.tls {
+            Some(tls) => Some(Arc::new(tls_config(tls)?)),
+            None => None,
+        };
+        Ok(Self { config, tls })
+    }
+}
+
+fn tls_config(tls: &PostgresTls) -> Result<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    match &tls.ca_cert_bundle {
+        Some(bundle) => {
+            let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(bundle)?))?;
+            for cert in certs {
+                roots.add(&Certificate(cert)).map_err(|e| {
+                    MeshError::Internal(format!("Invalid CA cert in {bundle}: {e}"))
+                })?;
+            }
+        }
+        None => {
+            for cert in rustls_native_certs::load_native_certs()? {
+                // Skip platform certs which rustls is unable to parse
+                let _ = roots.add(&Certificate(cert.0));
+            }
+        }
+    }
+    Ok(ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+/// Returns the Arrow [DataType] which values of a Postgres column [Type] are read as.
+fn pg_type_to_arrow(pg_type: &Type) -> Result<DataType> {
+    Ok(match *pg_type {
+        Type::BOOL => DataType::Boolean,
+        Type::INT2 => DataType::Int16,
+        Type::INT4 => DataType::Int32,
+        Type::INT8 => DataType::Int64,
+        Type::FLOAT4 => DataType::Float32,
+        Type::FLOAT8 => DataType::Float64,
+        Type::TEXT
+        | Type::VARCHAR
+        | Type::BPCHAR
+        | Type::NAME
+        | Type::JSON
+        | Type::JSONB
+        | Type::UUID => DataType::Utf8,
+        Type::BYTEA => DataType::Binary,
+        Type::DATE => DataType::Date32,
+        Type::TIME => DataType::Time64(TimeUnit::Microsecond),
+        Type::TIMESTAMP => DataType::Timestamp(TimeUnit::Microsecond, None),
+        Type::TIMESTAMPTZ => DataType::Timestamp(TimeUnit::Microsecond, Some("+00:00".into())),
+        _ => {
+            return Err(MeshError::NotImplemented(format!(
+                "Postgres type {pg_type} is not supported. Cast it to a supported \
+                type in the source_sql of the DataSource."
+            )))
+        }
+    })
+}
+
+/// Reads column i of every row into an array of the Arrow type returned by [pg_type_to_arrow].
+fn column_to_array(
+    rows: &[Row],
+    i: usize,
+    pg_type: &Type,
+) -> std::result::Result<ArrayRef, tokio_postgres::Error> {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default();
+    Ok(match *pg_type {
+        Type::BOOL => Arc::new(
+            rows.iter()
+                .map(|r| r.try_get::<_, Option<bool>>(i))
+                .collect::<std::result::Result<BooleanArray, _>>()?,
+        ),
+        Type::INT2 => Arc::new(
+            rows.iter()
+                .map(|r| r.try_get::<_, Option<i16>>(i))
+                .collect::<std::result::Result<Int16Array, _>>()?,
+        ),
+        Type::INT4 => Arc::new(
+            rows.iter()
+                .map(|r| r.try_get::<_, Option<i32>>(i))
+                .collect::<std::result::Result<Int32Array, _>>()?,
+        ),
+        Type::INT8 => Arc::new(
+            rows.iter()
+                .map(|r| r.try_get::<_, Option<i64>>(i))
+                .collect::<std::result::Result<Int64Array, _>>()?,
+        ),
+        Type::FLOAT4 => Arc::new(
+            rows.iter()
+                .map(|r| r.try_get::<_, Option<f32>>(i))
+                .collect::<std::result::Result<Float32Array, _>>()?,
+        ),
+        Type::FLOAT8 => Arc::new(
+            rows.iter()
+                .map(|r| r.try_get::<_, Option<f64>>(i))
+                .collect::<std::result::Result<Float64Array, _>>()?,
+        ),
+        Type::JSON | Type::JSONB => Arc::new(
+            rows.iter()
+                .map(|r| {
+                    r.try_get::<_, Option<serde_json::Value>>(i)
+                        .map(|v| v.map(|v| v.to_string()))
+                })
+                .collect::<std::result::Result<StringArray, _>>()?,
+        ),
+        Type::UUID => Arc::new(
+            rows.iter()
+                .map(|r| {
+                    r.try_get::<_, Option<uuid::Uuid>>(i)
+                        .map(|v| v.map(|v| v.to_string()))
+                })
+                .collect::<std::result::Result<StringArray, _>>()?,
+        ),
+        Type::BYTEA => Arc::new(
+            rows.iter()
+                .map(|r| r.try_get::<_, Option<Vec<u8>>>(i))
+                .collect::<std::result::Result<BinaryArray, _>>()?,
+        ),
+        Type::DATE => Arc::new(
+            rows.iter()
+                .map(|r| {
+                    r.try_get::<_, Option<NaiveDate>>(i)
+                        .map(|v| v.map(|d| (d - epoch).num_days() as i32))
+                })
+                .collect::<std::result::Result<Date32Array, _>>()?,
+        ),
+        Type::TIME => Arc::new(
+            rows.iter()
+                .map(|r| {
+                    r.try_get::<_, Option<NaiveTime>>(i).map(|v| {
+                        v.map(|t| {
+                            t.num_seconds_from_midnight() as i64 * 1_000_000
+                                + t.nanosecond() as i64 / 1_000
+                        })
+                    })
+                })
+                .collect::<std::result::Result<Time64MicrosecondArray, _>>()?,
+        ),
+        Type::TIMESTAMP => Arc::new(
+            rows.iter()
+                .map(|r| {
+                    r.try_get::<_, Option<NaiveDateTime>>(i)
+                        .map(|v| v.map(|t| t.and_utc().timestamp_micros()))
+                })
+                .collect::<std::result::Result<TimestampMicrosecondArray, _>>()?,
+        ),
+        Type::TIMESTAMPTZ => Arc::new(
+            rows.iter()
+                .map(|r| {
+                    r.try_get::<_, Option<DateTime<Utc>>>(i)
+                        .map(|v| v.map(|t| t.timestamp_micros()))
+                })
+                .collect::<std::result::Result<TimestampMicrosecondArray, _>>()?
+                .with_timezone("+00:00"),
+        ),
+        // Every other type is rejected by pg_type_to_arrow before any rows are read
+        _ => Arc::new(
+            rows.iter()
+                .map(|r| r.try_get::<_, Option<String>>(i))
+                .collect::<std::result::Result<StringArray, _>>()?,
+        ),
+    })
+}
+
+/// Converts a chunk of rows into a [RecordBatch], casting each column to the type of the
+/// corresponding field of the output schema.
+fn rows_to_batch(
+    rows: Vec<std::result::Result<Row, tokio_postgres::Error>>,
+    pg_types: &[Type],
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, DataFusionError> {
+    let rows = rows
+        .into_iter()
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| DataFusionError::External(Box::new(e)))?;
+    let columns = pg_types
+        .iter()
+        .zip(schema.fields())
+        .enumerate()
+        .map(|(i, (pg_type, field))| {
+            let array = column_to_array(&rows, i, pg_type)
+                .map_err(|e| DataFusionError::External(Box::new(e)))?;
+            arrow::compute::cast(&array, field.data_type()).map_err(DataFusionError::from)
+        })
+        .collect::<std::result::Result<Vec<_>, DataFusionError>>()?;
+    RecordBatch::try_new(schema, columns).map_err(DataFusionError::from)
+}
+
+async fn connect(config: &Config, tls: Option<Arc<ClientConfig>>) -> Result<Client> {
+    let connect_err = |e: tokio_postgres::Error| {
+        MeshError::RemoteError(format!("Failed to connect to postgres: {e}"))
+    };
+    let client = match tls {
+        Some(tls) => {
+            let (client, connection) = config
+                .connect(MakeRustlsConnect { config: tls })
+                .await
+                .map_err(connect_err)?;
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    error!("Postgres connection error: {e}");
+                }
+            });
+            client
+        }
+        None => {
+            let (client, connection) = config.connect(NoTls).await.map_err(connect_err)?;
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    error!("Postgres connection error: {e}");
+                }
+            });
+            client
+        }
+    };
+    Ok(client)
+}
+
+async fn execute_stream(
+    config: Config,
+    tls: Option<Arc<ClientConfig>>,
+    query: Query,
+) -> Result<SendableRecordBatchStream> {
+    let client = connect(&config, tls).await?;
+    let statement = client
+        .prepare(&query.sql)
+        .await
+        .map_err(|e| MeshError::RemoteError(format!("{e}")))?;
+
+    let pg_types = statement
+        .columns()
+        .iter()
+        .map(|c| c.type_().clone())
+        .collect::<Vec<_>>();
+    let arrow_types = pg_types
+        .iter()
+        .map(pg_type_to_arrow)
+        .collect::<Result<Vec<_>>>()?;
+    let schema = match query.return_schema {
+        Some(schema) => Arc::new(schema),
+        None => {
+            let fields = statement
+                .columns()
+                .iter()
+                .zip(arrow_types)
+                .map(|(c, dtype)| Field::new(c.name(), dtype, true))
+                .collect::<Vec<_>>();
+            Arc::new(Schema::new(fields))
+        }
+    };
+    debug!("Postgres runner streaming data with arrow schema {schema}");
+
+    let rows = client
+        .query_raw(&statement, std::iter::empty::<String>())
+        .await
+        .map_err(|e| MeshError::RemoteError(format!("{e}")))?;
+
+    let schema_clone = schema.clone();
+    let stream = Box::pin(rows).chunks(BATCH_ROWS).map(move |chunk| {
+        // The client must outlive the stream, otherwise the connection is closed.
+        let _client = &client;
+        rows_to_batch(chunk, &pg_types, schema_clone.clone())
+    });
+    Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+}
+
+#[async_trait]
+impl QueryRunner for PostgresRunner {
+    async fn execute_stream(
+        &mut self,
+        query: Query,
+        cancel: CancellationToken,
+    ) -> Result<SendableRecordBatchStream> {
+        debug!("Executing {query:?} on PostgresRunner");
+        let stream = execute_stream(self.config.clone(), self.tls.clone(), query).await?;
+        // Dropping the stream on cancellation drops its client, which closes the connection
+        Ok(Box::pin(CancellableStream::new(stream, cancel)))
+    }
+}
+
+/// Establishes TLS connections to Postgres via rustls.
+#[derive(Clone)]
+struct MakeRustlsConnect {
+    config: Arc<ClientConfig>,
+}
+
+impl<S> MakeTlsConnect<S> for MakeRustlsConnect
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = RustlsStream<S>;
+    type TlsConnect = RustlsConnect;
+    type Error = io::Error;
+
+    fn make_tls_connect(&mut self, domain: &str) -> io::Result<RustlsConnect> {
+        let server_name = ServerName::try_from(domain)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(RustlsConnect {
+            connector: tokio_rustls::TlsConnector::from(self.config.clone()),
+            server_name,
+        })
+    }
+}
+
+struct RustlsConnect {
+    connector: tokio_rustls::TlsConnector,
+    server_name: ServerName,
+}
+
+impl<S> TlsConnect<S> for RustlsConnect
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = RustlsStream<S>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<RustlsStream<S>>> + Send>>;
+
+    fn connect(self, stream: S) -> Self::Future {
+        Box::pin(async move {
+            let stream = self.connector.connect(self.server_name, stream).await?;
+            Ok(RustlsStream(stream))
+        })
+    }
+}
+
+struct RustlsStream<S>(tokio_rustls::client::TlsStream<S>);
+
+impl<S> tokio_postgres::tls::TlsStream for RustlsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn channel_binding(&self) -> ChannelBinding {
+        ChannelBinding::none()
+    }
+}
+
+impl<S> AsyncRead for RustlsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl<S> AsyncWrite for RustlsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_schema::{DataType, TimeUnit};
+    use tokio_postgres::types::Type;
+
+    use crate::error::Result;
+    use crate::model::data_stores::options::postgres::{PostgresConnection, PostgresSource};
+
+    use super::{pg_type_to_arrow, PostgresRunner};
+
+    #[test]
+    fn test_pg_type_to_arrow() -> Result<()> {
+        assert_eq!(pg_type_to_arrow(&Type::INT8)?, DataType::Int64);
+        assert_eq!(pg_type_to_arrow(&Type::VARCHAR)?, DataType::Utf8);
+        assert_eq!(pg_type_to_arrow(&Type::JSONB)?, DataType::Utf8);
+        assert_eq!(
+            pg_type_to_arrow(&Type::TIMESTAMP)?,
+            DataType::Timestamp(TimeUnit::Microsecond, None)
+        );
+        assert!(pg_type_to_arrow(&Type::NUMERIC).is_err());
+
+        let runner = PostgresRunner::try_from((
+            PostgresConnection {
+                host: "localhost".to_string(),
+                port: 5432,
+                database: "warehouse".to_string(),
+                user: "reader".to_string(),
+                password: None,
+                tls: None,
+            },
+            PostgresSource {},
+        ))?;
+        assert_eq!(runner.config.get_dbname(), Some("warehouse"));
+        assert!(runner.tls.is_none());
+        Ok(())
+    }
+}
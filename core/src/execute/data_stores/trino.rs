@@ -14,18 +14,65 @@ use datafusion::physical_plan::{EmptyRecordBatchStream, SendableRecordBatchStrea
 use futures::StreamExt;
 use prusto::auth::Auth;
 use prusto::{Client, ClientBuilder, DataSet, Row};
-use tracing::debug;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
 
 use crate::error::{MeshError, Result};
 use crate::model::data_stores::options::trino::{TrinoConnection, TrinoSource};
 use crate::model::query::Query;
 
-use super::QueryRunner;
+use super::{CancellableStream, QueryRunner};
+
+/// Identifies the user of a request to the Trino REST api.
+const HEADER_USER: &str = "X-Trino-User";
 
 /// Provides [QueryRunner] impl leveraging an external Trino cluster
 /// as the execution engine.
 pub struct TrinoRunner {
     pub client: Arc<Client>,
+    canceller: TrinoCanceller,
+}
+
+/// Cancels queries on the Trino cluster. prusto does not expose the DELETE request which
+/// cancels a query, so it is issued with a separate HTTP client.
+#[derive(Clone)]
+struct TrinoCanceller {
+    http: reqwest::Client,
+    user: String,
+    password: Option<String>,
+}
+
+impl TrinoCanceller {
+    async fn cancel(&self, next_uri: &str) {
+        let mut request = self.http.delete(next_uri).header(HEADER_USER, &self.user);
+        if let Some(password) = &self.password {
+            request = request.basic_auth(&self.user, Some(password));
+        }
+        match request.send().await {
+            Ok(_) => debug!("Cancelled trino query {next_uri}"),
+            Err(e) => warn!("Failed to cancel trino query {next_uri} with error {e}"),
+        }
+    }
+}
+
+/// A query running on the Trino cluster, identified by the uri its next page is read from.
+/// A query which is dropped before it was read to completion, e.g. because it was cancelled,
+/// is cancelled on the cluster too, rather than left running in the background.
+struct PendingQuery {
+    client: Arc<Client>,
+    canceller: TrinoCanceller,
+    next_uri: Option<String>,
+}
+
+impl Drop for PendingQuery {
+    fn drop(&mut self) {
+        if let Some(next_uri) = self.next_uri.take() {
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                let canceller = self.canceller.clone();
+                handle.spawn(async move { canceller.cancel(&next_uri).await });
+            }
+        }
+    }
 }
 
 impl TryFrom<(TrinoConnection, TrinoSource)> for TrinoRunner {
@@ -33,18 +80,18 @@ impl TryFrom<(TrinoConnection, TrinoSource)> for TrinoRunner {
 
     fn try_from(value: (TrinoConnection, TrinoSource)) -> Result<Self> {
         let (con, _source) = value;
-        let auth = if con.password.is_empty() {
-            Auth::Basic(con.user.clone(), None)
+        let password = if con.password.is_empty() {
+            None
         } else {
-            let pass = env::var(&con.password).map_err(|_e| {
+            Some(env::var(&con.password).map_err(|_e| {
                 MeshError::Internal(format!(
                     "Expected trino password to be set in {} \
                 env variable, but it is unset!",
                     con.password
                 ))
-            })?;
-            Auth::Basic(con.user.clone(), Some(pass))
+            })?)
         };
+        let auth = Auth::Basic(con.user.clone(), password.clone());
         let client = if con.secure {
             ClientBuilder::new(&con.user, &con.host)
                 .port(con.port.parse().map_err(|_e| {
@@ -67,8 +114,16 @@ impl TryFrom<(TrinoConnection, TrinoSource)> for TrinoRunner {
                 })?
         };
 
+        let canceller = TrinoCanceller {
+            http: reqwest::Client::new(),
+            user: con.user.clone(),
+            // Credentials are only sent to a secure cluster, as for the prusto client
+            password: password.filter(|_| con.secure),
+        };
+
         Ok(Self {
             client: Arc::new(client),
+            canceller,
         })
     }
 }
@@ -100,32 +155,28 @@ fn infer_arrow_schema_from_dataset(dset: &DataSet<Row>) -> Result<SchemaRef> {
     Ok(schema)
 }
 
-async fn execute_stream(client: Arc<Client>, query: Query) -> Result<SendableRecordBatchStream> {
-    let client_clone = client.clone();
-    let sql = Some(query.sql.clone());
-    let sql_check = query.sql;
+async fn execute_stream(
+    client: Arc<Client>,
+    canceller: TrinoCanceller,
+    query: Query,
+    cancel: CancellationToken,
+) -> Result<SendableRecordBatchStream> {
+    let pending = PendingQuery {
+        client,
+        canceller,
+        next_uri: None,
+    };
     let trino_stream = futures::stream::try_unfold(
-        (sql, sql_check, client_clone),
-        |(get_next, sql_check, client_clone)| async move {
-            if let Some(next) = get_next {
-                let res = if next == sql_check {
-                    client_clone
-                        .get::<Row>(next)
-                        .await
-                        .map_err(|e| DataFusionError::Execution(format!("{e}")))?
-                } else {
-                    client_clone
-                        .get_next::<Row>(&next)
-                        .await
-                        .map_err(|e| DataFusionError::Execution(format!("{e}")))?
-                };
-                Ok(Some((
-                    res.data_set,
-                    (res.next_uri, sql_check, client_clone),
-                )))
-            } else {
-                Ok(None)
+        (Some(query.sql.clone()), pending),
+        |(sql, mut pending)| async move {
+            let res = match (sql, &pending.next_uri) {
+                (Some(sql), _) => pending.client.get::<Row>(sql).await,
+                (None, Some(next)) => pending.client.get_next::<Row>(next).await,
+                (None, None) => return Ok(None),
             }
+            .map_err(|e| DataFusionError::Execution(format!("{e}")))?;
+            pending.next_uri = res.next_uri;
+            Ok(Some((res.data_set, (None, pending))))
         },
     )
     .filter_map(|data| async {
@@ -147,7 +198,14 @@ async fn execute_stream(client: Arc<Client>, query: Query) -> Result<SendableRec
     // Peek at the first batch of data from trino to impute the appropraite arrow schema
     // for the returned data. Otherwise return an EmptyRecordBatchStream if there is no data.
     let mut peekable = Box::pin(trino_stream).peekable();
-    let schema = if let Some(data) = Pin::new(&mut peekable).peek().await {
+    let peek = Pin::new(&mut peekable).peek();
+    let peeked = tokio::select! {
+        peeked = peek => peeked,
+        _ = cancel.cancelled() => {
+            return Err(MeshError::RemoteError("Trino query was cancelled".to_string()))
+        }
+    };
+    let schema = if let Some(data) = peeked {
         match data {
             Ok(d) => match query.return_schema {
                 Some(schema) => {
@@ -187,14 +245,20 @@ async fn execute_stream(client: Arc<Client>, query: Query) -> Result<SendableRec
         }
         Err(e) => Err(e),
     });
-    Ok(Box::pin(RecordBatchStreamAdapter::new(schema, rb_stream)))
+    let stream = Box::pin(RecordBatchStreamAdapter::new(schema, rb_stream));
+    // Dropping the stream on cancellation drops the PendingQuery, which cancels it on the cluster
+    Ok(Box::pin(CancellableStream::new(stream, cancel)))
 }
 
 #[async_trait]
 impl QueryRunner for TrinoRunner {
-    async fn execute_stream(&mut self, query: Query) -> Result<SendableRecordBatchStream> {
+    async fn execute_stream(
+        &mut self,
+        query: Query,
+        cancel: CancellationToken,
+    ) -> Result<SendableRecordBatchStream> {
         // Note: async_trait macro causes a higher ranked lifetime error if the
         // execute_stream helper function is included literally in this method.
-        Ok(execute_stream(self.client.clone(), query).await?)
+        Ok(execute_stream(self.client.clone(), self.canceller.clone(), query, cancel).await?)
     }
 }
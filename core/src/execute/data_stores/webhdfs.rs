@@ -0,0 +1,344 @@
+use std::fmt::{Debug, Display};
+use std::ops::Range;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, TimeZone, Utc};
+use futures::stream::{self, BoxStream, StreamExt};
+use object_store::path::Path;
+use object_store::{
+    GetOptions, GetRange, GetResult, GetResultPayload, ListResult, MultipartId, ObjectMeta,
+    ObjectStore, PutOptions, PutResult,
+};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use tokio::io::AsyncWrite;
+use url::Url;
+
+use crate::error::{MeshError, Result};
+use crate::model::data_stores::options::file_directory::HdfsOptions;
+
+const STORE: &str = "WebHDFS";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileStatus {
+    path_suffix: String,
+    #[serde(rename = "type")]
+    file_type: String,
+    length: usize,
+    modification_time: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FileStatusResponse {
+    file_status: FileStatus,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FileStatuses {
+    file_status: Vec<FileStatus>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListStatusResponse {
+    file_statuses: FileStatuses,
+}
+
+impl FileStatus {
+    fn is_dir(&self) -> bool {
+        self.file_type == "DIRECTORY"
+    }
+
+    fn last_modified(&self) -> DateTime<Utc> {
+        Utc.timestamp_millis_opt(self.modification_time)
+            .single()
+            .unwrap_or_default()
+    }
+}
+
+/// A read only [ObjectStore] for files on a Hadoop cluster, accessed via the WebHDFS REST API of
+/// the namenode. Supports the listing and ranged reads required to query files with DataFusion.
+#[derive(Debug)]
+pub struct WebHdfsStore {
+    client: Client,
+    /// e.g. http://namenode:9870/webhdfs/v1/
+    base: Url,
+    /// Directory on HDFS which object paths are relative to.
+    root: String,
+    user: Option<String>,
+}
+
+impl Display for WebHdfsStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WebHdfsStore({}{})", self.base, self.root)
+    }
+}
+
+fn child_path(dir: &str, suffix: &str) -> String {
+    match (dir.is_empty(), suffix.is_empty()) {
+        (true, _) => suffix.to_string(),
+        (_, true) => dir.to_string(),
+        _ => format!("{dir}/{suffix}"),
+    }
+}
+
+fn generic_err(e: impl std::error::Error + Send + Sync + 'static) -> object_store::Error {
+    object_store::Error::Generic {
+        store: STORE,
+        source: Box::new(e),
+    }
+}
+
+impl WebHdfsStore {
+    pub fn try_new(options: &HdfsOptions, prefix: Option<&str>) -> Result<Self> {
+        let base = Url::parse(&options.namenode_url)?.join("webhdfs/v1/")?;
+        let root = prefix.unwrap_or_default().trim_matches('/').to_string();
+        let client = Client::builder()
+            .build()
+            .map_err(|e| MeshError::Internal(format!("Unable to build WebHDFS client: {e}")))?;
+        Ok(Self {
+            client,
+            base,
+            root,
+            user: options.user.clone(),
+        })
+    }
+
+    /// Returns the HDFS path of an object, relative to the filesystem root.
+    fn hdfs_path(&self, location: Option<&Path>) -> String {
+        let location = location.map(|l| l.as_ref()).unwrap_or_default();
+        [self.root.as_str(), location]
+            .iter()
+            .filter(|p| !p.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Converts an HDFS path relative to the filesystem root back into an object [Path].
+    fn object_path(&self, hdfs_path: &str) -> Path {
+        let relative = hdfs_path
+            .strip_prefix(self.root.as_str())
+            .unwrap_or(hdfs_path)
+            .trim_start_matches('/');
+        Path::from(relative)
+    }
+
+    async fn request(
+        &self,
+        hdfs_path: &str,
+        op: &str,
+        params: &[(&str, String)],
+    ) -> object_store::Result<reqwest::Response> {
+        let mut url = self.base.join(hdfs_path).map_err(generic_err)?;
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("op", op);
+            if let Some(user) = &self.user {
+                query.append_pair("user.name", user);
+            }
+            for (key, value) in params {
+                query.append_pair(key, value);
+            }
+        }
+        let response = self.client.get(url).send().await.map_err(generic_err)?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Err(object_store::Error::NotFound {
+                path: hdfs_path.to_string(),
+                source: format!("{op} returned 404").into(),
+            }),
+            _ => response.error_for_status().map_err(generic_err),
+        }
+    }
+
+    async fn file_status(&self, hdfs_path: &str) -> object_store::Result<FileStatus> {
+        let response: FileStatusResponse = self
+            .request(hdfs_path, "GETFILESTATUS", &[])
+            .await?
+            .json()
+            .await
+            .map_err(generic_err)?;
+        Ok(response.file_status)
+    }
+
+    async fn list_status(&self, hdfs_path: &str) -> object_store::Result<Vec<FileStatus>> {
+        let response: ListStatusResponse = self
+            .request(hdfs_path, "LISTSTATUS", &[])
+            .await?
+            .json()
+            .await
+            .map_err(generic_err)?;
+        Ok(response.file_statuses.file_status)
+    }
+
+    fn meta(&self, hdfs_path: &str, status: &FileStatus) -> ObjectMeta {
+        ObjectMeta {
+            location: self.object_path(hdfs_path),
+            last_modified: status.last_modified(),
+            size: status.length,
+            e_tag: None,
+            version: None,
+        }
+    }
+
+    /// Lists every file below a directory, descending into subdirectories.
+    async fn list_recursive(&self, prefix: Option<&Path>) -> object_store::Result<Vec<ObjectMeta>> {
+        let mut files = vec![];
+        let mut directories = vec![self.hdfs_path(prefix)];
+        while let Some(dir) = directories.pop() {
+            let statuses = match self.list_status(&dir).await {
+                Ok(s) => s,
+                Err(object_store::Error::NotFound { .. }) => continue,
+                Err(e) => return Err(e),
+            };
+            for status in statuses {
+                let path = child_path(&dir, &status.path_suffix);
+                if status.is_dir() {
+                    directories.push(path);
+                } else {
+                    files.push(self.meta(&path, &status));
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for WebHdfsStore {
+    async fn put_opts(
+        &self,
+        _location: &Path,
+        _bytes: Bytes,
+        _opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn put_multipart(
+        &self,
+        _location: &Path,
+    ) -> object_store::Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn abort_multipart(
+        &self,
+        _location: &Path,
+        _multipart_id: &MultipartId,
+    ) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        let hdfs_path = self.hdfs_path(Some(location));
+        let meta = self.meta(&hdfs_path, &self.file_status(&hdfs_path).await?);
+        let range: Range<usize> = match options.range {
+            Some(GetRange::Bounded(r)) => r.start..r.end.min(meta.size),
+            Some(GetRange::Offset(offset)) => offset..meta.size,
+            Some(GetRange::Suffix(n)) => meta.size.saturating_sub(n)..meta.size,
+            None => 0..meta.size,
+        };
+        let bytes = self
+            .request(
+                &hdfs_path,
+                "OPEN",
+                &[
+                    ("offset", range.start.to_string()),
+                    ("length", range.len().to_string()),
+                ],
+            )
+            .await?
+            .bytes()
+            .await
+            .map_err(generic_err)?;
+        Ok(GetResult {
+            payload: GetResultPayload::Stream(stream::once(async move { Ok(bytes) }).boxed()),
+            meta,
+            range,
+        })
+    }
+
+    async fn head(&self, location: &Path) -> object_store::Result<ObjectMeta> {
+        let hdfs_path = self.hdfs_path(Some(location));
+        Ok(self.meta(&hdfs_path, &self.file_status(&hdfs_path).await?))
+    }
+
+    async fn delete(&self, _location: &Path) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        let prefix = prefix.cloned();
+        stream::once(async move { self.list_recursive(prefix.as_ref()).await })
+            .flat_map(|result| match result {
+                Ok(files) => stream::iter(files.into_iter().map(Ok)).boxed(),
+                Err(e) => stream::once(async { Err(e) }).boxed(),
+            })
+            .boxed()
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        let dir = self.hdfs_path(prefix);
+        let mut result = ListResult {
+            common_prefixes: vec![],
+            objects: vec![],
+        };
+        for status in self.list_status(&dir).await? {
+            let path = child_path(&dir, &status.path_suffix);
+            if status.is_dir() {
+                result.common_prefixes.push(self.object_path(&path));
+            } else {
+                result.objects.push(self.meta(&path, &status));
+            }
+        }
+        Ok(result)
+    }
+
+    async fn copy(&self, _from: &Path, _to: &Path) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn copy_if_not_exists(&self, _from: &Path, _to: &Path) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::path::Path;
+
+    use crate::error::Result;
+    use crate::model::data_stores::options::file_directory::HdfsOptions;
+
+    use super::WebHdfsStore;
+
+    #[test]
+    fn test_paths_relative_to_prefix() -> Result<()> {
+        let options = HdfsOptions {
+            namenode_url: "http://namenode:9870".to_string(),
+            user: None,
+        };
+        let store = WebHdfsStore::try_new(&options, Some("/data/events/"))?;
+        let location = Path::from("2024/01/part-0.parquet");
+        let hdfs_path = store.hdfs_path(Some(&location));
+        assert_eq!(hdfs_path, "data/events/2024/01/part-0.parquet");
+        assert_eq!(store.object_path(&hdfs_path), location);
+        assert_eq!(
+            store.base.join(&hdfs_path)?.as_str(),
+            "http://namenode:9870/webhdfs/v1/data/events/2024/01/part-0.parquet"
+        );
+
+        let store = WebHdfsStore::try_new(&options, None)?;
+        assert_eq!(store.hdfs_path(None), "");
+        Ok(())
+    }
+}
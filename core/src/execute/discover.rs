@@ -0,0 +1,343 @@
+use std::collections::BTreeMap;
+
+use arrow::compute::cast;
+use arrow_array::cast::AsArray;
+use arrow_array::{Array, RecordBatch};
+use arrow_schema::DataType;
+use datafusion::physical_plan::common::collect;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{MeshError, Result};
+use crate::model::access_control::PolicyMode;
+use crate::model::config_commands::empty_permission;
+use crate::model::config_commands::local_data::{DataFieldsDeclaration, DataSourcesDeclaration};
+use crate::model::data_stores::options::{ConnectionOptions, SourceOptions};
+use crate::model::data_stores::DataConnection;
+use crate::model::query::Query;
+
+#[cfg(feature = "postgres")]
+use crate::model::data_stores::options::postgres::PostgresSource;
+#[cfg(feature = "trino")]
+use crate::model::data_stores::options::trino::TrinoSource;
+
+#[cfg(feature = "datafusion")]
+use super::data_stores::file_directory::FileDirectoryRunner;
+#[cfg(feature = "postgres")]
+use super::data_stores::postgres::PostgresRunner;
+#[cfg(feature = "trino")]
+use super::data_stores::trino::TrinoRunner;
+use super::data_stores::QueryRunner;
+
+/// Restricts which tables [discover_sources] drafts DataSources for.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct DiscoverOptions {
+    /// For Postgres, the schema whose tables are discovered. For Trino, the catalog or
+    /// catalog.schema. Every table is discovered if unset, except those of system schemas.
+    #[serde(default)]
+    pub schema: Option<String>,
+    /// Options of the DataSource whose files are inspected, required for FileDirectory
+    /// connections, where the files of a DataSource are not located by the connection alone.
+    #[serde(default)]
+    pub source_options: Option<SourceOptions>,
+    /// Name of the DataSource drafted for a FileDirectory connection, defaults to the name of
+    /// the connection.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Connects to the [DataConnection] and drafts a [DataSourcesDeclaration] for every table it
+/// serves, with a field for every column. Tables are listed from the catalog of Trino and
+/// Postgres connections, while the files of a FileDirectory source are inspected to infer their
+/// columns. The drafts grant no default permission and are meant to be reviewed before they are
+/// declared.
+pub async fn discover_sources(
+    con: DataConnection,
+    options: DiscoverOptions,
+) -> Result<Vec<DataSourcesDeclaration>> {
+    match (con.connection_options, options.source_options) {
+        #[cfg(feature = "trino")]
+        (ConnectionOptions::Trino(con_opts), _) => {
+            let mut runner = TrinoRunner::try_from((con_opts, TrinoSource {}))?;
+            let sql = trino_columns_sql(options.schema.as_deref())?;
+            let batches = run(&mut runner, sql).await?;
+            draft_sources(&batches, &SourceOptions::Trino(TrinoSource {}))
+        }
+        #[cfg(feature = "postgres")]
+        (ConnectionOptions::Postgres(con_opts), _) => {
+            let mut runner = PostgresRunner::try_from((con_opts, PostgresSource {}))?;
+            let sql = postgres_columns_sql(options.schema.as_deref());
+            let batches = run(&mut runner, sql).await?;
+            draft_sources(&batches, &SourceOptions::Postgres(PostgresSource {}))
+        }
+        #[cfg(feature = "datafusion")]
+        (ConnectionOptions::FileDirectory(con_opts), Some(SourceOptions::FileDirectory(opts))) => {
+            let name = options.name.unwrap_or(con.name);
+            let mut runner = FileDirectoryRunner::try_from((con_opts, opts.clone(), name.clone()))?;
+            // The schema is inferred from the files without reading any rows
+            let stream = runner
+                .execute_stream(
+                    Query {
+                        sql: format!("select * from {name} limit 0"),
+                        return_schema: None,
+                    },
+                    CancellationToken::new(),
+                )
+                .await?;
+            Ok(vec![draft_source(
+                name.clone(),
+                name,
+                stream.schema().fields().iter().map(|f| f.name().clone()),
+                SourceOptions::FileDirectory(opts),
+            )])
+        }
+        #[cfg(feature = "datafusion")]
+        (ConnectionOptions::FileDirectory(_), _) => Err(MeshError::InvalidQuery(format!(
+            "The FileDirectory source_options of the files to inspect are required to discover \
+            sources of DataConnection {}!",
+            con.name
+        ))),
+        _ => Err(MeshError::InvalidQuery(format!(
+            "Discovering sources is not supported for DataConnection {}, only for Trino, \
+            Postgres and FileDirectory connections!",
+            con.name
+        ))),
+    }
+}
+
+/// Runs sql to completion, returning every batch.
+#[cfg(any(feature = "trino", feature = "postgres"))]
+async fn run(runner: &mut impl QueryRunner, sql: String) -> Result<Vec<RecordBatch>> {
+    let stream = runner
+        .execute_stream(
+            Query {
+                sql,
+                return_schema: None,
+            },
+            CancellationToken::new(),
+        )
+        .await?;
+    Ok(collect(stream).await?)
+}
+
+/// Lists the columns of every table of the Trino cluster, optionally restricted to a catalog or
+/// catalog.schema, as rows of qualified table name and column name.
+#[cfg(feature = "trino")]
+fn trino_columns_sql(schema: Option<&str>) -> Result<String> {
+    let mut filters = vec!["table_schem <> 'information_schema'".to_string()];
+    match schema.map(|s| s.split('.').collect::<Vec<_>>()).as_deref() {
+        None => (),
+        Some([catalog]) => filters.push(format!("table_cat = {}", sql_literal(catalog))),
+        Some([catalog, schema]) => {
+            filters.push(format!("table_cat = {}", sql_literal(catalog)));
+            filters.push(format!("table_schem = {}", sql_literal(schema)));
+        }
+        Some(_) => {
+            return Err(MeshError::InvalidQuery(format!(
+                "Expected a catalog or catalog.schema to discover, found {}",
+                schema.unwrap_or_default()
+            )))
+        }
+    }
+    Ok(format!(
+        "select table_cat || '.' || table_schem || '.' || table_name as table_name, column_name \
+        from system.jdbc.columns where {} \
+        order by table_cat, table_schem, table_name, ordinal_position",
+        filters.join(" and ")
+    ))
+}
+
+/// Lists the columns of every table and view of the Postgres database, optionally restricted to
+/// a schema, as rows of qualified table name and column name.
+#[cfg(feature = "postgres")]
+fn postgres_columns_sql(schema: Option<&str>) -> String {
+    let filter = match schema {
+        Some(schema) => format!("table_schema = {}", sql_literal(schema)),
+        None => "table_schema not in ('pg_catalog', 'information_schema')".to_string(),
+    };
+    format!(
+        "select (table_schema || '.' || table_name)::text as table_name, \
+        column_name::text as column_name \
+        from information_schema.columns where {filter} \
+        order by table_schema, table_name, ordinal_position"
+    )
+}
+
+#[cfg(any(feature = "trino", feature = "postgres"))]
+fn sql_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Drafts a DataSource for every table of batches, whose rows are the qualified table name and
+/// a column name. The columns of each table keep the order of the rows.
+#[cfg(any(feature = "trino", feature = "postgres"))]
+fn draft_sources(
+    batches: &[RecordBatch],
+    source_options: &SourceOptions,
+) -> Result<Vec<DataSourcesDeclaration>> {
+    let mut tables: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for batch in batches {
+        if batch.num_columns() < 2 {
+            return Err(MeshError::RemoteError(
+                "Expected the table and column names of the discovered columns".to_string(),
+            ));
+        }
+        let table_names = cast(batch.column(0), &DataType::Utf8)?;
+        let column_names = cast(batch.column(1), &DataType::Utf8)?;
+        let table_names = table_names.as_string::<i32>();
+        let column_names = column_names.as_string::<i32>();
+        for i in 0..batch.num_rows() {
+            if table_names.is_null(i) || column_names.is_null(i) {
+                continue;
+            }
+            tables
+                .entry(table_names.value(i).to_string())
+                .or_default()
+                .push(column_names.value(i).to_string());
+        }
+    }
+    Ok(tables
+        .into_iter()
+        .map(|(table, columns)| draft_source(table.clone(), table, columns, source_options.clone()))
+        .collect())
+}
+
+#[cfg(any(feature = "trino", feature = "postgres", feature = "datafusion"))]
+fn draft_source(
+    name: String,
+    source_sql: String,
+    columns: impl IntoIterator<Item = String>,
+    source_options: SourceOptions,
+) -> DataSourcesDeclaration {
+    DataSourcesDeclaration {
+        name,
+        source_sql,
+        source_options,
+        engines: Default::default(),
+        freshness_query: None,
+        policy_mode: PolicyMode::default(),
+        fields: columns
+            .into_iter()
+            .map(|column| DataFieldsDeclaration {
+                name: column.clone(),
+                path: column,
+            })
+            .collect(),
+        default_permission: empty_permission(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{RecordBatch, StringArray};
+    use uuid::Uuid;
+
+    use crate::error::Result;
+    use crate::model::data_stores::options::file_directory::{
+        FileDirectoryConnection, FileDirectorySource,
+    };
+    use crate::model::data_stores::options::trino::TrinoSource;
+    use crate::model::data_stores::options::{
+        ConnectionOptions, SourceFileType, SourceOptions, SupportedObjectStore,
+    };
+    use crate::model::data_stores::schedule::ExecutionWindows;
+    use crate::model::data_stores::DataConnection;
+
+    use super::{discover_sources, draft_sources, trino_columns_sql, DiscoverOptions};
+
+    #[test]
+    fn test_draft_sources() -> Result<()> {
+        let batch = RecordBatch::try_from_iter(vec![
+            (
+                "table_name",
+                Arc::new(StringArray::from(vec![
+                    "tpch.tiny.orders",
+                    "tpch.tiny.orders",
+                    "tpch.tiny.customer",
+                ])) as _,
+            ),
+            (
+                "column_name",
+                Arc::new(StringArray::from(vec!["orderkey", "custkey", "custkey"])) as _,
+            ),
+        ])?;
+        let sources = draft_sources(&[batch], &SourceOptions::Trino(TrinoSource {}))?;
+
+        let sources = sources
+            .iter()
+            .map(|s| {
+                (
+                    s.source_sql.as_str(),
+                    s.fields.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sources,
+            vec![
+                ("tpch.tiny.customer", vec!["custkey"]),
+                ("tpch.tiny.orders", vec!["orderkey", "custkey"]),
+            ]
+        );
+
+        assert!(trino_columns_sql(Some("tpch.tiny"))?
+            .contains("table_cat = 'tpch' and table_schem = 'tiny'"));
+        assert!(trino_columns_sql(Some("it's"))?.contains("table_cat = 'it''s'"));
+        assert!(trino_columns_sql(Some("a.b.c")).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_discover_file_directory() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dataweb-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("orders.csv"), "orderkey,custkey\n1,2\n")?;
+
+        let con = DataConnection {
+            id: Uuid::new_v4(),
+            name: "files".to_string(),
+            connection_options: ConnectionOptions::FileDirectory(FileDirectoryConnection {
+                object_store_type: SupportedObjectStore::LocalFileSystem,
+                url: format!("file://{}/", dir.display()),
+                client_config: Default::default(),
+            }),
+            execution_windows: ExecutionWindows::default(),
+        };
+        let source_options = SourceOptions::FileDirectory(FileDirectorySource {
+            bucket: None,
+            region: None,
+            prefix: None,
+            file_type: SourceFileType::CSV,
+            s3: None,
+            hdfs: None,
+            include: vec![],
+            exclude: vec![],
+            path_regex: None,
+            modified_after: None,
+            declared_schema: None,
+            partition_columns: vec![],
+        });
+        let sources = discover_sources(
+            con,
+            DiscoverOptions {
+                schema: None,
+                source_options: Some(source_options),
+                name: Some("orders".to_string()),
+            },
+        )
+        .await?;
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].name, "orders");
+        let fields = sources[0]
+            .fields
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(fields, vec!["orderkey", "custkey"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use arrow::compute::cast;
+use arrow_array::cast::AsArray;
+use arrow_array::types::TimestampMicrosecondType;
+use arrow_array::{Array, RecordBatch};
+use arrow_schema::{DataType, TimeUnit};
+use chrono::{DateTime, Utc};
+use datafusion::physical_plan::common::collect;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{MeshError, Result};
+use crate::model::data_stores::{DataConnection, DataSource};
+use crate::model::query::Query;
+
+use super::data_stores::try_connect;
+
+/// Returns when the data of a [DataSource] was last updated. Runs the freshness_query of the
+/// [DataSource] if it declares one, and otherwise asks its
+/// [QueryRunner][super::data_stores::QueryRunner], which may not be able to tell.
+pub async fn source_freshness(
+    con: DataConnection,
+    source: DataSource,
+) -> Result<Option<DateTime<Utc>>> {
+    let freshness_query = source.freshness_query.clone();
+    let mut runner = try_connect(con, source).await?;
+    match freshness_query {
+        Some(sql) => {
+            let stream = runner
+                .execute_stream(
+                    Query {
+                        sql,
+                        return_schema: None,
+                    },
+                    CancellationToken::new(),
+                )
+                .await?;
+            probe_timestamp(&collect(stream).await?)
+        }
+        None => runner.freshness().await,
+    }
+}
+
+/// Reads the timestamp returned by a freshness_query from the first column of its first row.
+/// Any type which casts to a timestamp is accepted, e.g. a date or an ISO 8601 string.
+fn probe_timestamp(batches: &[RecordBatch]) -> Result<Option<DateTime<Utc>>> {
+    let batch = match batches.iter().find(|b| b.num_rows() > 0) {
+        Some(batch) => batch,
+        None => return Ok(None),
+    };
+    if batch.num_columns() == 0 {
+        return Err(MeshError::InvalidQuery(
+            "freshness_query must return a timestamp column".to_string(),
+        ));
+    }
+    let timestamps = cast(
+        batch.column(0),
+        &DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+    )?;
+    let timestamps = timestamps.as_primitive::<TimestampMicrosecondType>();
+    if timestamps.is_null(0) {
+        return Ok(None);
+    }
+    Ok(DateTime::from_timestamp_micros(timestamps.value(0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{RecordBatch, StringArray};
+    use chrono::{TimeZone, Utc};
+
+    use crate::error::Result;
+
+    use super::probe_timestamp;
+
+    #[test]
+    fn test_probe_timestamp() -> Result<()> {
+        let batch = RecordBatch::try_from_iter(vec![(
+            "max_updated_at",
+            Arc::new(StringArray::from(vec![Some("2024-09-01T12:30:00Z"), None])) as _,
+        )])?;
+        assert_eq!(
+            probe_timestamp(std::slice::from_ref(&batch))?,
+            Some(Utc.with_ymd_and_hms(2024, 9, 1, 12, 30, 0).unwrap())
+        );
+        assert_eq!(probe_timestamp(&[batch.slice(1, 1)])?, None);
+        assert_eq!(probe_timestamp(&[])?, None);
+        Ok(())
+    }
+}
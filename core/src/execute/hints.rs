@@ -0,0 +1,107 @@
+use serde::Deserialize;
+
+use crate::error::{MeshError, Result};
+use crate::model::query::{RawQueryRequest, ResultFormat};
+
+/// Typed view of the hints of a [RawQueryRequest], which give users control over how each relay
+/// plans and executes their request without a dedicated field for every option. Hints which are
+/// not recognized are ignored, so that a request carrying hints introduced by a newer relay is
+/// still executed by older ones.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct QueryHints {
+    /// Runs local tasks on the [SourceEngine][crate::model::data_stores::engines::SourceEngine]
+    /// with this name, for [DataSource][crate::model::data_stores::DataSource]s which declare
+    /// one. Takes precedence over [RawQueryRequest::engine_hint].
+    pub engine: Option<String>,
+    /// Caps the rows returned by each DataSource, lowering the LIMIT of the request if it has a
+    /// larger one.
+    pub max_rows: Option<u64>,
+    /// Rejects the request if more than this many local DataSources would execute it, guarding
+    /// against unexpectedly expensive fan out.
+    pub max_sources: Option<usize>,
+    /// Skips DataSources whose connection is outside of its execution windows, rather than
+    /// deferring the request until they are open.
+    #[serde(default)]
+    pub skip_deferred: bool,
+    /// Retains the results of the request for this many seconds after they were produced, rather
+    /// than for RESULT_TTL_SECS. 0 retains them forever.
+    pub result_ttl_secs: Option<i64>,
+    /// Stores the results of the request in this format, rather than in RESULT_FORMAT.
+    pub result_format: Option<ResultFormat>,
+}
+
+impl QueryHints {
+    pub fn parse(raw_request: &RawQueryRequest) -> Result<Self> {
+        let hints = serde_json::Value::Object(
+            raw_request
+                .hints
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        );
+        let mut parsed: QueryHints = serde_json::from_value(hints)
+            .map_err(|e| MeshError::InvalidQuery(format!("Invalid query hints: {e}")))?;
+        if parsed.engine.is_none() {
+            parsed.engine.clone_from(&raw_request.engine_hint);
+        }
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use crate::error::MeshError;
+    use crate::model::query::RawQueryRequest;
+
+    use super::QueryHints;
+
+    fn request(hints: serde_json::Value, engine_hint: Option<&str>) -> RawQueryRequest {
+        RawQueryRequest {
+            sql: "select * from e".to_string(),
+            statements: vec![],
+            request_uuid: None,
+            requesting_user: None,
+            originating_relay: None,
+            originating_task_id: None,
+            return_arrow_schema: None,
+            engine_hint: engine_hint.map(|e| e.to_string()),
+            count_only: false,
+            interactive: false,
+            reexecution_of: None,
+            hints: serde_json::from_value::<HashMap<_, _>>(hints).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_parse_hints() {
+        let hints = QueryHints::parse(&request(
+            json!({"engine": "trino", "max_rows": 10, "some_future_hint": [1]}),
+            Some("duckdb"),
+        ))
+        .unwrap();
+        assert_eq!(
+            hints,
+            QueryHints {
+                engine: Some("trino".to_string()),
+                max_rows: Some(10),
+                max_sources: None,
+                skip_deferred: false,
+                result_ttl_secs: None,
+                result_format: None,
+            }
+        );
+
+        // The engine_hint field applies when no engine hint is given
+        let hints = QueryHints::parse(&request(json!({}), Some("duckdb"))).unwrap();
+        assert_eq!(hints.engine.as_deref(), Some("duckdb"));
+
+        assert!(matches!(
+            QueryHints::parse(&request(json!({"max_rows": "ten"}), None)),
+            Err(MeshError::InvalidQuery(_))
+        ));
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::crud::PgDb;
+use crate::error::Result;
+use crate::model::relay::Relay;
+use crate::model::user::User;
+
+/// Number of identities of each kind held before expired entries are purged.
+const MAX_ENTRIES: usize = 10_000;
+
+struct Entry<T> {
+    value: T,
+    expires_at: Instant,
+}
+
+struct TtlMap<T> {
+    ttl: Duration,
+    entries: RwLock<HashMap<String, Entry<T>>>,
+}
+
+impl<T: Clone> TtlMap<T> {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<T> {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(key)
+            .filter(|entry| entry.expires_at > Instant::now())
+            .map(|entry| entry.value.clone())
+    }
+
+    fn insert(&self, key: &str, value: T) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= MAX_ENTRIES {
+            entries.retain(|_, entry| entry.expires_at > now);
+            if entries.len() >= MAX_ENTRIES {
+                entries.clear();
+            }
+        }
+        entries.insert(
+            key.to_string(),
+            Entry {
+                value,
+                expires_at: now + self.ttl,
+            },
+        );
+    }
+
+    fn remove(&self, key: &str) {
+        self.entries
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(key);
+    }
+
+    fn clear(&self) {
+        self.entries
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+/// Caches the [User]s and [Relay]s identified by x509 certificate fingerprints, so that
+/// authenticating a client does not require a database round trip on every request. Only
+/// identities which exist are cached, and each is looked up again once its TTL elapses.
+///
+/// Changes applied locally must call [IdentityCache::invalidate] or [IdentityCache::clear], so
+/// that e.g. a revoked admin attribute takes effect immediately. Changes made by another process
+/// take effect within the TTL.
+pub struct IdentityCache {
+    users: TtlMap<User>,
+    relays: TtlMap<Relay>,
+}
+
+impl IdentityCache {
+    /// A ttl of zero disables caching.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            users: TtlMap::new(ttl),
+            relays: TtlMap::new(ttl),
+        }
+    }
+
+    pub async fn get_user(&self, db: &mut PgDb<'_>, fingerprint: &str) -> Result<User> {
+        if let Some(user) = self.users.get(fingerprint) {
+            return Ok(user);
+        }
+        let user = db.get_user_by_x509_fingerprint(fingerprint).await?;
+        self.users.insert(fingerprint, user.clone());
+        Ok(user)
+    }
+
+    pub async fn get_relay(&self, db: &mut PgDb<'_>, fingerprint: &str) -> Result<Relay> {
+        if let Some(relay) = self.relays.get(fingerprint) {
+            return Ok(relay);
+        }
+        let relay = db.get_relay_by_x509_fingerprint(fingerprint).await?;
+        self.relays.insert(fingerprint, relay.clone());
+        Ok(relay)
+    }
+
+    /// Returns the [User] identified by fingerprint if it is cached, without querying the database.
+    pub fn cached_user(&self, fingerprint: &str) -> Option<User> {
+        self.users.get(fingerprint)
+    }
+
+    /// Caches a [User] which was just read or written, e.g. when it is registered on first use.
+    pub fn insert_user(&self, user: &User) {
+        self.users.insert(&user.x509_sha256, user.clone());
+    }
+
+    /// Forgets any [User] or [Relay] identified by fingerprint.
+    pub fn invalidate(&self, fingerprint: &str) {
+        self.users.remove(fingerprint);
+        self.relays.remove(fingerprint);
+    }
+
+    /// Forgets every cached identity.
+    pub fn clear(&self) {
+        self.users.clear();
+        self.relays.clear();
+    }
+}
+
+/// Returns the [IdentityCache] shared by every service of the process, with a TTL read from
+/// IDENTITY_CACHE_TTL_SECS (default 30). A TTL of 0 disables the cache.
+pub fn identity_cache() -> &'static IdentityCache {
+    static CACHE: OnceLock<IdentityCache> = OnceLock::new();
+    CACHE.get_or_init(|| {
+        let ttl_secs = env::var("IDENTITY_CACHE_TTL_SECS")
+            .unwrap_or("30".to_string())
+            .parse()
+            .expect("Unable to parse IDENTITY_CACHE_TTL_SECS as u64!");
+        IdentityCache::new(Duration::from_secs(ttl_secs))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::TtlMap;
+
+    #[test]
+    fn test_ttl_map() {
+        let map = TtlMap::new(Duration::from_secs(60));
+        map.insert("a", 1);
+        map.insert("b", 2);
+        assert_eq!(map.get("a"), Some(1));
+        map.remove("a");
+        assert_eq!(map.get("a"), None);
+        assert_eq!(map.get("b"), Some(2));
+        map.clear();
+        assert_eq!(map.get("b"), None);
+
+        // Expired entries are never returned
+        let map = TtlMap::new(Duration::from_nanos(1));
+        map.insert("a", 1);
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(map.get("a"), None);
+
+        let disabled = TtlMap::new(Duration::ZERO);
+        disabled.insert("a", 1);
+        assert_eq!(disabled.get("a"), None);
+    }
+}
@@ -0,0 +1,795 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::crud::PgDb;
+use crate::error::Result;
+use crate::model::access_control::SourcePermission;
+use crate::model::config_commands::entity::{
+    ResolvedEntityDeclaration, ResolvedInformationDeclaration,
+};
+use crate::model::config_commands::local_data::{
+    DataConnectionsDeclaration, DataFieldsDeclaration, DataSourcesDeclaration,
+};
+use crate::model::config_commands::local_mapping::{
+    DataConnectionMappingDeclaration, DataFieldMappingDeclaration, DataSourceMappingsDeclaration,
+    LocalMappingDeclaration,
+};
+use crate::model::config_commands::quota::ResolvedQuotaDeclaration;
+use crate::model::config_commands::relay::ResolvedPeerRelayDeclaration;
+use crate::model::config_commands::remote_mapping::{
+    PeerRelayMappingsDeclaration, RemoteInfoMappingsDeclaration, RemoteMappingsDeclaration,
+};
+use crate::model::config_commands::role::ResolvedRoleDeclaration;
+use crate::model::config_commands::user::{
+    PermissionsDecl, ResolvedServiceAccountDeclaration, ResolvedUserDeclaration,
+    SourcePermissionDecl,
+};
+use crate::model::config_commands::validation_rules::ValidationRulesDeclaration;
+use crate::model::config_commands::{
+    empty_permission, DefaultPermissionDeclaration, ResolvedConfigCommand, ResolvedConfigObject,
+    API_VERSION,
+};
+use crate::model::quota::QuotaSubject;
+use crate::model::user::UserAttributes;
+
+/// The declared state of a peer [Relay][crate::model::relay::Relay]. Shaped like a
+/// [PeerRelayDeclaration][crate::model::config_commands::relay::PeerRelayDeclaration], except
+/// that the relay is identified by its certificate fingerprint, since certificates are not stored.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct DeclaredPeerRelay {
+    pub name: String,
+    pub rest_endpoint: String,
+    pub flight_endpoint: String,
+    pub x509_sha256: String,
+    pub x509_subject: String,
+    pub x509_issuer: String,
+    pub permissions: Vec<PermissionsDecl>,
+}
+
+/// The declared state of a [User][crate::model::user::User]. Shaped like a
+/// [UserDeclaration][crate::model::config_commands::user::UserDeclaration], except that the user
+/// is identified by its certificate fingerprint, since certificates are not stored.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct DeclaredUser {
+    pub x509_sha256: String,
+    pub x509_subject: String,
+    pub x509_issuer: String,
+    pub attributes: UserAttributes,
+    pub permissions: Vec<PermissionsDecl>,
+}
+
+/// Describes an Information of an Entity to users discovering what they are able to query.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct InformationSummary {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+}
+
+/// Returns the [InformationSummary]s of every entity, keyed by entity name.
+pub async fn entity_summaries(
+    db: &mut PgDb<'_>,
+) -> Result<BTreeMap<String, Vec<InformationSummary>>> {
+    Ok(db
+        .get_all_information()
+        .await?
+        .into_iter()
+        .map(|(entity, infos)| {
+            let infos = infos
+                .into_iter()
+                .map(|info| InformationSummary {
+                    name: info.name,
+                    data_type: info.arrow_dtype.inner.to_string(),
+                    nullable: info.nullable,
+                })
+                .collect();
+            (entity, infos)
+        })
+        .collect())
+}
+
+/// Returns an Entity [ResolvedConfigObject] for every entity, which declares its current
+/// information, validation query and aliases.
+pub async fn declared_entities(db: &mut PgDb<'_>) -> Result<Vec<ResolvedConfigObject>> {
+    let mut information = db.get_all_information().await?;
+    let mut aliases = db.get_all_entity_aliases().await?;
+    Ok(db
+        .get_entities()
+        .await?
+        .into_iter()
+        .map(|entity| {
+            ResolvedConfigObject::Entity(ResolvedEntityDeclaration {
+                information: information
+                    .remove(&entity.name)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|info| ResolvedInformationDeclaration {
+                        name: info.name,
+                        arrow_dtype: info.arrow_dtype.inner,
+                        nullable: info.nullable,
+                    })
+                    .collect(),
+                validation_query: entity.validation_query,
+                aliases: aliases.remove(&entity.id).unwrap_or_default(),
+                name: entity.name,
+            })
+        })
+        .collect())
+}
+
+/// Returns a LocalData [ResolvedConfigObject] for every data connection, which declares its
+/// current sources, their fields and default permissions.
+pub async fn declared_data(db: &mut PgDb<'_>) -> Result<Vec<ResolvedConfigObject>> {
+    let mut fields: HashMap<Uuid, Vec<DataFieldsDeclaration>> = HashMap::new();
+    for field in db.get_all_fields().await? {
+        fields
+            .entry(field.data_source_id)
+            .or_default()
+            .push(DataFieldsDeclaration {
+                name: field.name,
+                path: field.path,
+            });
+    }
+    let mut default_permissions = db
+        .get_default_source_permissions()
+        .await?
+        .into_iter()
+        .map(|p| (p.data_source_id, p.source_permission))
+        .collect::<HashMap<_, _>>();
+    let mut sources: HashMap<Uuid, Vec<DataSourcesDeclaration>> = HashMap::new();
+    for source in db.get_sources().await? {
+        let default_permission = match default_permissions.remove(&source.id) {
+            Some(permission) => {
+                let (allowed_columns, allowed_rows) = permission_columns_and_rows(permission);
+                DefaultPermissionDeclaration {
+                    allowed_columns,
+                    allowed_rows,
+                }
+            }
+            None => empty_permission(),
+        };
+        sources
+            .entry(source.data_connection_id)
+            .or_default()
+            .push(DataSourcesDeclaration {
+                fields: fields.remove(&source.id).unwrap_or_default(),
+                name: source.name,
+                source_sql: source.source_sql,
+                source_options: source.source_options,
+                engines: source.engines,
+                freshness_query: source.freshness_query,
+                policy_mode: source.policy_mode,
+                default_permission,
+            });
+    }
+    Ok(db
+        .get_connections()
+        .await?
+        .into_iter()
+        .map(|con| {
+            ResolvedConfigObject::LocalData(DataConnectionsDeclaration {
+                data_sources: sources.remove(&con.id).unwrap_or_default(),
+                name: con.name,
+                connection_options: con.connection_options,
+                execution_windows: con.execution_windows,
+            })
+        })
+        .collect())
+}
+
+/// Returns a LocalMapping and a RemoteMapping [ResolvedConfigObject] for every entity which is
+/// mapped to local data sources and peer relays respectively.
+pub async fn declared_mappings(db: &mut PgDb<'_>) -> Result<Vec<ResolvedConfigObject>> {
+    let entities = db.get_entities().await?;
+    let names = entities.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+
+    // entity -> connection -> source -> field mappings
+    let mut local: BTreeMap<String, BTreeMap<String, BTreeMap<String, Vec<_>>>> = BTreeMap::new();
+    for ((con, source), mappings) in db.get_mappings_by_entity_names(names.clone()).await? {
+        for (entity, info, field, map) in mappings {
+            local
+                .entry(entity.name)
+                .or_default()
+                .entry(con.name.clone())
+                .or_default()
+                .entry(source.name.clone())
+                .or_default()
+                .push(DataFieldMappingDeclaration {
+                    info: info.name,
+                    field: field.name,
+                    transformation: map.transformation,
+                });
+        }
+    }
+
+    // entity -> relay -> (remote entity mapping, info mappings)
+    let mut remote: BTreeMap<String, BTreeMap<String, (_, Vec<_>)>> = BTreeMap::new();
+    for (relay, mappings) in db.get_remote_mappings_by_entity_names(names).await? {
+        for (entity, info, entity_map, info_map) in mappings {
+            remote
+                .entry(entity.name)
+                .or_default()
+                .entry(relay.name.clone())
+                .or_insert_with(|| (entity_map, vec![]))
+                .1
+                .push(RemoteInfoMappingsDeclaration {
+                    local_info: info.name,
+                    info_mapped_name: info_map.info_mapped_name,
+                    transformation: info_map.transformation,
+                });
+        }
+    }
+
+    let mut declared = Vec::with_capacity(local.len() + remote.len());
+    for (entity_name, connections) in local {
+        declared.push(ResolvedConfigObject::LocalMapping(
+            LocalMappingDeclaration {
+                entity_name,
+                mappings: connections
+                    .into_iter()
+                    .map(
+                        |(data_con_name, sources)| DataConnectionMappingDeclaration {
+                            data_con_name,
+                            source_mappings: sources
+                                .into_iter()
+                                .map(|(data_source_name, mut field_mappings)| {
+                                    field_mappings.sort_by(|a, b| a.field.cmp(&b.field));
+                                    DataSourceMappingsDeclaration {
+                                        data_source_name,
+                                        field_mappings,
+                                    }
+                                })
+                                .collect(),
+                        },
+                    )
+                    .collect(),
+            },
+        ));
+    }
+    for (entity_name, relays) in remote {
+        declared.push(ResolvedConfigObject::RemoteMapping(
+            RemoteMappingsDeclaration {
+                entity_name,
+                mappings: relays
+                    .into_iter()
+                    .map(|(relay_name, (entity_map, mut relay_mappings))| {
+                        relay_mappings.sort_by(|a, b| a.local_info.cmp(&b.local_info));
+                        PeerRelayMappingsDeclaration {
+                            relay_name,
+                            remote_entity_name: entity_map.remote_entity_name,
+                            sql: Some(entity_map.sql),
+                            relay_mappings,
+                        }
+                    })
+                    .collect(),
+            },
+        ));
+    }
+    Ok(declared)
+}
+
+/// Returns the declared state of every peer relay.
+pub async fn declared_relays(db: &mut PgDb<'_>) -> Result<Vec<DeclaredPeerRelay>> {
+    let mut permissions = HashMap::new();
+    for p in db.get_relay_source_permissions().await? {
+        permissions
+            .entry(p.relay_id)
+            .or_insert_with(Vec::new)
+            .push((p.data_source_id, p.source_permission));
+    }
+    let names = source_names(db).await?;
+    Ok(db
+        .get_relays()
+        .await?
+        .into_iter()
+        .map(|relay| DeclaredPeerRelay {
+            permissions: permissions_decl(permissions.remove(&relay.id), &names),
+            name: relay.name,
+            rest_endpoint: relay.rest_endpoint,
+            flight_endpoint: relay.flight_endpoint,
+            x509_sha256: relay.x509_sha256,
+            x509_subject: relay.x509_subject,
+            x509_issuer: relay.x509_issuer,
+        })
+        .collect())
+}
+
+/// Returns the declared state of every user, including service accounts.
+pub async fn declared_users(db: &mut PgDb<'_>) -> Result<Vec<DeclaredUser>> {
+    let mut permissions = HashMap::new();
+    for p in db.get_user_source_permissions().await? {
+        permissions
+            .entry(p.user_id)
+            .or_insert_with(Vec::new)
+            .push((p.data_source_id, p.source_permission));
+    }
+    let names = source_names(db).await?;
+    Ok(db
+        .get_users()
+        .await?
+        .into_iter()
+        .map(|user| DeclaredUser {
+            permissions: permissions_decl(permissions.remove(&user.id), &names),
+            x509_sha256: user.x509_sha256,
+            x509_subject: user.x509_subject,
+            x509_issuer: user.x509_issuer,
+            attributes: user.attributes,
+        })
+        .collect())
+}
+
+/// The declared state of a [Role][crate::model::role::Role]. Shaped like a
+/// [RoleDeclaration][crate::model::config_commands::role::RoleDeclaration], except that member
+/// users are identified by their certificate fingerprint.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct DeclaredRole {
+    pub name: String,
+    pub user_x509_sha256: Vec<String>,
+    pub relays: Vec<String>,
+    pub permissions: Vec<PermissionsDecl>,
+}
+
+/// Returns the declared state of every role.
+pub async fn declared_roles(db: &mut PgDb<'_>) -> Result<Vec<DeclaredRole>> {
+    let mut role_users: HashMap<Uuid, Vec<String>> = HashMap::new();
+    for (role_id, fingerprint) in db.get_role_users().await? {
+        role_users.entry(role_id).or_default().push(fingerprint);
+    }
+    let mut role_relays: HashMap<Uuid, Vec<String>> = HashMap::new();
+    for (role_id, relay_name) in db.get_role_relays().await? {
+        role_relays.entry(role_id).or_default().push(relay_name);
+    }
+    let mut permissions = HashMap::new();
+    for p in db.get_role_source_permissions().await? {
+        permissions
+            .entry(p.role_id)
+            .or_insert_with(Vec::new)
+            .push((p.data_source_id, p.source_permission));
+    }
+    let names = source_names(db).await?;
+    Ok(db
+        .get_roles()
+        .await?
+        .into_iter()
+        .map(|role| DeclaredRole {
+            user_x509_sha256: role_users.remove(&role.id).unwrap_or_default(),
+            relays: role_relays.remove(&role.id).unwrap_or_default(),
+            permissions: permissions_decl(permissions.remove(&role.id), &names),
+            name: role.name,
+        })
+        .collect())
+}
+
+/// Whether an apply creates, updates or deletes a config object.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeAction {
+    Create,
+    Update,
+    Delete,
+}
+
+/// A change to a single config object, see [diff_config].
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ConfigChange {
+    pub action: ChangeAction,
+    /// Describes the object, e.g. "DataSource trino/orders".
+    pub object: String,
+}
+
+/// The declared state of every config object, keyed by a description of the object.
+pub type ConfigSnapshot = BTreeMap<String, serde_json::Value>;
+
+/// Returns the declared state of every config object. Data sources are snapshot separately from
+/// their connection, and mappings by entity, so that changes are reported per object.
+pub async fn config_snapshot(db: &mut PgDb<'_>) -> Result<ConfigSnapshot> {
+    let mut snapshot = ConfigSnapshot::new();
+    for config_obj in declared_entities(db)
+        .await?
+        .into_iter()
+        .chain(declared_data(db).await?)
+        .chain(declared_mappings(db).await?)
+    {
+        match config_obj {
+            ResolvedConfigObject::Entity(entity) => {
+                snapshot.insert(
+                    format!("Entity {}", entity.name),
+                    serde_json::to_value(&entity)?,
+                );
+            }
+            ResolvedConfigObject::LocalData(mut con) => {
+                for source in std::mem::take(&mut con.data_sources) {
+                    snapshot.insert(
+                        format!("DataSource {}/{}", con.name, source.name),
+                        serde_json::to_value(&source)?,
+                    );
+                }
+                snapshot.insert(
+                    format!("DataConnection {}", con.name),
+                    serde_json::to_value(&con)?,
+                );
+            }
+            ResolvedConfigObject::LocalMapping(mapping) => {
+                snapshot.insert(
+                    format!("LocalMapping {}", mapping.entity_name),
+                    serde_json::to_value(&mapping)?,
+                );
+            }
+            ResolvedConfigObject::RemoteMapping(mapping) => {
+                snapshot.insert(
+                    format!("RemoteMapping {}", mapping.entity_name),
+                    serde_json::to_value(&mapping)?,
+                );
+            }
+            _ => (),
+        }
+    }
+    for relay in declared_relays(db).await? {
+        snapshot.insert(
+            format!("PeerRelay {}", relay.name),
+            serde_json::to_value(&relay)?,
+        );
+    }
+    for user in declared_users(db).await? {
+        snapshot.insert(
+            format!("User {}", user.x509_subject),
+            serde_json::to_value(&user)?,
+        );
+    }
+    for rule_set in db.get_validation_rule_sets().await? {
+        snapshot.insert(
+            format!("ValidationRules {}", rule_set.name),
+            serde_json::to_value(&rule_set.rules)?,
+        );
+    }
+    for quota in db.get_quotas().await? {
+        snapshot.insert(
+            format!("Quota {:?} {}", quota.subject, quota.x509_sha256),
+            serde_json::to_value(&quota)?,
+        );
+    }
+
+    for role in declared_roles(db).await? {
+        let role_state = serde_json::json!({
+            "user_x509_sha256": role.user_x509_sha256,
+            "relays": role.relays,
+            "permissions": role.permissions,
+        });
+        snapshot.insert(format!("Role {}", role.name), role_state);
+    }
+    Ok(snapshot)
+}
+
+/// Compares the snapshots taken before and after an apply, returning a change for every object
+/// which was created, updated or deleted, ordered by object.
+pub fn diff_config(before: &ConfigSnapshot, after: &ConfigSnapshot) -> Vec<ConfigChange> {
+    let mut changes = vec![];
+    for (object, state) in before {
+        match after.get(object) {
+            None => changes.push((ChangeAction::Delete, object)),
+            Some(new_state) if new_state != state => changes.push((ChangeAction::Update, object)),
+            Some(_) => (),
+        }
+    }
+    for object in after.keys() {
+        if !before.contains_key(object) {
+            changes.push((ChangeAction::Create, object));
+        }
+    }
+    changes.sort_by(|a, b| a.1.cmp(b.1));
+    changes
+        .into_iter()
+        .map(|(action, object)| ConfigChange {
+            action,
+            object: object.clone(),
+        })
+        .collect()
+}
+
+/// The configuration of a relay as config commands, see [export_config].
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ConfigExport {
+    pub commands: Vec<ResolvedConfigCommand>,
+    /// Describes the objects which could not be exported, since their certificate is not stored.
+    pub skipped: Vec<String>,
+}
+
+/// Returns the configuration of the relay as config commands in the order they should be
+/// applied, such that applying them to an empty relay restores the configuration. Users and
+/// relays are declared by their certificate, so those registered without one, e.g. on their
+/// first query or by redeeming an invite, are skipped along with the quotas and role
+/// memberships referencing them. Users with the default attributes and no permissions are
+/// not configuration and are left out silently.
+pub async fn export_config(db: &mut PgDb<'_>) -> Result<ConfigExport> {
+    let mut objects = declared_entities(db).await?;
+    objects.extend(declared_data(db).await?);
+    objects.extend(declared_mappings(db).await?);
+    let mut skipped = vec![];
+
+    let mut relay_certs = db
+        .get_relay_certificates()
+        .await?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let mut relay_names = HashMap::new();
+    for relay in declared_relays(db).await? {
+        relay_names.insert(relay.x509_sha256, relay.name.clone());
+        match relay_certs.remove(&relay.name) {
+            Some(x509_cert) => objects.push(ResolvedConfigObject::PeerRelay(
+                ResolvedPeerRelayDeclaration {
+                    name: relay.name,
+                    rest_endpoint: relay.rest_endpoint,
+                    flight_endpoint: relay.flight_endpoint,
+                    x509_cert,
+                    permissions: Some(relay.permissions),
+                },
+            )),
+            None => skipped.push(format!("PeerRelay {}", relay.name)),
+        }
+    }
+
+    let user_certs = db
+        .get_user_certificates()
+        .await?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let mut user_subjects = HashMap::new();
+    for user in declared_users(db).await? {
+        user_subjects.insert(user.x509_sha256.clone(), user.x509_subject.clone());
+        let Some(x509_cert) = user_certs.get(&user.x509_sha256).cloned() else {
+            if user.attributes != UserAttributes::new() || !user.permissions.is_empty() {
+                skipped.push(format!("User {}", user.x509_subject));
+            }
+            continue;
+        };
+        let permissions = Some(user.permissions);
+        objects.push(match user.attributes.service_account {
+            Some(scope) => {
+                ResolvedConfigObject::ServiceAccount(ResolvedServiceAccountDeclaration {
+                    x509_cert,
+                    scope,
+                    misc: user.attributes.misc,
+                    permissions,
+                })
+            }
+            None => ResolvedConfigObject::User(ResolvedUserDeclaration {
+                x509_cert,
+                attributes: user.attributes,
+                permissions,
+            }),
+        });
+    }
+    let subject = |fingerprint: &str| {
+        user_subjects
+            .get(fingerprint)
+            .map_or(fingerprint, |subject| subject.as_str())
+            .to_string()
+    };
+
+    for rule_set in db.get_validation_rule_sets().await? {
+        objects.push(ResolvedConfigObject::ValidationRules(
+            ValidationRulesDeclaration {
+                name: rule_set.name,
+                rules: rule_set.rules,
+            },
+        ));
+    }
+
+    for quota in db.get_quotas().await? {
+        let (user_x509_cert, relay) = match quota.subject {
+            QuotaSubject::User => match user_certs.get(&quota.x509_sha256) {
+                Some(x509_cert) => (Some(x509_cert.clone()), None),
+                None => {
+                    skipped.push(format!("Quota of user {}", subject(&quota.x509_sha256)));
+                    continue;
+                }
+            },
+            QuotaSubject::Relay => match relay_names.get(&quota.x509_sha256) {
+                Some(name) => (None, Some(name.clone())),
+                None => {
+                    skipped.push(format!("Quota of relay {}", quota.x509_sha256));
+                    continue;
+                }
+            },
+        };
+        objects.push(ResolvedConfigObject::Quota(ResolvedQuotaDeclaration {
+            user_x509_cert,
+            relay,
+            max_concurrent_requests: quota.max_concurrent_requests.map(|max| max as u32),
+            max_requests_per_minute: quota.max_requests_per_minute.map(|max| max as u32),
+        }));
+    }
+
+    for role in declared_roles(db).await? {
+        let mut user_x509_certs = Vec::with_capacity(role.user_x509_sha256.len());
+        for fingerprint in &role.user_x509_sha256 {
+            match user_certs.get(fingerprint) {
+                Some(x509_cert) => user_x509_certs.push(x509_cert.clone()),
+                None => skipped.push(format!(
+                    "Member {} of Role {}",
+                    subject(fingerprint),
+                    role.name
+                )),
+            }
+        }
+        objects.push(ResolvedConfigObject::Role(ResolvedRoleDeclaration {
+            name: role.name,
+            user_x509_certs,
+            relays: role.relays,
+            permissions: Some(role.permissions),
+        }));
+    }
+
+    objects.sort_by_key(|o| o.apply_precedence());
+    Ok(ConfigExport {
+        commands: objects
+            .into_iter()
+            .map(|config_object| ResolvedConfigCommand {
+                api_version: API_VERSION.to_string(),
+                config_object,
+            })
+            .collect(),
+        skipped,
+    })
+}
+
+/// Maps the id of every data source to the names of its connection and itself.
+async fn source_names(db: &mut PgDb<'_>) -> Result<HashMap<Uuid, (String, String)>> {
+    let connections = db
+        .get_connections()
+        .await?
+        .into_iter()
+        .map(|con| (con.id, con.name))
+        .collect::<HashMap<_, _>>();
+    Ok(db
+        .get_sources()
+        .await?
+        .into_iter()
+        .filter_map(|source| {
+            let con_name = connections.get(&source.data_connection_id)?.clone();
+            Some((source.id, (con_name, source.name)))
+        })
+        .collect())
+}
+
+/// Groups source permissions by the connection of the source, as they are declared.
+fn permissions_decl(
+    permissions: Option<Vec<(Uuid, SourcePermission)>>,
+    names: &HashMap<Uuid, (String, String)>,
+) -> Vec<PermissionsDecl> {
+    let mut by_connection: BTreeMap<&str, Vec<SourcePermissionDecl>> = BTreeMap::new();
+    for (source_id, permission) in permissions.unwrap_or_default() {
+        let Some((con_name, source_name)) = names.get(&source_id) else {
+            continue;
+        };
+        let (allowed_columns, allowed_rows) = permission_columns_and_rows(permission);
+        by_connection
+            .entry(con_name)
+            .or_default()
+            .push(SourcePermissionDecl {
+                data_source_name: source_name.clone(),
+                allowed_columns,
+                allowed_rows,
+            });
+    }
+    by_connection
+        .into_iter()
+        .map(|(data_con_name, mut source_permissions)| {
+            source_permissions.sort_by(|a, b| a.data_source_name.cmp(&b.data_source_name));
+            PermissionsDecl {
+                data_con_name: data_con_name.to_string(),
+                source_permissions,
+            }
+        })
+        .collect()
+}
+
+/// Returns the allowed columns, sorted, and the allowed rows of a [SourcePermission].
+fn permission_columns_and_rows(permission: SourcePermission) -> (Vec<String>, String) {
+    let mut allowed_columns = permission
+        .columns
+        .allowed_columns
+        .into_iter()
+        .collect::<Vec<_>>();
+    allowed_columns.sort();
+    (allowed_columns, permission.rows.allowed_rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use uuid::Uuid;
+
+    use crate::model::access_control::{ColumnPermission, RowPermission, SourcePermission};
+
+    use super::{diff_config, permissions_decl, ChangeAction, ConfigSnapshot};
+
+    #[test]
+    fn test_permissions_decl() {
+        let (orders, customers, lineitem) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let names = HashMap::from([
+            (orders, ("trino".to_string(), "orders".to_string())),
+            (customers, ("trino".to_string(), "customers".to_string())),
+            (lineitem, ("postgres".to_string(), "lineitem".to_string())),
+        ]);
+        let permission = |columns: &[&str]| SourcePermission {
+            columns: ColumnPermission {
+                allowed_columns: HashSet::from_iter(columns.iter().map(|c| c.to_string())),
+            },
+            rows: RowPermission {
+                allowed_rows: "true".to_string(),
+            },
+        };
+
+        let declared = permissions_decl(
+            Some(vec![
+                (orders, permission(&["b", "a"])),
+                (lineitem, permission(&["c"])),
+                (customers, permission(&[])),
+                (Uuid::new_v4(), permission(&["deleted"])),
+            ]),
+            &names,
+        );
+
+        let declared = declared
+            .iter()
+            .map(|p| {
+                (
+                    p.data_con_name.as_str(),
+                    p.source_permissions
+                        .iter()
+                        .map(|s| (s.data_source_name.as_str(), s.allowed_columns.clone()))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            declared,
+            vec![
+                ("postgres", vec![("lineitem", vec!["c".to_string()])]),
+                (
+                    "trino",
+                    vec![
+                        ("customers", vec![]),
+                        ("orders", vec!["a".to_string(), "b".to_string()])
+                    ]
+                ),
+            ]
+        );
+        assert!(permissions_decl(None, &names).is_empty());
+    }
+
+    #[test]
+    fn test_diff_config() {
+        let before = ConfigSnapshot::from([
+            ("Entity a".to_string(), serde_json::json!({"name": "a"})),
+            ("Entity b".to_string(), serde_json::json!({"name": "b"})),
+            ("Role r".to_string(), serde_json::json!({"relays": []})),
+        ]);
+        let after = ConfigSnapshot::from([
+            (
+                "DataSource c/s".to_string(),
+                serde_json::json!({"name": "s"}),
+            ),
+            ("Entity a".to_string(), serde_json::json!({"name": "a"})),
+            ("Role r".to_string(), serde_json::json!({"relays": ["p"]})),
+        ]);
+
+        let changes = diff_config(&before, &after)
+            .into_iter()
+            .map(|c| (c.action, c.object))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                (ChangeAction::Create, "DataSource c/s".to_string()),
+                (ChangeAction::Delete, "Entity b".to_string()),
+                (ChangeAction::Update, "Role r".to_string()),
+            ]
+        );
+        assert!(diff_config(&after, &after).is_empty());
+    }
+}
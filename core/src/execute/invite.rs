@@ -0,0 +1,291 @@
+use std::io::BufReader;
+
+use arrow_flight::Action;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use futures::TryStreamExt;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::crud::PgDb;
+use crate::error::{MeshError, Result};
+use crate::model::relay::{NewRelay, NewRelayInvite, Relay};
+use crate::pki::{load_certificate_from_reader, parse_certificate};
+
+use super::identity::identity_cache;
+use super::result_manager::ResultManager;
+
+/// Type of the Flight [Action] with which a peer redeems an invite at the issuing relay. The
+/// body of the action is a JSON encoded [RedeemInviteRequest] and the single result a JSON
+/// encoded [InviteAcceptance].
+pub const REDEEM_INVITE_ACTION: &str = "redeem_invite";
+
+/// Claims of an invite token, which tell the invited peer where to redeem it. Tokens are the
+/// base64 encoded JSON claims and an HMAC-SHA256 signature of them, keyed with the secret of the
+/// [RelayInvite][crate::model::relay::RelayInvite], so only the issuing relay can verify them.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct InviteClaims {
+    pub invite_id: Uuid,
+    /// Name of the issuing relay.
+    pub relay_name: String,
+    pub rest_endpoint: String,
+    pub flight_endpoint: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl InviteClaims {
+    pub fn sign(&self, secret: &[u8]) -> Result<String> {
+        let payload = serde_json::to_vec(self)?;
+        let signature = mac(secret, &payload)?.finalize().into_bytes();
+        Ok(format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(signature)
+        ))
+    }
+
+    /// Reads the claims of a token without verifying its signature.
+    pub fn decode(token: &str) -> Result<Self> {
+        let (payload, _) = split_token(token)?;
+        Ok(serde_json::from_slice(&payload)?)
+    }
+
+    /// Reads the claims of a token, failing if it was not signed with secret.
+    pub fn verify(token: &str, secret: &[u8]) -> Result<Self> {
+        let (payload, signature) = split_token(token)?;
+        mac(secret, &payload)?
+            .verify_slice(&signature)
+            .map_err(|_e| MeshError::InvalidQuery("Invalid invite token signature!".to_string()))?;
+        Ok(serde_json::from_slice(&payload)?)
+    }
+}
+
+fn mac(secret: &[u8], payload: &[u8]) -> Result<Hmac<Sha256>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret)
+        .map_err(|e| MeshError::Internal(format!("Invalid invite secret: {e}")))?;
+    mac.update(payload);
+    Ok(mac)
+}
+
+fn split_token(token: &str) -> Result<(Vec<u8>, Vec<u8>)> {
+    let invalid = || MeshError::InvalidQuery("Malformed invite token!".to_string());
+    let (payload, signature) = token.trim().split_once('.').ok_or_else(invalid)?;
+    Ok((
+        URL_SAFE_NO_PAD.decode(payload).map_err(|_e| invalid())?,
+        URL_SAFE_NO_PAD.decode(signature).map_err(|_e| invalid())?,
+    ))
+}
+
+/// Sent by the invited peer when redeeming an invite, so the issuing relay can reach it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RedeemInviteRequest {
+    pub token: String,
+    pub rest_endpoint: String,
+    pub flight_endpoint: String,
+}
+
+/// Returned by the issuing relay once an invite is redeemed, so the peer can register it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InviteAcceptance {
+    pub name: String,
+    pub rest_endpoint: String,
+    pub flight_endpoint: String,
+    /// PEM encoded client certificate of the issuing relay.
+    pub x509_cert: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IssuedInvite {
+    pub invite_id: Uuid,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Looks up the local [Relay], whose name and endpoints are shared with invited peers.
+async fn local_relay(db: &mut PgDb<'_>, local_fingerprint: &str) -> Result<Relay> {
+    db.get_relay_by_x509_fingerprint(local_fingerprint)
+        .await
+        .map_err(|_e| {
+            MeshError::InvalidQuery(
+                "The local relay must be declared as a PeerRelay, so that its name and \
+                endpoints are known, before invites can be exchanged!"
+                    .to_string(),
+            )
+        })
+}
+
+/// Issues an invite for a peer, which is registered as peer_name once it redeems the token.
+pub async fn issue_invite(
+    db: &mut PgDb<'_>,
+    local_fingerprint: &str,
+    peer_name: &str,
+    ttl: Duration,
+) -> Result<IssuedInvite> {
+    let local = local_relay(db, local_fingerprint).await?;
+    let mut secret = vec![0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    let invite = db
+        .create_relay_invite(&NewRelayInvite {
+            peer_name: peer_name.to_string(),
+            secret,
+            expires_at: Utc::now() + ttl,
+        })
+        .await?;
+    let token = InviteClaims {
+        invite_id: invite.id,
+        relay_name: local.name,
+        rest_endpoint: local.rest_endpoint,
+        flight_endpoint: local.flight_endpoint,
+        expires_at: invite.expires_at,
+    }
+    .sign(&invite.secret)?;
+    Ok(IssuedInvite {
+        invite_id: invite.id,
+        token,
+        expires_at: invite.expires_at,
+    })
+}
+
+/// Called by the issuing relay when a peer redeems an invite. The peer is registered with the
+/// certificate it authenticated with, given as the (fingerprint, subject, issuer) returned by
+/// [parse_certificate].
+pub async fn accept_invite(
+    db: &mut PgDb<'_>,
+    local_fingerprint: &str,
+    local_cert_pem: &[u8],
+    peer_cert: (String, String, String),
+    request: RedeemInviteRequest,
+) -> Result<InviteAcceptance> {
+    let claims = InviteClaims::decode(&request.token)?;
+    let invite = db.get_relay_invite(&claims.invite_id).await?;
+    InviteClaims::verify(&request.token, &invite.secret)?;
+
+    let (x509_sha256, x509_subject, x509_issuer) = peer_cert;
+    identity_cache().invalidate(&x509_sha256);
+    db.redeem_relay_invite(
+        &invite.id,
+        &NewRelay {
+            name: invite.peer_name,
+            rest_endpoint: request.rest_endpoint,
+            flight_endpoint: request.flight_endpoint,
+            x509_sha256,
+            x509_subject,
+            x509_issuer,
+            x509_cert: None,
+        },
+    )
+    .await?;
+
+    let local = local_relay(db, local_fingerprint).await?;
+    Ok(InviteAcceptance {
+        name: local.name,
+        rest_endpoint: local.rest_endpoint,
+        flight_endpoint: local.flight_endpoint,
+        x509_cert: String::from_utf8_lossy(local_cert_pem).to_string(),
+    })
+}
+
+/// Redeems an invite token issued by another relay over Flight and registers the issuing relay
+/// locally, under name if given and otherwise under the name it declared.
+pub async fn redeem_invite(
+    db: &mut PgDb<'_>,
+    result_manager: &ResultManager,
+    local_fingerprint: &str,
+    token: &str,
+    name: Option<String>,
+) -> Result<Relay> {
+    let local = local_relay(db, local_fingerprint).await?;
+    let claims = InviteClaims::decode(token)?;
+    if claims.expires_at < Utc::now() {
+        return Err(MeshError::InvalidQuery(format!(
+            "Invite {} expired at {}!",
+            claims.invite_id, claims.expires_at
+        )));
+    }
+
+    let request = RedeemInviteRequest {
+        token: token.to_string(),
+        rest_endpoint: local.rest_endpoint,
+        flight_endpoint: local.flight_endpoint,
+    };
+    let mut client = result_manager
+        .flight_client(claims.flight_endpoint.clone())
+        .await?;
+    let remote_err = |e| MeshError::RemoteError(format!("Failed to redeem invite: {e}"));
+    let body = client
+        .do_action(Action::new(
+            REDEEM_INVITE_ACTION,
+            serde_json::to_vec(&request)?,
+        ))
+        .await
+        .map_err(remote_err)?
+        .try_next()
+        .await
+        .map_err(remote_err)?
+        .ok_or(MeshError::RemoteError(
+            "Issuing relay returned no response when redeeming invite!".to_string(),
+        ))?;
+    let acceptance: InviteAcceptance = serde_json::from_slice(&body)?;
+
+    let mut certs =
+        load_certificate_from_reader(&mut BufReader::new(acceptance.x509_cert.as_bytes()))?;
+    if certs.len() != 1 {
+        return Err(MeshError::RemoteError(format!(
+            "Expected exactly one certificate from relay {}, found {}!",
+            acceptance.name,
+            certs.len()
+        )));
+    }
+    let (x509_sha256, x509_subject, x509_issuer) = parse_certificate(&certs.remove(0))?;
+    identity_cache().invalidate(&x509_sha256);
+    db.upsert_relay(&NewRelay {
+        name: name.unwrap_or(acceptance.name),
+        rest_endpoint: acceptance.rest_endpoint,
+        flight_endpoint: acceptance.flight_endpoint,
+        x509_sha256,
+        x509_subject,
+        x509_issuer,
+        x509_cert: Some(acceptance.x509_cert.into_bytes()),
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
+
+    use crate::error::Result;
+
+    use super::InviteClaims;
+
+    #[test]
+    fn test_invite_token() -> Result<()> {
+        let claims = InviteClaims {
+            invite_id: Uuid::new_v4(),
+            relay_name: "relay_a".to_string(),
+            rest_endpoint: "https://relay-a:8443".to_string(),
+            flight_endpoint: "https://relay-a:50055".to_string(),
+            expires_at: Utc.with_ymd_and_hms(2024, 7, 10, 0, 0, 0).unwrap(),
+        };
+        let token = claims.sign(b"secret")?;
+
+        assert_eq!(InviteClaims::decode(&token)?, claims);
+        assert_eq!(InviteClaims::verify(&token, b"secret")?, claims);
+        assert!(InviteClaims::verify(&token, b"other secret").is_err());
+
+        let (_, signature) = token.split_once('.').unwrap();
+        let mut forged = claims;
+        forged.flight_endpoint = "https://attacker:50055".to_string();
+        let forged_payload = forged.sign(b"attacker secret")?;
+        let (forged_payload, _) = forged_payload.split_once('.').unwrap();
+        let forged_token = format!("{forged_payload}.{signature}");
+        assert!(InviteClaims::verify(&forged_token, b"secret").is_err());
+        assert!(InviteClaims::decode("not a token").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::crud::PgDb;
+use crate::error::{MeshError, Result};
+use crate::model::query::{
+    FlightStreamStatus, QueryRequest, QueryTaskRemoteStatus, QueryTaskStatus,
+};
+use crate::model::relay::Relay;
+
+use super::result_manager::ResultManager;
+
+const PRODUCER: &str = "https://github.com/datawebdb/dataweb";
+const RUN_EVENT_SCHEMA: &str = "https://openlineage.io/spec/2-0-2/OpenLineage.json#/$defs/RunEvent";
+const SQL_FACET_SCHEMA: &str = "https://openlineage.io/spec/facets/1-0-1/SQLJobFacet.json";
+const DATAWEB_FACET_SCHEMA: &str =
+    "https://github.com/datawebdb/dataweb/blob/main/README.md#lineage";
+
+/// Where and how often OpenLineage events are emitted, read from the environment. Lineage is
+/// disabled unless OPENLINEAGE_URL is set.
+#[derive(Debug, Clone)]
+pub struct LineageOptions {
+    /// Endpoint which events are POSTed to, e.g. http://marquez:5000/api/v1/lineage
+    pub url: String,
+    /// Sent as a bearer token, if set.
+    pub api_key: Option<String>,
+    pub poll_interval: Duration,
+    /// How long after it was received a request is first considered for lineage, giving peered
+    /// relays time to start sending their results.
+    pub settle: Duration,
+}
+
+impl LineageOptions {
+    pub fn from_env() -> Option<Self> {
+        let url = env::var("OPENLINEAGE_URL").ok()?;
+        let poll_secs = env::var("OPENLINEAGE_POLL_SECS")
+            .unwrap_or("30".to_string())
+            .parse()
+            .expect("Unable to parse OPENLINEAGE_POLL_SECS as u64!");
+        let settle_secs = env::var("OPENLINEAGE_SETTLE_SECS")
+            .unwrap_or("60".to_string())
+            .parse()
+            .expect("Unable to parse OPENLINEAGE_SETTLE_SECS as u64!");
+        Some(Self {
+            url,
+            api_key: env::var("OPENLINEAGE_API_KEY").ok(),
+            poll_interval: Duration::from_secs(poll_secs),
+            settle: Duration::from_secs(settle_secs),
+        })
+    }
+}
+
+/// An OpenLineage RunEvent describing a [QueryRequest] which has no pending work left. The run is
+/// the request, its inputs are the data sources and peered relay results it read, and its
+/// outputs are the results it stored or sent to the relay which forwarded it.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunEvent {
+    pub event_type: String,
+    pub event_time: DateTime<Utc>,
+    pub run: Value,
+    pub job: Value,
+    pub inputs: Vec<Dataset>,
+    pub outputs: Vec<Dataset>,
+    pub producer: String,
+    #[serde(rename = "schemaURL")]
+    pub schema_url: String,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Dataset {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl Dataset {
+    /// Identifies a dataset of a relay by the fingerprint of its certificate, since names of
+    /// relays are only meaningful to the relay which registered them.
+    fn of_relay(relay: &Relay, name: String) -> Self {
+        Self {
+            namespace: format!("dataweb://{}", relay.x509_sha256),
+            name,
+        }
+    }
+
+    /// Follows the OpenLineage naming of object store datasets, e.g. s3://bucket and
+    /// path/to/result.parquet, or file and /path/to/result.parquet.
+    fn of_uri(uri: &str) -> Self {
+        let (namespace, name) = match uri.split_once("://") {
+            Some(("file", path)) => ("file".to_string(), path.to_string()),
+            Some((scheme, rest)) => match rest.split_once('/') {
+                Some((authority, path)) => (format!("{scheme}://{authority}"), path.to_string()),
+                None => (uri.to_string(), String::new()),
+            },
+            None => (String::new(), uri.to_string()),
+        };
+        Self { namespace, name }
+    }
+}
+
+/// Name of the dataset of a result sent as a flight, which the sending relay reports as an
+/// output and the receiving relay as an input, so lineage of both relays joins up.
+fn flight_dataset_name(flight_id: &Uuid) -> String {
+    format!("flight/{flight_id}")
+}
+
+/// Describes the lineage of a [QueryRequest] executed by local_relay.
+fn run_event(
+    local_relay: &Relay,
+    request: &QueryRequest,
+    origin_relay: Option<&Relay>,
+    inputs: BTreeSet<Dataset>,
+    outputs: BTreeSet<Dataset>,
+    failed: bool,
+    now: DateTime<Utc>,
+) -> RunEvent {
+    let user = request.origin_info.origin_user.as_ref();
+    RunEvent {
+        event_type: if failed { "FAIL" } else { "COMPLETE" }.to_string(),
+        event_time: now,
+        run: json!({
+            "runId": request.id,
+            "facets": {
+                "dataweb": {
+                    "_producer": PRODUCER,
+                    "_schemaURL": DATAWEB_FACET_SCHEMA,
+                    "relay": local_relay.name,
+                    "originatorRequestId": request.originator_request_id,
+                    "originRelay": origin_relay.map(|r| &r.x509_sha256),
+                    "user": user.map(|u| &u.x509_subject),
+                    "userFingerprint": user.map(|u| &u.x509_sha256),
+                },
+            },
+        }),
+        job: json!({
+            "namespace": format!("dataweb://{}", local_relay.x509_sha256),
+            "name": "query",
+            "facets": {
+                "sql": {
+                    "_producer": PRODUCER,
+                    "_schemaURL": SQL_FACET_SCHEMA,
+                    "query": request.sql,
+                },
+            },
+        }),
+        inputs: inputs.into_iter().collect(),
+        outputs: outputs.into_iter().collect(),
+        producer: PRODUCER.to_string(),
+        schema_url: RUN_EVENT_SCHEMA.to_string(),
+    }
+}
+
+/// Collects the lineage of a [QueryRequest] which has no pending work left.
+pub async fn lineage_event(
+    db: &mut PgDb<'_>,
+    result_manager: &ResultManager,
+    local_relay: &Relay,
+    request: &QueryRequest,
+) -> Result<RunEvent> {
+    let (_, tasks, remote_tasks) = db.get_query_request(request.id).await?.ok_or_else(|| {
+        MeshError::Internal(format!("Query request {} does not exist", request.id))
+    })?;
+    let flights = db.get_all_flight_streams(&remote_tasks).await?;
+    let origin_relay = request.origin_info.origin_relay.as_ref();
+
+    let mut inputs = BTreeSet::new();
+    let mut outputs = BTreeSet::new();
+    let mut failed = remote_tasks
+        .iter()
+        .any(|t| matches!(t.status, QueryTaskRemoteStatus::Failed));
+    for task in tasks.iter() {
+        let (_, source) = db.get_source_by_id(&task.data_source_id).await?;
+        inputs.insert(Dataset::of_relay(local_relay, source.name));
+        match task.status {
+            QueryTaskStatus::Complete => {
+                outputs.insert(match origin_relay {
+                    Some(origin) => Dataset::of_relay(origin, flight_dataset_name(&task.id)),
+                    None => Dataset::of_uri(&result_manager.task_result_uri(
+                        &task.id,
+                        None,
+                        request.result_format,
+                    )),
+                });
+            }
+            QueryTaskStatus::Failed => failed = true,
+            QueryTaskStatus::Queued | QueryTaskStatus::InProgress => (),
+        }
+    }
+    for (remote, flight) in flights.iter() {
+        match flight.status {
+            FlightStreamStatus::Complete => {
+                inputs.insert(Dataset::of_relay(
+                    local_relay,
+                    flight_dataset_name(&flight.flight_id),
+                ));
+                // Results of forwarded requests are only passed on, never stored
+                if origin_relay.is_none() {
+                    let source_relay = db.get_relay_by_id(&remote.relay_id).await?;
+                    outputs.insert(Dataset::of_uri(&result_manager.task_result_uri(
+                        &flight.flight_id,
+                        Some(&source_relay.name),
+                        request.result_format,
+                    )));
+                }
+            }
+            FlightStreamStatus::Failed => failed = true,
+            FlightStreamStatus::Started | FlightStreamStatus::Invalid => (),
+        }
+    }
+    Ok(run_event(
+        local_relay,
+        request,
+        origin_relay,
+        inputs,
+        outputs,
+        failed,
+        Utc::now(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dataset;
+
+    #[test]
+    fn test_dataset_of_uri() {
+        let s3 = Dataset::of_uri("s3://results/relay/task_1/result.parquet");
+        assert_eq!(s3.namespace, "s3://results");
+        assert_eq!(s3.name, "relay/task_1/result.parquet");
+
+        let local = Dataset::of_uri("file:///tmp/results/task_1/result.parquet");
+        assert_eq!(local.namespace, "file");
+        assert_eq!(local.name, "/tmp/results/task_1/result.parquet");
+    }
+}
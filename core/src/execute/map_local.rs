@@ -1,10 +1,14 @@
 use std::collections::HashMap;
+use std::ops::ControlFlow;
 
-use datafusion::sql::sqlparser::ast::{Statement, TableFactor, TableWithJoins};
+use datafusion::sql::sqlparser::ast::{
+    visit_expressions_mut, Expr, Statement, TableFactor, TableWithJoins, Value,
+};
 
 use crate::error::Result;
 
-use crate::model::access_control::SourcePermission;
+use crate::model::access_control::{RowPermission, SourcePermission};
+use crate::model::user::User;
 
 use crate::{
     error::MeshError,
@@ -35,6 +39,111 @@ pub(crate) fn map_sql(
     Ok(statement)
 }
 
+/// Prefix of the placeholders in a row filter which reference an attribute of the requesting
+/// [User], e.g. `region = '{user.region}'`.
+const USER_ATTRIBUTE_PREFIX: &str = "{user.";
+
+/// Substitutes the attributes of the requesting [User] into the allowed_rows filter of a
+/// [SourcePermission]. A placeholder `{user.<name>}` is replaced by the certificate field
+/// x509_sha256, x509_subject or x509_issuer, or else by the user defined attribute of that name.
+/// The filter is parsed before values are substituted into its string literals, so placeholders
+/// outside of single quoted literals are rejected rather than letting a value add to the filter.
+/// Values with backslashes or control characters are rejected, since some backends, e.g.
+/// MySQL and ClickHouse, treat a backslash as an escape within string literals.
+///
+/// Referencing an attribute the user does not have is an error rather than an empty value, so a
+/// missing attribute never widens the allowed rows.
+pub(crate) fn bind_user_attributes(
+    permission: SourcePermission,
+    user: &User,
+) -> Result<SourcePermission> {
+    let placeholders = permission
+        .rows
+        .allowed_rows
+        .matches(USER_ATTRIBUTE_PREFIX)
+        .count();
+    if placeholders == 0 {
+        return Ok(permission);
+    }
+    let not_quoted = || {
+        MeshError::InvalidQuery(format!(
+            "User attributes must be referenced within single quoted string literals, e.g. \
+            '{{user.region}}', in row permission {}",
+            permission.rows.allowed_rows
+        ))
+    };
+
+    let mut selection =
+        parse_sql_as_expr(&permission.rows.allowed_rows).map_err(|_e| not_quoted())?;
+    let mut bound = 0;
+    if let ControlFlow::Break(e) = visit_expressions_mut(&mut selection, |expr| {
+        if let Expr::Value(Value::SingleQuotedString(literal)) = expr {
+            match bind_literal(literal, user, &permission.rows.allowed_rows) {
+                Ok((value, count)) => {
+                    *literal = value;
+                    bound += count;
+                }
+                Err(e) => return ControlFlow::Break(e),
+            }
+        }
+        ControlFlow::Continue(())
+    }) {
+        return Err(e);
+    }
+    if bound != placeholders {
+        return Err(not_quoted());
+    }
+
+    Ok(SourcePermission {
+        columns: permission.columns,
+        rows: RowPermission {
+            allowed_rows: selection.to_string(),
+        },
+    })
+}
+
+/// Replaces the user attribute placeholders of a string literal by their values, returning the
+/// new literal and the number of placeholders replaced.
+fn bind_literal(literal: &str, user: &User, allowed_rows: &str) -> Result<(String, usize)> {
+    let mut rest = literal;
+    let mut bound = String::with_capacity(literal.len());
+    let mut count = 0;
+    while let Some(start) = rest.find(USER_ATTRIBUTE_PREFIX) {
+        bound.push_str(&rest[..start]);
+        let placeholder = &rest[start + USER_ATTRIBUTE_PREFIX.len()..];
+        let end = placeholder
+            .find('}')
+            .ok_or(MeshError::InvalidQuery(format!(
+                "Unterminated user attribute in row permission {allowed_rows}"
+            )))?;
+        let name = &placeholder[..end];
+        let value = match name {
+            "x509_sha256" => &user.x509_sha256,
+            "x509_subject" => &user.x509_subject,
+            "x509_issuer" => &user.x509_issuer,
+            _ => user
+                .attributes
+                .misc
+                .get(name)
+                .ok_or(MeshError::InvalidQuery(format!(
+                "Requesting user does not have the attribute {name} referenced by a row permission"
+            )))?,
+        };
+        if value.chars().any(|c| c == '\\' || c.is_control()) {
+            return Err(MeshError::InvalidQuery(format!(
+                "The attribute {name} of the requesting user contains a backslash or control \
+                character, so it cannot be referenced by a row permission"
+            )));
+        }
+        // Quotes within the value are escaped once the literal is written as SQL again
+        bound.push_str(value);
+        count += 1;
+        rest = &placeholder[end + 1..];
+    }
+    bound.push_str(rest);
+    Ok((bound, count))
+}
+
 /// Applies the [SourcePermission] to [TableFactor] returning a new [TableFactor] which only allows
 /// access to the specified columns and rows.
 fn apply_source_permission(
@@ -129,7 +238,10 @@ mod tests {
 
     use crate::execute::planning::EntityContext;
     use crate::execute::validation::logical_round_trip;
-    use crate::model::access_control::{ColumnPermission, RowPermission, SourcePermission};
+    use crate::model::access_control::{
+        ColumnPermission, PolicyMode, RowPermission, SourcePermission,
+    };
+    use crate::model::data_stores::engines::SourceEngines;
     use crate::model::data_stores::options::SourceOptions;
     use crate::model::data_stores::DataField;
     use crate::model::mappings::{Mapping, Transformation};
@@ -144,7 +256,9 @@ mod tests {
         model::data_stores::{options::trino::TrinoSource, DataSource},
     };
 
-    use super::{apply_info_substitutions, apply_source_substitutions};
+    use crate::model::user::{User, UserAttributes};
+
+    use super::{apply_info_substitutions, apply_source_substitutions, bind_user_attributes};
 
     #[test]
     fn test_source_substitution() -> Result<()> {
@@ -174,6 +288,10 @@ mod tests {
                 source_sql: "select * from test".to_string(),
                 data_connection_id: Uuid::new_v4(),
                 source_options: SourceOptions::Trino(TrinoSource {}),
+                paused: false,
+                engines: SourceEngines::default(),
+                freshness_query: None,
+                policy_mode: PolicyMode::Permissive,
             },
             &SourcePermission {
                 columns: ColumnPermission {
@@ -254,4 +372,77 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_bind_user_attributes() -> Result<()> {
+        let mut attributes = UserAttributes::new();
+        attributes
+            .misc
+            .insert("region".to_string(), "o'hare".to_string());
+        let user = User {
+            id: Uuid::new_v4(),
+            x509_sha256: "abc123".to_string(),
+            x509_subject: "CN=test".to_string(),
+            x509_issuer: "CN=ca".to_string(),
+            attributes,
+        };
+        let permission = |allowed_rows: &str| SourcePermission {
+            columns: ColumnPermission {
+                allowed_columns: HashSet::from_iter(["col1".to_string()]),
+            },
+            rows: RowPermission {
+                allowed_rows: allowed_rows.to_string(),
+            },
+        };
+
+        let bound = bind_user_attributes(
+            permission("region = '{user.region}' or owner = '{user.x509_sha256}'"),
+            &user,
+        )?;
+        assert_eq!(
+            bound.rows.allowed_rows,
+            "region = 'o''hare' OR owner = 'abc123'"
+        );
+        assert_eq!(bound.columns, permission("").columns);
+
+        assert_eq!(
+            bind_user_attributes(permission("col1 > 0"), &user)?,
+            permission("col1 > 0")
+        );
+        assert!(bind_user_attributes(permission("team = '{user.team}'"), &user).is_err());
+        assert!(bind_user_attributes(permission("team = '{user.team"), &user).is_err());
+
+        // A placeholder outside of a string literal would let the value add to the filter
+        let mut widening = user.clone();
+        widening
+            .attributes
+            .misc
+            .insert("region".to_string(), "x OR 1=1".to_string());
+        for allowed_rows in ["region = {user.region}", "region = \"{user.region}\""] {
+            assert!(matches!(
+                bind_user_attributes(permission(allowed_rows), &widening),
+                Err(MeshError::InvalidQuery(_))
+            ));
+        }
+        assert_eq!(
+            bind_user_attributes(permission("region = '{user.region}'"), &widening)?
+                .rows
+                .allowed_rows,
+            "region = 'x OR 1=1'"
+        );
+
+        // A backslash would escape the closing quote on backends such as MySQL and ClickHouse
+        for value in ["\\' or 1=1 --", "line\nbreak"] {
+            let mut user = user.clone();
+            user.attributes
+                .misc
+                .insert("region".to_string(), value.to_string());
+            assert!(matches!(
+                bind_user_attributes(permission("region = '{user.region}'"), &user),
+                Err(MeshError::InvalidQuery(_))
+            ));
+        }
+
+        Ok(())
+    }
 }
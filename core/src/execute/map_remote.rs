@@ -8,7 +8,8 @@ use datafusion::sql::sqlparser::ast::Statement;
 use tracing::debug;
 
 use super::parse_utils::{
-    apply_aliases, apply_col_iden_mapping, parse_sql_as_table_factor, substitute_table_factor,
+    apply_aliases, apply_col_iden_mapping, name_to_ident, parse_sql_as_table_factor,
+    substitute_table_factor,
 };
 
 /// Substitutes appropriate [Entity][crate::model::entity::Entity] and
@@ -50,9 +51,10 @@ fn apply_info_substitutions(
             let transform = &map.transformation;
             (
                 *info,
-                transform
-                    .other_to_local_info
-                    .replace(&transform.replace_from, &map.info_mapped_name),
+                transform.other_to_local_info.replace(
+                    &transform.replace_from,
+                    &name_to_ident(&map.info_mapped_name).to_string(),
+                ),
             )
         })
         .collect::<HashMap<_, _>>();
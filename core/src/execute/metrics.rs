@@ -0,0 +1,226 @@
+use std::convert::Infallible;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use crate::crud::PgDb;
+use crate::error::{MeshError, Result};
+
+/// Upper bounds in seconds of the task latency histogram buckets, from sub second cache hits up
+/// to hour long scans.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0,
+];
+
+/// Metrics of every service of the relay, exposed in the Prometheus text format. The rest_server
+/// serves them at /metrics, while the query_runner and flight_server each bind a listener of
+/// their own via [serve_metrics].
+pub struct Metrics {
+    registry: Registry,
+    /// Query requests received, labeled by the api which received them.
+    pub queries_received: IntCounterVec,
+    /// Local tasks executed, labeled by data source and whether they completed, failed, were
+    /// cancelled or were served from the result cache.
+    pub tasks_executed: IntCounterVec,
+    /// Time taken to execute local tasks, labeled by data source.
+    pub task_latency: HistogramVec,
+    /// Arrow bytes of results streamed out of the relay, labeled by where they were streamed.
+    pub bytes_streamed: IntCounterVec,
+    /// Tasks waiting in or taken from the message broker, labeled by data source and status.
+    pub broker_depth: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("relay".to_string()), None)
+            .expect("Invalid metrics registry prefix!");
+        let queries_received = IntCounterVec::new(
+            Opts::new("queries_received_total", "Query requests received"),
+            &["api"],
+        )
+        .expect("Invalid queries_received metric!");
+        let tasks_executed = IntCounterVec::new(
+            Opts::new("tasks_executed_total", "Local query tasks executed"),
+            &["data_source", "status"],
+        )
+        .expect("Invalid tasks_executed metric!");
+        let task_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "task_latency_seconds",
+                "Time taken to execute local query tasks",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+            &["data_source"],
+        )
+        .expect("Invalid task_latency metric!");
+        let bytes_streamed = IntCounterVec::new(
+            Opts::new(
+                "bytes_streamed_total",
+                "Arrow bytes of results streamed out of the relay",
+            ),
+            &["sink"],
+        )
+        .expect("Invalid bytes_streamed metric!");
+        let broker_depth = IntGaugeVec::new(
+            Opts::new("broker_depth", "Query tasks queued or in progress"),
+            &["data_source", "status"],
+        )
+        .expect("Invalid broker_depth metric!");
+
+        for metric in [
+            Box::new(queries_received.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(tasks_executed.clone()),
+            Box::new(task_latency.clone()),
+            Box::new(bytes_streamed.clone()),
+            Box::new(broker_depth.clone()),
+        ] {
+            registry.register(metric).expect("Metric registered twice!");
+        }
+
+        Self {
+            registry,
+            queries_received,
+            tasks_executed,
+            task_latency,
+            bytes_streamed,
+            broker_depth,
+        }
+    }
+
+    /// Counts a query request received by api, e.g. rest or flight.
+    pub fn query_received(&self, api: &str) {
+        self.queries_received.with_label_values(&[api]).inc();
+    }
+
+    /// Records a local task of data_source which finished with status after elapsed.
+    pub fn task_executed(&self, data_source: &str, status: &str, elapsed: Duration) {
+        self.tasks_executed
+            .with_label_values(&[data_source, status])
+            .inc();
+        self.task_latency
+            .with_label_values(&[data_source])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Counts bytes of results streamed to sink, e.g. a peered relay or a rest client.
+    pub fn bytes_streamed(&self, sink: &str, bytes: usize) {
+        self.bytes_streamed
+            .with_label_values(&[sink])
+            .inc_by(bytes as u64);
+    }
+
+    /// Replaces the broker depth of every data source with the current queue stats, so that
+    /// sources which were removed are no longer reported.
+    pub async fn refresh_broker_depth(&self, db: &mut PgDb<'_>) -> Result<()> {
+        let stats = db.get_source_queue_stats(None).await?;
+        self.broker_depth.reset();
+        for source in stats {
+            self.broker_depth
+                .with_label_values(&[&source.data_source_name, "queued"])
+                .set(source.queued);
+            self.broker_depth
+                .with_label_values(&[&source.data_source_name, "in_progress"])
+                .set(source.in_progress);
+        }
+        Ok(())
+    }
+
+    /// Encodes every metric in the Prometheus text exposition format.
+    pub fn encode(&self) -> Result<String> {
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| MeshError::Internal(format!("Failed to encode metrics: {e}")))?;
+        String::from_utf8(buffer)
+            .map_err(|e| MeshError::Internal(format!("Metrics are not valid UTF-8: {e}")))
+    }
+}
+
+/// Returns the [Metrics] shared by every service of the process.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Content type of the Prometheus text exposition format.
+pub fn metrics_content_type() -> &'static str {
+    prometheus::TEXT_FORMAT
+}
+
+/// Reads the address a standalone metrics listener binds to from var. None if it is not set,
+/// which disables the listener.
+pub fn metrics_addr(var: &str) -> Option<SocketAddr> {
+    env::var(var).ok().map(|addr| {
+        addr.parse()
+            .unwrap_or_else(|_| panic!("Unable to parse {var} as a socket address!"))
+    })
+}
+
+async fn handle_metrics(req: Request<Body>) -> std::result::Result<Response<Body>, Infallible> {
+    let response = if req.method() != Method::GET || req.uri().path() != "/metrics" {
+        Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+    } else {
+        match metrics().encode() {
+            Ok(encoded) => Response::builder()
+                .header(header::CONTENT_TYPE, metrics_content_type())
+                .body(Body::from(encoded)),
+            Err(e) => {
+                error!("{e}");
+                Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::empty())
+            }
+        }
+    };
+    Ok(response.expect("Metrics response is valid"))
+}
+
+/// Serves GET /metrics over plain HTTP at addr until shutdown is cancelled, for services which
+/// have no HTTP server of their own.
+pub async fn serve_metrics(addr: SocketAddr, shutdown: CancellationToken) -> Result<()> {
+    let make_svc =
+        make_service_fn(|_conn| async { Ok::<_, Infallible>(service_fn(handle_metrics)) });
+    let server = Server::try_bind(&addr)
+        .map_err(|e| MeshError::Internal(format!("Failed to bind metrics listener {addr}: {e}")))?
+        .serve(make_svc);
+    info!("Serving /metrics on {addr}");
+    server
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
+        .map_err(|e| MeshError::Internal(format!("Metrics listener failed: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Metrics;
+
+    #[test]
+    fn test_encode() {
+        let metrics = Metrics::new();
+        metrics.query_received("rest");
+        metrics.query_received("rest");
+        metrics.task_executed("orders", "complete", Duration::from_millis(200));
+        metrics.bytes_streamed("relay", 1024);
+
+        let encoded = metrics.encode().unwrap();
+        assert!(encoded.contains("relay_queries_received_total{api=\"rest\"} 2"));
+        assert!(encoded
+            .contains("relay_tasks_executed_total{data_source=\"orders\",status=\"complete\"} 1"));
+        assert!(encoded
+            .contains("relay_task_latency_seconds_bucket{data_source=\"orders\",le=\"0.25\"} 1"));
+        assert!(encoded.contains("relay_bytes_streamed_total{sink=\"relay\"} 1024"));
+    }
+}
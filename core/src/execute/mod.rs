@@ -1,31 +1,56 @@
 pub mod data_stores;
+pub mod discover;
+pub mod freshness;
+pub mod hints;
+pub mod identity;
+pub mod inspect;
+pub mod invite;
+pub mod lineage;
 mod map_local;
 mod map_remote;
-pub(crate) mod parse_utils;
+pub mod metrics;
+pub mod outbox;
+pub mod parse_utils;
 pub(crate) mod planning;
+pub mod progress;
+pub mod relay_status;
+pub mod replay;
 pub mod result_manager;
+#[cfg(feature = "datafusion")]
+pub mod scratch;
+pub mod shutdown;
+pub mod telemetry;
+pub mod udf;
 pub mod utils;
 pub mod validation;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::ops::ControlFlow;
+use std::sync::OnceLock;
 
 use crate::error::Result;
-use crate::model::access_control::SourcePermission;
-use crate::model::data_stores::DataSource;
+use crate::model::access_control::{ColumnPermission, PolicyMode, RowPermission, SourcePermission};
+use crate::model::data_stores::{DataConnection, DataSource};
+use crate::model::entity::Entity;
 
 use crate::model::query::RawQueryRequest;
 use crate::model::relay::Relay;
 use crate::model::user::User;
 use crate::{crud::PgDb, error::MeshError, model::query::Query};
 
+use arrow_schema::{DataType, Field, Schema};
+use chrono::{DateTime, Utc};
 use datafusion::sql::sqlparser::ast::Statement;
 use datafusion::sql::sqlparser::ast::{TableFactor, VisitMut, VisitorMut};
-use tracing::debug;
+use tracing::{debug, error, info};
 use uuid::Uuid;
 
-use self::map_local::map_sql;
+use self::hints::QueryHints;
+use self::map_local::{bind_user_attributes, map_sql};
 use self::map_remote::map_remote_request;
+use self::parse_utils::{cap_limit, inject_default_limit, referenced_information, statement_limit};
+use self::utils::validate_sql_and_logical_round_trip;
 
 struct TableVisitor<F>(F);
 
@@ -85,8 +110,53 @@ pub enum Requester {
     Relay(Relay),
 }
 
-/// Resolves a [RawQueryRequest] to the corresponding [Query]s which must be
+/// Returns the LIMIT injected into interactive [RawQueryRequest]s which do not specify one, read
+/// from INTERACTIVE_DEFAULT_LIMIT. A limit of 0 disables the injection.
+pub fn interactive_default_limit() -> u64 {
+    static LIMIT: OnceLock<u64> = OnceLock::new();
+    *LIMIT.get_or_init(|| {
+        env::var("INTERACTIVE_DEFAULT_LIMIT")
+            .unwrap_or("1000".to_string())
+            .parse()
+            .expect("Unable to parse INTERACTIVE_DEFAULT_LIMIT as u64!")
+    })
+}
+
+/// Returns how long a request_uuid marks new requests with the same id as duplicates, read from
+/// DEDUP_RETENTION_SECS. If unset, a request_uuid is deduplicated for as long as its request is
+/// stored.
+pub fn dedup_retention() -> Option<chrono::Duration> {
+    static RETENTION: OnceLock<Option<chrono::Duration>> = OnceLock::new();
+    *RETENTION.get_or_init(|| {
+        env::var("DEDUP_RETENTION_SECS").ok().map(|secs| {
+            chrono::Duration::seconds(
+                secs.parse()
+                    .expect("Unable to parse DEDUP_RETENTION_SECS as i64!"),
+            )
+        })
+    })
+}
+
+/// A [Query] which must be executed against a local [DataSource].
+pub struct LocalQuery {
+    pub data_source_id: Uuid,
+    pub query: Query,
+    /// Set if the connection executing the query is currently outside of its declared
+    /// execution windows, to the time the query must be deferred until.
+    pub not_before: Option<DateTime<Utc>>,
+    /// The name of the [SourceEngine][crate::model::data_stores::engines::SourceEngine] selected to execute the query, if any.
+    pub engine: Option<String>,
+    /// The permission which was applied when mapping the query.
+    pub permission: SourcePermission,
+}
+
+/// Resolves a [RawQueryRequest] to the corresponding [LocalQuery]s which must be
 /// executed on the local relay to complete the request.
+///
+/// If permission_snapshot is set, the [SourcePermission] recorded for each [DataSource] is
+/// applied instead of evaluating the current policies, sources without a recorded permission are
+/// skipped and usage is not recorded. This reproduces an earlier request, see
+/// [replay_request][crate::execute::replay::replay_request].
 pub async fn request_to_local_queries(
     db: &mut PgDb<'_>,
     query: &Statement,
@@ -94,12 +164,61 @@ pub async fn request_to_local_queries(
     raw_request: &RawQueryRequest,
     direct_requester: &Requester,
     requesting_user: &User,
-) -> Result<Vec<(Uuid, Query)>> {
+    permission_snapshot: Option<&HashMap<Uuid, SourcePermission>>,
+) -> Result<Vec<LocalQuery>> {
+    let hints = QueryHints::parse(raw_request)?;
     let sources = db.get_mappings_by_entity_names(vec![entity_name]).await?;
+    let now = Utc::now();
+
+    let mut query = query.to_owned();
+    if let Some(max_rows) = hints.max_rows {
+        if cap_limit(&mut query, max_rows) {
+            debug!("Limited request to {max_rows} rows as hinted");
+        }
+    }
+    let default_limit = interactive_default_limit();
+    if raw_request.interactive
+        && !raw_request.count_only
+        && default_limit > 0
+        && inject_default_limit(&mut query, default_limit)
+    {
+        debug!("Limited interactive request to {default_limit} rows");
+    }
+    let query = &query;
+    let limit = statement_limit(query);
+
+    // Usage statistics are informational, so failing to record them does not fail the query.
+    if permission_snapshot.is_some() {
+        debug!("Not recording usage of entity {entity_name} for a replayed request");
+    } else if let Err(e) = db
+        .record_entity_access(entity_name, &referenced_information(query, entity_name))
+        .await
+    {
+        error!("Failed to record usage of entity {entity_name}: {e}");
+    }
+
+    // Without an explicit return schema, every source returns the schema declared by the entity
+    // rather than whatever its own data infers to, so combined results do not drift.
+    let return_schema = match &raw_request.return_arrow_schema {
+        Some(schema) => Some(schema.clone()),
+        None if !raw_request.count_only => {
+            let (_, _, schema) =
+                validate_sql_and_logical_round_trip(&query.to_string(), db).await?;
+            Some(schema)
+        }
+        None => None,
+    };
 
     debug!("Got mappings for entity {entity_name}: {sources:?}");
     let mut queries = Vec::with_capacity(sources.len());
-    for ((_, source), mappings) in sources {
+    for ((con, source), mappings) in sources {
+        if source.paused {
+            info!(
+                "Skipping paused source {} for entity {entity_name}",
+                source.name
+            );
+            continue;
+        }
         debug!("Creating map for {}", source.name);
         let mut info_map_lookup = HashMap::with_capacity(mappings.len());
         for (entity, info, field, map) in mappings.iter() {
@@ -118,27 +237,193 @@ pub async fn request_to_local_queries(
                 )));
             }
         }
-        let permission =
-            evaluate_permission_policies(db, direct_requester, requesting_user, &source).await?;
+        let permission = match permission_snapshot {
+            Some(snapshot) => match snapshot.get(&source.id) {
+                Some(permission) => permission.clone(),
+                None => {
+                    info!(
+                        "Skipping source {} which has no recorded permission to replay",
+                        source.name
+                    );
+                    continue;
+                }
+            },
+            None => bind_user_attributes(
+                evaluate_permission_policies(db, direct_requester, requesting_user, &source)
+                    .await?,
+                requesting_user,
+            )?,
+        };
+
+        let engine = source
+            .engines
+            .select(hints.engine.as_deref(), limit)
+            .cloned();
+        let (con, engine_source) = match &engine {
+            Some(engine) => {
+                debug!("Selected engine {} for source {}", engine.name, source.name);
+                (
+                    db.get_connection(&engine.data_con_name).await?,
+                    source.with_engine(engine),
+                )
+            }
+            None => (con, source),
+        };
+
         let source_mapped_sql = map_sql(
             query.to_owned(),
             entity_name,
+            &engine_source,
+            &info_map_lookup,
+            permission.clone(),
+        )?;
+        let not_before = con.execution_windows.next_open(now);
+        if not_before.is_some() && hints.skip_deferred {
+            info!(
+                "Skipping source {} whose connection {} is outside of its execution windows, as hinted",
+                engine_source.name, con.name
+            );
+            continue;
+        }
+        if let Some(t) = &not_before {
+            info!(
+                "Connection {} is outside of its execution windows, deferring source {} until {t}",
+                con.name, engine_source.name
+            );
+        }
+        let query = if raw_request.count_only {
+            count_only_query(&source_mapped_sql)
+        } else {
+            Query {
+                sql: source_mapped_sql.to_string(),
+                return_schema: return_schema.clone(),
+            }
+        };
+        queries.push(LocalQuery {
+            data_source_id: engine_source.id,
+            query,
+            not_before,
+            engine: engine.map(|e| e.name),
+            permission,
+        });
+    }
+
+    if let Some(max_sources) = hints.max_sources {
+        if queries.len() > max_sources {
+            return Err(MeshError::InvalidQuery(format!(
+                "Request would be executed by {} sources of entity {entity_name}, \
+                more than the hinted max_sources of {max_sources}",
+                queries.len()
+            )));
+        }
+    }
+
+    Ok(queries)
+}
+
+/// Resolves the validation_query of an [Entity] to a [Query] for each mapped [DataSource] which
+/// is not paused or outside of its connection's execution windows. Validation queries are
+/// permitted to access every mapped field so that any broken mapping is detected.
+pub async fn entity_validation_queries(
+    db: &mut PgDb<'_>,
+    entity: &Entity,
+) -> Result<Vec<(DataConnection, DataSource, Query)>> {
+    let sql = entity
+        .validation_query
+        .as_ref()
+        .ok_or(MeshError::InvalidQuery(format!(
+            "Entity {} does not declare a validation query",
+            entity.name
+        )))?;
+    let (entity_name, statement, _) = validate_sql_and_logical_round_trip(sql, db).await?;
+    if entity_name != entity.name {
+        return Err(MeshError::InvalidQuery(format!(
+            "Validation query for entity {} references entity {entity_name}",
+            entity.name
+        )));
+    }
+
+    let sources = db.get_mappings_by_entity_names(vec![&entity.name]).await?;
+    let now = Utc::now();
+    let mut queries = Vec::with_capacity(sources.len());
+    for ((con, source), mappings) in sources {
+        if source.paused || !con.execution_windows.is_open(now) {
+            continue;
+        }
+        let info_map_lookup = mappings
+            .iter()
+            .map(|(_, info, field, map)| (info.name.as_str(), (field, map)))
+            .collect::<HashMap<_, _>>();
+        let permission = SourcePermission {
+            columns: ColumnPermission {
+                allowed_columns: mappings
+                    .iter()
+                    .map(|(_, _, field, _)| field.path.clone())
+                    .collect(),
+            },
+            rows: RowPermission {
+                allowed_rows: "true".to_string(),
+            },
+        };
+        let sql = map_sql(
+            statement.to_owned(),
+            &entity.name,
             &source,
             &info_map_lookup,
             permission,
-        )?;
+        )?
+        .to_string();
         queries.push((
-            source.id,
+            con,
+            source,
             Query {
-                sql: source_mapped_sql.to_string(),
-                return_schema: raw_request.return_arrow_schema.clone(),
+                sql,
+                return_schema: None,
             },
         ));
     }
-
     Ok(queries)
 }
 
+/// Name of the column returned by [DataSource]s for a count_only [RawQueryRequest].
+pub const COUNT_ONLY_COLUMN: &str = "row_count";
+
+/// Wraps SQL mapped to a [DataSource] so that only the number of rows it returns is computed.
+fn count_only_query(source_mapped_sql: &Statement) -> Query {
+    Query {
+        sql: format!(
+            "select count(*) as {COUNT_ONLY_COLUMN} from ({source_mapped_sql}) as counted"
+        ),
+        return_schema: Some(Schema::new(vec![Field::new(
+            COUNT_ONLY_COLUMN,
+            DataType::Int64,
+            false,
+        )])),
+    }
+}
+
+/// Resolves the [DataConnection] and [DataSource] which execute a task, taking into account the
+/// [SourceEngine][crate::model::data_stores::engines::SourceEngine] selected for the task, if any.
+pub async fn resolve_task_engine(
+    db: &mut PgDb<'_>,
+    con: DataConnection,
+    source: DataSource,
+    engine: Option<&str>,
+) -> Result<(DataConnection, DataSource)> {
+    let engine = match engine {
+        Some(name) => source
+            .engines
+            .get(name)
+            .ok_or(MeshError::InvalidQuery(format!(
+                "Engine {name} is no longer declared for source {}",
+                source.name
+            )))?,
+        None => return Ok((con, source)),
+    };
+    let con = db.get_connection(&engine.data_con_name).await?;
+    Ok((con, source.with_engine(engine)))
+}
+
 /// Looks up relevant [SourcePermission]s for the requesting [User] and
 /// [Relay] (if query not recieved directly by a [User]). The
 /// source permissions are combined into a single access policy by
@@ -153,48 +438,108 @@ async fn evaluate_permission_policies(
 ) -> Result<SourcePermission> {
     debug!("Evaluating permission policies for direct_requester {direct_requester:?} and user: {requesting_user:?}");
     let default_permission = db.get_default_source_permission(&source.id).await?;
-    let (user_permission, relay_permission) = match direct_requester {
-        Requester::User(user) => {
-            let user_permission = db
-                .get_user_source_permission(&user.x509_sha256, &source.id)
-                .await?;
-            (user_permission, None)
-        }
-        Requester::Relay(relay) => {
-            let user_permission = db
-                .get_user_source_permission(&requesting_user.x509_sha256, &source.id)
-                .await?;
-
-            let relay_permission = db
-                .get_relay_source_permission(&relay.id, &source.id)
-                .await?;
-            (user_permission, relay_permission)
-        }
+    let user_fingerprint = match direct_requester {
+        Requester::User(user) => &user.x509_sha256,
+        Requester::Relay(_) => &requesting_user.x509_sha256,
+    };
+    let user_permission = db
+        .get_user_source_permission(user_fingerprint, &source.id)
+        .await?;
+    let user_role_permissions = db
+        .get_user_role_source_permissions(user_fingerprint, &source.id)
+        .await?;
+    let (relay_permission, relay_role_permissions) = match direct_requester {
+        Requester::User(_) => (None, vec![]),
+        Requester::Relay(relay) => (
+            db.get_relay_source_permission(&relay.id, &source.id)
+                .await?,
+            db.get_relay_role_source_permissions(&relay.id, &source.id)
+                .await?,
+        ),
     };
 
     debug!(
-        "Got default permission: {:?}, user permission: {:?}, and relay permission: {:?}",
-        default_permission, user_permission, relay_permission
+        "Got default permission: {:?}, user permission: {:?}, user role permissions: {:?}, \
+        relay permission: {:?}, and relay role permissions: {:?}",
+        default_permission,
+        user_permission,
+        user_role_permissions,
+        relay_permission,
+        relay_role_permissions
     );
 
-    let permission = match (user_permission, relay_permission) {
-        (Some(u), Some(r)) => default_permission
-            .source_permission
-            .union(&u.source_permission.intersection(&r.source_permission)),
-        (None, Some(r)) => default_permission
-            .source_permission
-            .union(&r.source_permission),
-        (Some(u), None) => default_permission
-            .source_permission
-            .union(&u.source_permission),
-        (None, None) => default_permission.source_permission,
+    // Roles grant in addition to the direct grant of their members
+    let user_permission = union_permissions(
+        user_permission
+            .map(|u| u.source_permission)
+            .into_iter()
+            .chain(user_role_permissions),
+    );
+    let relay_permission = union_permissions(
+        relay_permission
+            .map(|r| r.source_permission)
+            .into_iter()
+            .chain(relay_role_permissions),
+    );
+
+    let default_permission = default_permission.source_permission;
+    let explicit_permission = match (&user_permission, &relay_permission) {
+        (Some(u), Some(r)) => Some(u.intersection(r)),
+        (None, Some(r)) => Some(r.clone()),
+        (Some(u), None) => Some(u.clone()),
+        (None, None) => None,
+    };
+    let permission = match (source.policy_mode, explicit_permission) {
+        (PolicyMode::Permissive, Some(e)) => default_permission.union(&e),
+        (PolicyMode::Permissive, None) => default_permission.clone(),
+        (PolicyMode::Restrictive, Some(e)) => default_permission.intersection(&e),
+        (PolicyMode::Restrictive, None) => SourcePermission {
+            columns: ColumnPermission {
+                allowed_columns: HashSet::new(),
+            },
+            rows: RowPermission {
+                allowed_rows: "false".to_string(),
+            },
+        },
     };
 
+    let mut allowed_columns = permission
+        .columns
+        .allowed_columns
+        .iter()
+        .collect::<Vec<_>>();
+    allowed_columns.sort();
+    for column in allowed_columns {
+        let granted_by = [
+            ("default", Some(&default_permission)),
+            ("user", user_permission.as_ref()),
+            ("relay", relay_permission.as_ref()),
+        ]
+        .into_iter()
+        .filter(|(_, p)| p.is_some_and(|p| p.columns.allowed_columns.contains(column)))
+        .map(|(policy, _)| policy)
+        .collect::<Vec<_>>();
+        debug!(
+            "Column {column} of source {} is allowed by the {:?} policy granted by {granted_by:?}",
+            source.name, source.policy_mode
+        );
+    }
+
     debug!("Resolved to {:?}", permission);
 
     Ok(permission)
 }
 
+/// Combines the [SourcePermission]s granted to a single user or relay, returning None if there
+/// are none.
+fn union_permissions(
+    permissions: impl IntoIterator<Item = SourcePermission>,
+) -> Option<SourcePermission> {
+    permissions
+        .into_iter()
+        .reduce(|acc, permission| acc.union(&permission))
+}
+
 /// Converts a [RawQueryRequest] received locally to a [RawQueryRequest] for each
 /// peered remote relay.
 pub async fn request_to_remote_requests(
@@ -239,14 +584,136 @@ pub async fn request_to_remote_requests(
             relay.id,
             RawQueryRequest {
                 sql: mapped_query.to_string(),
+                statements: vec![],
                 request_uuid: Some(*request_uuid),
                 requesting_user: Some(requesting_user.clone()),
                 originating_relay: Some(originating_relay.clone()),
                 originating_task_id: raw_request.originating_task_id,
                 return_arrow_schema: raw_request.return_arrow_schema.clone(),
+                engine_hint: raw_request.engine_hint.clone(),
+                count_only: raw_request.count_only,
+                interactive: raw_request.interactive,
+                reexecution_of: raw_request.reexecution_of,
+                hints: raw_request.hints.clone(),
             },
         ))
     }
 
     Ok(remote_query_requests)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, Int64Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::prelude::SessionContext;
+    use datafusion::sql::sqlparser::{dialect::GenericDialect, parser::Parser};
+    use uuid::Uuid;
+
+    use crate::error::Result;
+    use crate::execute::planning::EntityContext;
+    use crate::execute::validation::logical_round_trip;
+    use crate::model::access_control::{
+        ColumnPermission, PolicyMode, RowPermission, SourcePermission,
+    };
+    use crate::model::data_stores::engines::SourceEngines;
+    use crate::model::data_stores::options::{trino::TrinoSource, SourceOptions};
+    use crate::model::data_stores::{DataField, DataSource};
+    use crate::model::mappings::{Mapping, Transformation};
+
+    use super::map_local::map_sql;
+    use super::{count_only_query, COUNT_ONLY_COLUMN};
+
+    #[tokio::test]
+    async fn test_count_only_query_returns_one_row_per_source() -> Result<()> {
+        let mut ast = Parser::parse_sql(&GenericDialect {}, "select foo from entityname").unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("foo", DataType::Int32, false)]));
+        let (statement, _) =
+            logical_round_trip(ast.remove(0), EntityContext::new("entityname", schema))?;
+        let map = Mapping {
+            information_id: Uuid::new_v4(),
+            data_field_id: Uuid::new_v4(),
+            transformation: Transformation {
+                other_to_local_info: "{v}".to_string(),
+                replace_from: "{v}".to_string(),
+            },
+        };
+        let permission = SourcePermission {
+            columns: ColumnPermission {
+                allowed_columns: HashSet::from(["col1".to_string()]),
+            },
+            rows: RowPermission {
+                allowed_rows: "col1 > 1".to_string(),
+            },
+        };
+
+        let ctx = SessionContext::new();
+        for (table, rows) in [("table_a", vec![1, 2, 3]), ("table_b", vec![])] {
+            let data_source_id = Uuid::new_v4();
+            let source = DataSource {
+                id: data_source_id,
+                name: table.to_string(),
+                source_sql: format!("select * from {table}"),
+                data_connection_id: Uuid::new_v4(),
+                source_options: SourceOptions::Trino(TrinoSource {}),
+                paused: false,
+                engines: SourceEngines::default(),
+                freshness_query: None,
+                policy_mode: PolicyMode::Permissive,
+            };
+            let field = DataField {
+                id: Uuid::new_v4(),
+                name: "col1".to_string(),
+                data_source_id,
+                path: "col1".to_string(),
+            };
+            let info_map_lookup = HashMap::from([("foo", (&field, &map))]);
+            let mapped = map_sql(
+                statement.clone(),
+                "entityname",
+                &source,
+                &info_map_lookup,
+                permission.clone(),
+            )?;
+
+            let query = count_only_query(&mapped);
+            assert_eq!(
+                query.sql,
+                format!(
+                    "select count(*) as row_count from (SELECT col1 AS foo FROM (SELECT col1 \
+                    FROM (SELECT * FROM {table}) WHERE col1 > 1)) as counted"
+                )
+            );
+            let return_schema =
+                Schema::new(vec![Field::new(COUNT_ONLY_COLUMN, DataType::Int64, false)]);
+            assert_eq!(query.return_schema, Some(return_schema));
+
+            // Executing the wrapped query returns a single row with the count, even without rows
+            let data = RecordBatch::try_new(
+                Arc::new(Schema::new(vec![Field::new(
+                    "col1",
+                    DataType::Int32,
+                    false,
+                )])),
+                vec![Arc::new(Int32Array::from(rows.clone()))],
+            )?;
+            ctx.register_batch(table, data)?;
+            let batches = ctx.sql(&query.sql).await?.collect().await?;
+            let batch = arrow::compute::concat_batches(&batches[0].schema(), &batches)?;
+            let counted = batch.schema().field(0).clone();
+            assert_eq!(
+                (counted.name().as_str(), counted.data_type()),
+                (COUNT_ONLY_COLUMN, &DataType::Int64)
+            );
+            let expected = rows.iter().filter(|v| **v > 1).count() as i64;
+            assert_eq!(
+                batch.column(0).as_any().downcast_ref::<Int64Array>(),
+                Some(&Int64Array::from(vec![expected]))
+            );
+        }
+        Ok(())
+    }
+}
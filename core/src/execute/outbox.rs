@@ -0,0 +1,72 @@
+use chrono::{DateTime, Utc};
+use tracing::{debug, error};
+
+use crate::crud::PgDb;
+use crate::error::Result;
+use crate::messaging::{GenericMessage, MessageProducer};
+
+/// Number of outbox messages loaded from the database at a time.
+const OUTBOX_BATCH_SIZE: i64 = 100;
+
+/// Publishes every message in the task outbox which was written before created_before and
+/// removes it from the outbox once the [MessageProducer] accepted it. A message is only removed
+/// after it was sent, so dispatch is at-least-once and consumers must tolerate duplicates.
+/// Each batch is published in a transaction which locks its messages, so that concurrent
+/// publishers, e.g. a service and the outbox dispatcher, skip them rather than publishing them
+/// again. Returns the number of published messages.
+pub async fn publish_outbox(
+    db: &mut PgDb<'_>,
+    producer: &mut dyn MessageProducer,
+    created_before: DateTime<Utc>,
+) -> Result<usize> {
+    let mut published = 0;
+    loop {
+        db.begin().await?;
+        let batch = match publish_outbox_batch(db, producer, created_before).await {
+            Ok(batch) => {
+                db.commit().await?;
+                batch
+            }
+            Err(e) => {
+                db.rollback().await?;
+                return Err(e);
+            }
+        };
+        match batch {
+            None => return Ok(published),
+            Some(n) => published += n,
+        }
+    }
+}
+
+/// Publishes and removes one batch of outbox messages within the transaction of
+/// [publish_outbox]. Returns None once no unlocked messages are left.
+async fn publish_outbox_batch(
+    db: &mut PgDb<'_>,
+    producer: &mut dyn MessageProducer,
+    created_before: DateTime<Utc>,
+) -> Result<Option<usize>> {
+    let messages = db
+        .get_outbox_messages(created_before, OUTBOX_BATCH_SIZE)
+        .await?;
+    if messages.is_empty() {
+        return Ok(None);
+    }
+    let mut published = 0;
+    for outbox_message in messages {
+        match serde_json::from_value::<GenericMessage>(outbox_message.message) {
+            Ok(msg) => {
+                producer.send_message(&msg).await?;
+                debug!("Published outbox message {msg:?}");
+                published += 1;
+            }
+            // Retrying can never succeed, so the message is dropped rather than blocking the outbox
+            Err(e) => error!(
+                "Dropping outbox message {} which could not be parsed with error {e}",
+                outbox_message.id
+            ),
+        }
+        db.delete_outbox_message(outbox_message.id).await?;
+    }
+    Ok(Some(published))
+}
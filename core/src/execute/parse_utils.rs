@@ -1,9 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use datafusion::sql::sqlparser::{
     ast::{
-        visit_expressions_mut, Expr, GroupByExpr, Ident, Query, Select, SelectItem, SetExpr,
-        Statement, TableFactor, TableWithJoins,
+        visit_expressions, visit_expressions_mut, Expr, GroupByExpr, Ident, Query, Select,
+        SelectItem, SetExpr, Statement, TableFactor, TableWithJoins, Value,
     },
     dialect::GenericDialect,
     parser::Parser,
@@ -20,10 +20,65 @@ pub(crate) fn parse_sql_as_expr(sql: &str) -> Result<Expr> {
     Ok(parser.parse_expr()?)
 }
 
+/// Returns an [Ident] for an entity or information name. Names which are not plain lowercase
+/// identifiers are quoted, so that they keep their case when parsed again.
+pub fn name_to_ident(name: &str) -> Ident {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if plain {
+        Ident::new(name)
+    } else {
+        Ident::with_quote('"', name)
+    }
+}
+
 pub(crate) fn null_lit_expr() -> Expr {
     Expr::Value(datafusion::sql::sqlparser::ast::Value::Null)
 }
 
+/// Returns the LIMIT of the outermost query of the [Statement], if it is a literal number.
+pub(crate) fn statement_limit(statement: &Statement) -> Option<u64> {
+    match statement {
+        Statement::Query(query) => match &query.limit {
+            Some(Expr::Value(Value::Number(n, _))) => n.parse().ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Sets the LIMIT of the outermost query of the [Statement] if it does not already have one.
+/// Returns true if the limit was injected.
+pub(crate) fn inject_default_limit(statement: &mut Statement, limit: u64) -> bool {
+    match statement {
+        Statement::Query(query) if query.limit.is_none() => {
+            query.limit = Some(Expr::Value(Value::Number(limit.to_string(), false)));
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Lowers the LIMIT of the outermost query of the [Statement] to limit if it has no LIMIT or a
+/// larger literal one. Returns true if the limit was changed.
+pub(crate) fn cap_limit(statement: &mut Statement, limit: u64) -> bool {
+    if statement_limit(statement).is_some_and(|existing| existing <= limit) {
+        return false;
+    }
+    match statement {
+        Statement::Query(query) => {
+            query.limit = Some(Expr::Value(Value::Number(limit.to_string(), false)));
+            true
+        }
+        _ => false,
+    }
+}
+
 pub(crate) fn parse_sql_as_table_factor(sql: &str) -> Result<TableFactor> {
     let mut parser = Parser::new(&DIALECT).try_with_sql(&format!("({sql})"))?;
     Ok(parser.parse_table_factor()?)
@@ -160,10 +215,23 @@ fn maybe_extract_info<'a>(expr: &'a Expr, entity_name: &'a str) -> Option<&'a St
     }
 }
 
+/// Returns the names of all information about entity_name which are referenced anywhere in the
+/// [Statement].
+pub(crate) fn referenced_information(statement: &Statement, entity_name: &str) -> HashSet<String> {
+    let mut info_names = HashSet::new();
+    let _ = visit_expressions(statement, |expr| {
+        if let Some(info_name) = maybe_extract_info(expr, entity_name) {
+            info_names.insert(info_name.clone());
+        }
+        std::ops::ControlFlow::<()>::Continue(())
+    });
+    info_names
+}
+
 /// Visit all [SelectItem]s and make UnnamedExprs into ExprWithAlias so that fields retain their names
 /// even when transformed by [apply_col_iden_mapping].
 pub(crate) fn apply_aliases(statement: &mut Statement, entity_name: &str) -> Result<()> {
-    visit_query_mut(statement, |query| {
+    let _ = visit_query_mut(statement, |query| {
         if let SetExpr::Select(select) = query.body.as_mut() {
             let updated_proj = select
                 .projection
@@ -176,10 +244,7 @@ pub(crate) fn apply_aliases(statement: &mut Statement, entity_name: &str) -> Res
                         };
                         SelectItem::ExprWithAlias {
                             expr: expr.clone(),
-                            alias: Ident {
-                                value: info_name.to_string(),
-                                quote_style: None,
-                            },
+                            alias: name_to_ident(info_name),
                         }
                     } else {
                         item.clone()
@@ -230,3 +295,51 @@ pub(crate) fn apply_col_iden_mapping(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Result;
+    use crate::execute::validation::validate_sql;
+
+    use super::{cap_limit, inject_default_limit, referenced_information, statement_limit};
+
+    #[test]
+    fn test_inject_default_limit() -> Result<()> {
+        let (_, mut statement) = validate_sql("select a from entity")?;
+        assert!(inject_default_limit(&mut statement, 100));
+        assert_eq!(statement_limit(&statement), Some(100));
+
+        let (_, mut statement) = validate_sql("select a from entity limit 5000")?;
+        assert!(!inject_default_limit(&mut statement, 100));
+        assert_eq!(statement_limit(&statement), Some(5000));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cap_limit() -> Result<()> {
+        let (_, mut statement) = validate_sql("select a from entity limit 5000")?;
+        assert!(cap_limit(&mut statement, 100));
+        assert_eq!(statement_limit(&statement), Some(100));
+        assert!(!cap_limit(&mut statement, 1000));
+        assert_eq!(statement_limit(&statement), Some(100));
+
+        let (_, mut statement) = validate_sql("select a from entity")?;
+        assert!(cap_limit(&mut statement, 10));
+        assert_eq!(statement_limit(&statement), Some(10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_referenced_information() -> Result<()> {
+        let (_, statement) = validate_sql(
+            "select entity.a, entity.b + 1 as b, c from entity \
+            where entity.d > 1 and other.e = 1",
+        )?;
+        let mut referenced = referenced_information(&statement, "entity")
+            .into_iter()
+            .collect::<Vec<_>>();
+        referenced.sort();
+        assert_eq!(referenced, vec!["a", "b", "d"]);
+        Ok(())
+    }
+}
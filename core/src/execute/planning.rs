@@ -9,6 +9,8 @@ use datafusion::{
     sql::{planner::ContextProvider, TableReference},
 };
 
+use super::udf::udf_registry;
+
 /// DataFusion logical planning ContextProvider for a single Entity.
 pub struct EntityContext {
     entity: String,
@@ -40,8 +42,8 @@ impl ContextProvider for EntityContext {
         }
     }
 
-    fn get_function_meta(&self, _name: &str) -> Option<Arc<ScalarUDF>> {
-        None
+    fn get_function_meta(&self, name: &str) -> Option<Arc<ScalarUDF>> {
+        udf_registry().read().ok()?.get(name)
     }
 
     fn get_aggregate_meta(&self, _name: &str) -> Option<Arc<AggregateUDF>> {
@@ -61,7 +63,10 @@ impl ContextProvider for EntityContext {
     }
 
     fn udfs_names(&self) -> Vec<String> {
-        vec![]
+        udf_registry()
+            .read()
+            .map(|registry| registry.names())
+            .unwrap_or_default()
     }
 
     fn udafs_names(&self) -> Vec<String> {
@@ -0,0 +1,262 @@
+use std::env;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use arrow::ipc::{root_as_message, MessageBuilder, MessageHeader, MetadataVersion};
+use arrow_flight::FlightData;
+use futures::stream::{BoxStream, Stream, StreamExt, TryStreamExt};
+use tokio::time::{interval_at, Instant, Interval, MissedTickBehavior};
+
+use crate::model::usage::{TransferCounter, TransferProgress};
+
+/// Returns how often progress messages are interleaved with do_get and do_put streams, read from
+/// FLIGHT_PROGRESS_SECS. Progress messages double as keepalives for proxies with idle timeouts.
+pub fn flight_progress_interval() -> Duration {
+    static INTERVAL: OnceLock<Duration> = OnceLock::new();
+    *INTERVAL.get_or_init(|| {
+        let secs: u64 = env::var("FLIGHT_PROGRESS_SECS")
+            .unwrap_or("15".to_string())
+            .parse()
+            .expect("Unable to parse FLIGHT_PROGRESS_SECS as u64!");
+        Duration::from_secs(secs)
+    })
+}
+
+/// Returns how long a do_put may go without writing to its
+/// [FlightStream][crate::model::query::FlightStream] before the stream is considered abandoned and
+/// marked Failed, read from FLIGHT_STREAM_TIMEOUT_SECS.
+pub fn flight_stream_timeout() -> Duration {
+    static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+    *TIMEOUT.get_or_init(|| {
+        let secs: u64 = env::var("FLIGHT_STREAM_TIMEOUT_SECS")
+            .unwrap_or("600".to_string())
+            .parse()
+            .expect("Unable to parse FLIGHT_STREAM_TIMEOUT_SECS as u64!");
+        Duration::from_secs(secs)
+    })
+}
+
+/// Creates a [FlightData] message which carries only a [TransferProgress] as JSON app_metadata.
+/// See [metadata_flight_data].
+pub fn progress_flight_data(progress: &TransferProgress) -> FlightData {
+    metadata_flight_data(serde_json::to_vec(progress).unwrap_or_default())
+}
+
+/// Creates a [FlightData] message which carries only app_metadata. Its data_header is an IPC
+/// message of type NONE, so Flight decoders such as
+/// [FlightRecordBatchStream][arrow_flight::decode::FlightRecordBatchStream] skip it.
+pub fn metadata_flight_data(app_metadata: Vec<u8>) -> FlightData {
+    let mut fbb = flatbuffers::FlatBufferBuilder::new();
+    let mut message = MessageBuilder::new(&mut fbb);
+    message.add_version(MetadataVersion::V5);
+    message.add_header_type(MessageHeader::NONE);
+    message.add_bodyLength(0);
+    let root = message.finish();
+    fbb.finish(root, None);
+    FlightData {
+        data_header: fbb.finished_data().to_vec().into(),
+        app_metadata: app_metadata.into(),
+        ..Default::default()
+    }
+}
+
+/// Returns true if the [FlightData] carries no schema, dictionary or RecordBatch, e.g. because it
+/// was created by [progress_flight_data].
+pub fn is_progress_message(data: &FlightData) -> bool {
+    root_as_message(&data.data_header[..])
+        .map(|m| m.header_type() == MessageHeader::NONE)
+        .unwrap_or(false)
+}
+
+/// Returns true if the [FlightData] carries a schema.
+pub fn is_schema_message(data: &FlightData) -> bool {
+    root_as_message(&data.data_header[..])
+        .map(|m| m.header_type() == MessageHeader::Schema)
+        .unwrap_or(false)
+}
+
+/// Drops progress messages from the messages of a do_put which follow its first message, along
+/// with the schema its sender sends again ahead of the RecordBatches. Messages are dropped by
+/// their type rather than position, since progress messages are sent while the sender's source
+/// has yet to produce the first RecordBatch.
+pub fn put_data_messages<S, E>(stream: S) -> impl Stream<Item = Result<FlightData, E>>
+where
+    S: Stream<Item = Result<FlightData, E>>,
+{
+    stream.try_filter(|data| {
+        futures::future::ready(!is_progress_message(data) && !is_schema_message(data))
+    })
+}
+
+struct ProgressState<T, E> {
+    inner: BoxStream<'static, Result<T, E>>,
+    counter: Arc<TransferCounter>,
+    ticker: Interval,
+    done: bool,
+}
+
+/// Interleaves the progress of a [TransferCounter] with a stream every interval, and once more
+/// with complete set after the stream ends. The stream ends without a final progress item if it
+/// yields an error.
+pub fn with_progress<S, T, E, F>(
+    stream: S,
+    counter: Arc<TransferCounter>,
+    interval: Duration,
+    progress_item: F,
+) -> impl Stream<Item = Result<T, E>> + Send + 'static
+where
+    S: Stream<Item = Result<T, E>> + Send + 'static,
+    T: Send + 'static,
+    E: Send + 'static,
+    F: Fn(&TransferProgress) -> T + Send + Sync + 'static,
+{
+    let mut ticker = interval_at(Instant::now() + interval, interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let state = ProgressState {
+        inner: stream.boxed(),
+        counter,
+        ticker,
+        done: false,
+    };
+    let progress_item = Arc::new(progress_item);
+    futures::stream::unfold(state, move |mut state| {
+        let progress_item = progress_item.clone();
+        async move {
+            if state.done {
+                return None;
+            }
+            tokio::select! {
+                item = state.inner.next() => match item {
+                    Some(Ok(item)) => Some((Ok(item), state)),
+                    Some(Err(e)) => {
+                        state.done = true;
+                        Some((Err(e), state))
+                    }
+                    None => {
+                        state.done = true;
+                        let progress = state.counter.progress(true);
+                        Some((Ok(progress_item(&progress)), state))
+                    }
+                },
+                _ = state.ticker.tick() => {
+                    let progress = state.counter.progress(false);
+                    Some((Ok(progress_item(&progress)), state))
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use arrow::ipc::writer::IpcWriteOptions;
+    use arrow_array::{Int64Array, RecordBatch};
+    use arrow_flight::decode::FlightRecordBatchStream;
+    use arrow_flight::encode::FlightDataEncoderBuilder;
+    use arrow_flight::utils::flight_data_to_arrow_batch;
+    use arrow_flight::{FlightData, SchemaAsIpc};
+    use futures::{StreamExt, TryStreamExt};
+
+    use crate::error::{MeshError, Result};
+
+    use crate::model::usage::{TransferCounter, TransferProgress};
+
+    use super::{is_progress_message, progress_flight_data, put_data_messages, with_progress};
+
+    #[tokio::test]
+    async fn test_progress_messages_are_skipped_by_decoder() -> Result<()> {
+        let batch = RecordBatch::try_from_iter(vec![(
+            "a",
+            Arc::new(Int64Array::from(vec![1, 2, 3])) as _,
+        )])?;
+        let counter = Arc::new(TransferCounter::default());
+        counter.add(batch.num_rows(), 0);
+        let data = FlightDataEncoderBuilder::new()
+            .build(futures::stream::iter(vec![Ok(batch.clone())]))
+            // a pending stream lets the ticker fire before the data ends
+            .chain(
+                futures::stream::pending()
+                    .take_until(tokio::time::sleep(Duration::from_millis(50))),
+            );
+        let data = with_progress(
+            data,
+            counter,
+            Duration::from_millis(10),
+            progress_flight_data,
+        );
+
+        let messages: Vec<_> = data
+            .try_collect()
+            .await
+            .map_err(|e| MeshError::RemoteError(e.to_string()))?;
+        let last = messages.last().unwrap();
+        assert!(is_progress_message(last));
+        let progress: TransferProgress = serde_json::from_slice(&last.app_metadata)?;
+        assert_eq!(progress.rows, 3);
+        assert!(progress.complete);
+        assert!(messages.iter().filter(|m| is_progress_message(m)).count() > 1);
+        assert!(!is_progress_message(&messages[0]));
+
+        let decoded: Vec<RecordBatch> = FlightRecordBatchStream::new_from_flight_data(
+            futures::stream::iter(messages.into_iter().map(Ok)),
+        )
+        .try_collect()
+        .await
+        .map_err(|e| MeshError::RemoteError(e.to_string()))?;
+        assert_eq!(decoded, vec![batch]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_put_data_messages_skip_progress_before_first_batch() -> Result<()> {
+        let batch = RecordBatch::try_from_iter(vec![(
+            "a",
+            Arc::new(Int64Array::from(vec![1, 2, 3])) as _,
+        )])?;
+        let schema = batch.schema();
+        for resend_schema_first in [true, false] {
+            // The first batch arrives only after several progress messages were sent
+            let delayed = futures::stream::once({
+                let batch = batch.clone();
+                async move {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Ok(batch)
+                }
+            });
+            let mut encoder = FlightDataEncoderBuilder::new();
+            if resend_schema_first {
+                encoder = encoder.with_schema(schema.clone());
+            }
+            let first = FlightData::from(SchemaAsIpc::new(&schema, &IpcWriteOptions::default()));
+            let data = with_progress(
+                futures::stream::once(async { Ok(first) }).chain(encoder.build(delayed)),
+                Arc::new(TransferCounter::default()),
+                Duration::from_millis(10),
+                progress_flight_data,
+            );
+            let messages: Vec<_> = data
+                .try_collect()
+                .await
+                .map_err(|e| MeshError::RemoteError(e.to_string()))?;
+            assert!(messages[1..4].iter().any(is_progress_message));
+            // Unless the encoder sends the schema right away, it follows the progress messages
+            assert_eq!(is_progress_message(&messages[1]), !resend_schema_first);
+
+            let dictionaries = Default::default();
+            let decoded = put_data_messages(futures::stream::iter(
+                messages.into_iter().skip(1).map(Ok::<_, MeshError>),
+            ))
+            .and_then(|data| {
+                let decoded = flight_data_to_arrow_batch(&data, schema.clone(), &dictionaries);
+                futures::future::ready(decoded.map_err(MeshError::from))
+            })
+            .try_collect::<Vec<_>>()
+            .await?;
+            assert_eq!(decoded, vec![batch.clone()]);
+        }
+        Ok(())
+    }
+}
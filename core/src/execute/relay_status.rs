@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+use arrow_flight::error::FlightError;
+use arrow_flight::Action;
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::crud::PgDb;
+use crate::error::Result;
+use crate::model::relay::RelayStatus;
+
+use super::result_manager::ResultManager;
+
+/// Type of the Flight [Action] with which a relay checks that a peer is reachable. It has an
+/// empty body and returns a single empty result.
+pub const PING_ACTION: &str = "ping";
+
+/// A peer [Relay][crate::model::relay::Relay] along with its last probed [RelayStatus], which
+/// is None if it was not probed yet.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct RelayStatusReport {
+    pub name: String,
+    pub rest_endpoint: String,
+    pub flight_endpoint: String,
+    pub status: Option<RelayStatus>,
+}
+
+/// Returns the status of every peer relay ordered by name.
+pub async fn relay_status_report(db: &mut PgDb<'_>) -> Result<Vec<RelayStatusReport>> {
+    Ok(db
+        .get_relay_statuses()
+        .await?
+        .into_iter()
+        .map(|(relay, status)| RelayStatusReport {
+            name: relay.name,
+            rest_endpoint: relay.rest_endpoint,
+            flight_endpoint: relay.flight_endpoint,
+            status,
+        })
+        .collect())
+}
+
+/// Connects to the flight endpoint of a peer over mTLS and sends it a [PING_ACTION], returning
+/// the round trip in milliseconds including the TLS handshake, or why the peer is unreachable.
+pub async fn ping_flight(
+    result_manager: &ResultManager,
+    flight_endpoint: &str,
+    timeout: Duration,
+) -> std::result::Result<i64, String> {
+    let started = Instant::now();
+    let ping = async {
+        let mut client = result_manager
+            .flight_client(flight_endpoint.to_string())
+            .await
+            .map_err(|e| e.to_string())?;
+        let pinged = match client.do_action(Action::new(PING_ACTION, "")).await {
+            Ok(results) => results.try_collect::<Vec<_>>().await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        match pinged {
+            Err(e) if !flight_reached(&e) => Err(e.to_string()),
+            _ => Ok(()),
+        }
+    };
+    match tokio::time::timeout(timeout, ping).await {
+        Ok(Ok(())) => Ok(started.elapsed().as_millis() as i64),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err(format!("Timed out after {timeout:?}")),
+    }
+}
+
+/// Whether the peer responded, even though the action failed. Peers which do not support the
+/// [PING_ACTION] reject it as unimplemented, which still shows their endpoint is reachable.
+fn flight_reached(e: &FlightError) -> bool {
+    matches!(e, FlightError::Tonic(status) if status.code() == tonic::Code::Unimplemented)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_flight::error::FlightError;
+
+    use super::flight_reached;
+
+    #[test]
+    fn test_flight_reached() {
+        assert!(flight_reached(&FlightError::Tonic(
+            tonic::Status::unimplemented("Unknown action ping")
+        )));
+        assert!(!flight_reached(&FlightError::Tonic(
+            tonic::Status::unavailable("error trying to connect")
+        )));
+        assert!(!flight_reached(&FlightError::ProtocolError(
+            "unexpected message".to_string()
+        )));
+    }
+}
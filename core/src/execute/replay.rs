@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::crud::PgDb;
+use crate::error::{MeshError, Result};
+use crate::model::access_control::SourcePermission;
+use crate::model::query::{
+    NewQueryTask, QueryRequest, QueryTask, QueryTaskStatus, RawQueryRequest,
+};
+
+use super::utils::validate_sql_and_logical_round_trip;
+use super::{request_to_local_queries, Requester};
+
+/// Executes a stored [QueryRequest] again against the current mappings of the local relay, so
+/// that issues reported by users can be reproduced. The original SQL is mapped with the
+/// [SourcePermission]s recorded for the tasks of the original request rather than the current
+/// policies, and only local sources are queried.
+///
+/// The replay is itself a [QueryRequest] with replay_of set. Its results are written apart from
+/// the results of regular requests, see
+/// [write_replay_result][super::result_manager::ResultManager::write_replay_result], and are
+/// not returned to the [User][crate::model::user::User] who submitted the original request.
+pub async fn replay_request(
+    db: &mut PgDb<'_>,
+    request_id: Uuid,
+) -> Result<(QueryRequest, Vec<QueryTask>)> {
+    let (original, tasks, _remote_tasks) =
+        db.get_query_request(request_id)
+            .await?
+            .ok_or(MeshError::InvalidQuery(format!(
+                "No request exists with id {request_id} to replay"
+            )))?;
+    let user = original
+        .origin_info
+        .origin_user
+        .clone()
+        .ok_or(MeshError::InvalidQuery(format!(
+            "Request {request_id} has no originating user to replay it as"
+        )))?;
+    let mut permission_snapshots: HashMap<i32, HashMap<Uuid, SourcePermission>> = HashMap::new();
+    for task in tasks {
+        if let Some(permission) = task.permission {
+            permission_snapshots
+                .entry(task.statement_index)
+                .or_default()
+                .insert(task.data_source_id, permission);
+        }
+    }
+
+    // Every statement of a batch is mapped before the replay is created, so a statement which
+    // no longer validates fails the replay as a whole.
+    let mut statement_queries = vec![];
+    for (statement_index, sql) in (0..).zip(original.statement_sql()) {
+        let (entity_name, statement, logical_schema) =
+            validate_sql_and_logical_round_trip(sql, db).await?;
+        let raw_request = RawQueryRequest {
+            sql: sql.to_string(),
+            statements: vec![],
+            request_uuid: None,
+            requesting_user: None,
+            originating_relay: None,
+            originating_task_id: None,
+            return_arrow_schema: Some(logical_schema),
+            engine_hint: None,
+            count_only: false,
+            interactive: false,
+            reexecution_of: None,
+            hints: HashMap::new(),
+        };
+        let queries = request_to_local_queries(
+            db,
+            &statement,
+            &entity_name,
+            &raw_request,
+            &Requester::User(user.clone()),
+            &user,
+            Some(
+                permission_snapshots
+                    .get(&statement_index)
+                    .unwrap_or(&HashMap::new()),
+            ),
+        )
+        .await?;
+        statement_queries.push((statement_index, queries));
+    }
+
+    let replay = db.create_replay_request(&original).await?;
+    let new_tasks = statement_queries
+        .into_iter()
+        .flat_map(|(statement_index, queries)| {
+            queries.into_iter().map(move |q| NewQueryTask {
+                query_request_id: replay.id,
+                data_source_id: q.data_source_id,
+                task: q.query,
+                status: QueryTaskStatus::Queued,
+                not_before: q.not_before,
+                engine: q.engine,
+                permission: Some(q.permission),
+                statement_index,
+            })
+        })
+        .collect();
+    let tasks = db.create_query_tasks_with_outbox(&new_tasks).await?;
+    Ok((replay, tasks))
+}
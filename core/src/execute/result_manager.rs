@@ -1,63 +1,483 @@
+use std::collections::{BTreeSet, HashMap};
+use std::env;
 use std::pin::Pin;
 use std::sync::Arc;
 
+use arrow::buffer::Buffer;
+use arrow::compute::cast;
+use arrow::ipc::convert::fb_to_schema;
+use arrow::ipc::reader::{read_footer_length, FileDecoder};
+use arrow::ipc::root_as_footer;
+use arrow::ipc::writer::FileWriter;
 use arrow_array::RecordBatch;
 use arrow_flight::encode::FlightDataEncoderBuilder;
 use arrow_flight::error::FlightError;
 use arrow_flight::{flight_descriptor, FlightClient, FlightData, FlightDescriptor, SchemaAsIpc};
-use datafusion::arrow::datatypes::Schema;
+use chrono::{Duration, Utc};
+use datafusion::arrow::datatypes::{DataType, Schema};
 
 use datafusion::error::DataFusionError;
-use datafusion::parquet::arrow::AsyncArrowWriter;
+use datafusion::parquet::arrow::async_reader::ParquetObjectReader;
+use datafusion::parquet::arrow::{AsyncArrowWriter, ParquetRecordBatchStreamBuilder};
 
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use datafusion::physical_plan::SendableRecordBatchStream;
 use datafusion::prelude::{ParquetReadOptions, SessionContext};
 use object_store::path::Path;
+use object_store::prefix::PrefixStore;
 use object_store::ObjectStore;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncWriteExt, BufWriter};
 
 use arrow_flight::flight_service_client::FlightServiceClient;
 use tonic::transport::{Certificate, ClientTlsConfig, Identity};
+use tracing::{debug, info};
 use url::Url;
 use uuid::Uuid;
 
 use crate::error::{MeshError, Result};
-use crate::model::data_stores::options::file_directory::FileDirectorySource;
+use crate::model::access_control::SourcePermission;
+#[cfg(feature = "datafusion")]
+use crate::model::data_stores::options::file_directory::FileDirectoryConnection;
+use crate::model::data_stores::options::file_directory::{
+    FileDirectorySource, ObjectStoreClientConfig, TenantResultStore,
+};
 use crate::model::data_stores::options::SupportedObjectStore;
+#[cfg(feature = "datafusion")]
+use crate::model::data_stores::options::{ConnectionOptions, SourceFileType, SourceOptions};
+use crate::model::query::{Query, ResultFormat, ResultMetadata};
 use crate::model::relay::Relay;
+use crate::model::usage::{PutDecision, PutDedup, TransferCounter};
 
 use futures::{Stream, StreamExt, TryStreamExt};
 
 use super::data_stores::initialize_object_store;
+use super::metrics::metrics;
+use super::progress::{flight_progress_interval, progress_flight_data, with_progress};
 
 /// Manages storing and retrieving query results in an [ObjectStore] as a [Stream]
 /// and sending [RecordBatch] streams to remote flight services
 pub struct ResultManager {
     object_store: Arc<dyn ObjectStore>,
+    store_type: SupportedObjectStore,
+    source: FileDirectorySource,
+    client_config: ObjectStoreClientConfig,
+    /// Stores of results received from peer relays, by relay name, used instead of the default.
+    tenants: HashMap<String, ResultStore>,
+    /// URI of the root of the default store, see [store_uri].
+    uri: String,
+    /// How long a cached result is served instead of executing its task again, or None if
+    /// results are not cached. See [ResultManager::with_result_cache].
+    cache_ttl: Option<Duration>,
     client_cert_pem: Vec<u8>,
     client_key_pem: Vec<u8>,
     cacert_pem: Vec<u8>,
 }
 
+struct ResultStore {
+    object_store: Arc<dyn ObjectStore>,
+    client_config: ObjectStoreClientConfig,
+    uri: String,
+}
+
+/// Returns the URI of the root of a result store, which identifies results outside of the relay,
+/// e.g. in lineage events.
+fn store_uri(
+    store_type: &SupportedObjectStore,
+    bucket: Option<&str>,
+    prefix: Option<&str>,
+) -> String {
+    let scheme = match store_type {
+        SupportedObjectStore::LocalFileSystem => "file",
+        #[cfg(feature = "os-aws")]
+        SupportedObjectStore::S3 => "s3",
+        #[cfg(feature = "os-azure")]
+        SupportedObjectStore::Azure => "az",
+        #[cfg(feature = "os-gcp")]
+        SupportedObjectStore::GCP => "gs",
+        #[cfg(feature = "os-hdfs")]
+        SupportedObjectStore::HDFS => "hdfs",
+    };
+    // The prefix of a LocalFileSystem is a directory, and its bucket is not used
+    let authority = match scheme {
+        "file" => "",
+        _ => bucket.unwrap_or_default(),
+    };
+    let uri = format!(
+        "{scheme}://{}/{}",
+        authority.trim_matches('/'),
+        prefix.unwrap_or_default().trim_matches('/')
+    );
+    uri.trim_end_matches('/').to_string()
+}
+
+/// How long results are retained unless their request hints otherwise, read from
+/// RESULT_TTL_SECS (default 0, which retains them forever).
+pub fn default_result_ttl_secs() -> i64 {
+    env::var("RESULT_TTL_SECS")
+        .unwrap_or("0".to_string())
+        .parse()
+        .expect("Unable to parse RESULT_TTL_SECS as i64!")
+}
+
+/// Format results are stored in unless their request hints otherwise, read from RESULT_FORMAT
+/// (default parquet).
+pub fn default_result_format() -> ResultFormat {
+    match env::var("RESULT_FORMAT") {
+        Ok(format) => serde_json::from_value(serde_json::Value::String(format))
+            .expect("Unable to parse RESULT_FORMAT as parquet or arrow_ipc!"),
+        Err(_) => ResultFormat::default(),
+    }
+}
+
+/// How long results are cached, read from RESULT_CACHE_TTL_SECS (default 0, which disables the
+/// cache).
+pub fn result_cache_ttl() -> Option<Duration> {
+    let ttl_secs: i64 = env::var("RESULT_CACHE_TTL_SECS")
+        .unwrap_or("0".to_string())
+        .parse()
+        .expect("Unable to parse RESULT_CACHE_TTL_SECS as i64!");
+    (ttl_secs > 0).then(|| Duration::seconds(ttl_secs))
+}
+
+/// Identifies the cached result of a task, see [ResultManager::get_cached_result]. Tasks share a
+/// key if they run the same SQL, up to whitespace, with the same return schema and
+/// [SourcePermission] on the same [DataSource][crate::model::data_stores::DataSource], so a
+/// result is never served to a requester with different permissions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultCacheKey {
+    data_source_id: Uuid,
+    hash: String,
+}
+
+impl ResultCacheKey {
+    pub fn new(data_source_id: Uuid, query: &Query, permission: Option<&SourcePermission>) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(data_source_id.as_bytes());
+        for word in query.sql.split_whitespace() {
+            hasher.update(word.as_bytes());
+            hasher.update(b" ");
+        }
+        if let Some(schema) = &query.return_schema {
+            hasher.update(schema.to_string().as_bytes());
+        }
+        if let Some(permission) = permission {
+            // Allowed columns are a set, so they are sorted to hash the same in every order
+            let columns: BTreeSet<_> = permission.columns.allowed_columns.iter().collect();
+            for column in columns {
+                hasher.update(b"\0");
+                hasher.update(column.as_bytes());
+            }
+            hasher.update(b"\0");
+            hasher.update(permission.rows.allowed_rows.as_bytes());
+        }
+        Self {
+            data_source_id,
+            hash: format!("{:x}", hasher.finalize()),
+        }
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "{}/{}/result.parquet",
+            cache_prefix(Some(&self.data_source_id)),
+            self.hash
+        )
+    }
+}
+
+/// Prefix of the cached results of a DataSource, or of every cached result if None.
+fn cache_prefix(data_source_id: Option<&Uuid>) -> String {
+    match data_source_id {
+        Some(id) => format!("cache/source_{id}"),
+        None => "cache".to_string(),
+    }
+}
+
+/// Deletes the object at path, succeeding if it does not exist, e.g. because it was deleted by
+/// another query_runner.
+async fn delete_if_exists(object_store: &Arc<dyn ObjectStore>, path: &Path) -> Result<()> {
+    match object_store.delete(path).await {
+        Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Path of the result of a task written by [ResultManager::write_task_result].
+fn task_result_path(task_id: &Uuid, format: ResultFormat) -> String {
+    format!("task_{task_id}/result.{}", format.extension())
+}
+
+/// Path of the result of a task of a replayed request, see
+/// [replay_request][super::replay::replay_request].
+fn replay_result_path(replay_id: &Uuid, task_id: &Uuid, format: ResultFormat) -> String {
+    format!("replay/{replay_id}/{}", task_result_path(task_id, format))
+}
+
+/// Directory of a dataset uploaded by a user, see [ResultManager::write_scratch_dataset].
+fn scratch_dataset_dir(user_id: &Uuid, dataset_name: &str) -> String {
+    format!("scratch/{}/{dataset_name}", user_id.simple())
+}
+
+/// Wraps an error of encoding or decoding an Arrow IPC result.
+fn ipc_error(e: impl std::fmt::Display) -> MeshError {
+    MeshError::Internal(format!("Arrow IPC error in task serialization! {e}"))
+}
+
 impl ResultManager {
     pub fn try_initialize(
         store_type: SupportedObjectStore,
         source: FileDirectorySource,
+        client_config: ObjectStoreClientConfig,
         client_cert_pem: Vec<u8>,
         client_key_pem: Vec<u8>,
         cacert_pem: Vec<u8>,
     ) -> Result<Self> {
-        let object_store = initialize_object_store(store_type, &source)?;
+        let object_store = initialize_object_store(store_type.clone(), &source, &client_config)?;
+        let uri = store_uri(
+            &store_type,
+            source.bucket.as_deref(),
+            source.prefix.as_deref(),
+        );
         Ok(Self {
             object_store,
+            uri,
+            store_type,
+            source,
+            client_config,
+            tenants: HashMap::new(),
+            cache_ttl: None,
             client_cert_pem,
             client_key_pem,
             cacert_pem,
         })
     }
 
+    /// Stores results received from the named peer relays in their own [TenantResultStore]
+    /// rather than the default result store.
+    pub fn with_tenant_stores(
+        mut self,
+        stores: HashMap<String, TenantResultStore>,
+    ) -> Result<Self> {
+        for (relay_name, store) in stores {
+            let object_store = initialize_object_store(
+                store.object_store_type.clone(),
+                &store.source(),
+                &store.client_config,
+            )?;
+            // A LocalFileSystem is already rooted at the prefix
+            let object_store: Arc<dyn ObjectStore> = match &store.prefix {
+                Some(prefix)
+                    if !matches!(
+                        store.object_store_type,
+                        SupportedObjectStore::LocalFileSystem
+                    ) =>
+                {
+                    Arc::new(PrefixStore::new(object_store, prefix.as_str()))
+                }
+                _ => object_store,
+            };
+            info!("Results from relay {relay_name} are stored in {object_store}");
+            self.tenants.insert(
+                relay_name,
+                ResultStore {
+                    object_store,
+                    uri: store_uri(
+                        &store.object_store_type,
+                        store.bucket.as_deref(),
+                        store.prefix.as_deref(),
+                    ),
+                    client_config: store.client_config,
+                },
+            );
+        }
+        Ok(self)
+    }
+
+    /// Serves results of local tasks from the cache for ttl after they were written, rather than
+    /// executing the same task again. Results are not cached if ttl is None.
+    pub fn with_result_cache(mut self, ttl: Option<Duration>) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Returns the store and client configuration for results received from source_relay, or
+    /// produced locally if None.
+    fn store(
+        &self,
+        source_relay: Option<&str>,
+    ) -> (&Arc<dyn ObjectStore>, &ObjectStoreClientConfig) {
+        match source_relay.and_then(|name| self.tenants.get(name)) {
+            Some(tenant) => (&tenant.object_store, &tenant.client_config),
+            None => (&self.object_store, &self.client_config),
+        }
+    }
+
+    /// Writes the result of a task in format, which was received from source_relay or produced
+    /// locally if None. The result must be read with the same source_relay and format.
     pub async fn write_task_result<S>(
         &self,
         task_id: &Uuid,
+        source_relay: Option<&str>,
+        format: ResultFormat,
+        rb_stream: Pin<Box<S>>,
+        schema: Arc<Schema>,
+    ) -> Result<()>
+    where
+        S: Stream<Item = std::result::Result<RecordBatch, DataFusionError>>
+            + Send
+            + 'static
+            + ?Sized,
+    {
+        let path = Path::parse(task_result_path(task_id, format))?;
+        self.write_result(source_relay, &path, format, rb_stream, schema)
+            .await
+    }
+
+    /// Writes the result of a task of a replayed request under a separate replay prefix, so that
+    /// it is never served as the result of a regular request.
+    pub async fn write_replay_result<S>(
+        &self,
+        replay_id: &Uuid,
+        task_id: &Uuid,
+        format: ResultFormat,
+        rb_stream: Pin<Box<S>>,
+        schema: Arc<Schema>,
+    ) -> Result<()>
+    where
+        S: Stream<Item = std::result::Result<RecordBatch, DataFusionError>>
+            + Send
+            + 'static
+            + ?Sized,
+    {
+        let path = Path::parse(replay_result_path(replay_id, task_id, format))?;
+        self.write_result(None, &path, format, rb_stream, schema)
+            .await
+    }
+
+    /// Writes a user uploaded dataset to the result [ObjectStore], replacing any previous upload
+    /// of the same dataset, and returns the [ConnectionOptions] and [SourceOptions] needed to
+    /// query it as a [DataSource][crate::model::data_stores::DataSource].
+    #[cfg(feature = "datafusion")]
+    pub async fn write_scratch_dataset<S>(
+        &self,
+        user_id: &Uuid,
+        dataset_name: &str,
+        rb_stream: Pin<Box<S>>,
+        schema: Arc<Schema>,
+    ) -> Result<(ConnectionOptions, SourceOptions)>
+    where
+        S: Stream<Item = std::result::Result<RecordBatch, DataFusionError>>
+            + Send
+            + 'static
+            + ?Sized,
+    {
+        let dir = scratch_dataset_dir(user_id, dataset_name);
+        let path = Path::parse(format!("{dir}/data.parquet"))?;
+        self.write_parquet(None, &path, rb_stream, schema).await?;
+
+        let con_opts = ConnectionOptions::FileDirectory(FileDirectoryConnection {
+            object_store_type: self.store_type.clone(),
+            url: format!("scratch://results/{dir}/"),
+            client_config: self.client_config.clone(),
+        });
+        let source_opts = SourceOptions::FileDirectory(FileDirectorySource {
+            file_type: SourceFileType::Parquet,
+            ..self.source.clone()
+        });
+        Ok((con_opts, source_opts))
+    }
+
+    async fn write_result<S>(
+        &self,
+        source_relay: Option<&str>,
+        path: &Path,
+        format: ResultFormat,
+        rb_stream: Pin<Box<S>>,
+        schema: Arc<Schema>,
+    ) -> Result<()>
+    where
+        S: Stream<Item = std::result::Result<RecordBatch, DataFusionError>>
+            + Send
+            + 'static
+            + ?Sized,
+    {
+        match format {
+            ResultFormat::Parquet => {
+                self.write_parquet(source_relay, path, rb_stream, schema)
+                    .await
+            }
+            ResultFormat::ArrowIpc => {
+                self.write_arrow_ipc(source_relay, path, rb_stream, schema)
+                    .await
+            }
+        }
+    }
+
+    /// Writes an Arrow IPC file, which [ResultManager::read_arrow_ipc] decodes without copying
+    /// its data. Each batch is flushed to the multipart upload once encoded, so the result is
+    /// never held in memory whole. IPC files allow only one dictionary per column, whereas the
+    /// dictionaries of a stream may differ between batches, so dictionary encoded columns are
+    /// stored as their values.
+    async fn write_arrow_ipc<S>(
+        &self,
+        source_relay: Option<&str>,
+        path: &Path,
+        mut rb_stream: Pin<Box<S>>,
+        schema: Arc<Schema>,
+    ) -> Result<()>
+    where
+        S: Stream<Item = std::result::Result<RecordBatch, DataFusionError>>
+            + Send
+            + 'static
+            + ?Sized,
+    {
+        let (object_store, client_config) = self.store(source_relay);
+        let (_, multipart) = object_store.put_multipart(path).await?;
+        let mut multipart = match client_config.multipart_chunk_size {
+            Some(size) => BufWriter::with_capacity(size, multipart),
+            None => BufWriter::new(multipart),
+        };
+        let schema = Arc::new(Schema::new(
+            schema
+                .fields()
+                .iter()
+                .map(|field| match field.data_type() {
+                    DataType::Dictionary(_, values) => field
+                        .as_ref()
+                        .clone()
+                        .with_data_type(values.as_ref().clone()),
+                    _ => field.as_ref().clone(),
+                })
+                .collect::<Vec<_>>(),
+        ));
+        // The writer tracks the offsets of the batches itself, so its buffer can be drained
+        let mut writer = FileWriter::try_new(vec![], &schema).map_err(ipc_error)?;
+        while let Some(batch) = rb_stream.next().await.transpose()? {
+            let columns = batch
+                .columns()
+                .iter()
+                .zip(schema.fields())
+                .map(|(column, field)| cast(column, field.data_type()))
+                .collect::<std::result::Result<_, _>>()?;
+            let batch = RecordBatch::try_new(schema.clone(), columns)?;
+            writer.write(&batch).map_err(ipc_error)?;
+            multipart
+                .write_all(&std::mem::take(writer.get_mut()))
+                .await?;
+        }
+        writer.finish().map_err(ipc_error)?;
+        multipart
+            .write_all(&writer.into_inner().map_err(ipc_error)?)
+            .await?;
+        multipart.shutdown().await?;
+        Ok(())
+    }
+
+    async fn write_parquet<S>(
+        &self,
+        source_relay: Option<&str>,
+        path: &Path,
         mut rb_stream: Pin<Box<S>>,
         schema: Arc<Schema>,
     ) -> Result<()>
@@ -67,8 +487,12 @@ impl ResultManager {
             + 'static
             + ?Sized,
     {
-        let path = Path::parse(format!("task_{}/result.parquet", task_id))?;
-        let (_, multipart) = self.object_store.put_multipart(&path).await?;
+        let (object_store, client_config) = self.store(source_relay);
+        let (_, multipart) = object_store.put_multipart(path).await?;
+        let multipart = match client_config.multipart_chunk_size {
+            Some(size) => BufWriter::with_capacity(size, multipart),
+            None => BufWriter::new(multipart),
+        };
         let mut writer = AsyncArrowWriter::try_new(multipart, schema, None).map_err(|e| {
             MeshError::Internal(format!(
                 "Parquet serialization error in task serialization! {e}"
@@ -91,19 +515,352 @@ impl ResultManager {
         Ok(())
     }
 
-    pub async fn get_task_result(&self, task_id: Uuid) -> Result<SendableRecordBatchStream> {
+    /// Returns the URI of the result of a task written by [ResultManager::write_task_result].
+    pub fn task_result_uri(
+        &self,
+        task_id: &Uuid,
+        source_relay: Option<&str>,
+        format: ResultFormat,
+    ) -> String {
+        let uri = match source_relay.and_then(|name| self.tenants.get(name)) {
+            Some(tenant) => &tenant.uri,
+            None => &self.uri,
+        };
+        format!("{uri}/{}", task_result_path(task_id, format))
+    }
+
+    pub async fn get_task_result(
+        &self,
+        task_id: Uuid,
+        source_relay: Option<&str>,
+        format: ResultFormat,
+    ) -> Result<SendableRecordBatchStream> {
+        self.read_result(&task_result_path(&task_id, format), source_relay, format)
+            .await
+    }
+
+    /// Reads the result of a task written by [ResultManager::write_replay_result].
+    pub async fn get_replay_result(
+        &self,
+        replay_id: &Uuid,
+        task_id: &Uuid,
+        format: ResultFormat,
+    ) -> Result<SendableRecordBatchStream> {
+        self.read_result(
+            &replay_result_path(replay_id, task_id, format),
+            None,
+            format,
+        )
+        .await
+    }
+
+    /// Reads rows offset..offset + limit of the result of a task written by
+    /// [ResultManager::write_task_result], and returns them with the number of rows of the whole
+    /// result. Only the row groups of parquet results which hold the rows are read.
+    pub async fn get_task_result_page(
+        &self,
+        task_id: Uuid,
+        source_relay: Option<&str>,
+        format: ResultFormat,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(SendableRecordBatchStream, usize)> {
+        let path = task_result_path(&task_id, format);
+        self.read_result_page(&path, source_relay, format, offset, limit)
+            .await
+    }
+
+    /// Reads a page of the result of a task written by [ResultManager::write_replay_result], see
+    /// [ResultManager::get_task_result_page].
+    pub async fn get_replay_result_page(
+        &self,
+        replay_id: &Uuid,
+        task_id: &Uuid,
+        format: ResultFormat,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(SendableRecordBatchStream, usize)> {
+        let path = replay_result_path(replay_id, task_id, format);
+        self.read_result_page(&path, None, format, offset, limit)
+            .await
+    }
+
+    async fn read_result_page(
+        &self,
+        path: &str,
+        source_relay: Option<&str>,
+        format: ResultFormat,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(SendableRecordBatchStream, usize)> {
+        match format {
+            ResultFormat::Parquet => {
+                self.read_parquet_page(path, source_relay, offset, limit)
+                    .await
+            }
+            ResultFormat::ArrowIpc => {
+                // Decoding IPC batches copies no data, so the page is sliced from all of them
+                let stream = self.read_arrow_ipc(path, source_relay).await?;
+                let schema = stream.schema();
+                let batches: Vec<RecordBatch> = stream.try_collect().await?;
+                let total_rows = batches.iter().map(|batch| batch.num_rows()).sum();
+                let (mut skip, mut remaining) = (offset, limit);
+                let mut page = vec![];
+                for batch in batches {
+                    if skip >= batch.num_rows() {
+                        skip -= batch.num_rows();
+                        continue;
+                    }
+                    if remaining == 0 {
+                        break;
+                    }
+                    let len = (batch.num_rows() - skip).min(remaining);
+                    page.push(Ok(batch.slice(skip, len)));
+                    remaining -= len;
+                    skip = 0;
+                }
+                let stream = RecordBatchStreamAdapter::new(schema, futures::stream::iter(page));
+                Ok((Box::pin(stream), total_rows))
+            }
+        }
+    }
+
+    async fn read_parquet_page(
+        &self,
+        path: &str,
+        source_relay: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(SendableRecordBatchStream, usize)> {
+        let (object_store, _) = self.store(source_relay);
+        let meta = object_store.head(&Path::parse(path)?).await?;
+        let reader = ParquetObjectReader::new(object_store.clone(), meta);
+        let builder = ParquetRecordBatchStreamBuilder::new(reader)
+            .await
+            .map_err(DataFusionError::from)?;
+        let total_rows = builder.metadata().file_metadata().num_rows() as usize;
+
+        let (mut row_groups, mut skip, mut group_start) = (vec![], 0, 0);
+        for (i, group) in builder.metadata().row_groups().iter().enumerate() {
+            let group_end = group_start + group.num_rows() as usize;
+            if group_end > offset && group_start < offset.saturating_add(limit) {
+                if row_groups.is_empty() {
+                    skip = offset - group_start;
+                }
+                row_groups.push(i);
+            }
+            group_start = group_end;
+        }
+        let schema = builder.schema().clone();
+        let stream = builder
+            .with_row_groups(row_groups)
+            .with_offset(skip)
+            .with_limit(limit)
+            .build()
+            .map_err(DataFusionError::from)?
+            .map_err(DataFusionError::from);
+        Ok((
+            Box::pin(RecordBatchStreamAdapter::new(schema, stream)),
+            total_rows,
+        ))
+    }
+
+    async fn read_result(
+        &self,
+        path: &str,
+        source_relay: Option<&str>,
+        format: ResultFormat,
+    ) -> Result<SendableRecordBatchStream> {
+        match format {
+            ResultFormat::Parquet => self.read_parquet(path, source_relay).await,
+            ResultFormat::ArrowIpc => self.read_arrow_ipc(path, source_relay).await,
+        }
+    }
+
+    /// Reads an Arrow IPC file written by [ResultManager::write_arrow_ipc]. The file is fetched
+    /// into a single buffer which the returned record batches reference, so only the IPC
+    /// metadata is decoded and no data is copied.
+    async fn read_arrow_ipc(
+        &self,
+        path: &str,
+        source_relay: Option<&str>,
+    ) -> Result<SendableRecordBatchStream> {
+        let (object_store, _) = self.store(source_relay);
+        let bytes = object_store.get(&Path::parse(path)?).await?.bytes().await?;
+        let buffer = Buffer::from_bytes(bytes.into());
+        // An IPC file ends in the length of its footer followed by the magic number
+        let trailer_start = buffer
+            .len()
+            .checked_sub(10)
+            .ok_or_else(|| ipc_error(format!("{path} is too short")))?;
+        let trailer = buffer[trailer_start..]
+            .try_into()
+            .expect("Trailer is 10 bytes");
+        let footer_start = trailer_start
+            .checked_sub(read_footer_length(trailer).map_err(ipc_error)?)
+            .ok_or_else(|| ipc_error(format!("{path} has an invalid footer")))?;
+        let footer = root_as_footer(&buffer[footer_start..trailer_start]).map_err(ipc_error)?;
+        let schema = Arc::new(fb_to_schema(
+            footer
+                .schema()
+                .ok_or_else(|| ipc_error(format!("{path} has no schema")))?,
+        ));
+
+        let block_data = |buffer: &Buffer, block: &arrow::ipc::Block| {
+            let len = block.bodyLength() as usize + block.metaDataLength() as usize;
+            buffer.slice_with_length(block.offset() as usize, len)
+        };
+        let mut decoder = FileDecoder::new(schema.clone(), footer.version());
+        for block in footer.dictionaries().iter().flatten() {
+            decoder
+                .read_dictionary(block, &block_data(&buffer, block))
+                .map_err(ipc_error)?;
+        }
+        let blocks: Vec<_> = footer
+            .recordBatches()
+            .map(|blocks| blocks.iter().copied().collect())
+            .unwrap_or_default();
+        let batches = blocks.into_iter().filter_map(move |block| {
+            decoder
+                .read_record_batch(&block, &block_data(&buffer, &block))
+                .map_err(DataFusionError::from)
+                .transpose()
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            schema,
+            futures::stream::iter(batches),
+        )))
+    }
+
+    async fn read_parquet(
+        &self,
+        path: &str,
+        source_relay: Option<&str>,
+    ) -> Result<SendableRecordBatchStream> {
         let url = &Url::parse("results://results")?;
-        let path_str = format!("{url}/task_{}/result.parquet", task_id);
+        let path_str = format!("{url}/{path}");
         let ctx = SessionContext::new();
         ctx.runtime_env()
-            .register_object_store(url, self.object_store.clone());
+            .register_object_store(url, self.store(source_relay).0.clone());
         let df = ctx
             .read_parquet(path_str, ParquetReadOptions::default())
             .await?;
         Ok(df.execute_stream().await?)
     }
 
-    /// Sends a stream of RecordBatches using Flight to the originating remote [Relay]
+    /// Deletes the result of a task written by [ResultManager::write_task_result] with the same
+    /// source_relay and format. Results which were already deleted are ignored.
+    pub async fn delete_task_result(
+        &self,
+        task_id: &Uuid,
+        source_relay: Option<&str>,
+        format: ResultFormat,
+    ) -> Result<()> {
+        let (object_store, _) = self.store(source_relay);
+        let path = Path::parse(task_result_path(task_id, format))?;
+        delete_if_exists(object_store, &path).await
+    }
+
+    /// Deletes the result of a task written by [ResultManager::write_replay_result].
+    pub async fn delete_replay_result(
+        &self,
+        replay_id: &Uuid,
+        task_id: &Uuid,
+        format: ResultFormat,
+    ) -> Result<()> {
+        let path = Path::parse(replay_result_path(replay_id, task_id, format))?;
+        delete_if_exists(&self.object_store, &path).await
+    }
+
+    /// Deletes a dataset written by [ResultManager::write_scratch_dataset].
+    pub async fn delete_scratch_dataset(&self, user_id: &Uuid, dataset_name: &str) -> Result<()> {
+        let dir = scratch_dataset_dir(user_id, dataset_name);
+        let path = Path::parse(format!("{dir}/data.parquet"))?;
+        delete_if_exists(&self.object_store, &path).await
+    }
+
+    /// Reads the cached result for key, if the cache is enabled and holds a result which was
+    /// written within its ttl.
+    pub async fn get_cached_result(
+        &self,
+        key: &ResultCacheKey,
+    ) -> Result<Option<SendableRecordBatchStream>> {
+        let Some(ttl) = self.cache_ttl else {
+            return Ok(None);
+        };
+        let path = key.path();
+        let cached_at = match self.object_store.head(&Path::parse(&path)?).await {
+            Ok(meta) => meta.last_modified,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // Expired results are overwritten once the task is executed again
+        if cached_at + ttl < Utc::now() {
+            debug!("Cached result {path} expired at {}", cached_at + ttl);
+            return Ok(None);
+        }
+        Ok(Some(self.read_parquet(&path, None).await?))
+    }
+
+    /// Writes rb_stream to the cache under key and returns the cached result, so that it is
+    /// delivered as usual. Returns rb_stream unchanged if the cache is disabled.
+    pub async fn cache_result(
+        &self,
+        key: &ResultCacheKey,
+        rb_stream: SendableRecordBatchStream,
+    ) -> Result<SendableRecordBatchStream> {
+        if self.cache_ttl.is_none() {
+            return Ok(rb_stream);
+        }
+        let path = key.path();
+        let schema = rb_stream.schema();
+        self.write_parquet(None, &Path::parse(&path)?, rb_stream, schema)
+            .await?;
+        self.read_parquet(&path, None).await
+    }
+
+    /// Deletes the cached results of a DataSource, or every cached result if None, so that
+    /// their tasks are executed again. Returns how many results were deleted.
+    pub async fn invalidate_cached_results(&self, data_source_id: Option<&Uuid>) -> Result<usize> {
+        let prefix = Path::parse(cache_prefix(data_source_id))?;
+        let locations = self
+            .object_store
+            .list(Some(&prefix))
+            .map_ok(|meta| meta.location)
+            .boxed();
+        let deleted: Vec<Path> = self
+            .object_store
+            .delete_stream(locations)
+            .try_collect()
+            .await?;
+        Ok(deleted.len())
+    }
+
+    /// Connects to a remote flight service, authenticating with the client certificate of the
+    /// local [Relay].
+    pub async fn flight_client(&self, flight_endpoint: String) -> Result<FlightClient> {
+        let channel = tonic::transport::Channel::from_shared(flight_endpoint)?
+            .tls_config(
+                ClientTlsConfig::new()
+                    .identity(Identity::from_pem(
+                        &self.client_cert_pem,
+                        &self.client_key_pem,
+                    ))
+                    .ca_certificate(Certificate::from_pem(&self.cacert_pem)),
+            )?
+            .connect()
+            .await?;
+        let svc_client = FlightServiceClient::new(channel);
+        Ok(FlightClient::new_from_inner(svc_client))
+    }
+
+    /// Returns the PEM encoded client certificate of the local [Relay].
+    pub fn client_cert_pem(&self) -> &[u8] {
+        &self.client_cert_pem
+    }
+
+    /// Sends a stream of RecordBatches using Flight to the originating remote [Relay], returning
+    /// a [TransferCounter] with the total rows and bytes sent.
     pub async fn send_result_flight<S>(
         &self,
         local_task_id: &Uuid,
@@ -111,26 +868,15 @@ impl ResultManager {
         rb_stream: Pin<Box<S>>,
         schema: Arc<Schema>,
         relay: Relay,
-    ) -> Result<()>
+        metadata: &ResultMetadata,
+    ) -> Result<Arc<TransferCounter>>
     where
         S: Stream<Item = std::result::Result<RecordBatch, DataFusionError>>
             + Send
             + 'static
             + ?Sized,
     {
-        let channel = tonic::transport::Channel::from_shared(relay.flight_endpoint)?
-            .tls_config(
-                ClientTlsConfig::new()
-                    .identity(Identity::from_pem(
-                        &self.client_cert_pem,
-                        &self.client_key_pem,
-                    ))
-                    .ca_certificate(Certificate::from_pem(&self.cacert_pem)),
-            )?
-            .connect()
-            .await?;
-        let svc_client = FlightServiceClient::new(channel);
-        let mut client = FlightClient::new_from_inner(svc_client);
+        let mut client = self.flight_client(relay.flight_endpoint).await?;
 
         // add an initial FlightData message that sends schema
         let options = datafusion::arrow::ipc::writer::IpcWriteOptions::default();
@@ -142,22 +888,424 @@ impl ResultManager {
             cmd: Default::default(),
             path: vec![local_task_id.to_string(), origin_task_id.to_string()],
         });
+        first_flight.app_metadata = serde_json::to_vec(metadata)?.into();
+
+        let counter = Arc::new(TransferCounter::default());
+        let counter_clone = counter.clone();
+        let rb_stream = rb_stream.inspect_ok(move |batch| {
+            let bytes = batch.get_array_memory_size();
+            counter_clone.add(batch.num_rows(), bytes);
+            metrics().bytes_streamed("relay", bytes);
+        });
 
-        // Chain the data stream behind the initial metadata message
-        let flight_data_stream = futures::stream::once(async { Ok(first_flight) }).chain(
-            FlightDataEncoderBuilder::new()
-                .build(rb_stream.map_err(|e| FlightError::ExternalError(Box::new(e)))),
+        // Chain the data stream behind the initial metadata message, and interleave progress
+        // messages so that the upload is not considered idle while the source is slow. The
+        // encoder sends the schema again right away, so that no progress message precedes it.
+        let flight_data_stream = with_progress(
+            futures::stream::once(async { Ok(first_flight) }).chain(
+                FlightDataEncoderBuilder::new()
+                    .with_schema(schema)
+                    .build(rb_stream.map_err(|e| FlightError::ExternalError(Box::new(e)))),
+            ),
+            counter.clone(),
+            flight_progress_interval(),
+            progress_flight_data,
         );
 
         let mut resp = client.do_put(flight_data_stream).await.map_err(|e| {
             MeshError::RemoteError(format!("error in do_put to relay {}: {}", relay.id, e))
         })?;
 
-        while let Some(_put_resp) = resp.next().await.transpose().map_err(|e| {
+        while let Some(put_resp) = resp.next().await.transpose().map_err(|e| {
             MeshError::RemoteError(format!("error in do_put to relay {}: {}", relay.id, e))
         })? {
-            // nothing to do with response for now
+            // Other responses only carry the receiving relay's progress
+            if let Ok(decision) = serde_json::from_slice::<PutDecision>(&put_resp.app_metadata) {
+                match decision.dedup {
+                    PutDedup::Skipped => info!(
+                        "Relay {} already had a complete result for flight {}, skipped sending",
+                        relay.id, decision.flight_id
+                    ),
+                    dedup => debug!(
+                        "Relay {} accepted flight {}: {dedup:?}",
+                        relay.id, decision.flight_id
+                    ),
+                }
+            }
+        }
+        Ok(counter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::sync::Arc;
+
+    use arrow_array::types::Int8Type;
+    use arrow_array::{DictionaryArray, Int32Array, RecordBatch, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use futures::TryStreamExt;
+    use uuid::Uuid;
+
+    use crate::error::Result;
+    use crate::model::access_control::{ColumnPermission, RowPermission, SourcePermission};
+    use crate::model::data_stores::options::file_directory::TenantResultStore;
+    use crate::model::data_stores::options::SupportedObjectStore;
+    use crate::model::query::{Query, ResultFormat};
+
+    use super::{ResultCacheKey, ResultManager};
+
+    #[tokio::test]
+    async fn test_tenant_result_stores() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dataweb-{}", Uuid::new_v4()));
+        let (default_dir, tenant_dir) = (dir.join("default"), dir.join("partner"));
+        fs::create_dir_all(&default_dir)?;
+        fs::create_dir_all(&tenant_dir)?;
+
+        let tenant = TenantResultStore {
+            object_store_type: SupportedObjectStore::LocalFileSystem,
+            bucket: None,
+            region: None,
+            prefix: Some(tenant_dir.display().to_string()),
+            s3: None,
+            client_config: Default::default(),
+        };
+        let mut source = tenant.source();
+        source.prefix = Some(default_dir.display().to_string());
+        let manager = ResultManager::try_initialize(
+            SupportedObjectStore::LocalFileSystem,
+            source,
+            Default::default(),
+            vec![],
+            vec![],
+            vec![],
+        )?
+        .with_tenant_stores(HashMap::from([("partner_relay".to_string(), tenant)]))?;
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )?;
+        let task_id = Uuid::new_v4();
+        let stream = Box::pin(futures::stream::iter(vec![Ok(batch)]));
+        manager
+            .write_task_result(
+                &task_id,
+                Some("partner_relay"),
+                ResultFormat::Parquet,
+                stream,
+                schema,
+            )
+            .await?;
+
+        let result_path = format!("task_{task_id}/result.parquet");
+        assert!(tenant_dir.join(&result_path).exists());
+        assert!(!default_dir.join(&result_path).exists());
+        let batches: Vec<RecordBatch> = manager
+            .get_task_result(task_id, Some("partner_relay"), ResultFormat::Parquet)
+            .await?
+            .try_collect()
+            .await?;
+        assert_eq!(batches[0].num_rows(), 3);
+        assert!(manager
+            .get_task_result(task_id, None, ResultFormat::Parquet)
+            .await
+            .is_err());
+
+        manager
+            .delete_task_result(&task_id, Some("partner_relay"), ResultFormat::Parquet)
+            .await?;
+        assert!(!tenant_dir.join(&result_path).exists());
+        // Deleting a result again, e.g. from another query_runner, succeeds
+        manager
+            .delete_task_result(&task_id, Some("partner_relay"), ResultFormat::Parquet)
+            .await?;
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_arrow_ipc_results() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dataweb-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+        let mut source = TenantResultStore {
+            object_store_type: SupportedObjectStore::LocalFileSystem,
+            bucket: None,
+            region: None,
+            prefix: None,
+            s3: None,
+            client_config: Default::default(),
         }
+        .source();
+        source.prefix = Some(dir.display().to_string());
+        let manager = ResultManager::try_initialize(
+            SupportedObjectStore::LocalFileSystem,
+            source,
+            Default::default(),
+            vec![],
+            vec![],
+            vec![],
+        )?;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new(
+                "b",
+                DataType::Dictionary(Box::new(DataType::Int8), Box::new(DataType::Utf8)),
+                false,
+            ),
+        ]));
+        let batches = vec![
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(vec![1, 2, 3])),
+                    Arc::new(DictionaryArray::<Int8Type>::from_iter(["x", "y", "x"])),
+                ],
+            )?,
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(vec![4])),
+                    Arc::new(DictionaryArray::<Int8Type>::from_iter(["x"])),
+                ],
+            )?,
+        ];
+        let task_id = Uuid::new_v4();
+        let stream = Box::pin(futures::stream::iter(batches.into_iter().map(Ok)));
+        manager
+            .write_task_result(
+                &task_id,
+                None,
+                ResultFormat::ArrowIpc,
+                stream,
+                schema.clone(),
+            )
+            .await?;
+
+        assert!(dir.join(format!("task_{task_id}/result.arrow")).exists());
+        let result = manager
+            .get_task_result(task_id, None, ResultFormat::ArrowIpc)
+            .await?;
+        // Dictionaries may differ between batches, so their values are stored instead
+        let stored_schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Utf8, false),
+        ]));
+        assert_eq!(result.schema(), stored_schema);
+        let read: Vec<RecordBatch> = result.try_collect().await?;
+        assert_eq!(
+            read,
+            vec![
+                RecordBatch::try_new(
+                    stored_schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from(vec![1, 2, 3])),
+                        Arc::new(StringArray::from(vec!["x", "y", "x"])),
+                    ],
+                )?,
+                RecordBatch::try_new(
+                    stored_schema,
+                    vec![
+                        Arc::new(Int32Array::from(vec![4])),
+                        Arc::new(StringArray::from(vec!["x"])),
+                    ],
+                )?,
+            ]
+        );
+        // The result is not stored as parquet
+        assert!(manager
+            .get_task_result(task_id, None, ResultFormat::Parquet)
+            .await
+            .is_err());
+
+        manager
+            .delete_task_result(&task_id, None, ResultFormat::ArrowIpc)
+            .await?;
+        assert!(manager
+            .get_task_result(task_id, None, ResultFormat::ArrowIpc)
+            .await
+            .is_err());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_result_pages() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dataweb-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+        let mut source = TenantResultStore {
+            object_store_type: SupportedObjectStore::LocalFileSystem,
+            bucket: None,
+            region: None,
+            prefix: None,
+            s3: None,
+            client_config: Default::default(),
+        }
+        .source();
+        source.prefix = Some(dir.display().to_string());
+        let manager = ResultManager::try_initialize(
+            SupportedObjectStore::LocalFileSystem,
+            source,
+            Default::default(),
+            vec![],
+            vec![],
+            vec![],
+        )?;
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batches = [1..4, 4..8, 8..11]
+            .into_iter()
+            .map(|rows| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int32Array::from_iter_values(rows))],
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        for format in [ResultFormat::Parquet, ResultFormat::ArrowIpc] {
+            let task_id = Uuid::new_v4();
+            let stream = Box::pin(futures::stream::iter(batches.clone().into_iter().map(Ok)));
+            manager
+                .write_task_result(&task_id, None, format, stream, schema.clone())
+                .await?;
+
+            let manager = &manager;
+            let page = |offset, limit| async move {
+                let (stream, total_rows) = manager
+                    .get_task_result_page(task_id, None, format, offset, limit)
+                    .await?;
+                let rows: Vec<i32> = stream
+                    .try_collect::<Vec<_>>()
+                    .await?
+                    .iter()
+                    .flat_map(|batch| {
+                        batch
+                            .column(0)
+                            .as_any()
+                            .downcast_ref::<Int32Array>()
+                            .unwrap()
+                            .values()
+                            .to_vec()
+                    })
+                    .collect();
+                Ok::<_, crate::error::MeshError>((rows, total_rows))
+            };
+            assert_eq!(page(2, 4).await?, (vec![3, 4, 5, 6], 10));
+            assert_eq!(page(8, 5).await?, (vec![9, 10], 10));
+            assert_eq!(page(10, 5).await?, (vec![], 10));
+            assert_eq!(page(0, 0).await?, (vec![], 10));
+        }
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_result_cache() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dataweb-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+        let mut source = TenantResultStore {
+            object_store_type: SupportedObjectStore::LocalFileSystem,
+            bucket: None,
+            region: None,
+            prefix: None,
+            s3: None,
+            client_config: Default::default(),
+        }
+        .source();
+        source.prefix = Some(dir.display().to_string());
+        let manager = ResultManager::try_initialize(
+            SupportedObjectStore::LocalFileSystem,
+            source,
+            Default::default(),
+            vec![],
+            vec![],
+            vec![],
+        )?;
+
+        let source_id = Uuid::new_v4();
+        let query = |sql: &str| Query {
+            sql: sql.to_string(),
+            return_schema: None,
+        };
+        let permission = |columns: &[&str]| SourcePermission {
+            columns: ColumnPermission {
+                allowed_columns: columns.iter().map(|c| c.to_string()).collect(),
+            },
+            rows: RowPermission {
+                allowed_rows: "true".to_string(),
+            },
+        };
+        let key = ResultCacheKey::new(
+            source_id,
+            &query("select a from t"),
+            Some(&permission(&["a", "b"])),
+        );
+        assert_eq!(
+            key,
+            ResultCacheKey::new(
+                source_id,
+                &query("select  a\nfrom t"),
+                Some(&permission(&["b", "a"]))
+            )
+        );
+        assert_ne!(
+            key,
+            ResultCacheKey::new(
+                source_id,
+                &query("select a from t"),
+                Some(&permission(&["a"]))
+            )
+        );
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )?;
+        let stream = || {
+            Box::pin(RecordBatchStreamAdapter::new(
+                schema.clone(),
+                futures::stream::iter(vec![Ok(batch.clone())]),
+            ))
+        };
+
+        // Without a ttl results pass through and are never cached
+        manager.cache_result(&key, stream()).await?;
+        assert!(manager.get_cached_result(&key).await?.is_none());
+
+        let manager = manager.with_result_cache(Some(chrono::Duration::minutes(5)));
+        assert!(manager.get_cached_result(&key).await?.is_none());
+        let batches: Vec<RecordBatch> = manager
+            .cache_result(&key, stream())
+            .await?
+            .try_collect()
+            .await?;
+        assert_eq!(batches[0].num_rows(), 3);
+        let cached: Vec<RecordBatch> = manager
+            .get_cached_result(&key)
+            .await?
+            .unwrap()
+            .try_collect()
+            .await?;
+        assert_eq!(cached, batches);
+
+        assert_eq!(
+            manager
+                .invalidate_cached_results(Some(&Uuid::new_v4()))
+                .await?,
+            0
+        );
+        assert_eq!(
+            manager.invalidate_cached_results(Some(&source_id)).await?,
+            1
+        );
+        assert!(manager.get_cached_result(&key).await?.is_none());
+        fs::remove_dir_all(&dir)?;
         Ok(())
     }
 }
@@ -0,0 +1,279 @@
+use std::collections::HashSet;
+use std::env;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use chrono::{Duration, Utc};
+use datafusion::arrow::datatypes::Schema;
+use datafusion::error::DataFusionError;
+use futures::Stream;
+use regex::Regex;
+
+use crate::crud::PgDb;
+use crate::error::{MeshError, Result};
+use crate::model::access_control::{ColumnPermission, PolicyMode, RowPermission, SourcePermission};
+use crate::model::config_commands::no_transformation;
+use crate::model::data_stores::engines::SourceEngines;
+use crate::model::data_stores::schedule::ExecutionWindows;
+use crate::model::data_stores::{NewDataField, NewDataSource, ScratchDataset};
+use crate::model::entity::{ArrowDataType, Entity, NewInformation};
+use crate::model::mappings::Mapping;
+use crate::model::user::User;
+
+use super::result_manager::ResultManager;
+
+/// How long a scratch dataset is kept after it was last uploaded, read from
+/// SCRATCH_DATASET_TTL_SECS (default 604800, i.e. 7 days).
+pub fn scratch_dataset_ttl() -> Duration {
+    let ttl_secs: i64 = env::var("SCRATCH_DATASET_TTL_SECS")
+        .unwrap_or("604800".to_string())
+        .parse()
+        .expect("Unable to parse SCRATCH_DATASET_TTL_SECS as i64!");
+    Duration::seconds(ttl_secs)
+}
+
+/// Returns the name of the [Entity] which a scratch dataset is mapped to.
+pub fn scratch_entity_name(dataset_name: &str) -> String {
+    format!("scratch_{dataset_name}")
+}
+
+/// Stores a user uploaded dataset via the [ResultManager] and registers it as a local
+/// [DataSource][crate::model::data_stores::DataSource] mapped one to one to a scratch [Entity].
+/// Only the uploading [User] is granted access to the dataset. Uploading a dataset with the
+/// same name again replaces the data and restarts its [scratch_dataset_ttl], but only the
+/// original uploader may do so. Expired datasets are deleted by [delete_expired_scratch_datasets].
+pub async fn create_scratch_dataset<S>(
+    db: &mut PgDb<'_>,
+    result_manager: &ResultManager,
+    user: &User,
+    dataset_name: &str,
+    rb_stream: Pin<Box<S>>,
+    schema: Arc<Schema>,
+) -> Result<Entity>
+where
+    S: Stream<Item = std::result::Result<RecordBatch, DataFusionError>> + Send + 'static + ?Sized,
+{
+    let valid_name = Regex::new(r"^[a-zA-Z][a-zA-Z0-9_]*$")?;
+    if !valid_name.is_match(dataset_name) {
+        return Err(MeshError::InvalidQuery(format!(
+            "Invalid dataset name {dataset_name}. Dataset names must start with a letter \
+            and contain only letters, numbers and underscores."
+        )));
+    }
+    if schema.fields().is_empty() {
+        return Err(MeshError::InvalidQuery(format!(
+            "Uploaded dataset {dataset_name} has no columns!"
+        )));
+    }
+
+    let entity_name = scratch_entity_name(dataset_name);
+    if let Ok(con) = db.get_connection(&entity_name).await {
+        let source = db.get_source(dataset_name, &con.id).await?;
+        if db
+            .get_user_source_permission(&user.x509_sha256, &source.id)
+            .await?
+            .is_none()
+        {
+            return Err(MeshError::InvalidQuery(format!(
+                "Dataset {dataset_name} already exists and was uploaded by another user!"
+            )));
+        }
+    }
+
+    let (con_opts, source_opts) = result_manager
+        .write_scratch_dataset(&user.id, dataset_name, rb_stream, schema.clone())
+        .await?;
+
+    let con = db
+        .upsert_connection(&entity_name, con_opts, ExecutionWindows::default())
+        .await?;
+    db.upsert_scratch_dataset(&ScratchDataset {
+        data_connection_id: con.id,
+        user_id: user.id,
+        dataset_name: dataset_name.to_string(),
+        expires_at: Utc::now() + scratch_dataset_ttl(),
+    })
+    .await?;
+    let source = db
+        .upsert_source(&NewDataSource {
+            name: dataset_name.to_string(),
+            source_sql: format!("select * from {dataset_name}"),
+            data_connection_id: con.id,
+            source_options: source_opts,
+            engines: SourceEngines::default(),
+            freshness_query: None,
+            policy_mode: PolicyMode::Permissive,
+        })
+        .await?;
+
+    let entity = db.create_entity_if_not_exist(&entity_name).await?;
+    for (position, field) in schema.fields().iter().enumerate() {
+        db.upsert_field(&NewDataField {
+            name: field.name().clone(),
+            data_source_id: source.id,
+            path: field.name().clone(),
+        })
+        .await?;
+        let data_field = db.get_field(field.name(), &source.id).await?;
+
+        db.upsert_information(&NewInformation {
+            name: field.name().clone(),
+            arrow_dtype: ArrowDataType {
+                inner: field.data_type().clone(),
+            },
+            entity_id: entity.id,
+            position: Some(position as i32),
+            nullable: field.is_nullable(),
+        })
+        .await?;
+        let info = db.get_information(field.name(), &entity.id).await?;
+
+        db.upsert_local_mapping(&Mapping {
+            information_id: info.id,
+            data_field_id: data_field.id,
+            transformation: no_transformation(),
+        })
+        .await?;
+    }
+
+    let no_access = SourcePermission {
+        columns: ColumnPermission {
+            allowed_columns: HashSet::new(),
+        },
+        rows: RowPermission {
+            allowed_rows: "false".to_string(),
+        },
+    };
+    db.upsert_default_source_permission(&source.id, &no_access)
+        .await?;
+
+    let owner_access = SourcePermission {
+        columns: ColumnPermission {
+            allowed_columns: schema.fields().iter().map(|f| f.name().clone()).collect(),
+        },
+        rows: RowPermission {
+            allowed_rows: "true".to_string(),
+        },
+    };
+    db.upsert_user_source_permission(&user.id, &source.id, &owner_access)
+        .await?;
+
+    Ok(entity)
+}
+
+/// Deletes up to limit expired scratch datasets, their data along with the connection, source and
+/// [Entity] registered by [create_scratch_dataset], returning how many were deleted. The data is
+/// deleted first, so that a dataset whose data failed to be deleted is retried by the next call.
+pub async fn delete_expired_scratch_datasets(
+    db: &mut PgDb<'_>,
+    result_manager: &ResultManager,
+    limit: i64,
+) -> Result<usize> {
+    let mut deleted = 0;
+    for dataset in db.get_expired_scratch_datasets(Utc::now(), limit).await? {
+        result_manager
+            .delete_scratch_dataset(&dataset.user_id, &dataset.dataset_name)
+            .await?;
+        deleted += db
+            .delete_scratch_dataset(&dataset, &scratch_entity_name(&dataset.dataset_name))
+            .await?;
+    }
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    use crate::crud::test_utils::test_db;
+    use crate::crud::PgDb;
+    use crate::execute::result_manager::ResultManager;
+    use crate::model::data_stores::options::file_directory::TenantResultStore;
+    use crate::model::data_stores::options::SupportedObjectStore;
+    use crate::model::data_stores::ScratchDataset;
+    use crate::model::user::{NewUser, UserAttributes};
+
+    use super::{create_scratch_dataset, delete_expired_scratch_datasets, scratch_entity_name};
+
+    #[tokio::test]
+    async fn test_expired_scratch_dataset_is_deleted() {
+        let Some(test_db) = test_db().await else {
+            return;
+        };
+        let mut db = PgDb::try_from_pool(&test_db.pool).await.unwrap();
+        let dir = std::env::temp_dir().join(format!("dataweb-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let store = TenantResultStore {
+            object_store_type: SupportedObjectStore::LocalFileSystem,
+            bucket: None,
+            region: None,
+            prefix: Some(dir.display().to_string()),
+            s3: None,
+            client_config: Default::default(),
+        };
+        let result_manager = ResultManager::try_initialize(
+            SupportedObjectStore::LocalFileSystem,
+            store.source(),
+            Default::default(),
+            vec![],
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let user = db
+            .upsert_user_by_fingerprint(&NewUser {
+                x509_sha256: "abc123".to_string(),
+                x509_subject: "CN=user".to_string(),
+                x509_issuer: "CN=ca".to_string(),
+                x509_cert: None,
+                attributes: UserAttributes::new(),
+            })
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(vec![1]))])
+            .unwrap();
+        let stream = Box::pin(futures::stream::iter(vec![Ok(batch)]));
+        create_scratch_dataset(&mut db, &result_manager, &user, "mine", stream, schema)
+            .await
+            .unwrap();
+        let data_path = dir.join(format!("scratch/{}/mine/data.parquet", user.id.simple()));
+        assert!(data_path.exists());
+
+        // Datasets within their ttl are kept
+        assert_eq!(
+            delete_expired_scratch_datasets(&mut db, &result_manager, 10)
+                .await
+                .unwrap(),
+            0
+        );
+        let entity_name = scratch_entity_name("mine");
+        let con = db.get_connection(&entity_name).await.unwrap();
+        db.upsert_scratch_dataset(&ScratchDataset {
+            data_connection_id: con.id,
+            user_id: user.id,
+            dataset_name: "mine".to_string(),
+            expires_at: Utc::now() - Duration::seconds(1),
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            delete_expired_scratch_datasets(&mut db, &result_manager, 10)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(!data_path.exists());
+        assert!(db.get_connection(&entity_name).await.is_err());
+        assert!(db.get_entity(&entity_name).await.is_err());
+    }
+}
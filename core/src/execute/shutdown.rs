@@ -0,0 +1,101 @@
+use std::env;
+use std::future::Future;
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// How long in-flight work may take to finish once shutdown was requested, read from
+/// SHUTDOWN_TIMEOUT_SECS (default 30). Should be shorter than the termination grace period of
+/// the deployment, e.g. terminationGracePeriodSeconds in Kubernetes.
+pub fn shutdown_timeout() -> Duration {
+    let timeout_secs = env::var("SHUTDOWN_TIMEOUT_SECS")
+        .unwrap_or("30".to_string())
+        .parse()
+        .expect("Unable to parse SHUTDOWN_TIMEOUT_SECS as u64!");
+    Duration::from_secs(timeout_secs)
+}
+
+/// Resolves once the process receives SIGTERM or SIGINT.
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut sigterm = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM!");
+        tokio::select! {
+            _ = sigterm.recv() => info!("Received SIGTERM, shutting down..."),
+            _ = tokio::signal::ctrl_c() => info!("Received SIGINT, shutting down..."),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        info!("Received ctrl-c, shutting down...");
+    }
+}
+
+/// Returns a token which is cancelled once the process receives SIGTERM or SIGINT.
+pub fn shutdown_token() -> CancellationToken {
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        cancel.cancel();
+    });
+    token
+}
+
+/// Drives work to completion, but once shutdown is cancelled gives it at most timeout to finish.
+/// Returns None if work was abandoned because it did not finish in time.
+pub async fn drain<T>(
+    shutdown: &CancellationToken,
+    timeout: Duration,
+    work: impl Future<Output = T>,
+) -> Option<T> {
+    tokio::pin!(work);
+    tokio::select! {
+        done = &mut work => return Some(done),
+        _ = shutdown.cancelled() => (),
+    }
+    match tokio::time::timeout(timeout, work).await {
+        Ok(done) => Some(done),
+        Err(_) => {
+            warn!(
+                "In-flight work did not finish within {}s of shutdown and was abandoned",
+                timeout.as_secs()
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio_util::sync::CancellationToken;
+
+    use super::drain;
+
+    #[tokio::test]
+    async fn test_drain() {
+        let shutdown = CancellationToken::new();
+        assert_eq!(drain(&shutdown, Duration::ZERO, async { 1 }).await, Some(1));
+
+        // Work which finishes within the timeout is not abandoned
+        shutdown.cancel();
+        let finishes = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            2
+        };
+        assert_eq!(
+            drain(&shutdown, Duration::from_secs(5), finishes).await,
+            Some(2)
+        );
+        let hangs = std::future::pending::<i32>();
+        assert_eq!(
+            drain(&shutdown, Duration::from_millis(10), hangs).await,
+            None
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use std::env;
+
+use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{config, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use tracing::{error, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::model::query::TraceContext;
+
+impl Injector for TraceContext {
+    fn set(&mut self, key: &str, value: String) {
+        self.fields.insert(key.to_string(), value);
+    }
+}
+
+impl Extractor for TraceContext {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(|v| v.as_str())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.fields.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+impl TraceContext {
+    /// Extracts the trace context of an incoming call, where header returns the value of a
+    /// header or gRPC metadata key if it is present.
+    pub fn from_headers<'a>(header: impl Fn(&str) -> Option<&'a str>) -> Self {
+        let fields = TraceContextPropagator::new()
+            .fields()
+            .filter_map(|name| Some((name.to_string(), header(name)?.to_string())))
+            .collect();
+        Self { fields }
+    }
+
+    /// Returns the trace context of span, to be propagated to the calls made on its behalf.
+    pub fn of(span: &Span) -> Self {
+        let mut context = Self::default();
+        TraceContextPropagator::new().inject_context(&span.context(), &mut context);
+        context
+    }
+
+    /// Makes span a child of the span which this context was extracted from, returning the
+    /// context of span. Spans without a parent start a new trace.
+    pub fn start_span(&self, span: Span) -> (Span, TraceContext) {
+        if !self.fields.is_empty() {
+            span.set_parent(TraceContextPropagator::new().extract(self));
+        }
+        let context = Self::of(&span);
+        (span, context)
+    }
+
+    /// The headers which propagate this context to a peered relay.
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// Installs the tracing subscriber of the process, which logs to stdout and records spans with
+/// OpenTelemetry so that trace context is propagated across relay hops. Spans are exported via
+/// OTLP if OTEL_EXPORTER_OTLP_ENDPOINT is set, and otherwise only propagated.
+pub fn init_tracing(service_name: &str) {
+    let trace_config = config().with_resource(Resource::new(vec![KeyValue::new(
+        "service.name",
+        service_name.to_string(),
+    )]));
+    let tracer = match env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Ok(endpoint) => opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_trace_config(trace_config)
+            .install_batch(runtime::Tokio)
+            .expect("Failed to install OTLP trace exporter!"),
+        Err(_) => {
+            let provider = TracerProvider::builder().with_config(trace_config).build();
+            let tracer = provider.tracer(service_name.to_string());
+            global::set_tracer_provider(provider);
+            tracer
+        }
+    };
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .init();
+}
+
+/// Exports any spans which are still buffered, should be called before the process exits. The
+/// exporter is shut down on a blocking thread, since it waits on a task of the runtime.
+pub async fn shutdown_tracing() {
+    if let Err(e) = tokio::task::spawn_blocking(global::shutdown_tracer_provider).await {
+        error!("Failed to shut down trace exporter with error {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::model::query::TraceContext;
+
+    #[test]
+    fn test_from_headers() {
+        let headers = HashMap::from([
+            (
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ),
+            ("tracestate", "vendor=value"),
+            ("x-client-cert", "ignored"),
+        ]);
+        let context = TraceContext::from_headers(|name| headers.get(name).copied());
+        assert_eq!(context.fields.len(), 2);
+        assert_eq!(
+            context.fields["traceparent"],
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+
+        let empty = TraceContext::from_headers(|_| None);
+        assert!(empty.fields.is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use arrow_schema::DataType;
+use datafusion::common::not_impl_err;
+use datafusion::logical_expr::{create_udf, ScalarUDF, Volatility};
+use datafusion::prelude::SessionContext;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{MeshError, Result};
+
+/// Declares the signature of a custom scalar function, e.g. a domain specific hash or geo function,
+/// which [DataSource][crate::model::data_stores::DataSource]s are able to evaluate. Declared functions
+/// may be used in query templates and are passed through to the data sources as-is. The same
+/// declarations are understood by the data web engine, so both sides agree on the query surface.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScalarFunctionDeclaration {
+    pub name: String,
+    pub args: Vec<DataType>,
+    pub return_type: DataType,
+}
+
+impl ScalarFunctionDeclaration {
+    /// Creates a [ScalarUDF] with this signature which can be planned but not executed locally.
+    fn to_udf(&self) -> ScalarUDF {
+        let name = self.name.clone();
+        create_udf(
+            &self.name,
+            self.args.clone(),
+            Arc::new(self.return_type.clone()),
+            Volatility::Immutable,
+            Arc::new(move |_| {
+                not_impl_err!(
+                    "Function {name} is declared only and must be evaluated by a data source"
+                )
+            }),
+        )
+    }
+}
+
+/// Holds custom [ScalarUDF]s which are made available to query validation and planning as well as
+/// to DataFusion based [QueryRunner][crate::execute::data_stores::QueryRunner]s.
+#[derive(Default)]
+pub struct UdfRegistry {
+    functions: HashMap<String, Arc<ScalarUDF>>,
+}
+
+impl UdfRegistry {
+    /// Loads a JSON list of [ScalarFunctionDeclaration]s from the file named by the
+    /// UDF_REGISTRY_FILE environment variable, if it is set.
+    pub fn from_env() -> Result<Self> {
+        let mut registry = Self::default();
+        if let Ok(path) = env::var("UDF_REGISTRY_FILE") {
+            let declarations: Vec<ScalarFunctionDeclaration> =
+                serde_json::from_reader(std::fs::File::open(&path)?)?;
+            for declaration in declarations {
+                registry.declare(&declaration)?;
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Registers a [ScalarUDF] with a local implementation. Replaces any function with the same name.
+    pub fn register(&mut self, udf: ScalarUDF) {
+        self.functions
+            .insert(udf.name().to_lowercase(), Arc::new(udf));
+    }
+
+    /// Registers a function which can only be evaluated by data sources.
+    pub fn declare(&mut self, declaration: &ScalarFunctionDeclaration) -> Result<()> {
+        if self
+            .functions
+            .contains_key(&declaration.name.to_lowercase())
+        {
+            return Err(MeshError::SerDe(format!(
+                "Scalar function {} is declared more than once!",
+                declaration.name
+            )));
+        }
+        self.register(declaration.to_udf());
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<ScalarUDF>> {
+        self.functions.get(&name.to_lowercase()).cloned()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.functions.keys().cloned().collect()
+    }
+
+    /// Registers every function in this registry with a DataFusion [SessionContext].
+    pub fn register_all(&self, ctx: &SessionContext) {
+        for udf in self.functions.values() {
+            ctx.register_udf(udf.as_ref().clone());
+        }
+    }
+}
+
+static REGISTRY: OnceLock<RwLock<UdfRegistry>> = OnceLock::new();
+
+/// Returns the process wide [UdfRegistry], loading it from the environment on first access.
+/// Panics if UDF_REGISTRY_FILE is set but cannot be parsed.
+pub fn udf_registry() -> &'static RwLock<UdfRegistry> {
+    REGISTRY.get_or_init(|| {
+        RwLock::new(UdfRegistry::from_env().expect("Failed to load UDF_REGISTRY_FILE!"))
+    })
+}
+
+/// Adds a [ScalarUDF] with a local implementation to the process wide [UdfRegistry]. Should be
+/// called at startup, before any queries are planned.
+pub fn register_udf(udf: ScalarUDF) {
+    udf_registry()
+        .write()
+        .expect("UDF registry lock poisoned")
+        .register(udf);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_schema::{DataType, Field, Schema};
+
+    use crate::error::Result;
+    use crate::execute::planning::EntityContext;
+    use crate::execute::validation::{logical_round_trip, validate_sql};
+
+    use super::{udf_registry, ScalarFunctionDeclaration};
+
+    #[test]
+    fn test_declared_function_plans() -> Result<()> {
+        udf_registry()
+            .write()
+            .unwrap()
+            .declare(&ScalarFunctionDeclaration {
+                name: "geohash".to_string(),
+                args: vec![DataType::Float64, DataType::Float64],
+                return_type: DataType::Utf8,
+            })?;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("lat", DataType::Float64, true),
+            Field::new("lon", DataType::Float64, true),
+        ]));
+        let (_, statement) = validate_sql("select geohash(lat, lon) as hash from entityname")?;
+        let context = EntityContext::new("entityname", schema);
+        let (statement, schema) = logical_round_trip(statement, context)?;
+
+        assert!(statement.to_string().contains("geohash("));
+        assert_eq!(schema.field(0).data_type(), &DataType::Utf8);
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::error::Result;
@@ -6,19 +7,25 @@ use crate::execute::{request_to_local_queries, request_to_remote_requests};
 use crate::model::entity::Information;
 use crate::model::query::{
     NewQueryTask, QueryOriginationInfo, QueryRequest, QueryTask, QueryTaskRemote,
-    QueryTaskRemoteStatus, QueryTaskStatus, RawQueryRequest,
+    QueryTaskRemoteStatus, QueryTaskStatus, RawQueryRequest, TraceContext,
 };
+use crate::model::quota::{Quota, QuotaSubject};
 use crate::model::relay::Relay;
 use crate::model::user::{NewUser, User, UserAttributes};
+use crate::model::validation::DEFAULT_RULE_SET;
 use crate::{crud::PgDb, error::MeshError};
 
 use arrow_schema::{Field, Schema, SchemaBuilder, SchemaRef};
+use chrono::{DateTime, Utc};
 use datafusion::sql::sqlparser::ast::Statement;
 use tracing::debug;
 use uuid::Uuid;
 
+use super::hints::QueryHints;
+use super::identity::identity_cache;
 use super::planning::EntityContext;
-use super::validation::{logical_round_trip, validate_sql};
+use super::result_manager::{default_result_format, default_result_ttl_secs};
+use super::validation::{check_validation_rules, logical_round_trip, rename_entity, validate_sql};
 use super::Requester;
 
 /// Uses datafusion to logically plan and optimize the [Statement], ultimately converting back to
@@ -27,13 +34,169 @@ pub async fn validate_sql_and_logical_round_trip(
     sql: &str,
     db: &mut PgDb<'_>,
 ) -> Result<(String, Statement, Schema)> {
+    let (entity_name, statement, schema, _) = validate_sql_with_warnings(sql, db).await?;
+    Ok((entity_name, statement, schema))
+}
+
+/// Same as [validate_sql_and_logical_round_trip], additionally returning warnings for the
+/// requester, e.g. that the query refers to an Entity by a deprecated alias. A query which refers
+/// to an alias is planned as if it referred to the Entity by name.
+pub async fn validate_sql_with_warnings(
+    sql: &str,
+    db: &mut PgDb<'_>,
+) -> Result<(String, Statement, Schema, Vec<String>)> {
     debug!("Parsing SQL to statement: {sql}");
-    let (entity_name, statement) = validate_sql(sql)?;
+    let (mut entity_name, mut statement) = validate_sql(sql)?;
+    let mut warnings = vec![];
+    // Aliases are only looked up once no Entity has the name, so queries by name cost nothing extra
+    let context = match create_planning_context(&entity_name, db).await {
+        Ok(context) => context,
+        Err(e) => match db.get_entity_by_alias(&entity_name).await? {
+            Some(entity) => {
+                warnings.push(format!(
+                    "{entity_name} is a deprecated alias of entity {}, refer to it by name instead",
+                    entity.name
+                ));
+                rename_entity(&mut statement, &entity_name, &entity.name);
+                entity_name = entity.name;
+                create_planning_context(&entity_name, db).await?
+            }
+            None => return Err(e),
+        },
+    };
     debug!("pre round trip statement: {statement}");
-    let context = create_planning_context(&entity_name, db).await?;
     let (statement, schema) = logical_round_trip(statement, context)?;
     debug!("post round trip statement: {statement}");
-    Ok((entity_name, statement, schema))
+    Ok((entity_name, statement, schema, warnings))
+}
+
+/// Checks sql against the [ValidationRules][crate::model::validation::ValidationRules] which
+/// apply to the user, i.e. the rule set named in its attributes or otherwise the default rule
+/// set, if one is declared.
+pub async fn enforce_validation_rules(sql: &str, user: &User, db: &mut PgDb<'_>) -> Result<()> {
+    let assigned = user.attributes.validation_rules.as_deref();
+    let rules = match (
+        db.get_validation_rule_set(assigned.unwrap_or(DEFAULT_RULE_SET))
+            .await?,
+        assigned,
+    ) {
+        (Some(rule_set), _) => rule_set.rules,
+        // Fail closed, rather than letting a typo lift every restriction
+        (None, Some(name)) => {
+            return Err(MeshError::InvalidQuery(format!(
+                "Validation rule set {name} assigned to the requesting user does not exist!"
+            )))
+        }
+        (None, None) => return Ok(()),
+    };
+    let (_, statement) = validate_sql(sql)?;
+    check_validation_rules(sql, &statement, &rules)
+}
+
+/// Checks that the user may query entity_name if it is a service account, i.e. that the entity
+/// is in its [ServiceAccountScope][crate::model::user::ServiceAccountScope]. Other users are not restricted.
+pub fn check_service_account_entity(user: &User, entity_name: &str) -> Result<()> {
+    match &user.attributes.service_account {
+        Some(scope) if !scope.allowed_entities.iter().any(|e| e == entity_name) => {
+            Err(MeshError::InvalidQuery(format!(
+                "Service account {} is not permitted to query entity {entity_name}!",
+                user.x509_subject
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that requesting_user may query entity_name with sql, i.e. that the entity is in the
+/// scope of a service account and that sql passes the validation rules which apply to the user.
+/// Every entry point which executes a query or describes its result calls this once the entity
+/// of the query is resolved.
+pub async fn authorize_query(
+    sql: &str,
+    entity_name: &str,
+    requesting_user: &User,
+    db: &mut PgDb<'_>,
+) -> Result<()> {
+    check_service_account_entity(requesting_user, entity_name)?;
+    enforce_validation_rules(sql, requesting_user, db).await
+}
+
+/// Checks that the user has not exceeded the request rate of its
+/// [ServiceAccountScope][crate::model::user::ServiceAccountScope], if it is
+/// a service account. Requests are counted in the database, so the limit holds across every
+/// service and replica of the relay.
+pub async fn enforce_service_account_rate(user: &User, db: &mut PgDb<'_>) -> Result<()> {
+    let limit = match user
+        .attributes
+        .service_account
+        .as_ref()
+        .and_then(|scope| scope.max_requests_per_minute)
+    {
+        Some(limit) => limit,
+        None => return Ok(()),
+    };
+    let recent = db
+        .count_user_requests_since(&user.x509_sha256, Utc::now() - chrono::Duration::minutes(1))
+        .await?;
+    if recent >= limit as i64 {
+        return Err(MeshError::RateLimited(format!(
+            "Service account {} exceeded its limit of {limit} requests per minute!",
+            user.x509_subject
+        )));
+    }
+    Ok(())
+}
+
+/// How long after it was received a request counts as running towards its [Quota], read from
+/// QUOTA_RUNNING_WINDOW_SECS (default 3600). Bounds how long a request whose result a peer relay
+/// never sends holds the quota.
+pub fn quota_running_window() -> chrono::Duration {
+    let secs: i64 = std::env::var("QUOTA_RUNNING_WINDOW_SECS")
+        .unwrap_or("3600".to_string())
+        .parse()
+        .expect("Unable to parse QUOTA_RUNNING_WINDOW_SECS as i64!");
+    chrono::Duration::seconds(secs)
+}
+
+/// Checks that the direct requester of a query has not exceeded its [Quota] of running requests
+/// or requests per minute. Users submitting directly are limited by their own quota, and
+/// requests forwarded by a peer relay by the quota of that relay. Requests are counted in the
+/// database, so the limits hold across every service and replica of the relay, though requests
+/// submitted at the same moment may both pass.
+pub async fn enforce_quotas(direct_requester: &Requester, db: &mut PgDb<'_>) -> Result<()> {
+    let (subject, x509_sha256, name) = match direct_requester {
+        Requester::User(user) => (QuotaSubject::User, &user.x509_sha256, &user.x509_subject),
+        Requester::Relay(relay) => (QuotaSubject::Relay, &relay.x509_sha256, &relay.name),
+    };
+    let default = Quota::default_for(subject, x509_sha256);
+    let quota = match db.get_quota(subject, x509_sha256).await? {
+        Some(quota) => quota.or(default),
+        None => default,
+    };
+
+    if let Some(limit) = quota.max_requests_per_minute.filter(|limit| *limit > 0) {
+        let since = Utc::now() - chrono::Duration::minutes(1);
+        let recent = db
+            .count_subject_requests_since(subject, x509_sha256, since, false)
+            .await?;
+        if recent >= limit as i64 {
+            return Err(MeshError::RateLimited(format!(
+                "{name} exceeded its quota of {limit} requests per minute!"
+            )));
+        }
+    }
+    if let Some(limit) = quota.max_concurrent_requests.filter(|limit| *limit > 0) {
+        let since = Utc::now() - quota_running_window();
+        let running = db
+            .count_subject_requests_since(subject, x509_sha256, since, true)
+            .await?;
+        if running >= limit as i64 {
+            return Err(MeshError::RateLimited(format!(
+                "{name} exceeded its quota of {limit} concurrently running requests!"
+            )));
+        }
+    }
+    Ok(())
 }
 
 pub async fn create_planning_context(
@@ -47,11 +210,11 @@ pub async fn create_planning_context(
     Ok(context_provider)
 }
 
-/// Converts a Vec of [Information] to an arrow [SchemaRef]
+/// Converts a Vec of [Information] to an arrow [SchemaRef], keeping the order of the Vec
 pub fn information_to_schema(information: Vec<Information>) -> SchemaRef {
     let mut schema_builder = SchemaBuilder::new();
     for info in information {
-        schema_builder.push(Field::new(info.name, info.arrow_dtype.inner, true));
+        schema_builder.push(Field::new(info.name, info.arrow_dtype.inner, info.nullable));
     }
     Arc::new(schema_builder.finish())
 }
@@ -76,16 +239,15 @@ pub async fn verify_query_origination_information(
         (Some(originator), Some(requesting_user), Some(_), Some(_)) => {
             // If originating relay is set, the request must not come directly from a user. but rather another relay
             // thus the client certificate fingerprint should match a trusted relay, or otherwise we reject the request
-            let requesting_relay = Requester::Relay(
-                db.get_relay_by_x509_fingerprint(&fingerprint)
-                    .await
-                    .map_err(|e| {
+            let requesting_relay =
+                Requester::Relay(identity_cache().get_relay(db, &fingerprint).await.map_err(
+                    |e| {
                         MeshError::DbError(format!(
                             "Rejecting query request from unrecognized \
                  relay with fingerprint {fingerprint} and dn: {subject_dn}, {e}"
                         ))
-                    })?,
-            );
+                    },
+                )?);
             (
                 requesting_relay,
                 requesting_user.clone(),
@@ -96,15 +258,23 @@ pub async fn verify_query_origination_information(
             // If originating relay is not set, the request must come directly from a user. The local relay is the originating
             // relay.
             debug!("Checking for user with fingerprint {fingerprint}");
-            let user = NewUser {
-                x509_sha256: fingerprint.clone(),
-                x509_subject: subject_dn.clone(),
-                x509_issuer: issuer_dn,
-                attributes: UserAttributes::new(),
+            let requesting_user = match identity_cache().cached_user(&fingerprint) {
+                Some(user) => user,
+                None => {
+                    let user = NewUser {
+                        x509_sha256: fingerprint.clone(),
+                        x509_subject: subject_dn.clone(),
+                        x509_issuer: issuer_dn,
+                        attributes: UserAttributes::new(),
+                        x509_cert: None,
+                    };
+                    let user = db.upsert_user_by_fingerprint(&user).await?;
+                    identity_cache().insert_user(&user);
+                    user
+                }
             };
-            let requesting_user = db.upsert_user_by_fingerprint(&user).await?;
-            let originator = db
-                .get_relay_by_x509_fingerprint(local_fingerprint.as_ref())
+            let originator = identity_cache()
+                .get_relay(db, local_fingerprint.as_ref())
                 .await?;
             (
                 Requester::User(requesting_user.clone()),
@@ -124,15 +294,32 @@ pub async fn verify_query_origination_information(
 }
 
 /// Helper function that creates a [QueryRequest], filling in origination information
-/// as appropriate depending on the [Requester]
+/// as appropriate depending on the [Requester]. The trace_context of the call which received the
+/// request is stored, so that its remote tasks continue the same trace.
 pub async fn create_query_request(
     query: &RawQueryRequest,
     db: &mut PgDb<'_>,
     direct_requester: &Requester,
     requesting_user: &User,
     originating_relay: &Relay,
+    trace_context: &TraceContext,
 ) -> Result<QueryRequest> {
     let local_req_id = Uuid::new_v4();
+    let trace_context = Some(trace_context).filter(|c| !c.fields.is_empty());
+    let hints = QueryHints::parse(query)?;
+    let result_ttl_secs = Some(
+        hints
+            .result_ttl_secs
+            .unwrap_or_else(default_result_ttl_secs),
+    )
+    .filter(|ttl| *ttl > 0);
+    let result_format = hints.result_format.unwrap_or_else(default_result_format);
+    // The statements of a batch are stored apart, and joined into sql for display
+    let sql = if query.statements.is_empty() {
+        query.sql.clone()
+    } else {
+        query.statements.join(";\n")
+    };
     match &direct_requester {
         Requester::Relay(requesting_relay) => {
             let origin_info = QueryOriginationInfo {
@@ -150,8 +337,13 @@ pub async fn create_query_request(
                     &local_req_id,
                     &requesting_relay.id,
                     orig_req_id,
-                    &query.sql,
+                    &sql,
+                    &query.statements,
                     &origin_info,
+                    query.reexecution_of.as_ref(),
+                    trace_context,
+                    result_ttl_secs,
+                    result_format,
                 )
                 .await?)
         }
@@ -161,14 +353,40 @@ pub async fn create_query_request(
                 origin_relay: None,
                 origin_task_id: None,
             };
+            // Executing an earlier request again requires that it exists and belongs to the same
+            // user. Both cases give the same response to prevent brute forcing valid Uuids.
+            let reexecution_of =
+                match &query.reexecution_of {
+                    Some(earlier_id) => {
+                        let earlier =
+                            db.check_if_request_already_received(earlier_id, None)
+                                .await
+                                .ok()
+                                .filter(|earlier| {
+                                    earlier.origin_info.origin_user.as_ref().is_some_and(|u| {
+                                        u.x509_sha256 == requesting_user.x509_sha256
+                                    })
+                                })
+                                .ok_or(MeshError::InvalidQuery(format!(
+                                    "No request exists with id {earlier_id} to execute again"
+                                )))?;
+                        Some(earlier.originator_request_id)
+                    }
+                    None => None,
+                };
             // We are the origin so we set the origin id to local id
             Ok(db
                 .create_query_request(
                     &local_req_id,
                     &originating_relay.id,
                     &local_req_id,
-                    &query.sql,
+                    &sql,
+                    &query.statements,
                     &origin_info,
+                    reexecution_of.as_ref(),
+                    trace_context,
+                    result_ttl_secs,
+                    result_format,
                 )
                 .await?)
         }
@@ -177,6 +395,10 @@ pub async fn create_query_request(
 
 /// Helper function that maps a [RawQueryRequest] to [Querys][crate::model::query::Query] for all relevant local
 /// data sources and stores the needed info in the database as [QueryTasks][crate::model::query::QueryTask].
+/// If outbox is set, a message dispatching each task to the QueryRunner is written to the task outbox in the
+/// same transaction, see [publish_outbox][crate::execute::outbox::publish_outbox]. The tasks execute the statement
+/// of the request at statement_index, see [RawQueryRequest::statements].
+#[allow(clippy::too_many_arguments)]
 pub async fn map_and_create_local_tasks(
     query: &Statement,
     raw_request: &RawQueryRequest,
@@ -185,6 +407,8 @@ pub async fn map_and_create_local_tasks(
     db: &mut PgDb<'_>,
     direct_requester: &Requester,
     requesting_user: &User,
+    outbox: bool,
+    statement_index: i32,
 ) -> Result<Vec<QueryTask>> {
     let queries = request_to_local_queries(
         db,
@@ -193,24 +417,78 @@ pub async fn map_and_create_local_tasks(
         raw_request,
         direct_requester,
         requesting_user,
+        None,
     )
     .await?;
     debug!("Creating {} local tasks!", queries.len());
     let mut tasks = Vec::with_capacity(queries.len());
-    for (data_source_id, q) in queries {
+    for q in queries {
         tasks.push(NewQueryTask {
             query_request_id: request.id,
-            data_source_id,
-            task: q,
+            data_source_id: q.data_source_id,
+            task: q.query,
             status: QueryTaskStatus::Queued,
+            not_before: q.not_before,
+            engine: q.engine,
+            permission: Some(q.permission),
+            statement_index,
         })
     }
 
-    db.create_query_tasks(&tasks).await
+    if outbox {
+        db.create_query_tasks_with_outbox(&tasks).await
+    } else {
+        db.create_query_tasks(&tasks).await
+    }
+}
+
+/// Estimates when the newly created local tasks start and complete, from the current queue depth
+/// and recent latency of their [DataSource][crate::model::data_stores::DataSource]s. Deferred
+/// tasks start no earlier than their execution window. Each estimate is None if no task has a
+/// basis for it.
+pub async fn estimate_task_timing(
+    tasks: &[QueryTask],
+    db: &mut PgDb<'_>,
+    now: DateTime<Utc>,
+) -> Result<(Option<DateTime<Utc>>, Option<DateTime<Utc>>)> {
+    let mut own: HashMap<Uuid, i64> = HashMap::new();
+    for task in tasks.iter().filter(|t| t.not_before.is_none()) {
+        *own.entry(task.data_source_id).or_default() += 1;
+    }
+    let source_ids: Vec<Uuid> = own.keys().copied().collect();
+    let stats = if source_ids.is_empty() {
+        vec![]
+    } else {
+        db.get_source_queue_stats(Some(&source_ids)).await?
+    };
+
+    let mut estimated_start = tasks.iter().filter_map(|t| t.not_before).max();
+    let mut estimated_completion = None;
+    for source in stats {
+        let count = own.get(&source.data_source_id).copied().unwrap_or_default();
+        if let Some((start, completion)) = source.estimate(now, count) {
+            estimated_start = estimated_start.max(Some(start));
+            estimated_completion = estimated_completion.max(Some(completion));
+        }
+    }
+    Ok((estimated_start, estimated_completion))
+}
+
+/// Returns the request_uuid under which the statement at statement_index of a batch request is
+/// forwarded to peered relays. Each statement is forwarded as a request of its own, so all but
+/// the first need an id of their own which peers deduplicate on. The id is derived from the
+/// request id, so that every relay derives the same id for the same statement.
+pub fn statement_request_uuid(request_uuid: &Uuid, statement_index: i32) -> Uuid {
+    match statement_index {
+        0 => *request_uuid,
+        i => Uuid::new_v5(request_uuid, &i.to_be_bytes()),
+    }
 }
 
 /// Helper function that maps a [RawQueryRequest] to [Querys][crate::model::query::Query] for all relevant local
 /// data sources and stores the needed info in the database as [RemoteQueryTasks][crate::model::query::Query].
+/// A message dispatching each task to the QueryRunner is written to the task outbox in the same transaction.
+#[allow(clippy::too_many_arguments)]
 pub async fn map_and_create_remote_tasks(
     raw_request: &RawQueryRequest,
     query: &Statement,
@@ -219,13 +497,14 @@ pub async fn map_and_create_remote_tasks(
     db: &mut PgDb<'_>,
     requesting_user: User,
     originating_relay: Relay,
+    statement_index: i32,
 ) -> Result<Vec<QueryTaskRemote>> {
     let remote_requests = request_to_remote_requests(
         db,
         raw_request,
         query,
         entity_name,
-        &request.originator_request_id,
+        &statement_request_uuid(&request.originator_request_id, statement_index),
         originating_relay,
         requesting_user,
     )
@@ -245,8 +524,45 @@ pub async fn map_and_create_remote_tasks(
             relay_id,
             task: remote_request,
             status: QueryTaskRemoteStatus::Queued,
+            attempts: 0,
+            error: None,
+            statement_index,
+            dispatched_at: None,
+            not_before: None,
         })
     }
     debug!("Creating {} remote tasks!", remote_tasks.len());
-    db.create_remote_query_tasks(&remote_tasks).await
+    db.create_remote_query_tasks_with_outbox(&remote_tasks)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::model::user::{ServiceAccountScope, User, UserAttributes};
+
+    use super::check_service_account_entity;
+
+    #[test]
+    fn test_check_service_account_entity() {
+        let mut user = User {
+            id: Uuid::new_v4(),
+            x509_sha256: "fingerprint".to_string(),
+            x509_subject: "CN=nightly-export".to_string(),
+            x509_issuer: "CN=ca".to_string(),
+            attributes: UserAttributes::new(),
+        };
+        assert!(check_service_account_entity(&user, "customer").is_ok());
+
+        user.attributes = UserAttributes::new()
+            .with_is_admin(true)
+            .with_service_account(ServiceAccountScope {
+                allowed_entities: vec!["order".to_string()],
+                max_requests_per_minute: Some(10),
+            });
+        assert!(!user.attributes.is_admin);
+        assert!(check_service_account_entity(&user, "order").is_ok());
+        assert!(check_service_account_entity(&user, "customer").is_err());
+    }
 }
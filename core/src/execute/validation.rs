@@ -1,14 +1,18 @@
+use std::ops::ControlFlow;
+
 use crate::error::Result;
 
 use crate::error::MeshError;
+use crate::model::validation::{SqlConstruct, ValidationRules};
 
 use arrow_schema::Schema;
 
 use datafusion::sql::planner::SqlToRel;
 use datafusion::sql::sqlparser::ast::TopQuantity;
 use datafusion::sql::sqlparser::ast::{
-    visit_relations, Distinct, Expr, FunctionArg, FunctionArgExpr, GroupByExpr, ListAggOnOverflow,
-    Select, SelectItem, SetExpr, Statement, TableFactor, WindowFrameBound, WindowSpec, WindowType,
+    visit_expressions_mut, visit_relations, visit_relations_mut, Distinct, Expr, FunctionArg,
+    FunctionArgExpr, GroupByExpr, Ident, ListAggOnOverflow, ObjectName, Query, Select, SelectItem,
+    SetExpr, Statement, TableFactor, Visit, Visitor, WindowFrameBound, WindowSpec, WindowType,
 };
 use datafusion::sql::sqlparser::dialect::GenericDialect;
 
@@ -17,6 +21,7 @@ use datafusion::sql::unparser::plan_to_sql;
 use tracing::debug;
 
 use super::planning::EntityContext;
+use super::visit_query_mut;
 
 static MAX_QUERY_LENGTH: usize = 1_000_000;
 
@@ -58,6 +63,125 @@ pub fn validate_sql(sql: &str) -> Result<(String, Statement)> {
     Ok((entity, statement))
 }
 
+/// Checks a [Statement] which passed [validate_sql] against the configurable [ValidationRules].
+pub fn check_validation_rules(
+    sql: &str,
+    statement: &Statement,
+    rules: &ValidationRules,
+) -> Result<()> {
+    if let Some(max_length) = rules.max_query_length {
+        if sql.len() > max_length {
+            return Err(MeshError::InvalidQuery(format!(
+                "SQL string exceeds maximum length of {max_length} characters!"
+            )));
+        }
+    }
+
+    let mut visitor = RuleVisitor { rules, joins: 0 };
+    if let ControlFlow::Break(e) = statement.visit(&mut visitor) {
+        return Err(e);
+    }
+
+    if let Some(max_joins) = rules.max_joins {
+        if visitor.joins > max_joins {
+            return Err(MeshError::InvalidQuery(format!(
+                "Query contains {} joins, but at most {max_joins} are allowed!",
+                visitor.joins
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Walks a [Statement], breaking on the first violation of the [ValidationRules] and counting
+/// joins along the way.
+struct RuleVisitor<'a> {
+    rules: &'a ValidationRules,
+    joins: usize,
+}
+
+impl RuleVisitor<'_> {
+    fn check_construct(&self, construct: SqlConstruct) -> ControlFlow<MeshError> {
+        if self.rules.banned_constructs.contains(&construct) {
+            return ControlFlow::Break(MeshError::InvalidQuery(format!(
+                "{construct:?} is not allowed by the validation rules!"
+            )));
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Nested queries are visited separately, so only the selects of this body are inspected.
+    fn check_body(&mut self, body: &SetExpr) -> ControlFlow<MeshError> {
+        match body {
+            SetExpr::Select(select) => {
+                // FROM a, b is an implicit cross join
+                self.joins += select.from.len().saturating_sub(1);
+                self.joins += select.from.iter().map(|t| t.joins.len()).sum::<usize>();
+                if select.distinct.is_some() {
+                    self.check_construct(SqlConstruct::Distinct)?;
+                }
+                if select.projection.iter().any(|item| {
+                    matches!(
+                        item,
+                        SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(_, _)
+                    )
+                }) {
+                    self.check_construct(SqlConstruct::Wildcard)?;
+                }
+            }
+            SetExpr::SetOperation { left, right, .. } => {
+                self.check_construct(SqlConstruct::SetOperation)?;
+                self.check_body(left)?;
+                self.check_body(right)?;
+            }
+            _ => (),
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+impl Visitor for RuleVisitor<'_> {
+    type Break = MeshError;
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<Self::Break> {
+        if query.with.is_some() {
+            self.check_construct(SqlConstruct::Cte)?;
+        }
+        self.check_body(&query.body)
+    }
+
+    fn pre_visit_table_factor(&mut self, table_factor: &TableFactor) -> ControlFlow<Self::Break> {
+        match table_factor {
+            TableFactor::Derived { .. } => self.check_construct(SqlConstruct::Subquery),
+            _ => ControlFlow::Continue(()),
+        }
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<Self::Break> {
+        match expr {
+            Expr::Subquery(_)
+            | Expr::Exists { .. }
+            | Expr::InSubquery { .. }
+            | Expr::ArraySubquery(_) => self.check_construct(SqlConstruct::Subquery),
+            Expr::Function(fun) => {
+                if fun.over.is_some() {
+                    self.check_construct(SqlConstruct::Window)?;
+                }
+                if let Some(allowed) = &self.rules.allowed_functions {
+                    let name = fun.name.to_string();
+                    if !allowed.iter().any(|f| f.eq_ignore_ascii_case(&name)) {
+                        return ControlFlow::Break(MeshError::InvalidQuery(format!(
+                            "Function {name} is not allowed by the validation rules!"
+                        )));
+                    }
+                }
+                ControlFlow::Continue(())
+            }
+            _ => ControlFlow::Continue(()),
+        }
+    }
+}
+
 /// Uses datafusion to logically plan and optimize the [Statement], ultimately converting back to
 /// a [Statement] which has alias names resolved, columns fully qualified, expressions simplified and more.
 pub fn logical_round_trip(
@@ -72,12 +196,27 @@ pub fn logical_round_trip(
     Ok((statement, schema))
 }
 
+/// Normalizes a possibly multi part name the same way as logical planning does, i.e. quoted names
+/// keep their case while unquoted names are lowercased.
+fn normalize_name(idents: &[Ident]) -> String {
+    idents
+        .iter()
+        .map(|ident| match ident.quote_style {
+            Some(_) => ident.value.clone(),
+            None => ident.value.to_lowercase(),
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
 /// Each [Statement] should only reference a single Entity. Verifies this is the case
 /// and returns the name of that Entity.
+///
+/// Names are normalized as by [normalize_name].
 fn get_entity_for_statement(statement: &Statement) -> Result<String> {
     let mut entities = vec![];
-    visit_relations(statement, |relation| {
-        let entity = relation.to_string();
+    let _ = visit_relations(statement, |relation| {
+        let entity = normalize_name(&relation.0);
         if !entities.contains(&entity) {
             entities.push(entity);
         }
@@ -93,6 +232,44 @@ fn get_entity_for_statement(statement: &Statement) -> Result<String> {
     Ok(entities.remove(0))
 }
 
+/// Rewrites a [Statement] which passed [validate_sql] for the Entity named from to refer to the
+/// Entity named to instead, i.e. the relation as well as identifiers and wildcards qualified with
+/// it. Used to resolve an alias of an Entity to its name.
+pub fn rename_entity(statement: &mut Statement, from: &str, to: &str) {
+    let to_ident = if to == to.to_lowercase() {
+        Ident::new(to)
+    } else {
+        Ident::with_quote('"', to)
+    };
+    let _ = visit_relations_mut(statement, |relation| {
+        if normalize_name(&relation.0) == from {
+            *relation = ObjectName(vec![to_ident.clone()]);
+        }
+        ControlFlow::<()>::Continue(())
+    });
+    let _ = visit_expressions_mut(statement, |expr| {
+        if let Expr::CompoundIdentifier(idents) = expr {
+            let qualifier_len = idents.len() - 1;
+            if qualifier_len > 0 && normalize_name(&idents[..qualifier_len]) == from {
+                idents.splice(..qualifier_len, [to_ident.clone()]);
+            }
+        }
+        ControlFlow::<()>::Continue(())
+    });
+    let _ = visit_query_mut(statement, |query| {
+        if let SetExpr::Select(select) = query.body.as_mut() {
+            for item in select.projection.iter_mut() {
+                if let SelectItem::QualifiedWildcard(name, _) = item {
+                    if normalize_name(&name.0) == from {
+                        *name = ObjectName(vec![to_ident.clone()]);
+                    }
+                }
+            }
+        }
+        ControlFlow::<()>::Continue(())
+    });
+}
+
 fn validate_expr(expr: &Expr) -> Result<()> {
     match expr {
         Expr::Identifier(_) => (),
@@ -570,11 +747,19 @@ fn validate_query_statement(query: &datafusion::sql::sqlparser::ast::Query) -> R
 #[cfg(test)]
 mod tests {
 
-    use arrow_schema::Schema;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use arrow_schema::{DataType, Field, Schema};
 
     use crate::error::Result;
-    use crate::execute::validation::validate_sql;
+    use crate::execute::parse_utils::apply_aliases;
+    use crate::execute::planning::EntityContext;
+    use crate::execute::validation::{
+        check_validation_rules, logical_round_trip, rename_entity, validate_sql,
+    };
     use crate::model::query::RawQueryRequest;
+    use crate::model::validation::{SqlConstruct, ValidationRules};
 
     #[test]
     fn insert_into_test() -> Result<()> {
@@ -582,11 +767,17 @@ mod tests {
 
         let raw_request = RawQueryRequest {
             sql,
+            statements: vec![],
             request_uuid: None,
             requesting_user: None,
             originating_relay: None,
             originating_task_id: None,
             return_arrow_schema: Some(Schema::empty()),
+            engine_hint: None,
+            count_only: false,
+            interactive: false,
+            reexecution_of: None,
+            hints: HashMap::new(),
         };
 
         let err_msg = validate_sql(&raw_request.sql)
@@ -604,11 +795,17 @@ mod tests {
 
         let raw_request = RawQueryRequest {
             sql,
+            statements: vec![],
             request_uuid: None,
             requesting_user: None,
             originating_relay: None,
             originating_task_id: None,
             return_arrow_schema: Some(Schema::empty()),
+            engine_hint: None,
+            count_only: false,
+            interactive: false,
+            reexecution_of: None,
+            hints: HashMap::new(),
         };
 
         let err_msg = validate_sql(&raw_request.sql)
@@ -632,11 +829,17 @@ mod tests {
 
         let raw_request = RawQueryRequest {
             sql,
+            statements: vec![],
             request_uuid: None,
             requesting_user: None,
             originating_relay: None,
             originating_task_id: None,
             return_arrow_schema: Some(Schema::empty()),
+            engine_hint: None,
+            count_only: false,
+            interactive: false,
+            reexecution_of: None,
+            hints: HashMap::new(),
         };
 
         let err_msg = validate_sql(&raw_request.sql)
@@ -650,4 +853,82 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn mixed_case_names_test() -> Result<()> {
+        let (entity, _) = validate_sql("select x from Orders")?;
+        assert_eq!(entity, "orders");
+
+        let (entity, statement) =
+            validate_sql(r#"select "Value" from "SalesOrders" where "Value" > 1"#)?;
+        assert_eq!(entity, "SalesOrders");
+
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "Value",
+            DataType::Int64,
+            true,
+        )]));
+        let context = EntityContext::new(&entity, schema);
+        let (mut statement, schema) = logical_round_trip(statement, context)?;
+        assert_eq!(schema.field(0).name(), "Value");
+
+        apply_aliases(&mut statement, &entity)?;
+        assert_eq!(
+            statement.to_string(),
+            r#"SELECT "SalesOrders"."Value" AS "Value" FROM "SalesOrders" WHERE ("SalesOrders"."Value" > 1)"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rename_entity_test() -> Result<()> {
+        let (entity, mut statement) = validate_sql(
+            "select old_orders.x, old_orders.* from Old_Orders where old_orders.y > 1 and z = 2",
+        )?;
+        assert_eq!(entity, "old_orders");
+        rename_entity(&mut statement, &entity, "SalesOrders");
+        assert_eq!(
+            statement.to_string(),
+            r#"SELECT "SalesOrders".x, "SalesOrders".* FROM "SalesOrders" WHERE "SalesOrders".y > 1 AND z = 2"#
+        );
+        let (entity, _) = validate_sql(&statement.to_string())?;
+        assert_eq!(entity, "SalesOrders");
+        Ok(())
+    }
+
+    #[test]
+    fn validation_rules_test() -> Result<()> {
+        let rules: ValidationRules = serde_json::from_str(
+            r#"{"max_joins": 1, "allowed_functions": ["SUM", "lower"],
+            "banned_constructs": ["cte", "window"]}"#,
+        )?;
+        assert_eq!(rules.banned_constructs[1], SqlConstruct::Window);
+        let check = |sql: &str| {
+            let (_, statement) = validate_sql(sql)?;
+            check_validation_rules(sql, &statement, &rules)
+        };
+
+        check("select sum(a), lower(b) from t join t as t2 on t.a = t2.a group by b")?;
+        let err = |sql: &str| check(sql).expect_err("Query should have failed validation!");
+        assert_eq!(
+            err("select upper(b) from t").to_string(),
+            "invalid query: Function upper is not allowed by the validation rules!"
+        );
+        assert_eq!(
+            err("select a from t, t as t2 join t as t3 on t2.a = t3.a").to_string(),
+            "invalid query: Query contains 2 joins, but at most 1 are allowed!"
+        );
+        // Joins in subqueries count towards the same limit
+        assert!(check("select a from t where b in (select b from t as t2, t as t3)").is_ok());
+        err("select a from t join t as t1 on t.a = t1.a where b in (select b from t as t2, t as t3)");
+        assert_eq!(
+            err("with t as (select a from t) select a from t").to_string(),
+            "invalid query: Cte is not allowed by the validation rules!"
+        );
+        assert_eq!(
+            err("select sum(a) over (partition by b) from t").to_string(),
+            "invalid query: Window is not allowed by the validation rules!"
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,301 @@
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tokio_postgres::{AsyncMessage, Client, NoTls};
+use tracing::error;
+
+use crate::error::{MeshError, Result};
+
+use super::{GenericMessage, MessageConsumer, MessageProducer};
+
+/// Channel which an insert into the task_queue table notifies, see the task_queue migration.
+const NOTIFY_CHANNEL: &str = "task_queue";
+
+/// Leases the oldest message which is not leased by another consumer. SKIP LOCKED lets
+/// concurrent consumers each lease a different message without waiting on one another.
+const LEASE_MESSAGE: &str = "UPDATE task_queue
+    SET locked_until = now() + make_interval(secs => $1)
+    WHERE id = (
+        SELECT id FROM task_queue
+        WHERE locked_until IS NULL OR locked_until < now()
+        ORDER BY id
+        FOR UPDATE SKIP LOCKED
+        LIMIT 1
+    )
+    RETURNING id, message";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseQueueOptions {
+    /// Postgres connection url of the queue. Defaults to DATABASE_URL, so that the relay's own
+    /// database serves as the queue.
+    pub db_url: Option<String>,
+    /// How long a message is leased to the consumer which received it. A message which is not
+    /// acked within the lease, e.g. because its consumer died, is delivered again. Should be
+    /// longer than the longest running query task.
+    #[serde(default = "default_lease_ms")]
+    pub lease_ms: u64,
+    /// How often consumers look for messages when not notified of any, which picks up messages
+    /// whose lease expired.
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+fn default_lease_ms() -> u64 {
+    30 * 60 * 1000
+}
+
+fn default_poll_interval_ms() -> u64 {
+    5000
+}
+
+fn queue_err(e: tokio_postgres::Error) -> MeshError {
+    MeshError::Messaging(format!("Database task queue error: {e}"))
+}
+
+/// Connects to the queue database, waking up notified whenever the connection receives a
+/// notification.
+async fn connect(options: &DatabaseQueueOptions, notified: Option<Arc<Notify>>) -> Result<Client> {
+    let db_url = match &options.db_url {
+        Some(url) => url.clone(),
+        None => env::var("DATABASE_URL").map_err(|_| {
+            MeshError::Messaging(
+                "Database task queue requires db_url or DATABASE_URL to be set!".to_string(),
+            )
+        })?,
+    };
+    let (client, mut connection) = tokio_postgres::connect(&db_url, NoTls)
+        .await
+        .map_err(queue_err)?;
+    tokio::spawn(async move {
+        let mut messages = futures::stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            match message {
+                Ok(AsyncMessage::Notification(_)) => {
+                    if let Some(notified) = &notified {
+                        notified.notify_one();
+                    }
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    error!("Database task queue connection error: {e}");
+                    break;
+                }
+            }
+        }
+    });
+    Ok(client)
+}
+
+/// Consumes messages from the task_queue table, which query runners compete for. Receiving a
+/// message leases it, and acking it deletes it. The consumer LISTENs for inserts, so it does not
+/// need to poll for new messages.
+pub struct DatabaseConsumer {
+    client: Client,
+    notified: Arc<Notify>,
+    lease: Duration,
+    poll_interval: Duration,
+}
+
+impl DatabaseConsumer {
+    pub async fn initialize(options: &DatabaseQueueOptions) -> Result<Self> {
+        let notified = Arc::new(Notify::new());
+        let client = connect(options, Some(notified.clone())).await?;
+        client
+            .batch_execute(&format!("LISTEN {NOTIFY_CHANNEL}"))
+            .await
+            .map_err(queue_err)?;
+        Ok(Self {
+            client,
+            notified,
+            lease: Duration::from_millis(options.lease_ms),
+            poll_interval: Duration::from_millis(options.poll_interval_ms),
+        })
+    }
+
+    async fn lease_message(&mut self) -> Result<Option<(u64, GenericMessage)>> {
+        let row = self
+            .client
+            .query_opt(LEASE_MESSAGE, &[&self.lease.as_secs_f64()])
+            .await
+            .map_err(queue_err)?;
+        let row = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        let msg_id = row.get::<_, i64>("id") as u64;
+        let msg: GenericMessage = serde_json::from_value(row.get("message"))
+            .map_err(|e| MeshError::BadMessage((msg_id, e.to_string())))?;
+        Ok(Some((msg_id, msg)))
+    }
+}
+
+#[async_trait]
+impl MessageConsumer for DatabaseConsumer {
+    async fn receive_message(&mut self) -> Result<(u64, GenericMessage)> {
+        loop {
+            if let Some(received) = self.lease_message().await? {
+                return Ok(received);
+            }
+            // A notification which arrived since the last lease attempt wakes this up at once
+            let _ = tokio::time::timeout(self.poll_interval, self.notified.notified()).await;
+        }
+    }
+
+    fn try_receive_message(&mut self) -> Result<(u64, GenericMessage)> {
+        Err(MeshError::NotImplemented(
+            "The database task queue cannot be read without awaiting, use receive_message"
+                .to_string(),
+        ))
+    }
+
+    async fn ack_message(&mut self, message_id: u64) -> Result<()> {
+        self.client
+            .execute(
+                "DELETE FROM task_queue WHERE id = $1",
+                &[&(message_id as i64)],
+            )
+            .await
+            .map_err(queue_err)?;
+        Ok(())
+    }
+}
+
+pub struct DatabaseProducer {
+    client: Client,
+}
+
+impl DatabaseProducer {
+    pub async fn initialize(options: &DatabaseQueueOptions) -> Result<Self> {
+        Ok(Self {
+            client: connect(options, None).await?,
+        })
+    }
+}
+
+#[async_trait]
+impl MessageProducer for DatabaseProducer {
+    async fn send_message(&mut self, msg: &GenericMessage) -> Result<()> {
+        let message = serde_json::to_value(msg)?;
+        self.client
+            .execute("INSERT INTO task_queue (message) VALUES ($1)", &[&message])
+            .await
+            .map_err(queue_err)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use crate::crud::test_utils::test_db;
+    use crate::messaging::{GenericMessage, MessageConsumer, MessageProducer, QueryTaskMessage};
+
+    use super::{DatabaseConsumer, DatabaseProducer, DatabaseQueueOptions};
+
+    fn options(url: &str, lease_ms: u64) -> DatabaseQueueOptions {
+        DatabaseQueueOptions {
+            db_url: Some(url.to_string()),
+            lease_ms,
+            poll_interval_ms: 60_000,
+        }
+    }
+
+    fn message() -> GenericMessage {
+        GenericMessage::LocalQueryTask(QueryTaskMessage { id: Uuid::new_v4() })
+    }
+
+    async fn leased_task_id(consumer: &mut DatabaseConsumer) -> Option<Uuid> {
+        consumer
+            .lease_message()
+            .await
+            .unwrap()
+            .map(|(_, msg)| msg.task_id())
+    }
+
+    #[tokio::test]
+    async fn test_consumers_lease_different_messages() {
+        let Some(test_db) = test_db().await else {
+            return;
+        };
+        let mut producer = DatabaseProducer::initialize(&options(&test_db.url, 0))
+            .await
+            .unwrap();
+        let mut consumer = DatabaseConsumer::initialize(&options(&test_db.url, 3_600_000))
+            .await
+            .unwrap();
+        let mut other_consumer = DatabaseConsumer::initialize(&options(&test_db.url, 3_600_000))
+            .await
+            .unwrap();
+        let (first, second) = (message(), message());
+        producer.send_message(&first).await.unwrap();
+        producer.send_message(&second).await.unwrap();
+
+        let (first_id, received) = consumer.receive_message().await.unwrap();
+        assert_eq!(received.task_id(), first.task_id());
+        let (second_id, received) = other_consumer.receive_message().await.unwrap();
+        assert_eq!(received.task_id(), second.task_id());
+        assert_eq!(leased_task_id(&mut consumer).await, None);
+
+        // Acked messages are deleted, and are not delivered again once their lease expired
+        consumer.ack_message(first_id).await.unwrap();
+        other_consumer.ack_message(second_id).await.unwrap();
+        let mut expiring_consumer = DatabaseConsumer::initialize(&options(&test_db.url, 0))
+            .await
+            .unwrap();
+        assert_eq!(leased_task_id(&mut expiring_consumer).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_unacked_message_is_redelivered_once_its_lease_expired() {
+        let Some(test_db) = test_db().await else {
+            return;
+        };
+        let mut producer = DatabaseProducer::initialize(&options(&test_db.url, 0))
+            .await
+            .unwrap();
+        let mut consumer = DatabaseConsumer::initialize(&options(&test_db.url, 0))
+            .await
+            .unwrap();
+        let msg = message();
+        producer.send_message(&msg).await.unwrap();
+
+        assert_eq!(leased_task_id(&mut consumer).await, Some(msg.task_id()));
+        assert_eq!(leased_task_id(&mut consumer).await, Some(msg.task_id()));
+    }
+
+    #[tokio::test]
+    async fn test_consumer_is_notified_of_new_messages() {
+        let Some(test_db) = test_db().await else {
+            return;
+        };
+        let mut producer = DatabaseProducer::initialize(&options(&test_db.url, 0))
+            .await
+            .unwrap();
+        let mut consumer = DatabaseConsumer::initialize(&options(&test_db.url, 3_600_000))
+            .await
+            .unwrap();
+        let msg = message();
+        let task_id = msg.task_id();
+        let sent = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            producer.send_message(&msg).await.unwrap();
+        });
+
+        // Much shorter than the poll interval, so the message must be received via NOTIFY
+        let (_, received) =
+            tokio::time::timeout(Duration::from_secs(10), consumer.receive_message())
+                .await
+                .expect("consumer was not notified of the new message")
+                .unwrap();
+        assert_eq!(received.task_id(), task_id);
+        sent.await.unwrap();
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::FutureExt;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::error::KafkaError;
+use rdkafka::message::{BorrowedMessage, Message};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::{Offset, TopicPartitionList};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{MeshError, Result};
+
+use super::{GenericMessage, MessageConsumer, MessageProducer};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KafkaConnectionOptions {
+    /// Comma separated host:port pairs of the brokers
+    pub bootstrap_servers: String,
+    /// The topic which query task messages are sent to
+    pub topic: String,
+    /// Consumer group shared by all query runners, so that each message is consumed by one of
+    /// them.
+    #[serde(default = "default_group_id")]
+    pub group_id: String,
+    /// Additional librdkafka configuration, e.g. security.protocol or sasl.mechanisms
+    #[serde(default)]
+    pub config: HashMap<String, String>,
+    /// How long sending a message may wait for the broker to acknowledge it
+    #[serde(default = "default_send_timeout_ms")]
+    pub send_timeout_ms: u64,
+}
+
+fn default_group_id() -> String {
+    "query_runner".to_string()
+}
+
+fn default_send_timeout_ms() -> u64 {
+    5000
+}
+
+impl From<KafkaError> for MeshError {
+    fn from(value: KafkaError) -> Self {
+        MeshError::Messaging(format!("Kafka error: {value}"))
+    }
+}
+
+fn client_config(options: &KafkaConnectionOptions) -> ClientConfig {
+    let mut config = ClientConfig::new();
+    config.set("bootstrap.servers", &options.bootstrap_servers);
+    for (key, value) in options.config.iter() {
+        config.set(key, value);
+    }
+    config
+}
+
+/// Offsets of the messages received from a partition which are not yet acked.
+#[derive(Default)]
+struct PartitionOffsets {
+    pending: BTreeSet<i64>,
+    /// Offset following the last message received.
+    next: i64,
+    /// Offset last committed.
+    committed: i64,
+}
+
+impl PartitionOffsets {
+    /// Returns the offset to commit after acking offset, if it advanced. Offsets are committed
+    /// only up to the first message which is not yet acked, so that a message is never lost if
+    /// a later message of the same partition is acked first.
+    fn ack(&mut self, offset: i64) -> Option<i64> {
+        self.pending.remove(&offset);
+        let commit = self.pending.first().copied().unwrap_or(self.next);
+        if commit > self.committed {
+            self.committed = commit;
+            Some(commit)
+        } else {
+            None
+        }
+    }
+}
+
+/// Consumes messages as a member of a consumer group, so query runners compete for messages.
+/// Acking a message commits its offset, so unacked messages are redelivered to another member of
+/// the group if this consumer dies.
+pub struct KafkaConsumer {
+    consumer: StreamConsumer,
+    topic: String,
+    /// Maps the message ids handed out to the (partition, offset) of the message.
+    in_flight: HashMap<u64, (i32, i64)>,
+    partitions: HashMap<i32, PartitionOffsets>,
+    next_id: u64,
+}
+
+impl KafkaConsumer {
+    pub fn initialize(options: &KafkaConnectionOptions) -> Result<Self> {
+        let consumer: StreamConsumer = client_config(options)
+            .set("group.id", &options.group_id)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest")
+            .create()?;
+        consumer.subscribe(&[&options.topic])?;
+        Ok(Self {
+            consumer,
+            topic: options.topic.clone(),
+            in_flight: HashMap::new(),
+            partitions: HashMap::new(),
+            next_id: 0,
+        })
+    }
+}
+
+/// Returns the serialized message along with the message id it is tracked by.
+fn track(
+    partitions: &mut HashMap<i32, PartitionOffsets>,
+    in_flight: &mut HashMap<u64, (i32, i64)>,
+    next_id: &mut u64,
+    msg: &BorrowedMessage,
+) -> (u64, Option<Vec<u8>>) {
+    let (partition, offset) = (msg.partition(), msg.offset());
+    let offsets = partitions.entry(partition).or_insert(PartitionOffsets {
+        committed: offset,
+        ..Default::default()
+    });
+    offsets.pending.insert(offset);
+    offsets.next = offsets.next.max(offset + 1);
+
+    let msg_id = *next_id;
+    *next_id += 1;
+    in_flight.insert(msg_id, (partition, offset));
+    (msg_id, msg.payload().map(|p| p.to_vec()))
+}
+
+fn parse_message(msg_id: u64, payload: Option<Vec<u8>>) -> Result<(u64, GenericMessage)> {
+    let bytes = payload.ok_or(MeshError::BadMessage((
+        msg_id,
+        "Kafka message has no payload!".to_string(),
+    )))?;
+    let msg: GenericMessage = serde_json::from_slice(&bytes)
+        .map_err(|e| MeshError::BadMessage((msg_id, e.to_string())))?;
+    Ok((msg_id, msg))
+}
+
+#[async_trait]
+impl MessageConsumer for KafkaConsumer {
+    async fn receive_message(&mut self) -> Result<(u64, GenericMessage)> {
+        let (msg_id, payload) = {
+            let msg = self.consumer.recv().await?;
+            track(
+                &mut self.partitions,
+                &mut self.in_flight,
+                &mut self.next_id,
+                &msg,
+            )
+        };
+        parse_message(msg_id, payload)
+    }
+
+    fn try_receive_message(&mut self) -> Result<(u64, GenericMessage)> {
+        let (msg_id, payload) = {
+            let msg = match self.consumer.recv().now_or_never() {
+                Some(msg) => msg?,
+                None => {
+                    return Err(MeshError::Messaging(
+                        "No Kafka message is available".to_string(),
+                    ))
+                }
+            };
+            track(
+                &mut self.partitions,
+                &mut self.in_flight,
+                &mut self.next_id,
+                &msg,
+            )
+        };
+        parse_message(msg_id, payload)
+    }
+
+    async fn ack_message(&mut self, message_id: u64) -> Result<()> {
+        let (partition, offset) =
+            self.in_flight
+                .remove(&message_id)
+                .ok_or(MeshError::Messaging(format!(
+                    "Kafka message {message_id} was already acked or never received!"
+                )))?;
+        let commit = self
+            .partitions
+            .get_mut(&partition)
+            .and_then(|offsets| offsets.ack(offset));
+        if let Some(commit) = commit {
+            let mut tpl = TopicPartitionList::new();
+            tpl.add_partition_offset(&self.topic, partition, Offset::Offset(commit))?;
+            self.consumer.commit(&tpl, CommitMode::Async)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct KafkaProducer {
+    producer: FutureProducer,
+    topic: String,
+    send_timeout: Duration,
+}
+
+impl KafkaProducer {
+    pub fn initialize(options: &KafkaConnectionOptions) -> Result<Self> {
+        Ok(Self {
+            producer: client_config(options).create()?,
+            topic: options.topic.clone(),
+            send_timeout: Duration::from_millis(options.send_timeout_ms),
+        })
+    }
+}
+
+#[async_trait]
+impl MessageProducer for KafkaProducer {
+    async fn send_message(&mut self, msg: &GenericMessage) -> Result<()> {
+        let payload = serde_json::to_vec(msg)?;
+        // Keyed by task, so messages are spread over the partitions of the topic
+        let key = msg.task_id().to_string();
+        self.producer
+            .send(
+                FutureRecord::to(&self.topic).payload(&payload).key(&key),
+                self.send_timeout,
+            )
+            .await
+            .map_err(|(e, _)| MeshError::Messaging(format!("Failed to send Kafka message: {e}")))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PartitionOffsets;
+
+    #[test]
+    fn test_commit_in_order() {
+        let mut offsets = PartitionOffsets {
+            committed: 10,
+            ..Default::default()
+        };
+        for offset in 10..13 {
+            offsets.pending.insert(offset);
+            offsets.next = offset + 1;
+        }
+        // 10 is still pending, so acking 11 must not commit past it
+        assert_eq!(offsets.ack(11), None);
+        assert_eq!(offsets.ack(10), Some(12));
+        assert_eq!(offsets.ack(12), Some(13));
+    }
+}
@@ -10,10 +10,25 @@ use self::rabbitmq::{RabbitMQConnectionOptions, RabbitMQConsumer, RabbitMQProduc
 #[cfg(feature = "async-channel")]
 use self::in_memory::AsyncChannelOptions;
 
+#[cfg(feature = "db-queue")]
+use self::database::{DatabaseConsumer, DatabaseProducer, DatabaseQueueOptions};
+
+#[cfg(feature = "kafka")]
+use self::kafka::{KafkaConnectionOptions, KafkaConsumer, KafkaProducer};
+
+#[cfg(feature = "redis")]
+use self::redis::{RedisConnectionOptions, RedisConsumer, RedisProducer};
+
+#[cfg(feature = "db-queue")]
+pub mod database;
 #[cfg(feature = "async-channel")]
 pub mod in_memory;
+#[cfg(feature = "kafka")]
+pub mod kafka;
 #[cfg(feature = "rabbitmq")]
 pub mod rabbitmq;
+#[cfg(feature = "redis")]
+pub mod redis;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 /// A message about a [QueryTask][crate::model::query::QueryTask] to be executed. Full details about
@@ -29,6 +44,15 @@ pub enum GenericMessage {
     RemoteQueryTask(QueryTaskMessage),
 }
 
+impl GenericMessage {
+    /// Returns the id of the task which the message is about.
+    pub fn task_id(&self) -> Uuid {
+        match self {
+            GenericMessage::LocalQueryTask(m) | GenericMessage::RemoteQueryTask(m) => m.id,
+        }
+    }
+}
+
 #[async_trait]
 pub trait MessageConsumer: Send {
     /// Recieves a [GenericMessage] from producers suspending execution until available
@@ -59,6 +83,18 @@ pub enum MessageBrokerOptions {
     /// Options to initialize an in memory AsyncChannel [MessageConsumer] or [MessageProducer]
     /// Can only be used in single binary deployment mode
     AsyncChannel(AsyncChannelOptions),
+    #[cfg(feature = "kafka")]
+    /// Options to initialize a Kafka [MessageConsumer] or [MessageProducer]. Query runners
+    /// share a consumer group, and acking a message commits its offset.
+    Kafka(KafkaConnectionOptions),
+    #[cfg(feature = "redis")]
+    /// Options to initialize a Redis Streams [MessageConsumer] or [MessageProducer]. Query
+    /// runners share a consumer group, and acking a message XACKs its stream entry.
+    Redis(RedisConnectionOptions),
+    #[cfg(feature = "db-queue")]
+    /// Options to initialize a [MessageConsumer] or [MessageProducer] backed by a Postgres table,
+    /// which requires no infrastructure besides the relay's database.
+    Database(DatabaseQueueOptions),
 }
 
 pub async fn initialize_producer(
@@ -73,6 +109,18 @@ pub async fn initialize_producer(
         MessageBrokerOptions::AsyncChannel(channel_opts) => {
             Ok(Box::new(in_memory::initialize_producer(channel_opts)))
         }
+        #[cfg(feature = "kafka")]
+        MessageBrokerOptions::Kafka(kafka_opts) => {
+            Ok(Box::new(KafkaProducer::initialize(kafka_opts)?))
+        }
+        #[cfg(feature = "redis")]
+        MessageBrokerOptions::Redis(redis_opts) => {
+            Ok(Box::new(RedisProducer::initialize(redis_opts).await?))
+        }
+        #[cfg(feature = "db-queue")]
+        MessageBrokerOptions::Database(db_opts) => {
+            Ok(Box::new(DatabaseProducer::initialize(db_opts).await?))
+        }
     }
 }
 
@@ -88,5 +136,17 @@ pub async fn initialize_consumer(
         MessageBrokerOptions::AsyncChannel(channel_opts) => {
             Ok(Box::new(in_memory::initialize_consumer(channel_opts)))
         }
+        #[cfg(feature = "kafka")]
+        MessageBrokerOptions::Kafka(kafka_opts) => {
+            Ok(Box::new(KafkaConsumer::initialize(kafka_opts)?))
+        }
+        #[cfg(feature = "redis")]
+        MessageBrokerOptions::Redis(redis_opts) => {
+            Ok(Box::new(RedisConsumer::initialize(redis_opts).await?))
+        }
+        #[cfg(feature = "db-queue")]
+        MessageBrokerOptions::Database(db_opts) => {
+            Ok(Box::new(DatabaseConsumer::initialize(db_opts).await?))
+        }
     }
 }
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use redis::aio::MultiplexedConnection;
+use redis::streams::{
+    StreamAutoClaimOptions, StreamAutoClaimReply, StreamId, StreamReadOptions, StreamReadReply,
+};
+use redis::{AsyncCommands, Client, Commands, RedisError};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{MeshError, Result};
+
+use super::{GenericMessage, MessageConsumer, MessageProducer};
+
+/// Field of each stream entry which holds the serialized [GenericMessage].
+const PAYLOAD_FIELD: &str = "payload";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedisConnectionOptions {
+    /// Connection url of the server, e.g. redis://redis:6379
+    pub url: String,
+    /// The stream which query task messages are added to
+    pub stream: String,
+    /// Consumer group shared by all query runners, so that each message is consumed by one of
+    /// them.
+    #[serde(default = "default_group")]
+    pub group: String,
+    /// Name of this consumer within the group. Defaults to a random name, so a restarted query
+    /// runner does not resume its own pending messages, but they are still claimed once idle.
+    pub consumer: Option<String>,
+    /// How long a message may be pending without being acked before another consumer claims it.
+    /// Should be longer than the longest running query task.
+    #[serde(default = "default_claim_idle_ms")]
+    pub claim_idle_ms: u64,
+    /// How long each read waits for a new message before checking for idle messages again
+    #[serde(default = "default_block_ms")]
+    pub block_ms: u64,
+    /// Approximate number of entries kept in the stream. Older entries are trimmed as new ones
+    /// are added, whether or not they were acked.
+    pub max_len: Option<usize>,
+}
+
+fn default_group() -> String {
+    "query_runner".to_string()
+}
+
+fn default_claim_idle_ms() -> u64 {
+    30 * 60 * 1000
+}
+
+fn default_block_ms() -> u64 {
+    5000
+}
+
+impl From<RedisError> for MeshError {
+    fn from(value: RedisError) -> Self {
+        MeshError::Messaging(format!("Redis error: {value}"))
+    }
+}
+
+fn parse_entry(msg_id: u64, entry: &StreamId) -> Result<(u64, GenericMessage)> {
+    let bytes: Vec<u8> = entry.get(PAYLOAD_FIELD).ok_or(MeshError::BadMessage((
+        msg_id,
+        format!(
+            "Redis stream entry {} has no {PAYLOAD_FIELD} field!",
+            entry.id
+        ),
+    )))?;
+    let msg: GenericMessage = serde_json::from_slice(&bytes)
+        .map_err(|e| MeshError::BadMessage((msg_id, e.to_string())))?;
+    Ok((msg_id, msg))
+}
+
+/// Consumes messages with XREADGROUP as a member of a consumer group, so query runners compete
+/// for messages. Acking a message XACKs its entry. Entries which stay pending for longer than
+/// [RedisConnectionOptions::claim_idle_ms], e.g. because their consumer died, are claimed by
+/// another consumer with XAUTOCLAIM.
+pub struct RedisConsumer {
+    client: Client,
+    conn: MultiplexedConnection,
+    options: RedisConnectionOptions,
+    consumer: String,
+    /// Maps the message ids handed out to the id of the stream entry.
+    in_flight: HashMap<u64, String>,
+    next_id: u64,
+}
+
+impl RedisConsumer {
+    pub async fn initialize(options: &RedisConnectionOptions) -> Result<Self> {
+        let client = Client::open(options.url.as_str())?;
+        let mut conn = client.get_multiplexed_tokio_connection().await?;
+        let created: std::result::Result<(), RedisError> = conn
+            .xgroup_create_mkstream(&options.stream, &options.group, "0")
+            .await;
+        match created {
+            Ok(()) => (),
+            // Another consumer already created the group
+            Err(e) if e.code() == Some("BUSYGROUP") => (),
+            Err(e) => return Err(e.into()),
+        }
+        Ok(Self {
+            client,
+            conn,
+            consumer: options
+                .consumer
+                .clone()
+                .unwrap_or_else(|| format!("query_runner-{}", Uuid::new_v4())),
+            options: options.clone(),
+            in_flight: HashMap::new(),
+            next_id: 0,
+        })
+    }
+
+    fn read_options(&self) -> StreamReadOptions {
+        StreamReadOptions::default()
+            .group(&self.options.group, &self.consumer)
+            .count(1)
+    }
+
+    fn track(&mut self, entry: &StreamId) -> Result<(u64, GenericMessage)> {
+        let msg_id = self.next_id;
+        self.next_id += 1;
+        self.in_flight.insert(msg_id, entry.id.clone());
+        parse_entry(msg_id, entry)
+    }
+
+    async fn claim_idle(&mut self) -> Result<Option<StreamId>> {
+        let reply: StreamAutoClaimReply = self
+            .conn
+            .xautoclaim_options(
+                &self.options.stream,
+                &self.options.group,
+                &self.consumer,
+                self.options.claim_idle_ms,
+                "0",
+                StreamAutoClaimOptions::default().count(1),
+            )
+            .await?;
+        Ok(reply.claimed.into_iter().next())
+    }
+}
+
+fn first_entry(reply: Option<StreamReadReply>) -> Option<StreamId> {
+    reply?.keys.into_iter().next()?.ids.into_iter().next()
+}
+
+#[async_trait]
+impl MessageConsumer for RedisConsumer {
+    async fn receive_message(&mut self) -> Result<(u64, GenericMessage)> {
+        loop {
+            if let Some(entry) = self.claim_idle().await? {
+                return self.track(&entry);
+            }
+            let options = self.read_options().block(self.options.block_ms as usize);
+            let reply: Option<StreamReadReply> = self
+                .conn
+                .xread_options(&[&self.options.stream], &[">"], &options)
+                .await?;
+            if let Some(entry) = first_entry(reply) {
+                return self.track(&entry);
+            }
+        }
+    }
+
+    fn try_receive_message(&mut self) -> Result<(u64, GenericMessage)> {
+        // Reads without blocking over a synchronous connection, since this cannot await
+        let mut conn = self.client.get_connection()?;
+        let reply: Option<StreamReadReply> =
+            conn.xread_options(&[&self.options.stream], &[">"], &self.read_options())?;
+        match first_entry(reply) {
+            Some(entry) => self.track(&entry),
+            None => Err(MeshError::Messaging(
+                "No Redis stream entry is available".to_string(),
+            )),
+        }
+    }
+
+    async fn ack_message(&mut self, message_id: u64) -> Result<()> {
+        let entry_id = self
+            .in_flight
+            .remove(&message_id)
+            .ok_or(MeshError::Messaging(format!(
+                "Redis message {message_id} was already acked or never received!"
+            )))?;
+        let _: u64 = self
+            .conn
+            .xack(&self.options.stream, &self.options.group, &[entry_id])
+            .await?;
+        Ok(())
+    }
+}
+
+pub struct RedisProducer {
+    conn: MultiplexedConnection,
+    stream: String,
+    max_len: Option<usize>,
+}
+
+impl RedisProducer {
+    pub async fn initialize(options: &RedisConnectionOptions) -> Result<Self> {
+        let client = Client::open(options.url.as_str())?;
+        Ok(Self {
+            conn: client.get_multiplexed_tokio_connection().await?,
+            stream: options.stream.clone(),
+            max_len: options.max_len,
+        })
+    }
+}
+
+#[async_trait]
+impl MessageProducer for RedisProducer {
+    async fn send_message(&mut self, msg: &GenericMessage) -> Result<()> {
+        let payload = serde_json::to_vec(msg)?;
+        let items = [(PAYLOAD_FIELD, payload)];
+        let _: String = match self.max_len {
+            Some(max_len) => {
+                self.conn
+                    .xadd_maxlen(
+                        &self.stream,
+                        redis::streams::StreamMaxlen::Approx(max_len),
+                        "*",
+                        &items,
+                    )
+                    .await?
+            }
+            None => self.conn.xadd(&self.stream, "*", &items).await?,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use redis::streams::StreamId;
+    use redis::Value;
+    use uuid::Uuid;
+
+    use crate::error::MeshError;
+    use crate::messaging::{GenericMessage, QueryTaskMessage};
+
+    use super::{parse_entry, PAYLOAD_FIELD};
+
+    #[test]
+    fn test_parse_entry() {
+        let id = Uuid::new_v4();
+        let msg = GenericMessage::LocalQueryTask(QueryTaskMessage { id });
+        let entry = StreamId {
+            id: "1-0".to_string(),
+            map: HashMap::from([(
+                PAYLOAD_FIELD.to_string(),
+                Value::BulkString(serde_json::to_vec(&msg).unwrap()),
+            )]),
+        };
+        let (msg_id, parsed) = parse_entry(7, &entry).unwrap();
+        assert_eq!(msg_id, 7);
+        assert_eq!(parsed.task_id(), id);
+
+        // Entries without a payload are reported as bad messages, so they can be acked
+        let entry = StreamId {
+            id: "2-0".to_string(),
+            map: HashMap::new(),
+        };
+        assert!(matches!(
+            parse_entry(8, &entry),
+            Err(MeshError::BadMessage((8, _)))
+        ));
+    }
+}
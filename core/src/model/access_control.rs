@@ -1,4 +1,5 @@
 use crate::model::relay::Relay;
+use crate::model::role::Role;
 use crate::model::user::User;
 use diesel::prelude::*;
 use diesel::{AsExpression, FromSqlRow};
@@ -9,13 +10,43 @@ use std::collections::HashSet;
 use uuid::Uuid;
 
 use crate::model::data_stores::DataSource;
-use crate::schema::{default_source_permission, relay_source_permission, user_source_permission};
+use crate::schema::{
+    default_source_permission, relay_source_permission, role_source_permission,
+    user_source_permission,
+};
 
 // Policy evaluation logic:
 // 1. Start with default source permission for given DataSource
-// 2. Add any columns and rows explicitly allowed for user/relay involved in query
+// 2. Add any columns and rows explicitly allowed for user/relay involved in query, directly or
+//    via the roles they are a member of. Restrictive sources instead keep only those columns and
+//    rows which are also explicitly allowed, see PolicyMode
 // 3. Remove any columns and rows explicitly denied for user/relay involved in query
 
+/// Controls how the default permission of a [DataSource] combines with the explicit grants of the
+/// [User] and [Relay] involved in a query.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Clone,
+    Copy,
+    Default,
+    diesel_derive_enum::DbEnum,
+)]
+#[ExistingTypePath = "crate::schema::sql_types::PolicyMode"]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyMode {
+    /// Grants the union of the default permission and any explicit grants.
+    #[default]
+    Permissive,
+    /// Grants only the intersection of the default permission and explicit grants, so access
+    /// requires an explicit grant.
+    Restrictive,
+}
+
 /// Defines the columns and rows any authenticated [User] or [Relay]
 /// is permitted to access for a specific [DataSource] in the absense
 /// of explicit allows or denys for the [User] or [Relay]
@@ -60,9 +91,24 @@ pub struct UserSourcePermission {
     pub source_permission: SourcePermission,
 }
 
+/// Database object for the role_source_permission table. Holds a [SourcePermission] for specific
+/// [Role]s
+#[derive(
+    Serialize, Deserialize, Queryable, Selectable, Identifiable, Associations, Debug, PartialEq,
+)]
+#[diesel(belongs_to(DataSource))]
+#[diesel(belongs_to(Role))]
+#[diesel(table_name = role_source_permission)]
+pub struct RoleSourcePermission {
+    pub id: Uuid,
+    pub data_source_id: Uuid,
+    pub role_id: Uuid,
+    pub source_permission: SourcePermission,
+}
+
 /// Defines the columns and rows of a [DataSource] that a given [Relay]
 /// or [User] are permitted to retrieve.
-#[derive(Serialize, Deserialize, Debug, AsJsonb, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, AsJsonb, PartialEq)]
 pub struct SourcePermission {
     pub columns: ColumnPermission,
     pub rows: RowPermission,
@@ -86,7 +132,7 @@ impl SourcePermission {
 
 /// Represents a set of columns for a specific [DataSource]
 /// which a given [Relay] or [User] are allowed to access
-#[derive(Serialize, Deserialize, Debug, AsJsonb, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, AsJsonb, PartialEq)]
 pub struct ColumnPermission {
     pub allowed_columns: HashSet<String>,
 }
@@ -117,7 +163,7 @@ impl ColumnPermission {
 
 /// Defines filter expressions which restrict the rows which can
 /// be accessed
-#[derive(Serialize, Deserialize, Debug, AsJsonb, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, AsJsonb, PartialEq)]
 pub struct RowPermission {
     /// A sql filter expression which defines the allowed rows:
     /// e.g. "(col1=1 or (col2=2 and name='joe')) and not col3='secret'"
@@ -0,0 +1,70 @@
+use crate::schema::audit_log;
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// What an [AuditEntry] records.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, diesel_derive_enum::DbEnum)]
+#[ExistingTypePath = "crate::schema::sql_types::AuditAction"]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// A [QueryRequest][crate::model::query::QueryRequest] was accepted, the detail lists the
+    /// sources it queries with the permissions resolved for the requesting user.
+    Query,
+    /// A configuration object was applied via /admin/apply, the detail is the applied object.
+    Apply,
+    /// A configuration object was deleted via /admin/apply or /admin/prune, the detail
+    /// describes the deleted object.
+    Delete,
+}
+
+/// An entry of the audit log, recording who ran which query or changed which configuration.
+#[derive(Queryable, Selectable, Serialize, Debug, PartialEq)]
+#[diesel(table_name = audit_log)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub recorded_at: DateTime<Utc>,
+    pub action: AuditAction,
+    /// Sha256 fingerprint of the certificate of the user who took the action
+    pub actor_sha256: String,
+    /// X509 Subject Distinguished Name of the user who took the action
+    pub actor_subject: String,
+    pub query_request_id: Option<Uuid>,
+    pub sql: Option<String>,
+    pub detail: serde_json::Value,
+}
+
+/// Used to create a new [AuditEntry] in the database.
+#[derive(Insertable, Debug, PartialEq)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditEntry {
+    pub action: AuditAction,
+    pub actor_sha256: String,
+    pub actor_subject: String,
+    pub query_request_id: Option<Uuid>,
+    pub sql: Option<String>,
+    pub detail: serde_json::Value,
+}
+
+/// Selects a page of [AuditEntry]s, newest first. Pages are keyed on the id of the last entry
+/// of the previous page, so entries recorded while paging do not shift later pages.
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    /// Only entries older than the entry with this id, i.e. the next_before_id of the
+    /// previous page.
+    pub before_id: Option<i64>,
+    /// Only entries of the user with this certificate fingerprint.
+    pub actor: Option<String>,
+    pub action: Option<AuditAction>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// A page of [AuditEntry]s, along with the before_id which selects the next page, if any.
+#[derive(Serialize, Debug)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    pub next_before_id: Option<i64>,
+}
@@ -0,0 +1,290 @@
+use super::local_data::{DataConnectionsDeclaration, DataSourcesDeclaration};
+use super::local_mapping::{
+    DataConnectionMappingDeclaration, DataSourceMappingsDeclaration, LocalMappingDeclaration,
+};
+use super::remote_mapping::{PeerRelayMappingsDeclaration, RemoteMappingsDeclaration};
+use super::{ResolvedConfigCommand, ResolvedConfigObject};
+
+impl ResolvedConfigCommand {
+    /// Splits a command into an ordered list of commands which each declare at most max_items
+    /// data fields or mappings, so that very large declarations can be applied in several
+    /// requests. Applying the chunks in order results in the same state as applying the
+    /// original command, since every declaration is applied as an upsert. Entities are never
+    /// split, as the position of each information is its index in the declaration.
+    pub fn into_chunks(self, max_items: usize) -> Vec<ResolvedConfigCommand> {
+        let max_items = max_items.max(1);
+        let api_version = self.api_version;
+        let objects = match self.config_object {
+            ResolvedConfigObject::LocalData(data_decl) => chunk_data_decl(data_decl, max_items)
+                .into_iter()
+                .map(ResolvedConfigObject::LocalData)
+                .collect(),
+            ResolvedConfigObject::LocalMapping(map_decl) => {
+                chunk_local_mapping_decl(map_decl, max_items)
+                    .into_iter()
+                    .map(ResolvedConfigObject::LocalMapping)
+                    .collect()
+            }
+            ResolvedConfigObject::RemoteMapping(map_decl) => {
+                chunk_remote_mapping_decl(map_decl, max_items)
+                    .into_iter()
+                    .map(ResolvedConfigObject::RemoteMapping)
+                    .collect()
+            }
+            other => vec![other],
+        };
+        objects
+            .into_iter()
+            .map(|config_object| ResolvedConfigCommand {
+                api_version: api_version.clone(),
+                config_object,
+            })
+            .collect()
+    }
+
+    /// Number of data fields or mappings declared by the command, and at least 1, which is the
+    /// weight [ResolvedConfigCommand::into_chunks] limits chunks by.
+    pub fn item_count(&self) -> usize {
+        let count = match &self.config_object {
+            ResolvedConfigObject::LocalData(data_decl) => {
+                data_decl.data_sources.iter().map(|s| s.fields.len()).sum()
+            }
+            ResolvedConfigObject::LocalMapping(map_decl) => map_decl
+                .mappings
+                .iter()
+                .flat_map(|c| &c.source_mappings)
+                .map(|s| s.field_mappings.len())
+                .sum(),
+            ResolvedConfigObject::RemoteMapping(map_decl) => map_decl
+                .mappings
+                .iter()
+                .map(|p| p.relay_mappings.len())
+                .sum(),
+            _ => 1,
+        };
+        count.max(1)
+    }
+}
+
+/// Greedily packs items, in order, into chunks whose total weight is at most max. An item
+/// heavier than max gets a chunk to itself.
+pub fn pack<T>(items: Vec<T>, weight: impl Fn(&T) -> usize, max: usize) -> Vec<Vec<T>> {
+    let mut chunks: Vec<Vec<T>> = vec![];
+    let mut current_weight = 0;
+    for item in items {
+        let w = weight(&item);
+        match chunks.last_mut() {
+            Some(chunk) if current_weight + w <= max => {
+                chunk.push(item);
+                current_weight += w;
+            }
+            _ => {
+                chunks.push(vec![item]);
+                current_weight = w;
+            }
+        }
+    }
+    chunks
+}
+
+/// Groups consecutive items with equal keys, preserving order.
+fn group_consecutive<K: PartialEq, T>(items: Vec<(K, T)>) -> Vec<(K, Vec<T>)> {
+    let mut groups: Vec<(K, Vec<T>)> = vec![];
+    for (key, item) in items {
+        match groups.last_mut() {
+            Some((last, group)) if *last == key => group.push(item),
+            _ => groups.push((key, vec![item])),
+        }
+    }
+    groups
+}
+
+fn chunk_data_decl(
+    data_decl: DataConnectionsDeclaration,
+    max_items: usize,
+) -> Vec<DataConnectionsDeclaration> {
+    let mut sources = vec![];
+    for source in data_decl.data_sources {
+        if source.fields.len() <= max_items {
+            sources.push(source);
+            continue;
+        }
+        let DataSourcesDeclaration {
+            name,
+            source_sql,
+            source_options,
+            engines,
+            freshness_query,
+            policy_mode,
+            fields,
+            default_permission,
+        } = source;
+        for fields in pack(fields, |_| 1, max_items) {
+            sources.push(DataSourcesDeclaration {
+                name: name.clone(),
+                source_sql: source_sql.clone(),
+                source_options: source_options.clone(),
+                engines: engines.clone(),
+                freshness_query: freshness_query.clone(),
+                policy_mode,
+                fields,
+                default_permission: default_permission.clone(),
+            });
+        }
+    }
+    pack(sources, |s| s.fields.len().max(1), max_items)
+        .into_iter()
+        .map(|data_sources| DataConnectionsDeclaration {
+            name: data_decl.name.clone(),
+            connection_options: data_decl.connection_options.clone(),
+            execution_windows: data_decl.execution_windows.clone(),
+            data_sources,
+        })
+        .collect()
+}
+
+fn chunk_local_mapping_decl(
+    map_decl: LocalMappingDeclaration,
+    max_items: usize,
+) -> Vec<LocalMappingDeclaration> {
+    let mut flat = vec![];
+    for con_map in map_decl.mappings {
+        for source_map in con_map.source_mappings {
+            for field_map in source_map.field_mappings {
+                let key = (
+                    con_map.data_con_name.clone(),
+                    source_map.data_source_name.clone(),
+                );
+                flat.push((key, field_map));
+            }
+        }
+    }
+    pack(flat, |_| 1, max_items)
+        .into_iter()
+        .map(|chunk| {
+            let by_source = group_consecutive(chunk)
+                .into_iter()
+                .map(|((data_con_name, data_source_name), field_mappings)| {
+                    (
+                        data_con_name,
+                        DataSourceMappingsDeclaration {
+                            data_source_name,
+                            field_mappings,
+                        },
+                    )
+                })
+                .collect();
+            let mappings = group_consecutive(by_source)
+                .into_iter()
+                .map(
+                    |(data_con_name, source_mappings)| DataConnectionMappingDeclaration {
+                        data_con_name,
+                        source_mappings,
+                    },
+                )
+                .collect();
+            LocalMappingDeclaration {
+                entity_name: map_decl.entity_name.clone(),
+                mappings,
+            }
+        })
+        .collect()
+}
+
+fn chunk_remote_mapping_decl(
+    map_decl: RemoteMappingsDeclaration,
+    max_items: usize,
+) -> Vec<RemoteMappingsDeclaration> {
+    let mut flat = vec![];
+    for peer_map in map_decl.mappings {
+        for info_map in peer_map.relay_mappings {
+            let key = (
+                peer_map.relay_name.clone(),
+                peer_map.remote_entity_name.clone(),
+                peer_map.sql.clone(),
+            );
+            flat.push((key, info_map));
+        }
+    }
+    pack(flat, |_| 1, max_items)
+        .into_iter()
+        .map(|chunk| RemoteMappingsDeclaration {
+            entity_name: map_decl.entity_name.clone(),
+            mappings: group_consecutive(chunk)
+                .into_iter()
+                .map(|((relay_name, remote_entity_name, sql), relay_mappings)| {
+                    PeerRelayMappingsDeclaration {
+                        relay_name,
+                        remote_entity_name,
+                        sql,
+                        relay_mappings,
+                    }
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::config_commands::local_mapping::{
+        DataConnectionMappingDeclaration, DataFieldMappingDeclaration,
+        DataSourceMappingsDeclaration, LocalMappingDeclaration,
+    };
+    use crate::model::config_commands::{
+        no_transformation, ResolvedConfigCommand, ResolvedConfigObject,
+    };
+
+    fn field_maps(source: &str, n: usize) -> DataSourceMappingsDeclaration {
+        DataSourceMappingsDeclaration {
+            data_source_name: source.to_string(),
+            field_mappings: (0..n)
+                .map(|i| DataFieldMappingDeclaration {
+                    info: format!("info_{i}"),
+                    field: format!("field_{i}"),
+                    transformation: no_transformation(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_chunk_local_mappings() {
+        let command = ResolvedConfigCommand {
+            api_version: "V1".to_string(),
+            config_object: ResolvedConfigObject::LocalMapping(LocalMappingDeclaration {
+                entity_name: "customer".to_string(),
+                mappings: vec![DataConnectionMappingDeclaration {
+                    data_con_name: "warehouse".to_string(),
+                    source_mappings: vec![field_maps("a", 3), field_maps("b", 4)],
+                }],
+            }),
+        };
+        assert_eq!(command.item_count(), 7);
+        let chunks = command.into_chunks(5);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(
+            chunks.iter().map(|c| c.item_count()).collect::<Vec<_>>(),
+            vec![5, 2]
+        );
+
+        let sources = chunks
+            .iter()
+            .map(|c| match &c.config_object {
+                ResolvedConfigObject::LocalMapping(m) => m.mappings[0]
+                    .source_mappings
+                    .iter()
+                    .map(|s| (s.data_source_name.clone(), s.field_mappings.len()))
+                    .collect::<Vec<_>>(),
+                _ => panic!("Expected a LocalMapping"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sources,
+            vec![
+                vec![("a".to_string(), 3), ("b".to_string(), 2)],
+                vec![("b".to_string(), 2)],
+            ]
+        );
+    }
+}
@@ -5,22 +5,42 @@ use serde::{Deserialize, Serialize};
 pub struct EntityDeclaration {
     pub name: String,
     pub information: Vec<InformationDeclaration>,
+    /// See [Entity::validation_query][crate::model::entity::Entity::validation_query]
+    #[serde(default)]
+    pub validation_query: Option<String>,
+    /// Alternative names queries may refer to the entity by, e.g. its names before it was
+    /// renamed. Queries using an alias succeed with a deprecation warning.
+    #[serde(default)]
+    pub aliases: Vec<String>,
 }
 
+/// Information is declared in the canonical column order of the entity.
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct InformationDeclaration {
     pub name: String,
     pub arrow_dtype: String,
+    #[serde(default = "default_nullable")]
+    pub nullable: bool,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct ResolvedEntityDeclaration {
     pub name: String,
     pub information: Vec<ResolvedInformationDeclaration>,
+    #[serde(default)]
+    pub validation_query: Option<String>,
+    #[serde(default)]
+    pub aliases: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct ResolvedInformationDeclaration {
     pub name: String,
     pub arrow_dtype: DataType,
+    #[serde(default = "default_nullable")]
+    pub nullable: bool,
+}
+
+fn default_nullable() -> bool {
+    true
 }
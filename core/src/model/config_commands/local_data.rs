@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 
+use crate::model::access_control::PolicyMode;
+use crate::model::data_stores::engines::SourceEngines;
 use crate::model::data_stores::options::{ConnectionOptions, SourceOptions};
+use crate::model::data_stores::schedule::ExecutionWindows;
 
 use super::{empty_permission, DefaultPermissionDeclaration};
 
@@ -8,6 +11,10 @@ use super::{empty_permission, DefaultPermissionDeclaration};
 pub struct DataConnectionsDeclaration {
     pub name: String,
     pub connection_options: ConnectionOptions,
+    /// If declared, queries against this connection only execute within these windows and are
+    /// otherwise deferred until the next window opens.
+    #[serde(default)]
+    pub execution_windows: ExecutionWindows,
     pub data_sources: Vec<DataSourcesDeclaration>,
 }
 
@@ -16,6 +23,18 @@ pub struct DataSourcesDeclaration {
     pub name: String,
     pub source_sql: String,
     pub source_options: SourceOptions,
+    /// Additional engines which can query the same data, see
+    /// [SourceEngines][crate::model::data_stores::engines::SourceEngines].
+    #[serde(default)]
+    pub engines: SourceEngines,
+    /// Query which reports when the data of the source was last updated, see
+    /// [DataSource][crate::model::data_stores::DataSource].
+    #[serde(default)]
+    pub freshness_query: Option<String>,
+    /// How default_permission combines with explicit grants, see
+    /// [PolicyMode][crate::model::access_control::PolicyMode].
+    #[serde(default)]
+    pub policy_mode: PolicyMode,
     pub fields: Vec<DataFieldsDeclaration>,
     #[serde(default = "empty_permission")]
     pub default_permission: DefaultPermissionDeclaration,
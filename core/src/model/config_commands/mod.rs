@@ -4,19 +4,34 @@ use self::{
     entity::{EntityDeclaration, ResolvedEntityDeclaration},
     local_data::{DataConnectionsDeclaration, ResolvedDataConnectionsDeclaration},
     local_mapping::{LocalMappingDeclaration, ResolvedLocalMappingDeclaration},
+    quota::{QuotaDeclaration, ResolvedQuotaDeclaration},
     relay::{PeerRelayDeclaration, ResolvedPeerRelayDeclaration},
     remote_mapping::{RemoteMappingsDeclaration, ResolvedRemoteMappingsDeclaration},
-    user::{PermissionsDecl, ResolvedUserDeclaration, UserDeclaration},
+    role::{ResolvedRoleDeclaration, RoleDeclaration},
+    user::{
+        PermissionsDecl, ResolvedServiceAccountDeclaration, ResolvedUserDeclaration,
+        ServiceAccountDeclaration, UserDeclaration,
+    },
+    validation_rules::ValidationRulesDeclaration,
 };
 
 use super::mappings::Transformation;
 
+pub mod chunk;
 pub mod entity;
 pub mod local_data;
 pub mod local_mapping;
+pub mod quota;
 pub mod relay;
 pub mod remote_mapping;
+pub mod report;
+pub mod role;
 pub mod user;
+pub mod validate;
+pub mod validation_rules;
+
+/// The api_version of the config commands the relay exports.
+pub const API_VERSION: &str = "v1alpha1";
 
 /// Describes a desired state for a declared [ConfigObject].
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -47,6 +62,10 @@ pub enum ResolvedConfigObject {
     PeerRelay(ResolvedPeerRelayDeclaration),
     RemoteMapping(ResolvedRemoteMappingsDeclaration),
     User(ResolvedUserDeclaration),
+    ServiceAccount(ResolvedServiceAccountDeclaration),
+    ValidationRules(ValidationRulesDeclaration),
+    Quota(ResolvedQuotaDeclaration),
+    Role(ResolvedRoleDeclaration),
 }
 
 impl ResolvedConfigObject {
@@ -61,6 +80,30 @@ impl ResolvedConfigObject {
             Self::PeerRelay(_) => 4,
             Self::RemoteMapping(_) => 5,
             Self::User(_) => 6,
+            Self::ServiceAccount(_) => 7,
+            Self::ValidationRules(_) => 8,
+            Self::Quota(_) => 9,
+            Self::Role(_) => 10,
+        }
+    }
+
+    /// Describes the object by its kind and name, e.g. "LocalData trino". Users and service
+    /// accounts are identified by their certificate, so only their kind is given.
+    pub fn description(&self) -> String {
+        match self {
+            Self::Entity(entity) => format!("Entity {}", entity.name),
+            Self::LocalData(con) => format!("LocalData {}", con.name),
+            Self::LocalMapping(mapping) => format!("LocalMapping {}", mapping.entity_name),
+            Self::PeerRelay(relay) => format!("PeerRelay {}", relay.name),
+            Self::RemoteMapping(mapping) => format!("RemoteMapping {}", mapping.entity_name),
+            Self::User(_) => "User".to_string(),
+            Self::ServiceAccount(_) => "ServiceAccount".to_string(),
+            Self::ValidationRules(rules) => format!("ValidationRules {}", rules.name),
+            Self::Quota(quota) => match &quota.relay {
+                Some(relay) => format!("Quota of relay {relay}"),
+                None => "Quota of user".to_string(),
+            },
+            Self::Role(role) => format!("Role {}", role.name),
         }
     }
 }
@@ -78,9 +121,13 @@ pub enum ConfigObject {
     PeerRelay(PeerRelayDeclaration),
     RemoteMapping(RemoteMappingsDeclaration),
     User(UserDeclaration),
+    ServiceAccount(ServiceAccountDeclaration),
+    ValidationRules(ValidationRulesDeclaration),
+    Quota(QuotaDeclaration),
+    Role(RoleDeclaration),
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct DefaultPermissionDeclaration {
     pub allowed_columns: Vec<String>,
     pub allowed_rows: String,
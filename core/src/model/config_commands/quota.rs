@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// Declares the [Quota][crate::model::quota::Quota] of either the user with the certificate in
+/// user_x509_cert_file, or the peer relay named relay.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct QuotaDeclaration {
+    #[serde(default)]
+    pub user_x509_cert_file: Option<String>,
+    #[serde(default)]
+    pub relay: Option<String>,
+    #[serde(default)]
+    pub max_concurrent_requests: Option<u32>,
+    #[serde(default)]
+    pub max_requests_per_minute: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ResolvedQuotaDeclaration {
+    #[serde(default)]
+    pub user_x509_cert: Option<Vec<u8>>,
+    #[serde(default)]
+    pub relay: Option<String>,
+    #[serde(default)]
+    pub max_concurrent_requests: Option<u32>,
+    #[serde(default)]
+    pub max_requests_per_minute: Option<u32>,
+}
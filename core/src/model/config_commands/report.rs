@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+/// Outcome of a single config object of an apply request.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplyStatus {
+    /// The object was applied and committed.
+    Applied,
+    /// The object failed to apply, see the error.
+    Failed,
+    /// The object was applied, but rolled back since another object of the request failed.
+    RolledBack,
+    /// The object was not attempted, since an earlier object of the request failed.
+    Skipped,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ObjectReport {
+    /// See [ResolvedConfigObject::description][super::ResolvedConfigObject::description].
+    pub object: String,
+    pub status: ApplyStatus,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// The outcome of every config object of an apply request, in the order they were sent. An apply
+/// is committed only if every object applied, unless it was a partial apply, which commits the
+/// objects which applied.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ApplyReport {
+    pub committed: bool,
+    pub objects: Vec<ObjectReport>,
+}
+
+impl ApplyReport {
+    /// Number of objects which failed to apply.
+    pub fn failed(&self) -> usize {
+        self.objects
+            .iter()
+            .filter(|o| o.status == ApplyStatus::Failed)
+            .count()
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+use super::{no_permission_decl, user::PermissionsDecl};
+
+/// Declares a [Role][crate::model::role::Role] with its source permissions, which are granted
+/// to the users with the certificates in user_x509_cert_files and the peer relays named in
+/// relays. The declared members replace any previous members of the role.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct RoleDeclaration {
+    pub name: String,
+    #[serde(default)]
+    pub user_x509_cert_files: Vec<String>,
+    #[serde(default)]
+    pub relays: Vec<String>,
+    #[serde(default = "no_permission_decl")]
+    pub permissions: Option<Vec<PermissionsDecl>>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ResolvedRoleDeclaration {
+    pub name: String,
+    #[serde(default)]
+    pub user_x509_certs: Vec<Vec<u8>>,
+    #[serde(default)]
+    pub relays: Vec<String>,
+    #[serde(default = "no_permission_decl")]
+    pub permissions: Option<Vec<PermissionsDecl>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::config_commands::{ConfigCommand, ConfigObject};
+
+    #[test]
+    fn test_role_declaration() {
+        let command: ConfigCommand = serde_json::from_value(serde_json::json!({
+            "api_version": "v1",
+            "kind": "Role",
+            "spec": {
+                "name": "analysts",
+                "relays": ["partner"],
+                "permissions": [{
+                    "data_con_name": "trino_tpch",
+                    "source_permissions": [{
+                        "data_source_name": "tpch.tiny.customer",
+                        "allowed_columns": ["custkey"],
+                        "allowed_rows": "true"
+                    }]
+                }]
+            }
+        }))
+        .unwrap();
+        match command.config_object {
+            ConfigObject::Role(role) => {
+                assert_eq!(role.name, "analysts");
+                assert!(role.user_x509_cert_files.is_empty());
+                assert_eq!(role.relays, vec!["partner".to_string()]);
+                assert_eq!(role.permissions.map(|p| p.len()), Some(1));
+            }
+            other => panic!("Parsed unexpected config object {other:?}"),
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use crate::model::user::UserAttributes;
+use std::collections::HashMap;
+
+use crate::model::user::{ServiceAccountScope, UserAttributes};
 use serde::{Deserialize, Serialize};
 
 use super::no_permission_decl;
@@ -20,6 +22,32 @@ pub struct UserDeclaration {
     pub permissions: Option<Vec<PermissionsDecl>>,
 }
 
+/// Declares a service account, a [User][crate::model::user::User] for automation such as CI jobs
+/// and schedulers, which may only query the entities of its scope and at its rate.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ResolvedServiceAccountDeclaration {
+    pub x509_cert: Vec<u8>,
+    #[serde(default)]
+    pub scope: ServiceAccountScope,
+    #[serde(default)]
+    pub misc: HashMap<String, String>,
+    #[serde(default = "no_permission_decl")]
+    pub permissions: Option<Vec<PermissionsDecl>>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ServiceAccountDeclaration {
+    pub x509_cert_file: String,
+    #[serde(default)]
+    pub scope: ServiceAccountScope,
+    /// Arbitrary attributes of the service account, see
+    /// [UserAttributes][crate::model::user::UserAttributes].
+    #[serde(default)]
+    pub misc: HashMap<String, String>,
+    #[serde(default = "no_permission_decl")]
+    pub permissions: Option<Vec<PermissionsDecl>>,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct SourcePermissionDecl {
     pub data_source_name: String,
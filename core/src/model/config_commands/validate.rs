@@ -0,0 +1,379 @@
+use std::collections::{HashMap, HashSet};
+
+use super::local_data::DataSourcesDeclaration;
+use super::user::PermissionsDecl;
+use super::ResolvedConfigObject;
+use crate::execute::parse_utils::parse_sql_as_expr;
+
+/// Index of the objects declared by a complete configuration, against which the references of
+/// each object are checked without contacting a relay.
+pub struct DeclaredConfig<'a> {
+    /// entity -> information
+    entities: HashMap<&'a str, HashSet<&'a str>>,
+    connections: HashSet<&'a str>,
+    /// (connection, source) -> source
+    sources: HashMap<(&'a str, &'a str), &'a DataSourcesDeclaration>,
+    relays: HashSet<&'a str>,
+    user_certs: HashSet<&'a [u8]>,
+    validation_rules: HashSet<&'a str>,
+}
+
+impl<'a> DeclaredConfig<'a> {
+    pub fn new(objects: impl IntoIterator<Item = &'a ResolvedConfigObject>) -> Self {
+        let mut declared = DeclaredConfig {
+            entities: HashMap::new(),
+            connections: HashSet::new(),
+            sources: HashMap::new(),
+            relays: HashSet::new(),
+            user_certs: HashSet::new(),
+            validation_rules: HashSet::new(),
+        };
+        for object in objects {
+            match object {
+                ResolvedConfigObject::Entity(entity) => {
+                    declared
+                        .entities
+                        .entry(&entity.name)
+                        .or_default()
+                        .extend(entity.information.iter().map(|info| info.name.as_str()));
+                }
+                ResolvedConfigObject::LocalData(con) => {
+                    declared.connections.insert(&con.name);
+                    for source in &con.data_sources {
+                        declared
+                            .sources
+                            .insert((con.name.as_str(), source.name.as_str()), source);
+                    }
+                }
+                ResolvedConfigObject::PeerRelay(relay) => {
+                    declared.relays.insert(&relay.name);
+                }
+                ResolvedConfigObject::User(user) => {
+                    declared.user_certs.insert(&user.x509_cert);
+                }
+                ResolvedConfigObject::ServiceAccount(account) => {
+                    declared.user_certs.insert(&account.x509_cert);
+                }
+                ResolvedConfigObject::ValidationRules(rules) => {
+                    declared.validation_rules.insert(&rules.name);
+                }
+                ResolvedConfigObject::LocalMapping(_)
+                | ResolvedConfigObject::RemoteMapping(_)
+                | ResolvedConfigObject::Quota(_)
+                | ResolvedConfigObject::Role(_) => (),
+            }
+        }
+        declared
+    }
+
+    /// Returns every error found in object: references to entities, information, data sources,
+    /// fields, relays, users and validation rules which are not declared, and allowed_rows
+    /// filters which are not valid SQL expressions.
+    pub fn validate(&self, object: &ResolvedConfigObject) -> Vec<String> {
+        let mut errors = vec![];
+        match object {
+            ResolvedConfigObject::Entity(_) | ResolvedConfigObject::ValidationRules(_) => (),
+            ResolvedConfigObject::LocalData(con) => {
+                for source in &con.data_sources {
+                    let paths = source.fields.iter().map(|f| f.path.as_str()).collect();
+                    check_permission(
+                        &mut errors,
+                        &format!("Default permission of {}/{}", con.name, source.name),
+                        &paths,
+                        &source.default_permission.allowed_columns,
+                        &source.default_permission.allowed_rows,
+                    );
+                }
+            }
+            ResolvedConfigObject::LocalMapping(mapping) => {
+                let context = format!("LocalMapping of entity {}", mapping.entity_name);
+                let information = self.entity(&mut errors, &context, &mapping.entity_name);
+                for con_mapping in &mapping.mappings {
+                    for source_mapping in &con_mapping.source_mappings {
+                        let Some(source) = self.source(
+                            &mut errors,
+                            &context,
+                            &con_mapping.data_con_name,
+                            &source_mapping.data_source_name,
+                        ) else {
+                            continue;
+                        };
+                        for field_mapping in &source_mapping.field_mappings {
+                            if !source.fields.iter().any(|f| f.name == field_mapping.field) {
+                                errors.push(format!(
+                                    "{context} references undeclared field {} of {}/{}",
+                                    field_mapping.field, con_mapping.data_con_name, source.name
+                                ));
+                            }
+                            if information.is_some_and(|i| !i.contains(field_mapping.info.as_str()))
+                            {
+                                errors.push(format!(
+                                    "{context} references undeclared information {}",
+                                    field_mapping.info
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            ResolvedConfigObject::RemoteMapping(mapping) => {
+                let context = format!("RemoteMapping of entity {}", mapping.entity_name);
+                let information = self.entity(&mut errors, &context, &mapping.entity_name);
+                for relay_mapping in &mapping.mappings {
+                    self.relay(&mut errors, &context, &relay_mapping.relay_name);
+                    for info_mapping in &relay_mapping.relay_mappings {
+                        if information
+                            .is_some_and(|i| !i.contains(info_mapping.local_info.as_str()))
+                        {
+                            errors.push(format!(
+                                "{context} references undeclared information {}",
+                                info_mapping.local_info
+                            ));
+                        }
+                    }
+                }
+            }
+            ResolvedConfigObject::PeerRelay(relay) => {
+                let context = format!("PeerRelay {}", relay.name);
+                self.permissions(&mut errors, &context, relay.permissions.as_deref());
+            }
+            ResolvedConfigObject::User(user) => {
+                let context = "User".to_string();
+                self.permissions(&mut errors, &context, user.permissions.as_deref());
+                if let Some(rules) = &user.attributes.validation_rules {
+                    if !self.validation_rules.contains(rules.as_str()) {
+                        errors.push(format!(
+                            "{context} references undeclared ValidationRules {rules}"
+                        ));
+                    }
+                }
+            }
+            ResolvedConfigObject::ServiceAccount(account) => {
+                let context = "ServiceAccount".to_string();
+                self.permissions(&mut errors, &context, account.permissions.as_deref());
+            }
+            ResolvedConfigObject::Quota(quota) => {
+                let context = "Quota".to_string();
+                if let Some(relay) = &quota.relay {
+                    self.relay(&mut errors, &context, relay);
+                }
+                if let Some(cert) = &quota.user_x509_cert {
+                    self.user(&mut errors, &context, cert);
+                }
+            }
+            ResolvedConfigObject::Role(role) => {
+                let context = format!("Role {}", role.name);
+                for relay in &role.relays {
+                    self.relay(&mut errors, &context, relay);
+                }
+                for cert in &role.user_x509_certs {
+                    self.user(&mut errors, &context, cert);
+                }
+                self.permissions(&mut errors, &context, role.permissions.as_deref());
+            }
+        }
+        errors
+    }
+
+    fn entity(
+        &self,
+        errors: &mut Vec<String>,
+        context: &str,
+        name: &str,
+    ) -> Option<&HashSet<&'a str>> {
+        let information = self.entities.get(name);
+        if information.is_none() {
+            errors.push(format!("{context} references undeclared entity {name}"));
+        }
+        information
+    }
+
+    fn source(
+        &self,
+        errors: &mut Vec<String>,
+        context: &str,
+        con_name: &str,
+        source_name: &str,
+    ) -> Option<&'a DataSourcesDeclaration> {
+        if !self.connections.contains(con_name) {
+            errors.push(format!(
+                "{context} references undeclared DataConnection {con_name}"
+            ));
+            return None;
+        }
+        let source = self.sources.get(&(con_name, source_name)).copied();
+        if source.is_none() {
+            errors.push(format!(
+                "{context} references undeclared DataSource {con_name}/{source_name}"
+            ));
+        }
+        source
+    }
+
+    fn relay(&self, errors: &mut Vec<String>, context: &str, name: &str) {
+        if !self.relays.contains(name) {
+            errors.push(format!("{context} references undeclared PeerRelay {name}"));
+        }
+    }
+
+    fn user(&self, errors: &mut Vec<String>, context: &str, cert: &[u8]) {
+        if !self.user_certs.contains(cert) {
+            errors.push(format!(
+                "{context} references a user certificate which no User or ServiceAccount declares"
+            ));
+        }
+    }
+
+    fn permissions(
+        &self,
+        errors: &mut Vec<String>,
+        context: &str,
+        permissions: Option<&[PermissionsDecl]>,
+    ) {
+        for con_permissions in permissions.unwrap_or_default() {
+            for permission in &con_permissions.source_permissions {
+                let Some(source) = self.source(
+                    errors,
+                    context,
+                    &con_permissions.data_con_name,
+                    &permission.data_source_name,
+                ) else {
+                    continue;
+                };
+                let paths = source.fields.iter().map(|f| f.path.as_str()).collect();
+                check_permission(
+                    errors,
+                    &format!(
+                        "{context} permission on {}/{}",
+                        con_permissions.data_con_name, source.name
+                    ),
+                    &paths,
+                    &permission.allowed_columns,
+                    &permission.allowed_rows,
+                );
+            }
+        }
+    }
+}
+
+/// Checks that the allowed columns are field paths of the source and that allowed_rows parses as
+/// a SQL expression. Placeholders for user attributes are expected within string literals, so
+/// they are parsed as is.
+fn check_permission(
+    errors: &mut Vec<String>,
+    context: &str,
+    paths: &HashSet<&str>,
+    allowed_columns: &[String],
+    allowed_rows: &str,
+) {
+    for column in allowed_columns {
+        if !paths.contains(column.as_str()) {
+            errors.push(format!(
+                "{context} allows column {column}, which is not the path of a declared field"
+            ));
+        }
+    }
+    if let Err(e) = parse_sql_as_expr(allowed_rows) {
+        errors.push(format!(
+            "{context} has invalid allowed_rows {allowed_rows}: {e}"
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::config_commands::ResolvedConfigObject;
+
+    use super::DeclaredConfig;
+
+    #[test]
+    fn test_validate_config() {
+        let objects: Vec<ResolvedConfigObject> = serde_json::from_value(serde_json::json!([
+            {
+                "kind": "Entity",
+                "spec": {
+                    "name": "orders",
+                    "information": [{"name": "orderkey", "arrow_dtype": "Int64", "nullable": false}]
+                }
+            },
+            {
+                "kind": "LocalData",
+                "spec": {
+                    "name": "trino",
+                    "connection_options": {"Trino": {
+                        "user": "trino", "password": "", "host": "localhost", "port": "8080",
+                        "secure": false
+                    }},
+                    "data_sources": [{
+                        "name": "orders",
+                        "source_sql": "select * from orders",
+                        "source_options": {"Trino": {}},
+                        "fields": [{"name": "orderkey", "path": "o_orderkey"}],
+                        "default_permission": {
+                            "allowed_columns": ["orderkey"],
+                            "allowed_rows": "o_orderkey >"
+                        }
+                    }]
+                }
+            },
+            {
+                "kind": "LocalMapping",
+                "spec": {
+                    "entity_name": "orders",
+                    "mappings": [{
+                        "data_con_name": "trino",
+                        "source_mappings": [
+                            {
+                                "data_source_name": "orders",
+                                "field_mappings": [
+                                    {"info": "orderkey", "field": "orderkey"},
+                                    {"info": "custkey", "field": "custkey"}
+                                ]
+                            },
+                            {"data_source_name": "lineitem", "field_mappings": []}
+                        ]
+                    }]
+                }
+            },
+            {
+                "kind": "Role",
+                "spec": {
+                    "name": "analysts",
+                    "relays": ["partner"],
+                    "permissions": [{
+                        "data_con_name": "trino",
+                        "source_permissions": [{
+                            "data_source_name": "orders",
+                            "allowed_columns": ["o_orderkey"],
+                            "allowed_rows": "o_orderkey = '{user.region}'"
+                        }]
+                    }]
+                }
+            }
+        ]))
+        .unwrap();
+
+        let declared = DeclaredConfig::new(&objects);
+        let errors = objects
+            .iter()
+            .map(|o| declared.validate(o))
+            .collect::<Vec<_>>();
+
+        assert!(errors[0].is_empty());
+        assert_eq!(errors[1].len(), 2);
+        assert!(errors[1][0].contains("allows column orderkey"));
+        assert!(errors[1][1].contains("invalid allowed_rows"));
+        assert_eq!(
+            errors[2],
+            vec![
+                "LocalMapping of entity orders references undeclared field custkey of trino/orders",
+                "LocalMapping of entity orders references undeclared information custkey",
+                "LocalMapping of entity orders references undeclared DataSource trino/lineitem",
+            ]
+        );
+        assert_eq!(
+            errors[3],
+            vec!["Role analysts references undeclared PeerRelay partner"]
+        );
+    }
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+use crate::model::validation::ValidationRules;
+
+/// Declares a named [ValidationRuleSet][crate::model::validation::ValidationRuleSet]. The set
+/// named "default" applies to every user which is not assigned another set.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ValidationRulesDeclaration {
+    pub name: String,
+    #[serde(flatten)]
+    pub rules: ValidationRules,
+}
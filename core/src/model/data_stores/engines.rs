@@ -0,0 +1,88 @@
+use diesel::{AsExpression, FromSqlRow};
+use diesel_as_jsonb::AsJsonb;
+use serde::{Deserialize, Serialize};
+
+use super::options::SourceOptions;
+
+/// An additional engine which can query the same data as a
+/// [DataSource][crate::model::data_stores::DataSource], e.g. the files of a FileDirectory source
+/// queried via Trino instead of DataFusion. The [DataField][crate::model::data_stores::DataField]s
+/// and mappings of the [DataSource][crate::model::data_stores::DataSource] are shared, so the engine
+/// must expose the same field paths.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SourceEngine {
+    /// Name which requests may pass as an engine hint to select this engine.
+    pub name: String,
+    /// Name of the [DataConnection][crate::model::data_stores::DataConnection] used to reach the engine.
+    pub data_con_name: String,
+    pub source_sql: String,
+    pub source_options: SourceOptions,
+    /// If set, this engine is selected for requests without an engine hint whose LIMIT is at
+    /// most this many rows, e.g. to run small interactive queries on a low latency engine.
+    #[serde(default)]
+    pub max_limit: Option<u64>,
+}
+
+/// The [SourceEngine]s declared for a [DataSource][crate::model::data_stores::DataSource]. If
+/// none is selected, queries run via the connection and options of the source itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize, AsJsonb)]
+pub struct SourceEngines(pub Vec<SourceEngine>);
+
+impl SourceEngines {
+    pub fn get(&self, name: &str) -> Option<&SourceEngine> {
+        self.0.iter().find(|e| e.name == name)
+    }
+
+    /// Selects the engine named by the hint if there is one. Otherwise selects the engine with the
+    /// smallest max_limit which admits the query's limit, if the query has one.
+    pub fn select(&self, hint: Option<&str>, limit: Option<u64>) -> Option<&SourceEngine> {
+        if let Some(engine) = hint.and_then(|name| self.get(name)) {
+            return Some(engine);
+        }
+        let limit = limit?;
+        self.0
+            .iter()
+            .filter(|e| e.max_limit.is_some_and(|max| limit <= max))
+            .min_by_key(|e| e.max_limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::data_stores::options::flight_sql::FlightSQLSource;
+    use crate::model::data_stores::options::SourceOptions;
+
+    use super::{SourceEngine, SourceEngines};
+
+    fn engine(name: &str, max_limit: Option<u64>) -> SourceEngine {
+        SourceEngine {
+            name: name.to_string(),
+            data_con_name: format!("{name}_con"),
+            source_sql: "select * from t".to_string(),
+            source_options: SourceOptions::FlightSQL(FlightSQLSource {}),
+            max_limit,
+        }
+    }
+
+    #[test]
+    fn test_engine_selection() {
+        let engines = SourceEngines(vec![
+            engine("trino", None),
+            engine("medium", Some(10_000)),
+            engine("small", Some(100)),
+        ]);
+
+        assert_eq!(
+            engines.select(Some("trino"), Some(10)).unwrap().name,
+            "trino"
+        );
+        assert_eq!(engines.select(None, Some(10)).unwrap().name, "small");
+        assert_eq!(engines.select(None, Some(500)).unwrap().name, "medium");
+        assert_eq!(
+            engines.select(Some("unknown"), Some(500)).unwrap().name,
+            "medium"
+        );
+        assert!(engines.select(None, Some(50_000)).is_none());
+        assert!(engines.select(None, None).is_none());
+    }
+}
@@ -1,23 +1,31 @@
+pub mod engines;
 pub mod options;
+pub mod schedule;
 
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::schema::{data_connection, data_field, data_source};
+use crate::model::access_control::PolicyMode;
+use crate::schema::{data_connection, data_field, data_source, scratch_dataset};
 
+use self::engines::{SourceEngine, SourceEngines};
 use self::options::{ConnectionOptions, SourceOptions};
+use self::schedule::ExecutionWindows;
 
 /// A DataConnection is a collection of [DataSource]s which can be queried via a common
 /// connection. This could be an invidual database or an ObjectStore.
 #[derive(
-    Serialize, Deserialize, Queryable, Identifiable, Selectable, Debug, PartialEq, Eq, Hash,
+    Serialize, Deserialize, Queryable, Identifiable, Selectable, Debug, Clone, PartialEq, Eq, Hash,
 )]
 #[diesel(table_name = data_connection)]
 pub struct DataConnection {
     pub id: Uuid,
     pub name: String,
     pub connection_options: ConnectionOptions,
+    /// Restricts when queries may be executed against this connection, see [ExecutionWindows].
+    pub execution_windows: ExecutionWindows,
 }
 
 /// An individual table in a database or any collection of physical data which can be queried.
@@ -30,6 +38,7 @@ pub struct DataConnection {
     Identifiable,
     Associations,
     Debug,
+    Clone,
     PartialEq,
     Eq,
     Hash,
@@ -42,6 +51,35 @@ pub struct DataSource {
     pub source_sql: String,
     pub data_connection_id: Uuid,
     pub source_options: SourceOptions,
+    /// A paused [DataSource] is skipped when mapping new queries, allowing administrators to take
+    /// the backing system offline without failing every request which touches it.
+    pub paused: bool,
+    /// Additional engines which can query the same data, see [SourceEngines].
+    pub engines: SourceEngines,
+    /// Query executed as is against the [DataSource] which returns when its data was last
+    /// updated in its first column, e.g. select max(updated_at) from orders. Without one, only
+    /// [QueryRunner][crate::execute::data_stores::QueryRunner]s which can tell on their own,
+    /// e.g. from file modification times, report freshness.
+    pub freshness_query: Option<String>,
+    /// How the default permission combines with explicit grants, see [PolicyMode].
+    pub policy_mode: PolicyMode,
+}
+
+impl DataSource {
+    /// Returns a copy of this [DataSource] which queries the same data via the passed [SourceEngine].
+    pub fn with_engine(&self, engine: &SourceEngine) -> DataSource {
+        DataSource {
+            id: self.id,
+            name: self.name.clone(),
+            source_sql: engine.source_sql.clone(),
+            data_connection_id: self.data_connection_id,
+            source_options: engine.source_options.clone(),
+            paused: self.paused,
+            engines: SourceEngines::default(),
+            freshness_query: self.freshness_query.clone(),
+            policy_mode: self.policy_mode,
+        }
+    }
 }
 
 /// An invidual column or unit of [Information][crate::model::entity::Information]
@@ -65,11 +103,15 @@ pub struct DataField {
 #[derive(Queryable, Selectable, Insertable, Associations, Debug, PartialEq, AsChangeset)]
 #[diesel(belongs_to(DataConnection))]
 #[diesel(table_name = data_source)]
+#[diesel(treat_none_as_null = true)]
 pub struct NewDataSource {
     pub name: String,
     pub source_sql: String,
     pub data_connection_id: Uuid,
     pub source_options: SourceOptions,
+    pub engines: SourceEngines,
+    pub freshness_query: Option<String>,
+    pub policy_mode: PolicyMode,
 }
 
 /// Used to create a new [DataField] object in the database
@@ -81,3 +123,17 @@ pub struct NewDataField {
     pub data_source_id: Uuid,
     pub path: String,
 }
+
+/// A dataset uploaded by a user and queried via its own [DataConnection], see
+/// [create_scratch_dataset][crate::execute::scratch::create_scratch_dataset]. It is deleted along
+/// with its connection and entity once it expires.
+#[derive(Queryable, Selectable, Insertable, Associations, Debug, Clone, PartialEq)]
+#[diesel(belongs_to(DataConnection))]
+#[diesel(table_name = scratch_dataset)]
+pub struct ScratchDataset {
+    pub data_connection_id: Uuid,
+    /// The uploading [User][crate::model::user::User], under whose id the data is stored
+    pub user_id: Uuid,
+    pub dataset_name: String,
+    pub expires_at: DateTime<Utc>,
+}
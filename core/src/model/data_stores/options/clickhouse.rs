@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+/// Holds settings needed to connect to a ClickHouse server via its HTTP interface
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ClickHouseConnection {
+    /// e.g. http://clickhouse:8123 or https://clickhouse:8443
+    pub url: String,
+    /// Database which unqualified table names are resolved in. Defaults to the default
+    /// database of the user.
+    #[serde(default)]
+    pub database: Option<String>,
+    #[serde(default)]
+    pub user: Option<String>,
+    /// An environment variable which will hold the password of the user.
+    /// Note that this is NOT the plaintext password literally.
+    #[serde(default)]
+    pub password: Option<String>,
+    /// The bundle of trusted CA certs for validating an https server. If unset, the
+    /// bundled webpki root certificates are trusted.
+    #[serde(default)]
+    pub ca_cert_bundle: Option<String>,
+}
+
+/// Holds settings needed to query a specific table or view in a ClickHouse database
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ClickHouseSource {}
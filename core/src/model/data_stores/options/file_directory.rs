@@ -1,20 +1,195 @@
+use std::env;
+use std::str::FromStr;
+
+use arrow_schema::{DataType, Schema};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::error::MeshError;
+
 use super::{SourceFileType, SupportedObjectStore};
 
 /// Represents files (such as parquet or CSV) stored in an ObjectStore.
 /// Uses DataFusion's ListingTable table provider to query.
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FileDirectorySource {
     pub bucket: Option<String>,
     pub region: Option<String>,
     pub prefix: Option<String>,
     pub file_type: SourceFileType,
+    /// Settings of an S3 compatible object store which take precedence over the environment.
+    /// Ignored for other object store types.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s3: Option<Box<S3Options>>,
+    /// Location of the Hadoop cluster, required for the HDFS object store type. The prefix is
+    /// the directory on HDFS containing the files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hdfs: Option<HdfsOptions>,
+    /// If not empty, only files whose path below the prefix matches one of these globs are
+    /// queried, e.g. "events/2024/*/*.parquet". A "*" does not match across "/".
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Files whose path matches any of these globs are never queried.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// If set, only files whose path matches this regular expression are queried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_regex: Option<String>,
+    /// If set, only files modified after this time are queried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_after: Option<DateTime<Utc>>,
+    /// If set, files are read with this schema rather than one inferred from the files.
+    /// Partition columns are not part of the files, so must not be declared here.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub declared_schema: Option<DeclaredSchema>,
+    /// Hive style partition columns, in the order of the directories below the prefix, e.g.
+    /// year and month for "year=2024/month=07/part-0.parquet". They are queried like any other
+    /// column, and filters on them prune which directories are listed and scanned.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub partition_columns: Vec<PartitionColumn>,
+}
+
+/// A column whose value is encoded in the directory path of each file rather than in the file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PartitionColumn {
+    pub name: String,
+    pub data_type: DataType,
+}
+
+/// An Arrow [Schema] declared for the files of a [FileDirectorySource].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DeclaredSchema {
+    pub schema: Schema,
+    /// If true, every file is checked against the schema before a query runs, and the query
+    /// fails on the first file which is missing a declared field or stores it with a different
+    /// type. If false, files are read with the declared schema and values are cast where possible.
+    #[serde(default)]
+    pub strict: bool,
+}
+
+/// Identifies a Hadoop cluster which serves files via the WebHDFS REST API.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct HdfsOptions {
+    /// HTTP address of the namenode, e.g. "http://namenode:9870"
+    pub namenode_url: String,
+    /// User to act as on a cluster using simple (pseudo) authentication.
+    pub user: Option<String>,
+}
+
+/// Per source configuration of an S3 compatible object store, e.g. an on-prem MinIO deployment.
+/// Any setting which is not declared falls back to the AWS_* environment variables.
+#[derive(Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct S3Options {
+    /// Custom endpoint, e.g. "http://minio:9000". Plain HTTP is allowed if the endpoint uses it.
+    pub endpoint: Option<String>,
+    /// If true, requests use path style addressing (endpoint/bucket/key), as is typical for
+    /// MinIO. If false, virtual hosted style addressing (bucket.endpoint/key) is used.
+    pub path_style: Option<bool>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub session_token: Option<String>,
+}
+
+impl std::fmt::Debug for S3Options {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redacted = |v: &Option<String>| v.as_ref().map(|_| "<redacted>");
+        f.debug_struct("S3Options")
+            .field("endpoint", &self.endpoint)
+            .field("path_style", &self.path_style)
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &redacted(&self.secret_access_key))
+            .field("session_token", &redacted(&self.session_token))
+            .finish()
+    }
+}
+
+/// An ObjectStore in which results received from a particular peer relay are stored instead of
+/// the default result store, e.g. to keep an organization's data in its own bucket or region.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantResultStore {
+    pub object_store_type: SupportedObjectStore,
+    pub bucket: Option<String>,
+    pub region: Option<String>,
+    /// Results are stored below this prefix of the bucket.
+    pub prefix: Option<String>,
+    /// Settings, including credentials, which take precedence over the environment.
+    #[serde(default)]
+    pub s3: Option<Box<S3Options>>,
+    #[serde(default)]
+    pub client_config: ObjectStoreClientConfig,
+}
+
+impl TenantResultStore {
+    /// Returns the [FileDirectorySource] describing the location of the results.
+    pub fn source(&self) -> FileDirectorySource {
+        FileDirectorySource {
+            bucket: self.bucket.clone(),
+            region: self.region.clone(),
+            prefix: self.prefix.clone(),
+            file_type: SourceFileType::Parquet,
+            s3: self.s3.clone(),
+            hdfs: None,
+            include: vec![],
+            exclude: vec![],
+            path_regex: None,
+            modified_after: None,
+            declared_schema: None,
+            partition_columns: vec![],
+        }
+    }
 }
 
 /// Information needed to identify and connect to files in an ObjectStore
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FileDirectoryConnection {
     pub object_store_type: SupportedObjectStore,
     pub url: String,
+    #[serde(default)]
+    pub client_config: ObjectStoreClientConfig,
+}
+
+/// Request level configuration of the client used to reach an ObjectStore. Unset values use the
+/// defaults of the object_store crate. Retry and timeout settings only apply to cloud object stores.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ObjectStoreClientConfig {
+    /// Maximum number of times a failed request is retried.
+    pub max_retries: Option<usize>,
+    /// Maximum time from the initial request after which no further retries are attempted.
+    pub retry_timeout_secs: Option<u64>,
+    /// Timeout of each individual request.
+    pub request_timeout_secs: Option<u64>,
+    pub connect_timeout_secs: Option<u64>,
+    /// Maximum number of requests in flight to the ObjectStore at any time.
+    pub max_concurrent_requests: Option<usize>,
+    /// Maximum number of idle connections kept open per host.
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Size in bytes of the chunks in which written files are handed to a multipart upload.
+    /// Parts are never smaller than the minimum part size of the ObjectStore.
+    pub multipart_chunk_size: Option<usize>,
+}
+
+impl ObjectStoreClientConfig {
+    /// Reads each setting from an environment variable named by the prefix followed by the
+    /// setting in upper case, e.g. RESULT_OBJECT_STORE_MAX_RETRIES.
+    pub fn from_env(prefix: &str) -> Result<Self, MeshError> {
+        fn var<T: FromStr>(prefix: &str, name: &str) -> Result<Option<T>, MeshError> {
+            let key = format!("{prefix}_{name}");
+            match env::var(&key) {
+                Ok(val) => val
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| MeshError::SerDe(format!("Unable to parse {key}: {val}"))),
+                Err(_) => Ok(None),
+            }
+        }
+        Ok(Self {
+            max_retries: var(prefix, "MAX_RETRIES")?,
+            retry_timeout_secs: var(prefix, "RETRY_TIMEOUT_SECS")?,
+            request_timeout_secs: var(prefix, "REQUEST_TIMEOUT_SECS")?,
+            connect_timeout_secs: var(prefix, "CONNECT_TIMEOUT_SECS")?,
+            max_concurrent_requests: var(prefix, "MAX_CONCURRENT_REQUESTS")?,
+            pool_max_idle_per_host: var(prefix, "POOL_MAX_IDLE_PER_HOST")?,
+            multipart_chunk_size: var(prefix, "MULTIPART_CHUNK_SIZE")?,
+        })
+    }
 }
@@ -1,16 +1,16 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FlightSQLSource {}
 
 /// Information needed to identify and connect to a FlightSQL Endpoint
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FlightSqlConnection {
     pub endpoint: String,
     pub auth: FlightSQLAuth,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum FlightSQLAuth {
     Basic(BasicFlightSQLAuth),
     PKI(PKIFlightSQLAuth),
@@ -19,7 +19,7 @@ pub enum FlightSQLAuth {
     Unsecured,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct BasicFlightSQLAuth {
     /// The plaintext username for basic auth
     pub username: String,
@@ -28,7 +28,7 @@ pub struct BasicFlightSQLAuth {
     pub password: String,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PKIFlightSQLAuth {
     /// The public x509 cert pem file used to auth with the FlightSQL server
     pub client_cert_file: String,
@@ -5,17 +5,26 @@ use diesel::{AsExpression, FromSqlRow};
 
 use crate::error::MeshError;
 
+#[cfg(feature = "clickhouse")]
+use self::clickhouse::{ClickHouseConnection, ClickHouseSource};
 #[cfg(feature = "datafusion")]
 use self::file_directory::{FileDirectoryConnection, FileDirectorySource};
 use self::flight_sql::{FlightSQLSource, FlightSqlConnection};
+#[cfg(feature = "mysql")]
+use self::mysql::{MySqlConnection, MySqlSource};
+#[cfg(feature = "postgres")]
+use self::postgres::{PostgresConnection, PostgresSource};
 #[cfg(feature = "trino")]
 use self::trino::{TrinoConnection, TrinoSource};
 
+pub mod clickhouse;
 pub mod file_directory;
 pub mod flight_sql;
+pub mod mysql;
+pub mod postgres;
 pub mod trino;
 
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum SourceFileType {
     CSV,
     JSON,
@@ -31,6 +40,9 @@ pub enum SupportedObjectStore {
     Azure,
     #[cfg(feature = "os-gcp")]
     GCP,
+    /// Hadoop clusters, accessed via WebHDFS. Read only.
+    #[cfg(feature = "os-hdfs")]
+    HDFS,
 }
 
 impl TryFrom<String> for SupportedObjectStore {
@@ -44,9 +56,11 @@ impl TryFrom<String> for SupportedObjectStore {
             "Azure" => Ok(Self::Azure),
             #[cfg(feature = "os-gcp")]
             "GCP" => Ok(Self::GCP),
+            #[cfg(feature = "os-hdfs")]
+            "HDFS" => Ok(Self::HDFS),
             _ => Err(MeshError::SerDe(format!(
                 "Invalid Object Store variant specified: {value}. \
-            Valid values are LocalFileSystem, S3, Azure, or GCP"
+            Valid values are LocalFileSystem, S3, Azure, GCP or HDFS"
             ))),
         }
     }
@@ -54,7 +68,7 @@ impl TryFrom<String> for SupportedObjectStore {
 
 /// The suported [DataConnection][crate::model::data_stores::DataConnection] backend stores and contains
 /// the information needed to connect to each store.
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, AsJsonb)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, AsJsonb)]
 pub enum ConnectionOptions {
     /// Represents a collection of files in any ObjectStore compatible interface, such as S3
     /// azure blob, MinIO, or a local file system / network drive.
@@ -63,12 +77,20 @@ pub enum ConnectionOptions {
     #[cfg(feature = "trino")]
     Trino(TrinoConnection),
     FlightSQL(FlightSqlConnection),
+    #[cfg(feature = "postgres")]
+    Postgres(PostgresConnection),
+    #[cfg(feature = "clickhouse")]
+    ClickHouse(ClickHouseConnection),
+    #[cfg(feature = "mysql")]
+    MySql(MySqlConnection),
 }
 
 /// The suported [DataSource][crate::model::data_stores::DataSource] backend stores and contains
 /// the information needed to query a specific dataset within
 /// a [DataConnection][crate::model::data_stores::DataConnection].
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, AsJsonb)]
+// Options are deserialized once per query, so the variant size does not matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, AsJsonb)]
 pub enum SourceOptions {
     /// Represents a collection of files in any ObjectStore compatible interface, such as S3
     /// azure blob, MinIO, or a local file system / network drive.
@@ -77,4 +99,10 @@ pub enum SourceOptions {
     #[cfg(feature = "trino")]
     Trino(TrinoSource),
     FlightSQL(FlightSQLSource),
+    #[cfg(feature = "postgres")]
+    Postgres(PostgresSource),
+    #[cfg(feature = "clickhouse")]
+    ClickHouse(ClickHouseSource),
+    #[cfg(feature = "mysql")]
+    MySql(MySqlSource),
 }
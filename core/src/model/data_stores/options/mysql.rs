@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+/// Holds settings needed to connect to a MySQL database. The connection is not encrypted, so
+/// the database should only be reached over a trusted network.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MySqlConnection {
+    pub host: String,
+    pub port: u16,
+    pub database: String,
+    pub user: String,
+    /// An environment variable which will hold the password of the user.
+    /// Note that this is NOT the plaintext password literally.
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+/// Holds settings needed to query a specific table or view in a MySQL database
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MySqlSource {}
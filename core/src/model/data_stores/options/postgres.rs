@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+/// Holds settings needed to connect to a PostgreSQL database
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PostgresConnection {
+    pub host: String,
+    pub port: u16,
+    pub database: String,
+    pub user: String,
+    /// An environment variable which will hold the password of the user.
+    /// Note that this is NOT the plaintext password literally.
+    #[serde(default)]
+    pub password: Option<String>,
+    /// If set, the connection to the database is encrypted with TLS.
+    #[serde(default)]
+    pub tls: Option<PostgresTls>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PostgresTls {
+    /// The bundle of trusted CA certs for validating the database server. If unset, the
+    /// platform's native root certificates are trusted.
+    #[serde(default)]
+    pub ca_cert_bundle: Option<String>,
+}
+
+/// Holds settings needed to query a specific table or view in a PostgreSQL database
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PostgresSource {}
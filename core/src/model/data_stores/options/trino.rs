@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 /// Holds settings needed to connect to a Trino cluster
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TrinoConnection {
     pub user: String,
     pub password: String,
@@ -11,5 +11,5 @@ pub struct TrinoConnection {
 }
 
 /// Holds settings needed to query a specific table via a trino cluster
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TrinoSource {}
@@ -0,0 +1,112 @@
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use diesel::{AsExpression, FromSqlRow};
+use diesel_as_jsonb::AsJsonb;
+use serde::{Deserialize, Serialize};
+
+/// A recurring window of time (in UTC) during which queries may be executed against
+/// a [DataConnection][crate::model::data_stores::DataConnection]. If end is before start,
+/// the window wraps past midnight into the following day.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ExecutionWindow {
+    /// Days of the week on which the window opens. If empty, the window opens every day.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Time of day at which the window opens, e.g. "22:00:00"
+    pub start: NaiveTime,
+    /// Time of day at which the window closes, e.g. "06:00:00"
+    pub end: NaiveTime,
+}
+
+impl ExecutionWindow {
+    fn opens_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    /// Returns true if the window is open at the passed time.
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        let time = at.time();
+        let day = at.weekday();
+        if self.start <= self.end {
+            self.opens_on(day) && self.start <= time && time < self.end
+        } else {
+            (self.opens_on(day) && time >= self.start)
+                || (self.opens_on(day.pred()) && time < self.end)
+        }
+    }
+
+    /// Returns the next time strictly after the passed time at which this window opens.
+    pub fn next_open(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        (0..=7)
+            .map(|offset| after.date_naive() + Duration::days(offset))
+            .filter(|date| self.opens_on(date.weekday()))
+            .map(|date| date.and_time(self.start).and_utc())
+            .find(|candidate| *candidate > after)
+    }
+}
+
+/// The set of [ExecutionWindow]s declared for a
+/// [DataConnection][crate::model::data_stores::DataConnection]. No declared windows means
+/// queries may be executed at any time.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize, AsJsonb)]
+pub struct ExecutionWindows(pub Vec<ExecutionWindow>);
+
+impl ExecutionWindows {
+    /// Returns true if queries may be executed at the passed time.
+    pub fn is_open(&self, at: DateTime<Utc>) -> bool {
+        self.0.is_empty() || self.0.iter().any(|w| w.contains(at))
+    }
+
+    /// Returns None if queries may be executed at the passed time, otherwise the earliest
+    /// time at which one of the windows opens.
+    pub fn next_open(&self, at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if self.is_open(at) {
+            return None;
+        }
+        self.0.iter().filter_map(|w| w.next_open(at)).min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, NaiveTime, TimeZone, Utc, Weekday};
+
+    use super::{ExecutionWindow, ExecutionWindows};
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        // 2024-01-01 is a Monday
+        Utc.with_ymd_and_hms(2024, 1, day, hour, 0, 0).unwrap()
+    }
+
+    fn nightly(days: Vec<Weekday>) -> ExecutionWindows {
+        ExecutionWindows(vec![ExecutionWindow {
+            days,
+            start: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(6, 0, 0).unwrap(),
+        }])
+    }
+
+    #[test]
+    fn test_no_windows_always_open() {
+        let windows = ExecutionWindows::default();
+        assert!(windows.is_open(at(1, 12)));
+        assert_eq!(windows.next_open(at(1, 12)), None);
+    }
+
+    #[test]
+    fn test_window_wraps_midnight() {
+        let windows = nightly(vec![Weekday::Mon]);
+        assert!(windows.is_open(at(1, 23)));
+        assert!(windows.is_open(at(2, 5)));
+        assert!(!windows.is_open(at(2, 23)));
+        assert!(!windows.is_open(at(1, 12)));
+        assert_eq!(windows.next_open(at(1, 12)), Some(at(1, 22)));
+        assert_eq!(windows.next_open(at(2, 12)), Some(at(8, 22)));
+    }
+
+    #[test]
+    fn test_every_day_window() {
+        let windows = nightly(vec![]);
+        assert!(windows.is_open(at(3, 2)));
+        assert_eq!(windows.next_open(at(3, 7)), Some(at(3, 22)));
+    }
+}
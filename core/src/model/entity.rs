@@ -1,4 +1,5 @@
 use arrow_schema::DataType;
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 
 use diesel::{prelude::Insertable, AsExpression, FromSqlRow};
@@ -6,7 +7,8 @@ use diesel_as_jsonb::AsJsonb;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::schema::{entities, information};
+use crate::model::data_stores::DataSource;
+use crate::schema::{entities, entity_validation, information};
 
 /// Represents a name-space for a collection of [Information] which is scoped to an individual
 /// [Relay][crate::model::relay::Relay]. Even if two [Relay][crate::model::relay::Relay]s
@@ -19,6 +21,9 @@ use crate::schema::{entities, information};
 pub struct Entity {
     pub id: Uuid,
     pub name: String,
+    /// An optional query expressed in terms of this Entity, e.g. `select count(*) from entity`,
+    /// which the relay periodically runs against each mapped [DataSource] to detect broken mappings.
+    pub validation_query: Option<String>,
 }
 
 /// Represents a distinct unit of information scoped to an individual [Entity] within an individual
@@ -37,6 +42,10 @@ pub struct Information {
     pub name: String,
     pub arrow_dtype: ArrowDataType,
     pub entity_id: Uuid,
+    /// Position of the column in the canonical schema of the [Entity]. Information without a
+    /// position is ordered after all others, by name.
+    pub position: Option<i32>,
+    pub nullable: bool,
 }
 
 /// NewType wrapper of [DataType]
@@ -53,4 +62,39 @@ pub struct NewInformation {
     pub name: String,
     pub arrow_dtype: ArrowDataType,
     pub entity_id: Uuid,
+    pub position: Option<i32>,
+    pub nullable: bool,
+}
+
+/// The most recent result of running an [Entity]'s validation_query against a mapped [DataSource].
+#[derive(
+    Serialize, Deserialize, Queryable, Selectable, Identifiable, Associations, Debug, PartialEq,
+)]
+#[diesel(belongs_to(Entity))]
+#[diesel(belongs_to(DataSource))]
+#[diesel(table_name = entity_validation)]
+pub struct EntityValidation {
+    pub id: Uuid,
+    pub entity_id: Uuid,
+    pub data_source_id: Uuid,
+    pub success: bool,
+    /// The count returned by the validation query, or the number of rows returned if the
+    /// query does not return a single integer.
+    pub row_count: Option<i64>,
+    pub error: Option<String>,
+    pub checked_at: DateTime<Utc>,
+    pub last_success_at: Option<DateTime<Utc>>,
+}
+
+/// Used to record a new [EntityValidation] result in the database
+#[derive(Insertable, Debug, PartialEq, AsChangeset)]
+#[diesel(table_name = entity_validation)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewEntityValidation {
+    pub entity_id: Uuid,
+    pub data_source_id: Uuid,
+    pub success: bool,
+    pub row_count: Option<i64>,
+    pub error: Option<String>,
+    pub checked_at: DateTime<Utc>,
 }
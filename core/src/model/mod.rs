@@ -1,8 +1,13 @@
 pub mod access_control;
+pub mod audit;
 pub mod config_commands;
 pub mod data_stores;
 pub mod entity;
 pub mod mappings;
 pub mod query;
+pub mod quota;
 pub mod relay;
+pub mod role;
+pub mod usage;
 pub mod user;
+pub mod validation;
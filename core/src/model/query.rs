@@ -1,7 +1,13 @@
-use super::{data_stores::DataSource, relay::Relay, user::User};
-use crate::schema::{incoming_flight_streams, query_request, query_task, query_task_remote};
+use std::collections::HashMap;
+
+use super::{access_control::SourcePermission, data_stores::DataSource, relay::Relay, user::User};
+use crate::error::{MeshError, Result};
+use crate::schema::{
+    dead_letter, incoming_flight_streams, query_request, query_task, query_task_remote, task_outbox,
+};
 
 use arrow_schema::Schema;
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use diesel::{AsExpression, FromSqlRow};
 use diesel_as_jsonb::AsJsonb;
@@ -23,15 +29,23 @@ pub struct Query {
     pub return_schema: Option<Schema>,
 }
 
-#[derive(Serialize, Deserialize, Debug, AsJsonb, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, AsJsonb, PartialEq)]
 /// This is the original, unresolved query request which is either
 /// recieved directly by a [User] or indirectly via a peered [Relay].
 /// Each relay processing a QueryRequest will need to resolve it
 /// to [Query] objects which can be executed against local [DataSource]s.
 pub struct RawQueryRequest {
     /// A raw SQL string, expressed in terms of [Entity][crate::model::entity::Entity]
-    /// and [Information][crate::model::entity::Information].
+    /// and [Information][crate::model::entity::Information]. Left empty for batch requests,
+    /// which set statements instead.
+    #[serde(default)]
     pub sql: String,
+    /// An ordered list of SQL statements submitted as a single batch request. Each statement is
+    /// executed as its own group of tasks, whose results are retrieved by statement index.
+    /// Statements are forwarded to peered relays one by one, so only the relay which received
+    /// the batch from a [User] ever sets this.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub statements: Vec<String>,
     /// This is the globally unique [Uuid] for the query request, which is required for handling
     /// cyclical relay network topologies. If the same Uuid is encountered twice, the request should be
     /// acknowledged as already in progress. This corresponds to the Uuid of the [QueryRequest] on the
@@ -50,6 +64,51 @@ pub struct RawQueryRequest {
     /// e.g. how the schema of a JSON or CSV file is inferred.
     #[serde(default = "no_schema")]
     pub return_arrow_schema: Option<Schema>,
+    /// Requests that local tasks run on the [SourceEngine][crate::model::data_stores::engines::SourceEngine]
+    /// with this name, for [DataSource]s which declare one.
+    #[serde(default)]
+    pub engine_hint: Option<String>,
+    /// If true, each [DataSource] only counts the rows it would return for the request and
+    /// a single row with the count is returned per [DataSource] instead of the data itself.
+    #[serde(default)]
+    pub count_only: bool,
+    /// Marks a request as interactive, e.g. submitted from a console. Each relay limits
+    /// interactive requests without an explicit LIMIT to its configured default limit.
+    #[serde(default)]
+    pub interactive: bool,
+    /// Explicitly executes an earlier request again. Set to the request_uuid of the earlier
+    /// request, while request_uuid identifies this new execution. Only the [User] who submitted
+    /// the earlier request may execute it again.
+    #[serde(default)]
+    pub reexecution_of: Option<Uuid>,
+    /// Hints which tune how each relay plans and executes the request, e.g.
+    /// {"engine": "trino", "max_rows": 100}, see [QueryHints][crate::execute::hints::QueryHints].
+    /// Forwarded to peered relays along with the request.
+    #[serde(default)]
+    pub hints: HashMap<String, serde_json::Value>,
+}
+
+impl RawQueryRequest {
+    /// Splits a batch request into one request per statement, in order. A request which is not
+    /// a batch is returned as its only statement.
+    pub fn split_statements(&self) -> Result<Vec<RawQueryRequest>> {
+        match (self.sql.is_empty(), self.statements.is_empty()) {
+            (false, true) => Ok(vec![self.clone()]),
+            (true, false) => Ok(self
+                .statements
+                .iter()
+                .map(|sql| RawQueryRequest {
+                    sql: sql.clone(),
+                    statements: vec![],
+                    ..self.clone()
+                })
+                .collect()),
+            (false, false) => Err(MeshError::InvalidQuery(
+                "A query request must set either sql or statements, but not both!".to_string(),
+            )),
+            (true, true) => Err(MeshError::EmptyQuery),
+        }
+    }
 }
 
 fn no_schema() -> Option<Schema> {
@@ -85,6 +144,48 @@ pub struct QueryRequest {
     pub sql: String,
     pub relay_id: Uuid,
     pub origin_info: QueryOriginationInfo,
+    pub received_at: DateTime<Utc>,
+    /// Set once the request is older than the dedup retention window, after which its
+    /// originator_request_id no longer marks new requests as duplicates.
+    pub retired: bool,
+    /// The originator_request_id of an earlier request which this request explicitly executes again.
+    pub reexecution_of: Option<Uuid>,
+    /// The id of an earlier request which this request replays for debugging, see
+    /// [replay_request][crate::execute::replay::replay_request]. Results of replays are never
+    /// delivered to the [User] who submitted the earlier request.
+    pub replay_of: Option<Uuid>,
+    /// The statements of a batch request, see [RawQueryRequest::statements]. Empty unless the
+    /// request is a batch, in which case sql holds the statements joined for display.
+    pub statements: Vec<String>,
+    /// Trace context of the call which created the request, if it was traced.
+    pub trace_context: Option<TraceContext>,
+    /// When an OpenLineage event was emitted for the request, see
+    /// [lineage][crate::execute::lineage].
+    pub lineage_emitted_at: Option<DateTime<Utc>>,
+    /// How long the results of the request are retained after they were produced, or None to
+    /// retain them forever. See [QueryHints::result_ttl_secs][crate::execute::hints::QueryHints].
+    pub result_ttl_secs: Option<i64>,
+    /// Format the results of the request are stored in.
+    pub result_format: ResultFormat,
+}
+
+impl QueryRequest {
+    /// Returns the SQL of each statement of the request, in order.
+    pub fn statement_sql(&self) -> Vec<&str> {
+        if self.statements.is_empty() {
+            vec![self.sql.as_str()]
+        } else {
+            self.statements.iter().map(|s| s.as_str()).collect()
+        }
+    }
+}
+
+/// The W3C trace context of a call, e.g. its traceparent and tracestate headers, which is
+/// propagated to peered relays so that a query can be traced across the relay network. See
+/// [telemetry][crate::execute::telemetry] for how it is extracted and injected.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, AsJsonb)]
+pub struct TraceContext {
+    pub fields: HashMap<String, String>,
 }
 
 /// Contains information about the origin of a [QueryRequest], which
@@ -113,8 +214,80 @@ pub struct QueryTask {
     pub id: Uuid,
     pub query_request_id: Uuid,
     pub data_source_id: Uuid,
+    /// The final SQL after mapping the request to the [DataSource] and applying permissions.
     pub task: Query,
     pub status: QueryTaskStatus,
+    /// If set, the task is deferred and must not be dispatched for execution before this time,
+    /// see [ExecutionWindows][crate::model::data_stores::schedule::ExecutionWindows].
+    pub not_before: Option<DateTime<Utc>>,
+    /// Name of the [SourceEngine][crate::model::data_stores::engines::SourceEngine] of the
+    /// [DataSource] which executes the task, or None to use the [DataSource] itself.
+    pub engine: Option<String>,
+    /// Snapshot of the [SourcePermission] which was applied when mapping the task, retained
+    /// for debugging. None for tasks created before permissions were recorded.
+    pub permission: Option<SourcePermission>,
+    /// Statistics about the data scanned to complete the task, if reported by the
+    /// [QueryRunner][crate::execute::data_stores::QueryRunner].
+    pub scan_metrics: Option<ScanMetrics>,
+    /// When a query_runner began executing the task.
+    pub started_at: Option<DateTime<Utc>>,
+    /// When the task reached [QueryTaskStatus::Complete] or [QueryTaskStatus::Failed].
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Last time the query_runner executing the task reported it is still running. A task which
+    /// is InProgress without a recent heartbeat was abandoned by a crashed query_runner.
+    pub heartbeat_at: Option<DateTime<Utc>>,
+    /// How many times a query_runner began executing the task.
+    pub attempts: i32,
+    /// When the data of the [DataSource] was last updated, as reported when the task executed.
+    pub freshness: Option<DateTime<Utc>>,
+    /// Index of the statement of a batch [QueryRequest] which the task executes, 0 otherwise.
+    pub statement_index: i32,
+    /// When the result of the task was deleted because the retention of its [QueryRequest]
+    /// elapsed.
+    pub result_expired_at: Option<DateTime<Utc>>,
+    /// When the message about the task was last published to the message broker.
+    pub dispatched_at: Option<DateTime<Utc>>,
+}
+
+/// Statistics reported by [QueryRunner][crate::execute::data_stores::QueryRunner]s which scan
+/// files directly, used to tune file layout and mappings so that queries prune as much as possible.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, AsJsonb, PartialEq)]
+pub struct ScanMetrics {
+    pub files_scanned: usize,
+    /// Parquet row groups skipped based on column statistics or bloom filters.
+    pub row_groups_pruned: usize,
+    /// Rows skipped based on the Parquet page index.
+    pub page_index_rows_filtered: usize,
+    /// Rows removed by filters evaluated while decoding Parquet.
+    pub pushdown_rows_filtered: usize,
+    pub bytes_scanned: usize,
+    pub output_rows: usize,
+}
+
+/// Current load of a [DataSource], used to estimate when newly submitted tasks will run.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SourceQueueStats {
+    pub data_source_id: Uuid,
+    pub data_source_name: String,
+    /// Tasks waiting to be dispatched, not counting tasks deferred to an execution window.
+    pub queued: i64,
+    pub in_progress: i64,
+    /// Average time from start to completion of the most recently completed tasks, or None if
+    /// no task of the source has completed yet.
+    pub avg_task_latency_ms: Option<i64>,
+}
+
+impl SourceQueueStats {
+    /// Estimates when own tasks, which are already counted in queued, start and complete if
+    /// submitted at now. Tasks ahead of them are assumed to run one at a time, so this is an
+    /// upper bound when the query_runner executes tasks of the source concurrently.
+    pub fn estimate(&self, now: DateTime<Utc>, own: i64) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let latency = self.avg_task_latency_ms?;
+        let ahead = (self.queued + self.in_progress - own).max(0);
+        let start = now + chrono::Duration::milliseconds(latency.saturating_mul(ahead));
+        let completion = start + chrono::Duration::milliseconds(latency.saturating_mul(own.max(1)));
+        Some((start, completion))
+    }
 }
 
 /// Used to create a new [QueryTask] object in the database
@@ -126,6 +299,34 @@ pub struct NewQueryTask {
     pub data_source_id: Uuid,
     pub task: Query,
     pub status: QueryTaskStatus,
+    pub not_before: Option<DateTime<Utc>>,
+    pub engine: Option<String>,
+    pub permission: Option<SourcePermission>,
+    pub statement_index: i32,
+}
+
+/// Format in which the [ResultManager][crate::execute::result_manager::ResultManager] stores
+/// the results of a [QueryRequest]. Parquet is compact, while Arrow IPC skips encoding and
+/// decoding, so results are served with less latency at the cost of storage.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, diesel_derive_enum::DbEnum,
+)]
+#[ExistingTypePath = "crate::schema::sql_types::ResultFormat"]
+#[serde(rename_all = "snake_case")]
+pub enum ResultFormat {
+    #[default]
+    Parquet,
+    ArrowIpc,
+}
+
+impl ResultFormat {
+    /// Extension of the files of results stored in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ResultFormat::Parquet => "parquet",
+            ResultFormat::ArrowIpc => "arrow",
+        }
+    }
 }
 
 /// Represents the status of a [QueryTask]. Only used in asynchronous execution mode.
@@ -154,6 +355,16 @@ pub struct QueryTaskRemote {
     pub relay_id: Uuid,
     pub task: RawQueryRequest,
     pub status: QueryTaskRemoteStatus,
+    /// How many times submitting the task to its relay was attempted.
+    pub attempts: i32,
+    /// Why the last attempt to submit the task failed, if it did.
+    pub error: Option<String>,
+    /// Index of the statement of a batch [QueryRequest] which the task executes, 0 otherwise.
+    pub statement_index: i32,
+    /// When the message about the task was last published to the message broker.
+    pub dispatched_at: Option<DateTime<Utc>>,
+    /// If set, submitting the task failed and is retried once this time has passed.
+    pub not_before: Option<DateTime<Utc>>,
 }
 
 /// Represents the status of a [QueryTaskRemote]
@@ -193,12 +404,21 @@ pub struct FlightStream {
     pub remote_fingerprint: String,
     pub flight_id: Uuid,
     pub status: FlightStreamStatus,
+    /// Last time the stream was written to or changed status. A stream which stays Started
+    /// without being updated was abandoned, e.g. because the flight_server crashed.
+    pub updated_at: DateTime<Utc>,
+    /// When the data of the remote source was last updated, as reported by the sending relay.
+    pub freshness: Option<DateTime<Utc>>,
+    /// When the received result was deleted because the retention of its [QueryRequest]
+    /// elapsed.
+    pub result_expired_at: Option<DateTime<Utc>>,
 }
 
 /// Used to create a [FlightStream] object in the database
 #[derive(Queryable, Insertable, Selectable, Associations, Debug, PartialEq, AsChangeset)]
 #[diesel(belongs_to(QueryTaskRemote))]
 #[diesel(table_name = incoming_flight_streams)]
+#[diesel(treat_none_as_null = true)]
 /// Insertable form of [FlightStream]
 pub struct NewFlightStream {
     /// The id of the local [QueryTaskRemote] object
@@ -207,6 +427,109 @@ pub struct NewFlightStream {
     pub remote_fingerprint: String,
     pub flight_id: Uuid,
     pub status: FlightStreamStatus,
+    pub freshness: Option<DateTime<Utc>>,
+}
+
+/// Describes a single result, i.e. the data returned by one [DataSource]. Sent as JSON
+/// app_metadata of the first [FlightData][arrow_flight::FlightData] of a do_put and of each
+/// [FlightEndpoint][arrow_flight::FlightEndpoint] served for a request.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ResultMetadata {
+    /// When the data of the [DataSource] was last updated, if it reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub freshness: Option<DateTime<Utc>>,
+}
+
+/// Used as a [Ticket][arrow_flight::Ticket] to retrieve a stored result of a [QueryRequest]
+/// submitted via the rest_server, rather than executing a [QueryTask] again. The result_id is the
+/// id of a complete local [QueryTask] or the flight_id of a complete [FlightStream].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StoredResultTicket {
+    pub request_id: Uuid,
+    pub result_id: Uuid,
+}
+
+/// Type of the Flight [Action][arrow_flight::Action] with which a [User] cancels a
+/// [QueryRequest] they submitted. The body is a JSON encoded [QueryAction] and the single result
+/// a JSON encoded [CancelledQuery].
+pub const CANCEL_QUERY_ACTION: &str = "CancelQuery";
+
+/// Type of the Flight [Action][arrow_flight::Action] with which a [User] executes a
+/// [QueryRequest] they submitted again, bypassing the result cache. The body is a JSON encoded
+/// [QueryAction] and the single result the protobuf encoded
+/// [FlightInfo][arrow_flight::FlightInfo] of the new request.
+pub const REFRESH_RESULT_ACTION: &str = "RefreshResult";
+
+/// Type of the Flight [Action][arrow_flight::Action] which lists the Entities of the relay. The
+/// body is empty and the single result a JSON encoded map from entity name to its
+/// [InformationSummary][crate::execute::inspect::InformationSummary]s.
+pub const LIST_ENTITIES_ACTION: &str = "ListEntities";
+
+/// Body of the Flight actions which act on a [QueryRequest], identified by the request_id of
+/// the [FlightInfoMetadata] of its [FlightInfo][arrow_flight::FlightInfo].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueryAction {
+    pub request_id: Uuid,
+}
+
+/// Result of the [CANCEL_QUERY_ACTION], counting the tasks which were cancelled. Tasks which
+/// already completed or failed are left as they are.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CancelledQuery {
+    pub tasks: usize,
+    pub remote_tasks: usize,
+}
+
+/// Sent as the app_metadata of the [FlightInfo][arrow_flight::FlightInfo] returned by
+/// get_flight_info, so that clients can refer to the [QueryRequest] in later actions.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FlightInfoMetadata {
+    pub request_id: Uuid,
+}
+
+/// Response of the rest_server to a submitted [RawQueryRequest], which peered relays parse to
+/// confirm that a forwarded request was accepted.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SubmitQueryResponse {
+    /// Id of the [QueryRequest] created by the receiving relay.
+    pub id: Uuid,
+    /// Latest time at which a local task is expected to start, based on the queue depth of its
+    /// source or, for tasks deferred because their connection is outside of its declared
+    /// execution windows, the time the window opens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_start: Option<DateTime<Utc>>,
+    /// Latest time at which a local task is expected to complete, based on the recent average
+    /// latency of tasks of its source. Unset if no source has completed a task yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_completion: Option<DateTime<Utc>>,
+    /// Issues with the query which did not prevent its execution, e.g. use of a deprecated alias.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl SubmitQueryResponse {
+    pub fn new(id: Uuid) -> Self {
+        Self {
+            id,
+            estimated_start: None,
+            estimated_completion: None,
+            warnings: vec![],
+        }
+    }
+}
+
+/// A result of a [QueryRequest] which is stored by the
+/// [ResultManager][crate::execute::result_manager::ResultManager].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredResult {
+    /// Id of the local [QueryTask] or of the [FlightStream] which produced the result.
+    pub id: Uuid,
+    /// The [Relay] which sent the result, if it is a [FlightStream].
+    pub relay_id: Option<Uuid>,
+    /// When the data of the source which returned the result was last updated, if reported.
+    pub freshness: Option<DateTime<Utc>>,
+    /// Index of the statement of a batch [QueryRequest] which the result answers.
+    pub statement_index: i32,
 }
 
 /// Indicates the status of a [FlightStream]
@@ -222,3 +545,118 @@ pub enum FlightStreamStatus {
     /// A valid do_put call was received and completed successfully
     Complete,
 }
+
+#[derive(Queryable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = task_outbox)]
+/// A [GenericMessage][crate::messaging::GenericMessage] about a [QueryTask] or [QueryTaskRemote]
+/// which is written in the same transaction as the task itself and published to the message
+/// broker afterwards, so that a crash in between cannot strand the task.
+pub struct OutboxMessage {
+    pub id: i64,
+    pub message: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    /// The task the message is about.
+    pub task_id: Option<Uuid>,
+}
+
+/// Used to create a new [OutboxMessage] in the database.
+#[derive(Insertable, Debug, PartialEq)]
+#[diesel(table_name = task_outbox)]
+pub struct NewOutboxMessage {
+    pub message: serde_json::Value,
+    pub task_id: Uuid,
+}
+
+#[derive(Queryable, Selectable, Serialize, Debug, PartialEq)]
+#[diesel(table_name = dead_letter)]
+/// A [GenericMessage][crate::messaging::GenericMessage] which the query_runner failed to
+/// process. A message about a task is retried until it failed the configured number of
+/// attempts, after which it is dead and kept until an administrator replays it.
+pub struct DeadLetter {
+    pub id: i64,
+    /// The id of the [QueryTask] or [QueryTaskRemote] the message is about, or None if the
+    /// message could not be parsed.
+    pub task_id: Option<Uuid>,
+    pub message: Option<serde_json::Value>,
+    /// The error of the most recent attempt
+    pub error: String,
+    pub attempts: i32,
+    pub dead: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Used to create a new [DeadLetter] in the database.
+#[derive(Insertable, Debug, PartialEq)]
+#[diesel(table_name = dead_letter)]
+pub struct NewDeadLetter {
+    pub task_id: Option<Uuid>,
+    pub message: Option<serde_json::Value>,
+    pub error: String,
+    pub dead: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use uuid::Uuid;
+
+    use crate::error::MeshError;
+
+    use super::{RawQueryRequest, SourceQueueStats, SubmitQueryResponse};
+
+    #[test]
+    fn test_queue_estimate() {
+        let now = Utc.with_ymd_and_hms(2024, 7, 31, 0, 0, 0).unwrap();
+        let mut stats = SourceQueueStats {
+            data_source_id: Uuid::new_v4(),
+            data_source_name: "source".to_string(),
+            queued: 4,
+            in_progress: 1,
+            avg_task_latency_ms: None,
+        };
+        assert_eq!(stats.estimate(now, 2), None);
+
+        stats.avg_task_latency_ms = Some(1000);
+        let (start, completion) = stats.estimate(now, 2).unwrap();
+        assert_eq!(start, now + Duration::seconds(3));
+        assert_eq!(completion, now + Duration::seconds(5));
+    }
+    #[test]
+    fn test_split_statements() {
+        let mut request: RawQueryRequest = serde_json::from_str(r#"{"sql": "select 1"}"#).unwrap();
+        let split = request.split_statements().unwrap();
+        assert_eq!(split.len(), 1);
+        assert_eq!(split[0].sql, "select 1");
+
+        request.statements = vec!["select 2".to_string(), "select 3".to_string()];
+        assert!(matches!(
+            request.split_statements(),
+            Err(MeshError::InvalidQuery(_))
+        ));
+
+        request.sql = String::new();
+        let split = request.split_statements().unwrap();
+        let sql = split.iter().map(|r| r.sql.as_str()).collect::<Vec<_>>();
+        assert_eq!(sql, vec!["select 2", "select 3"]);
+        assert!(split.iter().all(|r| r.statements.is_empty()));
+
+        request.statements.clear();
+        assert!(matches!(
+            request.split_statements(),
+            Err(MeshError::EmptyQuery)
+        ));
+    }
+    #[test]
+    fn test_submit_query_response() {
+        let response = SubmitQueryResponse::new(Uuid::new_v4());
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(json, format!(r#"{{"id":"{}"}}"#, response.id));
+        assert_eq!(
+            serde_json::from_str::<SubmitQueryResponse>(&json).unwrap(),
+            response
+        );
+
+        assert!(serde_json::from_str::<SubmitQueryResponse>("Accepted").is_err());
+    }
+}
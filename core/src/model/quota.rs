@@ -0,0 +1,120 @@
+use std::env;
+
+use crate::schema::quota;
+
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Whether a [Quota] limits a [User][crate::model::user::User] or a peer
+/// [Relay][crate::model::relay::Relay].
+#[derive(
+    Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy, diesel_derive_enum::DbEnum,
+)]
+#[ExistingTypePath = "crate::schema::sql_types::QuotaSubject"]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaSubject {
+    /// Limits the requests a user submits directly to the local relay.
+    User,
+    /// Limits the requests a peer relay forwards to the local relay.
+    Relay,
+}
+
+/// Limits the query requests of a user or peer relay, identified by the fingerprint of its
+/// certificate. Limits which are unset fall back to the default of the subject, see
+/// [Quota::default_for], and a limit of 0 is unlimited.
+#[derive(
+    Queryable, Selectable, Insertable, AsChangeset, Serialize, Deserialize, Debug, PartialEq, Clone,
+)]
+#[diesel(table_name = quota)]
+pub struct Quota {
+    pub subject: QuotaSubject,
+    /// Sha256 Fingerprint of the DER encoded certificate
+    pub x509_sha256: String,
+    /// Maximum number of query requests which may be running at once.
+    pub max_concurrent_requests: Option<i32>,
+    /// Maximum number of query requests which may be submitted per minute.
+    pub max_requests_per_minute: Option<i32>,
+}
+
+impl Quota {
+    /// Returns the quota of subjects without a declared one, read from
+    /// USER_MAX_CONCURRENT_REQUESTS and USER_MAX_REQUESTS_PER_MINUTE, or
+    /// RELAY_MAX_CONCURRENT_REQUESTS and RELAY_MAX_REQUESTS_PER_MINUTE. All default to 0, which
+    /// is unlimited.
+    pub fn default_for(subject: QuotaSubject, x509_sha256: &str) -> Quota {
+        let prefix = match subject {
+            QuotaSubject::User => "USER",
+            QuotaSubject::Relay => "RELAY",
+        };
+        let limit = |name: &str| -> Option<i32> {
+            let var = format!("{prefix}_{name}");
+            let limit: i32 = env::var(&var)
+                .unwrap_or("0".to_string())
+                .parse()
+                .unwrap_or_else(|_| panic!("Unable to parse {var} as i32!"));
+            Some(limit)
+        };
+        Quota {
+            subject,
+            x509_sha256: x509_sha256.to_string(),
+            max_concurrent_requests: limit("MAX_CONCURRENT_REQUESTS"),
+            max_requests_per_minute: limit("MAX_REQUESTS_PER_MINUTE"),
+        }
+    }
+
+    /// Fills the limits which are unset from default.
+    pub fn or(self, default: Quota) -> Quota {
+        Quota {
+            max_concurrent_requests: self
+                .max_concurrent_requests
+                .or(default.max_concurrent_requests),
+            max_requests_per_minute: self
+                .max_requests_per_minute
+                .or(default.max_requests_per_minute),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Quota, QuotaSubject};
+    use crate::model::config_commands::{ConfigCommand, ConfigObject};
+
+    fn quota(concurrent: Option<i32>, per_minute: Option<i32>) -> Quota {
+        Quota {
+            subject: QuotaSubject::Relay,
+            x509_sha256: "abc".to_string(),
+            max_concurrent_requests: concurrent,
+            max_requests_per_minute: per_minute,
+        }
+    }
+
+    #[test]
+    fn test_declared_limits_override_defaults() {
+        let declared = quota(Some(2), None).or(quota(Some(5), Some(60)));
+        assert_eq!(declared, quota(Some(2), Some(60)));
+        // A declared limit of 0 lifts the default
+        let unlimited = quota(Some(0), None).or(quota(Some(5), None));
+        assert_eq!(unlimited.max_concurrent_requests, Some(0));
+    }
+
+    #[test]
+    fn test_quota_declaration() {
+        let command: ConfigCommand = serde_json::from_value(serde_json::json!({
+            "api_version": "v1",
+            "kind": "Quota",
+            "spec": {"relay": "partner", "max_concurrent_requests": 4}
+        }))
+        .unwrap();
+        match command.config_object {
+            ConfigObject::Quota(quota) => {
+                assert_eq!(quota.relay.as_deref(), Some("partner"));
+                assert_eq!(quota.user_x509_cert_file, None);
+                assert_eq!(quota.max_concurrent_requests, Some(4));
+                assert_eq!(quota.max_requests_per_minute, None);
+            }
+            other => panic!("Parsed unexpected config object {other:?}"),
+        }
+    }
+}
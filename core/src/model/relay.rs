@@ -1,5 +1,6 @@
-use crate::schema::relays;
+use crate::schema::{relay_invites, relay_status, relays};
 
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -50,4 +51,62 @@ pub struct NewRelay {
     pub x509_subject: String,
     /// X509 Issuer Distinguished NAme
     pub x509_issuer: String,
+    /// PEM encoded certificate, if it is known. Left unchanged on update if None.
+    pub x509_cert: Option<Vec<u8>>,
+}
+
+/// An invite issued to a peer [Relay], see [crate::execute::invite]. The secret signs the invite
+/// token and never leaves the issuing relay.
+#[derive(Queryable, Selectable, Identifiable, Debug, PartialEq)]
+#[diesel(table_name = relay_invites)]
+pub struct RelayInvite {
+    pub id: Uuid,
+    /// Name under which the peer is registered once it redeems the invite.
+    pub peer_name: String,
+    pub secret: Vec<u8>,
+    pub expires_at: DateTime<Utc>,
+    pub redeemed_at: Option<DateTime<Utc>>,
+    /// The [Relay] which redeemed the invite.
+    pub redeemed_by: Option<Uuid>,
+}
+
+/// Used to create a new [RelayInvite] object in the database
+#[derive(Insertable, Debug, PartialEq)]
+#[diesel(table_name = relay_invites)]
+pub struct NewRelayInvite {
+    pub peer_name: String,
+    pub secret: Vec<u8>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Reachability of the endpoints of a peer [Relay], as last probed by a query_runner. Latencies
+/// are None if the endpoint could not be reached, in which case the error says why.
+#[derive(
+    Serialize, Deserialize, Queryable, Selectable, Identifiable, Associations, Debug, PartialEq,
+)]
+#[diesel(belongs_to(Relay))]
+#[diesel(primary_key(relay_id))]
+#[diesel(table_name = relay_status)]
+pub struct RelayStatus {
+    pub relay_id: Uuid,
+    pub checked_at: DateTime<Utc>,
+    pub rest_latency_ms: Option<i64>,
+    pub rest_error: Option<String>,
+    pub rest_last_seen_at: Option<DateTime<Utc>>,
+    pub flight_latency_ms: Option<i64>,
+    pub flight_error: Option<String>,
+    pub flight_last_seen_at: Option<DateTime<Utc>>,
+}
+
+/// Used to record a new [RelayStatus] in the database
+#[derive(Insertable, Debug, PartialEq, AsChangeset)]
+#[diesel(table_name = relay_status)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewRelayStatus {
+    pub relay_id: Uuid,
+    pub checked_at: DateTime<Utc>,
+    pub rest_latency_ms: Option<i64>,
+    pub rest_error: Option<String>,
+    pub flight_latency_ms: Option<i64>,
+    pub flight_error: Option<String>,
 }
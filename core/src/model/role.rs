@@ -0,0 +1,16 @@
+use crate::schema::roles;
+
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A named group of [SourcePermission][crate::model::access_control::SourcePermission]s which
+/// is granted to each [User][crate::model::user::User] and
+/// [Relay][crate::model::relay::Relay] who is a member of the role, in addition to their direct
+/// grants.
+#[derive(Serialize, Deserialize, Queryable, Selectable, Identifiable, Debug, PartialEq, Clone)]
+#[diesel(table_name = roles)]
+pub struct Role {
+    pub id: Uuid,
+    pub name: String,
+}
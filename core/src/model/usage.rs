@@ -0,0 +1,142 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::schema::{entity_usage, information_usage, relay_usage};
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{query::QueryTask, relay::Relay};
+
+/// Records the rows and bytes of a single [QueryTask] result which were served to a peer [Relay].
+#[derive(
+    Serialize, Deserialize, Queryable, Selectable, Identifiable, Associations, Debug, PartialEq,
+)]
+#[diesel(belongs_to(Relay))]
+#[diesel(belongs_to(QueryTask))]
+#[diesel(table_name = relay_usage)]
+pub struct RelayUsage {
+    pub id: Uuid,
+    pub relay_id: Uuid,
+    pub query_task_id: Uuid,
+    pub rows: i64,
+    /// In memory size of the Arrow data served, which approximates the bytes transferred.
+    pub bytes: i64,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Used to create a new [RelayUsage] object in the database
+#[derive(Insertable, Debug, PartialEq)]
+#[diesel(table_name = relay_usage)]
+pub struct NewRelayUsage {
+    pub relay_id: Uuid,
+    pub query_task_id: Uuid,
+    pub rows: i64,
+    pub bytes: i64,
+}
+
+/// Aggregated [RelayUsage] for a single peer [Relay] over a period of time.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RelayUsageReport {
+    pub relay_id: Uuid,
+    pub relay_name: String,
+    pub tasks: i64,
+    pub rows: i64,
+    pub bytes: i64,
+}
+
+/// Counts the queries which accessed an [Entity][crate::model::entity::Entity].
+#[derive(Queryable, Selectable, Insertable, Debug, PartialEq)]
+#[diesel(table_name = entity_usage)]
+pub struct EntityUsage {
+    pub entity_id: Uuid,
+    pub query_count: i64,
+    pub last_accessed_at: DateTime<Utc>,
+}
+
+/// Counts the queries which referenced an [Information][crate::model::entity::Information].
+#[derive(Queryable, Selectable, Insertable, Debug, PartialEq)]
+#[diesel(table_name = information_usage)]
+pub struct InformationUsage {
+    pub information_id: Uuid,
+    pub query_count: i64,
+    pub last_accessed_at: DateTime<Utc>,
+}
+
+/// Query counts of an [Entity][crate::model::entity::Entity] and each of its
+/// [Information][crate::model::entity::Information], in canonical column order. Information
+/// which was never queried has a query_count of 0.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EntityUsageReport {
+    pub entity_name: String,
+    pub query_count: i64,
+    pub last_accessed_at: Option<DateTime<Utc>>,
+    pub information: Vec<InformationUsageReport>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct InformationUsageReport {
+    pub name: String,
+    pub query_count: i64,
+    pub last_accessed_at: Option<DateTime<Utc>>,
+}
+
+/// Rows and bytes transferred so far on a Flight stream, sent as JSON app_metadata alongside the
+/// data of do_get and do_put streams.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferProgress {
+    pub rows: i64,
+    pub bytes: i64,
+    /// Set on the final progress message once the stream is exhausted.
+    pub complete: bool,
+}
+
+/// How a relay receiving a do_put handled a result for a flight_id it may have already received,
+/// e.g. because the sending runner retried.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PutDedup {
+    /// The result was not received before.
+    New,
+    /// A failed result, or one whose do_put was abandoned, was received before, and is overwritten.
+    Replaced,
+    /// A complete result was received before, so the sent data is discarded.
+    Skipped,
+}
+
+/// Sent as JSON app_metadata of the first PutResult of a do_put, before any [TransferProgress].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PutDecision {
+    pub flight_id: Uuid,
+    pub dedup: PutDedup,
+}
+
+/// Thread safe counter of rows and bytes flowing through a RecordBatch stream.
+#[derive(Debug, Default)]
+pub struct TransferCounter {
+    rows: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl TransferCounter {
+    pub fn add(&self, rows: usize, bytes: usize) {
+        self.rows.fetch_add(rows as u64, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn rows(&self) -> i64 {
+        self.rows.load(Ordering::Relaxed) as i64
+    }
+
+    pub fn bytes(&self) -> i64 {
+        self.bytes.load(Ordering::Relaxed) as i64
+    }
+
+    pub fn progress(&self, complete: bool) -> TransferProgress {
+        TransferProgress {
+            rows: self.rows(),
+            bytes: self.bytes(),
+            complete,
+        }
+    }
+}
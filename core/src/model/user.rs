@@ -37,6 +37,27 @@ pub struct UserAttributes {
     /// Arbitrary, user defined attributes.
     #[serde(default = "default_attributes")]
     pub misc: HashMap<String, String>,
+    /// Name of the [ValidationRuleSet][crate::model::validation::ValidationRuleSet] enforced on
+    /// this user's queries. If unset, the default rule set applies.
+    #[serde(default)]
+    pub validation_rules: Option<String>,
+    /// Set if this user is a service account, e.g. of a CI job or scheduler, whose queries are
+    /// restricted to the declared scope. Service accounts are never admins.
+    #[serde(default)]
+    pub service_account: Option<ServiceAccountScope>,
+}
+
+/// Restricts the queries of a service account beyond its source permissions.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct ServiceAccountScope {
+    /// Names of the [Entities][crate::model::entity::Entity] the service account may query. No
+    /// entities means it may not query any.
+    #[serde(default)]
+    pub allowed_entities: Vec<String>,
+    /// Maximum number of query requests the service account may submit per minute, across every
+    /// service of the relay. Unlimited if unset.
+    #[serde(default)]
+    pub max_requests_per_minute: Option<u32>,
 }
 
 fn default_admin() -> bool {
@@ -52,6 +73,8 @@ impl UserAttributes {
         Self {
             is_admin: false,
             misc: HashMap::new(),
+            validation_rules: None,
+            service_account: None,
         }
     }
 
@@ -60,6 +83,18 @@ impl UserAttributes {
         self
     }
 
+    pub fn with_validation_rules(mut self, validation_rules: Option<String>) -> Self {
+        self.validation_rules = validation_rules;
+        self
+    }
+
+    /// Makes the user a service account restricted to scope, which also revokes admin access.
+    pub fn with_service_account(mut self, scope: ServiceAccountScope) -> Self {
+        self.is_admin = false;
+        self.service_account = Some(scope);
+        self
+    }
+
     pub fn with_attributes(mut self, attributes: HashMap<String, String>) -> Self {
         self.misc = attributes;
         self
@@ -86,4 +121,6 @@ pub struct NewUser {
     pub x509_issuer: String,
     /// Abritrary user attributes
     pub attributes: UserAttributes,
+    /// PEM encoded certificate, if it was declared. Left unchanged on update if None.
+    pub x509_cert: Option<Vec<u8>>,
 }
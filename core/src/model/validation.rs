@@ -0,0 +1,64 @@
+use crate::schema::validation_rule_sets;
+
+use diesel::prelude::*;
+use diesel::{AsExpression, FromSqlRow};
+use diesel_as_jsonb::AsJsonb;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Name of the [ValidationRuleSet] which applies to users that are not assigned another one.
+pub const DEFAULT_RULE_SET: &str = "default";
+
+/// SQL constructs which a [ValidationRules] may ban.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SqlConstruct {
+    /// Common table expressions, i.e. WITH clauses.
+    Cte,
+    /// Subqueries in expressions or in the FROM clause.
+    Subquery,
+    /// UNION, INTERSECT and EXCEPT.
+    SetOperation,
+    /// Window functions.
+    Window,
+    /// SELECT DISTINCT.
+    Distinct,
+    /// SELECT * and qualified wildcards.
+    Wildcard,
+}
+
+/// A query policy, which is enforced in addition to the built in validation that every query
+/// must pass. Rules can only further restrict which queries are allowed.
+#[derive(Serialize, Deserialize, AsJsonb, Debug, Clone, PartialEq, Default)]
+pub struct ValidationRules {
+    /// Maximum length of the SQL string, in characters.
+    #[serde(default)]
+    pub max_query_length: Option<usize>,
+    /// Maximum number of joins across all of the queries of a statement.
+    #[serde(default)]
+    pub max_joins: Option<usize>,
+    /// If set, only these functions may be called. Names are case insensitive.
+    #[serde(default)]
+    pub allowed_functions: Option<Vec<String>>,
+    #[serde(default)]
+    pub banned_constructs: Vec<SqlConstruct>,
+}
+
+/// A named set of [ValidationRules]. Users are assigned a rule set via their
+/// [UserAttributes][crate::model::user::UserAttributes], otherwise the [DEFAULT_RULE_SET]
+/// applies if it is declared.
+#[derive(Queryable, Selectable, Identifiable, Debug, PartialEq)]
+#[diesel(table_name = validation_rule_sets)]
+pub struct ValidationRuleSet {
+    pub id: Uuid,
+    pub name: String,
+    pub rules: ValidationRules,
+}
+
+/// Used to create a new [ValidationRuleSet] object in the database
+#[derive(Insertable, AsChangeset, Debug, PartialEq)]
+#[diesel(table_name = validation_rule_sets)]
+pub struct NewValidationRuleSet {
+    pub name: String,
+    pub rules: ValidationRules,
+}
@@ -52,3 +52,111 @@ pub fn parse_urlencoded_pemstr(pemstr: &str) -> Result<(String, String, String)>
         ))
     })
 }
+
+/// Name of the header in which Envoy and other service meshes forward details of the client's
+/// certificate.
+pub const XFCC_HEADER: &str = "x-forwarded-client-cert";
+
+/// Splits a client_cert_header setting, which may list several comma separated header names, into
+/// the candidate headers in order of precedence.
+pub fn client_cert_header_names(client_cert_header: &str) -> impl Iterator<Item = &str> {
+    client_cert_header
+        .split(',')
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+}
+
+/// Extracts the client certificate from the value of a client cert header. An
+/// x-forwarded-client-cert header is parsed with [parse_xfcc], any other header is expected to
+/// contain a urlencoded PEM, see [parse_urlencoded_pemstr].
+pub fn parse_client_cert_header(name: &str, value: &str) -> Result<(String, String, String)> {
+    if name.eq_ignore_ascii_case(XFCC_HEADER) {
+        parse_xfcc(value)
+    } else {
+        parse_urlencoded_pemstr(value)
+    }
+}
+
+/// Extracts the client certificate from an x-forwarded-client-cert header, as set by Envoy. Each
+/// proxy appends an element describing the client it authenticated, so the last element, which was
+/// added by the proxy in front of the Relay, is used. The proxy must be configured to forward the
+/// Cert key, e.g. with set_current_client_cert_details cert: true in Envoy.
+pub fn parse_xfcc(value: &str) -> Result<(String, String, String)> {
+    parse_urlencoded_pemstr(&xfcc_cert(value)?)
+}
+
+/// Returns the urlencoded PEM of the Cert key in the last element of an x-forwarded-client-cert
+/// header value.
+fn xfcc_cert(value: &str) -> Result<String> {
+    let element = split_unquoted(value, ',')
+        .pop()
+        .ok_or_else(|| MeshError::SerDe("Empty x-forwarded-client-cert header".to_string()))?;
+    split_unquoted(element, ';')
+        .into_iter()
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("cert"))
+        .map(|(_, cert)| unquote(cert.trim()))
+        .ok_or_else(|| {
+            MeshError::SerDe("x-forwarded-client-cert header does not contain Cert".to_string())
+        })
+}
+
+/// Splits on sep, except where sep is inside a double quoted value.
+fn split_unquoted(value: &str, sep: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            c if c == sep && !in_quotes => {
+                parts.push(&value[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => (),
+        }
+    }
+    parts.push(&value[start..]);
+    parts.retain(|p| !p.trim().is_empty());
+    parts
+}
+
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => inner.replace("\\\"", "\""),
+        None => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Result;
+
+    use super::{client_cert_header_names, xfcc_cert};
+
+    #[test]
+    fn test_xfcc_cert() -> Result<()> {
+        let value =
+            "By=spiffe://mesh/edge;Hash=abc;Cert=\"-----BEGIN%20CERTIFICATE-----%0AMIIB1\";\
+            Subject=\"CN=edge,O=Org\";URI=,\
+            By=spiffe://mesh/relay;Hash=def;Subject=\"CN=user;O=Org, Inc\";\
+            Cert=\"-----BEGIN%20CERTIFICATE-----%0AMIIB2\"";
+        assert_eq!(
+            xfcc_cert(value)?,
+            "-----BEGIN%20CERTIFICATE-----%0AMIIB2".to_string()
+        );
+        assert!(xfcc_cert("By=spiffe://mesh/relay;Hash=def").is_err());
+        assert!(xfcc_cert("").is_err());
+
+        let names: Vec<_> =
+            client_cert_header_names("x-forwarded-client-cert, X-CLIENT-CERT,").collect();
+        assert_eq!(names, vec!["x-forwarded-client-cert", "X-CLIENT-CERT"]);
+        Ok(())
+    }
+}
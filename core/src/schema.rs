@@ -1,10 +1,18 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "audit_action"))]
+    pub struct AuditAction;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "flight_stream_status"))]
     pub struct FlightStreamStatus;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "policy_mode"))]
+    pub struct PolicyMode;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "query_task_remote_status"))]
     pub struct QueryTaskRemoteStatus;
@@ -12,6 +20,30 @@ pub mod sql_types {
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "query_task_status"))]
     pub struct QueryTaskStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "quota_subject"))]
+    pub struct QuotaSubject;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "result_format"))]
+    pub struct ResultFormat;
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::AuditAction;
+
+    audit_log (id) {
+        id -> Int8,
+        recorded_at -> Timestamptz,
+        action -> AuditAction,
+        actor_sha256 -> Varchar,
+        actor_subject -> Varchar,
+        query_request_id -> Nullable<Uuid>,
+        sql -> Nullable<Varchar>,
+        detail -> Jsonb,
+    }
 }
 
 diesel::table! {
@@ -19,6 +51,7 @@ diesel::table! {
         id -> Uuid,
         name -> Varchar,
         connection_options -> Jsonb,
+        execution_windows -> Jsonb,
     }
 }
 
@@ -32,12 +65,32 @@ diesel::table! {
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::PolicyMode;
+
     data_source (id) {
         id -> Uuid,
         name -> Varchar,
         source_sql -> Varchar,
         data_connection_id -> Uuid,
         source_options -> Jsonb,
+        paused -> Bool,
+        engines -> Jsonb,
+        freshness_query -> Nullable<Varchar>,
+        policy_mode -> PolicyMode,
+    }
+}
+
+diesel::table! {
+    dead_letter (id) {
+        id -> Int8,
+        task_id -> Nullable<Uuid>,
+        message -> Nullable<Jsonb>,
+        error -> Varchar,
+        attempts -> Int4,
+        dead -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -53,6 +106,35 @@ diesel::table! {
     entities (id) {
         id -> Uuid,
         name -> Varchar,
+        validation_query -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    entity_alias (alias) {
+        alias -> Varchar,
+        entity_id -> Uuid,
+    }
+}
+
+diesel::table! {
+    entity_usage (entity_id) {
+        entity_id -> Uuid,
+        query_count -> Int8,
+        last_accessed_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    entity_validation (id) {
+        id -> Uuid,
+        entity_id -> Uuid,
+        data_source_id -> Uuid,
+        success -> Bool,
+        row_count -> Nullable<Int8>,
+        error -> Nullable<Varchar>,
+        checked_at -> Timestamptz,
+        last_success_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -75,6 +157,17 @@ diesel::table! {
         remote_fingerprint -> Varchar,
         flight_id -> Uuid,
         status -> FlightStreamStatus,
+        updated_at -> Timestamptz,
+        freshness -> Nullable<Timestamptz>,
+        result_expired_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    information_usage (information_id) {
+        information_id -> Uuid,
+        query_count -> Int8,
+        last_accessed_at -> Timestamptz,
     }
 }
 
@@ -84,16 +177,42 @@ diesel::table! {
         name -> Varchar,
         arrow_dtype -> Jsonb,
         entity_id -> Uuid,
+        position -> Nullable<Int4>,
+        nullable -> Bool,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::QuotaSubject;
+
+    quota (subject, x509_sha256) {
+        subject -> QuotaSubject,
+        x509_sha256 -> Varchar,
+        max_concurrent_requests -> Nullable<Int4>,
+        max_requests_per_minute -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ResultFormat;
+
     query_request (id) {
         id -> Uuid,
         originator_request_id -> Uuid,
         sql -> Varchar,
         relay_id -> Uuid,
         origin_info -> Jsonb,
+        received_at -> Timestamptz,
+        retired -> Bool,
+        reexecution_of -> Nullable<Uuid>,
+        replay_of -> Nullable<Uuid>,
+        statements -> Array<Text>,
+        trace_context -> Nullable<Jsonb>,
+        lineage_emitted_at -> Nullable<Timestamptz>,
+        result_ttl_secs -> Nullable<Int8>,
+        result_format -> ResultFormat,
     }
 }
 
@@ -107,6 +226,18 @@ diesel::table! {
         data_source_id -> Uuid,
         task -> Jsonb,
         status -> QueryTaskStatus,
+        not_before -> Nullable<Timestamptz>,
+        engine -> Nullable<Varchar>,
+        permission -> Nullable<Jsonb>,
+        scan_metrics -> Nullable<Jsonb>,
+        started_at -> Nullable<Timestamptz>,
+        completed_at -> Nullable<Timestamptz>,
+        heartbeat_at -> Nullable<Timestamptz>,
+        attempts -> Int4,
+        freshness -> Nullable<Timestamptz>,
+        statement_index -> Int4,
+        result_expired_at -> Nullable<Timestamptz>,
+        dispatched_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -120,6 +251,29 @@ diesel::table! {
         relay_id -> Uuid,
         task -> Jsonb,
         status -> QueryTaskRemoteStatus,
+        attempts -> Int4,
+        error -> Nullable<Varchar>,
+        statement_index -> Int4,
+        dispatched_at -> Nullable<Timestamptz>,
+        not_before -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    relay_invites (id) {
+        id -> Uuid,
+        peer_name -> Varchar,
+        secret -> Bytea,
+        expires_at -> Timestamptz,
+        redeemed_at -> Nullable<Timestamptz>,
+        redeemed_by -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    relay_role (role_id, relay_id) {
+        role_id -> Uuid,
+        relay_id -> Uuid,
     }
 }
 
@@ -132,6 +286,30 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    relay_status (relay_id) {
+        relay_id -> Uuid,
+        checked_at -> Timestamptz,
+        rest_latency_ms -> Nullable<Int8>,
+        rest_error -> Nullable<Varchar>,
+        rest_last_seen_at -> Nullable<Timestamptz>,
+        flight_latency_ms -> Nullable<Int8>,
+        flight_error -> Nullable<Varchar>,
+        flight_last_seen_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    relay_usage (id) {
+        id -> Uuid,
+        relay_id -> Uuid,
+        query_task_id -> Uuid,
+        rows -> Int8,
+        bytes -> Int8,
+        recorded_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     relays (id) {
         id -> Uuid,
@@ -141,6 +319,7 @@ diesel::table! {
         x509_sha256 -> Varchar,
         x509_subject -> Varchar,
         x509_issuer -> Varchar,
+        x509_cert -> Nullable<Bytea>,
     }
 }
 
@@ -164,6 +343,56 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    role_source_permission (id) {
+        id -> Uuid,
+        data_source_id -> Uuid,
+        role_id -> Uuid,
+        source_permission -> Jsonb,
+    }
+}
+
+diesel::table! {
+    roles (id) {
+        id -> Uuid,
+        name -> Varchar,
+    }
+}
+
+diesel::table! {
+    scratch_dataset (data_connection_id) {
+        data_connection_id -> Uuid,
+        user_id -> Uuid,
+        dataset_name -> Varchar,
+        expires_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    task_outbox (id) {
+        id -> Int8,
+        message -> Jsonb,
+        created_at -> Timestamptz,
+        task_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    task_queue (id) {
+        id -> Int8,
+        message -> Jsonb,
+        created_at -> Timestamptz,
+        locked_until -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    user_role (role_id, user_id) {
+        role_id -> Uuid,
+        user_id -> Uuid,
+    }
+}
+
 diesel::table! {
     user_source_permission (id) {
         id -> Uuid,
@@ -180,46 +409,89 @@ diesel::table! {
         x509_subject -> Varchar,
         x509_issuer -> Varchar,
         attributes -> Jsonb,
+        x509_cert -> Nullable<Bytea>,
+    }
+}
+
+diesel::table! {
+    validation_rule_sets (id) {
+        id -> Uuid,
+        name -> Varchar,
+        rules -> Jsonb,
     }
 }
 
 diesel::joinable!(data_field -> data_source (data_source_id));
 diesel::joinable!(data_source -> data_connection (data_connection_id));
 diesel::joinable!(default_source_permission -> data_source (data_source_id));
+diesel::joinable!(entity_alias -> entities (entity_id));
+diesel::joinable!(entity_usage -> entities (entity_id));
+diesel::joinable!(entity_validation -> data_source (data_source_id));
+diesel::joinable!(entity_validation -> entities (entity_id));
 diesel::joinable!(field_mappings -> data_field (data_field_id));
 diesel::joinable!(field_mappings -> information (information_id));
 diesel::joinable!(incoming_flight_streams -> query_task_remote (query_task_remote_id));
 diesel::joinable!(information -> entities (entity_id));
+diesel::joinable!(information_usage -> information (information_id));
 diesel::joinable!(query_request -> relays (relay_id));
 diesel::joinable!(query_task -> data_source (data_source_id));
 diesel::joinable!(query_task -> query_request (query_request_id));
 diesel::joinable!(query_task_remote -> query_request (query_request_id));
 diesel::joinable!(query_task_remote -> relays (relay_id));
+diesel::joinable!(relay_invites -> relays (redeemed_by));
+diesel::joinable!(relay_role -> relays (relay_id));
+diesel::joinable!(relay_role -> roles (role_id));
 diesel::joinable!(relay_source_permission -> data_source (data_source_id));
 diesel::joinable!(relay_source_permission -> relays (relay_id));
+diesel::joinable!(relay_status -> relays (relay_id));
+diesel::joinable!(relay_usage -> query_task (query_task_id));
+diesel::joinable!(relay_usage -> relays (relay_id));
 diesel::joinable!(remote_entity_mapping -> entities (entity_id));
 diesel::joinable!(remote_entity_mapping -> relays (relay_id));
 diesel::joinable!(remote_info_mapping -> information (information_id));
 diesel::joinable!(remote_info_mapping -> remote_entity_mapping (remote_entity_mapping_id));
+diesel::joinable!(role_source_permission -> data_source (data_source_id));
+diesel::joinable!(role_source_permission -> roles (role_id));
+diesel::joinable!(scratch_dataset -> data_connection (data_connection_id));
+diesel::joinable!(user_role -> roles (role_id));
+diesel::joinable!(user_role -> users (user_id));
 diesel::joinable!(user_source_permission -> data_source (data_source_id));
 diesel::joinable!(user_source_permission -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    audit_log,
     data_connection,
     data_field,
     data_source,
+    dead_letter,
     default_source_permission,
     entities,
+    entity_alias,
+    entity_usage,
+    entity_validation,
     field_mappings,
     incoming_flight_streams,
     information,
+    information_usage,
+    quota,
     query_request,
     query_task,
     query_task_remote,
+    relay_invites,
+    relay_role,
     relay_source_permission,
+    relay_status,
+    relay_usage,
     relays,
     remote_entity_mapping,
+    task_outbox,
+    task_queue,
     remote_info_mapping,
+    role_source_permission,
+    roles,
+    scratch_dataset,
+    user_role,
     user_source_permission,
     users,
+    validation_rule_sets,
 );
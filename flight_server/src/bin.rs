@@ -1,8 +1,11 @@
 use flight_server_lib::run;
 use mesh::error::MeshError;
+use mesh::execute::telemetry::{init_tracing, shutdown_tracing};
 
 #[tokio::main]
 async fn main() -> Result<(), MeshError> {
-    tracing_subscriber::fmt::init();
-    run().await
+    init_tracing("flight_server");
+    let result = run().await;
+    shutdown_tracing().await;
+    result
 }
@@ -1,12 +1,16 @@
 use arrow::ipc::convert::try_schema_from_flatbuffer_bytes;
+use arrow::ipc::writer::IpcWriteOptions;
+use chrono::{DateTime, Utc};
 
 use arrow_flight::encode::FlightDataEncoderBuilder;
 use arrow_flight::error::FlightError;
+use arrow_flight::flight_descriptor::DescriptorType;
 use arrow_flight::flight_service_client::FlightServiceClient;
 use arrow_flight::utils::flight_data_to_arrow_batch;
-use arrow_flight::{FlightClient, FlightEndpoint, PollInfo};
-use arrow_schema::{Field, Schema};
+use arrow_flight::{FlightClient, FlightEndpoint, PollInfo, SchemaAsIpc};
+use arrow_schema::{ArrowError, Schema};
 use datafusion::error::DataFusionError;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use datafusion::physical_plan::SendableRecordBatchStream;
 use datafusion::sql::sqlparser::ast::Statement;
 use diesel_async::pooled_connection::bb8::Pool;
@@ -18,33 +22,54 @@ use mesh::crud::PgDb;
 
 use mesh::error::MeshError;
 use mesh::execute::data_stores::try_connect;
+use mesh::execute::freshness::source_freshness;
+use mesh::execute::identity::identity_cache;
+use mesh::execute::inspect::entity_summaries;
+use mesh::execute::invite::{accept_invite, RedeemInviteRequest, REDEEM_INVITE_ACTION};
+use mesh::execute::metrics::metrics;
+use mesh::execute::parse_utils::name_to_ident;
+use mesh::execute::relay_status::PING_ACTION;
 use mesh::execute::result_manager::ResultManager;
 
-use mesh::execute::request_to_remote_requests;
 use mesh::execute::utils::{
-    create_query_request, map_and_create_local_tasks, validate_sql_and_logical_round_trip,
+    authorize_query, create_query_request, enforce_quotas, enforce_service_account_rate,
+    map_and_create_local_tasks, validate_sql_and_logical_round_trip,
     verify_query_origination_information,
 };
+use mesh::execute::{dedup_retention, request_to_remote_requests, resolve_task_engine};
 
+use mesh::execute::progress::{
+    flight_stream_timeout, metadata_flight_data, progress_flight_data, put_data_messages,
+    with_progress,
+};
 use mesh::model::data_stores::{DataConnection, DataSource};
 use mesh::model::query::{
-    FlightStreamStatus, NewFlightStream, QueryRequest, QueryTask, RawQueryRequest,
+    CancelledQuery, FlightInfoMetadata, FlightStreamStatus, NewFlightStream, QueryAction,
+    QueryRequest, QueryTask, QueryTaskStatus, RawQueryRequest, ResultFormat, ResultMetadata,
+    StoredResult, StoredResultTicket, TraceContext, CANCEL_QUERY_ACTION, LIST_ENTITIES_ACTION,
+    REFRESH_RESULT_ACTION,
 };
 use mesh::model::relay::Relay;
+use mesh::model::usage::{NewRelayUsage, PutDecision, PutDedup, TransferCounter};
 use mesh::model::user::User;
-use mesh::pki::{parse_certificate, parse_urlencoded_pemstr};
+use mesh::pki::{client_cert_header_names, parse_certificate, parse_client_cert_header};
 
 use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::time::Duration;
 
+use tokio::sync::mpsc;
 use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 use tonic::transport::{Certificate, ClientTlsConfig, Identity};
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info, info_span, warn};
 
 use uuid::Uuid;
 
 use std::sync::Arc;
 
 use futures::stream::BoxStream;
+use prost::Message;
 
 use tonic::{Request, Response, Status, Streaming};
 
@@ -57,6 +82,11 @@ use arrow_flight::{
 use serde::{Deserialize, Serialize};
 use tonic::codegen::Bytes;
 
+use crate::flight_sql::{
+    sql_query_request, SqlCommand, CLOSE_PREPARED_STATEMENT_ACTION,
+    CREATE_PREPARED_STATEMENT_ACTION,
+};
+
 /// Used as a [Ticket], and can also pass metadata to flight clients
 #[derive(Serialize, Deserialize)]
 struct FlightInfoTicket {
@@ -70,6 +100,7 @@ struct FlightInfoTicket {
 /// terminates TLS. In that case have the proxy pass a header with the client's cert
 /// and use extract_certs_header function instead.
 /// See [parse_certificate] for more information about the return values.
+#[allow(clippy::result_large_err)] // Status is the error type of the FlightService trait
 fn extract_certs_direct_tls<T>(request: &Request<T>) -> Result<(String, String, String), Status> {
     let client_certs = request.peer_certs().ok_or(Status::permission_denied(
         "Expected client cert, found none",
@@ -90,30 +121,35 @@ fn extract_certs_direct_tls<T>(request: &Request<T>) -> Result<(String, String,
 /// Extracts client's certificate from a header. This assumes there is a trusted reverse proxy upstream
 /// which completes the mTLS handshake and forwards the validated cert. This method is only secure when
 /// the only way to connect to the Relay is via the upstream reverse proxy.
-/// See [parse_certificate] for more information about the return values.
+/// The header may list several comma separated header names, the first one present in the request
+/// is used. See [parse_client_cert_header] for the supported formats, and [parse_certificate] for
+/// more information about the return values.
+#[allow(clippy::result_large_err)]
 fn extract_certs_header<T>(
     request: &Request<T>,
-    header: &String,
+    header: &str,
 ) -> Result<(String, String, String), Status> {
-    match request.metadata().get(header) {
-        Some(value) => {
-            let inner = value
-                .to_str()
-                .map_err(|_e| Status::unauthenticated("Unable to read client cert from header"))?;
-            parse_urlencoded_pemstr(inner).map_err(|e| {
-                Status::unauthenticated(format!(
-                    "Cert header authentication failed with error: {e}"
-                ))
-            })
-        }
-        None => Err(Status::unauthenticated(
+    let (name, value) = client_cert_header_names(header)
+        .find_map(|name| {
+            request
+                .metadata()
+                .get(name.to_lowercase().as_str())
+                .map(|value| (name, value))
+        })
+        .ok_or(Status::unauthenticated(
             "Unable to retrieve client certificate from header!",
-        )),
-    }
+        ))?;
+    let inner = value
+        .to_str()
+        .map_err(|_e| Status::unauthenticated("Unable to read client cert from header"))?;
+    parse_client_cert_header(name, inner).map_err(|e| {
+        Status::unauthenticated(format!("Cert header authentication failed with error: {e}"))
+    })
 }
 
 /// Generic function to extract client certificate information from any [Request]
 /// See [parse_certificate] for more information about the return values.
+#[allow(clippy::result_large_err)]
 fn extract_certs<T>(
     request: &Request<T>,
     client_cert_header: &Option<String>,
@@ -124,6 +160,24 @@ fn extract_certs<T>(
     }
 }
 
+/// Parses the [RawQueryRequest] which a [FlightDescriptor] carries as JSON encoded cmd.
+#[allow(clippy::result_large_err)]
+fn parse_raw_query_request(descriptor: &FlightDescriptor) -> Result<RawQueryRequest, Status> {
+    serde_json::from_slice(&descriptor.cmd).map_err(|_e| {
+        Status::invalid_argument(
+            "FlightDescriptior.cmd is not a valid JSON encoded RawQueryRequest",
+        )
+    })
+}
+
+/// Maps the error of a rate or quota check to the [Status] returned to the requester.
+fn rate_limited(e: MeshError) -> Status {
+    match e {
+        MeshError::RateLimited(msg) => Status::resource_exhausted(msg),
+        e => Status::internal(e.to_string()),
+    }
+}
+
 #[derive(Clone)]
 pub struct FlightRelay {
     pub db_pool: Pool<AsyncPgConnection>,
@@ -143,10 +197,52 @@ pub struct FlightRelay {
     /// the upstream reverse proxy which terminates TLS. In direct_tls=true mode, this is None and the Relay
     /// directly validates the user's certificate via mTLS handshake.
     pub client_cert_header: Option<String>,
+    /// The maximum number of RecordBatches a running task may produce ahead of a do_get client
+    /// before execution is paused until the client catches up.
+    pub do_get_buffer: NonZeroUsize,
+    /// How often a [TransferProgress][mesh::model::usage::TransferProgress] message is sent on
+    /// do_get and do_put streams, so that clients and proxies do not consider them idle.
+    pub progress_interval: Duration,
+}
+
+/// How long get_flight_info waits for the freshness of the [DataSource]s of a query, which
+/// are probed concurrently.
+const FRESHNESS_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Drives write to completion while periodically touching the [FlightStream] identified by
+/// flight_id, so that a long running do_put is not mistaken for an abandoned one.
+async fn with_heartbeat<T>(
+    pool: &Pool<AsyncPgConnection>,
+    flight_id: &Uuid,
+    write: impl std::future::Future<Output = T>,
+) -> T {
+    let mut heartbeat = tokio::time::interval(flight_stream_timeout() / 4);
+    // The first tick completes immediately, and the stream was just upserted
+    heartbeat.tick().await;
+    tokio::pin!(write);
+    loop {
+        tokio::select! {
+            result = &mut write => return result,
+            _ = heartbeat.tick() => {
+                let touched = match PgDb::try_from_pool(pool).await {
+                    Ok(mut db) => db.touch_flight_stream(flight_id).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = touched {
+                    warn!("Failed to record heartbeat of flight {flight_id} with error {e}");
+                }
+            }
+        }
+    }
 }
 
 impl FlightRelay {
-    async fn get_flight_client(&self, relay: &Relay) -> Result<FlightClient, Status> {
+    /// Connects to the flight_server of relay, propagating trace_context with every call.
+    async fn get_flight_client(
+        &self,
+        relay: &Relay,
+        trace_context: Option<&TraceContext>,
+    ) -> Result<FlightClient, Status> {
         let channel = tonic::transport::Channel::from_shared(relay.flight_endpoint.clone())
             .map_err(|e| Status::from_error(Box::new(e)))?
             .tls_config(
@@ -162,7 +258,13 @@ impl FlightRelay {
             .await
             .map_err(|e| Status::from_error(Box::new(e)))?;
         let svc_client = FlightServiceClient::new(channel);
-        Ok(FlightClient::new_from_inner(svc_client))
+        let mut client = FlightClient::new_from_inner(svc_client);
+        for (name, value) in trace_context.iter().flat_map(|c| c.headers()) {
+            client.add_header(name, value).map_err(|e| {
+                Status::internal(format!("Invalid trace context header {name}: {e}"))
+            })?;
+        }
+        Ok(client)
     }
 
     /// Execute a local [QueryTask] and return a RecordBatch stream
@@ -180,15 +282,566 @@ impl FlightRelay {
                 task.id
             ))
         })?;
-        let rb_stream = runner.execute_stream(query).await.map_err(|e| {
-            error!("Execution error: {e}");
-            Status::internal(format!(
-                "An unexpected error occurred while processing local task {}",
-                task.id
+        // The query is cancelled when the stream is dropped, e.g. because the client disconnected
+        let rb_stream = runner
+            .execute_stream(query, CancellationToken::new())
+            .await
+            .map_err(|e| {
+                error!("Execution error: {e}");
+                Status::internal(format!(
+                    "An unexpected error occurred while processing local task {}",
+                    task.id
+                ))
+            })?;
+
+        Ok(rb_stream)
+    }
+
+    /// Wraps a [SendableRecordBatchStream] served on behalf of a peer [Relay] so that the total
+    /// rows and bytes are recorded as [RelayUsage][mesh::model::usage::RelayUsage] once the
+    /// stream is exhausted.
+    fn track_relay_usage(
+        &self,
+        rb_stream: SendableRecordBatchStream,
+        relay_id: Uuid,
+        task_id: Uuid,
+    ) -> SendableRecordBatchStream {
+        let schema = rb_stream.schema();
+        let counter = Arc::new(TransferCounter::default());
+        let counter_clone = counter.clone();
+        let pool = self.db_pool.clone();
+        let record_usage = futures::stream::once(async move {
+            let usage = NewRelayUsage {
+                relay_id,
+                query_task_id: task_id,
+                rows: counter.rows(),
+                bytes: counter.bytes(),
+            };
+            let recorded = match PgDb::try_from_pool(&pool).await {
+                Ok(mut db) => db.record_relay_usage(&usage).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = recorded {
+                error!("Failed to record usage for task {task_id} with error {e}");
+            }
+        })
+        .filter_map(|_| async { None });
+
+        let tracked = rb_stream
+            .inspect_ok(move |batch| {
+                counter_clone.add(batch.num_rows(), batch.get_array_memory_size())
+            })
+            .chain(record_usage);
+        Box::pin(RecordBatchStreamAdapter::new(schema, tracked))
+    }
+
+    /// Serves a result of a query submitted via the rest_server, which is already stored by the
+    /// [ResultManager], to the user who submitted the query.
+    async fn do_get_stored_result(
+        &self,
+        db: &mut PgDb<'_>,
+        fingerprint: &str,
+        ticket: StoredResultTicket,
+    ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
+        let StoredResultTicket {
+            request_id,
+            result_id,
+        } = ticket;
+        debug!("Request is for stored result {result_id} of query {request_id}");
+
+        let retreiving_user = identity_cache()
+            .get_user(db, fingerprint)
+            .await
+            .map_err(|_| Status::permission_denied("unrecognized user"))?;
+
+        // Access denied and no result exists intentionally give same response to prevent
+        // brute forcing valid Uuids.
+        let not_found = || {
+            Status::invalid_argument(format!(
+                "No result {result_id} exists for query {request_id}"
             ))
+        };
+        let (request, results) = db
+            .get_stored_results(request_id)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to look up query {request_id}: {e}")))?
+            .ok_or_else(not_found)?;
+        if request.origin_info.origin_user.as_ref() != Some(&retreiving_user) {
+            warn!("Rejecting request for valid Uuid to user with fingerprint {fingerprint} which does not match original requester!.");
+            return Err(not_found());
+        }
+        let source_relay = match results.iter().find(|r| r.id == result_id) {
+            Some(StoredResult {
+                relay_id: Some(relay_id),
+                ..
+            }) => Some(
+                db.get_relay_by_id(relay_id)
+                    .await
+                    .map_err(|e| Status::internal(format!("Failed to look up relay: {e}")))?
+                    .name,
+            ),
+            Some(_) => None,
+            None => return Err(not_found()),
+        };
+
+        let rb_stream = self
+            .result_manager
+            .get_task_result(result_id, source_relay.as_deref(), request.result_format)
+            .await
+            .map_err(|e| {
+                error!("Failed to read stored result {result_id}: {e}");
+                Status::internal(format!("Unable to read stored result {result_id}"))
+            })?;
+        let rb_stream = rb_stream
+            .inspect_ok(|batch| metrics().bytes_streamed("flight", batch.get_array_memory_size()));
+        let flight_data_stream = FlightDataEncoderBuilder::new()
+            .build(rb_stream.map_err(|e| FlightError::ExternalError(Box::new(e))))
+            .map_err(|e| Status::from_error(Box::new(e)));
+
+        Ok(Response::new(
+            Box::pin(flight_data_stream) as <Self as FlightService>::DoGetStream
+        ))
+    }
+
+    /// Executes a [SendableRecordBatchStream] on a separate tokio task so that RecordBatches are
+    /// sent to the client as soon as they are produced. At most do_get_buffer RecordBatches are
+    /// buffered ahead of the client.
+    fn spawn_buffered(&self, rb_stream: SendableRecordBatchStream) -> SendableRecordBatchStream {
+        let schema = rb_stream.schema();
+        let (tx, rx) = mpsc::channel(self.do_get_buffer.get());
+        tokio::spawn(async move {
+            let mut rb_stream = rb_stream;
+            while let Some(batch) = rb_stream.next().await {
+                if tx.send(batch).await.is_err() {
+                    debug!("do_get client disconnected, stopping execution");
+                    break;
+                }
+            }
+        });
+
+        let stream = futures::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|batch| (batch, rx))
+        });
+        Box::pin(RecordBatchStreamAdapter::new(schema, stream))
+    }
+
+    /// Executes a local [QueryTask] created by get_flight_info on behalf of the user who
+    /// submitted its query, identified by fingerprint.
+    async fn local_task_stream(
+        &self,
+        db: &mut PgDb<'_>,
+        fingerprint: &str,
+        task_id: Uuid,
+    ) -> Result<SendableRecordBatchStream, Status> {
+        debug!("Request is for task {task_id}");
+
+        let retreiving_user = identity_cache()
+            .get_user(db, fingerprint)
+            .await
+            .map_err(|_| Status::permission_denied("unrecognized user"))?;
+
+        let (con, source, task, request, _relay) =
+            db.get_query_task(task_id).await.map_err(|e| {
+                Status::invalid_argument(format!("No query exists with id {task_id}, error {e}"))
+            })?;
+
+        // Access denied and no query exists intentionally give same response to prevent
+        // brute forcing valid Uuids.
+        match request.origin_info.origin_user {
+            Some(origin_user) => {
+                if origin_user.x509_sha256 != retreiving_user.x509_sha256 {
+                    warn!("Rejecting request for valid Uuid to user with fingerprint {fingerprint} which does not match original requester!.");
+                    return Err(Status::invalid_argument(format!(
+                        "No query exists with id {task_id}"
+                    )));
+                }
+            }
+            None => {
+                error!("Origin user is not set for query with id {task_id}");
+                return Err(Status::invalid_argument(format!(
+                    "No query exists with id {task_id}"
+                )));
+            }
+        }
+
+        // Tasks created by get_flight_info only fail once their query is cancelled
+        if matches!(task.status, QueryTaskStatus::Failed) {
+            return Err(Status::cancelled(format!("Task {task_id} was cancelled")));
+        }
+
+        let (con, source) = resolve_task_engine(db, con, source, task.engine.as_deref())
+            .await
+            .map_err(|e| {
+                Status::internal(format!("Failed to resolve engine for task {task_id}: {e}"))
+            })?;
+
+        if let Some(not_before) = con.execution_windows.next_open(Utc::now()) {
+            return Err(Status::unavailable(format!(
+                "Task {task_id} is outside of its connection's execution windows. \
+                Retry at or after {not_before}."
+            )));
+        }
+
+        let rb_stream = self.execute_query_task(con, source, task).await?;
+        let rb_stream = match request.origin_info.origin_relay {
+            Some(origin_relay) => self.track_relay_usage(rb_stream, origin_relay.id, task_id),
+            None => rb_stream,
+        };
+        Ok(self.spawn_buffered(rb_stream))
+    }
+
+    /// Probes when the data of the [DataSource] a local [QueryTask] reads was last updated, and
+    /// records it on the task. Freshness is informational, so failing to determine it within
+    /// [FRESHNESS_PROBE_TIMEOUT] does not fail the request. Uses its own connection, so that the
+    /// tasks of a request are probed concurrently.
+    async fn task_freshness(&self, task_id: Uuid) -> Option<DateTime<Utc>> {
+        let probe = async {
+            let mut db = PgDb::try_from_pool(&self.db_pool).await?;
+            let (con, source, ..) = db.get_query_task(task_id).await?;
+            let freshness = source_freshness(con, source).await?;
+            if let Some(freshness) = freshness {
+                if let Err(e) = db.set_task_freshness(task_id, freshness).await {
+                    warn!("Failed to record freshness for task {task_id} with error {e}");
+                }
+            }
+            Ok::<_, MeshError>(freshness)
+        };
+        match tokio::time::timeout(FRESHNESS_PROBE_TIMEOUT, probe).await {
+            Ok(Ok(freshness)) => freshness,
+            Ok(Err(e)) => {
+                warn!("Failed to determine freshness for task {task_id} with error {e}");
+                None
+            }
+            Err(_) => {
+                warn!(
+                    "Determining freshness for task {task_id} timed out after \
+                    {FRESHNESS_PROBE_TIMEOUT:?}"
+                );
+                None
+            }
+        }
+    }
+
+    /// Accepts an invite issued by this Relay, registering the peer with the client certificate
+    /// it connected with, and returns the JSON encoded
+    /// [InviteAcceptance][mesh::execute::invite::InviteAcceptance].
+    async fn redeem_invite(
+        &self,
+        peer_cert: (String, String, String),
+        body: &[u8],
+    ) -> Result<Bytes, Status> {
+        info!(
+            "Got invite redemption from: subject: {}, issuer: {}, fingerprint: {}",
+            peer_cert.1, peer_cert.2, peer_cert.0
+        );
+
+        let redeem_request: RedeemInviteRequest = serde_json::from_slice(body)
+            .map_err(|_| Status::invalid_argument("Action body is not a valid invite!"))?;
+        let mut db = PgDb::try_from_pool(&self.db_pool)
+            .await
+            .map_err(|e| Status::internal(format!("failed to connect to database! {e}")))?;
+        let acceptance = accept_invite(
+            &mut db,
+            self.local_fingerprint.as_ref(),
+            self.client_cert.as_ref(),
+            peer_cert,
+            redeem_request,
+        )
+        .await
+        .map_err(|e| {
+            warn!("Failed to accept invite with error {e}");
+            Status::permission_denied(format!("Unable to redeem invite: {e}"))
+        })?;
+        info!(
+            "Registered peer relay via invite, sharing identity of {}",
+            acceptance.name
+        );
+
+        Ok(serde_json::to_vec(&acceptance)
+            .map_err(|e| Status::internal(format!("Failed to encode response {e}")))?
+            .into())
+    }
+
+    /// Looks up the [QueryRequest] a [QueryAction] refers to, which must have been submitted by
+    /// the user with the given fingerprint.
+    async fn owned_request(
+        &self,
+        db: &mut PgDb<'_>,
+        fingerprint: &str,
+        body: &[u8],
+    ) -> Result<QueryRequest, Status> {
+        let action: QueryAction = serde_json::from_slice(body).map_err(|_| {
+            Status::invalid_argument("Action body is not a valid JSON encoded QueryAction")
+        })?;
+        let request_id = action.request_id;
+        let user = identity_cache()
+            .get_user(db, fingerprint)
+            .await
+            .map_err(|_| Status::permission_denied("unrecognized user"))?;
+        // Access denied and no query exists intentionally give same response to prevent
+        // brute forcing valid Uuids.
+        db.check_if_request_already_received(&request_id, None)
+            .await
+            .ok()
+            .filter(|request| {
+                request
+                    .origin_info
+                    .origin_user
+                    .as_ref()
+                    .is_some_and(|u| u.x509_sha256 == user.x509_sha256)
+            })
+            .ok_or_else(|| {
+                Status::invalid_argument(format!("No query exists with id {request_id}"))
+            })
+    }
+
+    /// Cancels the tasks of a query which did not complete yet, returning the encoded
+    /// [CancelledQuery]. Tasks which were already submitted to peered relays are no longer
+    /// awaited, but keep executing on those relays.
+    async fn cancel_query(&self, fingerprint: &str, body: &[u8]) -> Result<Bytes, Status> {
+        let mut db = PgDb::try_from_pool(&self.db_pool)
+            .await
+            .map_err(|e| Status::internal(format!("failed to connect to database! {e}")))?;
+        let request = self.owned_request(&mut db, fingerprint, body).await?;
+        let (tasks, remote_tasks) = db
+            .cancel_query_request(request.id)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to cancel query: {e}")))?;
+        info!(
+            "Cancelled {tasks} tasks and {remote_tasks} remote tasks of request {}",
+            request.id
+        );
+
+        let cancelled = CancelledQuery {
+            tasks,
+            remote_tasks,
+        };
+        Ok(serde_json::to_vec(&cancelled)
+            .map_err(|e| Status::internal(format!("Failed to encode response {e}")))?
+            .into())
+    }
+
+    /// Executes the SQL of a query again as a new [QueryRequest], bypassing the result cache,
+    /// and returns the protobuf encoded [FlightInfo] of the new request.
+    async fn refresh_result(
+        &self,
+        client_cert: (String, String, String),
+        body: &[u8],
+        trace_context: &TraceContext,
+    ) -> Result<Bytes, Status> {
+        let mut db = PgDb::try_from_pool(&self.db_pool)
+            .await
+            .map_err(|e| Status::internal(format!("failed to connect to database! {e}")))?;
+        let earlier = self.owned_request(&mut db, &client_cert.0, body).await?;
+        let query = RawQueryRequest {
+            sql: earlier.sql,
+            statements: earlier.statements,
+            request_uuid: None,
+            requesting_user: None,
+            originating_relay: None,
+            originating_task_id: None,
+            return_arrow_schema: None,
+            engine_hint: None,
+            count_only: false,
+            interactive: false,
+            reexecution_of: Some(earlier.originator_request_id),
+            hints: HashMap::new(),
+        };
+        let descriptor = FlightDescriptor::new_cmd(
+            serde_json::to_vec(&query).map_err(|e| Status::from_error(Box::new(e)))?,
+        );
+        let info = self
+            .query_flight_info(&mut db, descriptor, query, client_cert, trace_context)
+            .await?;
+        Ok(info.encode_to_vec().into())
+    }
+
+    /// Returns the schema of the result of query, subject to the same checks of the requester as
+    /// get_flight_info, so that the schemas of Entities the requester may not query are not
+    /// revealed.
+    pub(crate) async fn authorized_schema(
+        &self,
+        db: &mut PgDb<'_>,
+        query: &RawQueryRequest,
+        (fingerprint, subject_dn, issuer_dn): (String, String, String),
+    ) -> Result<Schema, Status> {
+        let (direct_requester, requesting_user, _originating_relay) =
+            verify_query_origination_information(
+                query,
+                db,
+                fingerprint,
+                subject_dn,
+                issuer_dn,
+                &self.local_fingerprint,
+            )
+            .await
+            .map_err(|e| {
+                Status::invalid_argument(format!(
+                    "Unable to parse origination info with error: {e}"
+                ))
+            })?;
+        let (entity_name, _statement, schema) = validate_sql_and_logical_round_trip(&query.sql, db)
+            .await
+            .map_err(|e| {
+                Status::invalid_argument(format!("Query validation failed with error {e}"))
+            })?;
+        authorize_query(&query.sql, &entity_name, &requesting_user, db)
+            .await
+            .map_err(|e| Status::permission_denied(e.to_string()))?;
+        enforce_service_account_rate(&requesting_user, db)
+            .await
+            .map_err(rate_limited)?;
+        enforce_quotas(&direct_requester, db)
+            .await
+            .map_err(rate_limited)?;
+        Ok(schema)
+    }
+
+    /// Creates a [QueryRequest] for a [RawQueryRequest] submitted via Flight and returns the
+    /// [FlightInfo] listing an endpoint for each local and remote task, along with the schema of
+    /// the result and the id of the request in its app_metadata.
+    pub(crate) async fn query_flight_info(
+        &self,
+        db: &mut PgDb<'_>,
+        flight_descriptor: FlightDescriptor,
+        mut query: RawQueryRequest,
+        (fingerprint, subject_dn, issuer_dn): (String, String, String),
+        trace_context: &TraceContext,
+    ) -> Result<FlightInfo, Status> {
+        // FlightInfo describes the result of a single statement
+        if !query.statements.is_empty() {
+            return Err(Status::invalid_argument(
+                "Batch requests with statements are only supported by the REST API",
+            ));
+        }
+
+        let (direct_requester, requesting_user, originating_relay) =
+            verify_query_origination_information(
+                &query,
+                db,
+                fingerprint,
+                subject_dn,
+                issuer_dn,
+                &self.local_fingerprint,
+            )
+            .await
+            .map_err(|e| {
+                Status::invalid_argument(format!(
+                    "Unable to parse origination info with error: {e}"
+                ))
+            })?;
+
+        // It is possible that two requests bypass this check around the same time. This is OK as the database will later
+        // raise a Unique contraint violation error. This check is only for efficiency, the database will always ensure correctness.
+        if let Some(id) = &query.request_uuid {
+            match db
+                .check_if_request_already_received(id, dedup_retention())
+                .await
+            {
+                Ok(_) => {
+                    info!("Request id {id} already processed! Returning succesful empty response with no further action taken.");
+                    return Ok(FlightInfo::new());
+                }
+                Err(e) => debug!("Did not find already existing request with error: {e}"),
+            }
+        }
+
+        debug!("Checking if sql is allowed and logically valid...");
+        let (entity_name, statement, logical_schema) =
+            validate_sql_and_logical_round_trip(&query.sql, db)
+                .await
+                .map_err(|e| {
+                    Status::invalid_argument(format!("Query validation failed with error {e}"))
+                })?;
+        authorize_query(&query.sql, &entity_name, &requesting_user, db)
+            .await
+            .map_err(|e| Status::permission_denied(e.to_string()))?;
+        enforce_service_account_rate(&requesting_user, db)
+            .await
+            .map_err(rate_limited)?;
+        enforce_quotas(&direct_requester, db)
+            .await
+            .map_err(rate_limited)?;
+
+        let schema = query
+            .return_arrow_schema
+            .get_or_insert(logical_schema)
+            .clone();
+
+        debug!("Post round trip sql: {statement}");
+
+        debug!("Creating QueryRequest");
+        let request = match create_query_request(
+            &query,
+            db,
+            &direct_requester,
+            &requesting_user,
+            &originating_relay,
+            trace_context,
+        )
+        .await
+        {
+            Ok(q) => q,
+            Err(MeshError::DuplicateQueryRequest(q)) => {
+                info!(
+                    "Request id {} already processed! Returning succesful \
+                empty response with no further action taken.",
+                    q.originator_request_id
+                );
+                return Ok(FlightInfo::new());
+            }
+            Err(e) => return Err(Status::internal(e.to_string())),
+        };
+
+        debug!("Mapping QueryRequest to local queries");
+        let created_tasks = map_and_create_local_tasks(
+            &statement,
+            &query,
+            &entity_name,
+            &request,
+            db,
+            &direct_requester,
+            &requesting_user,
+            false,
+            0,
+        )
+        .await
+        .map_err(|e| {
+            error!("{e}");
+            Status::internal("Unexpected internal error")
         })?;
 
-        Ok(rb_stream)
+        if let Err(e) = db
+            .record_query_audit(&request, &requesting_user, &created_tasks)
+            .await
+        {
+            error!(
+                "Failed to record audit entry of request {} with error {e}",
+                request.id
+            );
+        }
+
+        let mut response = self
+            .create_flight_info_response(flight_descriptor, &schema, db, created_tasks)
+            .await?;
+
+        response = self
+            .update_flight_info_response_from_remotes(
+                response,
+                db,
+                &query,
+                &statement,
+                &entity_name,
+                &request,
+                originating_relay,
+                requesting_user,
+            )
+            .await?;
+
+        let metadata = FlightInfoMetadata {
+            request_id: request.id,
+        };
+        Ok(response.with_app_metadata(serde_json::to_vec(&metadata).unwrap_or_default()))
     }
 
     /// Creates an intial [FlightInfo] response including a [FlightEndpoint] for each
@@ -196,26 +849,31 @@ impl FlightRelay {
     async fn create_flight_info_response(
         &self,
         flight_descriptor: FlightDescriptor,
+        schema: &Schema,
         db: &mut PgDb<'_>,
         created_tasks: Vec<QueryTask>,
     ) -> Result<FlightInfo, Status> {
         let mut response = FlightInfo::new()
-            .try_with_schema(&Schema::new(Vec::<Field>::new()))
-            .expect("encoding empty schema should never fail")
+            .try_with_schema(schema)
+            .map_err(|e| Status::internal(format!("Failed to encode schema {e}")))?
             .with_descriptor(flight_descriptor);
 
         // If there is relevant local data, add our own endpoint to the FlightInfo
-        let local_relay = db
-            .get_relay_by_x509_fingerprint(self.local_fingerprint.as_ref())
+        let local_relay = identity_cache()
+            .get_relay(db, self.local_fingerprint.as_ref())
             .await
             .map_err(|e| {
                 Status::internal(format!("Unable to get local relay info with error {e}"))
             })?;
-        for task in created_tasks {
+        let freshness =
+            futures::future::join_all(created_tasks.iter().map(|t| self.task_freshness(t.id)))
+                .await;
+        for (task, freshness) in created_tasks.into_iter().zip(freshness) {
             let flight_info_ticket = FlightInfoTicket {
                 data_source_id: task.data_source_id,
                 task_id: task.id,
             };
+            let metadata = ResultMetadata { freshness };
 
             let ticket = serde_json::to_vec(&flight_info_ticket).map_err(|e| {
                 error!("Unexpected error encoding flight_info_ticket as json {e}");
@@ -224,7 +882,8 @@ impl FlightRelay {
             response = response.with_endpoint(
                 FlightEndpoint::new()
                     .with_ticket(Ticket::new(Into::<Bytes>::into(ticket)))
-                    .with_location(&local_relay.flight_endpoint),
+                    .with_location(&local_relay.flight_endpoint)
+                    .with_app_metadata(serde_json::to_vec(&metadata).unwrap_or_default()),
             )
         }
 
@@ -270,7 +929,10 @@ impl FlightRelay {
                 .await
                 .map_err(|e| Status::from_error(Box::new(e)))?;
             debug!("Connecting to {}", remote.flight_endpoint);
-            let mut client = match self.get_flight_client(&remote).await {
+            let mut client = match self
+                .get_flight_client(&remote, request.trace_context.as_ref())
+                .await
+            {
                 Ok(c) => c,
                 Err(e) => {
                     error!("Failed to connect to {remote:?} with error {e}");
@@ -315,10 +977,12 @@ impl FlightRelay {
         &self,
         first_data: Result<FlightData, Status>,
         db: &mut PgDb<'_>,
-    ) -> Result<(Uuid, Uuid, Arc<Schema>), Status> {
+    ) -> Result<(Uuid, Uuid, Arc<Schema>, ResultMetadata, ResultFormat), Status> {
         // These values should be initialized in the first batch of FlightData, otherwise error is thrown
         let remote_task_id;
         let local_task_id;
+        let result_format;
+        let mut metadata = ResultMetadata::default();
 
         let schema = match first_data {
             Ok(data) => {
@@ -331,7 +995,7 @@ impl FlightRelay {
                         }
                         let desc_remote_task_id = desc.path[0].clone();
                         let desc_local_task_id = desc.path[1].clone();
-                        let _remote_task = db
+                        let (remote_task, _) = db
                             .get_remote_query_task(
                                 uuid::Uuid::parse_str(&desc_local_task_id).map_err(|_e| {
                                     Status::invalid_argument(format!(
@@ -351,6 +1015,14 @@ impl FlightRelay {
                         local_task_id = Uuid::parse_str(&desc_local_task_id).map_err(|_e| {
                             Status::internal("unable to parse desc_origin_task_id as uuid")
                         })?;
+                        result_format = db
+                            .get_request_result_format(remote_task.query_request_id)
+                            .await
+                            .map_err(|e| {
+                                Status::internal(format!(
+                                    "Failed to look up result format of task {desc_local_task_id}: {e}"
+                                ))
+                            })?;
                     }
                     None => {
                         return Err(Status::invalid_argument(
@@ -359,6 +1031,16 @@ impl FlightRelay {
                     }
                 };
 
+                // Relays which do not report freshness send no metadata
+                if !data.app_metadata.is_empty() {
+                    match serde_json::from_slice(&data.app_metadata) {
+                        Ok(m) => metadata = m,
+                        Err(e) => warn!(
+                            "Ignoring invalid metadata of flight {remote_task_id} with error {e}"
+                        ),
+                    }
+                }
+
                 Arc::new(
                     try_schema_from_flatbuffer_bytes(&data.data_header).map_err(|_e| {
                         Status::invalid_argument(
@@ -370,7 +1052,13 @@ impl FlightRelay {
             Err(e) => return Err(e.to_owned()),
         };
         // These values should be initialized in the first batch of FlightData, otherwise error is thrown
-        Ok((remote_task_id, local_task_id, schema))
+        Ok((
+            remote_task_id,
+            local_task_id,
+            schema,
+            metadata,
+            result_format,
+        ))
     }
 }
 
@@ -384,15 +1072,56 @@ impl FlightService for FlightRelay {
     type ListActionsStream = BoxStream<'static, Result<ActionType, Status>>;
     type DoExchangeStream = BoxStream<'static, Result<FlightData, Status>>;
 
+    /// Returns the Arrow schema of the result of a query without executing it. The
+    /// [FlightDescriptor] is either a path naming an Entity, whose schema is returned, or a cmd
+    /// holding a JSON encoded [RawQueryRequest] as passed to get_flight_info, or a FlightSQL
+    /// command. Queries are subject to the same checks of the requester as get_flight_info.
     async fn get_schema(
         &self,
-        _request: Request<FlightDescriptor>,
+        request: Request<FlightDescriptor>,
     ) -> Result<Response<SchemaResult>, Status> {
-        Err(Status::unimplemented("Not yet implemented"))
+        let client_cert = extract_certs(&request, &self.client_cert_header)?;
+        info!(
+            "Got get_schema request from: subject: {}, issuer: {}, fingerprint: {}",
+            client_cert.1, client_cert.2, client_cert.0
+        );
+
+        let descriptor = request.into_inner();
+        let mut db = PgDb::try_from_pool(&self.db_pool)
+            .await
+            .map_err(|e| Status::internal(format!("failed to connect to database! {e}")))?;
+        let schema = match SqlCommand::decode(&descriptor.cmd)? {
+            Some(command) => self.sql_schema(&mut db, command, client_cert).await?,
+            None => {
+                let query = match descriptor.r#type() {
+                    DescriptorType::Path => match descriptor.path.as_slice() {
+                        [entity_name] => sql_query_request(format!(
+                            "select * from {}",
+                            name_to_ident(entity_name)
+                        )),
+                        _ => {
+                            return Err(Status::invalid_argument(
+                                "FlightDescriptor.path must name a single Entity",
+                            ))
+                        }
+                    },
+                    DescriptorType::Cmd => parse_raw_query_request(&descriptor)?,
+                    DescriptorType::Unknown => {
+                        return Err(Status::invalid_argument("Unknown FlightDescriptor type"))
+                    }
+                };
+                Arc::new(self.authorized_schema(&mut db, &query, client_cert).await?)
+            }
+        };
+        let result = SchemaAsIpc::new(&schema, &IpcWriteOptions::default())
+            .try_into()
+            .map_err(|e: ArrowError| Status::internal(format!("Failed to encode schema {e}")))?;
+        Ok(Response::new(result))
     }
 
     /// Retrieves an asynchronously generated query result (initiated via a REST call to /query)
-    /// or a synchronously generated query result (initiated via get_flight_info).
+    /// or a synchronously generated query result (initiated via get_flight_info), or lists the
+    /// metadata a FlightSQL command asks for.
     async fn do_get(
         &self,
         request: Request<Ticket>,
@@ -409,48 +1138,40 @@ impl FlightService for FlightRelay {
             .await
             .map_err(|e| Status::internal(format!("failed to connect to database! {e}")))?;
 
-        let flight_info_ticket: FlightInfoTicket =
-            serde_json::from_slice(&request.into_inner().ticket)
-                .map_err(|_| Status::invalid_argument("Passed Ticket is not valid!"))?;
-
-        let task_id = flight_info_ticket.task_id;
-
-        debug!("Request is for task {task_id}");
-
-        let retreiving_user = db
-            .get_user_by_x509_fingerprint(&fingerprint)
-            .await
-            .map_err(|_| Status::permission_denied("unrecognized user"))?;
-
-        let (con, source, task, request, _relay) =
-            db.get_query_task(task_id).await.map_err(|e| {
-                Status::invalid_argument(format!("No query exists with id {task_id}, error {e}"))
-            })?;
-
-        // Access denied and no query exists intentionally give same response to prevent
-        // brute forcing valid Uuids.
-        match request.origin_info.origin_user {
-            Some(origin_user) => {
-                if origin_user.x509_sha256 != retreiving_user.x509_sha256 {
-                    warn!("Rejecting request for valid Uuid to user with fingerprint {fingerprint} which does not match original requester!.");
-                    return Err(Status::invalid_argument(format!(
-                        "No query exists with id {task_id}"
-                    )));
-                }
-            }
-            None => {
-                error!("Origin user is not set for query with id {task_id}");
-                return Err(Status::invalid_argument(format!(
-                    "No query exists with id {task_id}"
-                )));
-            }
+        let ticket = request.into_inner().ticket;
+        if let Some(command) = SqlCommand::decode(&ticket)? {
+            return Ok(Response::new(self.sql_do_get(&mut db, command).await?));
         }
+        if let Ok(stored_ticket) = serde_json::from_slice::<StoredResultTicket>(&ticket) {
+            return self
+                .do_get_stored_result(&mut db, &fingerprint, stored_ticket)
+                .await;
+        }
+        let flight_info_ticket: FlightInfoTicket = serde_json::from_slice(&ticket)
+            .map_err(|_| Status::invalid_argument("Passed Ticket is not valid!"))?;
 
-        let rb_stream = self.execute_query_task(con, source, task).await?;
-
-        let flight_data_stream = FlightDataEncoderBuilder::new()
-            .build(rb_stream.map_err(|e| FlightError::ExternalError(Box::new(e))))
-            .map_err(|e| Status::from_error(Box::new(e)));
+        let rb_stream = self
+            .local_task_stream(&mut db, &fingerprint, flight_info_ticket.task_id)
+            .await?;
+        let schema = rb_stream.schema();
+        let counter = Arc::new(TransferCounter::default());
+        let counter_clone = counter.clone();
+        let rb_stream = rb_stream.inspect_ok(move |batch| {
+            let bytes = batch.get_array_memory_size();
+            counter_clone.add(batch.num_rows(), bytes);
+            metrics().bytes_streamed("flight", bytes);
+        });
+
+        // The schema is sent right away, so that no progress message precedes it
+        let flight_data_stream = with_progress(
+            FlightDataEncoderBuilder::new()
+                .with_schema(schema)
+                .build(rb_stream.map_err(|e| FlightError::ExternalError(Box::new(e))))
+                .map_err(|e| Status::from_error(Box::new(e))),
+            counter,
+            self.progress_interval,
+            progress_flight_data,
+        );
 
         debug!("Sending data stream response...");
 
@@ -481,6 +1202,7 @@ impl FlightService for FlightRelay {
             .await
             .map_err(|e| Status::from_error(Box::new(e)))?;
 
+        #[allow(clippy::result_large_err)]
         let stream = futures::stream::iter(all_information.into_iter().map(|t| {
             let ticket = serde_json::to_vec(&t).map_err(|e| {
                 error!("Unexpected error encoding flight_info_ticket as json {e}");
@@ -498,10 +1220,15 @@ impl FlightService for FlightRelay {
     /// a list of all endpoints which have relevant data to the query. The caller can then
     /// invoke do_get to retrieve all of the data  from each relay in the network directly.
     /// This method is a synchronous gRPC analog to the rest_server's /query endpoint.
+    /// FlightSQL statements and metadata commands are accepted as well.
     async fn get_flight_info(
         &self,
         get_info_request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>, Status> {
+        // The span ends once the call returns, its context is stored with the request
+        let (_span, trace_context) =
+            TraceContext::from_headers(|name| get_info_request.metadata().get(name)?.to_str().ok())
+                .start_span(info_span!("get_flight_info"));
         let (fingerprint, subject_dn, issuer_dn) =
             extract_certs(&get_info_request, &self.client_cert_header)?;
 
@@ -509,121 +1236,39 @@ impl FlightService for FlightRelay {
             "Got get_flight_info request from: subject: {}, issuer: {}, fingerprint: {}",
             subject_dn, issuer_dn, fingerprint
         );
+        metrics().query_received("flight");
 
         let mut db = PgDb::try_from_pool(&self.db_pool)
             .await
             .map_err(|e| Status::internal(format!("failed to connect to database! {e}")))?;
 
         let flight_descriptor = get_info_request.into_inner();
-        let mut query: RawQueryRequest =
-            serde_json::from_slice(&flight_descriptor.cmd).map_err(|_e| {
-                Status::invalid_argument(
-                    "FlightDescriptior.cmd is not a valid JSON encoded RawQueryRequest",
+        let client_cert = (fingerprint, subject_dn, issuer_dn);
+        let response = match SqlCommand::decode(&flight_descriptor.cmd)? {
+            Some(command) => {
+                self.sql_flight_info(
+                    &mut db,
+                    flight_descriptor,
+                    command,
+                    client_cert,
+                    &trace_context,
                 )
-            })?;
-
-        debug!("Got RawQueryRequest: {:?}", query);
-
-        let (direct_requester, requesting_user, originating_relay) =
-            verify_query_origination_information(
-                &query,
-                &mut db,
-                fingerprint,
-                subject_dn,
-                issuer_dn,
-                &self.local_fingerprint,
-            )
-            .await
-            .map_err(|e| {
-                Status::invalid_argument(format!(
-                    "Unable to parse origination info with error: {e}"
-                ))
-            })?;
-
-        // It is possible that two requests bypass this check around the same time. This is OK as the database will later
-        // raise a Unique contraint violation error. This check is only for efficiency, the database will always ensure correctness.
-        match &query.request_uuid {
-            Some(id) => match db.check_if_request_already_received(id).await {
-                Ok(_) => {
-                    info!("Request id {id} already processed! Returning succesful empty response with no further action taken.");
-                    let empty_info = FlightInfo::new();
-                    return Ok(Response::new(empty_info));
-                }
-                Err(e) => debug!("Did not find already existing request with error: {e}"),
-            },
-            None => (),
-        }
-
-        debug!("Checking if sql is allowed and logically valid...");
-        let (entity_name, statement, logical_schema) =
-            validate_sql_and_logical_round_trip(&query.sql, &mut db)
-                .await
-                .map_err(|e| {
-                    Status::invalid_argument(format!("Query validation failed with error {e}"))
-                })?;
-
-        if query.return_arrow_schema.is_none() {
-            query.return_arrow_schema = Some(logical_schema);
-        }
-
-        debug!("Post round trip sql: {statement}");
-
-        debug!("Creating QueryRequest");
-        let request = match create_query_request(
-            &query,
-            &mut db,
-            &direct_requester,
-            &requesting_user,
-            &originating_relay,
-        )
-        .await
-        {
-            Ok(q) => q,
-            Err(MeshError::DuplicateQueryRequest(q)) => {
-                info!(
-                    "Request id {} already processed! Returning succesful \
-                empty response with no further action taken.",
-                    q.originator_request_id
-                );
-                let empty_info = FlightInfo::new();
-                return Ok(Response::new(empty_info));
+                .await?
+            }
+            None => {
+                let query = parse_raw_query_request(&flight_descriptor)?;
+                debug!("Got RawQueryRequest: {:?}", query);
+                self.query_flight_info(
+                    &mut db,
+                    flight_descriptor,
+                    query,
+                    client_cert,
+                    &trace_context,
+                )
+                .await?
             }
-            Err(e) => return Err(Status::internal(e.to_string())),
         };
 
-        debug!("Mapping QueryRequest to local queries");
-        let created_tasks = map_and_create_local_tasks(
-            &statement,
-            &query,
-            &entity_name,
-            &request,
-            &mut db,
-            &direct_requester,
-            &requesting_user,
-        )
-        .await
-        .map_err(|e| {
-            error!("{e}");
-            Status::internal("Unexpected internal error")
-        })?;
-
-        let mut response = self
-            .create_flight_info_response(flight_descriptor, &mut db, created_tasks)
-            .await?;
-
-        response = self
-            .update_flight_info_response_from_remotes(
-                response,
-                &mut db,
-                &query,
-                &statement,
-                &entity_name,
-                &request,
-                originating_relay,
-                requesting_user,
-            )
-            .await?;
-
         debug!("Sending response: {response:?}");
 
         Ok(Response::new(response))
@@ -631,7 +1276,7 @@ impl FlightService for FlightRelay {
 
     /// This call is invoked by other Relay's QueryRunner service to asynchronously send
     /// the result of a query propagated via the rest API. The do_put call stores the
-    /// data as a parquet file in an ObjectStore and stores metadata in the database
+    /// data in an ObjectStore in the result format of the query and stores metadata in the database
     /// so that the rest API can retrieve this remotely generated result on demand.
     async fn do_put(
         &self,
@@ -649,33 +1294,85 @@ impl FlightService for FlightRelay {
             .await
             .map_err(|e| Status::internal(format!("failed to connect to database! {e}")))?;
 
-        let (remote_task_id, local_task_id, schema) =
+        let (remote_task_id, local_task_id, schema, metadata, result_format) =
             if let Some(first_data) = flight_stream.message().await.transpose() {
                 self.process_first_do_put_flightdata(first_data, &mut db)
                     .await?
             } else {
                 return Err(Status::internal("missing first flight data message!"));
             };
+        let source_relay = identity_cache()
+            .get_relay(&mut db, &fingerprint)
+            .await
+            .ok()
+            .map(|relay| relay.name);
+
         let mut new_flight = NewFlightStream {
             query_task_remote_id: local_task_id,
-            remote_fingerprint: fingerprint,
+            remote_fingerprint: fingerprint.clone(),
             flight_id: remote_task_id,
             status: FlightStreamStatus::Started,
+            freshness: metadata.freshness,
         };
 
-        db.upsert_flight_stream(&new_flight)
+        // Runners retry sending results, so the same flight may be received more than once. Only
+        // a Failed flight, or one whose do_put stopped sending heartbeats, is received again.
+        let stale_before = Utc::now()
+            - chrono::Duration::from_std(flight_stream_timeout())
+                .map_err(|e| Status::internal(e.to_string()))?;
+        let (existing, started) = db
+            .start_flight_stream(&new_flight, stale_before)
             .await
-            .map_err(|e| Status::internal(format!("Failed to upsert new flight! Error: {}", e)))?;
+            .map_err(|e| Status::internal(format!("Failed to upsert new flight! Error: {e}")))?;
+        let dedup = match existing {
+            Some(flight) if flight.remote_fingerprint != fingerprint => {
+                warn!(
+                    "Rejecting do_put for flight {remote_task_id} from {fingerprint}, which was \
+                    previously sent by {}",
+                    flight.remote_fingerprint
+                );
+                return Err(Status::permission_denied(format!(
+                    "Flight {remote_task_id} was sent by another relay"
+                )));
+            }
+            Some(flight) if flight.status == FlightStreamStatus::Complete => PutDedup::Skipped,
+            Some(flight) if !started => {
+                warn!(
+                    "Rejecting do_put for flight {remote_task_id}, which is {:?} and was updated \
+                    at {}",
+                    flight.status, flight.updated_at
+                );
+                return Err(Status::unavailable(format!(
+                    "Flight {remote_task_id} is still being received, retry once it completed"
+                )));
+            }
+            Some(_) => PutDedup::Replaced,
+            None => PutDedup::New,
+        };
+        let decision = PutResult {
+            app_metadata: serde_json::to_vec(&PutDecision {
+                flight_id: remote_task_id,
+                dedup,
+            })
+            .unwrap_or_default()
+            .into(),
+        };
+        if dedup == PutDedup::Skipped {
+            info!("Flight {remote_task_id} is already complete, discarding the resent result");
+            return Ok(Response::new(
+                Box::pin(futures::stream::once(async { Ok(decision) })) as Self::DoPutStream,
+            ));
+        }
+        debug!("Receiving flight {remote_task_id}: {dedup:?}");
 
         let schema_clone = schema.clone();
         let dictionaries_by_id = Arc::new(HashMap::new());
 
-        // ResultManager currently sending arrow schema twice. This throws the second schema message away...
-        // TODO: don't send the schema twice.
-        flight_stream.message().await?;
-
+        let counter = Arc::new(TransferCounter::default());
+        let counter_clone = counter.clone();
+        #[allow(clippy::result_large_err)]
         let rb_stream = Box::pin(
-            flight_stream
+            put_data_messages(flight_stream)
                 .map(move |data| match data {
                     Ok(data) => Ok((data, schema_clone.clone(), dictionaries_by_id.clone())),
                     Err(e) => Err(e),
@@ -684,24 +1381,49 @@ impl FlightService for FlightRelay {
                     flight_data_to_arrow_batch(&data, inner_schema, &id_dict)
                         .map_err(|e| Status::internal(e.to_string()))
                 })
-                .map_err(|e| DataFusionError::Execution(e.to_string())),
+                .map_err(|e| DataFusionError::Execution(e.to_string()))
+                .inspect_ok(move |batch| {
+                    counter_clone.add(batch.num_rows(), batch.get_array_memory_size())
+                }),
         );
 
-        self.result_manager
-            .write_task_result(&remote_task_id, rb_stream, schema.clone())
-            .await
-            .map_err(|e| Status::internal(format!("Writing stream failed with err {e}")))?;
+        // The result is written while the response streams, so that the sender receives progress
+        // messages during long transfers and a final message with complete set once written.
+        let result_manager = self.result_manager.clone();
+        let pool = self.db_pool.clone();
+        let write_result = async move {
+            let write = result_manager.write_task_result(
+                &remote_task_id,
+                source_relay.as_deref(),
+                result_format,
+                rb_stream,
+                schema.clone(),
+            );
+            let written = with_heartbeat(&pool, &remote_task_id, write).await;
 
-        new_flight.status = FlightStreamStatus::Complete;
-        db.upsert_flight_stream(&new_flight).await.map_err(|e| {
-            Status::internal(format!("Failed to update flight status! Error: {}", e))
-        })?;
-        let do_put_result = PutResult {
-            app_metadata: "success".into(),
+            new_flight.status = match written {
+                Ok(()) => FlightStreamStatus::Complete,
+                Err(_) => FlightStreamStatus::Failed,
+            };
+            let mut db = PgDb::try_from_pool(&pool)
+                .await
+                .map_err(|e| Status::internal(format!("failed to connect to database! {e}")))?;
+            db.upsert_flight_stream(&new_flight).await.map_err(|e| {
+                Status::internal(format!("Failed to update flight status! Error: {}", e))
+            })?;
+            written.map_err(|e| Status::internal(format!("Writing stream failed with err {e}")))
         };
-        Ok(Response::new(
-            Box::pin(futures::stream::once(async { Ok(do_put_result) })) as Self::DoPutStream,
-        ))
+        let write_stream =
+            futures::stream::once(write_result).filter_map(|r| async move { r.err().map(Err) });
+        let put_result_stream =
+            with_progress(write_stream, counter, self.progress_interval, |progress| {
+                PutResult {
+                    app_metadata: serde_json::to_vec(progress).unwrap_or_default().into(),
+                }
+            });
+        Ok(Response::new(Box::pin(
+            futures::stream::once(async { Ok(decision) }).chain(put_result_stream),
+        ) as Self::DoPutStream))
     }
 
     async fn poll_flight_info(
@@ -711,24 +1433,213 @@ impl FlightService for FlightRelay {
         Err(Status::unimplemented("Not yet implemented"))
     }
 
+    /// Supports the following actions, each of which returns a single result:
+    /// - [REDEEM_INVITE_ACTION], with which a peer Relay redeems an invite issued by this Relay.
+    /// - [PING_ACTION], with which peers check that this Relay is reachable.
+    /// - [CANCEL_QUERY_ACTION] and [REFRESH_RESULT_ACTION], with which users cancel or execute
+    ///   again a query they submitted via get_flight_info.
+    /// - [LIST_ENTITIES_ACTION], with which users discover what they are able to query.
+    /// - [CREATE_PREPARED_STATEMENT_ACTION] and [CLOSE_PREPARED_STATEMENT_ACTION], with which
+    ///   FlightSQL clients prepare statements.
     async fn do_action(
         &self,
-        _request: Request<Action>,
+        request: Request<Action>,
     ) -> Result<Response<Self::DoActionStream>, Status> {
-        Err(Status::unimplemented("Not yet implemented"))
+        // The span ends once the call returns, its context is stored with refreshed requests
+        let (_span, trace_context) =
+            TraceContext::from_headers(|name| request.metadata().get(name)?.to_str().ok())
+                .start_span(info_span!("do_action"));
+        let client_cert = extract_certs(&request, &self.client_cert_header)?;
+        let action = request.into_inner();
+        let body = match action.r#type.as_str() {
+            PING_ACTION => Bytes::new(),
+            REDEEM_INVITE_ACTION => self.redeem_invite(client_cert, &action.body).await?,
+            CANCEL_QUERY_ACTION => self.cancel_query(&client_cert.0, &action.body).await?,
+            REFRESH_RESULT_ACTION => {
+                self.refresh_result(client_cert, &action.body, &trace_context)
+                    .await?
+            }
+            LIST_ENTITIES_ACTION => {
+                let mut db = PgDb::try_from_pool(&self.db_pool)
+                    .await
+                    .map_err(|e| Status::internal(format!("failed to connect to database! {e}")))?;
+                let entities = entity_summaries(&mut db)
+                    .await
+                    .map_err(|e| Status::internal(format!("Failed to list entities: {e}")))?;
+                serde_json::to_vec(&entities)
+                    .map_err(|e| Status::internal(format!("Failed to encode response {e}")))?
+                    .into()
+            }
+            CREATE_PREPARED_STATEMENT_ACTION => {
+                self.create_prepared_statement(client_cert, &action.body)
+                    .await?
+            }
+            // Prepared statements hold no state, so there is nothing to close
+            CLOSE_PREPARED_STATEMENT_ACTION => Bytes::new(),
+            unknown => {
+                return Err(Status::unimplemented(format!("Unknown action {unknown}")));
+            }
+        };
+        let result = arrow_flight::Result { body };
+        Ok(Response::new(
+            Box::pin(futures::stream::once(async { Ok(result) })) as Self::DoActionStream,
+        ))
     }
 
     async fn list_actions(
         &self,
         _request: Request<Empty>,
     ) -> Result<Response<Self::ListActionsStream>, Status> {
-        Err(Status::unimplemented("Not yet implemented"))
+        let redeem_invite = ActionType {
+            r#type: REDEEM_INVITE_ACTION.to_string(),
+            description: "Redeem an invite issued by this relay to register as a peer".to_string(),
+        };
+        let ping = ActionType {
+            r#type: PING_ACTION.to_string(),
+            description: "Check that this relay is reachable".to_string(),
+        };
+        let cancel_query = ActionType {
+            r#type: CANCEL_QUERY_ACTION.to_string(),
+            description: "Cancel the tasks of a query which did not complete yet".to_string(),
+        };
+        let refresh_result = ActionType {
+            r#type: REFRESH_RESULT_ACTION.to_string(),
+            description: "Execute a query again, returning the FlightInfo of its new result"
+                .to_string(),
+        };
+        let list_entities = ActionType {
+            r#type: LIST_ENTITIES_ACTION.to_string(),
+            description: "List the Entities of this relay along with their Information".to_string(),
+        };
+        let create_prepared_statement = ActionType {
+            r#type: CREATE_PREPARED_STATEMENT_ACTION.to_string(),
+            description: "Prepare a FlightSQL statement, returning its handle and result schema"
+                .to_string(),
+        };
+        let close_prepared_statement = ActionType {
+            r#type: CLOSE_PREPARED_STATEMENT_ACTION.to_string(),
+            description: "Close a prepared FlightSQL statement".to_string(),
+        };
+        let actions = [
+            redeem_invite,
+            ping,
+            cancel_query,
+            refresh_result,
+            list_entities,
+            create_prepared_statement,
+            close_prepared_statement,
+        ];
+        Ok(Response::new(
+            Box::pin(futures::stream::iter(actions.map(Ok))) as Self::ListActionsStream,
+        ))
     }
 
+    /// Executes a query in a single call, for small queries where the round trips of
+    /// get_flight_info followed by a do_get per endpoint dominate. The first FlightData sent by
+    /// the client carries the same [FlightDescriptor] as passed to get_flight_info, and anything
+    /// sent after it is ignored. The results of the local tasks of the query are streamed back as
+    /// they are produced. Endpoints at other relays are only served to the user who submitted the
+    /// query, so they are listed by a final FlightData without data, whose app_metadata is the
+    /// protobuf encoded [FlightInfo] of the query holding only those endpoints.
     async fn do_exchange(
         &self,
-        _request: Request<Streaming<FlightData>>,
+        request: Request<Streaming<FlightData>>,
     ) -> Result<Response<Self::DoExchangeStream>, Status> {
-        Err(Status::unimplemented("Not yet implemented"))
+        // The span ends once the call returns, its context is stored with the request
+        let (_span, trace_context) =
+            TraceContext::from_headers(|name| request.metadata().get(name)?.to_str().ok())
+                .start_span(info_span!("do_exchange"));
+        let client_cert = extract_certs(&request, &self.client_cert_header)?;
+        info!(
+            "Got do_exchange request from: subject: {}, issuer: {}, fingerprint: {}",
+            client_cert.1, client_cert.2, client_cert.0
+        );
+        metrics().query_received("flight");
+
+        let flight_descriptor = request
+            .into_inner()
+            .message()
+            .await?
+            .and_then(|data| data.flight_descriptor)
+            .ok_or_else(|| {
+                Status::invalid_argument("The first FlightData must carry a FlightDescriptor")
+            })?;
+        let query = parse_raw_query_request(&flight_descriptor)?;
+        debug!("Got RawQueryRequest: {:?}", query);
+
+        let mut db = PgDb::try_from_pool(&self.db_pool)
+            .await
+            .map_err(|e| Status::internal(format!("failed to connect to database! {e}")))?;
+        let fingerprint = client_cert.0.clone();
+        let mut info = self
+            .query_flight_info(
+                &mut db,
+                flight_descriptor,
+                query,
+                client_cert,
+                &trace_context,
+            )
+            .await?;
+        // A request which was already received has no schema or endpoints
+        let schema = info.clone().try_decode_schema().map_err(|_| {
+            Status::already_exists("The request was already received and will not be executed")
+        })?;
+
+        let local_relay = identity_cache()
+            .get_relay(&mut db, self.local_fingerprint.as_ref())
+            .await
+            .map_err(|e| {
+                Status::internal(format!("Unable to get local relay info with error {e}"))
+            })?;
+        let (local_endpoints, remote_endpoints): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut info.endpoint)
+                .into_iter()
+                .partition(|endpoint| {
+                    endpoint
+                        .location
+                        .iter()
+                        .any(|location| location.uri == local_relay.flight_endpoint)
+                });
+        info.endpoint = remote_endpoints;
+
+        // Every local task starts executing right away, and batches are sent as they arrive
+        let mut rb_streams = Vec::with_capacity(local_endpoints.len());
+        for endpoint in local_endpoints {
+            let flight_info_ticket: FlightInfoTicket = endpoint
+                .ticket
+                .and_then(|ticket| serde_json::from_slice(&ticket.ticket).ok())
+                .ok_or_else(|| Status::internal("Unexpected internal error"))?;
+            rb_streams.push(
+                self.local_task_stream(&mut db, &fingerprint, flight_info_ticket.task_id)
+                    .await?,
+            );
+        }
+        let counter = Arc::new(TransferCounter::default());
+        let counter_clone = counter.clone();
+        let rb_stream = futures::stream::select_all(rb_streams).inspect_ok(move |batch| {
+            let bytes = batch.get_array_memory_size();
+            counter_clone.add(batch.num_rows(), bytes);
+            metrics().bytes_streamed("flight", bytes);
+        });
+
+        // Every task returns the logical schema, which is sent even if there are no results
+        let flight_data_stream = with_progress(
+            FlightDataEncoderBuilder::new()
+                .with_schema(Arc::new(schema))
+                .build(rb_stream.map_err(|e| FlightError::ExternalError(Box::new(e))))
+                .map_err(|e| Status::from_error(Box::new(e))),
+            counter,
+            self.progress_interval,
+            progress_flight_data,
+        );
+        let remote_endpoints = metadata_flight_data(info.encode_to_vec());
+        let flight_data_stream =
+            flight_data_stream.chain(futures::stream::once(async { Ok(remote_endpoints) }));
+
+        debug!("Sending data stream response...");
+
+        Ok(Response::new(
+            Box::pin(flight_data_stream) as Self::DoExchangeStream
+        ))
     }
 }
@@ -0,0 +1,301 @@
+//! FlightSQL facade of the [FlightRelay], with which off-the-shelf FlightSQL clients such as the
+//! JDBC and ADBC drivers query the mesh. FlightSQL commands are protobuf encoded [Any] messages,
+//! which are told apart from the JSON encoded [RawQueryRequest]s and tickets the relay otherwise
+//! accepts, so both are served on the same endpoint.
+//!
+//! Statements are executed like a [RawQueryRequest] submitted via get_flight_info, so their
+//! results are retrieved from the endpoint of every relay with relevant data. Entities are listed
+//! as tables which belong to no catalog and no schema.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, StringArray};
+use arrow::ipc::writer::IpcWriteOptions;
+use arrow::record_batch::RecordBatch;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::sql::metadata::{SqlInfoData, SqlInfoDataBuilder};
+use arrow_flight::sql::{
+    ActionCreatePreparedStatementRequest, ActionCreatePreparedStatementResult, Any, Command,
+    CommandGetCatalogs, CommandGetDbSchemas, CommandGetSqlInfo, CommandGetTables, ProstMessageExt,
+    SqlInfo,
+};
+use arrow_flight::{FlightData, FlightDescriptor, FlightEndpoint, FlightInfo, IpcMessage};
+use arrow_flight::{SchemaAsIpc, Ticket};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
+use mesh::crud::PgDb;
+use mesh::model::query::{RawQueryRequest, TraceContext};
+use prost::Message;
+use tonic::codegen::Bytes;
+use tonic::Status;
+
+use crate::flight::FlightRelay;
+
+/// Type of the FlightSQL [Action][arrow_flight::Action] which prepares a statement.
+pub const CREATE_PREPARED_STATEMENT_ACTION: &str = "CreatePreparedStatement";
+/// Type of the FlightSQL [Action][arrow_flight::Action] which closes a prepared statement.
+pub const CLOSE_PREPARED_STATEMENT_ACTION: &str = "ClosePreparedStatement";
+
+/// Every Entity is listed as a table of this type.
+const TABLE_TYPE: &str = "TABLE";
+
+/// A FlightSQL command supported by the relay.
+pub enum SqlCommand {
+    /// Executes a statement or prepared statement with the given SQL.
+    Query(String),
+    /// Lists metadata, which the relay answers itself.
+    Metadata(MetadataCommand),
+}
+
+/// A FlightSQL command listing metadata, whose result is a single RecordBatch.
+pub enum MetadataCommand {
+    Catalogs(CommandGetCatalogs),
+    DbSchemas(CommandGetDbSchemas),
+    Tables(CommandGetTables),
+    TableTypes,
+    SqlInfo(CommandGetSqlInfo),
+}
+
+impl SqlCommand {
+    /// Decodes the cmd of a [FlightDescriptor] or a [Ticket]. Returns None if it is not a FlightSQL
+    /// command, e.g. because it is a JSON encoded [RawQueryRequest].
+    #[allow(clippy::result_large_err)] // Status is the error type returned to FlightSQL clients
+    pub fn decode(bytes: &[u8]) -> Result<Option<Self>, Status> {
+        let Ok(any) = Any::decode(bytes) else {
+            return Ok(None);
+        };
+        let command = Command::try_from(any)
+            .map_err(|e| Status::invalid_argument(format!("Invalid FlightSQL command: {e}")))?;
+        let command = match command {
+            Command::Unknown(_) => return Ok(None),
+            Command::CommandStatementQuery(cmd) => SqlCommand::Query(cmd.query),
+            // Prepared statements hold no state, their handle is the SQL of the statement
+            Command::CommandPreparedStatementQuery(cmd) => SqlCommand::Query(
+                String::from_utf8(cmd.prepared_statement_handle.to_vec())
+                    .map_err(|_| Status::invalid_argument("Invalid prepared statement handle"))?,
+            ),
+            Command::CommandGetCatalogs(cmd) => {
+                SqlCommand::Metadata(MetadataCommand::Catalogs(cmd))
+            }
+            Command::CommandGetDbSchemas(cmd) => {
+                SqlCommand::Metadata(MetadataCommand::DbSchemas(cmd))
+            }
+            Command::CommandGetTables(cmd) => SqlCommand::Metadata(MetadataCommand::Tables(cmd)),
+            Command::CommandGetTableTypes(_) => SqlCommand::Metadata(MetadataCommand::TableTypes),
+            Command::CommandGetSqlInfo(cmd) => SqlCommand::Metadata(MetadataCommand::SqlInfo(cmd)),
+            command => {
+                return Err(Status::unimplemented(format!(
+                    "FlightSQL command {} is not supported",
+                    command.type_url()
+                )))
+            }
+        };
+        Ok(Some(command))
+    }
+}
+
+impl MetadataCommand {
+    fn schema(&self) -> SchemaRef {
+        match self {
+            MetadataCommand::Catalogs(cmd) => cmd.clone().into_builder().schema(),
+            MetadataCommand::DbSchemas(cmd) => cmd.clone().into_builder().schema(),
+            MetadataCommand::Tables(cmd) => cmd.clone().into_builder().schema(),
+            MetadataCommand::TableTypes => table_types_schema(),
+            MetadataCommand::SqlInfo(_) => Arc::new(SqlInfoDataBuilder::schema().clone()),
+        }
+    }
+
+    async fn record_batch(self, db: &mut PgDb<'_>) -> Result<RecordBatch, Status> {
+        match self {
+            // Entities belong to no catalog and no schema
+            MetadataCommand::Catalogs(cmd) => cmd.into_builder().build().map_err(metadata_error),
+            MetadataCommand::DbSchemas(cmd) => cmd.into_builder().build().map_err(metadata_error),
+            MetadataCommand::Tables(cmd) => {
+                let all_information = db
+                    .get_all_information()
+                    .await
+                    .map_err(|e| Status::internal(format!("Failed to list entities: {e}")))?;
+                let mut builder = cmd.into_builder();
+                for (entity, information) in all_information {
+                    let schema = Schema::new(
+                        information
+                            .into_iter()
+                            .map(|info| {
+                                Field::new(info.name, info.arrow_dtype.inner, info.nullable)
+                            })
+                            .collect::<Vec<_>>(),
+                    );
+                    builder
+                        .append("", "", entity, TABLE_TYPE, &schema)
+                        .map_err(metadata_error)?;
+                }
+                builder.build().map_err(metadata_error)
+            }
+            MetadataCommand::TableTypes => RecordBatch::try_new(
+                table_types_schema(),
+                vec![Arc::new(StringArray::from(vec![TABLE_TYPE])) as ArrayRef],
+            )
+            .map_err(metadata_error),
+            MetadataCommand::SqlInfo(cmd) => {
+                let infos = sql_info().map_err(metadata_error)?;
+                cmd.into_builder(&infos).build().map_err(metadata_error)
+            }
+        }
+    }
+}
+
+fn metadata_error(e: impl std::fmt::Display) -> Status {
+    Status::internal(format!("Failed to list metadata {e}"))
+}
+
+fn table_types_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![Field::new(
+        "table_type",
+        DataType::Utf8,
+        false,
+    )]))
+}
+
+/// Describes the relay to FlightSQL clients.
+#[allow(clippy::result_large_err)]
+fn sql_info() -> Result<SqlInfoData, FlightError> {
+    let mut builder = SqlInfoDataBuilder::new();
+    builder.append(SqlInfo::FlightSqlServerName, "dataweb relay");
+    builder.append(SqlInfo::FlightSqlServerVersion, env!("CARGO_PKG_VERSION"));
+    builder.append(SqlInfo::FlightSqlServerReadOnly, true);
+    builder.append(SqlInfo::FlightSqlServerSql, true);
+    builder.append(SqlInfo::FlightSqlServerSubstrait, false);
+    builder.append(SqlInfo::FlightSqlServerTransaction, 0);
+    builder.append(SqlInfo::FlightSqlServerCancel, false);
+    builder.build()
+}
+
+/// A [RawQueryRequest] executing a FlightSQL statement.
+pub(crate) fn sql_query_request(sql: String) -> RawQueryRequest {
+    RawQueryRequest {
+        sql,
+        statements: vec![],
+        request_uuid: None,
+        requesting_user: None,
+        originating_relay: None,
+        originating_task_id: None,
+        return_arrow_schema: None,
+        engine_hint: None,
+        count_only: false,
+        interactive: false,
+        reexecution_of: None,
+        hints: HashMap::new(),
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn encode_schema(schema: &Schema) -> Result<Bytes, Status> {
+    let IpcMessage(schema) = SchemaAsIpc::new(schema, &IpcWriteOptions::default())
+        .try_into()
+        .map_err(|e: ArrowError| Status::internal(format!("Failed to encode schema {e}")))?;
+    Ok(schema)
+}
+
+impl FlightRelay {
+    /// Returns the [FlightInfo] of a FlightSQL command. Statements are submitted as a query,
+    /// while metadata is listed by a single endpoint on this relay whose ticket is the command.
+    pub(crate) async fn sql_flight_info(
+        &self,
+        db: &mut PgDb<'_>,
+        descriptor: FlightDescriptor,
+        command: SqlCommand,
+        client_cert: (String, String, String),
+        trace_context: &TraceContext,
+    ) -> Result<FlightInfo, Status> {
+        match command {
+            SqlCommand::Query(sql) => {
+                let query = sql_query_request(sql);
+                self.query_flight_info(db, descriptor, query, client_cert, trace_context)
+                    .await
+            }
+            SqlCommand::Metadata(metadata) => {
+                let ticket = Ticket::new(descriptor.cmd.clone());
+                Ok(FlightInfo::new()
+                    .try_with_schema(&metadata.schema())
+                    .map_err(|e| Status::internal(format!("Failed to encode schema {e}")))?
+                    .with_descriptor(descriptor)
+                    .with_endpoint(FlightEndpoint::new().with_ticket(ticket)))
+            }
+        }
+    }
+
+    /// Returns the Arrow schema of the result of a FlightSQL command.
+    pub(crate) async fn sql_schema(
+        &self,
+        db: &mut PgDb<'_>,
+        command: SqlCommand,
+        client_cert: (String, String, String),
+    ) -> Result<SchemaRef, Status> {
+        match command {
+            SqlCommand::Query(sql) => {
+                let schema = self
+                    .authorized_schema(db, &sql_query_request(sql), client_cert)
+                    .await?;
+                Ok(Arc::new(schema))
+            }
+            SqlCommand::Metadata(metadata) => Ok(metadata.schema()),
+        }
+    }
+
+    /// Lists the metadata a FlightSQL command, passed as a [Ticket], asks for.
+    pub(crate) async fn sql_do_get(
+        &self,
+        db: &mut PgDb<'_>,
+        command: SqlCommand,
+    ) -> Result<BoxStream<'static, Result<FlightData, Status>>, Status> {
+        let SqlCommand::Metadata(metadata) = command else {
+            return Err(Status::invalid_argument(
+                "The results of FlightSQL statements are retrieved with the tickets returned by \
+                get_flight_info",
+            ));
+        };
+        let batch = metadata.record_batch(db).await?;
+        let stream = FlightDataEncoderBuilder::new()
+            .build(futures::stream::once(async { Ok(batch) }))
+            .map_err(|e| Status::from_error(Box::new(e)));
+        Ok(Box::pin(stream))
+    }
+
+    /// Validates the query of an encoded [ActionCreatePreparedStatementRequest], and returns the
+    /// encoded [ActionCreatePreparedStatementResult] with the schema of its result. Statements
+    /// take no parameters.
+    pub(crate) async fn create_prepared_statement(
+        &self,
+        client_cert: (String, String, String),
+        body: &[u8],
+    ) -> Result<Bytes, Status> {
+        let request = Any::decode(body)
+            .ok()
+            .and_then(|any| any.unpack::<ActionCreatePreparedStatementRequest>().ok())
+            .flatten()
+            .ok_or_else(|| {
+                Status::invalid_argument(
+                    "Action body is not an encoded ActionCreatePreparedStatementRequest",
+                )
+            })?;
+        let mut db = PgDb::try_from_pool(&self.db_pool)
+            .await
+            .map_err(|e| Status::internal(format!("failed to connect to database! {e}")))?;
+        let schema = self
+            .sql_schema(
+                &mut db,
+                SqlCommand::Query(request.query.clone()),
+                client_cert,
+            )
+            .await?;
+        let result = ActionCreatePreparedStatementResult {
+            prepared_statement_handle: request.query.into(),
+            dataset_schema: encode_schema(&schema)?,
+            parameter_schema: Bytes::new(),
+        };
+        Ok(result.as_any().encode_to_vec().into())
+    }
+}
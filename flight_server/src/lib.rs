@@ -5,22 +5,62 @@ use flight::FlightRelay;
 
 use mesh::{conf::EnvConfigSettings, crud::run_migrations};
 
+use mesh::crud::PgDb;
 use mesh::error::MeshError;
+use mesh::execute::metrics::{metrics_addr, serve_metrics};
+use mesh::execute::progress::{flight_progress_interval, flight_stream_timeout};
 use mesh::execute::result_manager::ResultManager;
+use mesh::execute::shutdown::{drain, shutdown_timeout, shutdown_token};
 use mesh::model::data_stores::options::file_directory::FileDirectorySource;
 use mesh::model::data_stores::options::SourceFileType;
 use mesh::pki::parse_certificate;
 
 use rustls_pemfile::certs;
+use std::env;
 use std::fs::File;
 use std::io::BufReader;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
 
 use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+use tracing::{error, info, warn};
 
 use arrow_flight::flight_service_server::FlightServiceServer;
 
 mod flight;
+mod flight_sql;
+
+/// Periodically marks [FlightStream][mesh::model::query::FlightStream]s which have been Started
+/// without a heartbeat for longer than FLIGHT_STREAM_TIMEOUT_SECS as Failed. Such a stream was
+/// abandoned by a do_put which crashed or disconnected, and would otherwise leave the status of
+/// its request in progress forever.
+async fn run_stale_flight_sweeper(db_pool: Pool<diesel_async::AsyncPgConnection>) {
+    let timeout = flight_stream_timeout();
+    loop {
+        tokio::time::sleep(timeout / 2).await;
+        let mut db = match PgDb::try_from_pool(&db_pool).await {
+            Ok(db) => db,
+            Err(e) => {
+                error!("Failed to connect to database to sweep stale flights with error {e}");
+                continue;
+            }
+        };
+        let updated_before = chrono::Utc::now()
+            - chrono::Duration::from_std(timeout)
+                .expect("FLIGHT_STREAM_TIMEOUT_SECS is too large!");
+        match db.fail_stale_flight_streams(updated_before).await {
+            Ok(flights) => {
+                for flight in flights {
+                    warn!(
+                        "Marked flight {} from {} as Failed after it was abandoned for {timeout:?}",
+                        flight.flight_id, flight.remote_fingerprint
+                    );
+                }
+            }
+            Err(e) => error!("Failed to sweep stale flights with error {e}"),
+        }
+    }
+}
 
 pub async fn run() -> Result<(), MeshError> {
     let env_conf = EnvConfigSettings::init();
@@ -33,21 +73,33 @@ pub async fn run() -> Result<(), MeshError> {
         .await
         .expect("pool failed to start");
 
+    let sweeper = tokio::spawn(run_stale_flight_sweeper(db_pool.clone()));
+
     let result_source = FileDirectorySource {
         bucket: env_conf.result_bucket.clone(),
         region: env_conf.result_region.clone(),
         prefix: env_conf.result_prefix.clone(),
         file_type: SourceFileType::Parquet,
+        s3: None,
+        hdfs: None,
+        include: vec![],
+        exclude: vec![],
+        path_regex: None,
+        modified_after: None,
+        declared_schema: None,
+        partition_columns: vec![],
     };
 
     let result_manager = Arc::new(
         ResultManager::try_initialize(
             env_conf.result_object_store.clone(),
             result_source,
+            env_conf.result_client_config.clone(),
             env_conf.read_client_cert().unwrap(),
             env_conf.read_client_key().unwrap(),
             env_conf.read_client_cacert_pem().unwrap(),
         )
+        .and_then(|manager| manager.with_tenant_stores(env_conf.result_tenant_stores.clone()))
         .expect("Failed to initialize result manager!"),
     );
 
@@ -65,6 +117,11 @@ pub async fn run() -> Result<(), MeshError> {
     let client_cert = Arc::new(env_conf.read_client_cert()?);
     let client_key = Arc::new(env_conf.read_client_key()?);
 
+    let do_get_buffer: NonZeroUsize = env::var("DO_GET_BUFFER_BATCHES")
+        .unwrap_or("8".to_string())
+        .parse()
+        .expect("Unable to parse DO_GET_BUFFER_BATCHES as a usize greater than 0!");
+
     let addr = env_conf
         .flight_addr
         .parse()
@@ -78,10 +135,23 @@ pub async fn run() -> Result<(), MeshError> {
         ca_cert: ca_cert.clone(),
         local_fingerprint: Arc::new(fingerprint),
         client_cert_header: env_conf.client_cert_header.clone(),
+        do_get_buffer,
+        progress_interval: flight_progress_interval(),
     };
     let flight_svc = FlightServiceServer::new(flight_service);
 
-    if env_conf.direct_tls {
+    // On SIGTERM or SIGINT the server stops accepting connections, and in-flight calls such as
+    // a do_put of results are given SHUTDOWN_TIMEOUT_SECS to finish.
+    let shutdown = shutdown_token();
+    if let Some(metrics_addr) = metrics_addr("FLIGHT_METRICS_ADDR") {
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_metrics(metrics_addr, shutdown).await {
+                error!("{e}");
+            }
+        });
+    }
+    let server = if env_conf.direct_tls {
         let tls_config = ServerTlsConfig::new()
             .client_ca_root(Certificate::from_pem(ca_cert.as_ref()))
             .client_auth_optional(false)
@@ -94,9 +164,13 @@ pub async fn run() -> Result<(), MeshError> {
         Server::builder()
     }
     .add_service(flight_svc)
-    .serve(addr)
-    .await
-    .expect("Failed to create flight service!");
+    .serve_with_shutdown(addr, shutdown.clone().cancelled_owned());
+    if let Some(served) = drain(&shutdown, shutdown_timeout(), server).await {
+        served.expect("Failed to create flight service!");
+    }
 
+    // Stopping the sweeper drops the last handle to the database pool, which closes it
+    sweeper.abort();
+    info!("Flight server shut down");
     Ok(())
 }
@@ -0,0 +1,175 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::datatypes::Schema;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use dataweb_client::{Client, ClientConfig, QueryStatus};
+use futures::TryStreamExt;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use tokio::runtime::Runtime;
+
+use crate::{ipc_to_table, py_err, runtime};
+
+/// Submits queries to the rest_server of a relay and retrieves their results as pyarrow Tables.
+/// See [Client] for how the query is executed.
+#[pyclass(name = "Client", module = "dataweb")]
+pub struct PyClient {
+    client: Client,
+    runtime: Arc<Runtime>,
+}
+
+fn status_dict(py: Python<'_>, status: QueryStatus) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("request_id", status.request_id.to_string())?;
+    dict.set_item("message", status.message)?;
+    dict.set_item("complete", status.complete)?;
+    dict.set_item("failed", status.failed)?;
+    dict.set_item("in_progress", status.in_progress)?;
+    Ok(dict.into())
+}
+
+fn parse_request_id(request_id: &str) -> PyResult<uuid::Uuid> {
+    request_id
+        .parse()
+        .map_err(|e| py_err(format!("Invalid request id {request_id}: {e}")))
+}
+
+/// Encodes the batches of a result as an Arrow IPC stream. A result without batches has no
+/// columns.
+fn to_ipc(batches: Vec<RecordBatch>) -> PyResult<Vec<u8>> {
+    let schema = batches
+        .first()
+        .map(|b| b.schema())
+        .unwrap_or_else(|| Arc::new(Schema::empty()));
+    let mut writer = StreamWriter::try_new(vec![], &schema).map_err(py_err)?;
+    for batch in &batches {
+        writer.write(batch).map_err(py_err)?;
+    }
+    writer.into_inner().map_err(py_err)
+}
+
+impl PyClient {
+    fn fetch_ipc(&self, py: Python<'_>, request_id: uuid::Uuid) -> PyResult<Vec<u8>> {
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                let batches = self
+                    .client
+                    .fetch(request_id)
+                    .await
+                    .map_err(py_err)?
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .map_err(py_err)?;
+                to_ipc(batches)
+            })
+        })
+    }
+}
+
+#[pymethods]
+impl PyClient {
+    /// Connects to the rest_server of a relay, authenticating with the client certificate and
+    /// key at the given PEM files. The poll options default to those of [ClientConfig].
+    #[new]
+    #[pyo3(signature = (
+        rest_endpoint,
+        client_cert,
+        client_key,
+        ca_cert,
+        allow_partial=false,
+        poll_interval_ms=None,
+        max_poll_interval_ms=None,
+        poll_backoff=None,
+        timeout_secs=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        rest_endpoint: String,
+        client_cert: String,
+        client_key: String,
+        ca_cert: String,
+        allow_partial: bool,
+        poll_interval_ms: Option<u64>,
+        max_poll_interval_ms: Option<u64>,
+        poll_backoff: Option<f64>,
+        timeout_secs: Option<u64>,
+    ) -> PyResult<Self> {
+        let mut config =
+            ClientConfig::from_pem_files(rest_endpoint, client_cert, client_key, ca_cert)
+                .map_err(py_err)?;
+        config.allow_partial = allow_partial;
+        if let Some(interval) = poll_interval_ms {
+            config.poll.initial_interval = Duration::from_millis(interval);
+        }
+        if let Some(interval) = max_poll_interval_ms {
+            config.poll.max_interval = Duration::from_millis(interval);
+        }
+        if let Some(backoff) = poll_backoff {
+            config.poll.backoff = backoff;
+        }
+        if let Some(timeout) = timeout_secs {
+            config.poll.timeout = Duration::from_secs(timeout);
+        }
+        Ok(Self {
+            client: Client::new(config).map_err(py_err)?,
+            runtime: runtime()?,
+        })
+    }
+
+    /// Reads the configuration from the same environment variables as [ClientConfig::from_env].
+    #[staticmethod]
+    fn from_env() -> PyResult<Self> {
+        let config = ClientConfig::from_env().map_err(py_err)?;
+        Ok(Self {
+            client: Client::new(config).map_err(py_err)?,
+            runtime: runtime()?,
+        })
+    }
+
+    /// Executes sql and returns its results as a pyarrow Table once every task completed.
+    fn query(&self, py: Python<'_>, sql: &str) -> PyResult<PyObject> {
+        let request_id = py.allow_threads(|| {
+            self.runtime.block_on(async {
+                let request_id = self.client.submit(sql).await.map_err(py_err)?;
+                self.client.wait(request_id).await.map_err(py_err)?;
+                Ok::<_, PyErr>(request_id)
+            })
+        })?;
+        let ipc = self.fetch_ipc(py, request_id)?;
+        ipc_to_table(py, ipc)
+    }
+
+    /// Submits sql without waiting for it to complete, returning the id of the query.
+    fn submit(&self, py: Python<'_>, sql: &str) -> PyResult<String> {
+        let request_id = py
+            .allow_threads(|| self.runtime.block_on(self.client.submit(sql)))
+            .map_err(py_err)?;
+        Ok(request_id.to_string())
+    }
+
+    /// Returns the status of a query as a dict.
+    fn status(&self, py: Python<'_>, request_id: &str) -> PyResult<PyObject> {
+        let request_id = parse_request_id(request_id)?;
+        let status = py
+            .allow_threads(|| self.runtime.block_on(self.client.status(request_id)))
+            .map_err(py_err)?;
+        status_dict(py, status)
+    }
+
+    /// Waits for a query to complete, returning its last status as a dict.
+    fn wait(&self, py: Python<'_>, request_id: &str) -> PyResult<PyObject> {
+        let request_id = parse_request_id(request_id)?;
+        let status = py
+            .allow_threads(|| self.runtime.block_on(self.client.wait(request_id)))
+            .map_err(py_err)?;
+        status_dict(py, status)
+    }
+
+    /// Retrieves the results of a query which completed as a pyarrow Table.
+    fn fetch(&self, py: Python<'_>, request_id: &str) -> PyResult<PyObject> {
+        let ipc = self.fetch_ipc(py, parse_request_id(request_id)?)?;
+        ipc_to_table(py, ipc)
+    }
+}
@@ -0,0 +1,49 @@
+//! Python bindings with which analysts query a DataWeb from python. Results are returned as
+//! pyarrow Tables, which are passed from Rust in the Arrow IPC stream format.
+// The impls generated by the pyo3 0.20 macros are not at the level of the items they implement.
+#![allow(non_local_definitions)]
+
+use std::sync::Arc;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use tokio::runtime::Runtime;
+
+mod client;
+mod session;
+
+create_exception!(
+    dataweb,
+    DataWebError,
+    PyException,
+    "Raised when a query or the configuration of a client is rejected."
+);
+
+pub(crate) fn py_err(e: impl std::fmt::Display) -> PyErr {
+    DataWebError::new_err(e.to_string())
+}
+
+/// Runtime on which the async calls of a client or session are executed, while python waits
+/// with the GIL released.
+pub(crate) fn runtime() -> PyResult<Arc<Runtime>> {
+    Ok(Arc::new(Runtime::new().map_err(py_err)?))
+}
+
+/// Reads an Arrow IPC stream as a pyarrow Table.
+pub(crate) fn ipc_to_table(py: Python<'_>, ipc: Vec<u8>) -> PyResult<PyObject> {
+    let table = py
+        .import("pyarrow.ipc")?
+        .call_method1("open_stream", (PyBytes::new(py, &ipc),))?
+        .call_method0("read_all")?;
+    Ok(table.into())
+}
+
+#[pymodule]
+fn dataweb(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<client::PyClient>()?;
+    m.add_class::<session::PyWebSession>()?;
+    m.add("DataWebError", py.get_type::<DataWebError>())?;
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use data_web_engine::fetch::FetchOptions;
+use data_web_engine::register::register_web_sources;
+use data_web_engine::rest::ScanMode;
+use datafusion::arrow::datatypes::Schema;
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::execution::context::SessionContext;
+use pyo3::prelude::*;
+use tokio::runtime::Runtime;
+
+use crate::{ipc_to_table, py_err, runtime};
+
+/// A DataFusion session in which every Entity of a relay is registered as a table, so that SQL
+/// joining and aggregating Entities is planned locally and each Entity is scanned from the web.
+#[pyclass(name = "WebSession", module = "dataweb")]
+pub struct PyWebSession {
+    ctx: SessionContext,
+    runtime: Arc<Runtime>,
+}
+
+#[pymethods]
+impl PyWebSession {
+    /// Registers the Entities of the relay with the given flight endpoint, authenticating with
+    /// the client certificate and key at the given PEM files. Scans are configured by the same
+    /// environment variables as the webengine.
+    #[new]
+    fn new(
+        py: Python<'_>,
+        flight_endpoint: String,
+        client_cert: String,
+        client_key: String,
+        ca_cert: String,
+    ) -> PyResult<Self> {
+        let read = |path: &str| {
+            std::fs::read(path)
+                .map(Arc::new)
+                .map_err(|e| py_err(format!("Unable to read {path}! {e}")))
+        };
+        let (client_cert, client_key, ca_cert) =
+            (read(&client_cert)?, read(&client_key)?, read(&ca_cert)?);
+        let runtime = runtime()?;
+        let ctx = SessionContext::new();
+        py.allow_threads(|| {
+            runtime.block_on(register_web_sources(
+                &ctx,
+                Arc::new(flight_endpoint),
+                client_cert,
+                client_key,
+                ca_cert,
+                Arc::new(ScanMode::from_env()?),
+                Arc::new(FetchOptions::from_env()?),
+            ))
+        })
+        .map_err(py_err)?;
+        Ok(Self { ctx, runtime })
+    }
+
+    /// Returns the names of the registered Entities.
+    fn entities(&self) -> PyResult<Vec<String>> {
+        let config = self.ctx.copied_config();
+        let options = &config.options().catalog;
+        let mut entities = self
+            .ctx
+            .catalog(&options.default_catalog)
+            .and_then(|catalog| catalog.schema(&options.default_schema))
+            .map(|schema| schema.table_names())
+            .unwrap_or_default();
+        entities.sort();
+        Ok(entities)
+    }
+
+    /// Executes sql in the session and returns its result as a pyarrow Table.
+    fn sql(&self, py: Python<'_>, sql: &str) -> PyResult<PyObject> {
+        let ipc = py.allow_threads(|| {
+            self.runtime.block_on(async {
+                let df = self.ctx.sql(sql).await.map_err(py_err)?;
+                let schema: Schema = df.schema().into();
+                let batches = df.collect().await.map_err(py_err)?;
+                let mut writer = StreamWriter::try_new(vec![], &schema).map_err(py_err)?;
+                for batch in &batches {
+                    writer.write(batch).map_err(py_err)?;
+                }
+                writer.into_inner().map_err(py_err)
+            })
+        })?;
+        ipc_to_table(py, ipc)
+    }
+}
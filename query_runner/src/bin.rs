@@ -1,7 +1,10 @@
+use mesh::execute::telemetry::{init_tracing, shutdown_tracing};
 use query_runner_lib::{run, Result};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-    run(None).await
+    init_tracing("query_runner");
+    let result = run(None).await;
+    shutdown_tracing().await;
+    result
 }
@@ -1,56 +1,327 @@
 use std::env;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use chrono::Utc;
+use datafusion::arrow::array::AsArray;
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Int64Type};
+use datafusion::physical_plan::common::collect;
 use datafusion::physical_plan::SendableRecordBatchStream;
 use diesel_async::pooled_connection::bb8::Pool;
 use diesel_async::pooled_connection::AsyncDieselConnectionManager;
 use diesel_async::AsyncPgConnection;
 use mesh::conf::EnvConfigSettings;
-use mesh::crud::PgDb;
+use mesh::crud::{LeaderLock, PgDb};
 use mesh::error::MeshError;
-use mesh::execute::data_stores::try_connect;
-use mesh::execute::result_manager::ResultManager;
+use mesh::execute::data_stores::{try_connect, QueryRunner};
+use mesh::execute::freshness::source_freshness;
+use mesh::execute::lineage::{lineage_event, LineageOptions};
+use mesh::execute::metrics::{metrics, metrics_addr, serve_metrics};
+use mesh::execute::outbox::publish_outbox;
+use mesh::execute::relay_status::ping_flight;
+use mesh::execute::result_manager::{result_cache_ttl, ResultCacheKey, ResultManager};
+use mesh::execute::scratch::delete_expired_scratch_datasets;
+use mesh::execute::shutdown::{drain, shutdown_timeout, shutdown_token};
+use mesh::execute::{entity_validation_queries, resolve_task_engine};
 use mesh::messaging::{
-    initialize_consumer, GenericMessage, MessageBrokerOptions, MessageConsumer, QueryTaskMessage,
+    initialize_consumer, initialize_producer, GenericMessage, MessageBrokerOptions,
+    MessageConsumer, MessageProducer, QueryTaskMessage,
 };
 use mesh::model::data_stores::options::file_directory::FileDirectorySource;
 use mesh::model::data_stores::options::SourceFileType;
 use mesh::model::data_stores::{DataConnection, DataSource};
-use mesh::model::query::{Query, QueryOriginationInfo, QueryTaskRemoteStatus, QueryTaskStatus};
-use reqwest::Client;
-use tracing::{error, info};
+use mesh::model::entity::NewEntityValidation;
+use mesh::model::query::{
+    Query, QueryOriginationInfo, QueryRequest, QueryTask, QueryTaskRemoteStatus, QueryTaskStatus,
+    RawQueryRequest, ResultMetadata, SubmitQueryResponse, TraceContext,
+};
+use mesh::model::relay::{NewRelayStatus, Relay};
+use mesh::model::usage::NewRelayUsage;
+use mesh::pki::{load_certificate_from_reader, parse_certificate};
+use reqwest::{Client, StatusCode};
+use tokio::task::{JoinError, JoinSet};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, warn};
 use uuid::Uuid;
 
 #[derive(Debug)]
 pub enum ExecutionError {
     InvalidMessage((u64, String)),
+    /// A message which was parsed, but could not be processed.
+    RejectedMessage((u64, GenericMessage, String)),
     ConnectionError(MeshError),
     QueryFailed((u64, Uuid, MeshError)),
+    /// A task whose execution was cancelled because it is no longer InProgress.
+    Cancelled((u64, Uuid)),
 }
 
 pub type Result<T, E = ExecutionError> = std::result::Result<T, E>;
 
+/// Keys of the advisory locks which elect the one query_runner replica running each of the
+/// background tasks whose work and alerts would otherwise be duplicated by every replica.
+const ENTITY_VALIDATOR_LOCK: i64 = 0x6d65_7368_0001;
+const RELAY_PROBER_LOCK: i64 = 0x6d65_7368_0002;
+const RESULT_SWEEPER_LOCK: i64 = 0x6d65_7368_0003;
+
+/// Returns true if this replica holds the [LeaderLock] of a background task, logging when it
+/// cannot be determined.
+async fn is_leader(lock: &mut LeaderLock, task: &str) -> bool {
+    match lock.acquire().await {
+        Ok(true) => true,
+        Ok(false) => {
+            debug!("Another replica runs the {task}, skipping");
+            false
+        }
+        Err(e) => {
+            error!("Failed to acquire the {task} lock with error {e}");
+            false
+        }
+    }
+}
+
 async fn execute_query(
     con: DataConnection,
     source: DataSource,
     query: Query,
-) -> std::result::Result<SendableRecordBatchStream, MeshError> {
+    cancel: CancellationToken,
+) -> std::result::Result<(Box<dyn QueryRunner + Send>, SendableRecordBatchStream), MeshError> {
     let mut runner = try_connect(con, source).await?;
-    runner.execute_stream(query).await
+    let rb_stream = runner.execute_stream(query, cancel).await?;
+    Ok((runner, rb_stream))
+}
+
+/// How long an InProgress task may go without a heartbeat before it is considered abandoned,
+/// read from QUERY_TASK_LEASE_SECS (default 300). Heartbeats are recorded 4 times per lease.
+fn query_task_lease() -> Duration {
+    let lease_secs = env::var("QUERY_TASK_LEASE_SECS")
+        .unwrap_or("300".to_string())
+        .parse()
+        .expect("Unable to parse QUERY_TASK_LEASE_SECS as u64!");
+    Duration::from_secs(lease_secs)
+}
+
+/// Drives execution to completion while periodically touching the [QueryTask] identified by
+/// task_id, so that a long running task is not reclaimed from a query_runner which is alive.
+/// If the task is found to no longer be InProgress, e.g. because it was reclaimed or failed
+/// elsewhere, cancel is cancelled so that execution stops.
+///
+/// [QueryTask]: mesh::model::query::QueryTask
+async fn with_heartbeat<T>(
+    pool: &Pool<AsyncPgConnection>,
+    task_id: Uuid,
+    interval: Duration,
+    cancel: &CancellationToken,
+    execution: impl std::future::Future<Output = T>,
+) -> T {
+    let mut heartbeat = tokio::time::interval(interval);
+    // The first tick completes immediately, and the task was just marked InProgress
+    heartbeat.tick().await;
+    tokio::pin!(execution);
+    loop {
+        tokio::select! {
+            result = &mut execution => return result,
+            _ = heartbeat.tick() => {
+                let touched = match PgDb::try_from_pool(pool).await {
+                    Ok(mut db) => db.touch_query_task(task_id).await,
+                    Err(e) => Err(e),
+                };
+                match touched {
+                    Ok(true) => (),
+                    Ok(false) => {
+                        if !cancel.is_cancelled() {
+                            info!("Task {task_id} is no longer in progress, cancelling its execution");
+                            cancel.cancel();
+                        }
+                    }
+                    Err(e) => warn!("Failed to record heartbeat of task {task_id} with error {e}"),
+                }
+            }
+        }
+    }
+}
+
+/// Initializes the [ResultManager] for the result store configured in env_conf.
+fn init_result_manager(env_conf: &EnvConfigSettings) -> ResultManager {
+    let result_source = FileDirectorySource {
+        bucket: env_conf.result_bucket.clone(),
+        region: env_conf.result_region.clone(),
+        prefix: env_conf.result_prefix.clone(),
+        file_type: SourceFileType::Parquet,
+        s3: None,
+        hdfs: None,
+        include: vec![],
+        exclude: vec![],
+        path_regex: None,
+        modified_after: None,
+        declared_schema: None,
+        partition_columns: vec![],
+    };
+    ResultManager::try_initialize(
+        env_conf.result_object_store.clone(),
+        result_source,
+        env_conf.result_client_config.clone(),
+        env_conf
+            .read_client_cert()
+            .expect("Could not read client cert"),
+        env_conf
+            .read_client_key()
+            .expect("Could not read client key"),
+        env_conf
+            .read_client_cacert_pem()
+            .expect("Could not read cacert"),
+    )
+    .and_then(|manager| manager.with_tenant_stores(env_conf.result_tenant_stores.clone()))
+    .expect("Failed to initialize result manager!")
+}
+
+/// Builds an HTTP client which authenticates to peered relays with the client certificate of
+/// the local relay.
+fn init_reqw_client(env_conf: &EnvConfigSettings) -> Client {
+    let iden_pem = env_conf
+        .read_client_identity_pem()
+        .expect("Could not read client iden pem");
+    let pkcs8 =
+        reqwest::Identity::from_pem(&iden_pem).expect("could not parse client cert and key");
+
+    let mut client = reqwest::Client::builder().use_rustls_tls().identity(pkcs8);
+
+    let cert = reqwest::Certificate::from_pem(
+        &env_conf
+            .read_client_cacert_pem()
+            .expect("Could not open cacert"),
+    )
+    .expect("Could not parse cacert");
+    client = client.add_root_certificate(cert);
+    client.build().expect("client build err")
+}
+
+/// Exponential backoff between attempts to submit a remote task to a peered relay, so that a
+/// relay which is briefly unavailable does not fail the task.
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+    max_attempts: i32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    /// How long a single attempt may take before it is abandoned and retried.
+    timeout: Duration,
+}
+
+impl RetryPolicy {
+    fn from_env() -> Self {
+        let max_attempts = env::var("REMOTE_SUBMIT_MAX_ATTEMPTS")
+            .unwrap_or("5".to_string())
+            .parse()
+            .expect("Unable to parse REMOTE_SUBMIT_MAX_ATTEMPTS as i32!");
+        let initial_backoff_ms = env::var("REMOTE_SUBMIT_BACKOFF_MS")
+            .unwrap_or("500".to_string())
+            .parse()
+            .expect("Unable to parse REMOTE_SUBMIT_BACKOFF_MS as u64!");
+        let max_backoff_ms = env::var("REMOTE_SUBMIT_MAX_BACKOFF_MS")
+            .unwrap_or("30000".to_string())
+            .parse()
+            .expect("Unable to parse REMOTE_SUBMIT_MAX_BACKOFF_MS as u64!");
+        let timeout_secs = env::var("REMOTE_SUBMIT_TIMEOUT_SECS")
+            .unwrap_or("30".to_string())
+            .parse()
+            .expect("Unable to parse REMOTE_SUBMIT_TIMEOUT_SECS as u64!");
+        Self {
+            max_attempts,
+            initial_backoff: Duration::from_millis(initial_backoff_ms),
+            max_backoff: Duration::from_millis(max_backoff_ms),
+            timeout: Duration::from_secs(timeout_secs),
+        }
+    }
+
+    /// Returns how long to wait after the given failed attempt, counting from 1.
+    fn backoff(&self, attempt: i32) -> Duration {
+        let exponent = attempt.saturating_sub(1).clamp(0, 31) as u32;
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.max_backoff)
+    }
+}
+
+/// Why submitting a remote task to a peered relay failed.
+enum SubmitError {
+    /// The relay may accept the task when it is submitted again, e.g. because it was
+    /// unreachable, timed out or gave a response which does not confirm the task was accepted.
+    /// Relays deduplicate requests by their request_uuid, so resubmitting is safe.
+    Retryable(String),
+    /// The relay rejected the task, e.g. as invalid or not permitted, so it is not submitted
+    /// again.
+    Rejected(String),
+}
+
+/// Whether a relay which responded with status rejected a task for good. Client errors are,
+/// except for those which ask the client to try again later.
+fn is_definitive_rejection(status: StatusCode) -> bool {
+    status.is_client_error()
+        && !matches!(
+            status,
+            StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS
+        )
+}
+
+/// Posts a remote task to the /query endpoint of a peered relay, returning its response once
+/// the relay confirmed it accepted the task. The trace_context is passed along, so the peered
+/// relay continues the trace of the request.
+async fn submit_remote_task(
+    client: &Client,
+    relay: &Relay,
+    task_request: &RawQueryRequest,
+    trace_context: &TraceContext,
+    timeout: Duration,
+) -> std::result::Result<SubmitQueryResponse, SubmitError> {
+    let mut builder = client
+        .post(format!("{}/query", relay.rest_endpoint))
+        .timeout(timeout)
+        .json(task_request);
+    for (name, value) in trace_context.headers() {
+        builder = builder.header(name, value);
+    }
+    let response = builder
+        .send()
+        .await
+        .map_err(|e| SubmitError::Retryable(e.to_string()))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| SubmitError::Retryable(format!("Failed to read response: {e}")))?;
+    if status.is_success() {
+        return serde_json::from_str(&body).map_err(|e| {
+            SubmitError::Retryable(format!("Unexpected response {body} with error {e}"))
+        });
+    }
+    let reason = format!("{status}: {body}");
+    match is_definitive_rejection(status) {
+        true => Err(SubmitError::Rejected(reason)),
+        false => Err(SubmitError::Retryable(reason)),
+    }
 }
+
 struct MessageProcessor<'a> {
     db: PgDb<'a>,
+    /// Pool used to record heartbeats, since db is busy while a task executes.
+    heartbeat_pool: Pool<AsyncPgConnection>,
     consumer: Box<dyn MessageConsumer>,
     result_manager: Arc<ResultManager>,
     reqw_client: Client,
+    /// How many times a message is processed before it is dead lettered
+    max_message_attempts: i32,
+    heartbeat_interval: Duration,
+    /// How long a task may execute before it is failed, or None to never time out.
+    task_timeout: Option<Duration>,
+    remote_retry: RetryPolicy,
 }
 
 impl<'a> MessageProcessor<'a> {
     async fn init(
         env_conf: &EnvConfigSettings,
         pool: &'a Pool<AsyncPgConnection>,
+        heartbeat_pool: Pool<AsyncPgConnection>,
         in_memory_msg_opts: &Option<MessageBrokerOptions>,
     ) -> MessageProcessor<'a> {
         let message_options = match in_memory_msg_opts {
@@ -61,56 +332,34 @@ impl<'a> MessageProcessor<'a> {
             .await
             .expect("failed to create message consumer");
 
-        let result_source = FileDirectorySource {
-            bucket: env_conf.result_bucket.clone(),
-            region: env_conf.result_region.clone(),
-            prefix: env_conf.result_prefix.clone(),
-            file_type: SourceFileType::Parquet,
-        };
-
-        let result_manager = Arc::new(
-            ResultManager::try_initialize(
-                env_conf.result_object_store.clone(),
-                result_source,
-                env_conf
-                    .read_client_cert()
-                    .expect("Could not read client cert"),
-                env_conf
-                    .read_client_key()
-                    .expect("Could not read client key"),
-                env_conf
-                    .read_client_cacert_pem()
-                    .expect("Could not read cacert"),
-            )
-            .expect("Failed to initialize result manager!"),
-        );
+        let result_manager =
+            Arc::new(init_result_manager(env_conf).with_result_cache(result_cache_ttl()));
 
         let db = PgDb::try_from_pool(pool)
             .await
             .expect("unable to get connection from pool!");
 
-        let iden_pem = env_conf
-            .read_client_identity_pem()
-            .expect("Could not read client iden pem");
-        let pkcs8 =
-            reqwest::Identity::from_pem(&iden_pem).expect("could not parse client cert and key");
-
-        let mut client = reqwest::Client::builder().use_rustls_tls().identity(pkcs8);
+        let reqw_client = init_reqw_client(env_conf);
 
-        let cert = reqwest::Certificate::from_pem(
-            &env_conf
-                .read_client_cacert_pem()
-                .expect("Could not open cacert"),
-        )
-        .expect("Could not parse cacert");
-        client = client.add_root_certificate(cert);
-        let reqw_client = client.build().expect("client build err");
+        let max_message_attempts = env::var("MAX_MESSAGE_ATTEMPTS")
+            .unwrap_or("3".to_string())
+            .parse()
+            .expect("Unable to parse MAX_MESSAGE_ATTEMPTS as i32!");
+        let task_timeout_secs: u64 = env::var("QUERY_TASK_TIMEOUT_SECS")
+            .unwrap_or("0".to_string())
+            .parse()
+            .expect("Unable to parse QUERY_TASK_TIMEOUT_SECS as u64!");
 
         Self {
             db,
+            heartbeat_pool,
             consumer,
             result_manager,
             reqw_client,
+            max_message_attempts,
+            heartbeat_interval: query_task_lease() / 4,
+            task_timeout: (task_timeout_secs > 0).then(|| Duration::from_secs(task_timeout_secs)),
+            remote_retry: RetryPolicy::from_env(),
         }
     }
 
@@ -124,56 +373,110 @@ impl<'a> MessageProcessor<'a> {
             .get_query_task(task_message.id)
             .await
             .map_err(|e| ExecutionError::InvalidMessage((msg_id, e.to_string())))?;
-        // TODO: implement timeout mechanism in case a query runner dies while holding a task as "in progress"
+        let (con, source) = resolve_task_engine(&mut self.db, con, source, task.engine.as_deref())
+            .await
+            .map_err(|e| ExecutionError::QueryFailed((msg_id, task.id, e)))?;
         if matches!(task.status, QueryTaskStatus::Queued) {
-            self.db
-                .update_task_status(task.id, QueryTaskStatus::InProgress)
+            if let Some(not_before) = con.execution_windows.next_open(Utc::now()) {
+                info!(
+                    "Connection {} is outside of its execution windows, deferring task {} until {not_before}",
+                    con.name, task.id
+                );
+                self.db
+                    .defer_task(task.id, not_before)
+                    .await
+                    .map_err(ExecutionError::ConnectionError)?;
+                return Ok(());
+            }
+        }
+        // Tasks left InProgress by a crashed query_runner are reclaimed by run_task_reclaimer.
+        // Claiming the task only succeeds once, so duplicate messages are acknowledged unexecuted.
+        if matches!(task.status, QueryTaskStatus::Queued) {
+            let claimed = self
+                .db
+                .claim_query_task(task.id)
                 .await
                 .map_err(ExecutionError::ConnectionError)?;
-            let query = task.task;
-            let rb_stream = execute_query(con, source, query)
-                .await
-                .map_err(|e| ExecutionError::QueryFailed((msg_id, task.id, e)))?;
-            let schema = rb_stream.schema();
-            match request.origin_info {
-                QueryOriginationInfo {
-                    origin_relay: None,
-                    origin_task_id: None,
-                    ..
-                } => {
-                    self.result_manager
-                        .write_task_result(&task.id, rb_stream, schema)
-                        .await
-                        .map_err(ExecutionError::ConnectionError)?;
+            if !claimed {
+                info!(
+                    "Task {} was already claimed, ignoring duplicate message",
+                    task.id
+                );
+                return Ok(());
+            }
+            let task_id = task.id;
+            // Freshness is informational, so failing to determine it does not fail the task
+            let freshness = match source_freshness(con.clone(), source.clone()).await {
+                Ok(freshness) => freshness,
+                Err(e) => {
+                    warn!("Failed to determine freshness for task {task_id} with error {e}");
+                    None
                 }
-                QueryOriginationInfo {
-                    origin_relay: Some(originating_relay),
-                    origin_task_id: Some(originating_task_id),
-                    ..
-                } => {
-                    self.result_manager
-                        .send_result_flight(
-                            &task_message.id,
-                            &originating_task_id,
-                            rb_stream,
-                            schema,
-                            originating_relay,
-                        )
-                        .await
-                        .map_err(ExecutionError::ConnectionError)?;
+            };
+            if let Some(freshness) = freshness {
+                if let Err(e) = self.db.set_task_freshness(task_id, freshness).await {
+                    error!("Failed to record freshness for task {task_id} with error {e}");
                 }
-                _ => {
-                    return Err(ExecutionError::InvalidMessage((
-                        msg_id,
-                        "Only one of origin_relay \
-                or origin_task_id was set. Either both or neither should be set!"
-                            .to_string(),
-                    )))
+            }
+            let metadata = ResultMetadata { freshness };
+            let heartbeat_pool = self.heartbeat_pool.clone();
+            let heartbeat_interval = self.heartbeat_interval;
+            let task_timeout = self.task_timeout;
+            let cancel = CancellationToken::new();
+            let source_name = source.name.clone();
+            let started = Instant::now();
+            let execution = with_heartbeat(
+                &heartbeat_pool,
+                task_id,
+                heartbeat_interval,
+                &cancel,
+                self.execute_local_query_task(
+                    msg_id,
+                    con,
+                    source,
+                    task,
+                    request,
+                    metadata,
+                    cancel.clone(),
+                ),
+            );
+            let executed = match task_timeout {
+                Some(timeout) => tokio::time::timeout(timeout, execution)
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(ExecutionError::QueryFailed((
+                            msg_id,
+                            task_id,
+                            MeshError::Internal(format!(
+                                "Task timed out after {}s",
+                                timeout.as_secs()
+                            )),
+                        )))
+                    }),
+                None => execution.await,
+            };
+            let status = match (&executed, cancel.is_cancelled()) {
+                (_, true) => "cancelled",
+                (Ok(None), false) => "cached",
+                (Ok(Some(_)), false) => "complete",
+                (Err(_), false) => "failed",
+            };
+            metrics().task_executed(&source_name, status, started.elapsed());
+            // The status of a cancelled task was already changed by whoever cancelled it
+            if cancel.is_cancelled() {
+                return Err(ExecutionError::Cancelled((msg_id, task_id)));
+            }
+            let runner = executed?;
+
+            if let Some(metrics) = runner.and_then(|runner| runner.scan_metrics()) {
+                debug!("Scan metrics for task {task_id}: {metrics:?}");
+                if let Err(e) = self.db.set_task_scan_metrics(task_id, &metrics).await {
+                    error!("Failed to record scan metrics for task {task_id} with error {e}");
                 }
             }
 
             self.db
-                .update_task_status(task.id, QueryTaskStatus::Complete)
+                .update_task_status(task_id, QueryTaskStatus::Complete)
                 .await
                 .map_err(ExecutionError::ConnectionError)?;
         }
@@ -181,6 +484,127 @@ impl<'a> MessageProcessor<'a> {
         Ok(())
     }
 
+    /// Executes a [QueryTask] and writes or sends its result, returning the [QueryRunner] which
+    /// executed it, or None if the result was served from the cache of the [ResultManager].
+    /// Replays and explicit re-executions always execute the task, and only the latter refresh
+    /// the cache.
+    ///
+    /// [QueryTask]: mesh::model::query::QueryTask
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_local_query_task(
+        &mut self,
+        msg_id: u64,
+        con: DataConnection,
+        source: DataSource,
+        task: QueryTask,
+        request: QueryRequest,
+        metadata: ResultMetadata,
+        cancel: CancellationToken,
+    ) -> Result<Option<Box<dyn QueryRunner + Send>>> {
+        let task_id = task.id;
+        let cache_key = request.replay_of.is_none().then(|| {
+            ResultCacheKey::new(task.data_source_id, &task.task, task.permission.as_ref())
+        });
+        // The cache only saves work, so failing to read it executes the task as usual
+        let cached = match &cache_key {
+            Some(key) if request.reexecution_of.is_none() => self
+                .result_manager
+                .get_cached_result(key)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to read cached result for task {task_id} with error {e}");
+                    None
+                }),
+            _ => None,
+        };
+        let (runner, rb_stream) = match cached {
+            Some(rb_stream) => {
+                info!("Serving result of task {task_id} from the result cache");
+                (None, rb_stream)
+            }
+            None => {
+                let (runner, rb_stream) = execute_query(con, source, task.task, cancel)
+                    .await
+                    .map_err(|e| ExecutionError::QueryFailed((msg_id, task_id, e)))?;
+                let rb_stream = match &cache_key {
+                    Some(key) => self
+                        .result_manager
+                        .cache_result(key, rb_stream)
+                        .await
+                        .map_err(|e| ExecutionError::QueryFailed((msg_id, task_id, e)))?,
+                    None => rb_stream,
+                };
+                (Some(runner), rb_stream)
+            }
+        };
+        let schema = rb_stream.schema();
+        match request.origin_info {
+            QueryOriginationInfo {
+                origin_relay: None,
+                origin_task_id: None,
+                ..
+            } if request.replay_of.is_some() => {
+                self.result_manager
+                    .write_replay_result(
+                        &request.id,
+                        &task_id,
+                        request.result_format,
+                        rb_stream,
+                        schema,
+                    )
+                    .await
+                    .map_err(ExecutionError::ConnectionError)?;
+            }
+            QueryOriginationInfo {
+                origin_relay: None,
+                origin_task_id: None,
+                ..
+            } => {
+                self.result_manager
+                    .write_task_result(&task_id, None, request.result_format, rb_stream, schema)
+                    .await
+                    .map_err(ExecutionError::ConnectionError)?;
+            }
+            QueryOriginationInfo {
+                origin_relay: Some(originating_relay),
+                origin_task_id: Some(originating_task_id),
+                ..
+            } => {
+                let relay_id = originating_relay.id;
+                let counter = self
+                    .result_manager
+                    .send_result_flight(
+                        &task_id,
+                        &originating_task_id,
+                        rb_stream,
+                        schema,
+                        originating_relay,
+                        &metadata,
+                    )
+                    .await
+                    .map_err(ExecutionError::ConnectionError)?;
+                let usage = NewRelayUsage {
+                    relay_id,
+                    query_task_id: task_id,
+                    rows: counter.rows(),
+                    bytes: counter.bytes(),
+                };
+                if let Err(e) = self.db.record_relay_usage(&usage).await {
+                    error!("Failed to record usage for task {task_id} with error {e}");
+                }
+            }
+            _ => {
+                return Err(ExecutionError::InvalidMessage((
+                    msg_id,
+                    "Only one of origin_relay \
+                or origin_task_id was set. Either both or neither should be set!"
+                        .to_string(),
+                )))
+            }
+        }
+        Ok(runner)
+    }
+
     async fn process_remote_query_task(
         &mut self,
         msg_id: u64,
@@ -191,49 +615,145 @@ impl<'a> MessageProcessor<'a> {
             .get_remote_query_task(task_message.id)
             .await
             .map_err(|e| ExecutionError::InvalidMessage((msg_id, e.to_string())))?;
+        // Messages are dispatched at-least-once, so the task may already have been submitted
+        if !matches!(remote_task.status, QueryTaskRemoteStatus::Queued) {
+            info!(
+                "Remote task {} was already submitted, ignoring duplicate message",
+                remote_task.id
+            );
+            return Ok(());
+        }
+        if remote_task.not_before.is_some() {
+            info!(
+                "Remote task {} is waiting to be retried, ignoring duplicate message",
+                remote_task.id
+            );
+            return Ok(());
+        }
 
         info!(
             "Sending {:?} to {:?}",
             remote_task.task.originating_task_id, relay
         );
-        let task_request = remote_task.task;
-
-        let r = self
-            .reqw_client
-            .post(format!("{}/query", relay.rest_endpoint))
-            .json(&task_request)
-            .send()
+        let (_span, trace_context) = self
+            .db
+            .get_request_trace_context(remote_task.query_request_id)
             .await
-            .map_err(|e| ExecutionError::ConnectionError(MeshError::RemoteError(e.to_string())))?;
-
-        match r.text().await {
-            Ok(s) => info!("Response from remote: {s}"),
-            Err(e) => {
-                error!("Failed to parse response as text with e {e}");
+            .map_err(ExecutionError::ConnectionError)?
+            .unwrap_or_default()
+            .start_span(info_span!("remote_query_task", task_id = %remote_task.id));
+        let task_request = remote_task.task;
+        let policy = self.remote_retry;
+        // Attempts are persisted, so a redelivered message does not start counting over
+        let attempt = remote_task.attempts + 1;
+        let (error, status) = match submit_remote_task(
+            &self.reqw_client,
+            &relay,
+            &task_request,
+            &trace_context,
+            policy.timeout,
+        )
+        .await
+        {
+            Ok(response) => {
+                info!(
+                    "Relay {} accepted remote task {} as request {}",
+                    relay.name, remote_task.id, response.id
+                );
+                for warning in response.warnings {
+                    warn!(
+                        "Relay {} warned about remote task {}: {warning}",
+                        relay.name, remote_task.id
+                    );
+                }
+                (None, QueryTaskRemoteStatus::Submitted)
             }
-        }
-
+            Err(SubmitError::Rejected(e)) => {
+                error!(
+                    "Relay {} rejected remote task {}, marking it Failed! Reason: {e}",
+                    relay.name, remote_task.id
+                );
+                (Some(e), QueryTaskRemoteStatus::Failed)
+            }
+            Err(SubmitError::Retryable(e)) if attempt >= policy.max_attempts => {
+                error!(
+                    "Submitting remote task {} to relay {} failed {attempt} times, marking it Failed! Last error: {e}",
+                    remote_task.id, relay.name
+                );
+                (Some(e), QueryTaskRemoteStatus::Failed)
+            }
+            Err(SubmitError::Retryable(e)) => {
+                warn!(
+                    "Attempt {attempt} of {} to submit remote task {} to relay {} failed with error {e}",
+                    policy.max_attempts, remote_task.id, relay.name
+                );
+                (Some(e), QueryTaskRemoteStatus::Queued)
+            }
+        };
+        // A retry is deferred rather than waited for, so that the worker moves on to other
+        // messages, and is dispatched again by the deferred dispatcher
+        let retry_at = matches!(status, QueryTaskRemoteStatus::Queued).then(|| {
+            Utc::now() + chrono::Duration::milliseconds(policy.backoff(attempt).as_millis() as i64)
+        });
         self.db
-            .update_remote_task_status(task_message.id, QueryTaskRemoteStatus::Submitted)
+            .record_remote_task_attempt(remote_task.id, error.as_deref(), status, retry_at)
             .await
-            .map_err(ExecutionError::ConnectionError)?;
-        Ok(())
+            .map_err(ExecutionError::ConnectionError)
     }
 
-    async fn process_message(&mut self) -> Result<()> {
+    /// Records a message which could not be processed as a dead letter, which retries it if it
+    /// has attempts left, and acks it.
+    async fn dead_letter(&mut self, msg_id: u64, msg: Option<&GenericMessage>, e: String) {
+        match self
+            .db
+            .record_failed_message(msg, &e, self.max_message_attempts)
+            .await
+        {
+            Ok(letter) if letter.dead => error!(
+                "Message id: {msg_id} is invalid with error {e}, dead lettered as {} after {} attempts!",
+                letter.id, letter.attempts
+            ),
+            Ok(letter) => warn!(
+                "Message id: {msg_id} failed attempt {} of {} with error {e}, retrying!",
+                letter.attempts, self.max_message_attempts
+            ),
+            Err(e2) => error!(
+                "Message id: {msg_id} is invalid with error {e}! Failed to dead letter it with error {e2}!"
+            ),
+        }
+        if let Err(e) = self.consumer.ack_message(msg_id).await {
+            error!("invalid message failed to delete with error {e}!");
+        }
+    }
+
+    /// Receives and processes a single message, unless shutdown is cancelled before a message
+    /// is received. A message which was received is processed to completion regardless.
+    async fn process_message(&mut self, shutdown: &CancellationToken) -> Result<()> {
         info!("Awaiting messages...");
-        let (msg_id, msg) = self.consumer.receive_message().await.map_err(|e| match e {
+        let received = tokio::select! {
+            received = self.consumer.receive_message() => received,
+            _ = shutdown.cancelled() => return Ok(()),
+        };
+        let (msg_id, msg) = received.map_err(|e| match e {
             MeshError::BadMessage((id, s)) => ExecutionError::InvalidMessage((id, s)),
             _ => ExecutionError::ConnectionError(e),
         })?;
-        match msg {
+        let processed = match &msg {
             GenericMessage::LocalQueryTask(task_message) => {
-                self.process_local_query_task(msg_id, task_message).await?
+                self.process_local_query_task(msg_id, task_message.clone())
+                    .await
             }
             GenericMessage::RemoteQueryTask(task_message) => {
-                self.process_remote_query_task(msg_id, task_message).await?
+                self.process_remote_query_task(msg_id, task_message.clone())
+                    .await
             }
         };
+        processed.map_err(|e| match e {
+            ExecutionError::InvalidMessage((id, s)) => {
+                ExecutionError::RejectedMessage((id, msg, s))
+            }
+            e => e,
+        })?;
 
         self.consumer
             .ack_message(msg_id)
@@ -246,8 +766,12 @@ impl<'a> MessageProcessor<'a> {
 
 /// Runs a single async task which consumes and processes messages from the queue one by one.
 /// Each worker may spawn many parallel tasks to execute each individual message, especially
-/// in the case of using in process DataFusion as the execution engine.
-async fn run_worker(in_memory_msg_opts: Option<MessageBrokerOptions>) -> Result<()> {
+/// in the case of using in process DataFusion as the execution engine. Once shutdown is
+/// cancelled the worker stops consuming messages and returns after finishing the current one.
+async fn run_worker(
+    in_memory_msg_opts: Option<MessageBrokerOptions>,
+    shutdown: CancellationToken,
+) -> Result<()> {
     let env_conf = EnvConfigSettings::init();
     let config =
         AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(&env_conf.db_url);
@@ -256,13 +780,21 @@ async fn run_worker(in_memory_msg_opts: Option<MessageBrokerOptions>) -> Result<
         .build(config)
         .await
         .expect("pool failed to start");
+    let heartbeat_config =
+        AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(&env_conf.db_url);
+    let heartbeat_pool = Pool::builder()
+        .max_size(1)
+        .build(heartbeat_config)
+        .await
+        .expect("pool failed to start");
 
-    let mut processor = MessageProcessor::init(&env_conf, &pool, &in_memory_msg_opts).await;
+    let mut processor =
+        MessageProcessor::init(&env_conf, &pool, heartbeat_pool, &in_memory_msg_opts).await;
 
     let mut connection_err_count = 0;
     let max_connection_err_count = 5;
-    loop {
-        match processor.process_message().await {
+    while !shutdown.is_cancelled() {
+        match processor.process_message(&shutdown).await {
             Ok(_) => {
                 connection_err_count = 0;
             }
@@ -285,16 +817,660 @@ async fn run_worker(in_memory_msg_opts: Option<MessageBrokerOptions>) -> Result<
                         Err(e) => error!("Failed query message failed to delete with error {e}!"),
                     }
                 }
-                ExecutionError::InvalidMessage((msg_id, e)) => {
-                    error!("Message id: {msg_id} is invalid with error {e}, deleting message!");
+                ExecutionError::Cancelled((msg_id, task_id)) => {
+                    info!("Query task {task_id} was cancelled");
                     match processor.consumer.ack_message(msg_id).await {
                         Ok(()) => (),
-                        Err(e) => error!("invalid message failed to delete with error {e}!"),
+                        Err(e) => {
+                            error!("Cancelled query message failed to delete with error {e}!")
+                        }
                     }
                 }
+                ExecutionError::InvalidMessage((msg_id, e)) => {
+                    processor.dead_letter(msg_id, None, e).await;
+                }
+                ExecutionError::RejectedMessage((msg_id, msg, e)) => {
+                    processor.dead_letter(msg_id, Some(&msg), e).await;
+                }
             },
         }
     }
+    info!("query_runner worker stopped consuming messages");
+    Ok(())
+}
+
+/// Runs a validation [Query] and returns the count it produced, or the number of rows returned
+/// if the result is not a single integer.
+async fn run_validation_query(
+    con: DataConnection,
+    source: DataSource,
+    query: Query,
+) -> std::result::Result<i64, MeshError> {
+    let batches = collect(
+        execute_query(con, source, query, CancellationToken::new())
+            .await?
+            .1,
+    )
+    .await?;
+    let total_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    if let [batch] = batches.as_slice() {
+        if total_rows == 1 && batch.num_columns() == 1 && batch.column(0).data_type().is_integer() {
+            let count = cast(batch.column(0), &DataType::Int64)?;
+            if let Some(count) = count.as_primitive_opt::<Int64Type>() {
+                return Ok(count.value(0));
+            }
+        }
+    }
+    Ok(total_rows as i64)
+}
+
+/// Periodically runs the validation_query of each [Entity][mesh::model::entity::Entity] which
+/// declares one against every mapped source, recording the row counts and logging an error when
+/// a previously working mapping fails.
+async fn run_entity_validator() -> Result<()> {
+    let env_conf = EnvConfigSettings::init();
+    let interval_secs: u64 = env::var("ENTITY_VALIDATION_INTERVAL_SECS")
+        .unwrap_or("3600".to_string())
+        .parse()
+        .expect("Unable to parse ENTITY_VALIDATION_INTERVAL_SECS as u64!");
+    let config =
+        AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(&env_conf.db_url);
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(config)
+        .await
+        .expect("pool failed to start");
+
+    let mut leader = LeaderLock::new(&env_conf.db_url, ENTITY_VALIDATOR_LOCK);
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+        if !is_leader(&mut leader, "entity validator").await {
+            continue;
+        }
+        let mut db = match PgDb::try_from_pool(&pool).await {
+            Ok(db) => db,
+            Err(e) => {
+                error!("Failed to connect to the database with error {e}");
+                continue;
+            }
+        };
+        let entities = match db.get_entities_with_validation_query().await {
+            Ok(entities) => entities,
+            Err(e) => {
+                error!("Failed to get entities to validate with error {e}");
+                continue;
+            }
+        };
+        for entity in entities {
+            let queries = match entity_validation_queries(&mut db, &entity).await {
+                Ok(queries) => queries,
+                Err(e) => {
+                    error!(
+                        "Validation query for entity {} is invalid with error {e}",
+                        entity.name
+                    );
+                    continue;
+                }
+            };
+            for (con, source, query) in queries {
+                let source_id = source.id;
+                let source_name = source.name.clone();
+                let result = run_validation_query(con, source, query).await;
+                let validation = NewEntityValidation {
+                    entity_id: entity.id,
+                    data_source_id: source_id,
+                    success: result.is_ok(),
+                    row_count: result.as_ref().ok().copied(),
+                    error: result.as_ref().err().map(|e| e.to_string()),
+                    checked_at: Utc::now(),
+                };
+                match db.record_entity_validation(&validation).await {
+                    Ok(previous) => match (&result, previous) {
+                        (Err(e), Some(previous)) if previous.success => error!(
+                            "Mapping of entity {} to source {source_name} is broken! \
+                            Validation query last succeeded at {} but now fails with error {e}",
+                            entity.name, previous.checked_at
+                        ),
+                        (Err(e), _) => warn!(
+                            "Validation query for entity {} on source {source_name} failed with error {e}",
+                            entity.name
+                        ),
+                        (Ok(count), _) => info!(
+                            "Validation query for entity {} on source {source_name} returned {count}",
+                            entity.name
+                        ),
+                    },
+                    Err(e) => error!(
+                        "Failed to record validation of entity {} on source {source_name} with error {e}",
+                        entity.name
+                    ),
+                }
+            }
+        }
+    }
+}
+
+/// Sends a GET to the /ping endpoint of a peer over mTLS, returning the round trip in
+/// milliseconds including the TLS handshake, or why the peer is unreachable. A response of any
+/// status shows the endpoint is reachable, so peers which do not serve /ping are seen as well.
+async fn ping_rest(
+    client: &Client,
+    rest_endpoint: &str,
+    timeout: Duration,
+) -> std::result::Result<i64, String> {
+    let started = Instant::now();
+    client
+        .get(format!("{rest_endpoint}/ping"))
+        .timeout(timeout)
+        .send()
+        .await
+        .map(|_| started.elapsed().as_millis() as i64)
+        .map_err(|e| e.to_string())
+}
+
+/// Periodically probes the rest and flight endpoints of every peer [Relay] except the local one,
+/// every RELAY_PROBE_INTERVAL_SECS (default 60), recording their latency and when they were last
+/// seen. Logs an error when a previously reachable endpoint becomes unreachable.
+async fn run_relay_prober() -> Result<()> {
+    let env_conf = EnvConfigSettings::init();
+    let interval_secs: u64 = env::var("RELAY_PROBE_INTERVAL_SECS")
+        .unwrap_or("60".to_string())
+        .parse()
+        .expect("Unable to parse RELAY_PROBE_INTERVAL_SECS as u64!");
+    let timeout_secs: u64 = env::var("RELAY_PROBE_TIMEOUT_SECS")
+        .unwrap_or("10".to_string())
+        .parse()
+        .expect("Unable to parse RELAY_PROBE_TIMEOUT_SECS as u64!");
+    let timeout = Duration::from_secs(timeout_secs);
+    let config =
+        AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(&env_conf.db_url);
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(config)
+        .await
+        .expect("pool failed to start");
+
+    let client_cert = env_conf
+        .read_client_cert()
+        .expect("Could not read client cert");
+    let (fingerprint, _subject, _issuer) =
+        load_certificate_from_reader(&mut client_cert.as_slice())
+            .ok()
+            .and_then(|certs| certs.into_iter().next())
+            .and_then(|cert| parse_certificate(&cert).ok())
+            .expect("Failed to parse own cert!");
+    let result_manager = init_result_manager(&env_conf);
+    let client = init_reqw_client(&env_conf);
+
+    let mut leader = LeaderLock::new(&env_conf.db_url, RELAY_PROBER_LOCK);
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+        if !is_leader(&mut leader, "relay prober").await {
+            continue;
+        }
+        let mut db = match PgDb::try_from_pool(&pool).await {
+            Ok(db) => db,
+            Err(e) => {
+                error!("Failed to connect to the database with error {e}");
+                continue;
+            }
+        };
+        let relays = match db.get_relays().await {
+            Ok(relays) => relays,
+            Err(e) => {
+                error!("Failed to get relays to probe with error {e}");
+                continue;
+            }
+        };
+        for relay in relays.into_iter().filter(|r| r.x509_sha256 != fingerprint) {
+            let (rest, flight) = tokio::join!(
+                ping_rest(&client, &relay.rest_endpoint, timeout),
+                ping_flight(&result_manager, &relay.flight_endpoint, timeout)
+            );
+            let status = NewRelayStatus {
+                relay_id: relay.id,
+                checked_at: Utc::now(),
+                rest_latency_ms: rest.as_ref().ok().copied(),
+                rest_error: rest.as_ref().err().cloned(),
+                flight_latency_ms: flight.as_ref().ok().copied(),
+                flight_error: flight.as_ref().err().cloned(),
+            };
+            match db.record_relay_status(&status).await {
+                Ok(previous) => {
+                    let previous = previous.as_ref();
+                    if let Err(e) = &rest {
+                        if previous.is_some_and(|p| p.rest_error.is_none()) {
+                            error!("Relay {} is no longer reachable via rest: {e}", relay.name);
+                        } else {
+                            warn!("Relay {} is unreachable via rest: {e}", relay.name);
+                        }
+                    }
+                    if let Err(e) = &flight {
+                        if previous.is_some_and(|p| p.flight_error.is_none()) {
+                            error!(
+                                "Relay {} is no longer reachable via flight: {e}",
+                                relay.name
+                            );
+                        } else {
+                            warn!("Relay {} is unreachable via flight: {e}", relay.name);
+                        }
+                    }
+                }
+                Err(e) => error!(
+                    "Failed to record status of relay {} with error {e}",
+                    relay.name
+                ),
+            }
+        }
+    }
+}
+
+/// Initializes a [MessageProducer] for a background task, retrying every 5 seconds while the
+/// message broker is unavailable, since background tasks only exit on shutdown.
+async fn connect_producer(options: &MessageBrokerOptions) -> Box<dyn MessageProducer> {
+    loop {
+        match initialize_producer(options).await {
+            Ok(producer) => return producer,
+            Err(e) => {
+                error!("Failed to connect to the message broker with error {e}... retrying in 5 seconds");
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        }
+    }
+}
+
+/// Periodically releases [QueryTask][mesh::model::query::QueryTask]s which were deferred
+/// because their connection was outside of its execution windows, and
+/// [QueryTaskRemote][mesh::model::query::QueryTaskRemote]s whose submission is to be retried,
+/// once the deferral has elapsed, and dispatches them via the task outbox.
+async fn run_deferred_dispatcher(in_memory_msg_opts: Option<MessageBrokerOptions>) -> Result<()> {
+    let env_conf = EnvConfigSettings::init();
+    let poll_secs: u64 = env::var("DEFERRED_TASK_POLL_SECS")
+        .unwrap_or("60".to_string())
+        .parse()
+        .expect("Unable to parse DEFERRED_TASK_POLL_SECS as u64!");
+    let config =
+        AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(&env_conf.db_url);
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(config)
+        .await
+        .expect("pool failed to start");
+    let message_options = match in_memory_msg_opts {
+        Some(opts) => opts,
+        None => env_conf.msg_broker_opts,
+    };
+    let mut producer = connect_producer(&message_options).await;
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(poll_secs)).await;
+        let mut db = match PgDb::try_from_pool(&pool).await {
+            Ok(db) => db,
+            Err(e) => {
+                error!("Failed to connect to the database with error {e}");
+                continue;
+            }
+        };
+        match db.release_deferred_tasks(Utc::now()).await {
+            Ok(task_ids) => {
+                for task_id in task_ids {
+                    info!("Dispatching deferred task {task_id}");
+                }
+            }
+            Err(e) => {
+                error!("Failed to check for deferred tasks with error {e}");
+                continue;
+            }
+        };
+        if let Err(e) = publish_outbox(&mut db, producer.as_mut(), Utc::now()).await {
+            error!("Failed to dispatch deferred tasks with error {e}");
+        }
+    }
+}
+
+/// Periodically publishes messages in the task outbox which were not published by the service
+/// which created their tasks, e.g. because it crashed or the message broker was unavailable.
+/// On startup, first requeues tasks which are still Queued ORPHANED_TASK_SECS after their message
+/// was published, without a message in the outbox, in case the message broker lost their messages.
+async fn run_outbox_dispatcher(in_memory_msg_opts: Option<MessageBrokerOptions>) -> Result<()> {
+    let env_conf = EnvConfigSettings::init();
+    let poll_secs: i64 = env::var("OUTBOX_POLL_SECS")
+        .unwrap_or("10".to_string())
+        .parse()
+        .expect("Unable to parse OUTBOX_POLL_SECS as i64!");
+    let orphaned_secs: i64 = env::var("ORPHANED_TASK_SECS")
+        .unwrap_or("300".to_string())
+        .parse()
+        .expect("Unable to parse ORPHANED_TASK_SECS as i64!");
+    let config =
+        AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(&env_conf.db_url);
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(config)
+        .await
+        .expect("pool failed to start");
+    let message_options = match in_memory_msg_opts {
+        Some(opts) => opts,
+        None => env_conf.msg_broker_opts,
+    };
+    let mut producer = connect_producer(&message_options).await;
+
+    match PgDb::try_from_pool(&pool).await {
+        Ok(mut db) => {
+            let orphaned_before = Utc::now() - chrono::Duration::seconds(orphaned_secs);
+            match db.requeue_orphaned_tasks(orphaned_before).await {
+                Ok(0) => (),
+                Ok(n) => {
+                    warn!("Requeued {n} orphaned tasks without a pending message");
+                    if let Err(e) = publish_outbox(&mut db, producer.as_mut(), Utc::now()).await {
+                        error!("Failed to publish task outbox with error {e}");
+                    }
+                }
+                Err(e) => error!("Failed to requeue orphaned tasks with error {e}"),
+            }
+        }
+        Err(e) => error!("Failed to connect to the database to requeue orphaned tasks: {e}"),
+    }
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(poll_secs.unsigned_abs())).await;
+        let mut db = match PgDb::try_from_pool(&pool).await {
+            Ok(db) => db,
+            Err(e) => {
+                error!("Failed to connect to the database with error {e}");
+                continue;
+            }
+        };
+        // Messages younger than one interval are usually still being published by their creator
+        let created_before = Utc::now() - chrono::Duration::seconds(poll_secs);
+        match publish_outbox(&mut db, producer.as_mut(), created_before).await {
+            Ok(0) => (),
+            Ok(n) => warn!("Published {n} stranded messages from the task outbox"),
+            Err(e) => error!("Failed to publish task outbox with error {e}"),
+        }
+    }
+}
+
+/// Periodically reclaims [QueryTask]s which are InProgress without a heartbeat for longer than
+/// QUERY_TASK_LEASE_SECS, because the query_runner executing them died. They are requeued via
+/// the task outbox, or failed once they were attempted MAX_MESSAGE_ATTEMPTS times, so that their
+/// requests never hang forever.
+async fn run_task_reclaimer(in_memory_msg_opts: Option<MessageBrokerOptions>) -> Result<()> {
+    let env_conf = EnvConfigSettings::init();
+    let lease = query_task_lease();
+    let max_attempts: i32 = env::var("MAX_MESSAGE_ATTEMPTS")
+        .unwrap_or("3".to_string())
+        .parse()
+        .expect("Unable to parse MAX_MESSAGE_ATTEMPTS as i32!");
+    let config =
+        AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(&env_conf.db_url);
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(config)
+        .await
+        .expect("pool failed to start");
+    let message_options = match in_memory_msg_opts {
+        Some(opts) => opts,
+        None => env_conf.msg_broker_opts,
+    };
+    let mut producer = connect_producer(&message_options).await;
+
+    loop {
+        tokio::time::sleep(lease / 2).await;
+        let mut db = match PgDb::try_from_pool(&pool).await {
+            Ok(db) => db,
+            Err(e) => {
+                error!("Failed to connect to the database with error {e}");
+                continue;
+            }
+        };
+        let heartbeat_before = Utc::now()
+            - chrono::Duration::from_std(lease).expect("QUERY_TASK_LEASE_SECS is out of range!");
+        let requeued = match db.reclaim_stale_tasks(heartbeat_before, max_attempts).await {
+            Ok((requeued, failed)) => {
+                for task_id in failed {
+                    error!(
+                        "Task {task_id} was abandoned {max_attempts} times and is marked Failed!"
+                    );
+                }
+                for task_id in requeued.iter() {
+                    warn!("Requeued task {task_id}, which was abandoned by its query_runner");
+                }
+                requeued.len()
+            }
+            Err(e) => {
+                error!("Failed to reclaim stale tasks with error {e}");
+                continue;
+            }
+        };
+        if requeued > 0 {
+            if let Err(e) = publish_outbox(&mut db, producer.as_mut(), Utc::now()).await {
+                error!("Failed to dispatch reclaimed tasks with error {e}");
+            }
+        }
+    }
+}
+
+/// Periodically deletes stored results whose [QueryRequest] retention elapsed, every
+/// RESULT_SWEEP_INTERVAL_SECS (default 300), and marks their tasks and flight streams as expired
+/// so that retrieving them tells the user to submit the query again. Also deletes scratch datasets
+/// older than SCRATCH_DATASET_TTL_SECS. A result which fails to be deleted is retried on the next
+/// sweep.
+async fn run_result_sweeper() -> Result<()> {
+    let env_conf = EnvConfigSettings::init();
+    let interval_secs = env::var("RESULT_SWEEP_INTERVAL_SECS")
+        .unwrap_or("300".to_string())
+        .parse()
+        .expect("Unable to parse RESULT_SWEEP_INTERVAL_SECS as u64!");
+    let config =
+        AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(&env_conf.db_url);
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(config)
+        .await
+        .expect("pool failed to start");
+    let result_manager = init_result_manager(&env_conf);
+
+    let mut leader = LeaderLock::new(&env_conf.db_url, RESULT_SWEEPER_LOCK);
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+        if !is_leader(&mut leader, "result sweeper").await {
+            continue;
+        }
+        let mut db = match PgDb::try_from_pool(&pool).await {
+            Ok(db) => db,
+            Err(e) => {
+                error!("Failed to connect to the database with error {e}");
+                continue;
+            }
+        };
+
+        let tasks = match db.get_expired_task_results(1000).await {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                error!("Failed to look up expired task results with error {e}");
+                continue;
+            }
+        };
+        let mut deleted = Vec::with_capacity(tasks.len());
+        for (task_id, request_id, replay_of, format) in tasks {
+            let deletion = match replay_of {
+                Some(_) => {
+                    result_manager
+                        .delete_replay_result(&request_id, &task_id, format)
+                        .await
+                }
+                None => {
+                    result_manager
+                        .delete_task_result(&task_id, None, format)
+                        .await
+                }
+            };
+            match deletion {
+                Ok(()) => deleted.push(task_id),
+                Err(e) => warn!("Failed to delete expired result of task {task_id} with error {e}"),
+            }
+        }
+        if !deleted.is_empty() {
+            match db.mark_task_results_expired(&deleted).await {
+                Ok(()) => info!("Deleted {} expired task results", deleted.len()),
+                Err(e) => error!("Failed to mark task results as expired with error {e}"),
+            }
+        }
+
+        let flights = match db.get_expired_flight_results(1000).await {
+            Ok(flights) => flights,
+            Err(e) => {
+                error!("Failed to look up expired flight results with error {e}");
+                continue;
+            }
+        };
+        let mut deleted = Vec::with_capacity(flights.len());
+        for (stream_id, flight_id, relay_id, format) in flights {
+            let deletion = match db.get_relay_by_id(&relay_id).await {
+                Ok(relay) => {
+                    result_manager
+                        .delete_task_result(&flight_id, Some(&relay.name), format)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            match deletion {
+                Ok(()) => deleted.push(stream_id),
+                Err(e) => {
+                    warn!("Failed to delete expired result of flight {flight_id} with error {e}")
+                }
+            }
+        }
+        if !deleted.is_empty() {
+            match db.mark_flight_results_expired(&deleted).await {
+                Ok(()) => info!("Deleted {} expired flight results", deleted.len()),
+                Err(e) => error!("Failed to mark flight results as expired with error {e}"),
+            }
+        }
+
+        match delete_expired_scratch_datasets(&mut db, &result_manager, 1000).await {
+            Ok(0) => (),
+            Ok(deleted) => info!("Deleted {deleted} expired scratch datasets"),
+            Err(e) => error!("Failed to delete expired scratch datasets with error {e}"),
+        }
+    }
+}
+
+/// Periodically refreshes the broker depth metrics from the queue stats of every [DataSource],
+/// since no single query_runner sees every message of the broker.
+async fn run_broker_depth_monitor() -> Result<()> {
+    let env_conf = EnvConfigSettings::init();
+    let config =
+        AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(&env_conf.db_url);
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(config)
+        .await
+        .expect("pool failed to start");
+    loop {
+        match PgDb::try_from_pool(&pool).await {
+            Ok(mut db) => {
+                if let Err(e) = metrics().refresh_broker_depth(&mut db).await {
+                    error!("Failed to refresh broker depth metrics with error {e}");
+                }
+            }
+            Err(e) => error!("Failed to connect to database to refresh metrics with error {e}"),
+        }
+        tokio::time::sleep(Duration::from_secs(15)).await;
+    }
+}
+
+/// Periodically emits an OpenLineage event for every [QueryRequest][mesh::model::query::QueryRequest]
+/// which has no pending work left, see [mesh::execute::lineage]. A request whose event could not
+/// be delivered is retried on the next poll.
+async fn run_lineage_emitter(options: LineageOptions) -> Result<()> {
+    let env_conf = EnvConfigSettings::init();
+    let config =
+        AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(&env_conf.db_url);
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(config)
+        .await
+        .expect("pool failed to start");
+
+    let client_cert = env_conf
+        .read_client_cert()
+        .expect("Could not read client cert");
+    let (fingerprint, _subject, _issuer) =
+        load_certificate_from_reader(&mut client_cert.as_slice())
+            .ok()
+            .and_then(|certs| certs.into_iter().next())
+            .and_then(|cert| parse_certificate(&cert).ok())
+            .expect("Failed to parse own cert!");
+    let result_manager = init_result_manager(&env_conf);
+    let client = Client::new();
+
+    loop {
+        tokio::time::sleep(options.poll_interval).await;
+        let mut db = match PgDb::try_from_pool(&pool).await {
+            Ok(db) => db,
+            Err(e) => {
+                error!("Failed to connect to the database with error {e}");
+                continue;
+            }
+        };
+        let local_relay = match db.get_relay_by_x509_fingerprint(&fingerprint).await {
+            Ok(relay) => relay,
+            Err(e) => {
+                error!("Unable to emit lineage, the local relay must be declared: {e}");
+                continue;
+            }
+        };
+        let received_before = Utc::now()
+            - chrono::Duration::from_std(options.settle).expect("Settle window out of range");
+        let requests = match db.claim_settled_requests(received_before, 100).await {
+            Ok(requests) => requests,
+            Err(e) => {
+                error!("Failed to claim settled query requests with error {e}");
+                continue;
+            }
+        };
+        for request in requests {
+            let delivered =
+                match lineage_event(&mut db, &result_manager, &local_relay, &request).await {
+                    Ok(event) => {
+                        let mut post = client.post(&options.url).json(&event);
+                        if let Some(api_key) = &options.api_key {
+                            post = post.bearer_auth(api_key);
+                        }
+                        match post.send().await.and_then(|r| r.error_for_status()) {
+                            Ok(_) => true,
+                            Err(e) => {
+                                warn!(
+                                    "Failed to emit lineage of request {} with error {e}",
+                                    request.id
+                                );
+                                false
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        error!(
+                            "Failed to collect lineage of request {} with error {e}",
+                            request.id
+                        );
+                        false
+                    }
+                };
+            if !delivered {
+                if let Err(e) = db.release_lineage_claim(request.id).await {
+                    error!(
+                        "Failed to release lineage claim of request {} with error {e}",
+                        request.id
+                    );
+                }
+            }
+        }
+    }
 }
 
 pub async fn run(in_memory_msg_opts: Option<MessageBrokerOptions>) -> Result<()> {
@@ -313,14 +1489,67 @@ pub async fn run(in_memory_msg_opts: Option<MessageBrokerOptions>) -> Result<()>
     info!("Got {min_parallelism_per_query_worker} min_parallelism_per_query_worker and {available_parallelism} available_parallelism");
     info!("Starting {num_workers} query_runner tasks!");
 
-    let mut taskset: tokio::task::JoinSet<Result<()>> = tokio::task::JoinSet::new();
+    let shutdown = shutdown_token();
+    let mut workers: JoinSet<Result<()>> = JoinSet::new();
     for _ in 0..num_workers {
         let in_memory_msg_opts_clone = in_memory_msg_opts.clone();
-        taskset.spawn(async move { run_worker(in_memory_msg_opts_clone).await });
+        let shutdown = shutdown.clone();
+        workers.spawn(async move { run_worker(in_memory_msg_opts_clone, shutdown).await });
+    }
+    let mut background: JoinSet<Result<()>> = JoinSet::new();
+    let in_memory_msg_opts_clone = in_memory_msg_opts.clone();
+    background.spawn(async move { run_deferred_dispatcher(in_memory_msg_opts_clone).await });
+    let in_memory_msg_opts_clone = in_memory_msg_opts.clone();
+    background.spawn(async move { run_task_reclaimer(in_memory_msg_opts_clone).await });
+    background.spawn(async move { run_outbox_dispatcher(in_memory_msg_opts).await });
+    background.spawn(async move { run_entity_validator().await });
+    background.spawn(async move { run_result_sweeper().await });
+    background.spawn(async move { run_relay_prober().await });
+    if let Some(metrics_addr) = metrics_addr("QUERY_RUNNER_METRICS_ADDR") {
+        let shutdown = shutdown.clone();
+        background.spawn(async move {
+            serve_metrics(metrics_addr, shutdown)
+                .await
+                .map_err(ExecutionError::ConnectionError)
+        });
+        background.spawn(async move { run_broker_depth_monitor().await });
+    }
+    if let Some(lineage_options) = LineageOptions::from_env() {
+        background.spawn(async move { run_lineage_emitter(lineage_options).await });
+    }
+
+    // All tasks should run until shutdown, so we panic if any in fact exit before.
+    tokio::select! {
+        biased;
+        _ = shutdown.cancelled() => (),
+        joined = workers.join_next() => panic_on_exit(joined),
+        joined = background.join_next() => panic_on_exit(joined),
     }
 
-    // All tasks should run forever, so we panic if any in fact exit.
-    match taskset.join_next().await {
+    // Background tasks hold no in-flight work, so they are stopped right away. Workers finish
+    // the task they are executing, and a task which does not finish within
+    // SHUTDOWN_TIMEOUT_SECS is reclaimed by another query_runner once its lease expires.
+    background.abort_all();
+    let drained = drain(&shutdown, shutdown_timeout(), async {
+        while let Some(joined) = workers.join_next().await {
+            match joined {
+                Ok(Ok(())) => (),
+                Ok(Err(e)) => error!("QueryRunner worker shut down with error: {e:?}"),
+                Err(e) => error!("QueryRunner worker join_error {e}"),
+            }
+        }
+    })
+    .await;
+    if drained.is_none() {
+        workers.abort_all();
+    }
+    // Every database pool is closed as the tasks owning them are dropped
+    info!("QueryRunner shut down");
+    Ok(())
+}
+
+fn panic_on_exit(joined: Option<std::result::Result<Result<()>, JoinError>>) -> ! {
+    match joined {
         Some(Ok(Ok(_))) => panic!("QueryRunner worker shut down with no error!"),
         Some(Ok(Err(e))) => panic!("QueryRunner worker shut down with error: {e:?}"),
         Some(Err(e)) => panic!("QueryRunner worker join_error {e}"),
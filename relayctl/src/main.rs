@@ -3,7 +3,10 @@ use std::{env, io::Read};
 use clap::{Parser, Subcommand};
 
 use mesh::error::Result;
-use process::apply;
+use process::{
+    apply, create_invite, delete, diff, discover, export, import, redeem_invite, set_source_paused,
+    test_connections, validate, ApplyOptions,
+};
 
 mod process;
 
@@ -22,6 +25,109 @@ enum Command {
         /// Path to the config command. Can be a directory of YAML files or a single YAML file.
         #[clap(long, short = 'f')]
         filepath: std::path::PathBuf,
+        #[clap(flatten)]
+        options: ApplyOptions,
+    },
+    /// Write the configuration of the Relay as YAML config commands, which import restores
+    Export {
+        /// File to write the configuration to, defaults to stdout
+        #[clap(long, short = 'o')]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Apply the configuration written by export, e.g. to restore it onto a new Relay
+    Import {
+        /// Path to the file written by export
+        #[clap(long, short = 'f')]
+        filepath: std::path::PathBuf,
+        #[clap(flatten)]
+        options: ApplyOptions,
+    },
+    /// Parse a YAML file and check the references between the declared objects, without
+    /// contacting the Relay
+    Validate {
+        /// Path to the config command. Can be a directory of YAML files or a single YAML file.
+        #[clap(long, short = 'f')]
+        filepath: std::path::PathBuf,
+    },
+    /// Parse a YAML file and show the objects which applying it would create, update and delete,
+    /// without changing the Relay
+    Diff {
+        /// Path to the config command. Can be a directory of YAML files or a single YAML file.
+        #[clap(long, short = 'f')]
+        filepath: std::path::PathBuf,
+        /// Include the objects which apply --prune would delete
+        #[clap(long)]
+        prune: bool,
+    },
+    /// Parse a YAML file and delete the declared objects, and everything depending on them, from
+    /// the Relay
+    Delete {
+        /// Path to the config command. Can be a directory of YAML files or a single YAML file.
+        #[clap(long, short = 'f')]
+        filepath: std::path::PathBuf,
+    },
+    /// Issue an invite token for a peer Relay, to be redeemed by the peer's admin
+    Invite {
+        /// Name under which the peer Relay is registered once it redeems the invite
+        #[clap(long, short = 'n')]
+        peer_name: String,
+        /// How long the invite may be redeemed for, defaults to 24 hours
+        #[clap(long)]
+        ttl_secs: Option<i64>,
+    },
+    /// Redeem an invite token issued by another Relay, registering both Relays as peers
+    Redeem {
+        /// The invite token issued by the other Relay
+        #[clap(long, short = 't')]
+        token: String,
+        /// Name under which the issuing Relay is registered, defaults to the name it declares
+        #[clap(long, short = 'n')]
+        name: Option<String>,
+    },
+    /// Parse a YAML file and check that the Relay can connect to every declared DataSource,
+    /// without applying the config objects
+    TestConnection {
+        /// Path to the config command. Can be a directory of YAML files or a single YAML file.
+        #[clap(long, short = 'f')]
+        filepath: std::path::PathBuf,
+        /// How long the connection to each DataSource may take, defaults to 30 seconds
+        #[clap(long)]
+        timeout_secs: Option<u64>,
+    },
+    /// Draft the YAML declaration of the DataSources served by a DataConnection, with a field
+    /// for every column
+    Discover {
+        /// Name of the DataConnection to discover
+        #[clap(long, short = 'c')]
+        connection: String,
+        /// For Postgres, the schema to discover. For Trino, the catalog or catalog.schema
+        #[clap(long, short = 's')]
+        schema: Option<String>,
+        /// YAML file declaring the source_options of the files to inspect, required for
+        /// FileDirectory connections
+        #[clap(long)]
+        source_options_file: Option<std::path::PathBuf>,
+        /// Name of the DataSource drafted for a FileDirectory connection
+        #[clap(long, short = 'n')]
+        name: Option<String>,
+    },
+    /// Pause dispatching new queries to a DataSource
+    Pause {
+        /// Name of the DataConnection which contains the DataSource
+        #[clap(long, short = 'c')]
+        connection: String,
+        /// Name of the DataSource to pause
+        #[clap(long, short = 's')]
+        source: String,
+    },
+    /// Resume dispatching new queries to a paused DataSource
+    Resume {
+        /// Name of the DataConnection which contains the DataSource
+        #[clap(long, short = 'c')]
+        connection: String,
+        /// Name of the DataSource to resume
+        #[clap(long, short = 's')]
+        source: String,
     },
 }
 
@@ -70,10 +176,84 @@ async fn main() -> Result<()> {
     let args = Relayctl::parse();
 
     match args.command {
-        Command::Apply { filepath } => {
+        Command::Apply { filepath, options } => {
+            let client = get_reqw_client()?;
+            let relay_endpoint = env::var("RELAY_ENDPOINT").expect("RELAY_ENDPOINT must be set");
+            apply(filepath, client, relay_endpoint, options).await?
+        }
+        Command::Export { output } => {
+            let client = get_reqw_client()?;
+            let relay_endpoint = env::var("RELAY_ENDPOINT").expect("RELAY_ENDPOINT must be set");
+            export(client, relay_endpoint, output).await?
+        }
+        Command::Import { filepath, options } => {
+            let client = get_reqw_client()?;
+            let relay_endpoint = env::var("RELAY_ENDPOINT").expect("RELAY_ENDPOINT must be set");
+            import(filepath, client, relay_endpoint, options).await?
+        }
+        Command::Validate { filepath } => validate(filepath)?,
+        Command::Diff { filepath, prune } => {
+            let client = get_reqw_client()?;
+            let relay_endpoint = env::var("RELAY_ENDPOINT").expect("RELAY_ENDPOINT must be set");
+            diff(filepath, client, relay_endpoint, prune).await?
+        }
+        Command::Delete { filepath } => {
+            let client = get_reqw_client()?;
+            let relay_endpoint = env::var("RELAY_ENDPOINT").expect("RELAY_ENDPOINT must be set");
+            delete(filepath, client, relay_endpoint).await?
+        }
+        Command::Invite {
+            peer_name,
+            ttl_secs,
+        } => {
+            let client = get_reqw_client()?;
+            let relay_endpoint = env::var("RELAY_ENDPOINT").expect("RELAY_ENDPOINT must be set");
+            let token = create_invite(&client, &relay_endpoint, &peer_name, ttl_secs).await?;
+            println!("{token}");
+        }
+        Command::Redeem { token, name } => {
+            let client = get_reqw_client()?;
+            let relay_endpoint = env::var("RELAY_ENDPOINT").expect("RELAY_ENDPOINT must be set");
+            let relay_name = redeem_invite(&client, &relay_endpoint, &token, name).await?;
+            println!("Registered peer relay {relay_name}!");
+        }
+        Command::TestConnection {
+            filepath,
+            timeout_secs,
+        } => {
+            let client = get_reqw_client()?;
+            let relay_endpoint = env::var("RELAY_ENDPOINT").expect("RELAY_ENDPOINT must be set");
+            test_connections(filepath, &client, &relay_endpoint, timeout_secs).await?
+        }
+        Command::Discover {
+            connection,
+            schema,
+            source_options_file,
+            name,
+        } => {
+            let client = get_reqw_client()?;
+            let relay_endpoint = env::var("RELAY_ENDPOINT").expect("RELAY_ENDPOINT must be set");
+            discover(
+                &client,
+                &relay_endpoint,
+                &connection,
+                schema,
+                name,
+                source_options_file,
+            )
+            .await?
+        }
+        Command::Pause { connection, source } => {
+            let client = get_reqw_client()?;
+            let relay_endpoint = env::var("RELAY_ENDPOINT").expect("RELAY_ENDPOINT must be set");
+            set_source_paused(&client, &relay_endpoint, &connection, &source, true).await?;
+            println!("{connection}/{source} paused!");
+        }
+        Command::Resume { connection, source } => {
             let client = get_reqw_client()?;
             let relay_endpoint = env::var("RELAY_ENDPOINT").expect("RELAY_ENDPOINT must be set");
-            apply(filepath, client, relay_endpoint).await?
+            set_source_paused(&client, &relay_endpoint, &connection, &source, false).await?;
+            println!("{connection}/{source} resumed!");
         }
     }
 
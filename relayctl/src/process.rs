@@ -4,67 +4,442 @@ use std::iter;
 use itertools::Itertools;
 use mesh::error::{MeshError, Result};
 
+use mesh::execute::data_stores::ConnectionTest;
+use mesh::execute::discover::DiscoverOptions;
+use mesh::execute::inspect::{ChangeAction, ConfigChange, ConfigExport};
+use mesh::execute::invite::IssuedInvite;
+use mesh::model::config_commands::chunk::pack;
 use mesh::model::config_commands::entity::{
     EntityDeclaration, ResolvedEntityDeclaration, ResolvedInformationDeclaration,
 };
+use mesh::model::config_commands::local_data::DataSourcesDeclaration;
+use mesh::model::config_commands::quota::{QuotaDeclaration, ResolvedQuotaDeclaration};
 use mesh::model::config_commands::relay::{PeerRelayDeclaration, ResolvedPeerRelayDeclaration};
-use mesh::model::config_commands::user::{ResolvedUserDeclaration, UserDeclaration};
+use mesh::model::config_commands::report::{ApplyReport, ApplyStatus};
+use mesh::model::config_commands::role::{ResolvedRoleDeclaration, RoleDeclaration};
+use mesh::model::config_commands::user::{
+    ResolvedServiceAccountDeclaration, ResolvedUserDeclaration, ServiceAccountDeclaration,
+    UserDeclaration,
+};
+use mesh::model::config_commands::validate::DeclaredConfig;
 use mesh::model::config_commands::{
     ConfigCommand, ConfigObject, ResolvedConfigCommand, ResolvedConfigObject,
 };
+use mesh::model::data_stores::options::SourceOptions;
+use mesh::model::relay::Relay;
 use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct ApplyOptions {
+    /// Maximum number of data fields or mappings sent to the Relay in a single request.
+    /// Larger declarations are split into several requests and smaller ones are batched
+    /// into one, applied in order. Each request is applied in a single transaction.
+    #[clap(long, default_value_t = 1000)]
+    pub chunk_size: usize,
+    /// Skip the requests which were already applied by a previous, failed apply of the same
+    /// config files, as recorded in the state file.
+    #[clap(long)]
+    pub resume: bool,
+    /// File in which the progress of a failed apply is recorded.
+    #[clap(long, default_value = ".relayctl-apply-state.json")]
+    pub state_file: std::path::PathBuf,
+    /// After a successful apply, delete every object on the Relay which is not declared in
+    /// the config files. The config files must declare the complete configuration of the
+    /// Relay.
+    #[clap(long)]
+    pub prune: bool,
+    /// Commit the objects of a request which applied even if others in the same request
+    /// fail, rather than rolling back the whole request.
+    #[clap(long)]
+    pub partial: bool,
+}
 
+/// Progress of an apply which failed part way through, persisted so that it can be resumed.
+#[derive(Serialize, Deserialize)]
+struct ApplyState {
+    /// Digest of every planned request, so that progress is only reused for an identical plan.
+    plan_digest: String,
+    /// Number of requests, in plan order, which were committed.
+    applied: usize,
+}
+
+/// Applies every config object found under path. Large objects are split into chunks (see
+/// [ResolvedConfigCommand::into_chunks]) and small ones are batched, so that every request
+/// declares about chunk_size items. Requests are sent in order of apply_precedence and each is
+/// applied by the relay in a single transaction. The apply stops at the first request which is
+/// rolled back, recording its progress in the state file so that a subsequent apply with resume
+/// set picks up from the failed request. With partial set, the objects of a request which applied
+/// are committed even if others fail, and the apply continues with the next request.
+///
+/// With prune set, every object on the relay which is not declared under path is deleted once
+/// every object applied.
 pub(crate) async fn apply(
     path: std::path::PathBuf,
+    client: Client,
+    relay_endpoint: String,
+    options: ApplyOptions,
+) -> Result<()> {
+    let (cmds, failed) = parse_directory(path)?;
+    apply_resolved(cmds, failed, client, relay_endpoint, options).await
+}
+
+/// Applies the config commands exported to path by [export], see [apply]. With prune set,
+/// every object on the relay which is not in the export is deleted.
+pub(crate) async fn import(
+    path: std::path::PathBuf,
+    client: Client,
+    relay_endpoint: String,
+    options: ApplyOptions,
+) -> Result<()> {
+    let filepath = path.to_string_lossy().to_string();
+    let reader = std::io::BufReader::new(std::fs::File::open(&path)?);
+    let mut cmds = vec![];
+    for document in serde_yaml::Deserializer::from_reader(reader) {
+        let cmd: ResolvedConfigCommand =
+            serde_yaml::with::singleton_map_recursive::deserialize(document).map_err(|e| {
+                MeshError::InvalidQuery(format!("Unable to parse export {filepath}: {e}"))
+            })?;
+        cmds.push((filepath.clone(), cmd));
+    }
+    cmds.sort_by_key(|(_, cmd)| cmd.config_object.apply_precedence());
+    apply_resolved(cmds, 0, client, relay_endpoint, options).await
+}
+
+/// Applies the resolved commands, labelled by the file they were read from, as described by
+/// [apply]. failed is the number of objects which could not be parsed or resolved.
+async fn apply_resolved(
+    cmds: Vec<(String, ResolvedConfigCommand)>,
+    failed: usize,
     mut client: Client,
     relay_endpoint: String,
+    options: ApplyOptions,
 ) -> Result<()> {
-    for (filepath, cmd) in parse_directory(path)? {
-        match apply_command(cmd, &mut client, &relay_endpoint).await {
-            Ok(()) => println!("{} applied!", filepath),
+    if options.prune && failed > 0 {
+        return Err(MeshError::InvalidQuery(format!(
+            "Refusing to prune, {failed} config objects could not be parsed or resolved!"
+        )));
+    }
+    // Serialized up front, as the commands are consumed when split into chunks.
+    let declared = match options.prune {
+        true => Some(serde_json::to_value(
+            cmds.iter().map(|(_, cmd)| cmd).collect_vec(),
+        )?),
+        false => None,
+    };
+    let plan = cmds
+        .into_iter()
+        .flat_map(|(filepath, cmd)| iter::repeat(filepath).zip(cmd.into_chunks(options.chunk_size)))
+        .collect_vec();
+    let plan_digest = plan_digest(&plan, options.chunk_size)?;
+    let batches = pack(plan, |(_, cmd)| cmd.item_count(), options.chunk_size);
+    let total = batches.len();
+
+    let skip = match read_state(&options.state_file)? {
+        Some(state) if options.resume && state.plan_digest == plan_digest => {
+            println!("Resuming apply after {} of {total} requests", state.applied);
+            state.applied
+        }
+        Some(_) if options.resume => {
+            println!(
+                "Config files changed since the apply recorded in {}, starting from the beginning",
+                options.state_file.to_string_lossy()
+            );
+            0
+        }
+        _ => 0,
+    };
+
+    let mut failed = 0;
+    for (i, batch) in batches.into_iter().enumerate().skip(skip) {
+        let (filepaths, cmds): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+        let result = apply_commands(&cmds, &mut client, &relay_endpoint, options.partial).await;
+        if let Ok(report) = &result {
+            for (filepath, object) in filepaths.iter().zip(&report.objects) {
+                let outcome = match (object.status, &object.error) {
+                    (ApplyStatus::Applied, _) => "applied!".to_string(),
+                    (ApplyStatus::Failed, Some(e)) => format!("failed with error {e}"),
+                    (ApplyStatus::Failed, None) => "failed!".to_string(),
+                    (ApplyStatus::RolledBack, _) => "rolled back".to_string(),
+                    (ApplyStatus::Skipped, _) => "skipped".to_string(),
+                };
+                println!("[{}/{total}] {filepath} {} {outcome}", i + 1, object.object);
+            }
+        }
+        let report = match result {
+            Ok(report) if report.committed => report,
+            Ok(_) => {
+                let e = MeshError::RemoteError(format!(
+                    "Request {} of {total} was rolled back!",
+                    i + 1
+                ));
+                return fail_apply(&options.state_file, plan_digest, i, e);
+            }
             Err(e) => {
-                println!("Unable to apply config file at {} with error {e}", filepath);
-                continue;
+                println!("[{}/{total}] Unable to apply request with error {e}", i + 1);
+                return fail_apply(&options.state_file, plan_digest, i, e);
+            }
+        };
+        failed += report.failed();
+    }
+    if options.state_file.exists() {
+        std::fs::remove_file(&options.state_file)?;
+    }
+
+    if failed > 0 {
+        if declared.is_some() {
+            println!("Skipping prune, since not every config object applied.");
+        }
+        return Err(MeshError::RemoteError(format!(
+            "{failed} config objects failed to apply!"
+        )));
+    }
+
+    if let Some(declared) = declared {
+        let r = client
+            .post(format!("{relay_endpoint}/admin/prune"))
+            .json(&declared)
+            .send()
+            .await
+            .map_err(|e| MeshError::RemoteError(e.to_string()))?;
+        let pruned: Vec<String> = parse_response(r).await?;
+        for object in &pruned {
+            println!("{object} pruned!");
+        }
+        println!("Pruned {} objects", pruned.len());
+    }
+    Ok(())
+}
+
+/// Exports the configuration of the relay as multi document YAML of config commands, written to
+/// output or stdout, which [import] restores. Objects which could not be exported are listed on
+/// stderr.
+pub(crate) async fn export(
+    client: Client,
+    relay_endpoint: String,
+    output: Option<std::path::PathBuf>,
+) -> Result<()> {
+    let r = client
+        .get(format!("{relay_endpoint}/admin/export"))
+        .send()
+        .await
+        .map_err(|e| MeshError::RemoteError(e.to_string()))?;
+    let export: ConfigExport = parse_response(r).await?;
+
+    let writer: Box<dyn std::io::Write> = match &output {
+        Some(path) => Box::new(std::fs::File::create(path)?),
+        None => Box::new(std::io::stdout()),
+    };
+    let mut serializer = serde_yaml::Serializer::new(writer);
+    for cmd in &export.commands {
+        serde_yaml::with::singleton_map_recursive::serialize(cmd, &mut serializer)
+            .map_err(|e| MeshError::Internal(format!("Unable to serialize export: {e}")))?;
+    }
+
+    for object in &export.skipped {
+        eprintln!("{object} skipped, since its certificate is not stored on the relay");
+    }
+    eprintln!(
+        "Exported {} config objects, skipped {}",
+        export.commands.len(),
+        export.skipped.len()
+    );
+    Ok(())
+}
+
+/// Sends every config object found under path to the relay as a dry run, printing the objects
+/// which an apply would create, update and, with prune set, delete.
+pub(crate) async fn diff(
+    path: std::path::PathBuf,
+    client: Client,
+    relay_endpoint: String,
+    prune: bool,
+) -> Result<()> {
+    let (cmds, failed) = parse_directory(path)?;
+    if prune && failed > 0 {
+        return Err(MeshError::InvalidQuery(format!(
+            "Refusing to diff with prune, {failed} config objects could not be parsed or resolved!"
+        )));
+    }
+    let r = client
+        .post(format!("{relay_endpoint}/admin/apply"))
+        .query(&[("dry_run", true), ("prune", prune)])
+        .json(&cmds.iter().map(|(_, cmd)| cmd).collect_vec())
+        .send()
+        .await
+        .map_err(|e| MeshError::RemoteError(e.to_string()))?;
+    if r.status() == StatusCode::UNPROCESSABLE_ENTITY {
+        let report: ApplyReport = r
+            .json()
+            .await
+            .map_err(|e| MeshError::RemoteError(format!("Failed to parse response with e {e}")))?;
+        for (object, error) in report
+            .objects
+            .iter()
+            .filter_map(|o| Some((&o.object, o.error.as_ref()?)))
+        {
+            println!("{object} would fail with error {error}");
+        }
+        return Err(MeshError::RemoteError(format!(
+            "{} config objects would fail to apply!",
+            report.failed()
+        )));
+    }
+    let changes: Vec<ConfigChange> = parse_response(r).await?;
+    if changes.is_empty() {
+        println!("No changes!");
+        return Ok(());
+    }
+    for change in &changes {
+        let symbol = match change.action {
+            ChangeAction::Create => "+",
+            ChangeAction::Update => "~",
+            ChangeAction::Delete => "-",
+        };
+        println!("{symbol} {}", change.object);
+    }
+    let count = |action| changes.iter().filter(|c| c.action == action).count();
+    println!(
+        "{} to create, {} to update, {} to delete",
+        count(ChangeAction::Create),
+        count(ChangeAction::Update),
+        count(ChangeAction::Delete)
+    );
+    Ok(())
+}
+
+/// Parses and resolves every config object found under path and checks the references between
+/// them, printing every error found. Nothing is sent to the relay, so the objects under path must
+/// declare every object they reference.
+pub(crate) fn validate(path: std::path::PathBuf) -> Result<()> {
+    let (cmds, failed) = parse_directory(path)?;
+    let declared = DeclaredConfig::new(cmds.iter().map(|(_, cmd)| &cmd.config_object));
+    let mut errors = failed;
+    for (filepath, cmd) in &cmds {
+        for e in declared.validate(&cmd.config_object) {
+            println!("Invalid config object {filepath}: {e}");
+            errors += 1;
+        }
+    }
+    if errors > 0 {
+        return Err(MeshError::InvalidQuery(format!(
+            "Found {errors} errors in config files!"
+        )));
+    }
+    println!("{} config objects are valid!", cmds.len());
+    Ok(())
+}
+
+/// Deletes every config object found under path, along with everything which depends on it on
+/// the relay. Objects are deleted in reverse order of apply_precedence and the delete stops at
+/// the first failed request.
+pub(crate) async fn delete(
+    path: std::path::PathBuf,
+    client: Client,
+    relay_endpoint: String,
+) -> Result<()> {
+    let (cmds, _) = parse_directory(path)?;
+    for (filepath, cmd) in cmds.into_iter().rev() {
+        let r = client
+            .delete(format!("{relay_endpoint}/admin/apply"))
+            .json(&cmd)
+            .send()
+            .await
+            .map_err(|e| MeshError::RemoteError(e.to_string()))?;
+        match parse_response::<Vec<String>>(r).await {
+            Ok(deleted) if deleted.is_empty() => println!("{filepath} already deleted!"),
+            Ok(deleted) => {
+                for object in deleted {
+                    println!("{object} deleted!");
+                }
+            }
+            Err(e) => {
+                println!("Unable to delete config file at {filepath} with error {e}");
+                return Err(e);
             }
         }
     }
     Ok(())
 }
 
+/// Records that the first applied requests of the plan were committed, so that the apply can be
+/// resumed, and returns the error of the failed request.
+fn fail_apply(
+    state_file: &std::path::Path,
+    plan_digest: String,
+    applied: usize,
+    e: MeshError,
+) -> Result<()> {
+    write_state(
+        state_file,
+        &ApplyState {
+            plan_digest,
+            applied,
+        },
+    )?;
+    println!("Rerun apply with --resume to continue from this request.");
+    Err(e)
+}
+
+fn plan_digest(plan: &[(String, ResolvedConfigCommand)], chunk_size: usize) -> Result<String> {
+    let mut hasher = Sha256::new();
+    // The requests are batched by chunk_size, so it is part of the plan
+    hasher.update(chunk_size.to_le_bytes());
+    for (_, cmd) in plan {
+        hasher.update(serde_json::to_vec(cmd)?);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn read_state(path: &std::path::Path) -> Result<Option<ApplyState>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_reader(std::fs::File::open(path)?)?))
+}
+
+fn write_state(path: &std::path::Path, state: &ApplyState) -> Result<()> {
+    std::fs::write(path, serde_json::to_vec(state)?)?;
+    Ok(())
+}
+
 /// Parses all objects found by recursively walking the provided path. Resolves the objects and
-/// yields them in order of their apply_precedence, see [ResolvedConfigObject] for details.
+/// returns them in order of their apply_precedence, see [ResolvedConfigObject] for details,
+/// along with the number of files and objects which could not be parsed or resolved.
 fn parse_directory(
     path: std::path::PathBuf,
-) -> Result<impl Iterator<Item = (String, ResolvedConfigCommand)>> {
-    let mut resolved_cmds = walk_directory(path)
-        .filter_map(|filepath| match try_read_as_config_command(&filepath) {
-            Ok(cmds) => Some(
-                iter::repeat(filepath.to_string_lossy().to_string())
-                    .zip(cmds)
-                    .collect_vec(),
-            ),
+) -> Result<(Vec<(String, ResolvedConfigCommand)>, usize)> {
+    let mut failed = 0;
+    let mut resolved_cmds = vec![];
+    for filepath in walk_directory(path) {
+        let cmds = match try_read_as_config_command(&filepath) {
+            Ok(cmds) => cmds,
             Err(e) => {
                 println!(
                     "Unable to parse file at {} with error {e}",
                     filepath.to_string_lossy()
                 );
-                None
+                failed += 1;
+                continue;
             }
-        })
-        .flatten()
-        .filter_map(|(filepath, cmd)| match resolve_command(cmd) {
-            Ok(resolved) => Some((filepath, resolved)),
-            Err(e) => {
-                println!(
-                    "Unable to resolve config object {} with error {e}",
-                    filepath
-                );
-                None
+        };
+        let filepath = filepath.to_string_lossy().to_string();
+        for cmd in cmds {
+            match resolve_command(cmd) {
+                Ok(resolved) => resolved_cmds.push((filepath.clone(), resolved)),
+                Err(e) => {
+                    println!(
+                        "Unable to resolve config object {} with error {e}",
+                        filepath
+                    );
+                    failed += 1;
+                }
             }
-        })
-        .collect::<Vec<_>>();
+        }
+    }
     resolved_cmds.sort_by_key(|(_, cmd)| cmd.config_object.apply_precedence());
-    Ok(resolved_cmds.into_iter())
+    Ok((resolved_cmds, failed))
 }
 
 fn resolve_command(command: ConfigCommand) -> Result<ResolvedConfigCommand> {
@@ -74,6 +449,9 @@ fn resolve_command(command: ConfigCommand) -> Result<ResolvedConfigCommand> {
             ResolvedConfigObject::PeerRelay(resolve_relay_decl(relay)?)
         }
         ConfigObject::User(user) => ResolvedConfigObject::User(resolve_user_decl(user)?),
+        ConfigObject::ServiceAccount(account) => {
+            ResolvedConfigObject::ServiceAccount(resolve_service_account_decl(account)?)
+        }
         ConfigObject::LocalData(local_data) => ResolvedConfigObject::LocalData(local_data),
         ConfigObject::LocalMapping(local_mapping) => {
             ResolvedConfigObject::LocalMapping(local_mapping)
@@ -81,6 +459,9 @@ fn resolve_command(command: ConfigCommand) -> Result<ResolvedConfigCommand> {
         ConfigObject::RemoteMapping(remote_mapping) => {
             ResolvedConfigObject::RemoteMapping(remote_mapping)
         }
+        ConfigObject::ValidationRules(rules) => ResolvedConfigObject::ValidationRules(rules),
+        ConfigObject::Quota(quota) => ResolvedConfigObject::Quota(resolve_quota_decl(quota)?),
+        ConfigObject::Role(role) => ResolvedConfigObject::Role(resolve_role_decl(role)?),
     };
 
     Ok(ResolvedConfigCommand {
@@ -89,18 +470,201 @@ fn resolve_command(command: ConfigCommand) -> Result<ResolvedConfigCommand> {
     })
 }
 
-pub async fn apply_command(
-    command: ResolvedConfigCommand,
+/// Sends the commands to the relay as a single apply request and returns the report of every
+/// object. The report is returned even if objects failed to apply.
+pub async fn apply_commands(
+    commands: &[ResolvedConfigCommand],
     client: &mut Client,
     relay_endpoint: &str,
-) -> Result<()> {
+    partial: bool,
+) -> Result<ApplyReport> {
     let r = client
         .post(format!("{relay_endpoint}/admin/apply"))
-        .json(&command)
+        .query(&[("partial", partial)])
+        .json(commands)
+        .send()
+        .await
+        .map_err(|e| MeshError::RemoteError(e.to_string()))?;
+
+    if r.status() == StatusCode::UNPROCESSABLE_ENTITY {
+        return r
+            .json()
+            .await
+            .map_err(|e| MeshError::RemoteError(format!("Failed to parse response with e {e}")));
+    }
+    parse_response(r).await
+}
+
+/// Pauses or resumes dispatch of new queries to a DataSource on the relay.
+pub(crate) async fn set_source_paused(
+    client: &Client,
+    relay_endpoint: &str,
+    connection: &str,
+    source: &str,
+    paused: bool,
+) -> Result<()> {
+    let action = if paused { "pause" } else { "resume" };
+    let r = client
+        .post(format!(
+            "{relay_endpoint}/admin/data/{connection}/{source}/{action}"
+        ))
+        .send()
+        .await
+        .map_err(|e| MeshError::RemoteError(e.to_string()))?;
+
+    check_response(r).await?;
+    Ok(())
+}
+
+/// Asks the relay to connect to every DataSource declared by the LocalData objects found under
+/// path and run a trivial probe query, printing the latency or error of each. The objects need
+/// not be applied yet.
+pub(crate) async fn test_connections(
+    path: std::path::PathBuf,
+    client: &Client,
+    relay_endpoint: &str,
+    timeout_secs: Option<u64>,
+) -> Result<()> {
+    let (cmds, _) = parse_directory(path)?;
+    let mut failed = 0;
+    for (filepath, cmd) in cmds {
+        let ResolvedConfigObject::LocalData(con) = cmd.config_object else {
+            continue;
+        };
+        if con.data_sources.is_empty() {
+            println!(
+                "{filepath} {} declares no DataSources to test the connection with",
+                con.name
+            );
+        }
+        for source in &con.data_sources {
+            let r = client
+                .post(format!("{relay_endpoint}/admin/test-connection"))
+                .json(&serde_json::json!({
+                    "connection_options": con.connection_options,
+                    "source_options": source.source_options,
+                    "timeout_secs": timeout_secs,
+                }))
+                .send()
+                .await
+                .map_err(|e| MeshError::RemoteError(e.to_string()))?;
+            let result: ConnectionTest = parse_response(r).await?;
+            match result.error {
+                None => println!(
+                    "{}/{} connected in {} ms",
+                    con.name, source.name, result.latency_ms
+                ),
+                Some(e) => {
+                    println!(
+                        "{}/{} failed after {} ms with error {e}",
+                        con.name, source.name, result.latency_ms
+                    );
+                    failed += 1;
+                }
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(MeshError::RemoteError(format!(
+            "{failed} DataSources could not be connected to!"
+        )));
+    }
+    Ok(())
+}
+
+/// Drafts the DataSources declaration of the tables served by a DataConnection of the relay and
+/// prints it as YAML, to be reviewed and added to the data_sources of its LocalData config file.
+/// The source_options of the files to inspect are read from source_options_file, which is
+/// required for FileDirectory connections.
+pub(crate) async fn discover(
+    client: &Client,
+    relay_endpoint: &str,
+    connection: &str,
+    schema: Option<String>,
+    name: Option<String>,
+    source_options_file: Option<std::path::PathBuf>,
+) -> Result<()> {
+    let source_options = match source_options_file {
+        Some(path) => {
+            let reader = std::io::BufReader::new(std::fs::File::open(&path)?);
+            let options: SourceOptions = serde_yaml::with::singleton_map_recursive::deserialize(
+                serde_yaml::Deserializer::from_reader(reader),
+            )
+            .map_err(|e| {
+                MeshError::InvalidQuery(format!(
+                    "Unable to parse source options {}: {e}",
+                    path.to_string_lossy()
+                ))
+            })?;
+            Some(options)
+        }
+        None => None,
+    };
+    let r = client
+        .post(format!("{relay_endpoint}/admin/data/{connection}/discover"))
+        .json(&DiscoverOptions {
+            schema,
+            source_options,
+            name,
+        })
+        .send()
+        .await
+        .map_err(|e| MeshError::RemoteError(e.to_string()))?;
+    let sources: Vec<DataSourcesDeclaration> = parse_response(r).await?;
+
+    let mut serializer = serde_yaml::Serializer::new(std::io::stdout());
+    serde_yaml::with::singleton_map_recursive::serialize(&sources, &mut serializer)
+        .map_err(|e| MeshError::Internal(format!("Unable to serialize sources: {e}")))?;
+    eprintln!("Discovered {} sources of {connection}", sources.len());
+    Ok(())
+}
+
+/// Issues an invite for a peer Relay, returning the invite token.
+pub(crate) async fn create_invite(
+    client: &Client,
+    relay_endpoint: &str,
+    peer_name: &str,
+    ttl_secs: Option<i64>,
+) -> Result<String> {
+    let r = client
+        .post(format!("{relay_endpoint}/admin/invites"))
+        .json(&serde_json::json!({"peer_name": peer_name, "ttl_secs": ttl_secs}))
+        .send()
+        .await
+        .map_err(|e| MeshError::RemoteError(e.to_string()))?;
+    let invite: IssuedInvite = parse_response(r).await?;
+    Ok(invite.token)
+}
+
+/// Redeems an invite issued by another Relay, returning the name the issuing Relay is
+/// registered under.
+pub(crate) async fn redeem_invite(
+    client: &Client,
+    relay_endpoint: &str,
+    token: &str,
+    name: Option<String>,
+) -> Result<String> {
+    let r = client
+        .post(format!("{relay_endpoint}/admin/invites/redeem"))
+        .json(&serde_json::json!({"token": token, "name": name}))
         .send()
         .await
         .map_err(|e| MeshError::RemoteError(e.to_string()))?;
+    let relay: Relay = parse_response(r).await?;
+    Ok(relay.name)
+}
+
+/// Checks the [Response][reqwest::Response] with [check_response] and parses its JSON body.
+async fn parse_response<T: serde::de::DeserializeOwned>(r: reqwest::Response) -> Result<T> {
+    let r = check_response(r).await?;
+    r.json()
+        .await
+        .map_err(|e| MeshError::RemoteError(format!("Failed to parse response with e {e}")))
+}
 
+/// Converts a non 200 [Response][reqwest::Response] into a [MeshError::RemoteError] with the
+/// response text as the message.
+async fn check_response(r: reqwest::Response) -> Result<reqwest::Response> {
     if !matches!(r.status(), StatusCode::OK) {
         let msg = match r.text().await {
             Ok(txt) => {
@@ -112,7 +676,7 @@ pub async fn apply_command(
         };
         return Err(MeshError::RemoteError(msg));
     }
-    Ok(())
+    Ok(r)
 }
 
 fn try_read_as_config_command(
@@ -166,12 +730,15 @@ fn resolve_entity_decl(entity: EntityDeclaration) -> Result<ResolvedEntityDeclar
         resolved_info.push(ResolvedInformationDeclaration {
             name: info_decl.name.clone(),
             arrow_dtype,
+            nullable: info_decl.nullable,
         })
     }
 
     Ok(ResolvedEntityDeclaration {
         name: entity.name,
         information: resolved_info,
+        validation_query: entity.validation_query,
+        aliases: entity.aliases,
     })
 }
 
@@ -186,6 +753,52 @@ fn resolve_user_decl(user: UserDeclaration) -> Result<ResolvedUserDeclaration> {
     })
 }
 
+fn resolve_service_account_decl(
+    account: ServiceAccountDeclaration,
+) -> Result<ResolvedServiceAccountDeclaration> {
+    let cert_path = &account.x509_cert_file;
+    let mut buf = Vec::new();
+    std::fs::File::open(cert_path)?.read_to_end(&mut buf)?;
+    Ok(ResolvedServiceAccountDeclaration {
+        x509_cert: buf,
+        scope: account.scope,
+        misc: account.misc,
+        permissions: account.permissions,
+    })
+}
+
+fn resolve_quota_decl(quota: QuotaDeclaration) -> Result<ResolvedQuotaDeclaration> {
+    let user_x509_cert = match &quota.user_x509_cert_file {
+        Some(cert_path) => {
+            let mut buf = Vec::new();
+            std::fs::File::open(cert_path)?.read_to_end(&mut buf)?;
+            Some(buf)
+        }
+        None => None,
+    };
+    Ok(ResolvedQuotaDeclaration {
+        user_x509_cert,
+        relay: quota.relay,
+        max_concurrent_requests: quota.max_concurrent_requests,
+        max_requests_per_minute: quota.max_requests_per_minute,
+    })
+}
+
+fn resolve_role_decl(role: RoleDeclaration) -> Result<ResolvedRoleDeclaration> {
+    let mut user_x509_certs = Vec::with_capacity(role.user_x509_cert_files.len());
+    for cert_path in &role.user_x509_cert_files {
+        let mut buf = Vec::new();
+        std::fs::File::open(cert_path)?.read_to_end(&mut buf)?;
+        user_x509_certs.push(buf);
+    }
+    Ok(ResolvedRoleDeclaration {
+        name: role.name,
+        user_x509_certs,
+        relays: role.relays,
+        permissions: role.permissions,
+    })
+}
+
 fn resolve_relay_decl(relay: PeerRelayDeclaration) -> Result<ResolvedPeerRelayDeclaration> {
     let cert_path = &relay.x509_cert_file;
     let mut buf = Vec::new();
@@ -0,0 +1,388 @@
+use std::collections::{HashMap, HashSet};
+
+use mesh::crud::PgDb;
+use mesh::error::{MeshError, Result};
+use mesh::model::config_commands::ResolvedConfigObject;
+
+use crate::admin::utils::{declared_quota_subject, parse_declared_certificate};
+
+/// Identifies the user making a delete or prune request and the local relay, which are never
+/// deleted.
+pub struct DeleteGuard<'a> {
+    pub admin_fingerprint: &'a str,
+    pub local_fingerprint: &'a str,
+}
+
+/// Deletes the objects declared by config_obj, along with everything which depends on them, and
+/// returns a description of each deleted object. A LocalData object deletes the declared data
+/// sources of the connection, and the connection once it has no sources left.
+pub async fn delete_config_obj(
+    db: &mut PgDb<'_>,
+    config_obj: ResolvedConfigObject,
+    guard: &DeleteGuard<'_>,
+) -> Result<Vec<String>> {
+    let mut deleted = vec![];
+    match config_obj {
+        ResolvedConfigObject::Entity(entity_decl) => {
+            if db.delete_entity(&entity_decl.name).await? > 0 {
+                deleted.push(format!("Entity {}", entity_decl.name));
+            }
+        }
+        ResolvedConfigObject::LocalData(data_decl) => {
+            let data_con = db.get_connection(&data_decl.name).await?;
+            for source_decl in data_decl.data_sources {
+                if db.delete_source(&data_con.id, &source_decl.name).await? > 0 {
+                    deleted.push(format!("DataSource {}/{}", data_con.name, source_decl.name));
+                }
+            }
+            if db.delete_connection_if_empty(&data_con.id).await? > 0 {
+                deleted.push(format!("DataConnection {}", data_con.name));
+            }
+        }
+        ResolvedConfigObject::LocalMapping(map_decl) => {
+            let entity = db.get_entity(&map_decl.entity_name).await?;
+            let information = db
+                .get_information_for_entity(entity.id)
+                .await?
+                .into_iter()
+                .map(|i| (i.name, i.id))
+                .collect::<HashMap<_, _>>();
+            for data_con_map_decl in map_decl.mappings {
+                let data_con = db.get_connection(&data_con_map_decl.data_con_name).await?;
+                for source_map_decl in data_con_map_decl.source_mappings {
+                    let source = db
+                        .get_source(&source_map_decl.data_source_name, &data_con.id)
+                        .await?;
+                    let fields = db
+                        .get_fields_for_source(&source.id)
+                        .await?
+                        .into_iter()
+                        .map(|f| (f.name, f.id))
+                        .collect::<HashMap<_, _>>();
+                    for field_map_decl in source_map_decl.field_mappings {
+                        let (Some(info_id), Some(field_id)) = (
+                            information.get(&field_map_decl.info),
+                            fields.get(&field_map_decl.field),
+                        ) else {
+                            continue;
+                        };
+                        if db.delete_local_mapping(info_id, field_id).await? > 0 {
+                            deleted.push(format!(
+                                "LocalMapping {}.{} to {}/{}.{}",
+                                entity.name,
+                                field_map_decl.info,
+                                data_con.name,
+                                source.name,
+                                field_map_decl.field
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        ResolvedConfigObject::PeerRelay(relay_decl) => {
+            let relay = db.get_relay_by_name(&relay_decl.name).await?;
+            if relay.x509_sha256 == guard.local_fingerprint {
+                return Err(MeshError::InvalidQuery(format!(
+                    "Relay {} is the local relay and can not be deleted!",
+                    relay.name
+                )));
+            }
+            if db.delete_relay(&relay.name).await? > 0 {
+                deleted.push(format!("PeerRelay {}", relay.name));
+            }
+        }
+        ResolvedConfigObject::RemoteMapping(remote_map_decl) => {
+            let entity = db.get_entity(&remote_map_decl.entity_name).await?;
+            for peer_map in remote_map_decl.mappings {
+                let relay = db.get_relay_by_name(&peer_map.relay_name).await?;
+                if db
+                    .delete_remote_entity_mapping(
+                        &relay.id,
+                        &entity.id,
+                        &peer_map.remote_entity_name,
+                    )
+                    .await?
+                    > 0
+                {
+                    deleted.push(format!(
+                        "RemoteMapping {} to {}/{}",
+                        entity.name, relay.name, peer_map.remote_entity_name
+                    ));
+                }
+            }
+        }
+        ResolvedConfigObject::User(user_decl) => {
+            deleted.extend(delete_user(db, &user_decl.x509_cert, guard).await?);
+        }
+        ResolvedConfigObject::ServiceAccount(account_decl) => {
+            deleted.extend(delete_user(db, &account_decl.x509_cert, guard).await?);
+        }
+        ResolvedConfigObject::ValidationRules(rules_decl) => {
+            if db.delete_validation_rule_set(&rules_decl.name).await? > 0 {
+                deleted.push(format!("ValidationRules {}", rules_decl.name));
+            }
+        }
+        ResolvedConfigObject::Quota(quota_decl) => {
+            let (subject, x509_sha256) = declared_quota_subject(db, &quota_decl).await?;
+            if db.delete_quota(subject, &x509_sha256).await? > 0 {
+                deleted.push(format!("Quota {subject:?} {x509_sha256}"));
+            }
+        }
+        ResolvedConfigObject::Role(role_decl) => {
+            if db.delete_role(&role_decl.name).await? > 0 {
+                deleted.push(format!("Role {}", role_decl.name));
+            }
+        }
+    }
+    Ok(deleted)
+}
+
+async fn delete_user(
+    db: &mut PgDb<'_>,
+    x509_cert: &[u8],
+    guard: &DeleteGuard<'_>,
+) -> Result<Option<String>> {
+    let (fingerprint, subject, _) = parse_declared_certificate(x509_cert)?;
+    if fingerprint == guard.admin_fingerprint {
+        return Err(MeshError::InvalidQuery(
+            "Refusing to delete the user making the request!".to_string(),
+        ));
+    }
+    Ok((db.delete_user(&fingerprint).await? > 0).then(|| format!("User {subject}")))
+}
+
+/// Names and fingerprints of every object declared in a complete configuration.
+#[derive(Default)]
+struct Declared {
+    entities: HashSet<String>,
+    /// (connection, source)
+    sources: HashSet<(String, String)>,
+    connections: HashSet<String>,
+    /// (entity, information, connection, source, field)
+    local_mappings: HashSet<(String, String, String, String, String)>,
+    relays: HashSet<String>,
+    /// (entity, relay, remote entity)
+    remote_mappings: HashSet<(String, String, String)>,
+    users: HashSet<String>,
+    validation_rules: HashSet<String>,
+    quotas: HashSet<String>,
+    roles: HashSet<String>,
+}
+
+impl Declared {
+    async fn collect(db: &mut PgDb<'_>, declared: &[ResolvedConfigObject]) -> Result<Self> {
+        let mut out = Declared::default();
+        for config_obj in declared {
+            match config_obj {
+                ResolvedConfigObject::Entity(entity_decl) => {
+                    out.entities.insert(entity_decl.name.clone());
+                }
+                ResolvedConfigObject::LocalData(data_decl) => {
+                    out.connections.insert(data_decl.name.clone());
+                    for source_decl in &data_decl.data_sources {
+                        out.sources
+                            .insert((data_decl.name.clone(), source_decl.name.clone()));
+                    }
+                }
+                ResolvedConfigObject::LocalMapping(map_decl) => {
+                    for con in &map_decl.mappings {
+                        for source in &con.source_mappings {
+                            for field in &source.field_mappings {
+                                out.local_mappings.insert((
+                                    map_decl.entity_name.clone(),
+                                    field.info.clone(),
+                                    con.data_con_name.clone(),
+                                    source.data_source_name.clone(),
+                                    field.field.clone(),
+                                ));
+                            }
+                        }
+                    }
+                }
+                ResolvedConfigObject::PeerRelay(relay_decl) => {
+                    out.relays.insert(relay_decl.name.clone());
+                }
+                ResolvedConfigObject::RemoteMapping(remote_map_decl) => {
+                    for peer_map in &remote_map_decl.mappings {
+                        out.remote_mappings.insert((
+                            remote_map_decl.entity_name.clone(),
+                            peer_map.relay_name.clone(),
+                            peer_map.remote_entity_name.clone(),
+                        ));
+                    }
+                }
+                ResolvedConfigObject::User(user_decl) => {
+                    out.users
+                        .insert(parse_declared_certificate(&user_decl.x509_cert)?.0);
+                }
+                ResolvedConfigObject::ServiceAccount(account_decl) => {
+                    out.users
+                        .insert(parse_declared_certificate(&account_decl.x509_cert)?.0);
+                }
+                ResolvedConfigObject::ValidationRules(rules_decl) => {
+                    out.validation_rules.insert(rules_decl.name.clone());
+                }
+                ResolvedConfigObject::Quota(quota_decl) => {
+                    let (subject, x509_sha256) = declared_quota_subject(db, quota_decl).await?;
+                    out.quotas.insert(format!("{subject:?} {x509_sha256}"));
+                }
+                ResolvedConfigObject::Role(role_decl) => {
+                    out.roles.insert(role_decl.name.clone());
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Deletes every object on the relay which is not declared in the complete configuration
+/// declared, returning a description of each deleted object. The local relay and the user
+/// making the request are never deleted.
+pub async fn prune_config(
+    db: &mut PgDb<'_>,
+    declared: &[ResolvedConfigObject],
+    guard: &DeleteGuard<'_>,
+) -> Result<Vec<String>> {
+    let declared = Declared::collect(db, declared).await?;
+    let mut deleted = vec![];
+
+    // Objects are deleted in reverse order of apply precedence, so that objects which depend on
+    // another are deleted, and reported, before it.
+    for role in db.get_roles().await? {
+        if !declared.roles.contains(&role.name) && db.delete_role(&role.name).await? > 0 {
+            deleted.push(format!("Role {}", role.name));
+        }
+    }
+
+    for quota in db.get_quotas().await? {
+        let key = format!("{:?} {}", quota.subject, quota.x509_sha256);
+        if !declared.quotas.contains(&key)
+            && db.delete_quota(quota.subject, &quota.x509_sha256).await? > 0
+        {
+            deleted.push(format!("Quota {key}"));
+        }
+    }
+
+    for rule_set in db.get_validation_rule_sets().await? {
+        if !declared.validation_rules.contains(&rule_set.name)
+            && db.delete_validation_rule_set(&rule_set.name).await? > 0
+        {
+            deleted.push(format!("ValidationRules {}", rule_set.name));
+        }
+    }
+
+    for user in db.get_users().await? {
+        if !declared.users.contains(&user.x509_sha256)
+            && user.x509_sha256 != guard.admin_fingerprint
+            && db.delete_user(&user.x509_sha256).await? > 0
+        {
+            deleted.push(format!("User {}", user.x509_subject));
+        }
+    }
+
+    let entity_names = db
+        .get_entities()
+        .await?
+        .into_iter()
+        .map(|e| e.name)
+        .collect::<Vec<_>>();
+    let entity_names = entity_names.iter().map(|n| n.as_str()).collect::<Vec<_>>();
+
+    for (relay, mappings) in db
+        .get_remote_mappings_by_entity_names(entity_names.clone())
+        .await?
+    {
+        let mut entity_maps = HashMap::new();
+        for (entity, _, entity_map, _) in mappings {
+            entity_maps.insert(entity_map.id, (entity, entity_map));
+        }
+        for (entity, entity_map) in entity_maps.into_values() {
+            let key = (
+                entity.name.clone(),
+                relay.name.clone(),
+                entity_map.remote_entity_name.clone(),
+            );
+            if !declared.remote_mappings.contains(&key)
+                && db
+                    .delete_remote_entity_mapping(
+                        &relay.id,
+                        &entity.id,
+                        &entity_map.remote_entity_name,
+                    )
+                    .await?
+                    > 0
+            {
+                deleted.push(format!(
+                    "RemoteMapping {} to {}/{}",
+                    entity.name, relay.name, entity_map.remote_entity_name
+                ));
+            }
+        }
+    }
+
+    for relay in db.get_relays().await? {
+        if !declared.relays.contains(&relay.name)
+            && relay.x509_sha256 != guard.local_fingerprint
+            && db.delete_relay(&relay.name).await? > 0
+        {
+            deleted.push(format!("PeerRelay {}", relay.name));
+        }
+    }
+
+    for ((con, source), mappings) in db.get_mappings_by_entity_names(entity_names).await? {
+        for (entity, info, field, _) in mappings {
+            let key = (
+                entity.name.clone(),
+                info.name.clone(),
+                con.name.clone(),
+                source.name.clone(),
+                field.name.clone(),
+            );
+            if !declared.local_mappings.contains(&key)
+                && db.delete_local_mapping(&info.id, &field.id).await? > 0
+            {
+                deleted.push(format!(
+                    "LocalMapping {}.{} to {}/{}.{}",
+                    entity.name, info.name, con.name, source.name, field.name
+                ));
+            }
+        }
+    }
+
+    let connections = db.get_connections().await?;
+    let connection_names = connections
+        .iter()
+        .map(|c| (c.id, c.name.clone()))
+        .collect::<HashMap<_, _>>();
+    for source in db.get_sources().await? {
+        let Some(con_name) = connection_names.get(&source.data_connection_id) else {
+            continue;
+        };
+        if !declared
+            .sources
+            .contains(&(con_name.clone(), source.name.clone()))
+            && db
+                .delete_source(&source.data_connection_id, &source.name)
+                .await?
+                > 0
+        {
+            deleted.push(format!("DataSource {con_name}/{}", source.name));
+        }
+    }
+    for con in connections {
+        if !declared.connections.contains(&con.name)
+            && db.delete_connection_if_empty(&con.id).await? > 0
+        {
+            deleted.push(format!("DataConnection {}", con.name));
+        }
+    }
+
+    for entity in db.get_entities().await? {
+        if !declared.entities.contains(&entity.name) && db.delete_entity(&entity.name).await? > 0 {
+            deleted.push(format!("Entity {}", entity.name));
+        }
+    }
+
+    Ok(deleted)
+}
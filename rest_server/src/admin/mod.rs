@@ -1,2 +1,3 @@
+pub mod delete;
 pub mod route;
 pub mod utils;
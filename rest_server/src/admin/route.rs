@@ -1,58 +1,936 @@
 use std::sync::Arc;
 
+use crate::admin::delete::{delete_config_obj, prune_config, DeleteGuard};
 use crate::admin::utils::process_config_obj;
 use crate::error::{RelayError, Result};
-use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Duration, Utc};
 use mesh::crud::PgDb;
+use mesh::error::MeshError;
+use mesh::execute::discover::{discover_sources, DiscoverOptions};
+use mesh::execute::identity::identity_cache;
+use mesh::execute::inspect::{
+    config_snapshot, declared_data, declared_entities, declared_mappings, declared_relays,
+    declared_users, diff_config, export_config, ConfigChange,
+};
+use mesh::execute::invite::{issue_invite, redeem_invite};
+use mesh::execute::outbox::publish_outbox;
+use mesh::execute::relay_status::relay_status_report;
+use mesh::execute::replay::replay_request;
+use mesh::execute::result_manager::ResultManager;
+use mesh::messaging::{initialize_producer, MessageBrokerOptions};
+use mesh::model::audit::{AuditAction, AuditFilter, NewAuditEntry};
+use mesh::model::config_commands::report::{ApplyReport, ApplyStatus, ObjectReport};
 use mesh::model::config_commands::ResolvedConfigCommand;
-use tracing::info;
+use mesh::model::data_stores::options::{ConnectionOptions, SourceOptions};
+use mesh::model::query::{QueryRequest, QueryTask, QueryTaskStatus};
+use mesh::model::user::User;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
 
+use crate::query::utils::{stream_all_task_results, ResponseFormat};
 use crate::utils::parse_certs_from_req;
 use crate::DbPool;
 
-#[post("/admin/apply")]
-async fn apply(
-    pool: web::Data<DbPool>,
-    _local_fingerprint: web::Data<Arc<String>>,
-    client_cert_header: web::Data<Option<String>>,
-    config_obj: web::Json<ResolvedConfigCommand>,
+/// Verifies that the client identified by the certificate in [HttpRequest] is a registered
+/// user with the is_admin attribute set, returning the user.
+async fn authorize_admin(
+    db: &mut PgDb<'_>,
     req: HttpRequest,
-) -> Result<impl Responder> {
-    let (fingerprint, subject_dn, issuer_dn) =
-        parse_certs_from_req(req, client_cert_header.as_ref())?;
+    client_cert_header: &Option<String>,
+) -> Result<User> {
+    let (fingerprint, subject_dn, issuer_dn) = parse_certs_from_req(req, client_cert_header)?;
 
     info!(
-        "Got new query request from: subject: {}, issuer: {}, fingerprint: {}",
+        "Got new admin request from: subject: {}, issuer: {}, fingerprint: {}",
         subject_dn, issuer_dn, fingerprint
     );
 
-    let mut db = PgDb::try_from_pool(&pool).await?;
-    let maybe_user = db.get_user_by_x509_fingerprint(&fingerprint).await;
+    let maybe_user = identity_cache().get_user(db, &fingerprint).await;
     let authorized = if let Ok(user) = maybe_user {
         if user.attributes.is_admin {
-            true
+            Some(user)
         } else {
             info!(
                 "User {}, lacks is_admin: true attribute, denying access to /admin.",
                 fingerprint
             );
-            false
+            None
         }
     } else {
         info!(
             "User {}, not registered, denying access to /admin.",
             fingerprint
         );
-        false
+        None
+    };
+
+    authorized.ok_or_else(|| RelayError::new("User is unauthorized for adminstrative actions!"))
+}
+
+#[derive(Deserialize)]
+struct ApplyOptions {
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    prune: bool,
+    #[serde(default)]
+    partial: bool,
+}
+
+/// Applies a config command, or a list of config commands in order, in a single transaction and
+/// returns an [ApplyReport]. If any object fails to apply, nothing is committed, unless
+/// partial=true, which commits the objects which applied. The report is returned with status 422
+/// if any object failed.
+///
+/// With prune=true, the list must be the complete configuration of the relay and every object it
+/// does not declare is deleted once every object applied. With dry_run=true, nothing is committed
+/// and the objects which would be created, updated and deleted are returned instead.
+#[post("/admin/apply")]
+async fn apply(
+    pool: web::Data<DbPool>,
+    local_fingerprint: web::Data<Arc<String>>,
+    client_cert_header: web::Data<Option<String>>,
+    options: web::Query<ApplyOptions>,
+    config_objs: web::Json<serde_json::Value>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    let admin = authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let body = config_objs.into_inner();
+    let guard = DeleteGuard {
+        admin_fingerprint: &admin.x509_sha256,
+        local_fingerprint: local_fingerprint.as_str(),
+    };
+    let prune_guard = options.prune.then_some(&guard);
+
+    if options.dry_run {
+        db.begin().await?;
+        let diff = diff_apply(&mut db, &body, prune_guard, options.partial).await;
+        db.rollback().await?;
+        let (report, changes) = diff?;
+        if report.failed() > 0 {
+            return Ok(HttpResponse::UnprocessableEntity().json(report));
+        }
+        return Ok(HttpResponse::Ok().json(changes));
+    }
+
+    let (report, pruned) = apply_commands(&mut db, &body, prune_guard, options.partial).await?;
+    if !report.committed {
+        info!("Rolled back apply, {} objects failed", report.failed());
+        return Ok(HttpResponse::UnprocessableEntity().json(report));
+    }
+    // Users and relays may have been changed, e.g. revoking is_admin
+    identity_cache().clear();
+
+    db.record_audit_entry(&NewAuditEntry {
+        action: AuditAction::Apply,
+        actor_sha256: admin.x509_sha256.clone(),
+        actor_subject: admin.x509_subject.clone(),
+        query_request_id: None,
+        sql: None,
+        detail: body,
+    })
+    .await?;
+    if !pruned.is_empty() {
+        info!("Pruned {pruned:?}");
+        db.record_audit_entry(&NewAuditEntry {
+            action: AuditAction::Delete,
+            actor_sha256: admin.x509_sha256,
+            actor_subject: admin.x509_subject,
+            query_request_id: None,
+            sql: None,
+            detail: serde_json::json!({ "pruned": pruned }),
+        })
+        .await?;
+    }
+
+    if report.failed() > 0 {
+        return Ok(HttpResponse::UnprocessableEntity().json(report));
+    }
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Parses the body of an apply, which is either a single config command or a list of them.
+fn parse_config_commands(body: &serde_json::Value) -> Result<Vec<ResolvedConfigCommand>> {
+    let commands = match body {
+        serde_json::Value::Array(_) => Vec::<ResolvedConfigCommand>::deserialize(body),
+        _ => ResolvedConfigCommand::deserialize(body).map(|c| vec![c]),
+    };
+    Ok(commands.map_err(MeshError::from)?)
+}
+
+/// Applies the config commands in body in a transaction, each in a savepoint of its own, and with
+/// a [DeleteGuard] prunes every object they do not declare. Returns the report of every object
+/// and a description of each pruned object.
+///
+/// Once an object fails, the transaction is rolled back and the remaining objects are skipped,
+/// unless partial is set, in which case only the failed object is rolled back. Nothing is pruned
+/// unless every object applied.
+async fn apply_commands(
+    db: &mut PgDb<'_>,
+    body: &serde_json::Value,
+    prune_guard: Option<&DeleteGuard<'_>>,
+    partial: bool,
+) -> Result<(ApplyReport, Vec<String>)> {
+    let commands = parse_config_commands(body)?;
+    db.begin().await?;
+    let mut report = ApplyReport {
+        committed: false,
+        objects: Vec::with_capacity(commands.len()),
+    };
+    for command in commands {
+        let object = command.config_object.description();
+        if !partial && report.failed() > 0 {
+            report.objects.push(ObjectReport {
+                object,
+                status: ApplyStatus::Skipped,
+                error: None,
+            });
+            continue;
+        }
+        db.begin().await?;
+        match process_config_obj(db, command.config_object).await {
+            Ok(()) => {
+                db.commit().await?;
+                report.objects.push(ObjectReport {
+                    object,
+                    status: ApplyStatus::Applied,
+                    error: None,
+                });
+            }
+            Err(e) => {
+                db.rollback().await?;
+                error!("Failed to apply {object} with error {e}");
+                report.objects.push(ObjectReport {
+                    object,
+                    status: ApplyStatus::Failed,
+                    error: Some(e.to_string()),
+                });
+            }
+        }
+    }
+
+    let failed = report.failed() > 0;
+    if failed && !partial {
+        db.rollback().await?;
+        for object in report.objects.iter_mut() {
+            if object.status == ApplyStatus::Applied {
+                object.status = ApplyStatus::RolledBack;
+            }
+        }
+        return Ok((report, vec![]));
+    }
+
+    let pruned = match prune_guard {
+        Some(guard) if !failed => {
+            // Parsed again, as applying consumes the commands
+            let declared = parse_config_commands(body)?
+                .into_iter()
+                .map(|c| c.config_object)
+                .collect::<Vec<_>>();
+            match prune_config(db, &declared, guard).await {
+                Ok(pruned) => pruned,
+                Err(e) => {
+                    db.rollback().await?;
+                    return Err(e.into());
+                }
+            }
+        }
+        _ => vec![],
+    };
+    db.commit().await?;
+    report.committed = true;
+    Ok((report, pruned))
+}
+
+/// Applies the config commands in body as [apply_commands] does and returns its report along
+/// with the resulting changes to the configuration. Must run in a transaction which is rolled
+/// back afterwards.
+async fn diff_apply(
+    db: &mut PgDb<'_>,
+    body: &serde_json::Value,
+    prune_guard: Option<&DeleteGuard<'_>>,
+    partial: bool,
+) -> Result<(ApplyReport, Vec<ConfigChange>)> {
+    let before = config_snapshot(db).await?;
+    let (report, _) = apply_commands(db, body, prune_guard, partial).await?;
+    let after = config_snapshot(db).await?;
+    Ok((report, diff_config(&before, &after)))
+}
+
+/// Deletes the objects declared by a config command, along with everything which depends on
+/// them, and returns a description of each deleted object.
+#[delete("/admin/apply")]
+async fn delete(
+    pool: web::Data<DbPool>,
+    local_fingerprint: web::Data<Arc<String>>,
+    client_cert_header: web::Data<Option<String>>,
+    config_obj: web::Json<ResolvedConfigCommand>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    let admin = authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let command = config_obj.into_inner();
+    let object = serde_json::to_value(&command).map_err(MeshError::from)?;
+    let guard = DeleteGuard {
+        admin_fingerprint: &admin.x509_sha256,
+        local_fingerprint: local_fingerprint.as_str(),
+    };
+    let deleted = delete_config_obj(&mut db, command.config_object, &guard).await?;
+    identity_cache().clear();
+    info!("Deleted {deleted:?}");
+
+    db.record_audit_entry(&NewAuditEntry {
+        action: AuditAction::Delete,
+        actor_sha256: admin.x509_sha256,
+        actor_subject: admin.x509_subject,
+        query_request_id: None,
+        sql: None,
+        detail: serde_json::json!({ "object": object, "deleted": deleted }),
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(deleted))
+}
+
+/// Deletes every object which is not declared by the given config commands, which must be the
+/// complete configuration of the relay, and returns a description of each deleted object.
+#[post("/admin/prune")]
+async fn prune(
+    pool: web::Data<DbPool>,
+    local_fingerprint: web::Data<Arc<String>>,
+    client_cert_header: web::Data<Option<String>>,
+    config_objs: web::Json<Vec<ResolvedConfigCommand>>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    let admin = authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let declared = config_objs
+        .into_inner()
+        .into_iter()
+        .map(|c| c.config_object)
+        .collect::<Vec<_>>();
+    let guard = DeleteGuard {
+        admin_fingerprint: &admin.x509_sha256,
+        local_fingerprint: local_fingerprint.as_str(),
+    };
+    let deleted = prune_config(&mut db, &declared, &guard).await?;
+    identity_cache().clear();
+    info!("Pruned {deleted:?}");
+
+    db.record_audit_entry(&NewAuditEntry {
+        action: AuditAction::Delete,
+        actor_sha256: admin.x509_sha256,
+        actor_subject: admin.x509_subject,
+        query_request_id: None,
+        sql: None,
+        detail: serde_json::json!({ "pruned": deleted }),
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(deleted))
+}
+
+/// Lists every Entity as the Entity config object which declares its current state.
+#[get("/admin/entities")]
+async fn list_entities(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(declared_entities(&mut db).await?))
+}
+
+/// Lists every DataConnection, with its DataSources, as the LocalData config object which
+/// declares its current state.
+#[get("/admin/data")]
+async fn list_data(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(declared_data(&mut db).await?))
+}
+
+/// Lists the mappings of every Entity to local DataSources and peer relays, as LocalMapping and
+/// RemoteMapping config objects.
+#[get("/admin/mappings")]
+async fn list_mappings(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(declared_mappings(&mut db).await?))
+}
+
+/// Lists every peer relay with its source permissions.
+#[get("/admin/relays")]
+async fn list_relays(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(declared_relays(&mut db).await?))
+}
+
+/// Lists every peer relay with the reachability of its endpoints, as last probed by a
+/// query_runner.
+#[get("/admin/relays/status")]
+async fn relay_status(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(relay_status_report(&mut db).await?))
+}
+
+/// Lists every user, including service accounts, with their attributes and source permissions.
+#[get("/admin/users")]
+async fn list_users(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(declared_users(&mut db).await?))
+}
+
+/// Exports the configuration of the relay as the list of config commands which restores it,
+/// along with the objects which were skipped since their certificate is not stored.
+#[get("/admin/export")]
+async fn export(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(export_config(&mut db).await?))
+}
+
+/// Connects to a DataConnection and drafts the DataSources declaration of the tables it serves,
+/// see [discover_sources].
+#[post("/admin/data/{connection_name}/discover")]
+async fn discover(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    path: web::Path<String>,
+    options: web::Json<DiscoverOptions>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let con = db.get_connection(&path.into_inner()).await?;
+    // Discovery may take a while, so the connection is returned to the pool first
+    drop(db);
+    Ok(HttpResponse::Ok().json(discover_sources(con, options.into_inner()).await?))
+}
+
+#[derive(Deserialize)]
+struct TestConnectionRequest {
+    connection_options: ConnectionOptions,
+    source_options: SourceOptions,
+    /// How long the probe may take, defaults to 30 seconds.
+    timeout_secs: Option<u64>,
+}
+
+/// Connects with the options of a DataConnection and DataSource, which need not be declared, and
+/// runs a trivial probe query, returning its latency and any error.
+#[post("/admin/test-connection")]
+async fn test_connection(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    request: web::Json<TestConnectionRequest>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+    drop(db);
+
+    let request = request.into_inner();
+    let timeout = std::time::Duration::from_secs(request.timeout_secs.unwrap_or(30));
+    let result = mesh::execute::data_stores::test_connection(
+        request.connection_options,
+        request.source_options,
+        timeout,
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// Pauses a DataSource so that new queries are no longer dispatched to it.
+#[post("/admin/data/{connection_name}/{source_name}/pause")]
+async fn pause_source(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let (connection_name, source_name) = path.into_inner();
+    let source = db
+        .set_source_paused(&connection_name, &source_name, true)
+        .await?;
+    info!("Paused source {source_name} on connection {connection_name}");
+
+    Ok(HttpResponse::Ok().json(source))
+}
+
+/// Resumes dispatching new queries to a previously paused DataSource.
+#[post("/admin/data/{connection_name}/{source_name}/resume")]
+async fn resume_source(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let (connection_name, source_name) = path.into_inner();
+    let source = db
+        .set_source_paused(&connection_name, &source_name, false)
+        .await?;
+    info!("Resumed source {source_name} on connection {connection_name}");
+
+    Ok(HttpResponse::Ok().json(source))
+}
+
+#[derive(Serialize, Debug)]
+struct ReplayTask {
+    id: Uuid,
+    data_source_id: Uuid,
+    status: QueryTaskStatus,
+    not_before: Option<DateTime<Utc>>,
+    /// The SQL sent to the data source, mapped with the current mappings and the original
+    /// permission.
+    sql: String,
+}
+
+#[derive(Serialize, Debug)]
+struct ReplayResponse {
+    replay_id: Uuid,
+    replay_of: Option<Uuid>,
+    sql: String,
+    tasks: Vec<ReplayTask>,
+}
+
+impl ReplayResponse {
+    fn new(request: QueryRequest, tasks: Vec<QueryTask>) -> Self {
+        Self {
+            replay_id: request.id,
+            replay_of: request.replay_of,
+            sql: request.sql,
+            tasks: tasks
+                .into_iter()
+                .map(|t| ReplayTask {
+                    id: t.id,
+                    data_source_id: t.data_source_id,
+                    status: t.status,
+                    not_before: t.not_before,
+                    sql: t.task.sql,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Replays an earlier query against the current mappings with the permissions recorded when it
+/// was executed. The results are kept apart from regular results, are never delivered to the
+/// user who submitted the query and are retrieved via [get_replay].
+#[post("/admin/query/{request_id}/replay")]
+async fn replay(
+    pool: web::Data<DbPool>,
+    message_options: web::Data<MessageBrokerOptions>,
+    client_cert_header: web::Data<Option<String>>,
+    request_id: web::Path<Uuid>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let request_id = request_id.into_inner();
+    let (replay, tasks) = replay_request(&mut db, request_id).await?;
+    info!(
+        "Replaying request {request_id} as {} with {} tasks",
+        replay.id,
+        tasks.len()
+    );
+
+    // As for regular queries, the outbox dispatcher publishes the tasks later if this fails.
+    let mut producer = initialize_producer(&message_options).await?;
+    if let Err(e) = publish_outbox(&mut db, producer.as_mut(), Utc::now()).await {
+        error!("Failed to publish task outbox with error {e}");
+    }
+
+    Ok(HttpResponse::Ok().json(ReplayResponse::new(replay, tasks)))
+}
+
+#[derive(Deserialize)]
+struct GetReplayOptions {
+    #[serde(default)]
+    results: bool,
+}
+
+/// Returns the status of the tasks of a replay or, with results=true, the results of its
+/// completed tasks as NDJSON.
+#[get("/admin/replay/{replay_id}")]
+async fn get_replay(
+    pool: web::Data<DbPool>,
+    result_manager: web::Data<Arc<ResultManager>>,
+    local_fingerprint: web::Data<Arc<String>>,
+    client_cert_header: web::Data<Option<String>>,
+    replay_id: web::Path<Uuid>,
+    options: web::Query<GetReplayOptions>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let replay_id = replay_id.into_inner();
+    let (request, tasks) = match db.get_query_request(replay_id).await? {
+        Some((request, tasks, _)) if request.replay_of.is_some() => (request, tasks),
+        _ => {
+            return Ok(
+                HttpResponse::BadRequest().json(format!("No replay exists with id {replay_id}"))
+            )
+        }
     };
 
-    if !authorized {
-        return Err(RelayError::new(
-            "User is unauthorized for adminstrative actions!",
-        ));
+    if !options.results {
+        return Ok(HttpResponse::Ok().json(ReplayResponse::new(request, tasks)));
     }
+    stream_all_task_results(
+        &mut db,
+        local_fingerprint.as_ref(),
+        result_manager.as_ref(),
+        tasks,
+        vec![],
+        Some(replay_id),
+        request.result_format,
+        ResponseFormat::Ndjson,
+        None,
+    )
+    .await
+}
+
+#[derive(Deserialize)]
+struct DeadLetterOptions {
+    #[serde(default)]
+    retrying: bool,
+}
+
+/// Lists query task messages which the query_runner failed to process too many times, and with
+/// retrying=true also the failed messages which are still being retried.
+#[get("/admin/dead_letters")]
+async fn dead_letters(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    options: web::Query<DeadLetterOptions>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let letters = db.get_dead_letters(options.retrying).await?;
+
+    Ok(HttpResponse::Ok().json(letters))
+}
+
+/// Requeues the task of a dead lettered message and dispatches the message again, with a fresh
+/// count of attempts.
+#[post("/admin/dead_letters/{id}/replay")]
+async fn replay_dead_letter(
+    pool: web::Data<DbPool>,
+    message_options: web::Data<MessageBrokerOptions>,
+    client_cert_header: web::Data<Option<String>>,
+    id: web::Path<i64>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let id = id.into_inner();
+    let letter = match db.replay_dead_letter(id).await? {
+        Some(letter) => letter,
+        None => {
+            return Ok(
+                HttpResponse::BadRequest().json(format!("No dead letter exists with id {id}"))
+            )
+        }
+    };
+    info!("Replaying dead letter {id} for task {:?}", letter.task_id);
+
+    let mut producer = initialize_producer(&message_options).await?;
+    if let Err(e) = publish_outbox(&mut db, producer.as_mut(), Utc::now()).await {
+        error!("Failed to publish task outbox with error {e}");
+    }
+
+    Ok(HttpResponse::Ok().json(letter))
+}
+
+#[derive(Deserialize)]
+struct InvalidateCacheOptions {
+    entity: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct InvalidateCacheResponse {
+    invalidated: usize,
+}
+
+/// Deletes cached query results, so that the next matching tasks are executed on their
+/// DataSources again. With an entity, only results of the DataSources it is mapped to are
+/// deleted.
+#[post("/admin/cache/invalidate")]
+async fn invalidate_cache(
+    pool: web::Data<DbPool>,
+    result_manager: web::Data<Arc<ResultManager>>,
+    client_cert_header: web::Data<Option<String>>,
+    options: web::Query<InvalidateCacheOptions>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let invalidated = match &options.entity {
+        Some(entity) => {
+            let sources = db.get_mappings_by_entity_names(vec![entity]).await?;
+            let mut invalidated = 0;
+            for (_, source) in sources.keys() {
+                invalidated += result_manager
+                    .invalidate_cached_results(Some(&source.id))
+                    .await?;
+            }
+            info!("Invalidated {invalidated} cached results of entity {entity}");
+            invalidated
+        }
+        None => {
+            let invalidated = result_manager.invalidate_cached_results(None).await?;
+            info!("Invalidated all {invalidated} cached results");
+            invalidated
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(InvalidateCacheResponse { invalidated }))
+}
+
+#[derive(Deserialize)]
+struct AuditOptions {
+    before_id: Option<i64>,
+    actor: Option<String>,
+    action: Option<AuditAction>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    limit: Option<i64>,
+}
+
+/// Lists the audit log newest first, one page of at most limit entries (100 by default) at a
+/// time. The next page is requested by passing the returned next_before_id as before_id.
+/// Entries can be restricted to the actor with a certificate fingerprint, an action of query or
+/// apply, and a time range with RFC 3339 since and until query parameters.
+#[get("/admin/audit")]
+async fn audit_log(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    options: web::Query<AuditOptions>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let options = options.into_inner();
+    let limit = options.limit.unwrap_or(100).clamp(1, 1000);
+    let filter = AuditFilter {
+        before_id: options.before_id,
+        actor: options.actor,
+        action: options.action,
+        since: options.since,
+        until: options.until,
+    };
+    let page = db.get_audit_page(&filter, limit).await?;
+
+    Ok(HttpResponse::Ok().json(page))
+}
+
+#[derive(Deserialize)]
+struct UsageReportOptions {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
+
+/// Reports the total rows and bytes served to each peer relay, optionally restricted to a
+/// time range with RFC 3339 since and until query parameters.
+#[get("/admin/usage")]
+async fn usage_report(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    options: web::Query<UsageReportOptions>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let report = db
+        .get_relay_usage_report(options.since, options.until)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Reports how often each Entity and each piece of Information about it has been queried, and
+/// when it was last accessed. Never queried Entities and Information are reported with a count of 0.
+#[get("/admin/usage/entities")]
+async fn entity_usage_report(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let report = db.get_entity_usage_report(None).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Same as [entity_usage_report], restricted to a single Entity.
+#[get("/admin/usage/entities/{entity_name}")]
+async fn single_entity_usage_report(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let entity = db.get_entity(&path.into_inner()).await?;
+    let report = db.get_entity_usage_report(Some(&entity.name)).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Reports the number of queued and in progress tasks of each DataSource, along with the
+/// average latency of its recently completed tasks.
+#[get("/admin/queue")]
+async fn queue_stats(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let stats = db.get_source_queue_stats(None).await?;
+
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+/// Returns the most recent validation result for each source mapped to an Entity.
+#[get("/admin/entities/{entity_name}/validation")]
+async fn entity_validation(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let entity = db.get_entity(&path.into_inner()).await?;
+    let validations = db.get_entity_validations(&entity.id).await?;
+
+    Ok(HttpResponse::Ok().json(validations))
+}
+
+#[derive(Deserialize)]
+struct InviteOptions {
+    /// Name under which the invited peer is registered once it redeems the invite.
+    peer_name: String,
+    /// How long the invite may be redeemed for, defaults to 24 hours.
+    ttl_secs: Option<i64>,
+}
+
+/// Issues a signed invite token, which the admin of the peer Relay redeems via /admin/invites/redeem
+/// on their own Relay. Both Relays are then registered with each other.
+#[post("/admin/invites")]
+async fn create_invite(
+    pool: web::Data<DbPool>,
+    local_fingerprint: web::Data<Arc<String>>,
+    client_cert_header: web::Data<Option<String>>,
+    options: web::Json<InviteOptions>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
+
+    let ttl = Duration::seconds(options.ttl_secs.unwrap_or(24 * 60 * 60));
+    let invite = issue_invite(&mut db, local_fingerprint.as_ref(), &options.peer_name, ttl).await?;
+    info!(
+        "Issued invite {} for peer relay {}",
+        invite.invite_id, options.peer_name
+    );
+
+    Ok(HttpResponse::Ok().json(invite))
+}
+
+#[derive(Deserialize)]
+struct RedeemOptions {
+    token: String,
+    /// Name under which the issuing Relay is registered, defaults to the name it declares.
+    name: Option<String>,
+}
+
+/// Redeems an invite token issued by another Relay and registers the issuing Relay as a peer.
+#[post("/admin/invites/redeem")]
+async fn redeem(
+    pool: web::Data<DbPool>,
+    local_fingerprint: web::Data<Arc<String>>,
+    result_manager: web::Data<Arc<ResultManager>>,
+    client_cert_header: web::Data<Option<String>>,
+    options: web::Json<RedeemOptions>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    authorize_admin(&mut db, req, client_cert_header.as_ref()).await?;
 
-    process_config_obj(&mut db, config_obj.0.config_object).await?;
+    let options = options.into_inner();
+    let relay = redeem_invite(
+        &mut db,
+        result_manager.as_ref(),
+        local_fingerprint.as_ref(),
+        &options.token,
+        options.name,
+    )
+    .await?;
+    info!("Registered peer relay {} via invite", relay.name);
 
-    Ok(HttpResponse::Ok())
+    Ok(HttpResponse::Ok().json(relay))
 }
@@ -1,23 +1,30 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use std::io::BufReader;
 
 use mesh::crud::PgDb;
 
 use mesh::error::{MeshError, Result};
+use mesh::execute::parse_utils::name_to_ident;
 
 use mesh::model::access_control::{ColumnPermission, RowPermission, SourcePermission};
 use mesh::model::config_commands::entity::ResolvedEntityDeclaration;
 use mesh::model::config_commands::local_data::ResolvedDataConnectionsDeclaration;
 use mesh::model::config_commands::local_mapping::ResolvedLocalMappingDeclaration;
+use mesh::model::config_commands::quota::ResolvedQuotaDeclaration;
 use mesh::model::config_commands::relay::ResolvedPeerRelayDeclaration;
 use mesh::model::config_commands::remote_mapping::ResolvedRemoteMappingsDeclaration;
-use mesh::model::config_commands::user::ResolvedUserDeclaration;
+use mesh::model::config_commands::role::ResolvedRoleDeclaration;
+use mesh::model::config_commands::user::{
+    PermissionsDecl, ResolvedServiceAccountDeclaration, ResolvedUserDeclaration,
+};
 use mesh::model::config_commands::ResolvedConfigObject;
 use mesh::model::entity::ArrowDataType;
 use mesh::model::mappings::{Mapping, NewRemoteEntityMapping, RemoteInfoMapping};
+use mesh::model::quota::{Quota, QuotaSubject};
 use mesh::model::relay::NewRelay;
 use mesh::model::user::{NewUser, UserAttributes};
+use mesh::model::validation::NewValidationRuleSet;
 use mesh::model::{
     data_stores::{NewDataField, NewDataSource},
     entity::NewInformation,
@@ -37,6 +44,18 @@ pub async fn process_config_obj(db: &mut PgDb<'_>, config_obj: ResolvedConfigObj
             process_remote_map_decl(db, remote_map_decl).await?
         }
         ResolvedConfigObject::User(user_decl) => process_user_decls(db, user_decl).await?,
+        ResolvedConfigObject::ServiceAccount(account_decl) => {
+            process_service_account_decl(db, account_decl).await?
+        }
+        ResolvedConfigObject::ValidationRules(rules_decl) => {
+            db.upsert_validation_rule_set(&NewValidationRuleSet {
+                name: rules_decl.name,
+                rules: rules_decl.rules,
+            })
+            .await?;
+        }
+        ResolvedConfigObject::Quota(quota_decl) => process_quota_decl(db, quota_decl).await?,
+        ResolvedConfigObject::Role(role_decl) => process_role_decl(db, role_decl).await?,
     }
     Ok(())
 }
@@ -46,16 +65,25 @@ async fn process_entity_decl(
     entity_decl: ResolvedEntityDeclaration,
 ) -> Result<()> {
     let entity = db.create_entity_if_not_exist(&entity_decl.name).await?;
-    for info_decl in entity_decl.information {
-        let new_info = NewInformation {
+    db.set_entity_validation_query(&entity.id, entity_decl.validation_query.as_deref())
+        .await?;
+    db.set_entity_aliases(&entity.id, &entity_decl.aliases)
+        .await?;
+    let new_infos = entity_decl
+        .information
+        .into_iter()
+        .enumerate()
+        .map(|(position, info_decl)| NewInformation {
             name: info_decl.name,
             arrow_dtype: ArrowDataType {
                 inner: info_decl.arrow_dtype,
             },
             entity_id: entity.id,
-        };
-        db.upsert_information(&new_info).await?;
-    }
+            position: Some(position as i32),
+            nullable: info_decl.nullable,
+        })
+        .collect::<Vec<_>>();
+    db.upsert_information_batch(&new_infos).await?;
     Ok(())
 }
 
@@ -64,7 +92,11 @@ async fn process_data_decl(
     data_decl: ResolvedDataConnectionsDeclaration,
 ) -> Result<()> {
     let data_con = db
-        .upsert_connection(&data_decl.name, data_decl.connection_options)
+        .upsert_connection(
+            &data_decl.name,
+            data_decl.connection_options,
+            data_decl.execution_windows,
+        )
         .await?;
     for source_decl in data_decl.data_sources {
         let new_source = NewDataSource {
@@ -72,16 +104,21 @@ async fn process_data_decl(
             source_sql: source_decl.source_sql,
             data_connection_id: data_con.id,
             source_options: source_decl.source_options,
+            engines: source_decl.engines,
+            freshness_query: source_decl.freshness_query,
+            policy_mode: source_decl.policy_mode,
         };
         let source = db.upsert_source(&new_source).await?;
-        for field_decl in source_decl.fields {
-            let new_field = NewDataField {
+        let new_fields = source_decl
+            .fields
+            .into_iter()
+            .map(|field_decl| NewDataField {
                 name: field_decl.name,
                 data_source_id: source.id,
                 path: field_decl.path,
-            };
-            db.upsert_field(&new_field).await?;
-        }
+            })
+            .collect::<Vec<_>>();
+        db.upsert_fields(&new_fields).await?;
 
         let default_permissions = source_decl.default_permission;
         let source_permission = SourcePermission {
@@ -104,22 +141,49 @@ async fn process_local_mapping_decl(
     map_decl: ResolvedLocalMappingDeclaration,
 ) -> Result<()> {
     let entity = db.get_entity(&map_decl.entity_name).await?;
+    let information = db
+        .get_information_for_entity(entity.id)
+        .await?
+        .into_iter()
+        .map(|i| (i.name, i.id))
+        .collect::<HashMap<_, _>>();
     for data_con_map_decl in map_decl.mappings {
         let data_con = db.get_connection(&data_con_map_decl.data_con_name).await?;
         for source_map_decl in data_con_map_decl.source_mappings {
             let source = db
                 .get_source(&source_map_decl.data_source_name, &data_con.id)
                 .await?;
-            for field_map_decl in source_map_decl.field_mappings {
-                let field = db.get_field(&field_map_decl.field, &source.id).await?;
-                let information = db.get_information(&field_map_decl.info, &entity.id).await?;
-                let map = Mapping {
-                    information_id: information.id,
-                    data_field_id: field.id,
-                    transformation: field_map_decl.transformation,
-                };
-                db.upsert_local_mapping(&map).await?;
-            }
+            let fields = db
+                .get_fields_for_source(&source.id)
+                .await?
+                .into_iter()
+                .map(|f| (f.name, f.id))
+                .collect::<HashMap<_, _>>();
+            let maps = source_map_decl
+                .field_mappings
+                .into_iter()
+                .map(|field_map_decl| {
+                    let data_field_id = *fields.get(&field_map_decl.field).ok_or_else(|| {
+                        MeshError::InvalidQuery(format!(
+                            "No field {} exists on data source {}",
+                            field_map_decl.field, source.name
+                        ))
+                    })?;
+                    let information_id =
+                        *information.get(&field_map_decl.info).ok_or_else(|| {
+                            MeshError::InvalidQuery(format!(
+                                "No information {} exists on entity {}",
+                                field_map_decl.info, entity.name
+                            ))
+                        })?;
+                    Ok(Mapping {
+                        information_id,
+                        data_field_id,
+                        transformation: field_map_decl.transformation,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            db.upsert_local_mappings(&maps).await?;
         }
     }
 
@@ -155,6 +219,7 @@ async fn process_relay_decl(
         x509_sha256: fingerprint,
         x509_subject: subject_dn,
         x509_issuer: issuer_dn,
+        x509_cert: Some(relay_decl.x509_cert),
     };
     let relay = db.upsert_relay(&new_relay).await?;
     if let Some(permissions) = relay_decl.permissions {
@@ -193,7 +258,7 @@ async fn process_remote_map_decl(
         let sql = if let Some(declared_sql) = peer_map.sql {
             declared_sql.clone()
         } else {
-            peer_map.remote_entity_name.clone()
+            name_to_ident(&peer_map.remote_entity_name).to_string()
         };
 
         let entity_map = NewRemoteEntityMapping {
@@ -219,7 +284,102 @@ async fn process_remote_map_decl(
 }
 
 async fn process_user_decls(db: &mut PgDb<'_>, user_decl: ResolvedUserDeclaration) -> Result<()> {
-    let mut cert_reader = BufReader::new(user_decl.x509_cert.as_slice());
+    let attributes = UserAttributes::new()
+        .with_is_admin(user_decl.attributes.is_admin)
+        .with_validation_rules(user_decl.attributes.validation_rules)
+        .with_attributes(user_decl.attributes.misc);
+    upsert_declared_user(db, &user_decl.x509_cert, attributes, user_decl.permissions).await
+}
+
+async fn process_service_account_decl(
+    db: &mut PgDb<'_>,
+    account_decl: ResolvedServiceAccountDeclaration,
+) -> Result<()> {
+    let attributes = UserAttributes::new()
+        .with_attributes(account_decl.misc)
+        .with_service_account(account_decl.scope);
+    upsert_declared_user(
+        db,
+        &account_decl.x509_cert,
+        attributes,
+        account_decl.permissions,
+    )
+    .await
+}
+
+async fn process_quota_decl(db: &mut PgDb<'_>, quota_decl: ResolvedQuotaDeclaration) -> Result<()> {
+    let (subject, x509_sha256) = declared_quota_subject(db, &quota_decl).await?;
+    db.upsert_quota(&Quota {
+        subject,
+        x509_sha256,
+        max_concurrent_requests: quota_decl.max_concurrent_requests.map(|max| max as i32),
+        max_requests_per_minute: quota_decl.max_requests_per_minute.map(|max| max as i32),
+    })
+    .await
+}
+
+/// Upserts the declared role and its source permissions, then replaces its members. Member users
+/// and relays must already be declared.
+async fn process_role_decl(db: &mut PgDb<'_>, role_decl: ResolvedRoleDeclaration) -> Result<()> {
+    let role = db.upsert_role(&role_decl.name).await?;
+    let mut user_ids = Vec::with_capacity(role_decl.user_x509_certs.len());
+    for x509_cert in &role_decl.user_x509_certs {
+        let (fingerprint, _, _) = parse_declared_certificate(x509_cert)?;
+        user_ids.push(db.get_user_by_x509_fingerprint(&fingerprint).await?.id);
+    }
+    let mut relay_ids = Vec::with_capacity(role_decl.relays.len());
+    for relay in &role_decl.relays {
+        relay_ids.push(db.get_relay_by_name(relay).await?.id);
+    }
+    db.set_role_members(&role.id, &user_ids, &relay_ids).await?;
+
+    if let Some(permissions) = role_decl.permissions {
+        for permission in permissions {
+            let data_con = db.get_connection(&permission.data_con_name).await?;
+            for source_permission_decl in permission.source_permissions {
+                let source = db
+                    .get_source(&source_permission_decl.data_source_name, &data_con.id)
+                    .await?;
+                let source_permission = SourcePermission {
+                    columns: ColumnPermission {
+                        allowed_columns: HashSet::from_iter(source_permission_decl.allowed_columns),
+                    },
+                    rows: RowPermission {
+                        allowed_rows: source_permission_decl.allowed_rows,
+                    },
+                };
+                db.upsert_role_source_permission(&role.id, &source.id, &source_permission)
+                    .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the subject and certificate fingerprint of the user or relay a quota is declared for.
+pub(crate) async fn declared_quota_subject(
+    db: &mut PgDb<'_>,
+    quota_decl: &ResolvedQuotaDeclaration,
+) -> Result<(QuotaSubject, String)> {
+    match (&quota_decl.user_x509_cert, &quota_decl.relay) {
+        (Some(x509_cert), None) => {
+            let (fingerprint, _, _) = parse_declared_certificate(x509_cert)?;
+            Ok((QuotaSubject::User, fingerprint))
+        }
+        (None, Some(relay)) => Ok((
+            QuotaSubject::Relay,
+            db.get_relay_by_name(relay).await?.x509_sha256,
+        )),
+        _ => Err(MeshError::Internal(
+            "Quota declaration must set exactly one of user_x509_cert_file and relay!".to_string(),
+        )),
+    }
+}
+
+/// Parses the single certificate in x509_cert of a declaration, returning its fingerprint,
+/// subject and issuer.
+pub(crate) fn parse_declared_certificate(x509_cert: &[u8]) -> Result<(String, String, String)> {
+    let mut cert_reader = BufReader::new(x509_cert);
     let mut certs = load_certificate_from_reader(&mut cert_reader)?;
     if certs.is_empty() {
         return Err(MeshError::Internal(
@@ -235,18 +395,28 @@ async fn process_user_decls(db: &mut PgDb<'_>, user_decl: ResolvedUserDeclaratio
         ));
     }
     let cert = certs.remove(0);
-    let (fingerprint, subject_dn, issuer_dn) = parse_certificate(&cert)?;
+    parse_certificate(&cert)
+}
+
+/// Upserts the user identified by the single certificate in x509_cert along with its declared
+/// source permissions.
+async fn upsert_declared_user(
+    db: &mut PgDb<'_>,
+    x509_cert: &[u8],
+    attributes: UserAttributes,
+    permissions: Option<Vec<PermissionsDecl>>,
+) -> Result<()> {
+    let (fingerprint, subject_dn, issuer_dn) = parse_declared_certificate(x509_cert)?;
 
     let new_user = NewUser {
         x509_sha256: fingerprint,
         x509_subject: subject_dn,
         x509_issuer: issuer_dn,
-        attributes: UserAttributes::new()
-            .with_is_admin(user_decl.attributes.is_admin)
-            .with_attributes(user_decl.attributes.misc),
+        attributes,
+        x509_cert: Some(x509_cert.to_vec()),
     };
     let user = db.upsert_user_by_fingerprint(&new_user).await?;
-    if let Some(permissions) = user_decl.permissions {
+    if let Some(permissions) = permissions {
         for permission in permissions {
             let data_con = db.get_connection(&permission.data_con_name).await?;
             for source_permission_decl in permission.source_permissions {
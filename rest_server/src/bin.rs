@@ -1,7 +1,10 @@
+use mesh::execute::telemetry::{init_tracing, shutdown_tracing};
 use rest_server_lib::run;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    tracing_subscriber::fmt::init();
-    run(None).await
+    init_tracing("rest_server");
+    let result = run(None).await;
+    shutdown_tracing().await;
+    result
 }
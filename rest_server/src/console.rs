@@ -0,0 +1,12 @@
+use actix_web::{get, HttpResponse, Responder};
+
+/// The bundled console page. It is plain HTML and JavaScript which only calls the existing
+/// /entities and /query endpoints, so it is subject to the same authentication as any client.
+const CONSOLE_HTML: &str = include_str!("../console/index.html");
+
+#[get("/console")]
+async fn index() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(CONSOLE_HTML)
+}
@@ -1,6 +1,7 @@
 use std::{error::Error, fmt};
 
-use actix_web::error;
+use actix_web::{error, http::StatusCode};
+use mesh::error::MeshError;
 use tracing::log::error;
 
 pub(crate) type Result<T, E = RelayError> = std::result::Result<T, E>;
@@ -8,12 +9,15 @@ pub(crate) type Result<T, E = RelayError> = std::result::Result<T, E>;
 #[derive(Debug)]
 pub(crate) struct RelayError {
     pub(crate) msg: String,
+    /// Tells callers, e.g. peered relays forwarding a query, whether retrying can succeed.
+    pub(crate) status: StatusCode,
 }
 
 impl RelayError {
     pub fn new(msg: &str) -> RelayError {
         RelayError {
             msg: msg.to_string(),
+            status: StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
@@ -26,11 +30,23 @@ impl fmt::Display for RelayError {
     }
 }
 
-impl error::ResponseError for RelayError {}
+impl error::ResponseError for RelayError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+}
 
-impl From<mesh::error::MeshError> for RelayError {
-    fn from(e: mesh::error::MeshError) -> Self {
+impl From<MeshError> for RelayError {
+    fn from(e: MeshError) -> Self {
         error!("Relay returning MeshError to caller: {e}");
-        RelayError { msg: e.to_string() }
+        let status = match e {
+            MeshError::InvalidQuery(_) | MeshError::InvalidTransform(_) => StatusCode::BAD_REQUEST,
+            MeshError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        RelayError {
+            msg: e.to_string(),
+            status,
+        }
     }
 }
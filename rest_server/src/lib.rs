@@ -7,6 +7,7 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 
 use actix_web::dev::Extensions;
+use actix_web::dev::{Server, ServerHandle};
 use actix_web::rt::net::TcpStream;
 use actix_web::{web, App, HttpServer};
 
@@ -18,6 +19,7 @@ use mesh::conf::EnvConfigSettings;
 
 use mesh::crud::PgDb;
 use mesh::execute::result_manager::ResultManager;
+use mesh::execute::shutdown::{shutdown_signal, shutdown_timeout};
 use mesh::messaging::MessageBrokerOptions;
 use mesh::model::data_stores::options::file_directory::FileDirectorySource;
 use mesh::model::data_stores::options::SourceFileType;
@@ -25,12 +27,16 @@ use mesh::model::data_stores::options::SourceFileType;
 use actix_tls::accept::rustls_0_21::{reexports::ServerConfig, TlsStream};
 use mesh::model::user::{NewUser, UserAttributes};
 use mesh::pki::parse_certificate;
-use rustls::server::AllowAnyAnonymousOrAuthenticatedClient;
+use rustls::server::{
+    AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, ClientCertVerifier,
+};
 use rustls::{Certificate, PrivateKey, RootCertStore};
 use rustls_pemfile::{certs, pkcs8_private_keys};
 use tracing::info;
 
 mod admin;
+#[cfg(feature = "console")]
+mod console;
 mod error;
 mod query;
 mod utils;
@@ -70,11 +76,13 @@ fn get_client_cert(connection: &dyn Any, data: &mut Extensions) {
     }
 }
 
-/// Creates a [ServerConfig] for an actix-web server running rustls and parses the server_cert_file as a [Certificate]
+/// Creates a [ServerConfig] for an actix-web server running rustls and parses the server_cert_file as a [Certificate].
+/// If require_client_cert, handshakes from clients without a trusted certificate are rejected.
 fn rustls_config(
     cacert_file: &str,
     server_cert_file: &str,
     server_key_file: &str,
+    require_client_cert: bool,
 ) -> std::io::Result<(Certificate, ServerConfig)> {
     let mut cert_store = RootCertStore::empty();
 
@@ -91,10 +99,14 @@ fn rustls_config(
     }
 
     // set up client authentication requirements
-    let client_auth = AllowAnyAnonymousOrAuthenticatedClient::new(cert_store);
+    let client_auth: Arc<dyn ClientCertVerifier> = if require_client_cert {
+        Arc::new(AllowAnyAuthenticatedClient::new(cert_store))
+    } else {
+        Arc::new(AllowAnyAnonymousOrAuthenticatedClient::new(cert_store))
+    };
     let config = ServerConfig::builder()
         .with_safe_defaults()
-        .with_client_cert_verifier(Arc::new(client_auth));
+        .with_client_cert_verifier(client_auth);
 
     // import server cert and key
     let cert_file = &mut BufReader::new(
@@ -136,6 +148,7 @@ async fn register_default_admin(cert_pem: String, pool: &DbPool) {
         x509_subject,
         x509_issuer,
         attributes: UserAttributes::new().with_is_admin(true),
+        x509_cert: std::fs::read(&cert_pem).ok(),
     };
 
     db.upsert_user_by_fingerprint(&newuser)
@@ -151,16 +164,26 @@ pub async fn run(in_memory_msg_opts: Option<MessageBrokerOptions>) -> std::io::R
         region: env_config.result_region.clone(),
         prefix: env_config.result_prefix.clone(),
         file_type: SourceFileType::Parquet,
+        s3: None,
+        hdfs: None,
+        include: vec![],
+        exclude: vec![],
+        path_regex: None,
+        modified_after: None,
+        declared_schema: None,
+        partition_columns: vec![],
     };
 
     let result_manager = Arc::new(
         ResultManager::try_initialize(
             env_config.result_object_store.clone(),
             result_source,
+            env_config.result_client_config.clone(),
             env_config.read_client_cert().unwrap(),
             env_config.read_client_key().unwrap(),
             env_config.read_client_cacert_pem().unwrap(),
         )
+        .and_then(|manager| manager.with_tenant_stores(env_config.result_tenant_stores.clone()))
         .expect("Failed to initialize result manager!"),
     );
 
@@ -174,8 +197,9 @@ pub async fn run(in_memory_msg_opts: Option<MessageBrokerOptions>) -> std::io::R
     let (fingerprint, _subject, _issuer) =
         parse_certificate(&client_cert).expect("Failed to parse own cert!");
 
-    let diesel_config =
-        AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(env_config.db_url);
+    let diesel_config = AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(
+        env_config.db_url.clone(),
+    );
     let pool = Pool::builder()
         .build(diesel_config)
         .await
@@ -186,49 +210,184 @@ pub async fn run(in_memory_msg_opts: Option<MessageBrokerOptions>) -> std::io::R
         register_default_admin(default_admin, &pool).await;
     }
 
+    let max_upload_bytes: usize = env::var("MAX_UPLOAD_BYTES")
+        .unwrap_or((64 * 1024 * 1024).to_string())
+        .parse()
+        .expect("Unable to parse MAX_UPLOAD_BYTES as usize!");
+
     let local_relay_fingerprint = Arc::new(fingerprint);
     let message_options = match in_memory_msg_opts {
         Some(opts) => opts,
-        None => env_config.msg_broker_opts,
+        None => env_config.msg_broker_opts.clone(),
     };
 
-    let base_server = HttpServer::new(move || {
-        App::new()
-            .app_data(web::Data::new(pool.clone()))
-            .app_data(web::Data::new(message_options.clone()))
-            .app_data(web::Data::new(result_manager.clone()))
-            .app_data(web::Data::new(local_relay_fingerprint.clone()))
-            .app_data(web::Data::new(env_config.client_cert_header.clone()))
-            .service(query::route::query)
-            .service(query::route::get_query_results)
-            .service(admin::route::apply)
+    let state = AppState {
+        pool,
+        message_options,
+        result_manager,
+        local_relay_fingerprint,
+        client_cert_header: env_config.client_cert_header.clone(),
+        max_upload_bytes,
+    };
+
+    let data_routes = match env_config.admin_rest_port {
+        Some(_) => Routes::Data,
+        None => Routes::All,
+    };
+    let data_server = serve(
+        state.clone(),
+        data_routes,
+        &env_config,
+        &env_config.rest_url,
+        &env_config.rest_port,
+        false,
+    )?;
+    let mut handles = vec![data_server.handle()];
+    let served = match &env_config.admin_rest_port {
+        Some(admin_port) => {
+            info!(
+                "Serving /admin on {}:{admin_port}",
+                env_config.admin_rest_url
+            );
+            let admin_server = serve(
+                state,
+                Routes::Admin,
+                &env_config,
+                &env_config.admin_rest_url,
+                admin_port,
+                env_config.admin_require_client_cert,
+            )?;
+            handles.push(admin_server.handle());
+            stop_on_shutdown(handles);
+            futures::future::try_join(data_server, admin_server)
+                .await
+                .map(|_| ())
+        }
+        None => {
+            stop_on_shutdown(handles);
+            data_server.await
+        }
+    };
+    // The database pool is closed as the state of the stopped servers is dropped
+    info!("REST server shut down");
+    served
+}
+
+/// Stops every server gracefully once the process receives SIGTERM or SIGINT, giving in-flight
+/// requests SHUTDOWN_TIMEOUT_SECS to finish. actix's own signal handling is disabled by [serve],
+/// since it stops immediately on SIGINT and stops each server on its own.
+fn stop_on_shutdown(handles: Vec<ServerHandle>) {
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        futures::future::join_all(handles.iter().map(|handle| handle.stop(true))).await;
     });
+}
 
-    if env_config.direct_tls {
+/// Shared state of every route, cloned into each worker of each listener.
+#[derive(Clone)]
+struct AppState {
+    pool: DbPool,
+    message_options: MessageBrokerOptions,
+    result_manager: Arc<ResultManager>,
+    local_relay_fingerprint: Arc<String>,
+    client_cert_header: Option<String>,
+    max_upload_bytes: usize,
+}
+
+/// Which routes a listener serves.
+#[derive(Clone, Copy, PartialEq)]
+enum Routes {
+    All,
+    /// Everything except /admin, so the data plane can be exposed to partners without the
+    /// administrative surface.
+    Data,
+    Admin,
+}
+
+impl AppState {
+    fn configure(&self, cfg: &mut web::ServiceConfig, routes: Routes) {
+        cfg.app_data(web::Data::new(self.pool.clone()))
+            .app_data(web::Data::new(self.message_options.clone()))
+            .app_data(web::Data::new(self.result_manager.clone()))
+            .app_data(web::Data::new(self.local_relay_fingerprint.clone()))
+            .app_data(web::Data::new(self.client_cert_header.clone()))
+            .app_data(web::PayloadConfig::new(self.max_upload_bytes))
+            .service(query::route::get_metrics)
+            .service(query::route::ping);
+        if routes != Routes::Admin {
+            cfg.service(query::route::query)
+                .service(query::route::get_query_results)
+                .service(query::route::get_query_result_tickets)
+                .service(query::route::get_query_task_detail)
+                .service(query::route::upload_dataset)
+                .service(query::route::list_entities)
+                .service(query::route::entity_preview);
+            #[cfg(feature = "console")]
+            cfg.service(console::index);
+        }
+        if routes != Routes::Data {
+            cfg.service(admin::route::apply)
+                .service(admin::route::delete)
+                .service(admin::route::prune)
+                .service(admin::route::list_entities)
+                .service(admin::route::list_data)
+                .service(admin::route::list_mappings)
+                .service(admin::route::list_relays)
+                .service(admin::route::relay_status)
+                .service(admin::route::list_users)
+                .service(admin::route::export)
+                .service(admin::route::discover)
+                .service(admin::route::test_connection)
+                .service(admin::route::pause_source)
+                .service(admin::route::resume_source)
+                .service(admin::route::usage_report)
+                .service(admin::route::audit_log)
+                .service(admin::route::entity_usage_report)
+                .service(admin::route::single_entity_usage_report)
+                .service(admin::route::queue_stats)
+                .service(admin::route::replay)
+                .service(admin::route::get_replay)
+                .service(admin::route::dead_letters)
+                .service(admin::route::replay_dead_letter)
+                .service(admin::route::invalidate_cache)
+                .service(admin::route::entity_validation)
+                .service(admin::route::create_invite)
+                .service(admin::route::redeem);
+        }
+    }
+}
+
+/// Binds a listener serving routes at url:port.
+fn serve(
+    state: AppState,
+    routes: Routes,
+    env_config: &EnvConfigSettings,
+    url: &str,
+    port: &str,
+    require_client_cert: bool,
+) -> std::io::Result<Server> {
+    let port: u16 = port
+        .parse()
+        .unwrap_or_else(|_| panic!("Unable to parse {port} as a port"));
+    let base_server = HttpServer::new(move || {
+        let state = state.clone();
+        App::new().configure(move |cfg| state.configure(cfg, routes))
+    })
+    .shutdown_timeout(shutdown_timeout().as_secs())
+    .disable_signals();
+
+    let server = if env_config.direct_tls {
         let (_cert, config) = rustls_config(
             env_config.ca_cert_file.as_str(),
             env_config.server_cert_file.as_str(),
             env_config.server_key_file.as_str(),
+            require_client_cert,
         )?;
-        base_server.on_connect(get_client_cert).bind_rustls_021(
-            (
-                env_config.rest_url,
-                env_config
-                    .rest_port
-                    .parse()
-                    .expect("Unable to parse REST_SERVICE_PORT as a port"),
-            ),
-            config,
-        )?
+        base_server
+            .on_connect(get_client_cert)
+            .bind_rustls_021((url, port), config)?
     } else {
-        base_server.bind((
-            env_config.rest_url,
-            env_config
-                .rest_port
-                .parse()
-                .expect("Unable to parse REST_SERVICE_PORT as a port"),
-        ))?
-    }
-    .run()
-    .await
+        base_server.bind((url, port))?
+    };
+    Ok(server.run())
 }
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
@@ -5,34 +6,47 @@ use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
 use mesh::error::MeshError;
 
 use mesh::execute::utils::{
-    create_query_request, map_and_create_local_tasks, map_and_create_remote_tasks,
-    validate_sql_and_logical_round_trip, verify_query_origination_information,
+    authorize_query, create_query_request, enforce_quotas, enforce_service_account_rate,
+    estimate_task_timing, map_and_create_local_tasks, map_and_create_remote_tasks,
+    validate_sql_and_logical_round_trip, validate_sql_with_warnings,
+    verify_query_origination_information,
 };
 
-use tracing::{debug, info};
+use tracing::{debug, error, info, info_span, warn};
 
-use super::utils::{count_task_status, stream_all_task_results};
+use super::utils::{
+    count_task_status, decode_upload, preview_local_queries, stream_all_task_results, PageToken,
+    ResponseFormat, UploadFormat,
+};
 use crate::error::Result;
 use crate::utils::parse_certs_from_req;
 use crate::DbPool;
 use mesh::crud::PgDb;
+use mesh::execute::identity::identity_cache;
+use mesh::execute::inspect::entity_summaries;
+use mesh::execute::metrics::{metrics, metrics_content_type};
+use mesh::execute::outbox::publish_outbox;
+use mesh::execute::parse_utils::name_to_ident;
 use mesh::execute::result_manager::ResultManager;
+use mesh::execute::scratch::create_scratch_dataset;
+use mesh::execute::{dedup_retention, request_to_local_queries};
 
-use mesh::messaging::{
-    initialize_producer, GenericMessage, MessageBrokerOptions, QueryTaskMessage,
-};
+use mesh::messaging::{initialize_producer, MessageBrokerOptions};
 
-use mesh::model::query::RawQueryRequest;
+use mesh::model::access_control::SourcePermission;
+use mesh::model::query::{
+    QueryTaskStatus, RawQueryRequest, ResultMetadata, ScanMetrics, StoredResultTicket,
+    SubmitQueryResponse, TraceContext,
+};
+use mesh::model::user::{NewUser, UserAttributes};
 
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use uuid::Uuid;
 
-#[derive(Serialize, Deserialize, Debug)]
-struct SubmitQueryResponse {
-    id: Uuid,
-}
-
+#[allow(dead_code)]
 #[derive(Serialize, Deserialize, Debug)]
 struct GetQueryResultResponse {
     data: Value,
@@ -52,6 +66,21 @@ struct GetQueryStatus {
 struct GetQueryOptions {
     allow_partial: Option<bool>,
     status_only: Option<bool>,
+    /// Index of the statement of a batch request to retrieve, rather than all of them.
+    statement: Option<i32>,
+    /// Encoding of the results, which otherwise is negotiated via the Accept header.
+    format: Option<ResponseFormat>,
+    /// Maximum number of rows to retrieve, which pages through the results.
+    limit: Option<usize>,
+    /// Number of rows of the results to skip.
+    offset: Option<usize>,
+    /// Continuation token of a page returned by a previous retrieval, which replaces offset.
+    page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GetTicketsOptions {
+    statement: Option<i32>,
 }
 
 #[get("/query/{request_id}")]
@@ -64,6 +93,7 @@ async fn get_query_results(
     options: web::Query<GetQueryOptions>,
     req: HttpRequest,
 ) -> Result<impl Responder> {
+    let response_format = ResponseFormat::negotiate(options.format, &req);
     let (fingerprint, subject_dn, issuer_dn) =
         parse_certs_from_req(req, client_cert_header.as_ref())?;
 
@@ -78,7 +108,7 @@ async fn get_query_results(
 
     let mut db = PgDb::try_from_pool(&pool).await?;
 
-    let (request, tasks, remote_tasks) = match db.get_query_request(request_id).await? {
+    let (request, mut tasks, mut remote_tasks) = match db.get_query_request(request_id).await? {
         Some(r) => r,
         None => {
             return Ok(
@@ -88,24 +118,59 @@ async fn get_query_results(
     };
 
     // Access denied and no query exists intentionally give same response to prevent
-    // brute forcing valid Uuids.
-    match request.origin_info.origin_user {
-        Some(origin_user) => {
-            let retreiving_user = db.get_user_by_x509_fingerprint(&fingerprint).await?;
-            if origin_user != retreiving_user {
+    // brute forcing valid Uuids. Replays are only available to admins.
+    match &request.origin_info.origin_user {
+        Some(origin_user) if request.replay_of.is_none() => {
+            let retreiving_user = identity_cache().get_user(&mut db, &fingerprint).await?;
+            if *origin_user != retreiving_user {
                 return Ok(HttpResponse::BadRequest()
                     .json(format!("No query exists with id {request_id}")));
             }
         }
-        None => {
+        _ => {
             return Ok(
                 HttpResponse::BadRequest().json(format!("No query exists with id {request_id}"))
             )
         }
     }
 
+    if let Some(statement) = options.statement {
+        if statement < 0 || statement as usize >= request.statement_sql().len() {
+            return Ok(HttpResponse::BadRequest()
+                .json(format!("Query {request_id} has no statement {statement}")));
+        }
+        tasks.retain(|t| t.statement_index == statement);
+        remote_tasks.retain(|t| t.statement_index == statement);
+    }
+
+    let page = match (&options.page_token, options.limit, options.offset) {
+        (_, Some(0), _) => return Ok(HttpResponse::BadRequest().json("limit must be positive")),
+        (Some(_), _, Some(_)) => {
+            return Ok(HttpResponse::BadRequest().json("Pass either offset or page_token"))
+        }
+        (Some(token), limit, None) => match PageToken::decode(token) {
+            Some(page) if page.request_id == request_id && page.statement == options.statement => {
+                Some(PageToken {
+                    limit: limit.unwrap_or(page.limit),
+                    ..page
+                })
+            }
+            _ => {
+                return Ok(HttpResponse::BadRequest()
+                    .json(format!("Invalid page_token for query {request_id}")))
+            }
+        },
+        (None, None, None) => None,
+        (None, limit, offset) => Some(PageToken {
+            request_id,
+            statement: options.statement,
+            offset: offset.unwrap_or(0),
+            limit: limit.unwrap_or(usize::MAX),
+        }),
+    };
+
     let flights = db.get_all_flight_streams(&remote_tasks).await?;
-    let (complete, failed, in_progress) = count_task_status(&tasks, &flights);
+    let (complete, failed, in_progress) = count_task_status(&tasks, &remote_tasks, &flights);
 
     if !allow_partial && failed > 0 {
         let status = GetQueryStatus{
@@ -142,25 +207,279 @@ async fn get_query_results(
         result_manager.as_ref(),
         tasks,
         flights,
+        None,
+        request.result_format,
+        response_format,
+        page,
     )
     .await
 }
 
+#[derive(Serialize, Debug)]
+struct StoredResultEndpoint {
+    /// Flight endpoint of the local relay, where the ticket is redeemed via do_get
+    location: String,
+    ticket: StoredResultTicket,
+    /// Index of the statement of a batch request which the result answers.
+    statement: i32,
+    /// Describes the result, e.g. how fresh the data of its source is.
+    metadata: ResultMetadata,
+}
+
+/// Returns a Flight ticket for each stored result of a query, so that clients can fetch the
+/// results as Arrow via do_get rather than as JSON. Only results which are already complete are
+/// listed, so clients should wait until the query is complete via status_only first. Pass
+/// statement to list only the results of one statement of a batch request.
+#[get("/query/{request_id}/tickets")]
+async fn get_query_result_tickets(
+    pool: web::Data<DbPool>,
+    local_fingerprint: web::Data<Arc<String>>,
+    client_cert_header: web::Data<Option<String>>,
+    request_id: web::Path<Uuid>,
+    options: web::Query<GetTicketsOptions>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let (fingerprint, _subject_dn, _issuer_dn) =
+        parse_certs_from_req(req, client_cert_header.as_ref())?;
+    let request_id = request_id.into_inner();
+
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    let not_found =
+        || Ok(HttpResponse::BadRequest().json(format!("No query exists with id {request_id}")));
+    let (request, results) = match db.get_stored_results(request_id).await? {
+        Some(r) => r,
+        None => return not_found(),
+    };
+
+    // Access denied and no query exists intentionally give same response to prevent
+    // brute forcing valid Uuids.
+    let retreiving_user = identity_cache().get_user(&mut db, &fingerprint).await?;
+    if request.origin_info.origin_user.as_ref() != Some(&retreiving_user) {
+        return not_found();
+    }
+
+    let local_relay = identity_cache()
+        .get_relay(&mut db, &local_fingerprint)
+        .await?;
+    let endpoints = results
+        .into_iter()
+        .filter(|result| {
+            options
+                .statement
+                .map_or(true, |statement| result.statement_index == statement)
+        })
+        .map(|result| StoredResultEndpoint {
+            location: local_relay.flight_endpoint.clone(),
+            ticket: StoredResultTicket {
+                request_id,
+                result_id: result.id,
+            },
+            statement: result.statement_index,
+            metadata: ResultMetadata {
+                freshness: result.freshness,
+            },
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(endpoints))
+}
+
+#[derive(Serialize, Debug)]
+struct QueryTaskDetailResponse {
+    id: Uuid,
+    request_id: Uuid,
+    data_source: String,
+    engine: Option<String>,
+    status: QueryTaskStatus,
+    not_before: Option<DateTime<Utc>>,
+    /// The SQL actually sent to the data source after mapping and applying permissions.
+    sql: String,
+    permission: Option<SourcePermission>,
+    scan_metrics: Option<ScanMetrics>,
+}
+
+/// Returns the mapped SQL and the permission applied to a local task of a query, so that the
+/// user who submitted the query can see why it returned the data it did.
+#[get("/query/{request_id}/tasks/{task_id}")]
+async fn get_query_task_detail(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    path: web::Path<(Uuid, Uuid)>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let (fingerprint, _subject_dn, _issuer_dn) =
+        parse_certs_from_req(req, client_cert_header.as_ref())?;
+    let (request_id, task_id) = path.into_inner();
+
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    let not_found = || {
+        HttpResponse::BadRequest().json(format!(
+            "No task exists with id {task_id} for query {request_id}"
+        ))
+    };
+
+    // As for results, access denied and no task exists intentionally give the same response.
+    let (_con, source, task, request, _relay) = match db.get_query_task(task_id).await {
+        Ok(t) => t,
+        Err(e) => {
+            debug!("Failed to get task {task_id} with error: {e}");
+            return Ok(not_found());
+        }
+    };
+    if request.id != request_id || request.replay_of.is_some() {
+        return Ok(not_found());
+    }
+    match request.origin_info.origin_user {
+        Some(origin_user) => {
+            let retreiving_user = identity_cache().get_user(&mut db, &fingerprint).await?;
+            if origin_user != retreiving_user {
+                return Ok(not_found());
+            }
+        }
+        None => return Ok(not_found()),
+    }
+
+    Ok(HttpResponse::Ok().json(QueryTaskDetailResponse {
+        id: task.id,
+        request_id,
+        data_source: source.name,
+        engine: task.engine,
+        status: task.status,
+        not_before: task.not_before,
+        sql: task.task.sql,
+        permission: task.permission,
+        scan_metrics: task.scan_metrics,
+    }))
+}
+
+/// Exposes the metrics of the relay in the Prometheus text format. Served without requiring a
+/// client certificate, so it should only be reachable by the monitoring system.
+#[get("/metrics")]
+async fn get_metrics() -> Result<impl Responder> {
+    let encoded = metrics().encode()?;
+    Ok(HttpResponse::Ok()
+        .content_type(metrics_content_type())
+        .body(encoded))
+}
+
+/// Responds with an empty body, so that peers can check the relay is reachable.
+#[get("/ping")]
+async fn ping() -> impl Responder {
+    HttpResponse::Ok().finish()
+}
+
+/// Lists every [Entity][mesh::model::entity::Entity] of the local relay along with its
+/// Information, so that users can discover what they are able to query.
+#[get("/entities")]
+async fn list_entities(
+    pool: web::Data<DbPool>,
+    client_cert_header: web::Data<Option<String>>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let (fingerprint, _subject_dn, _issuer_dn) =
+        parse_certs_from_req(req, client_cert_header.as_ref())?;
+    debug!("Listing entities for {fingerprint}");
+
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    Ok(HttpResponse::Ok().json(entity_summaries(&mut db).await?))
+}
+
+/// Upper bound on the number of rows returned by an entity preview.
+const MAX_PREVIEW_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+struct PreviewOptions {
+    limit: Option<usize>,
+}
+
+/// Returns a sample of rows of an [Entity][mesh::model::entity::Entity] from local sources only,
+/// so that users can inspect the shape of the data before writing a real query. The preview is
+/// executed immediately subject to the user's permissions, scope and quotas like any query, and
+/// is not recorded as a query.
+#[get("/entity/{entity_name}/preview")]
+async fn entity_preview(
+    pool: web::Data<DbPool>,
+    local_fingerprint: web::Data<Arc<String>>,
+    client_cert_header: web::Data<Option<String>>,
+    entity_name: web::Path<String>,
+    options: web::Query<PreviewOptions>,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let (fingerprint, subject_dn, issuer_dn) =
+        parse_certs_from_req(req, client_cert_header.as_ref())?;
+    let limit = options.limit.unwrap_or(100).min(MAX_PREVIEW_LIMIT);
+    let entity_name = entity_name.into_inner();
+    info!("Got preview request for entity {entity_name} from {subject_dn}");
+
+    let raw_request = RawQueryRequest {
+        sql: format!(
+            "select * from {} limit {limit}",
+            name_to_ident(&entity_name)
+        ),
+        statements: vec![],
+        request_uuid: None,
+        requesting_user: None,
+        originating_relay: None,
+        originating_task_id: None,
+        return_arrow_schema: None,
+        engine_hint: None,
+        count_only: false,
+        interactive: true,
+        reexecution_of: None,
+        hints: HashMap::new(),
+    };
+
+    let mut db = PgDb::try_from_pool(&pool).await?;
+    let (direct_requester, requesting_user, _originating_relay) =
+        verify_query_origination_information(
+            &raw_request,
+            &mut db,
+            fingerprint,
+            subject_dn,
+            issuer_dn,
+            local_fingerprint.as_ref(),
+        )
+        .await?;
+    let (entity_name, statement, _logical_schema) =
+        validate_sql_and_logical_round_trip(&raw_request.sql, &mut db).await?;
+    authorize_query(&raw_request.sql, &entity_name, &requesting_user, &mut db).await?;
+    enforce_service_account_rate(&requesting_user, &mut db).await?;
+    enforce_quotas(&direct_requester, &mut db).await?;
+
+    let local_queries = request_to_local_queries(
+        &mut db,
+        &statement,
+        &entity_name,
+        &raw_request,
+        &direct_requester,
+        &requesting_user,
+        None,
+    )
+    .await?;
+    let records = preview_local_queries(&mut db, local_queries, limit).await?;
+    Ok(HttpResponse::Ok().body(records))
+}
+
 #[post("/query")]
 async fn query(
     pool: web::Data<DbPool>,
     message_options: web::Data<MessageBrokerOptions>,
     local_fingerprint: web::Data<Arc<String>>,
     client_cert_header: web::Data<Option<String>>,
-    mut query: web::Json<RawQueryRequest>,
+    query: web::Json<RawQueryRequest>,
     req: HttpRequest,
 ) -> Result<impl Responder> {
+    // The span ends once the request returns, its context is stored with the request
+    let (_span, trace_context) =
+        TraceContext::from_headers(|name| req.headers().get(name)?.to_str().ok())
+            .start_span(info_span!("query"));
     let (fingerprint, subject_dn, issuer_dn) =
         parse_certs_from_req(req, client_cert_header.as_ref())?;
     info!(
         "Got new query request from: subject: {}, issuer: {}, fingerprint: {}",
         subject_dn, issuer_dn, fingerprint
     );
+    metrics().query_received("rest");
     let mut db = PgDb::try_from_pool(&pool).await?;
 
     let (direct_requester, requesting_user, originating_relay) =
@@ -176,24 +495,66 @@ async fn query(
 
     debug!("requesting_user: {requesting_user:?}, originating_relay: {originating_relay:?}");
 
+    // A request forwarded back to the relay which originated it went around a cycle of the relay
+    // network. The statements of a batch are forwarded under ids of their own which the check
+    // below does not know, so such requests are recognized by their originating task instead.
+    if let (Some(_), Some(task_id)) = (&query.originating_relay, &query.originating_task_id) {
+        if originating_relay.x509_sha256 == **local_fingerprint.as_ref() {
+            if let Ok((remote_task, _)) = db.get_remote_query_task(*task_id).await {
+                info!("Request for task {task_id} was forwarded back to its origin! Returning succesful response with no further action taken.");
+                return Ok(
+                    HttpResponse::Ok().json(SubmitQueryResponse::new(remote_task.query_request_id))
+                );
+            }
+        }
+    }
+
     // It is possible that two requests bypass this check around the same time. This is OK as the database will later
     // raise a Unique contraint violation error. This check is only for efficiency, the database will always ensure correctness.
-    match &query.request_uuid {
-        Some(id) => match db.check_if_request_already_received(id).await {
+    if let Some(id) = &query.request_uuid {
+        match db
+            .check_if_request_already_received(id, dedup_retention())
+            .await
+        {
             Ok(request) => {
                 info!("Request id {id} already processed! Returning succesful response with no further action taken.");
-                return Ok(HttpResponse::Ok().json(SubmitQueryResponse { id: request.id }));
+                return Ok(HttpResponse::Ok().json(SubmitQueryResponse::new(request.id)));
             }
             Err(e) => debug!("Did not find already existing request with error: {e}"),
-        },
-        None => (),
+        }
     }
 
+    enforce_service_account_rate(&requesting_user, &mut db).await?;
+    enforce_quotas(&direct_requester, &mut db).await?;
+
+    // Every statement of a batch is validated before any is executed, so that a batch either
+    // executes as a whole or is rejected as a whole.
     debug!("Checking if sql template is valid...");
-    let (entity_name, statement, logical_schema) =
-        validate_sql_and_logical_round_trip(&query.sql, &mut db).await?;
-    if query.return_arrow_schema.is_none() {
-        query.return_arrow_schema = Some(logical_schema);
+    let statement_requests = query.split_statements()?;
+    let is_batch = statement_requests.len() > 1;
+    let mut validated = Vec::with_capacity(statement_requests.len());
+    let mut warnings = vec![];
+    for (i, mut statement_request) in statement_requests.into_iter().enumerate() {
+        let (entity_name, statement, logical_schema, statement_warnings) =
+            validate_sql_with_warnings(&statement_request.sql, &mut db).await?;
+        for warning in statement_warnings {
+            warn!("Query from {}: {warning}", requesting_user.x509_subject);
+            warnings.push(match is_batch {
+                true => format!("Statement {i}: {warning}"),
+                false => warning,
+            });
+        }
+        authorize_query(
+            &statement_request.sql,
+            &entity_name,
+            &requesting_user,
+            &mut db,
+        )
+        .await?;
+        if statement_request.return_arrow_schema.is_none() {
+            statement_request.return_arrow_schema = Some(logical_schema);
+        }
+        validated.push((statement_request, entity_name, statement));
     }
 
     debug!("Creating QueryRequest");
@@ -203,6 +564,7 @@ async fn query(
         &direct_requester,
         &requesting_user,
         &originating_relay,
+        &trace_context,
     )
     .await
     {
@@ -213,56 +575,144 @@ async fn query(
             response with no further action taken.",
                 q.originator_request_id
             );
-            return Ok(HttpResponse::Ok().json(SubmitQueryResponse { id: q.id }));
+            return Ok(HttpResponse::Ok().json(SubmitQueryResponse::new(q.id)));
         }
         Err(e) => Err(e)?,
     };
 
-    debug!("Mapping QueryRequest to local queries");
-    let created_tasks = map_and_create_local_tasks(
-        &statement,
-        &query,
-        &entity_name,
-        &request,
-        &mut db,
-        &direct_requester,
-        &requesting_user,
-    )
-    .await?;
+    let mut created_tasks = vec![];
+    for (statement_index, (statement_request, entity_name, statement)) in
+        (0..).zip(validated.into_iter())
+    {
+        debug!("Mapping statement {statement_index} of QueryRequest to local queries");
+        created_tasks.extend(
+            map_and_create_local_tasks(
+                &statement,
+                &statement_request,
+                &entity_name,
+                &request,
+                &mut db,
+                &direct_requester,
+                &requesting_user,
+                true,
+                statement_index,
+            )
+            .await?,
+        );
+
+        debug!("Mapping statement {statement_index} of QueryRequest to remote queries");
+        map_and_create_remote_tasks(
+            &statement_request,
+            &statement,
+            &request,
+            &entity_name,
+            &mut db,
+            requesting_user.clone(),
+            originating_relay.clone(),
+            statement_index,
+        )
+        .await?;
+    }
 
-    debug!("Mapping QueryRequest to remote queries");
-    let created_remote_tasks = map_and_create_remote_tasks(
-        &query,
-        &statement,
-        &request,
-        &entity_name,
-        &mut db,
-        requesting_user,
-        originating_relay,
-    )
-    .await?;
+    if let Err(e) = db
+        .record_query_audit(&request, &requesting_user, &created_tasks)
+        .await
+    {
+        error!(
+            "Failed to record audit entry of request {} with error {e}",
+            request.id
+        );
+    }
+
+    // Deferred tasks are dispatched by the query_runner once their execution window opens
+    let (estimated_start, estimated_completion) =
+        estimate_task_timing(&created_tasks, &mut db, Utc::now()).await?;
 
+    // The tasks are already persisted along with their messages, so if publishing fails here
+    // the query_runner's outbox dispatcher publishes them later.
     debug!("Sending messages to QueryRunner");
     let mut producer = initialize_producer(&message_options).await?;
-    for task in created_tasks {
-        producer
-            .send_message(&GenericMessage::LocalQueryTask(QueryTaskMessage {
-                id: task.id,
-            }))
-            .await?;
-    }
-
-    for remote_task in created_remote_tasks {
-        producer
-            .send_message(&GenericMessage::RemoteQueryTask(QueryTaskMessage {
-                id: remote_task.id,
-            }))
-            .await?;
+    if let Err(e) = publish_outbox(&mut db, producer.as_mut(), Utc::now()).await {
+        error!("Failed to publish task outbox with error {e}");
     }
 
     debug!(
         "Successfully processed query with uuid {}!",
         request.originator_request_id
     );
-    Ok(HttpResponse::Ok().json(SubmitQueryResponse { id: request.id }))
+    Ok(HttpResponse::Ok().json(SubmitQueryResponse {
+        id: request.id,
+        estimated_start,
+        estimated_completion,
+        warnings,
+    }))
+}
+
+#[derive(Deserialize)]
+struct UploadDatasetOptions {
+    #[serde(default)]
+    format: UploadFormat,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct UploadDatasetResponse {
+    entity_name: String,
+    rows: usize,
+}
+
+/// Uploads a small Arrow IPC stream or Parquet dataset which the relay stores alongside query
+/// results and exposes as a scratch [Entity][mesh::model::entity::Entity], queryable only by the
+/// uploading user. The dataset expires SCRATCH_DATASET_TTL_SECS (default 7 days) after it was
+/// last uploaded, after which the query_runner deletes its data and entity. Uploading it again
+/// replaces the data and restarts its expiry.
+#[post("/dataset/{dataset_name}")]
+async fn upload_dataset(
+    pool: web::Data<DbPool>,
+    result_manager: web::Data<Arc<ResultManager>>,
+    client_cert_header: web::Data<Option<String>>,
+    dataset_name: web::Path<String>,
+    options: web::Query<UploadDatasetOptions>,
+    body: Bytes,
+    req: HttpRequest,
+) -> Result<impl Responder> {
+    let (fingerprint, subject_dn, issuer_dn) =
+        parse_certs_from_req(req, client_cert_header.as_ref())?;
+    info!(
+        "Got new dataset upload from: subject: {}, issuer: {}, fingerprint: {}",
+        subject_dn, issuer_dn, fingerprint
+    );
+    let mut db = PgDb::try_from_pool(&pool).await?;
+
+    let user = match identity_cache().get_user(&mut db, &fingerprint).await {
+        Ok(user) => user,
+        Err(_) => {
+            db.upsert_user_by_fingerprint(&NewUser {
+                x509_sha256: fingerprint,
+                x509_subject: subject_dn,
+                x509_issuer: issuer_dn,
+                attributes: UserAttributes::new(),
+                x509_cert: None,
+            })
+            .await?
+        }
+    };
+
+    let (schema, batches) = decode_upload(body, options.format)?;
+    let rows = batches.iter().map(|b| b.num_rows()).sum();
+    let rb_stream = Box::pin(futures::stream::iter(batches.into_iter().map(Ok)));
+
+    let entity = create_scratch_dataset(
+        &mut db,
+        result_manager.as_ref(),
+        &user,
+        &dataset_name,
+        rb_stream,
+        schema,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(UploadDatasetResponse {
+        entity_name: entity.name,
+        rows,
+    }))
 }
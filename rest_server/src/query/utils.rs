@@ -1,29 +1,54 @@
+use std::pin::Pin;
 use std::sync::Arc;
 
-use actix_web::HttpResponse;
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse};
+use arrow::array::{ArrayRef, StringArray};
+use arrow::csv::WriterBuilder;
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::error::ArrowError;
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
 #[allow(deprecated)]
 use arrow::json::writer::record_batches_to_json_rows;
-use arrow::record_batch::RecordBatch;
+use arrow::record_batch::{RecordBatch, RecordBatchReader};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use bytes::{Buf, Bytes, BytesMut};
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
 
-use tracing::{error, warn};
+use tracing::{debug, error, warn};
 
 use crate::error::{RelayError, Result};
 
 use mesh::crud::PgDb;
+use mesh::error::MeshError;
+use mesh::execute::data_stores::try_connect;
+use mesh::execute::identity::identity_cache;
+use mesh::execute::metrics::metrics;
 use mesh::execute::result_manager::ResultManager;
+use mesh::execute::{resolve_task_engine, LocalQuery};
 
 use mesh::model::query::{
-    FlightStream, FlightStreamStatus, QueryTask, QueryTaskRemote, QueryTaskStatus,
+    FlightStream, FlightStreamStatus, QueryTask, QueryTaskRemote, QueryTaskRemoteStatus,
+    QueryTaskStatus, ResultFormat,
 };
 
 use datafusion::common::DataFusionError;
-use futures::TryStreamExt;
+use futures::{Stream, StreamExt, TryStreamExt};
 
 use serde_json::Value;
+use uuid::Uuid;
 
-/// Counts how many local and remote tasks in total are complete, failed, or in progress
+/// Counts how many local and remote tasks in total are complete, failed, or in progress. Remote
+/// tasks which could not be submitted to their relay count as failed, since they produce no
+/// [FlightStream]s.
 pub(crate) fn count_task_status(
     tasks: &[QueryTask],
+    remote_tasks: &[QueryTaskRemote],
     remote_flight: &[(QueryTaskRemote, FlightStream)],
 ) -> (usize, usize, usize) {
     let mut complete = 0;
@@ -38,6 +63,11 @@ pub(crate) fn count_task_status(
         }
     }
 
+    failed += remote_tasks
+        .iter()
+        .filter(|t| matches!(t.status, QueryTaskRemoteStatus::Failed))
+        .count();
+
     for (remote, flight) in remote_flight.iter() {
         match flight.status {
             FlightStreamStatus::Complete => complete += 1,
@@ -76,20 +106,219 @@ pub(crate) fn convert_rb_to_serialized_json_records(
     Ok(bytes::Bytes::from(serialized))
 }
 
-/// Creates a HttpResponse::Ok().streaming(...) where the returned stream is all of the local and remote
-/// task results interleaved with additional injected metadata, serialized as NDJSON records.
+fn count_rest_bytes(batch: &RecordBatch) {
+    metrics().bytes_streamed("rest", batch.get_array_memory_size());
+}
+
+/// Encodings in which the results of a query are streamed to the client
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ResponseFormat {
+    /// Newline delimited JSON records
+    #[default]
+    Ndjson,
+    /// The Arrow IPC streaming format
+    Arrow,
+    Csv,
+}
+
+impl ResponseFormat {
+    /// Negotiates the format of a response, which is the format query parameter if passed, or
+    /// otherwise the first supported media type of the Accept header. Defaults to NDJSON.
+    pub(crate) fn negotiate(format: Option<ResponseFormat>, req: &HttpRequest) -> ResponseFormat {
+        if let Some(format) = format {
+            return format;
+        }
+        let accept = req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .unwrap_or_default();
+        accept
+            .split(',')
+            .filter_map(
+                |media| match media.split(';').next().unwrap_or_default().trim() {
+                    "application/vnd.apache.arrow.stream" => Some(ResponseFormat::Arrow),
+                    "text/csv" => Some(ResponseFormat::Csv),
+                    "application/x-ndjson" | "application/json" => Some(ResponseFormat::Ndjson),
+                    _ => None,
+                },
+            )
+            .next()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn content_type(&self) -> &'static str {
+        match self {
+            ResponseFormat::Ndjson => "application/x-ndjson",
+            ResponseFormat::Arrow => "application/vnd.apache.arrow.stream",
+            ResponseFormat::Csv => "text/csv",
+        }
+    }
+}
+
+/// Names of the metadata injected into each result record, see [stream_all_task_results].
+const METADATA_COLUMNS: [&str; 3] = ["_source_relay_", "_source_id_", "_freshness_"];
+
+/// Appends the injected metadata as string columns, for formats which cannot nest it in each
+/// record like NDJSON. Every batch gets all of the columns so that batches of different tasks
+/// share a schema, with nulls where the metadata is missing.
+fn append_metadata_columns(
+    batch: RecordBatch,
+    metadata: &Value,
+) -> Result<RecordBatch, DataFusionError> {
+    let mut fields = batch.schema().fields().to_vec();
+    let mut columns = batch.columns().to_vec();
+    for name in METADATA_COLUMNS {
+        let value = metadata.get(name).map(|value| match value {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        });
+        fields.push(Arc::new(Field::new(name, DataType::Utf8, true)));
+        columns.push(Arc::new(StringArray::from(vec![value; batch.num_rows()])) as ArrayRef);
+    }
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+/// Serializes batches as CSV, with a header only before the first batch.
+fn encode_csv(
+    batches: impl Stream<Item = Result<(RecordBatch, Arc<Value>), DataFusionError>>,
+) -> impl Stream<Item = Result<Bytes, DataFusionError>> {
+    let mut header = true;
+    batches.and_then(move |(batch, metadata)| {
+        let encoded = append_metadata_columns(batch, &metadata).and_then(|batch| {
+            let mut writer = WriterBuilder::new().with_header(header).build(vec![]);
+            writer.write(&batch)?;
+            Ok(Bytes::from(writer.into_inner()))
+        });
+        header = false;
+        futures::future::ready(encoded)
+    })
+}
+
+/// Serializes batches in the Arrow IPC streaming format. The schema is taken from the first batch,
+/// so results with differing schemas fail and must be retrieved in another format.
+fn encode_arrow(
+    batches: impl Stream<Item = Result<(RecordBatch, Arc<Value>), DataFusionError>> + Unpin,
+) -> impl Stream<Item = Result<Bytes, DataFusionError>> {
+    futures::stream::try_unfold(
+        (batches, None::<(StreamWriter<Vec<u8>>, SchemaRef)>),
+        |(mut batches, writer)| async move {
+            let Some((batch, metadata)) = batches.try_next().await? else {
+                // Terminates the stream with an end of stream marker
+                return match writer {
+                    Some((writer, _)) => {
+                        Ok(Some((Bytes::from(writer.into_inner()?), (batches, None))))
+                    }
+                    None => Ok(None),
+                };
+            };
+            let batch = append_metadata_columns(batch, &metadata)?;
+            let (mut writer, schema) = match writer {
+                Some((_, schema)) if schema != batch.schema() => {
+                    return Err(DataFusionError::Execution(
+                        "Results of the query have differing schemas, retrieve them as ndjson"
+                            .to_string(),
+                    ))
+                }
+                Some(writer) => writer,
+                None => (
+                    StreamWriter::try_new(vec![], &batch.schema())?,
+                    batch.schema(),
+                ),
+            };
+            writer.write(&batch)?;
+            // Only bytes the writer flushed are taken, so buffered ones follow them in order
+            let encoded = Bytes::from(std::mem::take(writer.get_mut()));
+            Ok(Some((encoded, (batches, Some((writer, schema))))))
+        },
+    )
+}
+
+/// Position of a page of the results of a query, which is passed back to the client as an
+/// opaque continuation token to retrieve the following page.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct PageToken {
+    pub request_id: Uuid,
+    /// Statement of a batch request the page belongs to, if only one is retrieved.
+    pub statement: Option<i32>,
+    /// Number of rows of the results before the page.
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl PageToken {
+    pub(crate) fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    pub(crate) fn decode(token: &str) -> Option<PageToken> {
+        URL_SAFE_NO_PAD
+            .decode(token)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+    }
+
+    /// The token of the page following this one.
+    pub(crate) fn next(&self) -> PageToken {
+        PageToken {
+            offset: self.offset.saturating_add(self.limit),
+            ..self.clone()
+        }
+    }
+}
+
+/// Response header holding the [PageToken] of the next page, if the results have more rows.
+pub(crate) const NEXT_PAGE_TOKEN_HEADER: &str = "x-next-page-token";
+
+/// Creates a HttpResponse::Ok().streaming(...) where the returned stream is all of the local and
+/// remote task results interleaved with additional injected metadata, serialized in
+/// response_format. NDJSON records nest the metadata, whereas Arrow and CSV append it as
+/// [METADATA_COLUMNS]. Results are read in the format of their request.
+///
+/// If page is set, only its rows are streamed. The results are then read one after another,
+/// ordered by task, rather than interleaved, and the [PageToken] of the next page is returned in
+/// the [NEXT_PAGE_TOKEN_HEADER] if any rows follow.
+///
+/// If replay_id is set, the tasks belong to that replayed request and their results are read
+/// from where replays are stored. If any result was deleted because its retention elapsed, the
+/// response is 410 Gone instead.
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn stream_all_task_results(
     db: &mut PgDb<'_>,
     local_fingerprint: &Arc<String>,
     result_manager: &Arc<ResultManager>,
-    tasks: Vec<QueryTask>,
-    flights: Vec<(QueryTaskRemote, FlightStream)>,
+    mut tasks: Vec<QueryTask>,
+    mut flights: Vec<(QueryTaskRemote, FlightStream)>,
+    replay_id: Option<Uuid>,
+    format: ResultFormat,
+    response_format: ResponseFormat,
+    page: Option<PageToken>,
 ) -> Result<HttpResponse> {
-    let rb_stream_converter =
-        |(batch, metadata)| async move { convert_rb_to_serialized_json_records(batch, metadata) };
-    let mut all_streams = Vec::with_capacity(tasks.len() + flights.len());
+    let expired = tasks
+        .iter()
+        .filter(|t| t.result_expired_at.is_some())
+        .count()
+        + flights
+            .iter()
+            .filter(|(_, f)| f.result_expired_at.is_some())
+            .count();
+    if expired > 0 {
+        return Ok(HttpResponse::Gone().json(format!(
+            "{expired} results of the query expired and were deleted, submit the query again!"
+        )));
+    }
+    if page.is_some() {
+        tasks.sort_by_key(|t| (t.statement_index, t.id));
+        flights.sort_by_key(|(_, f)| f.flight_id);
+    }
 
-    let local_relay = db.get_relay_by_x509_fingerprint(local_fingerprint).await?;
+    // The metadata, id, and relay it was received from of each result
+    let mut results = Vec::with_capacity(tasks.len() + flights.len());
+    let local_relay = identity_cache().get_relay(db, local_fingerprint).await?;
     for task in tasks {
         if matches!(task.status, QueryTaskStatus::Complete) {
             let data_source_id = task.data_source_id;
@@ -98,9 +327,7 @@ pub(crate) async fn stream_all_task_results(
                 "_source_relay_".to_string(),
                 serde_json::to_value(local_relay.id).map_err(|_e| {
                     error!("Failed to serde_json {:?}", local_relay.id);
-                    RelayError {
-                        msg: "Failed to serialize relay object".to_string(),
-                    }
+                    RelayError::new("Failed to serialize relay object")
                 })?,
             );
 
@@ -108,36 +335,30 @@ pub(crate) async fn stream_all_task_results(
                 "_source_id_".to_string(),
                 serde_json::to_value(data_source_id).map_err(|_e| {
                     error!("Failed to serde_json {data_source_id:?}");
-                    RelayError {
-                        msg: "Failed to serialize relay object".to_string(),
-                    }
+                    RelayError::new("Failed to serialize relay object")
                 })?,
             );
 
-            let metadata_arc = Arc::new(serde_json::Value::Object(metadata));
-            let inject_closure: Box<dyn Fn(RecordBatch) -> (RecordBatch, Arc<Value>)> =
-                Box::new(move |b| (b, metadata_arc.clone()));
-            all_streams.push(Box::pin(
-                result_manager
-                    .get_task_result(task.id)
-                    .await?
-                    .map_ok(inject_closure)
-                    .and_then(rb_stream_converter),
-            ));
+            if let Some(freshness) = task.freshness {
+                metadata.insert(
+                    "_freshness_".to_string(),
+                    Value::from(freshness.to_rfc3339()),
+                );
+            }
+            results.push((Value::Object(metadata), task.id, None));
         }
     }
 
-    for (_remote_task, flight) in flights {
+    for (remote_task, flight) in flights {
         if matches!(flight.status, FlightStreamStatus::Complete) {
+            let source_relay = db.get_relay_by_id(&remote_task.relay_id).await?.name;
             let data_source_id = flight.flight_id;
             let mut metadata = serde_json::Map::new();
             metadata.insert(
                 "_source_relay_".to_string(),
                 serde_json::to_value(&flight.remote_fingerprint).map_err(|_e| {
                     error!("Failed to serde_json {:?}", flight.remote_fingerprint);
-                    RelayError {
-                        msg: "Failed to serialize relay object".to_string(),
-                    }
+                    RelayError::new("Failed to serialize relay object")
                 })?,
             );
 
@@ -145,25 +366,181 @@ pub(crate) async fn stream_all_task_results(
                 "_source_id_".to_string(),
                 serde_json::to_value(data_source_id).map_err(|_e| {
                     error!("Failed to serde_json {data_source_id:?}");
-                    RelayError {
-                        msg: "Failed to serialize relay object".to_string(),
-                    }
+                    RelayError::new("Failed to serialize relay object")
                 })?,
             );
 
-            let metadata_arc = Arc::new(serde_json::Value::Object(metadata));
-            let inject_closure: Box<dyn Fn(RecordBatch) -> (RecordBatch, Arc<Value>)> =
-                Box::new(move |b| (b, metadata_arc.clone()));
-            all_streams.push(Box::pin(
+            if let Some(freshness) = flight.freshness {
+                metadata.insert(
+                    "_freshness_".to_string(),
+                    Value::from(freshness.to_rfc3339()),
+                );
+            }
+            results.push((
+                Value::Object(metadata),
+                flight.flight_id,
+                Some(source_relay),
+            ));
+        }
+    }
+
+    let mut all_streams = Vec::with_capacity(results.len());
+    let (mut offset, mut limit) = page.as_ref().map_or((0, 0), |p| (p.offset, p.limit));
+    let mut has_more = false;
+    for (metadata, result_id, source_relay) in results {
+        let source_relay = source_relay.as_deref();
+        let result = match (&page, &replay_id) {
+            (None, Some(replay_id)) => {
                 result_manager
-                    .get_task_result(flight.flight_id)
+                    .get_replay_result(replay_id, &result_id, format)
                     .await?
-                    .map_ok(inject_closure)
-                    .and_then(rb_stream_converter),
-            ));
+            }
+            (None, None) => {
+                result_manager
+                    .get_task_result(result_id, source_relay, format)
+                    .await?
+            }
+            // Once the page is full, results are only read to find out whether rows follow it
+            (Some(_), _) if limit == 0 && has_more => break,
+            (Some(_), _) => {
+                let (result, total_rows) = match &replay_id {
+                    Some(replay_id) => {
+                        result_manager
+                            .get_replay_result_page(replay_id, &result_id, format, offset, limit)
+                            .await?
+                    }
+                    None => {
+                        result_manager
+                            .get_task_result_page(result_id, source_relay, format, offset, limit)
+                            .await?
+                    }
+                };
+                let rows = total_rows.saturating_sub(offset);
+                let taken = rows.min(limit);
+                has_more |= rows > taken;
+                offset = offset.saturating_sub(total_rows);
+                limit -= taken;
+                if taken == 0 {
+                    continue;
+                }
+                result
+            }
+        };
+        let metadata_arc = Arc::new(metadata);
+        let inject_closure: Box<dyn Fn(RecordBatch) -> (RecordBatch, Arc<Value>)> =
+            Box::new(move |b| (b, metadata_arc.clone()));
+        all_streams.push(Box::pin(
+            result.inspect_ok(count_rest_bytes).map_ok(inject_closure),
+        ));
+    }
+
+    let merged_stream: Pin<Box<dyn Stream<Item = _>>> = match page {
+        Some(_) => Box::pin(futures::stream::iter(all_streams).flatten()),
+        None => Box::pin(futures::stream::select_all(all_streams)),
+    };
+    let mut response = HttpResponse::Ok();
+    response.content_type(response_format.content_type());
+    if let Some(page) = page.filter(|_| has_more) {
+        response.insert_header((NEXT_PAGE_TOKEN_HEADER, page.next().encode()));
+    }
+    Ok(match response_format {
+        ResponseFormat::Ndjson => {
+            response.streaming(merged_stream.and_then(|(batch, metadata)| async move {
+                convert_rb_to_serialized_json_records(batch, metadata)
+            }))
         }
+        ResponseFormat::Arrow => response.streaming(encode_arrow(merged_stream)),
+        ResponseFormat::Csv => response.streaming(encode_csv(merged_stream)),
+    })
+}
+
+/// Executes [LocalQuery]s directly rather than dispatching them to the query runners, and returns
+/// at most limit rows across all sources serialized as NDJSON records. Sources which are
+/// deferred or fail to execute are skipped so that a single broken source does not prevent a
+/// preview of the others.
+pub(crate) async fn preview_local_queries(
+    db: &mut PgDb<'_>,
+    queries: Vec<LocalQuery>,
+    limit: usize,
+) -> Result<Bytes> {
+    let mut executions = Vec::with_capacity(queries.len());
+    for local in queries {
+        if local.not_before.is_some() {
+            debug!(
+                "Skipping deferred source {} in preview",
+                local.data_source_id
+            );
+            continue;
+        }
+        let (con, source) = db.get_source_by_id(&local.data_source_id).await?;
+        let (con, source) = resolve_task_engine(db, con, source, local.engine.as_deref()).await?;
+        let data_source_id = local.data_source_id;
+        executions.push(async move {
+            let mut runner = try_connect(con, source).await?;
+            let batches: Vec<RecordBatch> = runner
+                .execute_stream(local.query, CancellationToken::new())
+                .await?
+                .try_collect()
+                .await?;
+            Ok::<_, MeshError>((data_source_id, batches))
+        });
     }
 
-    let merged_stream = futures::stream::select_all(all_streams);
-    Ok(HttpResponse::Ok().streaming(merged_stream))
+    let mut serialized = BytesMut::new();
+    let mut remaining = limit;
+    for result in futures::future::join_all(executions).await {
+        let (data_source_id, batches) = match result {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("Failed to execute preview with error: {e}");
+                continue;
+            }
+        };
+        let metadata = Arc::new(serde_json::json!({ "_source_id_": data_source_id }));
+        for batch in batches {
+            if remaining == 0 {
+                break;
+            }
+            let batch = batch.slice(0, batch.num_rows().min(remaining));
+            remaining -= batch.num_rows();
+            let records = convert_rb_to_serialized_json_records(batch, metadata.clone())
+                .map_err(|e| RelayError::new(&e.to_string()))?;
+            serialized.extend_from_slice(&records);
+        }
+    }
+    Ok(serialized.freeze())
+}
+
+/// Encodings accepted when uploading a scratch dataset
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum UploadFormat {
+    /// The Arrow IPC streaming format
+    #[default]
+    Arrow,
+    Parquet,
+}
+
+/// Decodes an uploaded dataset into its schema and [RecordBatch]es.
+pub(crate) fn decode_upload(
+    body: Bytes,
+    format: UploadFormat,
+) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+    let decode_err = |e: ArrowError| RelayError::new(&format!("Unable to decode upload: {e}"));
+    match format {
+        UploadFormat::Arrow => {
+            let reader = StreamReader::try_new(body.reader(), None).map_err(decode_err)?;
+            let schema = reader.schema();
+            let batches = reader.collect::<Result<Vec<_>, _>>().map_err(decode_err)?;
+            Ok((schema, batches))
+        }
+        UploadFormat::Parquet => {
+            let reader = ParquetRecordBatchReaderBuilder::try_new(body)
+                .and_then(|builder| builder.build())
+                .map_err(|e| RelayError::new(&format!("Unable to decode upload: {e}")))?;
+            let schema = reader.schema();
+            let batches = reader.collect::<Result<Vec<_>, _>>().map_err(decode_err)?;
+            Ok((schema, batches))
+        }
+    }
 }
@@ -1,6 +1,6 @@
 use actix_web::HttpRequest;
 
-use mesh::pki::{parse_certificate, parse_urlencoded_pemstr};
+use mesh::pki::{client_cert_header_names, parse_certificate, parse_client_cert_header};
 use rustls::Certificate;
 
 use crate::error::{RelayError, Result};
@@ -8,30 +8,30 @@ use crate::error::{RelayError, Result};
 /// Extracts client certificates from [HttpRequest], see [parse_certificate] for more information
 /// on the return values.
 pub(crate) fn parse_certs_direct_tls(req: HttpRequest) -> Result<(String, String, String)> {
-    let client_cert = req.conn_data::<Certificate>().ok_or(RelayError {
-        msg: "Got query request with no client cert!".into(),
-    })?;
+    let client_cert = req
+        .conn_data::<Certificate>()
+        .ok_or(RelayError::new("Got query request with no client cert!"))?;
 
     let (fingerprint, subject_dn, issuer_dn) = parse_certificate(client_cert)?;
     Ok((fingerprint, subject_dn, issuer_dn))
 }
 
-/// Extracts client certificates from [HttpRequest], see [parse_certificate] for more information
-/// on the return values.
+/// Extracts client certificates from [HttpRequest], using the first of the comma separated header
+/// names in client_cert_header which is present. See [parse_client_cert_header] for the supported
+/// formats, and [parse_certificate] for more information on the return values.
 pub(crate) fn parse_certs_from_header(
     req: HttpRequest,
-    client_cert_header: &String,
+    client_cert_header: &str,
 ) -> Result<(String, String, String)> {
-    let cert = req
-        .headers()
-        .get(client_cert_header)
+    let (name, cert) = client_cert_header_names(client_cert_header)
+        .find_map(|name| req.headers().get(name).map(|value| (name, value)))
         .ok_or(RelayError::new(
             "Specified client_cert_header is empty. Unable to authenticate client.",
         ))?;
     let inner = cert.to_str().map_err(|_e| {
         RelayError::new("Invalid client_cert_header value. Unable to authenticate client.")
     })?;
-    parse_urlencoded_pemstr(inner).map_err(|e| RelayError::new(&e.to_string()))
+    parse_client_cert_header(name, inner).map_err(|e| RelayError::new(&e.to_string()))
 }
 
 /// Extracts client certificates from [HttpRequest]. May extract certificate from passed header or direct TLS depending
@@ -1,69 +1,63 @@
 use flight_server_lib as flight;
+use mesh::execute::telemetry::{init_tracing, shutdown_tracing};
 use mesh::{conf::EnvConfigSettings, messaging::MessageBrokerOptions};
 use query_runner_lib as query_runner;
 use rest_server_lib as relay;
 use std::{thread, time::Duration};
 use tokio::runtime::Runtime;
+use tracing::info;
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    init_tracing("relay");
     let env_conf = EnvConfigSettings::init();
 
     // To use an in memory queue to communicate between threads, we must initialize
     // the queue now and pass clones to each thread. All other messaging types can
-    // be initialized later within the threads (e.g. connecting to external rabbitMQ).
+    // be initialized later within the threads (e.g. connecting to external rabbitMQ or the
+    // database task queue).
     let in_mem_messaging_opts = match &env_conf.msg_broker_opts {
         MessageBrokerOptions::AsyncChannel(_) => Some(env_conf.msg_broker_opts.clone()),
-        #[cfg(feature = "rabbitmq")]
         _ => None,
     };
     let relay_in_mem_opts = in_mem_messaging_opts.clone();
     let relay_task = thread::spawn(move || {
         let rt = Runtime::new().expect("error creating runtime!");
         rt.block_on(async { relay::run(relay_in_mem_opts).await })
+            .map_err(|e| format!("Relay shut down with error {e}"))
     });
 
     let flight_task = thread::spawn(|| {
         let rt = Runtime::new().expect("error creating runtime!");
         rt.block_on(async { flight::run().await })
+            .map_err(|e| format!("Flight server shut down with error {e}"))
     });
 
     let query_runner_in_mem_opts = in_mem_messaging_opts.clone();
     let query_runner_task = thread::spawn(move || {
         let rt = Runtime::new().expect("error creating runtime!");
         rt.block_on(async { query_runner::run(query_runner_in_mem_opts).await })
+            .map_err(|e| format!("QueryRunner shut down with error {:?}", e))
     });
 
-    loop {
-        if relay_task.is_finished() {
-            match relay_task.join() {
-                Ok(r) => match r {
-                    Ok(_) => (),
-                    Err(e) => panic!("Relay shut down with error {e}"),
-                },
-                Err(_e) => panic!("Relay shut down with unknown error"),
+    // Each service shuts down gracefully on SIGTERM or SIGINT, so once one of them exits without
+    // an error the others are waited for, as they are draining too.
+    let mut tasks = vec![
+        ("Relay", relay_task),
+        ("Flight server", flight_task),
+        ("QueryRunner", query_runner_task),
+    ];
+    while !tasks.is_empty() {
+        if let Some(i) = tasks.iter().position(|(_, task)| task.is_finished()) {
+            let (name, task) = tasks.remove(i);
+            match task.join() {
+                Ok(Ok(())) => info!("{name} shut down"),
+                Ok(Err(e)) => panic!("{e}"),
+                Err(_e) => panic!("{name} shut down with unknown error"),
             }
-            panic!("Relay shut down with no error!");
-        } else if flight_task.is_finished() {
-            match flight_task.join() {
-                Ok(r) => match r {
-                    Ok(_) => (),
-                    Err(e) => panic!("Flight server shut down with error {e}"),
-                },
-                Err(_e) => panic!("Flight server shut down with unknown error"),
-            }
-            panic!("Flight server shut down with no error!");
-        } else if query_runner_task.is_finished() {
-            match query_runner_task.join() {
-                Ok(r) => match r {
-                    Ok(_) => (),
-                    Err(e) => panic!("QueryRunner shut down with error {:?}", e),
-                },
-                Err(_e) => panic!("QueryRunner shut down with unknown error"),
-            }
-            panic!("QueryRunner shut down with no error!");
+            continue;
         }
         thread::sleep(Duration::from_secs(3));
     }
+    shutdown_tracing().await;
 }
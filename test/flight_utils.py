@@ -39,6 +39,27 @@ def read_endpoint(endpoint, cert, key, cacert):
     df["_source_id_"] = source_id
     return df
 
+def connect(relay_host, relay_port, access_level='default_access') -> FlightClient:
+    """
+    Connects to the flight endpoint of a relay with the client certificate
+    of the given access_level.
+    """
+    cert, key, cacert = read_certs(access_level)
+    return pa.flight.connect(
+        pa.flight.Location.for_grpc_tls(relay_host, relay_port),
+        tls_root_certs=cacert,
+        cert_chain=cert,
+        private_key=key,
+        )
+
+def do_action(client: FlightClient, action_type, body=b''):
+    """
+    Executes an action with a single result, returning the body of the result.
+    """
+    results = list(client.do_action(pa.flight.Action(action_type, body)))
+    assert len(results) == 1, f'Expected a single result, got {len(results)}'
+    return results[0].body.to_pybytes()
+
 def execute_query(query, relay_host, relay_port, access_level='default_access') -> pd.DataFrame:
     """
     Executes a query via a 2 step process. First, the query template
@@ -72,4 +93,77 @@ def execute_query(query, relay_host, relay_port, access_level='default_access')
     
     df = pd.concat(dfs)
     
-    return df
\ No newline at end of file
+    return df
+
+def execute_exchange(query, relay_host, relay_port, access_level='default_access') -> pd.DataFrame:
+    """
+    Executes a query with a single do_exchange call against the home relay,
+    which streams back the results of its own tasks. The last message of the
+    stream lists the endpoints at other relays as a FlightInfo in its
+    app_metadata, which are read via do_get as in execute_query.
+    """
+    cert, key, cacert = read_certs(access_level)
+    client = connect(relay_host, relay_port, access_level)
+    flight_desc = pa.flight.FlightDescriptor.for_command(json.dumps(query))
+    writer, reader = client.do_exchange(flight_desc)
+    writer.done_writing()
+
+    batches, app_metadata = [], None
+    while True:
+        try:
+            chunk = reader.read_chunk()
+        except StopIteration:
+            break
+        if chunk.data is not None:
+            batches.append(chunk.data)
+        if chunk.app_metadata is not None:
+            app_metadata = chunk.app_metadata.to_pybytes()
+    remote = pa.flight.FlightInfo.deserialize(app_metadata)
+
+    df = pa.Table.from_batches(batches, schema=reader.schema).to_pandas()
+    df["_source_relay_uri_"] = f"grpc+tls://{relay_host}:{relay_port}"
+    dfs = [df] + [read_endpoint(e, cert, key, cacert) for e in remote.endpoints]
+    return pd.concat(dfs)
+
+def _varint(value) -> bytes:
+    out = b''
+    while value > 0x7f:
+        out += bytes([(value & 0x7f) | 0x80])
+        value >>= 7
+    return out + bytes([value])
+
+def proto_field(number, value: bytes) -> bytes:
+    """
+    Encodes a length delimited protobuf field, i.e. a string, bytes or message.
+    """
+    return _varint(number << 3 | 2) + _varint(len(value)) + value
+
+def proto_fields(message: bytes) -> dict:
+    """
+    Decodes a protobuf message whose fields are all length delimited into a
+    map from field number to value.
+    """
+    fields = {}
+    pos = 0
+    while pos < len(message):
+        key, length = 0, 0
+        for shift in range(0, 64, 7):
+            key |= (message[pos] & 0x7f) << shift
+            pos += 1
+            if message[pos - 1] < 0x80:
+                break
+        for shift in range(0, 64, 7):
+            length |= (message[pos] & 0x7f) << shift
+            pos += 1
+            if message[pos - 1] < 0x80:
+                break
+        fields[key >> 3] = message[pos:pos + length]
+        pos += length
+    return fields
+
+def flight_sql_command(name, message=b'') -> bytes:
+    """
+    Packs an encoded FlightSQL message, e.g. CommandStatementQuery, as a protobuf Any.
+    """
+    type_url = f'type.googleapis.com/arrow.flight.protocol.sql.{name}'.encode()
+    return proto_field(1, type_url) + proto_field(2, message)
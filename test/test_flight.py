@@ -79,3 +79,82 @@ def test_flight_tpch_q1_na_data_relay_all_access():
     tpch1 = make_tpch_q1_query()
     df = execute_query(tpch1, 'localhost', 50055, 'all_access')
     validate_tpch_q1_all_access(df)
+
+def test_flight_get_schema_of_entity():
+    client = connect('localhost', 50055)
+    schema = client.get_schema(pa.flight.FlightDescriptor.for_path('lineitem')).schema
+    assert 'linenumber' in schema.names
+    assert 'tax_percent' in schema.names
+
+def test_flight_get_schema_of_query():
+    client = connect('localhost', 50055)
+    query = json.dumps(make_query1())
+    schema = client.get_schema(pa.flight.FlightDescriptor.for_command(query)).schema
+    assert schema.names == ['linenumber', 'tax_amount']
+
+def test_flight_list_entities_action():
+    client = connect('localhost', 50055)
+    entities = json.loads(do_action(client, 'ListEntities'))
+    assert 'lineitem' in entities
+    assert any(info['name'] == 'linenumber' for info in entities['lineitem'])
+
+def test_flight_cancel_query_action():
+    client = connect('localhost', 50055)
+    info = client.get_flight_info(pa.flight.FlightDescriptor.for_command(json.dumps(make_query2())))
+    request_id = json.loads(info.app_metadata)['request_id']
+    body = json.dumps({'request_id': request_id}).encode()
+    cancelled = json.loads(do_action(client, 'CancelQuery', body))
+    assert cancelled['tasks'] >= 0
+    try:
+        do_action(connect('localhost', 50055, 'all_access'), 'CancelQuery', body)
+        raise ShouldHaveFailedException
+    except ShouldHaveFailedException:
+        raise Exception("Cancelling the query of another user should have failed, but passed!")
+    except pa.flight.FlightError:
+        pass
+
+def test_flight_refresh_result_action():
+    client = connect('localhost', 50055)
+    info = client.get_flight_info(pa.flight.FlightDescriptor.for_command(json.dumps(make_query1())))
+    request_id = json.loads(info.app_metadata)['request_id']
+    body = json.dumps({'request_id': request_id}).encode()
+    refreshed = pa.flight.FlightInfo.deserialize(do_action(client, 'RefreshResult', body))
+    assert json.loads(refreshed.app_metadata)['request_id'] != request_id
+    cert, key, cacert = read_certs('default_access')
+    df = pd.concat([read_endpoint(e, cert, key, cacert) for e in refreshed.endpoints])
+    validate_query1(df)
+
+def test_flight_exchange_query1():
+    df = execute_exchange(make_query1(), 'localhost', 50055)
+    assert len(df)==53, f'expected 53 records found {len(df)}'
+    n_relays = df['_source_relay_uri_'].nunique()
+    assert n_relays==5, f'expected data from 5 relays found {n_relays}'
+
+def test_flight_sql_statement_query():
+    client = connect('localhost', 50055)
+    command = flight_sql_command('CommandStatementQuery', proto_field(1, make_query1()['sql'].encode()))
+    info = client.get_flight_info(pa.flight.FlightDescriptor.for_command(command))
+    assert info.schema.names == ['linenumber', 'tax_amount']
+    cert, key, cacert = read_certs('default_access')
+    df = pd.concat([read_endpoint(e, cert, key, cacert) for e in info.endpoints])
+    validate_query1(df)
+
+def test_flight_sql_prepared_statement():
+    client = connect('localhost', 50055)
+    request = flight_sql_command('ActionCreatePreparedStatementRequest', proto_field(1, make_query1()['sql'].encode()))
+    result = proto_fields(do_action(client, 'CreatePreparedStatement', request))
+    handle = proto_fields(result[2])[1]
+    command = flight_sql_command('CommandPreparedStatementQuery', proto_field(1, handle))
+    info = client.get_flight_info(pa.flight.FlightDescriptor.for_command(command))
+    cert, key, cacert = read_certs('default_access')
+    df = pd.concat([read_endpoint(e, cert, key, cacert) for e in info.endpoints])
+    validate_query1(df)
+    do_action(client, 'ClosePreparedStatement', flight_sql_command('ActionClosePreparedStatementRequest', proto_field(1, handle)))
+
+def test_flight_sql_get_tables():
+    client = connect('localhost', 50055)
+    command = flight_sql_command('CommandGetTables')
+    info = client.get_flight_info(pa.flight.FlightDescriptor.for_command(command))
+    tables = client.do_get(info.endpoints[0].ticket).read_all()
+    assert 'lineitem' in tables.column('table_name').to_pylist()
+    assert set(tables.column('table_type').to_pylist()) == {'TABLE'}
@@ -0,0 +1,378 @@
+use std::fmt::Debug;
+
+use arrow::datatypes::DataType;
+use datafusion::{
+    common::{not_impl_err, DataFusionError, Result},
+    logical_expr::BuiltinScalarFunction,
+    sql::sqlparser::ast::Ident,
+};
+
+/// How a dialect restricts the number of rows returned by a query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitStyle {
+    /// select ... limit n
+    Limit,
+    /// select top n ...
+    Top,
+}
+
+/// Describes how SQL is written for the engine which evaluates the queries generated from
+/// DataFusion [Expr][datafusion::logical_expr::Expr]s. Implement this trait to support engines
+/// such as MSSQL, Oracle or Trino. Every method has a default suited to most ANSI SQL engines.
+pub trait SqlWriterDialect: Debug + Send + Sync {
+    /// The character used to quote identifiers, or None to leave them unquoted.
+    fn identifier_quote_style(&self) -> Option<char> {
+        None
+    }
+
+    /// Returns the name of a built in scalar function in this dialect, or None if the dialect
+    /// cannot evaluate it, in which case filters using the function are not pushed down.
+    fn scalar_function_name(&self, fun: &BuiltinScalarFunction) -> Option<String> {
+        PORTABLE_FUNCTIONS.contains(fun).then(|| fun.to_string())
+    }
+
+    fn boolean_literal(&self, value: bool) -> String {
+        value.to_string()
+    }
+
+    fn limit_style(&self) -> LimitStyle {
+        LimitStyle::Limit
+    }
+
+    /// Writes a timestamp literal. The value is formatted as YYYY-MM-DD HH:MM:SS.f, followed by
+    /// +00:00 if with_time_zone is set.
+    fn timestamp_literal(&self, value: &str, with_time_zone: bool) -> String {
+        if with_time_zone {
+            format!("CAST('{value}' AS TIMESTAMP WITH TIME ZONE)")
+        } else {
+            format!("CAST('{value}' AS TIMESTAMP)")
+        }
+    }
+
+    /// Writes an interval literal, or returns None if the dialect cannot express the interval.
+    fn interval_literal(&self, months: i32, days: i32, nanos: i64) -> Option<String> {
+        Some(format!(
+            "INTERVAL '{months} months {days} days {} seconds'",
+            format_seconds(nanos)
+        ))
+    }
+
+    /// Overrides the SQL type name used for an Arrow [DataType]. None falls back to the default
+    /// ANSI type name, see [df_to_sql_data_type].
+    fn data_type_name(&self, _data_type: &DataType) -> Option<String> {
+        None
+    }
+
+    /// Whether the dialect understands TRY_CAST.
+    fn supports_try_cast(&self) -> bool {
+        false
+    }
+
+    /// Whether the dialect understands ILIKE for case insensitive pattern matching.
+    fn supports_ilike(&self) -> bool {
+        false
+    }
+}
+
+/// Returns the SQL type name of an Arrow [DataType] in the dialect, e.g. for CAST expressions.
+pub fn df_to_sql_data_type(dialect: &dyn SqlWriterDialect, data_type: &DataType) -> Result<String> {
+    if let Some(name) = dialect.data_type_name(data_type) {
+        return Ok(name);
+    }
+    let name = match data_type {
+        DataType::Boolean => "BOOLEAN".to_string(),
+        DataType::Int8 | DataType::Int16 | DataType::UInt8 => "SMALLINT".to_string(),
+        DataType::Int32 | DataType::UInt16 => "INTEGER".to_string(),
+        DataType::Int64 | DataType::UInt32 => "BIGINT".to_string(),
+        DataType::UInt64 => "DECIMAL(20, 0)".to_string(),
+        DataType::Float16 | DataType::Float32 => "REAL".to_string(),
+        DataType::Float64 => "DOUBLE PRECISION".to_string(),
+        DataType::Utf8 => "VARCHAR".to_string(),
+        DataType::LargeUtf8 => "TEXT".to_string(),
+        DataType::Decimal128(precision, scale) | DataType::Decimal256(precision, scale) => {
+            format!("DECIMAL({precision}, {scale})")
+        }
+        DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_) => {
+            "VARBINARY".to_string()
+        }
+        DataType::Date32 | DataType::Date64 => "DATE".to_string(),
+        DataType::Time32(_) | DataType::Time64(_) => "TIME".to_string(),
+        DataType::Timestamp(_, None) => "TIMESTAMP".to_string(),
+        DataType::Timestamp(_, Some(_)) => "TIMESTAMP WITH TIME ZONE".to_string(),
+        _ => return not_impl_err!("Data type {data_type} has no SQL equivalent in {dialect:?}"),
+    };
+    Ok(name)
+}
+
+/// Creates an [Ident] quoted as required by the dialect.
+pub fn new_ident(dialect: &dyn SqlWriterDialect, name: &str) -> Ident {
+    match dialect.identifier_quote_style() {
+        Some(quote) => Ident::with_quote(quote, name),
+        None => Ident::new(name),
+    }
+}
+
+/// Formats nanoseconds as seconds with a fractional part, e.g. -1.500000000
+pub fn format_seconds(nanos: i64) -> String {
+    let sign = if nanos < 0 { "-" } else { "" };
+    let nanos = nanos.unsigned_abs();
+    format!(
+        "{sign}{}.{:09}",
+        nanos / 1_000_000_000,
+        nanos % 1_000_000_000
+    )
+}
+
+/// Writes a select statement in the dialect. The filter is either empty or a complete WHERE clause.
+pub fn select_sql(
+    dialect: &dyn SqlWriterDialect,
+    projection: &str,
+    table: &str,
+    filter: &str,
+    limit: Option<usize>,
+) -> String {
+    match (limit, dialect.limit_style()) {
+        (Some(l), LimitStyle::Limit) => {
+            format!("select {projection} from {table} {filter} limit {l}")
+        }
+        (Some(l), LimitStyle::Top) => format!("select top {l} {projection} from {table} {filter}"),
+        (None, _) => format!("select {projection} from {table} {filter}"),
+    }
+}
+
+/// Relays plan queries with DataFusion, so every built in function is understood as-is.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DataFusionDialect;
+
+impl SqlWriterDialect for DataFusionDialect {
+    fn scalar_function_name(&self, fun: &BuiltinScalarFunction) -> Option<String> {
+        Some(fun.to_string())
+    }
+
+    fn supports_try_cast(&self) -> bool {
+        true
+    }
+
+    fn supports_ilike(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PostgresDialect;
+
+impl SqlWriterDialect for PostgresDialect {
+    fn identifier_quote_style(&self) -> Option<char> {
+        Some('"')
+    }
+
+    fn scalar_function_name(&self, fun: &BuiltinScalarFunction) -> Option<String> {
+        match fun {
+            BuiltinScalarFunction::Signum => Some("sign".to_string()),
+            BuiltinScalarFunction::Log10 => Some("log".to_string()),
+            BuiltinScalarFunction::Log2 => None,
+            BuiltinScalarFunction::Uuid => Some("gen_random_uuid".to_string()),
+            BuiltinScalarFunction::FromUnixtime => Some("to_timestamp".to_string()),
+            _ => PORTABLE_FUNCTIONS.contains(fun).then(|| fun.to_string()),
+        }
+    }
+
+    fn data_type_name(&self, data_type: &DataType) -> Option<String> {
+        match data_type {
+            DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_) => {
+                Some("BYTEA".to_string())
+            }
+            _ => None,
+        }
+    }
+
+    fn supports_ilike(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MySqlDialect;
+
+impl SqlWriterDialect for MySqlDialect {
+    fn identifier_quote_style(&self) -> Option<char> {
+        Some('`')
+    }
+
+    fn scalar_function_name(&self, fun: &BuiltinScalarFunction) -> Option<String> {
+        match fun {
+            BuiltinScalarFunction::Signum => Some("sign".to_string()),
+            BuiltinScalarFunction::Random => Some("rand".to_string()),
+            BuiltinScalarFunction::Chr => Some("char".to_string()),
+            BuiltinScalarFunction::ToHex => Some("hex".to_string()),
+            BuiltinScalarFunction::Btrim
+            | BuiltinScalarFunction::Strpos
+            | BuiltinScalarFunction::SplitPart
+            | BuiltinScalarFunction::StartsWith
+            | BuiltinScalarFunction::InitCap
+            | BuiltinScalarFunction::Translate
+            | BuiltinScalarFunction::DateTrunc
+            | BuiltinScalarFunction::DatePart
+            | BuiltinScalarFunction::RegexpMatch => None,
+            _ => PORTABLE_FUNCTIONS.contains(fun).then(|| fun.to_string()),
+        }
+    }
+
+    /// MySQL only casts to a small set of types.
+    fn data_type_name(&self, data_type: &DataType) -> Option<String> {
+        let name = match data_type {
+            DataType::Boolean
+            | DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64 => "SIGNED",
+            DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => "UNSIGNED",
+            DataType::Float16 | DataType::Float32 => "FLOAT",
+            DataType::Float64 => "DOUBLE",
+            DataType::Utf8 | DataType::LargeUtf8 => "CHAR",
+            DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_) => "BINARY",
+            DataType::Timestamp(_, _) => "DATETIME(6)",
+            _ => return None,
+        };
+        Some(name.to_string())
+    }
+
+    /// MySQL has no time zone aware timestamp type, so instants are compared as UTC DATETIMEs.
+    fn timestamp_literal(&self, value: &str, _with_time_zone: bool) -> String {
+        let value = value.trim_end_matches("+00:00");
+        format!("CAST('{value}' AS DATETIME(6))")
+    }
+
+    /// MySQL intervals have a single unit.
+    fn interval_literal(&self, months: i32, days: i32, nanos: i64) -> Option<String> {
+        match (months, days, nanos) {
+            (m, 0, 0) => Some(format!("INTERVAL {m} MONTH")),
+            (0, d, 0) => Some(format!("INTERVAL {d} DAY")),
+            (0, 0, n) if n % 1_000 == 0 => Some(format!("INTERVAL {} MICROSECOND", n / 1_000)),
+            _ => None,
+        }
+    }
+}
+
+/// Built in scalar functions which are understood with the same name and arguments by every
+/// SQL engine. Dialects other than [DataFusionDialect] push down only these by default.
+const PORTABLE_FUNCTIONS: &[BuiltinScalarFunction] = &[
+    BuiltinScalarFunction::Abs,
+    BuiltinScalarFunction::Acos,
+    BuiltinScalarFunction::Asin,
+    BuiltinScalarFunction::Atan,
+    BuiltinScalarFunction::Atan2,
+    BuiltinScalarFunction::Ceil,
+    BuiltinScalarFunction::Cos,
+    BuiltinScalarFunction::Cot,
+    BuiltinScalarFunction::Degrees,
+    BuiltinScalarFunction::Exp,
+    BuiltinScalarFunction::Floor,
+    BuiltinScalarFunction::Ln,
+    BuiltinScalarFunction::Log,
+    BuiltinScalarFunction::Log10,
+    BuiltinScalarFunction::Log2,
+    BuiltinScalarFunction::Pi,
+    BuiltinScalarFunction::Power,
+    BuiltinScalarFunction::Radians,
+    BuiltinScalarFunction::Random,
+    BuiltinScalarFunction::Round,
+    BuiltinScalarFunction::Signum,
+    BuiltinScalarFunction::Sin,
+    BuiltinScalarFunction::Sqrt,
+    BuiltinScalarFunction::Tan,
+    BuiltinScalarFunction::Trunc,
+    BuiltinScalarFunction::Coalesce,
+    BuiltinScalarFunction::NullIf,
+    BuiltinScalarFunction::Ascii,
+    BuiltinScalarFunction::BitLength,
+    BuiltinScalarFunction::Btrim,
+    BuiltinScalarFunction::CharacterLength,
+    BuiltinScalarFunction::Chr,
+    BuiltinScalarFunction::Concat,
+    BuiltinScalarFunction::ConcatWithSeparator,
+    BuiltinScalarFunction::InitCap,
+    BuiltinScalarFunction::Left,
+    BuiltinScalarFunction::Lower,
+    BuiltinScalarFunction::Lpad,
+    BuiltinScalarFunction::Ltrim,
+    BuiltinScalarFunction::MD5,
+    BuiltinScalarFunction::OctetLength,
+    BuiltinScalarFunction::Repeat,
+    BuiltinScalarFunction::Replace,
+    BuiltinScalarFunction::Reverse,
+    BuiltinScalarFunction::Right,
+    BuiltinScalarFunction::Rpad,
+    BuiltinScalarFunction::Rtrim,
+    BuiltinScalarFunction::SplitPart,
+    BuiltinScalarFunction::StartsWith,
+    BuiltinScalarFunction::Strpos,
+    BuiltinScalarFunction::Substr,
+    BuiltinScalarFunction::ToHex,
+    BuiltinScalarFunction::Translate,
+    BuiltinScalarFunction::Trim,
+    BuiltinScalarFunction::Upper,
+    BuiltinScalarFunction::Uuid,
+    BuiltinScalarFunction::RegexpMatch,
+    BuiltinScalarFunction::RegexpReplace,
+    BuiltinScalarFunction::Now,
+    BuiltinScalarFunction::DateTrunc,
+    BuiltinScalarFunction::DatePart,
+    BuiltinScalarFunction::FromUnixtime,
+];
+
+#[cfg(test)]
+mod tests {
+    use datafusion::logical_expr::BuiltinScalarFunction;
+
+    use super::{
+        new_ident, select_sql, DataFusionDialect, LimitStyle, MySqlDialect, PostgresDialect,
+        SqlWriterDialect,
+    };
+
+    /// A custom dialect, as a user would declare for e.g. MSSQL.
+    #[derive(Debug)]
+    struct MsSql;
+
+    impl SqlWriterDialect for MsSql {
+        fn identifier_quote_style(&self) -> Option<char> {
+            Some('[')
+        }
+
+        fn boolean_literal(&self, value: bool) -> String {
+            if value { "1" } else { "0" }.to_string()
+        }
+
+        fn limit_style(&self) -> LimitStyle {
+            LimitStyle::Top
+        }
+    }
+
+    #[test]
+    fn test_dialects() {
+        assert_eq!(new_ident(&DataFusionDialect, "Name").to_string(), "Name");
+        assert_eq!(new_ident(&PostgresDialect, "Name").to_string(), "\"Name\"");
+        assert_eq!(new_ident(&MySqlDialect, "Name").to_string(), "`Name`");
+        assert_eq!(new_ident(&MsSql, "Name").to_string(), "[Name]");
+
+        assert_eq!(
+            select_sql(&PostgresDialect, "a", "t", "", Some(10)).trim(),
+            "select a from t  limit 10"
+        );
+        assert_eq!(
+            select_sql(&MsSql, "a", "t", "", Some(10)).trim(),
+            "select top 10 a from t"
+        );
+        assert_eq!(MsSql.boolean_literal(true), "1");
+
+        let fun = BuiltinScalarFunction::ArrowTypeof;
+        assert!(DataFusionDialect.scalar_function_name(&fun).is_some());
+        assert!(MsSql.scalar_function_name(&fun).is_none());
+        assert_eq!(
+            MySqlDialect
+                .scalar_function_name(&BuiltinScalarFunction::Upper)
+                .unwrap(),
+            "upper"
+        );
+    }
+}
@@ -1,20 +1,30 @@
-use arrow::datatypes::SchemaRef;
+use std::sync::Arc;
+
+use arrow::datatypes::{IntervalDayTimeType, IntervalMonthDayNanoType, SchemaRef};
 
 use chrono::{DateTime, NaiveDate};
 use datafusion::{
     common::{not_impl_err, Result},
-    logical_expr::Expr,
+    logical_expr::{
+        expr::{Between, Cast, InList, Like, ScalarFunction, ScalarUDF, TryCast},
+        Expr,
+    },
     scalar::ScalarValue,
-    sql::sqlparser::ast::Ident,
 };
 
 use datafusion::common::DataFusionError;
 use tracing::info;
 
-pub fn map_filter_exprs(entity_name: &str, filters: &[Expr]) -> String {
+use crate::dialect::{df_to_sql_data_type, new_ident, SqlWriterDialect};
+
+pub fn map_filter_exprs(
+    entity_name: &str,
+    filters: &[Expr],
+    dialect: &dyn SqlWriterDialect,
+) -> String {
     let sql_exprs = filters
         .iter()
-        .filter_map(|f| match filter_expr_to_sql(entity_name, f) {
+        .filter_map(|f| match filter_expr_to_sql(entity_name, f, dialect) {
             Ok(s) => Some(s),
             Err(e) => {
                 info!("Failed to push down filter expr {f} with error {e}");
@@ -29,69 +39,158 @@ pub fn map_filter_exprs(entity_name: &str, filters: &[Expr]) -> String {
     }
 }
 
-pub fn filter_expr_to_sql(entity_name: &str, filter: &Expr) -> Result<String> {
+pub fn filter_expr_to_sql(
+    entity_name: &str,
+    filter: &Expr,
+    dialect: &dyn SqlWriterDialect,
+) -> Result<String> {
     match filter {
         Expr::Alias(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
         Expr::Column(col) => {
             let expr = datafusion::sql::sqlparser::ast::Expr::CompoundIdentifier(vec![
-                Ident::new(entity_name),
-                Ident::new(&col.name),
+                new_ident(dialect, entity_name),
+                new_ident(dialect, &col.name),
             ]);
             Ok(format!("{}", expr))
         }
         Expr::ScalarVariable(_, _) => not_impl_err!("Got unsupported filter Expr {filter}"),
-        Expr::Literal(lit) => scalar_value_to_sql(lit),
+        Expr::Literal(lit) => scalar_value_to_sql(lit, dialect),
         Expr::BinaryExpr(expr) => Ok(format!(
             "({} {} {})",
-            filter_expr_to_sql(entity_name, expr.left.as_ref())?,
+            filter_expr_to_sql(entity_name, expr.left.as_ref(), dialect)?,
             expr.op,
-            filter_expr_to_sql(entity_name, expr.right.as_ref())?
+            filter_expr_to_sql(entity_name, expr.right.as_ref(), dialect)?
         )),
-        Expr::Like(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
+        Expr::Like(Like {
+            negated,
+            expr,
+            pattern,
+            escape_char,
+            case_insensitive,
+        }) => {
+            if *case_insensitive && !dialect.supports_ilike() {
+                return not_impl_err!(
+                    "Got unsupported filter Expr {filter} for dialect {dialect:?}"
+                );
+            }
+            let op = match (*negated, *case_insensitive) {
+                (false, false) => "LIKE",
+                (true, false) => "NOT LIKE",
+                (false, true) => "ILIKE",
+                (true, true) => "NOT ILIKE",
+            };
+            let escape = match escape_char {
+                Some('\'') => " ESCAPE ''''".to_string(),
+                Some(c) => format!(" ESCAPE '{c}'"),
+                None => "".to_string(),
+            };
+            Ok(format!(
+                "({} {op} {}{escape})",
+                filter_expr_to_sql(entity_name, expr.as_ref(), dialect)?,
+                filter_expr_to_sql(entity_name, pattern.as_ref(), dialect)?
+            ))
+        }
         Expr::SimilarTo(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
         Expr::Not(expr) => Ok(format!(
             "(NOT {})",
-            filter_expr_to_sql(entity_name, expr.as_ref())?
+            filter_expr_to_sql(entity_name, expr.as_ref(), dialect)?
         )),
         Expr::IsNotNull(expr) => Ok(format!(
             "({} IS NOT NULL)",
-            filter_expr_to_sql(entity_name, expr.as_ref())?
+            filter_expr_to_sql(entity_name, expr.as_ref(), dialect)?
         )),
         Expr::IsNull(expr) => Ok(format!(
             "({} IS NULL)",
-            filter_expr_to_sql(entity_name, expr.as_ref())?
+            filter_expr_to_sql(entity_name, expr.as_ref(), dialect)?
         )),
         Expr::IsTrue(expr) => Ok(format!(
             "({} IS TRUE)",
-            filter_expr_to_sql(entity_name, expr.as_ref())?
+            filter_expr_to_sql(entity_name, expr.as_ref(), dialect)?
         )),
         Expr::IsFalse(expr) => Ok(format!(
             "({} IS FALSE)",
-            filter_expr_to_sql(entity_name, expr.as_ref())?
+            filter_expr_to_sql(entity_name, expr.as_ref(), dialect)?
         )),
         Expr::IsUnknown(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
         Expr::IsNotTrue(expr) => Ok(format!(
             "({} IS NOT TRUE)",
-            filter_expr_to_sql(entity_name, expr.as_ref())?
+            filter_expr_to_sql(entity_name, expr.as_ref(), dialect)?
         )),
         Expr::IsNotFalse(expr) => Ok(format!(
             "({} IS NOT FALSE)",
-            filter_expr_to_sql(entity_name, expr.as_ref())?
+            filter_expr_to_sql(entity_name, expr.as_ref(), dialect)?
         )),
         Expr::IsNotUnknown(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
         Expr::Negative(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
         Expr::GetIndexedField(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
-        Expr::Between(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
+        Expr::Between(Between {
+            expr,
+            negated,
+            low,
+            high,
+        }) => Ok(format!(
+            "({} {}BETWEEN {} AND {})",
+            filter_expr_to_sql(entity_name, expr.as_ref(), dialect)?,
+            if *negated { "NOT " } else { "" },
+            filter_expr_to_sql(entity_name, low.as_ref(), dialect)?,
+            filter_expr_to_sql(entity_name, high.as_ref(), dialect)?
+        )),
         Expr::Case(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
-        Expr::Cast(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
+        Expr::Cast(Cast { expr, data_type }) => Ok(format!(
+            "CAST({} AS {})",
+            filter_expr_to_sql(entity_name, expr.as_ref(), dialect)?,
+            df_to_sql_data_type(dialect, data_type)?
+        )),
+        Expr::TryCast(TryCast { expr, data_type }) if dialect.supports_try_cast() => Ok(format!(
+            "TRY_CAST({} AS {})",
+            filter_expr_to_sql(entity_name, expr.as_ref(), dialect)?,
+            df_to_sql_data_type(dialect, data_type)?
+        )),
         Expr::TryCast(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
         Expr::Sort(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
-        Expr::ScalarFunction(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
-        Expr::ScalarUDF(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
+        Expr::ScalarFunction(ScalarFunction { fun, args }) => {
+            let Some(name) = dialect.scalar_function_name(fun) else {
+                return not_impl_err!("Got unsupported function {fun} for dialect {dialect:?}");
+            };
+            Ok(format!(
+                "{}({})",
+                name,
+                args.iter()
+                    .map(|arg| filter_expr_to_sql(entity_name, arg, dialect))
+                    .collect::<Result<Vec<_>>>()?
+                    .join(", ")
+            ))
+        }
+        Expr::ScalarUDF(ScalarUDF { fun, args }) => Ok(format!(
+            "{}({})",
+            fun.name,
+            args.iter()
+                .map(|arg| filter_expr_to_sql(entity_name, arg, dialect))
+                .collect::<Result<Vec<_>>>()?
+                .join(", ")
+        )),
         Expr::AggregateFunction(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
         Expr::WindowFunction(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
         Expr::AggregateUDF(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
-        Expr::InList(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
+        Expr::InList(InList {
+            expr,
+            list,
+            negated,
+        }) => {
+            // An empty list is not valid SQL, but is trivially false (or true when negated).
+            if list.is_empty() {
+                return Ok(dialect.boolean_literal(*negated));
+            }
+            Ok(format!(
+                "({} {}IN ({}))",
+                filter_expr_to_sql(entity_name, expr.as_ref(), dialect)?,
+                if *negated { "NOT " } else { "" },
+                list.iter()
+                    .map(|item| filter_expr_to_sql(entity_name, item, dialect))
+                    .collect::<Result<Vec<_>>>()?
+                    .join(", ")
+            ))
+        }
         Expr::Exists(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
         Expr::InSubquery(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
         Expr::ScalarSubquery(_) => not_impl_err!("Got unsupported filter Expr {filter}"),
@@ -121,10 +220,13 @@ fn primative_option_to_string<T: std::fmt::Display + std::fmt::Debug>(
     }
 }
 
-fn scalar_value_to_sql(val: &ScalarValue) -> Result<String> {
+fn scalar_value_to_sql(val: &ScalarValue, dialect: &dyn SqlWriterDialect) -> Result<String> {
     match val {
         ScalarValue::Null => not_impl_err!("Got unsupported ScalarValue {val}"),
-        ScalarValue::Boolean(b) => primative_option_to_string(b, false),
+        ScalarValue::Boolean(b) => match b {
+            Some(b) => Ok(dialect.boolean_literal(*b)),
+            None => not_impl_err!("Got unsupported 'None' ScalarValue {val}"),
+        },
         ScalarValue::Float32(f) => primative_option_to_string(f, false),
         ScalarValue::Float64(f) => primative_option_to_string(f, false),
         ScalarValue::Decimal128(_, _, _) => not_impl_err!("Got unsupported ScalarValue {val}"),
@@ -168,40 +270,265 @@ fn scalar_value_to_sql(val: &ScalarValue) -> Result<String> {
         ScalarValue::Time32Millisecond(_) => not_impl_err!("Got unsupported ScalarValue {val}"),
         ScalarValue::Time64Microsecond(_) => not_impl_err!("Got unsupported ScalarValue {val}"),
         ScalarValue::Time64Nanosecond(_) => not_impl_err!("Got unsupported ScalarValue {val}"),
-        ScalarValue::TimestampSecond(_, _) => not_impl_err!("Got unsupported ScalarValue {val}"),
-        ScalarValue::TimestampMillisecond(_, _) => {
-            not_impl_err!("Got unsupported ScalarValue {val}")
-        }
-        ScalarValue::TimestampMicrosecond(_, _) => {
-            not_impl_err!("Got unsupported ScalarValue {val}")
-        }
-        ScalarValue::TimestampNanosecond(_, _) => {
-            not_impl_err!("Got unsupported ScalarValue {val}")
-        }
-        ScalarValue::IntervalYearMonth(_) => not_impl_err!("Got unsupported ScalarValue {val}"),
-        ScalarValue::IntervalDayTime(_) => not_impl_err!("Got unsupported ScalarValue {val}"),
-        ScalarValue::IntervalMonthDayNano(_) => not_impl_err!("Got unsupported ScalarValue {val}"),
-        ScalarValue::DurationSecond(_) => not_impl_err!("Got unsupported ScalarValue {val}"),
-        ScalarValue::DurationMillisecond(_) => not_impl_err!("Got unsupported ScalarValue {val}"),
-        ScalarValue::DurationMicrosecond(_) => not_impl_err!("Got unsupported ScalarValue {val}"),
-        ScalarValue::DurationNanosecond(_) => not_impl_err!("Got unsupported ScalarValue {val}"),
+        ScalarValue::TimestampSecond(ts, tz) => {
+            timestamp_to_sql(ts.map(|s| s * 1_000_000_000), tz, val, dialect)
+        }
+        ScalarValue::TimestampMillisecond(ts, tz) => {
+            timestamp_to_sql(ts.map(|ms| ms * 1_000_000), tz, val, dialect)
+        }
+        ScalarValue::TimestampMicrosecond(ts, tz) => {
+            timestamp_to_sql(ts.map(|us| us * 1_000), tz, val, dialect)
+        }
+        ScalarValue::TimestampNanosecond(ts, tz) => timestamp_to_sql(*ts, tz, val, dialect),
+        ScalarValue::IntervalYearMonth(i) => {
+            interval_to_sql(i.map(|months| (months, 0, 0)), val, dialect)
+        }
+        ScalarValue::IntervalDayTime(i) => interval_to_sql(
+            i.map(|i| {
+                let (days, ms) = IntervalDayTimeType::to_parts(i);
+                (0, days, ms as i64 * 1_000_000)
+            }),
+            val,
+            dialect,
+        ),
+        ScalarValue::IntervalMonthDayNano(i) => {
+            interval_to_sql(i.map(IntervalMonthDayNanoType::to_parts), val, dialect)
+        }
+        ScalarValue::DurationSecond(d) => {
+            interval_to_sql(d.map(|s| (0, 0, s * 1_000_000_000)), val, dialect)
+        }
+        ScalarValue::DurationMillisecond(d) => {
+            interval_to_sql(d.map(|ms| (0, 0, ms * 1_000_000)), val, dialect)
+        }
+        ScalarValue::DurationMicrosecond(d) => {
+            interval_to_sql(d.map(|us| (0, 0, us * 1_000)), val, dialect)
+        }
+        ScalarValue::DurationNanosecond(d) => interval_to_sql(d.map(|ns| (0, 0, ns)), val, dialect),
         ScalarValue::Struct(_, _) => not_impl_err!("Got unsupported ScalarValue {val}"),
         ScalarValue::Dictionary(_, _) => not_impl_err!("Got unsupported ScalarValue {val}"),
     }
 }
 
+/// Writes a timestamp given in nanoseconds since the epoch. Timestamps with a time zone are
+/// instants, so they are written in UTC with an explicit offset.
+fn timestamp_to_sql(
+    nanos: Option<i64>,
+    tz: &Option<Arc<str>>,
+    val: &ScalarValue,
+    dialect: &dyn SqlWriterDialect,
+) -> Result<String> {
+    let Some(nanos) = nanos else {
+        return not_impl_err!("Got unsupported 'None' ScalarValue {val}");
+    };
+    let datetime = DateTime::from_timestamp(
+        nanos.div_euclid(1_000_000_000),
+        nanos.rem_euclid(1_000_000_000) as u32,
+    )
+    .ok_or(DataFusionError::NotImplemented(format!(
+        "Timestamp overflow error for {val}"
+    )))?;
+    let value = datetime.format("%Y-%m-%d %H:%M:%S%.f").to_string();
+    Ok(match tz {
+        Some(_) => dialect.timestamp_literal(&format!("{value}+00:00"), true),
+        None => dialect.timestamp_literal(&value, false),
+    })
+}
+
+/// Writes an interval given as (months, days, nanoseconds).
+fn interval_to_sql(
+    parts: Option<(i32, i32, i64)>,
+    val: &ScalarValue,
+    dialect: &dyn SqlWriterDialect,
+) -> Result<String> {
+    let Some((months, days, nanos)) = parts else {
+        return not_impl_err!("Got unsupported 'None' ScalarValue {val}");
+    };
+    match dialect.interval_literal(months, days, nanos) {
+        Some(sql) => Ok(sql),
+        None => not_impl_err!("Got unsupported interval {val} for dialect {dialect:?}"),
+    }
+}
+
 /// Computes the appropriate projection string for a given projected [SchemaRef]
-pub fn map_projection(entity_name: &str, projected_schema: SchemaRef) -> String {
+pub fn map_projection(
+    entity_name: &str,
+    projected_schema: SchemaRef,
+    dialect: &dyn SqlWriterDialect,
+) -> String {
     projected_schema
         .fields()
         .iter()
         .map(|f| {
             let expr = datafusion::sql::sqlparser::ast::Expr::CompoundIdentifier(vec![
-                Ident::new(entity_name),
-                Ident::new(f.name()),
+                new_ident(dialect, entity_name),
+                new_ident(dialect, f.name()),
             ]);
             format!("{expr}")
         })
         .collect::<Vec<_>>()
         .join(", ")
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::datatypes::{DataType, Field, TimeUnit};
+    use datafusion::common::Result;
+    use datafusion::logical_expr::expr::Like;
+    use datafusion::logical_expr::{cast, col, lit, try_cast, BuiltinScalarFunction, Expr};
+    use datafusion::prelude::{coalesce, date_trunc, upper};
+    use datafusion::scalar::ScalarValue;
+
+    use crate::dialect::{DataFusionDialect, MySqlDialect, PostgresDialect};
+
+    use super::filter_expr_to_sql;
+
+    #[test]
+    fn test_scalar_functions_by_dialect() -> Result<()> {
+        let filter = upper(col("name")).eq(lit("A"));
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &DataFusionDialect)?,
+            "(upper(e.name) = 'A')"
+        );
+
+        let filter = coalesce(vec![col("a"), lit(1_i64)]).gt(lit(0_i64));
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &PostgresDialect)?,
+            "(coalesce(\"e\".\"a\", 1) > 0)"
+        );
+
+        let filter = Expr::ScalarFunction(datafusion::logical_expr::expr::ScalarFunction::new(
+            BuiltinScalarFunction::Signum,
+            vec![col("a")],
+        ))
+        .eq(lit(1_i64));
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &MySqlDialect)?,
+            "(sign(`e`.`a`) = 1)"
+        );
+
+        let filter = date_trunc(lit("day"), col("ts")).is_not_null();
+        assert!(filter_expr_to_sql("e", &filter, &PostgresDialect).is_ok());
+        assert!(filter_expr_to_sql("e", &filter, &MySqlDialect).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_predicates() -> Result<()> {
+        let filter = col("a").in_list(vec![lit(1_i64), lit(2_i64)], false);
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &DataFusionDialect)?,
+            "(e.a IN (1, 2))"
+        );
+        let filter = col("a").in_list(vec![lit("x")], true);
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &PostgresDialect)?,
+            "(\"e\".\"a\" NOT IN ('x'))"
+        );
+        let filter = col("a").in_list(vec![], false);
+        assert_eq!(filter_expr_to_sql("e", &filter, &MySqlDialect)?, "false");
+
+        let filter = col("a").between(lit(1_i64), lit(10_i64));
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &DataFusionDialect)?,
+            "(e.a BETWEEN 1 AND 10)"
+        );
+        let filter = col("a").not_between(lit(1_i64), lit(10_i64));
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &DataFusionDialect)?,
+            "(e.a NOT BETWEEN 1 AND 10)"
+        );
+
+        let filter = col("a").like(lit("ab%")).and(col("b").not_like(lit("%c")));
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &MySqlDialect)?,
+            "((`e`.`a` LIKE 'ab%') AND (`e`.`b` NOT LIKE '%c'))"
+        );
+        let filter = Expr::Like(Like::new(
+            false,
+            Box::new(col("a")),
+            Box::new(lit("a!%%")),
+            Some('!'),
+            true,
+        ));
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &PostgresDialect)?,
+            "(\"e\".\"a\" ILIKE 'a!%%' ESCAPE '!')"
+        );
+        assert!(filter_expr_to_sql("e", &filter, &MySqlDialect).is_err());
+
+        let filter = Expr::Not(Box::new(col("a").is_null()));
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &DataFusionDialect)?,
+            "(NOT (e.a IS NULL))"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_casts() -> Result<()> {
+        let filter = cast(col("a"), DataType::Utf8).eq(lit("1"));
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &DataFusionDialect)?,
+            "(CAST(e.a AS VARCHAR) = '1')"
+        );
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &MySqlDialect)?,
+            "(CAST(`e`.`a` AS CHAR) = '1')"
+        );
+        let filter = cast(col("a"), DataType::Decimal128(10, 2)).gt(lit(1_i64));
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &PostgresDialect)?,
+            "(CAST(\"e\".\"a\" AS DECIMAL(10, 2)) > 1)"
+        );
+        let filter =
+            try_cast(col("a"), DataType::Timestamp(TimeUnit::Microsecond, None)).is_not_null();
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &DataFusionDialect)?,
+            "(TRY_CAST(e.a AS TIMESTAMP) IS NOT NULL)"
+        );
+        assert!(filter_expr_to_sql("e", &filter, &PostgresDialect).is_err());
+        let list = DataType::List(Arc::new(Field::new("item", DataType::Int64, true)));
+        assert!(
+            filter_expr_to_sql("e", &cast(col("a"), list).is_null(), &DataFusionDialect).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_timestamp_and_interval_literals() -> Result<()> {
+        // 2024-01-01T00:00:00.5Z
+        let ts = 1_704_067_200_500_i64;
+        let filter = col("ts").gt(lit(ScalarValue::TimestampMillisecond(Some(ts), None)));
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &DataFusionDialect)?,
+            "(e.ts > CAST('2024-01-01 00:00:00.500' AS TIMESTAMP))"
+        );
+        let filter = col("ts").gt(lit(ScalarValue::TimestampMillisecond(
+            Some(ts),
+            Some("Europe/Berlin".into()),
+        )));
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &DataFusionDialect)?,
+            "(e.ts > CAST('2024-01-01 00:00:00.500+00:00' AS TIMESTAMP WITH TIME ZONE))"
+        );
+        assert_eq!(
+            filter_expr_to_sql("e", &filter, &MySqlDialect)?,
+            "(`e`.`ts` > CAST('2024-01-01 00:00:00.500' AS DATETIME(6)))"
+        );
+
+        let interval = lit(ScalarValue::new_interval_mdn(1, 2, -1_500_000_000));
+        assert_eq!(
+            filter_expr_to_sql("e", &col("d").lt(interval.clone()), &PostgresDialect)?,
+            "(\"e\".\"d\" < INTERVAL '1 months 2 days -1.500000000 seconds')"
+        );
+        assert!(filter_expr_to_sql("e", &col("d").lt(interval), &MySqlDialect).is_err());
+        let interval = lit(ScalarValue::new_interval_dt(3, 0));
+        assert_eq!(
+            filter_expr_to_sql("e", &col("d").lt(interval), &MySqlDialect)?,
+            "(`e`.`d` < INTERVAL 3 DAY)"
+        );
+        Ok(())
+    }
+}
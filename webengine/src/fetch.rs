@@ -0,0 +1,266 @@
+use std::{env, sync::Arc};
+
+use arrow::compute::concat_batches;
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+use arrow_flight::FlightEndpoint;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::memory_pool::{
+    GreedyMemoryPool, MemoryConsumer, MemoryPool, MemoryReservation, UnboundedMemoryPool,
+};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+
+/// How batches from the endpoints of a [WebEntityScan][crate::web_source::WebEntityScan] are
+/// combined.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum FetchStrategy {
+    /// Each endpoint is a separate partition, which DataFusion executes and merges in parallel.
+    #[default]
+    Partitioned,
+    /// A single partition which fetches each endpoint to completion, in order, before starting
+    /// the next. Only one stream is open at a time.
+    Sequential,
+    /// A single partition which fetches all endpoints concurrently and returns batches in the
+    /// order they arrive, so the fastest endpoints produce the first rows.
+    Interleaved,
+}
+
+impl TryFrom<&str> for FetchStrategy {
+    type Error = DataFusionError;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "partitioned" => Ok(Self::Partitioned),
+            "sequential" => Ok(Self::Sequential),
+            "interleaved" => Ok(Self::Interleaved),
+            _ => Err(DataFusionError::Configuration(format!(
+                "Invalid fetch strategy {value}. \
+                Valid values are partitioned, sequential or interleaved"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FetchOptions {
+    pub strategy: FetchStrategy,
+    /// If true, endpoints on the local relay are fetched before remote ones, which are usually
+    /// more expensive to reach.
+    pub local_first: bool,
+    /// If set, the batches decoded from the flight streams are coalesced into batches of at
+    /// least this many rows, which avoids the overhead of many tiny batches from small sources.
+    pub batch_rows: Option<usize>,
+    /// Maximum bytes of batches held while coalescing, shared by every partition of a scan.
+    /// Once exceeded, buffered batches are returned early rather than growing the buffer.
+    pub memory_budget: Option<usize>,
+}
+
+impl FetchOptions {
+    /// Reads the options from DATAWEB_FETCH_STRATEGY (default partitioned),
+    /// DATAWEB_LOCAL_FIRST (default false), DATAWEB_BATCH_ROWS and DATAWEB_MEMORY_BUDGET_BYTES
+    /// (both unset by default).
+    pub fn from_env() -> Result<Self> {
+        let parse_usize = |var: &str| match env::var(var) {
+            Ok(val) => val.parse::<usize>().map(Some).map_err(|e| {
+                DataFusionError::Configuration(format!("Unable to parse {var} as usize! {e}"))
+            }),
+            Err(_) => Ok(None),
+        };
+        let strategy = match env::var("DATAWEB_FETCH_STRATEGY") {
+            Ok(strategy) => FetchStrategy::try_from(strategy.as_str())?,
+            Err(_) => FetchStrategy::default(),
+        };
+        let local_first = match env::var("DATAWEB_LOCAL_FIRST") {
+            Ok(val) => val.parse::<bool>().map_err(|e| {
+                DataFusionError::Configuration(format!(
+                    "Unable to parse DATAWEB_LOCAL_FIRST as bool! {e}"
+                ))
+            })?,
+            Err(_) => false,
+        };
+        Ok(Self {
+            strategy,
+            local_first,
+            batch_rows: parse_usize("DATAWEB_BATCH_ROWS")?,
+            memory_budget: parse_usize("DATAWEB_MEMORY_BUDGET_BYTES")?,
+        })
+    }
+}
+
+fn is_local(endpoint: &FlightEndpoint, local_relay_endpoint: &str) -> bool {
+    let local = local_relay_endpoint.trim_end_matches('/');
+    endpoint
+        .location
+        .first()
+        .is_some_and(|l| l.uri.trim_end_matches('/') == local)
+}
+
+/// Orders endpoints according to the [FetchOptions]. The relative order of endpoints on the
+/// same relay is preserved.
+pub fn order_endpoints(
+    mut endpoints: Vec<FlightEndpoint>,
+    local_relay_endpoint: &str,
+    options: &FetchOptions,
+) -> Vec<FlightEndpoint> {
+    if options.local_first {
+        endpoints.sort_by_key(|e| !is_local(e, local_relay_endpoint));
+    }
+    endpoints
+}
+
+/// Creates the pool which batches held while coalescing are reserved against, which is shared
+/// by every partition of a single scan.
+pub fn budget_pool(options: &FetchOptions) -> Arc<dyn MemoryPool> {
+    match options.memory_budget {
+        Some(budget) => Arc::new(GreedyMemoryPool::new(budget)),
+        None => Arc::new(UnboundedMemoryPool::default()),
+    }
+}
+
+struct CoalesceState {
+    input: BoxStream<'static, Result<RecordBatch>>,
+    buffer: Vec<RecordBatch>,
+    rows: usize,
+    reservation: MemoryReservation,
+    done: bool,
+}
+
+impl CoalesceState {
+    fn flush(&mut self, schema: &SchemaRef) -> Result<RecordBatch> {
+        let batch = concat_batches(schema, &self.buffer)?;
+        self.buffer.clear();
+        self.rows = 0;
+        self.reservation.free();
+        Ok(batch)
+    }
+}
+
+/// Coalesces the batches of a stream into batches of at least batch_rows rows, flushing early
+/// when the batches held would exceed the budget of the pool.
+pub fn coalesce_batches(
+    input: BoxStream<'static, Result<RecordBatch>>,
+    schema: SchemaRef,
+    batch_rows: usize,
+    pool: &Arc<dyn MemoryPool>,
+) -> BoxStream<'static, Result<RecordBatch>> {
+    let state = CoalesceState {
+        input,
+        buffer: vec![],
+        rows: 0,
+        reservation: MemoryConsumer::new("WebEntityScan").register(pool),
+        done: false,
+    };
+    futures::stream::try_unfold(state, move |mut state| {
+        let schema = schema.clone();
+        async move {
+            while !state.done {
+                match state.input.next().await.transpose()? {
+                    Some(batch) => {
+                        let over_budget = state
+                            .reservation
+                            .try_grow(batch.get_array_memory_size())
+                            .is_err();
+                        state.rows += batch.num_rows();
+                        state.buffer.push(batch);
+                        if state.rows >= batch_rows || over_budget {
+                            let batch = state.flush(&schema)?;
+                            return Ok(Some((batch, state)));
+                        }
+                    }
+                    None => state.done = true,
+                }
+            }
+            if state.buffer.is_empty() {
+                return Ok(None);
+            }
+            let batch = state.flush(&schema)?;
+            Ok(Some((batch, state)))
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::Int32Array;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use arrow_flight::{FlightEndpoint, Ticket};
+    use datafusion::common::Result;
+    use futures::{StreamExt, TryStreamExt};
+
+    use super::{budget_pool, coalesce_batches, order_endpoints, FetchOptions, FetchStrategy};
+
+    fn endpoint(location: &str, ticket: &'static str) -> FlightEndpoint {
+        FlightEndpoint::new()
+            .with_ticket(Ticket::new(ticket))
+            .with_location(location)
+    }
+
+    #[test]
+    fn test_order_endpoints() -> Result<()> {
+        let endpoints = vec![
+            endpoint("https://remote:50055", "a"),
+            endpoint("https://localhost:50055/", "b"),
+            endpoint("https://other:50055", "c"),
+            endpoint("https://localhost:50055", "d"),
+        ];
+        let tickets = |endpoints: Vec<FlightEndpoint>| {
+            endpoints
+                .into_iter()
+                .map(|e| e.ticket.unwrap().ticket)
+                .collect::<Vec<_>>()
+        };
+
+        let options = FetchOptions::default();
+        let ordered = order_endpoints(endpoints.clone(), "https://localhost:50055", &options);
+        assert_eq!(tickets(ordered), vec!["a", "b", "c", "d"]);
+
+        let options = FetchOptions {
+            strategy: FetchStrategy::try_from("Sequential")?,
+            local_first: true,
+            ..Default::default()
+        };
+        let ordered = order_endpoints(endpoints, "https://localhost:50055", &options);
+        assert_eq!(tickets(ordered), vec!["b", "d", "a", "c"]);
+        assert!(FetchStrategy::try_from("random").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_batches() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )?;
+        let input = || {
+            futures::stream::iter(vec![batch.clone(); 5])
+                .map(Ok)
+                .boxed()
+        };
+
+        let pool = budget_pool(&FetchOptions::default());
+        let rows = coalesce_batches(input(), schema.clone(), 7, &pool)
+            .map_ok(|b| b.num_rows())
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(rows, vec![9, 6]);
+
+        // A budget smaller than two batches flushes every second batch
+        let pool = budget_pool(&FetchOptions {
+            memory_budget: Some(batch.get_array_memory_size() + 1),
+            ..Default::default()
+        });
+        let rows = coalesce_batches(input(), schema, 100, &pool)
+            .map_ok(|b| b.num_rows())
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(rows, vec![6, 6, 3]);
+        assert_eq!(pool.reserved(), 0);
+        Ok(())
+    }
+}
@@ -1,4 +1,8 @@
+pub mod dialect;
 pub mod expr_to_sql;
+pub mod fetch;
 pub mod register;
+pub mod rest;
+pub mod udf;
 pub mod utils;
 pub mod web_source;
@@ -3,8 +3,12 @@ use std::{io::Read, sync::Arc};
 use datafusion::{assert_batches_eq, common::Result, execution::context::SessionContext};
 use register::register_web_sources;
 
+pub mod dialect;
 pub mod expr_to_sql;
+pub mod fetch;
 pub mod register;
+pub mod rest;
+pub mod udf;
 pub mod utils;
 pub mod web_source;
 
@@ -25,12 +29,15 @@ async fn main() -> Result<()> {
     let local_relay_endpoint = "https://localhost:50055";
 
     let ctx = SessionContext::new();
+    udf::register_declared_functions(&ctx, &udf::load_declarations_from_env()?);
     let _providers = register_web_sources(
         &ctx,
         Arc::new(local_relay_endpoint.to_string()),
         client_cert.clone(),
         client_key.clone(),
         ca_cert.clone(),
+        Arc::new(rest::ScanMode::from_env()?),
+        Arc::new(fetch::FetchOptions::from_env()?),
     )
     .await?;
 
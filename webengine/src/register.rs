@@ -4,7 +4,10 @@ use arrow::datatypes::DataType;
 use arrow_schema::{Field, SchemaBuilder};
 use tracing::debug;
 
-use crate::{utils::get_flight_client, web_source::DataWebEntity};
+use crate::{
+    dialect::DataFusionDialect, fetch::FetchOptions, rest::ScanMode, utils::get_flight_client,
+    web_source::DataWebEntity,
+};
 use bytes::Bytes;
 use datafusion::{
     common::Result, datasource::TableProvider, error::DataFusionError,
@@ -39,6 +42,8 @@ pub async fn register_web_sources(
     client_cert: Arc<Vec<u8>>,
     client_key: Arc<Vec<u8>>,
     ca_cert: Arc<Vec<u8>>,
+    scan_mode: Arc<ScanMode>,
+    fetch_options: Arc<FetchOptions>,
 ) -> Result<Vec<Arc<dyn TableProvider>>> {
     // 1. Connect to local_relay
     let mut client = get_flight_client(
@@ -82,6 +87,9 @@ pub async fn register_web_sources(
             client_cert: client_cert.clone(),
             client_key: client_key.clone(),
             ca_cert: ca_cert.clone(),
+            dialect: Arc::new(DataFusionDialect),
+            scan_mode: scan_mode.clone(),
+            fetch_options: fetch_options.clone(),
         });
         ctx.register_table(&entity, entity_provider.clone())?;
         providers.push(entity_provider);
@@ -0,0 +1,214 @@
+use std::{env, sync::Arc, time::Duration};
+
+use arrow_flight::{FlightEndpoint, Ticket};
+use datafusion::error::{DataFusionError, Result};
+use reqwest::{Certificate, Client, Identity, Response};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::web_source::EntityScanRequest;
+
+/// How a [DataWebEntity][crate::web_source::DataWebEntity] retrieves data from the web.
+#[derive(Debug, Clone, Default)]
+pub enum ScanMode {
+    /// A get_flight_info request is propagated synchronously through the web, and each endpoint
+    /// executes its task when do_get is called.
+    #[default]
+    Sync,
+    /// The query is submitted to the rest_server of the local Relay via /query, and once it is
+    /// complete the stored results are fetched via do_get tickets. This scales better for long
+    /// running queries, as no streams are held open while they execute.
+    Async(AsyncScanOptions),
+}
+
+#[derive(Debug, Clone)]
+pub struct AsyncScanOptions {
+    /// e.g. https://localhost:8443
+    pub rest_endpoint: String,
+    /// How often the status of a submitted query is checked.
+    pub poll_interval: Duration,
+    /// How long to wait for a submitted query to complete before failing the scan.
+    pub timeout: Duration,
+}
+
+impl ScanMode {
+    /// Reads the scan mode from the environment. Scans are asynchronous if DATAWEB_REST_ENDPOINT
+    /// is set, polling every DATAWEB_POLL_INTERVAL_MS (default 500) for at most
+    /// DATAWEB_QUERY_TIMEOUT_SECS (default 3600).
+    pub fn from_env() -> Result<Self> {
+        let rest_endpoint = match env::var("DATAWEB_REST_ENDPOINT") {
+            Ok(endpoint) => endpoint,
+            Err(_) => return Ok(Self::Sync),
+        };
+        let parse = |var: &str, default: u64| match env::var(var) {
+            Ok(val) => val.parse::<u64>().map_err(|e| {
+                DataFusionError::Configuration(format!("Unable to parse {var} as u64! {e}"))
+            }),
+            Err(_) => Ok(default),
+        };
+        Ok(Self::Async(AsyncScanOptions {
+            rest_endpoint,
+            poll_interval: Duration::from_millis(parse("DATAWEB_POLL_INTERVAL_MS", 500)?),
+            timeout: Duration::from_secs(parse("DATAWEB_QUERY_TIMEOUT_SECS", 3600)?),
+        }))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct SubmitQueryResponse {
+    id: Uuid,
+}
+
+#[derive(Deserialize, Debug)]
+struct QueryStatus {
+    message: String,
+    failed: usize,
+    in_progress: usize,
+}
+
+/// A stored result of a query, as listed by the rest_server.
+#[derive(Serialize, Deserialize, Debug)]
+struct StoredResultEndpoint {
+    location: String,
+    /// Opaque to the webengine, and passed back to the Relay as is.
+    ticket: serde_json::Value,
+}
+
+impl TryFrom<StoredResultEndpoint> for FlightEndpoint {
+    type Error = DataFusionError;
+
+    fn try_from(value: StoredResultEndpoint) -> Result<Self> {
+        let ticket = serde_json::to_vec(&value.ticket)
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        Ok(FlightEndpoint::new()
+            .with_ticket(Ticket::new(ticket))
+            .with_location(value.location))
+    }
+}
+
+fn external(e: reqwest::Error) -> DataFusionError {
+    DataFusionError::External(Box::new(e))
+}
+
+/// Builds a client which authenticates with the rest_server using the same certificate as the
+/// flight client.
+pub fn get_rest_client(
+    client_cert: Arc<Vec<u8>>,
+    client_key: Arc<Vec<u8>>,
+    ca_cert: Arc<Vec<u8>>,
+) -> Result<Client> {
+    let identity_pem = [client_key.as_slice(), client_cert.as_slice()].join(&b'\n');
+    Client::builder()
+        .tls_built_in_root_certs(false)
+        .add_root_certificate(Certificate::from_pem(&ca_cert).map_err(external)?)
+        .identity(Identity::from_pem(&identity_pem).map_err(external)?)
+        .build()
+        .map_err(external)
+}
+
+async fn check_response(response: Response) -> Result<Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    Err(DataFusionError::Execution(format!(
+        "Relay returned {status}: {body}"
+    )))
+}
+
+/// Submits the [EntityScanRequest] via /query, waits for every task to complete and returns a
+/// [FlightEndpoint] for each stored result.
+pub async fn execute_async_scan(
+    client: &Client,
+    options: &AsyncScanOptions,
+    entity_scan_request: &EntityScanRequest,
+) -> Result<Vec<FlightEndpoint>> {
+    let endpoint = options.rest_endpoint.trim_end_matches('/');
+    let submitted: SubmitQueryResponse = check_response(
+        client
+            .post(format!("{endpoint}/query"))
+            .json(entity_scan_request)
+            .send()
+            .await
+            .map_err(external)?,
+    )
+    .await?
+    .json()
+    .await
+    .map_err(external)?;
+    let request_id = submitted.id;
+    debug!("Submitted query {request_id}");
+
+    let wait = async {
+        loop {
+            let status: QueryStatus = check_response(
+                client
+                    .get(format!("{endpoint}/query/{request_id}"))
+                    .query(&[("status_only", "true")])
+                    .send()
+                    .await
+                    .map_err(external)?,
+            )
+            .await?
+            .json()
+            .await
+            .map_err(external)?;
+            if status.failed > 0 {
+                return Err(DataFusionError::Execution(status.message));
+            }
+            if status.in_progress == 0 {
+                return Ok(());
+            }
+            debug!(
+                "Waiting on {} tasks of query {request_id}",
+                status.in_progress
+            );
+            tokio::time::sleep(options.poll_interval).await;
+        }
+    };
+    tokio::time::timeout(options.timeout, wait)
+        .await
+        .map_err(|_| {
+            DataFusionError::Execution(format!(
+                "Query {request_id} did not complete within {:?}",
+                options.timeout
+            ))
+        })??;
+
+    let stored: Vec<StoredResultEndpoint> = check_response(
+        client
+            .get(format!("{endpoint}/query/{request_id}/tickets"))
+            .send()
+            .await
+            .map_err(external)?,
+    )
+    .await?
+    .json()
+    .await
+    .map_err(external)?;
+    stored.into_iter().map(FlightEndpoint::try_from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_flight::FlightEndpoint;
+    use datafusion::common::Result;
+
+    use super::StoredResultEndpoint;
+
+    #[test]
+    fn test_stored_result_endpoint() -> Result<()> {
+        let stored: StoredResultEndpoint = serde_json::from_str(
+            r#"{"location": "https://relay:50055", "ticket": {"request_id": "a", "result_id": "b"}}"#,
+        )
+        .unwrap();
+        let endpoint = FlightEndpoint::try_from(stored)?;
+        assert_eq!(endpoint.location[0].uri, "https://relay:50055");
+        let ticket: serde_json::Value =
+            serde_json::from_slice(&endpoint.ticket.unwrap().ticket).unwrap();
+        assert_eq!(ticket["result_id"], "b");
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use std::{env, sync::Arc};
+
+use arrow::datatypes::DataType;
+use datafusion::{
+    common::{not_impl_err, Result},
+    error::DataFusionError,
+    execution::context::SessionContext,
+    logical_expr::{create_udf, Volatility},
+};
+use serde::{Deserialize, Serialize};
+
+/// Signature of a custom scalar function which the relay network is able to evaluate. Mirrors
+/// the declarations the relay loads from its UDF_REGISTRY_FILE, so the same file can be shared.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScalarFunctionDeclaration {
+    pub name: String,
+    pub args: Vec<DataType>,
+    pub return_type: DataType,
+}
+
+/// Loads a JSON list of [ScalarFunctionDeclaration]s from the file named by the
+/// UDF_REGISTRY_FILE environment variable, or an empty list if it is not set.
+pub fn load_declarations_from_env() -> Result<Vec<ScalarFunctionDeclaration>> {
+    match env::var("UDF_REGISTRY_FILE") {
+        Ok(path) => serde_json::from_reader(std::fs::File::open(path)?)
+            .map_err(|e| DataFusionError::External(Box::new(e))),
+        Err(_) => Ok(vec![]),
+    }
+}
+
+/// Registers each declared function with the [SessionContext]. The functions cannot be evaluated
+/// locally, so filters using them must be pushed down to the relay.
+pub fn register_declared_functions(
+    ctx: &SessionContext,
+    declarations: &[ScalarFunctionDeclaration],
+) {
+    for declaration in declarations {
+        let name = declaration.name.clone();
+        ctx.register_udf(create_udf(
+            &declaration.name,
+            declaration.args.clone(),
+            Arc::new(declaration.return_type.clone()),
+            Volatility::Immutable,
+            Arc::new(move |_| {
+                not_impl_err!("Function {name} must be evaluated by the relay network")
+            }),
+        ));
+    }
+}
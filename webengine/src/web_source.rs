@@ -1,6 +1,7 @@
 use std::{any::Any, fmt, sync::Arc};
 
 use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
 use arrow_flight::{FlightDescriptor, FlightEndpoint, FlightInfo, Ticket};
 use arrow_schema::Schema;
 use async_trait::async_trait;
@@ -8,7 +9,7 @@ use datafusion::common::DataFusionError;
 use datafusion::{
     common::Result,
     datasource::TableProvider,
-    execution::{context::SessionState, TaskContext},
+    execution::{context::SessionState, memory_pool::MemoryPool, TaskContext},
     logical_expr::{Expr, TableProviderFilterPushDown, TableType},
     physical_expr::PhysicalSortExpr,
     physical_plan::{
@@ -16,11 +17,15 @@ use datafusion::{
         ExecutionPlan, SendableRecordBatchStream, Statistics,
     },
 };
+use futures::stream::BoxStream;
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, error};
 
+use crate::dialect::{select_sql, SqlWriterDialect};
 use crate::expr_to_sql::filter_expr_to_sql;
+use crate::fetch::{budget_pool, coalesce_batches, order_endpoints, FetchOptions, FetchStrategy};
+use crate::rest::{execute_async_scan, get_rest_client, ScanMode};
 use crate::{
     expr_to_sql::{map_filter_exprs, map_projection},
     utils::get_flight_client,
@@ -58,6 +63,10 @@ pub struct DataWebEntity {
     pub client_key: Arc<Vec<u8>>,
     /// CAcert bundle used to verify other flight servers when making a request as a client
     pub ca_cert: Arc<Vec<u8>>,
+    /// Dialect of the SQL generated for pushed down filters
+    pub dialect: Arc<dyn SqlWriterDialect>,
+    pub scan_mode: Arc<ScanMode>,
+    pub fetch_options: Arc<FetchOptions>,
 }
 
 impl DataWebEntity {
@@ -104,18 +113,12 @@ impl TableProvider for DataWebEntity {
     ) -> Result<Arc<dyn ExecutionPlan>> {
         let projected_schema = project_schema(&self.schema, projection)?;
 
-        let proj_str = map_projection(&self.entity_name, projected_schema.clone());
+        let dialect = self.dialect.as_ref();
+        let proj_str = map_projection(&self.entity_name, projected_schema.clone(), dialect);
 
-        let filter_str = map_filter_exprs(&self.entity_name, filters);
+        let filter_str = map_filter_exprs(&self.entity_name, filters, dialect);
 
-        let template = if let Some(l) = limit {
-            format!(
-                "select {proj_str} from {} {filter_str} limit {l}",
-                self.entity_name
-            )
-        } else {
-            format!("select {proj_str} from {} {filter_str}", self.entity_name)
-        };
+        let template = select_sql(dialect, &proj_str, &self.entity_name, &filter_str, limit);
 
         let entity_scan_req = EntityScanRequest {
             sql: template,
@@ -124,21 +127,39 @@ impl TableProvider for DataWebEntity {
 
         debug!("Created request: {:?}", entity_scan_req);
 
-        // For large (particularly deep) webs this request could be slow, as our request
-        // must propagate to every relay in the web, and back again. If the average latency
-        // of a get_flight_info request is S, the depth of the web is N, we would expect
-        // the total latency to be at least N*S.
-        let info = self.get_flight_info(entity_scan_req).await?;
+        let scan_endpoints = match self.scan_mode.as_ref() {
+            // For large (particularly deep) webs this request could be slow, as our request
+            // must propagate to every relay in the web, and back again. If the average latency
+            // of a get_flight_info request is S, the depth of the web is N, we would expect
+            // the total latency to be at least N*S.
+            ScanMode::Sync => self.get_flight_info(entity_scan_req).await?.endpoint,
+            ScanMode::Async(options) => {
+                let client = get_rest_client(
+                    self.client_cert.clone(),
+                    self.client_key.clone(),
+                    self.ca_cert.clone(),
+                )?;
+                execute_async_scan(&client, options, &entity_scan_req).await?
+            }
+        };
+
+        debug!("Got info from {} sources!", scan_endpoints.len());
 
-        debug!("Got info from {} sources!", info.endpoint.len());
+        let scan_endpoints = order_endpoints(
+            scan_endpoints,
+            self.local_relay_endpoint.as_ref(),
+            self.fetch_options.as_ref(),
+        );
 
         Ok(Arc::new(WebEntityScan {
-            scan_endpoints: info.endpoint,
+            scan_endpoints,
             projected_schema,
             local_relay_endpoint: self.local_relay_endpoint.clone(),
             client_cert: self.client_cert.clone(),
             client_key: self.client_key.clone(),
             ca_cert: self.ca_cert.clone(),
+            budget: budget_pool(&self.fetch_options),
+            fetch_options: self.fetch_options.clone(),
         }))
     }
 
@@ -148,13 +169,15 @@ impl TableProvider for DataWebEntity {
     ) -> Result<Vec<TableProviderFilterPushDown>> {
         Ok(filters
             .iter()
-            .map(|f| match filter_expr_to_sql(&self.entity_name, f) {
-                Ok(_) => TableProviderFilterPushDown::Exact,
-                Err(e) => {
-                    error!("Got unsupported filter expr {e}");
-                    TableProviderFilterPushDown::Unsupported
-                }
-            })
+            .map(
+                |f| match filter_expr_to_sql(&self.entity_name, f, self.dialect.as_ref()) {
+                    Ok(_) => TableProviderFilterPushDown::Exact,
+                    Err(e) => {
+                        error!("Got unsupported filter expr {e}");
+                        TableProviderFilterPushDown::Unsupported
+                    }
+                },
+            )
             .collect())
     }
 }
@@ -172,6 +195,9 @@ pub struct WebEntityScan {
     pub client_key: Arc<Vec<u8>>,
     /// CAcert bundle used to verify other flight servers when making a request as a client
     pub ca_cert: Arc<Vec<u8>>,
+    pub fetch_options: Arc<FetchOptions>,
+    /// Budget for batches held while coalescing, shared by all partitions of the scan
+    pub budget: Arc<dyn MemoryPool>,
 }
 
 impl DisplayAs for WebEntityScan {
@@ -180,48 +206,13 @@ impl DisplayAs for WebEntityScan {
     }
 }
 
-impl ExecutionPlan for WebEntityScan {
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
-
-    fn schema(&self) -> SchemaRef {
-        self.projected_schema.clone()
-    }
-
-    fn output_partitioning(&self) -> datafusion::physical_plan::Partitioning {
-        datafusion::physical_plan::Partitioning::UnknownPartitioning(self.scan_endpoints.len())
-    }
-
-    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
-        None
-    }
-
-    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
-        vec![]
-    }
-
-    fn with_new_children(
-        self: Arc<Self>,
-        _: Vec<Arc<dyn ExecutionPlan>>,
-    ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(self)
-    }
-
-    fn execute(
+impl WebEntityScan {
+    /// Returns a stream of the batches of a single endpoint, which connects to the endpoint
+    /// when first polled.
+    fn endpoint_stream(
         &self,
-        partition: usize,
-        _context: Arc<TaskContext>,
-    ) -> Result<SendableRecordBatchStream> {
-        let endpoint = self
-            .scan_endpoints
-            .get(partition)
-            .ok_or(DataFusionError::Execution(format!(
-                "WebEntityScan has only {} partitions, but called on partition {}!",
-                self.scan_endpoints.len(),
-                partition
-            )))?;
-
+        endpoint: &FlightEndpoint,
+    ) -> Result<BoxStream<'static, Result<RecordBatch>>> {
         let relay_endpoint = endpoint
             .location
             .first()
@@ -282,6 +273,84 @@ impl ExecutionPlan for WebEntityScan {
                 }
             },
         );
+        Ok(stream.boxed())
+    }
+}
+
+impl ExecutionPlan for WebEntityScan {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.projected_schema.clone()
+    }
+
+    fn output_partitioning(&self) -> datafusion::physical_plan::Partitioning {
+        let partitions = match self.fetch_options.strategy {
+            FetchStrategy::Partitioned => self.scan_endpoints.len(),
+            FetchStrategy::Sequential | FetchStrategy::Interleaved => 1,
+        };
+        datafusion::physical_plan::Partitioning::UnknownPartitioning(partitions)
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        None
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(self)
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        _context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let partitions = self.output_partitioning().partition_count();
+        if partition >= partitions {
+            return Err(DataFusionError::Execution(format!(
+                "WebEntityScan has only {partitions} partitions, but called on partition {partition}!"
+            )));
+        }
+
+        let stream = match self.fetch_options.strategy {
+            FetchStrategy::Partitioned => self.endpoint_stream(&self.scan_endpoints[partition])?,
+            FetchStrategy::Sequential => {
+                let streams = self
+                    .scan_endpoints
+                    .iter()
+                    .map(|e| self.endpoint_stream(e))
+                    .collect::<Result<Vec<_>>>()?;
+                // Streams only connect to their endpoint once first polled
+                futures::stream::iter(streams).flatten().boxed()
+            }
+            FetchStrategy::Interleaved => {
+                let streams = self
+                    .scan_endpoints
+                    .iter()
+                    .map(|e| self.endpoint_stream(e))
+                    .collect::<Result<Vec<_>>>()?;
+                futures::stream::select_all(streams).boxed()
+            }
+        };
+
+        let stream = match self.fetch_options.batch_rows {
+            Some(batch_rows) => coalesce_batches(
+                stream,
+                self.projected_schema.clone(),
+                batch_rows,
+                &self.budget,
+            ),
+            None => stream,
+        };
 
         Ok(Box::pin(RecordBatchStreamAdapter::new(
             self.projected_schema.clone(),